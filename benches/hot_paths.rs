@@ -0,0 +1,158 @@
+//! Benchmarks de las rutas más calientes
+//!
+//! - `availability/engine`: motor de disponibilidad en memoria (siempre se ejecuta)
+//! - `http/*`: validación de token, búsqueda de disponibilidad y listado de
+//!   reservas contra un MongoDB real. Solo se ejecutan si `BENCH_MONGODB_URI`
+//!   está definida; usan una base de datos temporal que se elimina al final.
+//!
+//! ```bash
+//! cargo bench
+//! BENCH_MONGODB_URI=mongodb://localhost:27017 cargo bench
+//! ```
+
+use actix_web::{test, App};
+use chrono::{Duration, NaiveDate};
+use criterion::{criterion_group, criterion_main, Criterion};
+use mongodb::bson::oid::ObjectId;
+use pispas_reservation::api::restaurant::validate_access_token;
+use pispas_reservation::availability::{available_tables, CapacidadMesa, Ocupacion};
+use pispas_reservation::clock::{Clock, SystemClock};
+use pispas_reservation::db::MongoRepo;
+use pispas_reservation::notifications::Notifier;
+use pispas_reservation::app_config;
+use serde_json::{json, Value};
+use std::hint::black_box;
+use std::sync::Arc;
+
+/// Número de mesas y reservas sembradas para los benchmarks
+const MESAS: usize = 40;
+const RESERVAS_POR_MESA: usize = 12;
+
+fn bench_engine(c: &mut Criterion) {
+    let inicio = NaiveDate::from_ymd_opt(2030, 6, 15).unwrap().and_hms_opt(12, 0, 0).unwrap();
+
+    let mesas: Vec<CapacidadMesa> = (0..MESAS)
+        .map(|_| CapacidadMesa {
+            id_mesa: ObjectId::new(),
+            reservable: true,
+            min_personas: Some(2),
+            max_personas: Some(6),
+        })
+        .collect();
+
+    let ocupaciones: Vec<Ocupacion> = mesas
+        .iter()
+        .flat_map(|mesa| {
+            (0..RESERVAS_POR_MESA).map(move |i| {
+                Ocupacion::new(mesa.id_mesa, inicio + Duration::minutes(i as i64 * 95), 90)
+            })
+        })
+        .collect();
+
+    c.bench_function("availability/engine", |b| {
+        b.iter(|| {
+            available_tables(
+                black_box(&mesas),
+                black_box(&ocupaciones),
+                inicio + Duration::hours(3),
+                90,
+                4,
+            )
+        })
+    });
+}
+
+fn bench_http(c: &mut Criterion) {
+    let Ok(uri) = std::env::var("BENCH_MONGODB_URI") else {
+        eprintln!("BENCH_MONGODB_URI no definida: se omiten los benchmarks http/*");
+        return;
+    };
+
+    let system = actix_rt::System::new();
+    let database = format!("pispas_bench_{}", uuid::Uuid::new_v4().simple());
+    let repo = system.block_on(async {
+        let repo = MongoRepo::connect(&uri, &database).await.expect("No se pudo conectar a MongoDB");
+        repo.create_indexes().await.expect("No se pudieron crear los índices");
+        repo
+    });
+
+    let app = system.block_on(test::init_service(
+        App::new().configure(app_config(repo.clone(), Notifier::memory(), Arc::new(SystemClock))),
+    ));
+
+    let call = |req: test::TestRequest| {
+        system.block_on(async {
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert!(resp.status().is_success(), "status {}", resp.status());
+            test::read_body_json::<Value, _>(resp).await
+        })
+    };
+
+    // Sembrar un restaurante con mesas y reservas
+    let registro = call(test::TestRequest::post()
+        .uri("/restaurants/register")
+        .set_json(json!({
+            "objid_pispas": "bench",
+            "name": "Bench",
+            "password": "secreto123",
+            "confirmar_automaticamente": false
+        })));
+    let id = registro["id"].as_str().unwrap().to_string();
+    let token = registro["access_token"].as_str().unwrap().to_string();
+    let auth = ("Authorization", format!("Bearer {}", token));
+
+    for m in 0..MESAS {
+        let mesa = call(test::TestRequest::post()
+            .uri("/tables")
+            .insert_header(auth.clone())
+            .set_json(json!({
+                "id_restaurante": id,
+                "tipo": "mesa",
+                "nombre": format!("Mesa {}", m),
+                "pos_x": 0.0, "pos_y": 0.0, "size_x": 80.0, "size_y": 80.0,
+                "forma": "cuadrado",
+                "reservable": true,
+                "min_personas": 2,
+                "max_personas": 6
+            })));
+        let id_mesa = mesa["id"].as_str().unwrap().to_string();
+
+        for r in 0..RESERVAS_POR_MESA {
+            call(test::TestRequest::post()
+                .uri("/reservations")
+                .insert_header(auth.clone())
+                .set_json(json!({
+                    "id_mesa": id_mesa,
+                    "nombre_cliente": "Cliente",
+                    "email_cliente": "cliente@email.com",
+                    "telefono_cliente": "600000000",
+                    "numero_personas": 2,
+                    "fecha": format!("2030-06-{:02}", 1 + r),
+                    "hora": "21:00"
+                })));
+        }
+    }
+
+    c.bench_function("http/validate_access_token", |b| {
+        b.iter(|| system.block_on(validate_access_token(&repo, black_box(&token), SystemClock.timestamp())).unwrap())
+    });
+
+    c.bench_function("http/tables_available", |b| {
+        b.iter(|| call(test::TestRequest::get()
+            .uri("/tables/available?fecha=2030-06-05&hora=21:30&personas=4")
+            .insert_header(auth.clone())))
+    });
+
+    c.bench_function("http/reservations_list", |b| {
+        b.iter(|| call(test::TestRequest::get()
+            .uri("/reservations")
+            .insert_header(auth.clone())))
+    });
+
+    system
+        .block_on(async { repo.database.drop().await })
+        .expect("No se pudo eliminar la base de datos temporal");
+}
+
+criterion_group!(benches, bench_engine, bench_http);
+criterion_main!(benches);
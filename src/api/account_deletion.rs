@@ -0,0 +1,341 @@
+//! # Borrado de cuentas
+//!
+//! Borrar un restaurante no elimina nada en el momento: la cuenta pasa al
+//! estado `pendiente_borrado` durante [`DIAS_GRACIA_BORRADO`] días, en los
+//! que se rechaza cualquier petición de la cuenta, de su personal y de su
+//! widget (ver [`super::account_state`]) y se cierran todas las sesiones del
+//! propietario. Durante ese plazo se puede restaurar tal como estaba:
+//!
+//! - el propietario, con el enlace que recibe por email al borrarla
+//! - la administración, con `POST /admin/restaurants/{id}/restore`
+//!
+//! Al restaurarla vuelve al estado que tenía antes del borrado. Pasado el
+//! plazo, el trabajo [`crate::jobs::account_purge`] borra el restaurante y
+//! todos sus datos.
+//!
+//! ## Endpoints
+//! - `DELETE /restaurants/account` - El propietario borra su cuenta
+//! - `GET /restaurants/restore/{token}` - Restaura la cuenta (enlace del email)
+//! - `DELETE /admin/restaurants/{id}` - La administración borra una cuenta
+//! - `POST /admin/restaurants/{id}/restore` - La administración restaura una cuenta
+
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use mongodb::bson::{self, doc, oid::ObjectId, Document};
+use mongodb::options::ReturnDocument;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use super::{AppError, AppResult};
+use super::account_state;
+use super::admin::require_admin;
+use super::auth::{AuthenticatedRestaurant, PermisoConfiguracion};
+use super::public::public_base_url;
+use super::sessions;
+use crate::clock::Clock;
+use crate::db::{BorradoPendiente, EstadoCuenta, MongoRepo, Restaurant};
+use crate::events::{self, TipoEvento};
+use crate::notifications::{EmailMessage, Notifier};
+
+/// Días durante los que se puede restaurar una cuenta borrada
+pub const DIAS_GRACIA_BORRADO: i64 = 30;
+
+#[derive(Deserialize)]
+struct DeleteAccount {
+    /// Contraseña del propietario, para confirmar el borrado
+    password: String,
+}
+
+/// Deja un restaurante pendiente de borrado
+///
+/// Cierra las sesiones del propietario y le envía por email el enlace para
+/// restaurarlo, si la cuenta tiene email.
+///
+/// # Errores
+/// - `Conflict`: La cuenta ya está pendiente de borrado
+/// - `Database`: Error guardando el borrado
+async fn schedule_deletion(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    restaurant: &Restaurant,
+    por_administracion: bool,
+    now: i64,
+) -> AppResult<BorradoPendiente> {
+    let id = restaurant.id.unwrap();
+    let borrado = BorradoPendiente {
+        solicitado_en: now,
+        purgar_en: now + DIAS_GRACIA_BORRADO * 86_400,
+        estado_anterior: restaurant.estado,
+        por_administracion,
+        token_restauracion: Uuid::new_v4().to_string(),
+    };
+    let valor = bson::to_bson(&borrado)
+        .map_err(|e| AppError::Internal(format!("Error serializando el borrado: {}", e)))?;
+
+    let result = repo.restaurants()
+        .update_one(
+            doc! { "_id": id, "borrado": { "$exists": false } },
+            doc! { "$set": {
+                "estado": EstadoCuenta::PendienteBorrado.as_str(),
+                "borrado": valor,
+            } },
+        )
+        .await
+        .map_err(|e| AppError::database("schedule_deletion", e))?;
+    if result.matched_count == 0 {
+        return Err(AppError::Conflict("La cuenta ya está pendiente de borrado".to_string()));
+    }
+    sessions::revoke_all(repo, id).await?;
+    account_state::forget_all();
+
+    events::record(
+        repo,
+        TipoEvento::EstadoCuentaCambiado,
+        Some(id),
+        Some(id),
+        doc! {
+            "estado_anterior": restaurant.estado.as_str(),
+            "estado": EstadoCuenta::PendienteBorrado.as_str(),
+            "por_administracion": por_administracion,
+        },
+        now,
+    ).await;
+    tracing::info!(id_restaurante = %id, por_administracion, "Cuenta pendiente de borrado");
+
+    if let Some(email) = &restaurant.email {
+        let link = format!("{}/restaurants/restore/{}", public_base_url(), borrado.token_restauracion);
+        let envio = notifier.send_email(EmailMessage {
+            to: email.clone(),
+            subject: format!("La cuenta de {} se borrará en {} días", restaurant.nombre, DIAS_GRACIA_BORRADO),
+            body: format!(
+                "Hola,\n\nLa cuenta de {} y todos sus datos se borrarán definitivamente dentro de {} días.\n\nSi quieres conservarla, ábrela antes desde este enlace:\n{}\n",
+                restaurant.nombre, DIAS_GRACIA_BORRADO, link
+            ),
+            adjuntos: Vec::new(),
+            request_id: None,
+        }).await;
+        if let Err(e) = envio {
+            tracing::error!("Error enviando el enlace de restauración: {}", e);
+        }
+    }
+
+    Ok(borrado)
+}
+
+/// Restaura el restaurante pendiente de borrado que cumple `filtro`
+///
+/// Devuelve el estado al que vuelve la cuenta, o `None` si ningún
+/// restaurante pendiente de borrado cumple el filtro.
+async fn restore(repo: &MongoRepo, mut filtro: Document, now: i64) -> AppResult<Option<(ObjectId, EstadoCuenta)>> {
+    filtro.insert("borrado", doc! { "$exists": true });
+    let anterior = repo.restaurants()
+        .find_one_and_update(filtro, vec![
+            doc! { "$set": { "estado": "$borrado.estado_anterior" } },
+            doc! { "$unset": "borrado" },
+        ])
+        .return_document(ReturnDocument::Before)
+        .await
+        .map_err(|e| AppError::database("restore_restaurant", e))?;
+    let Some((id, borrado)) = anterior.and_then(|restaurant| restaurant.id.zip(restaurant.borrado)) else {
+        return Ok(None);
+    };
+    account_state::forget_all();
+
+    events::record(
+        repo,
+        TipoEvento::EstadoCuentaCambiado,
+        Some(id),
+        Some(id),
+        doc! {
+            "estado_anterior": EstadoCuenta::PendienteBorrado.as_str(),
+            "estado": borrado.estado_anterior.as_str(),
+        },
+        now,
+    ).await;
+    tracing::info!(id_restaurante = %id, "Cuenta restaurada");
+
+    Ok(Some((id, borrado.estado_anterior)))
+}
+
+/// Borra la cuenta del restaurante autenticado
+///
+/// La cuenta queda pendiente de borrado durante 30 días y se cierran todas
+/// las sesiones; el propietario recibe por email un enlace para restaurarla.
+///
+/// # Autenticación
+/// Requiere permiso `Configuracion` (propietario) y su contraseña.
+///
+/// # Cuerpo
+/// ```json
+/// { "password": "secreto123" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id": "507f1f77bcf86cd799439012",
+///   "estado": "pendiente_borrado",
+///   "purgar_en": 1719835200,
+///   "email_enviado": true
+/// }
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido, falta autorización o contraseña
+///   incorrecta
+/// - `409 Conflict`: La cuenta ya está pendiente de borrado
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/restaurants/account")]
+async fn delete_account(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<DeleteAccount>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    if data.password != auth.restaurant.password {
+        return Err(AppError::Unauthorized("Contraseña incorrecta".to_string()));
+    }
+
+    let borrado = schedule_deletion(repo.get_ref(), notifier.get_ref(), &auth.restaurant, false, clock.timestamp()).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": auth.id().to_hex(),
+        "estado": EstadoCuenta::PendienteBorrado,
+        "purgar_en": borrado.purgar_en,
+        "email_enviado": auth.restaurant.email.is_some()
+    })))
+}
+
+/// Restaura una cuenta pendiente de borrado con el enlace del email
+///
+/// El enlace deja de servir al restaurarla. Las sesiones cerradas al borrarla
+/// no se recuperan: el propietario vuelve a iniciar sesión.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Cuenta restaurada",
+///   "id": "507f1f77bcf86cd799439012",
+///   "estado": "activo"
+/// }
+/// ```
+///
+/// # Errores
+/// - `404 Not Found`: Enlace inválido, ya usado o caducado
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/restaurants/restore/{token}")]
+async fn restore_account(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let now = clock.timestamp();
+    let filtro = doc! {
+        "borrado.token_restauracion": path.as_str(),
+        "borrado.purgar_en": { "$gt": now },
+    };
+    let (id, estado) = restore(repo.get_ref(), filtro, now)
+        .await?
+        .ok_or(AppError::NotFound("Enlace de restauración inválido o caducado".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Cuenta restaurada",
+        "id": id.to_hex(),
+        "estado": estado
+    })))
+}
+
+/// Busca un restaurante por el ID de la ruta de administración
+async fn find_admin_restaurant(repo: &MongoRepo, id: &str) -> AppResult<Restaurant> {
+    let oid = ObjectId::parse_str(id)
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
+    repo.restaurants()
+        .find_one(doc! { "_id": oid })
+        .await
+        .map_err(|e| AppError::database("find_restaurant", e))?
+        .ok_or(AppError::not_found_id("Restaurante", id))
+}
+
+/// Borra la cuenta de un restaurante desde la administración
+///
+/// Igual que `DELETE /restaurants/account`: la cuenta queda pendiente de
+/// borrado durante 30 días y el propietario recibe el enlace para
+/// restaurarla.
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// La misma que `DELETE /restaurants/account`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada o el
+///   restaurante no existe
+/// - `409 Conflict`: La cuenta ya está pendiente de borrado
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/admin/restaurants/{id}")]
+async fn admin_delete_restaurant(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+    let restaurant = find_admin_restaurant(repo.get_ref(), path.as_str()).await?;
+
+    let borrado = schedule_deletion(repo.get_ref(), notifier.get_ref(), &restaurant, true, clock.timestamp()).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": path.as_str(),
+        "estado": EstadoCuenta::PendienteBorrado,
+        "purgar_en": borrado.purgar_en,
+        "email_enviado": restaurant.email.is_some()
+    })))
+}
+
+/// Restaura una cuenta pendiente de borrado desde la administración
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// ```json
+/// { "id": "507f1f77bcf86cd799439012", "estado": "activo" }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada o el
+///   restaurante no existe
+/// - `409 Conflict`: La cuenta no está pendiente de borrado
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/admin/restaurants/{id}/restore")]
+async fn admin_restore_restaurant(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+    let restaurant = find_admin_restaurant(repo.get_ref(), path.as_str()).await?;
+
+    let (id, estado) = restore(repo.get_ref(), doc! { "_id": restaurant.id }, clock.timestamp())
+        .await?
+        .ok_or(AppError::Conflict("La cuenta no está pendiente de borrado".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": id.to_hex(),
+        "estado": estado
+    })))
+}
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(delete_account);
+    cfg.service(restore_account);
+    cfg.service(admin_delete_restaurant);
+    cfg.service(admin_restore_restaurant);
+}
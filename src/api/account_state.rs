@@ -0,0 +1,198 @@
+//! # Estado de las cuentas
+//!
+//! Middleware que aplica el estado de la cuenta de cada restaurante
+//! ([`EstadoCuenta`]), que cambia la administración con
+//! `PUT /admin/restaurants/{id}/state`:
+//!
+//! | Estado              | Consultas | Cambios | Código de error            |
+//! |---------------------|:---------:|:-------:|----------------------------|
+//! | `activo`            | ✓         | ✓       |                            |
+//! | `solo_lectura`      | ✓         |         | `cuenta_solo_lectura`      |
+//! | `suspendido`        |           |         | `cuenta_suspendida`        |
+//! | `pendiente_borrado` |           |         | `cuenta_pendiente_borrado` |
+//!
+//! El estado `pendiente_borrado` no se fija con `PUT /admin/restaurants/{id}/state`
+//! sino al borrar la cuenta (ver [`super::account_deletion`]).
+//!
+//! Son consultas las peticiones `GET`, `HEAD` y `OPTIONS`. Las peticiones
+//! rechazadas responden 403 sin llegar al handler, con el código en el campo
+//! `codigo`:
+//!
+//! ```json
+//! { "error": "Prohibido", "codigo": "cuenta_suspendida", "message": "La cuenta del restaurante está suspendida: impagos" }
+//! ```
+//!
+//! ## Restaurante de la petición
+//!
+//! - Con `Authorization: Bearer`, el del token (del propio restaurante o de
+//!   su personal)
+//! - En las rutas del widget `/public/restaurants/{id}/*`, el de la ruta
+//!
+//! Los enlaces de los clientes a reservas ya hechas (`/public/reservations/*`)
+//! no se bloquean, para que puedan consultarlas o cancelarlas. Los logins de
+//! una cuenta suspendida se rechazan en sus handlers con [`ensure_can_login`].
+//!
+//! El estado se guarda en caché unos segundos por token o restaurante; la
+//! administración la vacía con [`forget_all`] al cambiar un estado.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use mongodb::bson::{doc, oid::ObjectId};
+use super::{AppError, AppResult};
+use super::request_log::bearer_token;
+use super::staff::resolve_token;
+use crate::clock::Clock;
+use crate::db::{EstadoCuenta, MongoRepo, Restaurant};
+
+/// Tiempo durante el que se recuerda el estado de un token o restaurante
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Máximo de entradas en caché; al superarlo se vacía
+const CACHE_MAX: usize = 10_000;
+
+/// Estado de la cuenta y motivo del último cambio
+type EstadoConMotivo = (EstadoCuenta, Option<String>);
+
+/// Caché clave → estado, para no consultar MongoDB en cada petición
+fn cache() -> &'static Mutex<HashMap<String, (EstadoConMotivo, Instant)>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, (EstadoConMotivo, Instant)>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Olvida todos los estados en caché
+///
+/// Se llama al cambiar el estado de una cuenta para que se aplique en la
+/// siguiente petición de cualquiera de sus tokens.
+pub fn forget_all() {
+    cache().lock().unwrap().clear();
+}
+
+/// Comprueba si el estado de la cuenta permite una petición con el método dado
+///
+/// # Errores
+/// - `Forbidden` (`cuenta_suspendida`): La cuenta está suspendida
+/// - `Forbidden` (`cuenta_solo_lectura`): La cuenta es de solo lectura y el
+///   método modifica datos
+/// - `Forbidden` (`cuenta_pendiente_borrado`): La cuenta está pendiente de
+///   borrado
+///
+/// ```
+/// use actix_web::http::Method;
+/// use pispas_reservation::api::account_state::check;
+/// use pispas_reservation::db::EstadoCuenta;
+///
+/// assert!(check(EstadoCuenta::SoloLectura, None, &Method::GET).is_ok());
+/// assert!(check(EstadoCuenta::SoloLectura, None, &Method::POST).is_err());
+/// assert!(check(EstadoCuenta::Suspendido, Some("impagos"), &Method::GET).is_err());
+/// assert!(check(EstadoCuenta::PendienteBorrado, None, &Method::GET).is_err());
+/// ```
+pub fn check(estado: EstadoCuenta, motivo: Option<&str>, method: &Method) -> AppResult<()> {
+    let con_motivo = |texto: &str| match motivo {
+        Some(motivo) => format!("{}: {}", texto, motivo),
+        None => texto.to_string(),
+    };
+
+    match estado {
+        EstadoCuenta::Activo => Ok(()),
+        EstadoCuenta::SoloLectura if method.is_safe() => Ok(()),
+        EstadoCuenta::SoloLectura => Err(AppError::forbidden(
+            "cuenta_solo_lectura",
+            &con_motivo("La cuenta del restaurante es de solo lectura"),
+        )),
+        EstadoCuenta::Suspendido => Err(AppError::forbidden(
+            "cuenta_suspendida",
+            &con_motivo("La cuenta del restaurante está suspendida"),
+        )),
+        EstadoCuenta::PendienteBorrado => Err(AppError::forbidden(
+            "cuenta_pendiente_borrado",
+            &con_motivo("La cuenta del restaurante está pendiente de borrado"),
+        )),
+    }
+}
+
+/// Comprueba que una cuenta puede iniciar sesión (no está suspendida ni
+/// pendiente de borrado)
+///
+/// # Errores
+/// - `Forbidden` (`cuenta_suspendida`): La cuenta está suspendida
+/// - `Forbidden` (`cuenta_pendiente_borrado`): La cuenta está pendiente de
+///   borrado
+pub fn ensure_can_login(restaurant: &Restaurant) -> AppResult<()> {
+    check(restaurant.estado, restaurant.motivo_estado.as_deref(), &Method::GET)
+}
+
+/// ID del restaurante en las rutas `/public/restaurants/{id}/*`
+pub(super) fn public_restaurant_id(path: &str) -> Option<ObjectId> {
+    let id = path.strip_prefix("/public/restaurants/")?.split('/').next()?;
+    ObjectId::parse_str(id).ok()
+}
+
+/// Estado de la cuenta a la que va dirigida la petición
+///
+/// `None` si la petición no es de ningún restaurante (sin token, token
+/// inválido o de administración...); los handlers la tratan como siempre.
+async fn request_state(req: &ServiceRequest) -> Option<EstadoConMotivo> {
+    let repo = req.app_data::<web::Data<MongoRepo>>()?;
+    let token = bearer_token(req);
+    let id = public_restaurant_id(req.path());
+    let clave = match (&token, id) {
+        (Some(token), _) => format!("token:{}", token),
+        (None, Some(id)) => format!("restaurante:{}", id),
+        (None, None) => return None,
+    };
+
+    if let Some((estado, desde)) = cache().lock().unwrap().get(&clave) {
+        if desde.elapsed() < CACHE_TTL {
+            return Some(estado.clone());
+        }
+    }
+
+    let restaurant = match token {
+        Some(token) => {
+            let now = req.app_data::<web::Data<dyn Clock>>()?.timestamp();
+            resolve_token(repo.get_ref(), &token, now).await.map(|identidad| Some(identidad.restaurant))
+        }
+        None => repo.restaurants()
+            .find_one(doc! { "_id": id })
+            .await
+            .map_err(|e| AppError::database("find_account_state", e)),
+    };
+    let estado = match restaurant {
+        Ok(Some(restaurant)) => (restaurant.estado, restaurant.motivo_estado),
+        Ok(None) | Err(AppError::Unauthorized(_)) => (EstadoCuenta::Activo, None),
+        Err(e) => {
+            tracing::warn!("No se pudo consultar el estado de la cuenta: {}", e);
+            return None;
+        }
+    };
+
+    let mut cache = cache().lock().unwrap();
+    if cache.len() >= CACHE_MAX {
+        cache.clear();
+    }
+    cache.insert(clave, (estado.clone(), Instant::now()));
+    Some(estado)
+}
+
+/// Middleware que aplica el estado de la cuenta
+///
+/// Responde 403 sin llegar al handler si el estado de la cuenta no permite
+/// la petición (ver [`check`]).
+pub async fn enforce_account_state(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    if let Some((estado, motivo)) = request_state(&req).await {
+        if let Err(error) = check(estado, motivo.as_deref(), req.method()) {
+            return Ok(req.error_response(error));
+        }
+    }
+
+    next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+}
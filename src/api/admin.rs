@@ -0,0 +1,1168 @@
+//! # API de administración
+//!
+//! Operaciones de mantenimiento de la plataforma, ajenas a cualquier
+//! restaurante concreto.
+//!
+//! ## Autenticación
+//!
+//! Requiere `Authorization: Bearer <ADMIN_TOKEN>`, donde `ADMIN_TOKEN` es una
+//! variable de entorno distinta de los tokens de los restaurantes. Si no está
+//! definida, la API de administración responde 404 como si no existiera.
+
+use std::env;
+use std::time::Instant;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
+use chrono::{Duration as ChronoDuration, NaiveDate};
+use futures_util::StreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use super::{AppError, AppResult};
+use super::account_state;
+use super::customer::{learn_preference, link_customer};
+use super::reservation::validate_date;
+use crate::clock::Clock;
+use super::status::ID_ESTADO_PLATAFORMA;
+use crate::availability::FORMATO_FECHA;
+use crate::db::{
+    is_duplicate_key, normalize_name, EstadoCuenta, EstadoPlataforma, EstadoReconstruccion, EstadoReserva, InformeAnonimizacion, MongoRepo,
+    ProgresoColeccion, Reconstruccion, TrabajoReconstruccion as Trabajo,
+};
+use crate::events::{self, TipoEvento};
+use crate::jobs::anonymization::{self, PoliticaRetencion};
+use crate::jobs::rollups;
+use crate::journal;
+
+/// Cada cuántos documentos se registra y se guarda el progreso de un trabajo
+const PROGRESO_CADA: u64 = 500;
+
+/// Días de estadísticas que se recalculan de una vez al reconstruir los contadores
+const DIAS_POR_TRAMO: i64 = 31;
+
+/// Comprueba la credencial de administración de la petición
+///
+/// El token se compara en tiempo constante (ver [`same_token`]).
+///
+/// # Errores
+/// - `NotFound`: `ADMIN_TOKEN` no está configurado
+/// - `Unauthorized`: Falta el token o no coincide
+pub(super) fn require_admin(req: &HttpRequest) -> AppResult<()> {
+    let admin_token = env::var("ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or(AppError::NotFound("Recurso no disponible".to_string()))?;
+
+    let token = req.headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .ok_or(AppError::Unauthorized("Token de administración requerido".to_string()))?;
+
+    if !same_token(token, &admin_token) {
+        return Err(AppError::Unauthorized("Token de administración inválido".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Compara dos tokens sin que el tiempo dependa de dónde difieren
+///
+/// Compara sus resúmenes SHA-256, que tienen siempre la misma longitud,
+/// recorriéndolos enteros en lugar de parar en el primer byte distinto.
+///
+/// ```
+/// use pispas_reservation::api::admin::same_token;
+///
+/// assert!(same_token("secreto", "secreto"));
+/// assert!(!same_token("secreto", "secretO"));
+/// assert!(!same_token("secreto", "secreto-largo"));
+/// ```
+pub fn same_token(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a.as_bytes()), Sha256::digest(b.as_bytes()));
+    let diferencia = a.iter().zip(b.iter()).fold(0u8, |diferencia, (x, y)| diferencia | (x ^ y));
+    std::hint::black_box(diferencia) == 0
+}
+
+#[derive(Deserialize)]
+struct RebuildQuery {
+    what: String,
+}
+
+impl RebuildQuery {
+    fn trabajo(&self) -> AppResult<Trabajo> {
+        match self.what.as_str() {
+            "nombres" => Ok(Trabajo::Nombres),
+            "crm" => Ok(Trabajo::Crm),
+            "counters" => Ok(Trabajo::Counters),
+            "indexes" => Ok(Trabajo::Indexes),
+            "reservas" => Ok(Trabajo::Reservas),
+            otro => Err(AppError::validation_field("what", &format!(
+                "Trabajo '{}' desconocido. Valores válidos: nombres, crm, counters, indexes, reservas", otro
+            ))),
+        }
+    }
+}
+
+/// Progreso de una reconstrucción en curso
+///
+/// Se registra en el log y se guarda en la reconstrucción al empezar cada
+/// colección y cada [`PROGRESO_CADA`] documentos revisados.
+struct Progreso<'a> {
+    repo: &'a MongoRepo,
+    reconstruccion: Reconstruccion,
+    /// Documentos revisados de la colección actual al guardar por última vez
+    guardados: u64,
+}
+
+impl Progreso<'_> {
+    /// Empieza a contar los documentos de otra colección
+    async fn coleccion(&mut self, coleccion: &str) {
+        self.reconstruccion.colecciones.push(ProgresoColeccion {
+            coleccion: coleccion.to_string(),
+            revisados: 0,
+            actualizados: 0,
+        });
+        self.guardados = 0;
+        self.save().await;
+    }
+
+    /// Cuenta un documento revisado de la colección actual
+    async fn revisado(&mut self, actualizado: bool) {
+        self.add(1, u64::from(actualizado)).await;
+    }
+
+    /// Suma documentos revisados y actualizados a la colección actual
+    async fn add(&mut self, revisados: u64, actualizados: u64) {
+        let Some(actual) = self.reconstruccion.colecciones.last_mut() else {
+            return;
+        };
+        actual.revisados += revisados;
+        actual.actualizados += actualizados;
+        if actual.revisados < self.guardados + PROGRESO_CADA {
+            return;
+        }
+
+        tracing::info!(
+            coleccion = %actual.coleccion,
+            revisados = actual.revisados,
+            actualizados = actual.actualizados,
+            "Reconstrucción en curso"
+        );
+        self.guardados = actual.revisados;
+        self.save().await;
+    }
+
+    /// Marca la reconstrucción como terminada o fallida y la guarda
+    async fn finish(&mut self, resultado: &AppResult<()>, now: i64) {
+        let (estado, error) = match resultado {
+            Ok(()) => (EstadoReconstruccion::Terminada, None),
+            Err(e) => (EstadoReconstruccion::Fallida, Some(e.to_string())),
+        };
+        self.reconstruccion.estado = estado;
+        self.reconstruccion.error = error;
+        self.reconstruccion.terminada_en = Some(now);
+        self.save().await;
+    }
+
+    /// Guarda la reconstrucción con su progreso actual
+    ///
+    /// Un fallo al guardarla no detiene el trabajo: solo se registra.
+    async fn save(&self) {
+        let resultado = self.repo.reconstrucciones()
+            .replace_one(doc! { "_id": self.reconstruccion.id }, &self.reconstruccion)
+            .await;
+        if let Err(e) = resultado {
+            tracing::warn!(error = %e, "No se pudo guardar el progreso de la reconstrucción");
+        }
+    }
+}
+
+/// Reconstrucción con los IDs como texto
+#[derive(Serialize)]
+struct ReconstruccionResponse {
+    id: String,
+    trabajo: Trabajo,
+    estado: EstadoReconstruccion,
+    colecciones: Vec<ProgresoColeccion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    iniciada_en: i64,
+    terminada_en: Option<i64>,
+}
+
+impl From<Reconstruccion> for ReconstruccionResponse {
+    fn from(reconstruccion: Reconstruccion) -> Self {
+        ReconstruccionResponse {
+            id: reconstruccion.id.map(|id| id.to_hex()).unwrap_or_default(),
+            trabajo: reconstruccion.trabajo,
+            estado: reconstruccion.estado,
+            colecciones: reconstruccion.colecciones,
+            error: reconstruccion.error,
+            iniciada_en: reconstruccion.iniciada_en,
+            terminada_en: reconstruccion.terminada_en,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct RebuildResponse {
+    #[serde(flatten)]
+    reconstruccion: ReconstruccionResponse,
+    duracion_ms: u64,
+}
+
+fn cursor_error(e: mongodb::error::Error) -> AppError {
+    AppError::Internal(format!("Error iterando cursor: {}", e))
+}
+
+/// Recalcula `nombre_normalizado` de restaurantes y mesas
+///
+/// Solo escribe los documentos cuyo valor guardado no coincide con el
+/// calculado, así que se puede repetir sin coste. Los que chocan con el
+/// nombre normalizado de otro se dejan como están y se avisa en el log
+/// (ver [`MongoRepo::migrate_normalized_names`]).
+async fn rebuild_nombres(repo: &MongoRepo, progreso: &mut Progreso<'_>) -> AppResult<()> {
+    progreso.coleccion("restaurants").await;
+    let mut cursor = repo.restaurants()
+        .find(doc! {})
+        .await
+        .map_err(|e| AppError::database("rebuild_nombres", e))?;
+
+    while cursor.advance().await.map_err(cursor_error)? {
+        let restaurant = cursor.deserialize_current().map_err(cursor_error)?;
+        let normalizado = normalize_name(&restaurant.nombre);
+        let mut actualizado = restaurant.nombre_normalizado != normalizado;
+
+        if actualizado {
+            let resultado = repo.restaurants()
+                .update_one(
+                    doc! { "_id": restaurant.id },
+                    doc! { "$set": { "nombre_normalizado": normalizado } },
+                )
+                .await;
+            match resultado {
+                Ok(_) => {}
+                Err(e) if is_duplicate_key(&e) => {
+                    tracing::warn!(coleccion = "restaurants", id = ?restaurant.id, nombre = %restaurant.nombre, "Nombre duplicado al normalizarlo");
+                    actualizado = false;
+                }
+                Err(e) => return Err(AppError::database("rebuild_nombres", e)),
+            }
+        }
+        progreso.revisado(actualizado).await;
+    }
+
+    progreso.coleccion("mesas").await;
+    let mut cursor = repo.mesas()
+        .find(doc! {})
+        .await
+        .map_err(|e| AppError::database("rebuild_nombres", e))?;
+
+    while cursor.advance().await.map_err(cursor_error)? {
+        let mesa = cursor.deserialize_current().map_err(cursor_error)?;
+        let normalizado = normalize_name(&mesa.nombre);
+        let mut actualizado = mesa.nombre_normalizado != normalizado;
+
+        if actualizado {
+            let resultado = repo.mesas()
+                .update_one(
+                    doc! { "_id": mesa.id },
+                    doc! { "$set": { "nombre_normalizado": normalizado } },
+                )
+                .await;
+            match resultado {
+                Ok(_) => {}
+                Err(e) if is_duplicate_key(&e) => {
+                    tracing::warn!(coleccion = "mesas", id = ?mesa.id, nombre = %mesa.nombre, "Nombre duplicado al normalizarlo");
+                    actualizado = false;
+                }
+                Err(e) => return Err(AppError::database("rebuild_nombres", e)),
+            }
+        }
+        progreso.revisado(actualizado).await;
+    }
+
+    Ok(())
+}
+
+/// Reconstruye el CRM a partir de las reservas
+///
+/// Vincula a su cliente las reservas que no lo tienen (anteriores al CRM o
+/// cuyo registro falló), recalcula las visitas de cada cliente como su
+/// número de reservas no canceladas, y sus no-shows, y vuelve a aprender su
+/// mesa preferida.
+async fn rebuild_crm(repo: &MongoRepo, progreso: &mut Progreso<'_>, now: i64) -> AppResult<()> {
+    progreso.coleccion("reservas").await;
+    let mut cursor = repo.reservas()
+        .find(doc! { "id_cliente": { "$exists": false }, "anonimizada_en": { "$exists": false } })
+        .await
+        .map_err(|e| AppError::database("rebuild_crm", e))?;
+
+    while cursor.advance().await.map_err(cursor_error)? {
+        let reserva = cursor.deserialize_current().map_err(cursor_error)?;
+        let id_cliente = link_customer(
+            repo,
+            reserva.id_restaurante,
+            &reserva.nombre_cliente,
+            &reserva.email_cliente,
+            &reserva.telefono_cliente,
+            false,
+            reserva.created_at,
+        ).await?;
+
+        if let Some(id_cliente) = id_cliente {
+            repo.reservas()
+                .update_one(doc! { "_id": reserva.id }, doc! { "$set": { "id_cliente": id_cliente } })
+                .await
+                .map_err(|e| AppError::database("rebuild_crm", e))?;
+            if let Some(id_reserva) = reserva.id {
+                journal::append(repo, id_reserva, "vincular_cliente", now).await;
+            }
+        }
+        progreso.revisado(id_cliente.is_some()).await;
+    }
+
+    progreso.coleccion("clientes").await;
+    let mut cursor = repo.clientes()
+        .find(doc! {})
+        .await
+        .map_err(|e| AppError::database("rebuild_crm", e))?;
+
+    while cursor.advance().await.map_err(cursor_error)? {
+        let cliente = cursor.deserialize_current().map_err(cursor_error)?;
+        let visitas = repo.reservas()
+            .count_documents(doc! { "id_cliente": cliente.id, "estado": { "$nin": [EstadoReserva::Cancelada, EstadoReserva::NoShow] } })
+            .await
+            .map_err(|e| AppError::database("rebuild_crm", e))?;
+        let no_shows = repo.reservas()
+            .count_documents(doc! { "id_cliente": cliente.id, "estado": EstadoReserva::NoShow })
+            .await
+            .map_err(|e| AppError::database("rebuild_crm", e))?;
+        let desactualizado = u64::from(cliente.visitas) != visitas || u64::from(cliente.no_shows) != no_shows;
+
+        if desactualizado {
+            repo.clientes()
+                .update_one(
+                    doc! { "_id": cliente.id },
+                    doc! { "$set": { "visitas": visitas as i64, "no_shows": no_shows as i64 } },
+                )
+                .await
+                .map_err(|e| AppError::database("rebuild_crm", e))?;
+        }
+        if let Some(id_cliente) = cliente.id {
+            learn_preference(repo, id_cliente).await;
+        }
+        progreso.revisado(desactualizado).await;
+    }
+
+    Ok(())
+}
+
+/// Recalcula las estadísticas diarias de toda la historia
+///
+/// Restaurante a restaurante, desde el día de su primera reserva hasta
+/// ayer en su zona horaria, en tramos de [`DIAS_POR_TRAMO`] días (ver
+/// [`rollups::rollup_restaurant_range`]). Como en el trabajo programado, el
+/// día en curso no se agrega. En el progreso, los revisados son días de
+/// cada restaurante y los actualizados, estadísticas escritas.
+async fn rebuild_counters(repo: &MongoRepo, progreso: &mut Progreso<'_>, clock: &dyn Clock) -> AppResult<()> {
+    progreso.coleccion("stats_daily").await;
+    let mut restaurantes = repo.restaurants()
+        .find(doc! {})
+        .await
+        .map_err(|e| AppError::database("rebuild_counters", e))?;
+
+    while restaurantes.advance().await.map_err(cursor_error)? {
+        let restaurant = restaurantes.deserialize_current().map_err(cursor_error)?;
+        let Some(id_restaurante) = restaurant.id else {
+            continue;
+        };
+        let primera = repo.reservas()
+            .find_one(doc! { "id_restaurante": id_restaurante })
+            .sort(doc! { "fecha": 1 })
+            .await
+            .map_err(|e| AppError::database("rebuild_counters", e))?;
+        let Some(mut desde) = primera.and_then(|reserva| NaiveDate::parse_from_str(&reserva.fecha, FORMATO_FECHA).ok()) else {
+            continue;
+        };
+
+        // Las fechas de las reservas son locales: ayer en la zona del restaurante
+        let hasta = restaurant.configuracion.hora_local(clock.timestamp()).date() - ChronoDuration::days(1);
+        while desde <= hasta {
+            let fin = (desde + ChronoDuration::days(DIAS_POR_TRAMO - 1)).min(hasta);
+            let escritas = rollups::rollup_restaurant_range(repo, id_restaurante, desde, fin, clock.timestamp()).await?;
+            progreso.add((fin - desde).num_days() as u64 + 1, escritas).await;
+            desde = fin + ChronoDuration::days(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Vuelve a proyectar las reservas desde el diario de reservas
+///
+/// Cada reserva con entradas en el diario queda como su última entrada, o
+/// se borra si la última es su borrado (ver [`journal::project`]); las que
+/// no tienen entradas no se tocan.
+async fn rebuild_reservas(repo: &MongoRepo, progreso: &mut Progreso<'_>) -> AppResult<()> {
+    progreso.coleccion("reservas").await;
+    let mut cursor = repo.diario_reservas()
+        .find(doc! {})
+        .sort(doc! { "id_reserva": 1, "secuencia": -1 })
+        .await
+        .map_err(|e| AppError::database("rebuild_reservas", e))?;
+
+    let mut anterior = None;
+    while cursor.advance().await.map_err(cursor_error)? {
+        let entrada = cursor.deserialize_current().map_err(cursor_error)?;
+        // Solo cuenta la última entrada de cada reserva
+        if anterior == Some(entrada.id_reserva) {
+            continue;
+        }
+        anterior = Some(entrada.id_reserva);
+        progreso.revisado(journal::project(repo, &entrada).await?).await;
+    }
+
+    Ok(())
+}
+
+/// Reconstruye datos derivados tras una migración de esquema
+///
+/// # Parámetros de query
+/// - `what`: Trabajo a ejecutar
+///   - `nombres`: recalcula los nombres normalizados de restaurantes y mesas
+///   - `crm`: vincula reservas a clientes y recalcula sus visitas, no-shows y mesas preferidas
+///   - `counters`: recalcula las estadísticas diarias (`stats_daily`) desde
+///     la primera reserva hasta ayer, en la zona horaria de cada restaurante
+///   - `indexes`: vuelve a crear los índices de MongoDB
+///   - `reservas`: vuelve a proyectar las reservas desde el diario de reservas
+///     (solo con `RESERVAS_PERSISTENCIA=eventos`; ver [`crate::journal`]).
+///     Conviene hacerlo con la plataforma en mantenimiento, para que ningún
+///     cambio llegue a la vez
+///
+/// El trabajo se ejecuta de forma síncrona y el resumen se devuelve al
+/// terminar. Mientras tanto, el progreso se registra en el log y se guarda
+/// cada 500 documentos en la reconstrucción, que se puede consultar desde
+/// otra petición con `GET /admin/rebuild/{id}`.
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id": "507f1f77bcf86cd799439011",
+///   "trabajo": "nombres",
+///   "estado": "terminada",
+///   "colecciones": [
+///     { "coleccion": "restaurants", "revisados": 12, "actualizados": 3 },
+///     { "coleccion": "mesas", "revisados": 140, "actualizados": 0 }
+///   ],
+///   "iniciada_en": 1780000000,
+///   "terminada_en": 1780000001,
+///   "duracion_ms": 85
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Trabajo desconocido, o `reservas` sin el diario de
+///   reservas activo
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada
+/// - `500 Internal Server Error`: Error de base de datos (la reconstrucción
+///   queda guardada como `fallida`, con el error)
+#[post("/admin/rebuild")]
+async fn rebuild(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    query: web::Query<RebuildQuery>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+    let trabajo = query.trabajo()?;
+    if trabajo == Trabajo::Reservas && !journal::enabled(repo.get_ref()) {
+        return Err(AppError::validation_field(
+            "what",
+            "El diario de reservas no está activo (RESERVAS_PERSISTENCIA=eventos)",
+        ));
+    }
+    let inicio = Instant::now();
+
+    let mut reconstruccion = Reconstruccion {
+        id: None,
+        trabajo,
+        estado: EstadoReconstruccion::EnCurso,
+        colecciones: Vec::new(),
+        error: None,
+        iniciada_en: clock.timestamp(),
+        terminada_en: None,
+    };
+    let insertada = repo.reconstrucciones()
+        .insert_one(&reconstruccion)
+        .await
+        .map_err(|e| AppError::database("create_rebuild", e))?;
+    reconstruccion.id = insertada.inserted_id.as_object_id();
+
+    tracing::info!(trabajo = ?trabajo, id = ?reconstruccion.id, "Iniciando reconstrucción de datos derivados");
+
+    let mut progreso = Progreso { repo: repo.get_ref(), reconstruccion, guardados: 0 };
+    let resultado = match trabajo {
+        Trabajo::Nombres => rebuild_nombres(repo.get_ref(), &mut progreso).await,
+        Trabajo::Crm => rebuild_crm(repo.get_ref(), &mut progreso, clock.timestamp()).await,
+        Trabajo::Counters => rebuild_counters(repo.get_ref(), &mut progreso, clock.get_ref()).await,
+        Trabajo::Reservas => rebuild_reservas(repo.get_ref(), &mut progreso).await,
+        Trabajo::Indexes => repo.create_indexes().await,
+    };
+    progreso.finish(&resultado, clock.timestamp()).await;
+    resultado?;
+
+    let duracion_ms = inicio.elapsed().as_millis() as u64;
+    tracing::info!(trabajo = ?trabajo, duracion_ms, "Reconstrucción terminada");
+
+    Ok(HttpResponse::Ok().json(RebuildResponse {
+        reconstruccion: progreso.reconstruccion.into(),
+        duracion_ms,
+    }))
+}
+
+/// Consulta una reconstrucción y su progreso
+///
+/// Sirve para seguir desde otra petición una reconstrucción en curso (ver
+/// `POST /admin/rebuild`), o para ver cómo terminó.
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id": "507f1f77bcf86cd799439011",
+///   "trabajo": "crm",
+///   "estado": "en_curso",
+///   "colecciones": [
+///     { "coleccion": "reservas", "revisados": 1500, "actualizados": 12 }
+///   ],
+///   "iniciada_en": 1780000000,
+///   "terminada_en": null
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada, o la
+///   reconstrucción no existe
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/admin/rebuild/{id}")]
+async fn get_rebuild(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+    let id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reconstrucción inválido".to_string()))?;
+
+    let reconstruccion = repo.reconstrucciones()
+        .find_one(doc! { "_id": id })
+        .await
+        .map_err(|e| AppError::database("get_rebuild", e))?
+        .ok_or_else(|| AppError::not_found_id("Reconstrucción", &id.to_hex()))?;
+
+    Ok(HttpResponse::Ok().json(ReconstruccionResponse::from(reconstruccion)))
+}
+
+/// Informe de anonimización con los IDs como texto
+#[derive(Serialize)]
+struct InformeResponse {
+    id: String,
+    fecha_limite: String,
+    retencion_dias: u32,
+    reservas_anonimizadas: u64,
+    por_restaurante: Vec<RecuentoResponse>,
+    reservas_retenidas: u64,
+    violaciones_eliminadas: u64,
+    mensajes_eliminados: u64,
+    created_at: i64,
+}
+
+#[derive(Serialize)]
+struct RecuentoResponse {
+    id_restaurante: String,
+    reservas: u64,
+}
+
+impl From<InformeAnonimizacion> for InformeResponse {
+    fn from(informe: InformeAnonimizacion) -> Self {
+        InformeResponse {
+            id: informe.id.map(|id| id.to_hex()).unwrap_or_default(),
+            fecha_limite: informe.fecha_limite,
+            retencion_dias: informe.retencion_dias,
+            reservas_anonimizadas: informe.reservas_anonimizadas,
+            por_restaurante: informe.por_restaurante
+                .into_iter()
+                .map(|recuento| RecuentoResponse {
+                    id_restaurante: recuento.id_restaurante.to_hex(),
+                    reservas: recuento.reservas,
+                })
+                .collect(),
+            reservas_retenidas: informe.reservas_retenidas,
+            violaciones_eliminadas: informe.violaciones_eliminadas,
+            mensajes_eliminados: informe.mensajes_eliminados,
+            created_at: informe.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AnonymizeQuery {
+    /// Días de retención; por defecto los de `RETENCION_RESERVAS_DIAS`
+    dias: Option<u32>,
+}
+
+/// Ejecuta ahora el trabajo de anonimización de reservas antiguas
+///
+/// Es el mismo trabajo que se programa con `RETENCION_RESERVAS_DIAS` (ver
+/// [`crate::jobs::anonymization`]); el informe resultante queda guardado.
+///
+/// # Parámetros de query
+/// - `dias` (opcional): Días de retención a aplicar en esta ejecución
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id": "507f1f77bcf86cd799439011",
+///   "fecha_limite": "2023-06-01",
+///   "retencion_dias": 730,
+///   "reservas_anonimizadas": 152,
+///   "por_restaurante": [
+///     { "id_restaurante": "507f1f77bcf86cd799439012", "reservas": 152 }
+///   ],
+///   "reservas_retenidas": 2,
+///   "violaciones_eliminadas": 4,
+///   "mensajes_eliminados": 12,
+///   "created_at": 1717243200
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: No se indica `dias` y no hay retención configurada
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/admin/anonymize")]
+async fn anonymize(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    query: web::Query<AnonymizeQuery>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+
+    let dias = query.dias
+        .or_else(|| PoliticaRetencion::from_env().map(|politica| politica.dias))
+        .ok_or(AppError::validation_field(
+            "dias",
+            "No hay retención configurada (RETENCION_RESERVAS_DIAS); indica los días",
+        ))?;
+
+    let informe = anonymization::run(repo.get_ref(), clock.get_ref(), dias).await?;
+    Ok(HttpResponse::Ok().json(InformeResponse::from(informe)))
+}
+
+/// Lista los informes de anonimización, del más reciente al más antiguo
+///
+/// Devuelve como máximo los 50 últimos.
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Errores
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/admin/anonymize/reports")]
+async fn list_anonymization_reports(
+    repo: web::Data<MongoRepo>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+
+    let options = FindOptions::builder()
+        .sort(doc! { "created_at": -1 })
+        .limit(50)
+        .build();
+
+    let mut cursor = repo.informes_anonimizacion()
+        .find(doc! {})
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("list_anonymization_reports", e))?;
+
+    let mut informes = Vec::new();
+    while cursor.advance().await.map_err(cursor_error)? {
+        informes.push(InformeResponse::from(cursor.deserialize_current().map_err(cursor_error)?));
+    }
+
+    Ok(HttpResponse::Ok().json(informes))
+}
+
+/// Diagnóstico de un restaurante (sin credenciales)
+#[derive(Serialize)]
+struct DiagnosticoRestaurante {
+    id: String,
+    nombre: String,
+    objid_pispas: String,
+    confirmar_automaticamente: bool,
+    /// Si tiene email para recuperar la contraseña
+    tiene_email: bool,
+    id_grupo: Option<String>,
+    estado: EstadoCuenta,
+    /// Fecha de la purga de una cuenta pendiente de borrado
+    purgar_en: Option<i64>,
+    mesas: u64,
+    empleados: u64,
+    reservas: u64,
+    created_at: i64,
+}
+
+/// Lista los restaurantes con datos de diagnóstico
+///
+/// Sustituye al antiguo `GET /restaurants/all/debug`: nunca incluye
+/// contraseñas ni tokens de acceso.
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439012",
+///     "nombre": "La Tasca",
+///     "objid_pispas": "PISPAS-001",
+///     "confirmar_automaticamente": false,
+///     "tiene_email": true,
+///     "id_grupo": null,
+///     "estado": "activo",
+///     "purgar_en": null,
+///     "mesas": 14,
+///     "empleados": 3,
+///     "reservas": 820,
+///     "created_at": 1717243200
+///   }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/admin/restaurants")]
+async fn list_restaurants(
+    repo: web::Data<MongoRepo>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+
+    let options = FindOptions::builder().sort(doc! { "created_at": 1 }).build();
+    let mut cursor = repo.restaurants()
+        .find(doc! {})
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("admin_list_restaurants", e))?;
+
+    let mut restaurantes = Vec::new();
+    while cursor.advance().await.map_err(cursor_error)? {
+        let restaurant = cursor.deserialize_current().map_err(cursor_error)?;
+        let filtro = doc! { "id_restaurante": restaurant.id };
+        let contar = |e| AppError::database("admin_count", e);
+
+        restaurantes.push(DiagnosticoRestaurante {
+            id: restaurant.id.map(|id| id.to_hex()).unwrap_or_default(),
+            nombre: restaurant.nombre,
+            objid_pispas: restaurant.objid_pispas,
+            confirmar_automaticamente: restaurant.confirmar_automaticamente,
+            tiene_email: restaurant.email.is_some(),
+            id_grupo: restaurant.id_grupo.map(|id| id.to_hex()),
+            estado: restaurant.estado,
+            purgar_en: restaurant.borrado.map(|borrado| borrado.purgar_en),
+            mesas: repo.mesas().count_documents(filtro.clone()).await.map_err(contar)?,
+            empleados: repo.empleados().count_documents(filtro.clone()).await.map_err(contar)?,
+            reservas: repo.reservas().count_documents(filtro).await.map_err(contar)?,
+            created_at: restaurant.created_at,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(restaurantes))
+}
+
+#[derive(Deserialize)]
+struct CambioEstado {
+    estado: EstadoCuenta,
+    /// Motivo mostrado en los errores de la cuenta (impagos, abuso...)
+    #[serde(default)]
+    motivo: Option<String>,
+}
+
+/// Cambia el estado de la cuenta de un restaurante
+///
+/// Con `suspendido` se rechaza cualquier petición de la cuenta, de su
+/// personal y de su widget; con `solo_lectura`, las que modifican datos (ver
+/// [`super::account_state`]). El cambio se aplica en la siguiente petición.
+///
+/// Las cuentas se borran y restauran con sus propias rutas (ver
+/// [`super::account_deletion`]), no cambiando el estado.
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Cuerpo
+/// ```json
+/// { "estado": "suspendido", "motivo": "Impagos" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id": "507f1f77bcf86cd799439012",
+///   "estado": "suspendido",
+///   "estado_anterior": "activo",
+///   "motivo": "Impagos"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido, estado desconocido o `pendiente_borrado`
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada o el
+///   restaurante no existe
+/// - `409 Conflict`: La cuenta está pendiente de borrado
+/// - `500 Internal Server Error`: Error de base de datos
+#[put("/admin/restaurants/{id}/state")]
+async fn set_restaurant_state(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<CambioEstado>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+    let id = ObjectId::parse_str(path.as_str())
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
+    let motivo = data.motivo.as_deref().map(str::trim).filter(|motivo| !motivo.is_empty());
+    if data.estado == EstadoCuenta::PendienteBorrado {
+        return Err(AppError::Validation(
+            "Para borrar la cuenta usa DELETE /admin/restaurants/{id}".to_string(),
+        ));
+    }
+
+    let anterior = repo.restaurants()
+        .find_one_and_update(
+            doc! { "_id": id, "borrado": { "$exists": false } },
+            doc! { "$set": {
+                "estado": data.estado.as_str(),
+                "motivo_estado": motivo,
+            } },
+        )
+        .await
+        .map_err(|e| AppError::database("set_restaurant_state", e))?;
+    let Some(anterior) = anterior else {
+        let existe = repo.restaurants()
+            .count_documents(doc! { "_id": id })
+            .await
+            .map_err(|e| AppError::database("set_restaurant_state", e))?;
+        return Err(if existe > 0 {
+            AppError::Conflict("La cuenta está pendiente de borrado; restáurala antes".to_string())
+        } else {
+            AppError::not_found_id("Restaurante", path.as_str())
+        });
+    };
+    account_state::forget_all();
+
+    if anterior.estado != data.estado {
+        events::record(
+            repo.get_ref(),
+            TipoEvento::EstadoCuentaCambiado,
+            Some(id),
+            Some(id),
+            doc! {
+                "estado_anterior": anterior.estado.as_str(),
+                "estado": data.estado.as_str(),
+            },
+            clock.timestamp(),
+        ).await;
+    }
+    tracing::info!(id_restaurante = %id, estado = ?data.estado, "Estado de la cuenta cambiado");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": id.to_hex(),
+        "estado": data.estado,
+        "estado_anterior": anterior.estado,
+        "motivo": motivo
+    })))
+}
+
+/// Levanta la retención legal de una reserva
+///
+/// La reserva vuelve a quedar sujeta a la anonimización en la siguiente
+/// ejecución del trabajo (ver [`crate::jobs::anonymization`]).
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// ```json
+/// { "id": "507f1f77bcf86cd799439011", "motivo": "Reclamación del cliente por el depósito" }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada, o la
+///   reserva no existe o no tiene retención legal
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/admin/reservations/{id}/legal-hold")]
+async fn lift_legal_hold(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+    let id = ObjectId::parse_str(path.as_str())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+
+    let reserva = repo.reservas()
+        .find_one_and_update(
+            doc! { "_id": id, "retencion_legal": { "$exists": true } },
+            doc! { "$unset": { "retencion_legal": "" } },
+        )
+        .await
+        .map_err(|e| AppError::database("lift_legal_hold", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada o sin retención legal".to_string()))?;
+    let motivo = reserva.retencion_legal.map(|retencion| retencion.motivo);
+
+    journal::append(repo.get_ref(), id, "levantar_retencion", clock.timestamp()).await;
+    events::record(
+        repo.get_ref(),
+        TipoEvento::RetencionLegalLevantada,
+        Some(reserva.id_restaurante),
+        Some(id),
+        doc! {},
+        clock.timestamp(),
+    ).await;
+    tracing::info!(id_reserva = %id, "Retención legal levantada");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id": id.to_hex(),
+        "motivo": motivo
+    })))
+}
+
+#[derive(Deserialize)]
+struct RollupQuery {
+    /// Primer día a recalcular (YYYY-MM-DD)
+    desde: String,
+    /// Último día a recalcular (YYYY-MM-DD)
+    hasta: String,
+}
+
+/// Recalcula las estadísticas diarias de todos los restaurantes en un rango
+///
+/// Es el mismo cálculo que hace el trabajo programado (ver
+/// [`crate::jobs::rollups`]) para los últimos días; sirve para rellenar el
+/// historial anterior o corregir días concretos.
+///
+/// # Parámetros de query
+/// - `desde`: Primer día (YYYY-MM-DD)
+/// - `hasta`: Último día (YYYY-MM-DD)
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// ```json
+/// { "desde": "2030-01-01", "hasta": "2030-05-31", "estadisticas": 1204 }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida o `desde` posterior a `hasta`
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/admin/stats/rollup")]
+async fn rollup_stats(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    query: web::Query<RollupQuery>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+    let desde = validate_date(&query.desde)?;
+    let hasta = validate_date(&query.hasta)?;
+    if desde > hasta {
+        return Err(AppError::validation_field("desde", "La fecha inicial es posterior a la final"));
+    }
+
+    let estadisticas = rollups::rollup_range(repo.get_ref(), desde, hasta, clock.timestamp()).await?;
+    tracing::info!(%desde, %hasta, estadisticas, "Estadísticas diarias recalculadas a demanda");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "desde": query.desde,
+        "hasta": query.hasta,
+        "estadisticas": estadisticas
+    })))
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    /// Timestamp unix desde el que exportar (incluido)
+    #[serde(default)]
+    desde: i64,
+    /// Timestamp unix hasta el que exportar (excluido)
+    hasta: Option<i64>,
+}
+
+/// Exporta los eventos de dominio como JSON Lines
+///
+/// Devuelve un evento por línea, en orden de creación, sin cargar la
+/// exportación entera en memoria. Para exportaciones incrementales se
+/// puede pasar como `desde` el `created_at` del último evento recibido
+/// (ese evento se vuelve a incluir; se descarta por su `id`).
+///
+/// # Parámetros de query
+/// - `desde` (opcional): Timestamp unix inicial, incluido (por defecto, todos)
+/// - `hasta` (opcional): Timestamp unix final, excluido
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// `Content-Type: application/x-ndjson`, una línea por evento:
+/// ```json
+/// {"id":"507f1f77bcf86cd799439031","tipo":"reserva_cancelada","id_restaurante":"507f1f77bcf86cd799439012","id_entidad":"507f1f77bcf86cd799439011","datos":{"estado_anterior":"confirmada"},"created_at":1717243200}
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/admin/events/export")]
+async fn export_events(
+    repo: web::Data<MongoRepo>,
+    query: web::Query<ExportQuery>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+
+    let mut rango = doc! { "$gte": query.desde };
+    if let Some(hasta) = query.hasta {
+        rango.insert("$lt", hasta);
+    }
+    let options = FindOptions::builder().sort(doc! { "created_at": 1, "_id": 1 }).build();
+    let cursor = repo.eventos()
+        .find(doc! { "created_at": rango })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("export_events", e))?;
+
+    let lineas = cursor.map(|evento| {
+        evento
+            .map(|evento| web::Bytes::from(events::json_line(&evento)))
+            .map_err(cursor_error)
+    });
+
+    Ok(HttpResponse::Ok().content_type("application/x-ndjson").streaming(lineas))
+}
+
+/// Cuerpo de `PUT /admin/status`
+#[derive(Deserialize)]
+struct CambioEstadoPlataforma {
+    mantenimiento: bool,
+    #[serde(default)]
+    incidencia: Option<String>,
+}
+
+/// Pone o quita el aviso de mantenimiento y la incidencia declarada
+///
+/// Solo cambia lo que publica `GET /public/status` (ver [`super::status`]);
+/// la API sigue atendiendo las peticiones con normalidad. Sin `incidencia`
+/// se da por resuelta la que hubiera.
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Cuerpo
+/// ```json
+/// { "mantenimiento": false, "incidencia": "Retrasos en los pagos con tarjeta" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// { "mantenimiento": false, "incidencia": "Retrasos en los pagos con tarjeta", "updated_at": 1780000000 }
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada
+/// - `500 Internal Server Error`: Error de base de datos
+#[put("/admin/status")]
+async fn set_platform_status(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<CambioEstadoPlataforma>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+    let estado = EstadoPlataforma {
+        id: ID_ESTADO_PLATAFORMA.to_string(),
+        mantenimiento: data.mantenimiento,
+        incidencia: data.incidencia.as_deref().map(str::trim).filter(|incidencia| !incidencia.is_empty()).map(String::from),
+        updated_at: clock.timestamp(),
+    };
+
+    repo.estado_plataforma()
+        .replace_one(doc! { "_id": ID_ESTADO_PLATAFORMA }, &estado)
+        .upsert(true)
+        .await
+        .map_err(|e| AppError::database("set_platform_status", e))?;
+    tracing::info!(mantenimiento = estado.mantenimiento, incidencia = ?estado.incidencia, "Estado de la plataforma cambiado");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "mantenimiento": estado.mantenimiento,
+        "incidencia": estado.incidencia,
+        "updated_at": estado.updated_at
+    })))
+}
+
+/// Configura las rutas de administración
+///
+/// # Rutas disponibles
+/// - `POST /admin/rebuild?what=...` - Reconstruir datos derivados
+/// - `GET /admin/rebuild/{id}` - Progreso de una reconstrucción
+/// - `POST /admin/anonymize` - Anonimizar ahora las reservas antiguas
+/// - `GET /admin/anonymize/reports` - Informes de anonimización
+/// - `GET /admin/restaurants` - Diagnóstico de los restaurantes
+/// - `PUT /admin/restaurants/{id}/state` - Suspender o reactivar una cuenta
+/// - `DELETE /admin/reservations/{id}/legal-hold` - Levantar una retención legal
+/// - `POST /admin/stats/rollup` - Recalcular las estadísticas diarias de un rango
+/// - `GET /admin/events/export` - Exportar los eventos de dominio (JSON Lines)
+/// - `PUT /admin/status` - Aviso de mantenimiento e incidencia de la página de estado
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(rebuild);
+    cfg.service(get_rebuild);
+    cfg.service(anonymize);
+    cfg.service(list_anonymization_reports);
+    cfg.service(list_restaurants);
+    cfg.service(set_restaurant_state);
+    cfg.service(lift_legal_hold);
+    cfg.service(rollup_stats);
+    cfg.service(export_events);
+    cfg.service(set_platform_status);
+}
@@ -0,0 +1,255 @@
+//! # Claves de API
+//!
+//! Los integradores (TPV, webs a medida...) usan una clave de API como token
+//! Bearer en lugar de iniciar sesión. El propietario las gestiona:
+//!
+//! - `GET /restaurants/api-keys` - Claves del restaurante
+//! - `POST /restaurants/api-keys` - Crea una clave; el token solo se muestra aquí
+//! - `DELETE /restaurants/api-keys/{id}` - Revoca una clave
+//!
+//! Cada clave actúa con el rol que se le da al crearla (camarero o
+//! encargado, nunca propietario) y con el usuario `api:<nombre>`.
+//!
+//! Una clave puede limitarse además a unos alcances (`reservations:read`,
+//! `tables:write`...): solo podrá llamar a las rutas que exigen alguno de
+//! ellos (ver [`super::auth`]). Sirve, por ejemplo, para la tablet del
+//! mostrador, que solo consulta reservas y marca llegadas.
+//!
+//! Las claves con `sandbox` trabajan en la base de datos de pruebas (ver
+//! [`super::sandbox`]): la misma API, sin tocar las reservas reales.
+
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use super::{AppError, AppResult};
+use super::auth::{AuthenticatedRestaurant, PermisoConfiguracion};
+use super::sandbox::{self, PREFIJO_SANDBOX};
+use crate::clock::Clock;
+use crate::db::{Alcance, ClaveApi, MongoRepo, Rol};
+
+/// Prefijo de los tokens de las claves que trabajan con datos reales
+pub const PREFIJO_PRODUCCION: &str = "live_";
+
+/// Caracteres del token que se muestran al listar las claves
+const LONGITUD_SUFIJO: usize = 4;
+
+/// Estructura para crear una clave de API
+#[derive(Deserialize)]
+struct NewApiKey {
+    nombre: String,
+    /// Rol con el que actúa (por defecto encargado)
+    #[serde(default = "rol_por_defecto")]
+    rol: Rol,
+    /// Rutas a las que puede llamar (por defecto, todas las de su rol)
+    alcances: Option<Vec<Alcance>>,
+    /// Trabajar en la base de datos de pruebas
+    #[serde(default)]
+    sandbox: bool,
+}
+
+fn rol_por_defecto() -> Rol {
+    Rol::Encargado
+}
+
+/// Respuesta para una clave de API
+#[derive(Serialize)]
+struct ApiKeyResponse {
+    id: String,
+    nombre: String,
+    rol: Rol,
+    alcances: Option<Vec<Alcance>>,
+    sandbox: bool,
+    /// Últimos caracteres del token, para reconocerla
+    sufijo: String,
+    /// Token completo, solo al crearla
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    created_at: i64,
+}
+
+impl From<ClaveApi> for ApiKeyResponse {
+    fn from(clave: ClaveApi) -> Self {
+        let inicio = clave.token.len().saturating_sub(LONGITUD_SUFIJO);
+        ApiKeyResponse {
+            id: clave.id.map(|id| id.to_hex()).unwrap_or_default(),
+            nombre: clave.nombre,
+            rol: clave.rol,
+            alcances: clave.alcances,
+            sandbox: clave.sandbox,
+            sufijo: clave.token[inicio..].to_string(),
+            token: None,
+            created_at: clave.created_at,
+        }
+    }
+}
+
+/// Claves de un restaurante guardadas en un repositorio
+async fn load_keys(repo: &MongoRepo, id_restaurante: ObjectId) -> AppResult<Vec<ClaveApi>> {
+    let mut cursor = repo.claves_api()
+        .find(doc! { "id_restaurante": id_restaurante })
+        .with_options(FindOptions::builder().sort(doc! { "created_at": 1 }).build())
+        .await
+        .map_err(|e| AppError::database("list_api_keys", e))?;
+
+    let mut claves = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("list_api_keys", e))? {
+        claves.push(cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando clave de API: {}", e)))?);
+    }
+    Ok(claves)
+}
+
+/// Lista las claves de API del restaurante, las de producción primero
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439011",
+///     "nombre": "TPV",
+///     "rol": "encargado",
+///     "alcances": null,
+///     "sandbox": false,
+///     "sufijo": "9f3a",
+///     "created_at": 1718000000
+///   }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o sin permiso de configuración
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/restaurants/api-keys")]
+async fn list_api_keys(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let mut claves = load_keys(repo.get_ref(), auth.id()).await?;
+    claves.extend(load_keys(&repo.sandbox(), auth.id()).await?);
+
+    let claves: Vec<ApiKeyResponse> = claves.into_iter().map(ApiKeyResponse::from).collect();
+    Ok(HttpResponse::Ok().json(claves))
+}
+
+/// Crea una clave de API
+///
+/// Una clave de pruebas copia antes el restaurante, sus plantas y sus mesas
+/// a la base de datos de pruebas (ver [`sandbox::provision`]).
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Cuerpo
+/// ```json
+/// { "nombre": "Integración web", "rol": "camarero", "sandbox": true }
+/// ```
+///
+/// Con `"alcances": ["reservations:read", "reservations:write"]` la clave
+/// queda limitada a esas rutas.
+///
+/// # Respuesta
+/// La clave con el mismo formato que `GET /restaurants/api-keys` y el
+/// `token` completo, que no se vuelve a mostrar.
+///
+/// # Errores
+/// - `400 Bad Request`: Nombre vacío, rol propietario o lista de alcances vacía
+/// - `401 Unauthorized`: Token inválido o sin permiso de configuración
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/restaurants/api-keys")]
+async fn create_api_key(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<NewApiKey>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let nombre = data.nombre.trim();
+    if nombre.is_empty() {
+        return Err(AppError::validation_field("nombre", "El nombre de la clave es requerido"));
+    }
+    if data.rol == Rol::Propietario {
+        return Err(AppError::validation_field("rol", "Una clave de API no puede actuar como propietario"));
+    }
+    if data.alcances.as_ref().is_some_and(|alcances| alcances.is_empty()) {
+        return Err(AppError::validation_field("alcances", "Indica al menos un alcance"));
+    }
+
+    let destino = if data.sandbox {
+        sandbox::provision(repo.get_ref(), &auth.restaurant).await?;
+        repo.sandbox()
+    } else {
+        repo.get_ref().clone()
+    };
+
+    let prefijo = if data.sandbox { PREFIJO_SANDBOX } else { PREFIJO_PRODUCCION };
+    let mut clave = ClaveApi {
+        id: None,
+        id_restaurante: auth.id(),
+        nombre: nombre.to_string(),
+        token: format!("{}{}", prefijo, Uuid::new_v4().simple()),
+        rol: data.rol,
+        alcances: data.alcances.clone(),
+        sandbox: data.sandbox,
+        created_at: clock.timestamp(),
+    };
+    let result = destino.claves_api()
+        .insert_one(&clave)
+        .await
+        .map_err(|e| AppError::database("create_api_key", e))?;
+    clave.id = result.inserted_id.as_object_id();
+
+    let token = clave.token.clone();
+    Ok(HttpResponse::Ok().json(ApiKeyResponse { token: Some(token), ..ApiKeyResponse::from(clave) }))
+}
+
+/// Revoca una clave de API; su token deja de funcionar
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido
+/// - `401 Unauthorized`: Token inválido o sin permiso de configuración
+/// - `404 Not Found`: Clave no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/restaurants/api-keys/{id}")]
+async fn revoke_api_key(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let id = ObjectId::parse_str(path.as_str())
+        .map_err(|_| AppError::Validation("ID de clave inválido".to_string()))?;
+
+    let filtro = doc! { "_id": id, "id_restaurante": auth.id() };
+    let mut borradas = 0;
+    for destino in [repo.get_ref().clone(), repo.sandbox()] {
+        borradas += destino.claves_api()
+            .delete_one(filtro.clone())
+            .await
+            .map_err(|e| AppError::database("revoke_api_key", e))?
+            .deleted_count;
+    }
+
+    if borradas == 0 {
+        return Err(AppError::NotFound("Clave de API no encontrada".to_string()));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Configura las rutas de claves de API
+///
+/// # Rutas disponibles
+/// - `GET /restaurants/api-keys` - Listar claves
+/// - `POST /restaurants/api-keys` - Crear clave
+/// - `DELETE /restaurants/api-keys/{id}` - Revocar clave
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_api_keys);
+    cfg.service(create_api_key);
+    cfg.service(revoke_api_key);
+}
@@ -0,0 +1,493 @@
+//! # Registro de auditoría
+//!
+//! Cada operación del panel que modifica datos deja una entrada en la
+//! colección `audit_log` con quién la hizo (usuario y rol), cuándo y qué
+//! campos cambiaron, con su valor antes y después:
+//!
+//! | Acción                   | Entidad         | Ruta                                 |
+//! |--------------------------|-----------------|--------------------------------------|
+//! | `crear_reserva`          | `reserva`       | `POST /reservations`                 |
+//! | `confirmar_reserva`      | `reserva`       | `POST /reservations/{id}/confirm`    |
+//! | `cancelar_reserva`       | `reserva`       | `POST /reservations/{id}/cancel`     |
+//! | `sentar_reserva`         | `reserva`       | `POST /reservations/{id}/seat`, `POST /reservations/{id}/checkin` |
+//! | `completar_reserva`      | `reserva`       | `POST /reservations/{id}/complete`   |
+//! | `marcar_no_show`         | `reserva`       | `POST /reservations/{id}/no-show`    |
+//! | `traspasar_reserva`      | `reserva`       | `POST /reservations/{id}/transfer`   |
+//! | `mover_reserva`          | `reserva`       | `POST /reservations/{id}/move`       |
+//! | `retener_reserva`        | `reserva`       | `POST /reservations/{id}/legal-hold` |
+//! | `modificar_reserva`      | `reserva`       | `PUT /reservations/{id}`, `POST /sync/push` |
+//! | `borrar_reserva`         | `reserva`       | `DELETE /reservations/{id}`, `DELETE /reservations/purge` (una por reserva) |
+//! | `cambiar_solicitud_grupo`| `solicitud_grupo` | `PUT /group-requests/{id}`         |
+//! | `crear_mesa`             | `mesa`          | `POST /tables`                       |
+//! | `borrar_mesa`            | `mesa`          | `DELETE /tables/clear` (una por mesa)|
+//! | `restaurar_mesa`         | `mesa`          | `POST /tables/undo-last` (una por mesa) |
+//! | `crear_planta`           | `planta`        | `POST /floors`                       |
+//! | `cambiar_planta`         | `planta`        | `PUT /floors/{id}`                   |
+//! | `borrar_planta`          | `planta`        | `DELETE /floors/{id}`                |
+//! | `crear_distribucion`     | `distribucion`  | `POST /layouts`                      |
+//! | `cambiar_distribucion`   | `distribucion`  | `PUT /layouts/{id}`                  |
+//! | `borrar_distribucion`    | `distribucion`  | `DELETE /layouts/{id}`               |
+//! | `cambiar_configuracion`  | `configuracion` | `PUT /restaurants/settings`          |
+//! | `crear_empleado`         | `empleado`      | `POST /staff`                        |
+//! | `borrar_empleado`        | `empleado`      | `DELETE /staff/{id}`                 |
+//!
+//! Las operaciones hechas con un token de suplantación (ver
+//! [`super::impersonation`]) llevan en `suplantada_por` quién del soporte
+//! las hizo.
+//!
+//! Los cambios se calculan con [`diff`]. Las contraseñas y los tokens no se
+//! guardan nunca, y tampoco los datos de contacto de los clientes: el
+//! registro no pasa por la anonimización (ver [`crate::jobs::anonymization`]).
+//!
+//! El propietario lo consulta con `GET /audit`. Como los eventos de dominio
+//! ([`crate::events`]), registrar una entrada nunca hace fallar la operación.
+
+use actix_web::{get, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use super::{reservation_history, AppError, AppResult};
+use super::auth::{AlcanceRuta, AuthenticatedRestaurant, NivelPermiso, PermisoConfiguracion};
+use crate::db::{CambioCampo, EntradaAuditoria, MongoRepo, Rol};
+
+/// Campos que no cuentan como cambio
+const CAMPOS_IGNORADOS: &[&str] = &["_id", "created_at", "updated_at"];
+
+/// Campos que nunca se guardan, estén donde estén: secretos y datos de
+/// contacto de los clientes
+const CAMPOS_OMITIDOS: &[&str] = &[
+    "password",
+    "access_token",
+    "token",
+    "nombre_cliente",
+    "email_cliente",
+    "telefono_cliente",
+];
+
+/// Entradas devueltas por defecto en `GET /audit`
+const LIMITE_DEFECTO: i64 = 100;
+
+/// Máximo de entradas por consulta
+const LIMITE_MAXIMO: i64 = 500;
+
+/// Operaciones auditadas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccionAuditoria {
+    CrearReserva,
+    ConfirmarReserva,
+    CancelarReserva,
+    SentarReserva,
+    CompletarReserva,
+    MarcarNoShow,
+    TraspasarReserva,
+    MoverReserva,
+    RetenerReserva,
+    ModificarReserva,
+    BorrarReserva,
+    RegistrarPago,
+    ReembolsarPago,
+    CambiarSolicitudGrupo,
+    CrearMesa,
+    BorrarMesa,
+    RestaurarMesa,
+    CrearPlanta,
+    CambiarPlanta,
+    BorrarPlanta,
+    CrearDistribucion,
+    CambiarDistribucion,
+    BorrarDistribucion,
+    CambiarConfiguracion,
+    CrearEmpleado,
+    BorrarEmpleado,
+}
+
+impl AccionAuditoria {
+    /// Nombre con el que se guarda la acción
+    ///
+    /// ```
+    /// use pispas_reservation::api::audit::AccionAuditoria;
+    ///
+    /// assert_eq!(AccionAuditoria::CambiarConfiguracion.as_str(), "cambiar_configuracion");
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccionAuditoria::CrearReserva => "crear_reserva",
+            AccionAuditoria::ConfirmarReserva => "confirmar_reserva",
+            AccionAuditoria::CancelarReserva => "cancelar_reserva",
+            AccionAuditoria::SentarReserva => "sentar_reserva",
+            AccionAuditoria::CompletarReserva => "completar_reserva",
+            AccionAuditoria::MarcarNoShow => "marcar_no_show",
+            AccionAuditoria::TraspasarReserva => "traspasar_reserva",
+            AccionAuditoria::MoverReserva => "mover_reserva",
+            AccionAuditoria::RetenerReserva => "retener_reserva",
+            AccionAuditoria::ModificarReserva => "modificar_reserva",
+            AccionAuditoria::BorrarReserva => "borrar_reserva",
+            AccionAuditoria::RegistrarPago => "registrar_pago",
+            AccionAuditoria::ReembolsarPago => "reembolsar_pago",
+            AccionAuditoria::CambiarSolicitudGrupo => "cambiar_solicitud_grupo",
+            AccionAuditoria::CrearMesa => "crear_mesa",
+            AccionAuditoria::BorrarMesa => "borrar_mesa",
+            AccionAuditoria::RestaurarMesa => "restaurar_mesa",
+            AccionAuditoria::CrearPlanta => "crear_planta",
+            AccionAuditoria::CambiarPlanta => "cambiar_planta",
+            AccionAuditoria::BorrarPlanta => "borrar_planta",
+            AccionAuditoria::CrearDistribucion => "crear_distribucion",
+            AccionAuditoria::CambiarDistribucion => "cambiar_distribucion",
+            AccionAuditoria::BorrarDistribucion => "borrar_distribucion",
+            AccionAuditoria::CambiarConfiguracion => "cambiar_configuracion",
+            AccionAuditoria::CrearEmpleado => "crear_empleado",
+            AccionAuditoria::BorrarEmpleado => "borrar_empleado",
+        }
+    }
+
+    /// Tipo de entidad sobre la que actúa
+    pub fn entidad(&self) -> &'static str {
+        match self {
+            AccionAuditoria::CrearReserva
+            | AccionAuditoria::ConfirmarReserva
+            | AccionAuditoria::CancelarReserva
+            | AccionAuditoria::SentarReserva
+            | AccionAuditoria::CompletarReserva
+            | AccionAuditoria::MarcarNoShow
+            | AccionAuditoria::TraspasarReserva
+            | AccionAuditoria::MoverReserva
+            | AccionAuditoria::RetenerReserva
+            | AccionAuditoria::ModificarReserva
+            | AccionAuditoria::BorrarReserva
+            | AccionAuditoria::RegistrarPago
+            | AccionAuditoria::ReembolsarPago => "reserva",
+            AccionAuditoria::CambiarSolicitudGrupo => "solicitud_grupo",
+            AccionAuditoria::CrearMesa
+            | AccionAuditoria::BorrarMesa
+            | AccionAuditoria::RestaurarMesa => "mesa",
+            AccionAuditoria::CrearPlanta
+            | AccionAuditoria::CambiarPlanta
+            | AccionAuditoria::BorrarPlanta => "planta",
+            AccionAuditoria::CrearDistribucion
+            | AccionAuditoria::CambiarDistribucion
+            | AccionAuditoria::BorrarDistribucion => "distribucion",
+            AccionAuditoria::CambiarConfiguracion => "configuracion",
+            AccionAuditoria::CrearEmpleado | AccionAuditoria::BorrarEmpleado => "empleado",
+        }
+    }
+}
+
+/// Quién hace una operación auditada
+///
+/// Se toma del [`AuthenticatedRestaurant`] al empezar el handler, antes de
+/// que este se quede con el restaurante.
+pub struct Autor {
+    pub id_restaurante: ObjectId,
+    pub usuario: String,
+    pub rol: Rol,
+    /// Quién del soporte actúa, si es una suplantación
+    pub suplantada_por: Option<String>,
+}
+
+impl<P: NivelPermiso, A: AlcanceRuta> From<&AuthenticatedRestaurant<P, A>> for Autor {
+    fn from(auth: &AuthenticatedRestaurant<P, A>) -> Self {
+        Autor {
+            id_restaurante: auth.id(),
+            usuario: auth.usuario.clone(),
+            rol: auth.rol,
+            suplantada_por: auth.suplantada_por.clone(),
+        }
+    }
+}
+
+/// Documento BSON de una entidad para pasar a [`record`]
+///
+/// Devuelve `None` si la entidad no se puede serializar, de forma que la
+/// entrada se registra igualmente sin ese lado del cambio.
+pub(super) fn snapshot<T: Serialize>(entidad: &T) -> Option<Document> {
+    mongodb::bson::to_document(entidad).ok()
+}
+
+/// Aplana un documento a pares `campo.anidado` → valor, sin los campos omitidos
+fn flatten(prefijo: &str, documento: &Document, campos: &mut Vec<(String, Bson)>) {
+    for (clave, valor) in documento {
+        if CAMPOS_OMITIDOS.contains(&clave.as_str()) {
+            continue;
+        }
+        if prefijo.is_empty() && CAMPOS_IGNORADOS.contains(&clave.as_str()) {
+            continue;
+        }
+
+        let campo = if prefijo.is_empty() { clave.clone() } else { format!("{}.{}", prefijo, clave) };
+        match valor {
+            Bson::Document(anidado) => flatten(&campo, anidado, campos),
+            valor => campos.push((campo, valor.clone())),
+        }
+    }
+}
+
+/// Campos que cambian entre dos versiones de una entidad
+///
+/// Sin versión anterior (alta) todos los campos aparecen con `antes` a
+/// `null`, y sin versión posterior (baja) con `despues` a `null`. Los
+/// documentos anidados se comparan campo a campo; los arrays, enteros. No
+/// cuentan `_id`, `created_at` ni `updated_at`, y nunca aparecen
+/// contraseñas, tokens ni datos de contacto de los clientes.
+///
+/// ```
+/// use mongodb::bson::{doc, Bson};
+/// use pispas_reservation::api::audit::diff;
+///
+/// let antes = doc! { "estado": "pendiente", "hora": "21:00", "updated_at": 1 };
+/// let despues = doc! { "estado": "confirmada", "hora": "21:00", "updated_at": 2 };
+/// let cambios = diff(Some(&antes), Some(&despues));
+/// assert_eq!(cambios.len(), 1);
+/// assert_eq!(cambios[0].campo, "estado");
+/// assert_eq!(cambios[0].antes, Bson::from("pendiente"));
+/// assert_eq!(cambios[0].despues, Bson::from("confirmada"));
+///
+/// let configuracion = doc! { "configuracion": { "duracion_reserva_minutos": 90 }, "password": "x" };
+/// let cambios = diff(None, Some(&configuracion));
+/// assert_eq!(cambios.len(), 1);
+/// assert_eq!(cambios[0].campo, "configuracion.duracion_reserva_minutos");
+/// assert_eq!(cambios[0].antes, Bson::Null);
+/// ```
+pub fn diff(antes: Option<&Document>, despues: Option<&Document>) -> Vec<CambioCampo> {
+    let mut campos_antes = Vec::new();
+    let mut campos_despues = Vec::new();
+    if let Some(antes) = antes {
+        flatten("", antes, &mut campos_antes);
+    }
+    if let Some(despues) = despues {
+        flatten("", despues, &mut campos_despues);
+    }
+
+    let valor = |campos: &[(String, Bson)], campo: &str| {
+        campos.iter()
+            .find(|(nombre, _)| nombre == campo)
+            .map(|(_, valor)| valor.clone())
+            .unwrap_or(Bson::Null)
+    };
+
+    let mut cambios = Vec::new();
+    for (campo, anterior) in &campos_antes {
+        let posterior = valor(&campos_despues, campo);
+        if *anterior != posterior {
+            cambios.push(CambioCampo { campo: campo.clone(), antes: anterior.clone(), despues: posterior });
+        }
+    }
+    for (campo, posterior) in &campos_despues {
+        let nuevo = !campos_antes.iter().any(|(nombre, _)| nombre == campo);
+        if nuevo && *posterior != Bson::Null {
+            cambios.push(CambioCampo { campo: campo.clone(), antes: Bson::Null, despues: posterior.clone() });
+        }
+    }
+    cambios
+}
+
+/// Registra una operación en el registro de auditoría
+///
+/// Una modificación que no cambia ningún campo no se registra. Las
+/// operaciones sobre reservas se anotan también en su historial (ver
+/// [`reservation_history`]). Si MongoDB rechaza la escritura, el error queda
+/// en el log.
+///
+/// # Parámetros
+/// - `antes`/`despues`: La entidad antes y después de la operación (ver
+///   [`snapshot`]); `None` en altas y bajas respectivamente
+/// - `now`: Timestamp de la operación
+pub(super) async fn record(
+    repo: &MongoRepo,
+    autor: &Autor,
+    accion: AccionAuditoria,
+    id_entidad: Option<ObjectId>,
+    antes: Option<Document>,
+    despues: Option<Document>,
+    now: i64,
+) {
+    let cambios = diff(antes.as_ref(), despues.as_ref());
+    if cambios.is_empty() && antes.is_some() && despues.is_some() {
+        return;
+    }
+
+    if let (Some(id_reserva), "reserva") = (id_entidad, accion.entidad()) {
+        let evento = reservation_history::from_staff(autor, id_reserva, accion.as_str(), cambios.clone(), now);
+        reservation_history::record(repo, evento).await;
+    }
+
+    let entrada = EntradaAuditoria {
+        id: None,
+        id_restaurante: autor.id_restaurante,
+        usuario: autor.usuario.clone(),
+        rol: autor.rol,
+        suplantada_por: autor.suplantada_por.clone(),
+        accion: accion.as_str().to_string(),
+        entidad: accion.entidad().to_string(),
+        id_entidad,
+        cambios,
+        created_at: now,
+    };
+
+    if let Err(e) = repo.audit_log().insert_one(entrada).await {
+        tracing::error!(accion = accion.as_str(), "Error registrando auditoría: {}", e);
+    }
+}
+
+/// Filtros de `GET /audit`
+#[derive(Deserialize)]
+struct AuditQuery {
+    /// Usuario del personal, o `propietario`
+    usuario: Option<String>,
+    accion: Option<String>,
+    entidad: Option<String>,
+    /// ID de la entidad afectada
+    id_entidad: Option<String>,
+    /// Desde este timestamp (incluido)
+    desde: Option<i64>,
+    /// Hasta este timestamp (incluido)
+    hasta: Option<i64>,
+    /// Máximo de entradas (por defecto 100, como mucho 500)
+    limit: Option<i64>,
+}
+
+/// Cambio de un campo en la respuesta, con los valores en JSON
+#[derive(Serialize)]
+pub(super) struct CambioResponse {
+    campo: String,
+    antes: serde_json::Value,
+    despues: serde_json::Value,
+}
+
+impl From<CambioCampo> for CambioResponse {
+    fn from(cambio: CambioCampo) -> Self {
+        CambioResponse {
+            campo: cambio.campo,
+            antes: cambio.antes.into_relaxed_extjson(),
+            despues: cambio.despues.into_relaxed_extjson(),
+        }
+    }
+}
+
+/// Respuesta para una entrada del registro
+#[derive(Serialize)]
+struct EntradaResponse {
+    id: String,
+    usuario: String,
+    rol: Rol,
+    suplantada_por: Option<String>,
+    accion: String,
+    entidad: String,
+    id_entidad: Option<String>,
+    cambios: Vec<CambioResponse>,
+    created_at: i64,
+}
+
+impl From<EntradaAuditoria> for EntradaResponse {
+    fn from(entrada: EntradaAuditoria) -> Self {
+        EntradaResponse {
+            id: entrada.id.map(|id| id.to_hex()).unwrap_or_default(),
+            usuario: entrada.usuario,
+            rol: entrada.rol,
+            suplantada_por: entrada.suplantada_por,
+            accion: entrada.accion,
+            entidad: entrada.entidad,
+            id_entidad: entrada.id_entidad.map(|id| id.to_hex()),
+            cambios: entrada.cambios.into_iter().map(CambioResponse::from).collect(),
+            created_at: entrada.created_at,
+        }
+    }
+}
+
+/// Lista el registro de auditoría del restaurante, lo más reciente primero
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Query
+/// `usuario`, `accion`, `entidad` e `id_entidad` filtran por igualdad;
+/// `desde` y `hasta` (timestamps unix) acotan la fecha; `limit` (por defecto
+/// 100, como mucho 500) limita el número de entradas.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439011",
+///     "usuario": "ana",
+///     "rol": "camarero",
+///     "suplantada_por": null,
+///     "accion": "confirmar_reserva",
+///     "entidad": "reserva",
+///     "id_entidad": "507f1f77bcf86cd799439012",
+///     "cambios": [{ "campo": "estado", "antes": "pendiente", "despues": "confirmada" }],
+///     "created_at": 1718000000
+///   }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: `id_entidad` o `limit` inválidos
+/// - `401 Unauthorized`: Token inválido o de una cuenta de personal
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/audit")]
+async fn list_audit(
+    repo: web::Data<MongoRepo>,
+    query: web::Query<AuditQuery>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let mut filtro = doc! { "id_restaurante": auth.id() };
+    if let Some(usuario) = &query.usuario {
+        filtro.insert("usuario", usuario);
+    }
+    if let Some(accion) = &query.accion {
+        filtro.insert("accion", accion);
+    }
+    if let Some(entidad) = &query.entidad {
+        filtro.insert("entidad", entidad);
+    }
+    if let Some(id_entidad) = &query.id_entidad {
+        let id_entidad = ObjectId::parse_str(id_entidad)
+            .map_err(|_| AppError::validation_field("id_entidad", "ID de entidad inválido"))?;
+        filtro.insert("id_entidad", id_entidad);
+    }
+    let mut fechas = Document::new();
+    if let Some(desde) = query.desde {
+        fechas.insert("$gte", desde);
+    }
+    if let Some(hasta) = query.hasta {
+        fechas.insert("$lte", hasta);
+    }
+    if !fechas.is_empty() {
+        filtro.insert("created_at", fechas);
+    }
+
+    let limite = query.limit.unwrap_or(LIMITE_DEFECTO);
+    if !(1..=LIMITE_MAXIMO).contains(&limite) {
+        return Err(AppError::validation_field(
+            "limit",
+            &format!("El límite debe estar entre 1 y {}", LIMITE_MAXIMO),
+        ));
+    }
+
+    let options = FindOptions::builder()
+        .sort(doc! { "created_at": -1, "_id": -1 })
+        .limit(limite)
+        .build();
+    let mut cursor = repo.audit_log()
+        .find(filtro)
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("list_audit", e))?;
+
+    let mut entradas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("list_audit", e))? {
+        let entrada = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando entrada de auditoría: {}", e)))?;
+        entradas.push(EntradaResponse::from(entrada));
+    }
+
+    Ok(HttpResponse::Ok().json(entradas))
+}
+
+/// Configura las rutas del registro de auditoría
+///
+/// # Rutas disponibles
+/// - `GET /audit` - Consultar el registro
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_audit);
+}
@@ -0,0 +1,252 @@
+//! # Autenticación de peticiones
+//!
+//! Extractor [`AuthenticatedRestaurant`] que lee el token Bearer, lo valida
+//! contra MongoDB y comprueba el permiso del rol (ver [`super::staff`]) y el
+//! alcance de la ruta antes de que se ejecute el handler:
+//!
+//! ```ignore
+//! #[get("/tables")]
+//! async fn list_tables(
+//!     auth: AuthenticatedRestaurant<PermisoReservas, LeerMesas>,
+//! ) -> AppResult<impl Responder> {
+//!     let restaurante_id = auth.id();
+//!     // ...
+//! }
+//! ```
+//!
+//! Si el token falta, no es válido o su rol no tiene el permiso, la petición
+//! responde con el error correspondiente sin llegar al handler. Con un token
+//! de suplantación, la respuesta lleva además el aviso de
+//! [`super::impersonation`].
+//!
+//! ## Alcances
+//!
+//! Las claves de API con alcances (ver [`super::api_keys`]) solo pueden
+//! llamar a las rutas que declaran uno de ellos; el resto responde 403. Las
+//! rutas sin alcance ([`SinAlcance`], el valor por defecto) quedan cerradas a
+//! esas claves. El alcance no amplía el rol: una clave de camarero con
+//! `tables:write` sigue sin poder tocar el plano.
+
+use std::marker::PhantomData;
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use futures_util::future::LocalBoxFuture;
+use mongodb::bson::oid::ObjectId;
+use super::{AppError, AppResult};
+use super::impersonation::Suplantada;
+use super::staff::{authorize_identity, Identidad, Permiso};
+use crate::clock::Clock;
+use crate::db::{Alcance, MongoRepo, Restaurant, Rol};
+
+/// Extrae el token Bearer del header Authorization
+///
+/// # Parámetros
+/// - `req`: Request HTTP que contiene los headers
+///
+/// # Retorna
+/// El token extraído sin el prefijo "Bearer "
+///
+/// # Errores
+/// - `Unauthorized`: Si falta el header, es inválido o no tiene el formato correcto
+pub fn extract_token(req: &HttpRequest) -> AppResult<String> {
+    let auth_header = req.headers()
+        .get("authorization")
+        .ok_or(AppError::Unauthorized("Falta header Authorization".to_string()))?;
+
+    let auth_str = auth_header
+        .to_str()
+        .map_err(|_| AppError::Unauthorized("Header Authorization inválido".to_string()))?;
+
+    if !auth_str.starts_with("Bearer ") {
+        return Err(AppError::Unauthorized("Formato de token inválido".to_string()));
+    }
+
+    Ok(auth_str[7..].to_string())
+}
+
+/// Permiso exigido por un [`AuthenticatedRestaurant`]
+pub trait NivelPermiso {
+    const PERMISO: Permiso;
+}
+
+/// Exige [`Permiso::Reservas`] (cualquier rol del personal)
+pub struct PermisoReservas;
+
+/// Exige [`Permiso::Gestion`] (encargado o propietario)
+pub struct PermisoGestion;
+
+/// Exige [`Permiso::Configuracion`] (solo el propietario)
+pub struct PermisoConfiguracion;
+
+impl NivelPermiso for PermisoReservas {
+    const PERMISO: Permiso = Permiso::Reservas;
+}
+
+impl NivelPermiso for PermisoGestion {
+    const PERMISO: Permiso = Permiso::Gestion;
+}
+
+impl NivelPermiso for PermisoConfiguracion {
+    const PERMISO: Permiso = Permiso::Configuracion;
+}
+
+/// Alcance exigido por un [`AuthenticatedRestaurant`]
+pub trait AlcanceRuta {
+    /// `None` si la ruta no está abierta a las claves con alcances
+    const ALCANCE: Option<Alcance>;
+}
+
+/// Ruta cerrada a las claves de API con alcances
+pub struct SinAlcance;
+
+/// Exige `reservations:read`
+pub struct LeerReservas;
+
+/// Exige `reservations:write`
+pub struct EscribirReservas;
+
+/// Exige `tables:read`
+pub struct LeerMesas;
+
+/// Exige `tables:write`
+pub struct EscribirMesas;
+
+/// Exige `customers:read`
+pub struct LeerClientes;
+
+/// Exige `customers:write`
+pub struct EscribirClientes;
+
+/// Exige `menu:read`
+pub struct LeerMenu;
+
+/// Exige `menu:write`
+pub struct EscribirMenu;
+
+/// Exige `shifts:read`
+pub struct LeerTurnos;
+
+/// Exige `shifts:write`
+pub struct EscribirTurnos;
+
+/// Exige `settings:read`
+pub struct LeerAjustes;
+
+/// Exige `settings:write`
+pub struct EscribirAjustes;
+
+/// Exige `stats:read`
+pub struct LeerEstadisticas;
+
+impl AlcanceRuta for SinAlcance {
+    const ALCANCE: Option<Alcance> = None;
+}
+
+impl AlcanceRuta for LeerReservas {
+    const ALCANCE: Option<Alcance> = Some(Alcance::LeerReservas);
+}
+
+impl AlcanceRuta for EscribirReservas {
+    const ALCANCE: Option<Alcance> = Some(Alcance::EscribirReservas);
+}
+
+impl AlcanceRuta for LeerMesas {
+    const ALCANCE: Option<Alcance> = Some(Alcance::LeerMesas);
+}
+
+impl AlcanceRuta for EscribirMesas {
+    const ALCANCE: Option<Alcance> = Some(Alcance::EscribirMesas);
+}
+
+impl AlcanceRuta for LeerClientes {
+    const ALCANCE: Option<Alcance> = Some(Alcance::LeerClientes);
+}
+
+impl AlcanceRuta for EscribirClientes {
+    const ALCANCE: Option<Alcance> = Some(Alcance::EscribirClientes);
+}
+
+impl AlcanceRuta for LeerMenu {
+    const ALCANCE: Option<Alcance> = Some(Alcance::LeerMenu);
+}
+
+impl AlcanceRuta for EscribirMenu {
+    const ALCANCE: Option<Alcance> = Some(Alcance::EscribirMenu);
+}
+
+impl AlcanceRuta for LeerTurnos {
+    const ALCANCE: Option<Alcance> = Some(Alcance::LeerTurnos);
+}
+
+impl AlcanceRuta for EscribirTurnos {
+    const ALCANCE: Option<Alcance> = Some(Alcance::EscribirTurnos);
+}
+
+impl AlcanceRuta for LeerAjustes {
+    const ALCANCE: Option<Alcance> = Some(Alcance::LeerAjustes);
+}
+
+impl AlcanceRuta for EscribirAjustes {
+    const ALCANCE: Option<Alcance> = Some(Alcance::EscribirAjustes);
+}
+
+impl AlcanceRuta for LeerEstadisticas {
+    const ALCANCE: Option<Alcance> = Some(Alcance::LeerEstadisticas);
+}
+
+/// Restaurante autenticado con un token que tiene el permiso `P` y el alcance `A`
+///
+/// Por defecto exige [`PermisoGestion`], el mismo nivel que
+/// [`super::restaurant::find_by_token`], y no admite claves con alcances.
+pub struct AuthenticatedRestaurant<P: NivelPermiso = PermisoGestion, A: AlcanceRuta = SinAlcance> {
+    pub restaurant: Restaurant,
+    pub rol: Rol,
+    /// Usuario del personal, o [`super::staff::USUARIO_PROPIETARIO`]
+    pub usuario: String,
+    /// Token con el que se ha autenticado la petición
+    pub token: String,
+    /// Quién del soporte actúa como el propietario (ver [`super::impersonation`])
+    pub suplantada_por: Option<String>,
+    permiso: PhantomData<(P, A)>,
+}
+
+impl<P: NivelPermiso, A: AlcanceRuta> AuthenticatedRestaurant<P, A> {
+    /// ID del restaurante autenticado
+    pub fn id(&self) -> ObjectId {
+        self.restaurant.id.unwrap()
+    }
+
+    fn new(identidad: Identidad, token: String) -> Self {
+        AuthenticatedRestaurant {
+            restaurant: identidad.restaurant,
+            rol: identidad.rol,
+            usuario: identidad.usuario,
+            token,
+            suplantada_por: identidad.suplantada_por,
+            permiso: PhantomData,
+        }
+    }
+}
+
+impl<P: NivelPermiso + 'static, A: AlcanceRuta + 'static> FromRequest for AuthenticatedRestaurant<P, A> {
+    type Error = AppError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = extract_token(req);
+        let repo = req.app_data::<web::Data<MongoRepo>>().cloned();
+        let clock = req.app_data::<web::Data<dyn Clock>>().cloned();
+        let req = req.clone();
+
+        Box::pin(async move {
+            let repo = repo.ok_or(AppError::Internal("MongoRepo no registrado en la aplicación".to_string()))?;
+            let clock = clock.ok_or(AppError::Internal("Clock no registrado en la aplicación".to_string()))?;
+            let token = token?;
+            let identidad = authorize_identity(repo.get_ref(), &token, P::PERMISO, A::ALCANCE, clock.timestamp()).await?;
+            if let Some(admin) = &identidad.suplantada_por {
+                req.extensions_mut().insert(Suplantada(admin.clone()));
+            }
+            Ok(AuthenticatedRestaurant::new(identidad, token))
+        })
+    }
+}
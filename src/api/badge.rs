@@ -0,0 +1,363 @@
+//! # Insignia pública
+//!
+//! Contadores agregados que un restaurante puede publicar en su web
+//! ("2.430 reservas gestionadas"), como JSON o como insignia SVG lista para
+//! incrustar con una etiqueta `<img>`. Cada restaurante elige en su
+//! configuración qué contadores publica (`insignia`, ver
+//! [`crate::db::ConfigInsignia`]); sin ella, las rutas responden `404`
+//! igual que si el restaurante no existiera.
+//!
+//! Para no desvelar la actividad del restaurante:
+//!
+//! - Solo hay totales de toda su historia, nunca por día ni por cliente
+//! - Los totales se redondean hacia abajo a la decena y, por debajo de
+//!   [`MINIMO_PUBLICO`], no se publican (ver [`public_count`])
+//! - La insignia no incluye ningún dato del restaurante, ni su nombre
+//!
+//! Los contadores se calculan como mucho una vez cada [`CACHE_TTL`] por
+//! restaurante y las respuestas se pueden guardar en caché ese mismo tiempo
+//! (`Cache-Control: public`), con un `ETag` para revalidarlas. Al cambiar la
+//! configuración se olvidan con [`forget_restaurant`]. Como el resto de
+//! rutas `/public/restaurants/{id}/*`, no responden para las cuentas
+//! suspendidas y solo se pueden pedir desde los `origenes_widget` del
+//! restaurante (ver [`super::widget_origin`]).
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use super::{AppError, AppResult};
+use super::stats::total;
+use crate::clock::Clock;
+use crate::db::{ContadorInsignia, EstadoReserva, MongoRepo};
+
+/// Tiempo durante el que se reutilizan los contadores de un restaurante
+pub const CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// Máximo de restaurantes en caché; al superarlo se vacía
+const CACHE_MAX: usize = 10_000;
+
+/// Total a partir del que se publica un contador
+pub const MINIMO_PUBLICO: u64 = 100;
+
+/// Contadores publicados de un restaurante
+#[derive(Debug, Clone)]
+struct Insignia {
+    contadores: Vec<(ContadorInsignia, Option<u64>)>,
+    actualizado_en: i64,
+}
+
+/// Caché restaurante → contadores (`None` si no publica ninguno) y cuándo se calcularon
+type Cache = Mutex<HashMap<ObjectId, (Option<Insignia>, Instant)>>;
+
+fn cache() -> &'static Cache {
+    static CACHE: OnceLock<Cache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Olvida los contadores en caché de un restaurante
+///
+/// Se llama al cambiar la configuración para que se aplique en la siguiente
+/// petición.
+pub fn forget_restaurant(id: ObjectId) {
+    cache().lock().unwrap().remove(&id);
+}
+
+/// Valor publicable de un total: redondeado hacia abajo a la decena, o
+/// `None` por debajo de [`MINIMO_PUBLICO`]
+///
+/// ```
+/// use pispas_reservation::api::badge::public_count;
+///
+/// assert_eq!(public_count(2431), Some(2430));
+/// assert_eq!(public_count(100), Some(100));
+/// assert_eq!(public_count(99), None);
+/// ```
+pub fn public_count(total: u64) -> Option<u64> {
+    (total >= MINIMO_PUBLICO).then_some(total - total % 10)
+}
+
+/// Escribe un número con separador de miles
+///
+/// ```
+/// use pispas_reservation::api::badge::format_count;
+///
+/// assert_eq!(format_count(2430), "2.430");
+/// assert_eq!(format_count(1_250_000), "1.250.000");
+/// assert_eq!(format_count(120), "120");
+/// ```
+pub fn format_count(valor: u64) -> String {
+    let digitos = valor.to_string();
+    let mut texto = String::new();
+    for (i, digito) in digitos.chars().enumerate() {
+        if i > 0 && (digitos.len() - i).is_multiple_of(3) {
+            texto.push('.');
+        }
+        texto.push(digito);
+    }
+    texto
+}
+
+/// Texto de un contador en la insignia
+///
+/// ```
+/// use pispas_reservation::api::badge::counter_text;
+/// use pispas_reservation::db::ContadorInsignia;
+///
+/// assert_eq!(counter_text(ContadorInsignia::Reservas, Some(2430)), "2.430 reservas gestionadas");
+/// assert_eq!(counter_text(ContadorInsignia::Comensales, None), "comensales atendidos");
+/// ```
+pub fn counter_text(contador: ContadorInsignia, valor: Option<u64>) -> String {
+    let nombre = match contador {
+        ContadorInsignia::Reservas => "reservas gestionadas",
+        ContadorInsignia::Comensales => "comensales atendidos",
+    };
+    match valor {
+        Some(valor) => format!("{} {}", format_count(valor), nombre),
+        None => nombre.to_string(),
+    }
+}
+
+/// Insignia SVG de dos partes: `etiqueta` sobre gris y `texto` sobre verde
+///
+/// El ancho de cada parte se calcula a partir de su número de caracteres.
+/// Los textos no se escapan: son fijos o números.
+///
+/// ```
+/// use pispas_reservation::api::badge::badge_svg;
+///
+/// let svg = badge_svg("pispas", "2.430 reservas gestionadas");
+/// assert!(svg.starts_with("<svg"));
+/// assert!(svg.contains(">2.430 reservas gestionadas</text>"));
+/// ```
+pub fn badge_svg(etiqueta: &str, texto: &str) -> String {
+    let ancho = |texto: &str| texto.chars().count() * 7 + 12;
+    let (ancho_etiqueta, ancho_texto) = (ancho(etiqueta), ancho(texto));
+    let total = ancho_etiqueta + ancho_texto;
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{total}" height="20" role="img" aria-label="{etiqueta}: {texto}">"#,
+            r#"<title>{etiqueta}: {texto}</title>"#,
+            r##"<rect width="{ancho_etiqueta}" height="20" rx="3" fill="#555"/>"##,
+            r##"<rect x="{ancho_etiqueta}" width="{ancho_texto}" height="20" rx="3" fill="#2e8b57"/>"##,
+            r##"<g fill="#fff" font-family="Verdana,DejaVu Sans,sans-serif" font-size="11" text-anchor="middle">"##,
+            r#"<text x="{centro_etiqueta}" y="14">{etiqueta}</text>"#,
+            r#"<text x="{centro_texto}" y="14">{texto}</text>"#,
+            "</g></svg>",
+        ),
+        total = total,
+        etiqueta = etiqueta,
+        texto = texto,
+        ancho_etiqueta = ancho_etiqueta,
+        ancho_texto = ancho_texto,
+        centro_etiqueta = ancho_etiqueta / 2,
+        centro_texto = ancho_etiqueta + ancho_texto / 2,
+    )
+}
+
+/// Total sin redondear de un contador
+async fn count(repo: &MongoRepo, id_restaurante: ObjectId, contador: ContadorInsignia) -> AppResult<u64> {
+    match contador {
+        ContadorInsignia::Reservas => repo.reservas()
+            .count_documents(doc! {
+                "id_restaurante": id_restaurante,
+                "estado": { "$ne": EstadoReserva::SinConfirmar },
+            })
+            .await
+            .map_err(|e| AppError::database("badge_counter", e)),
+        ContadorInsignia::Comensales => {
+            let mut cursor = repo.reservas()
+                .aggregate(vec![
+                    doc! { "$match": {
+                        "id_restaurante": id_restaurante,
+                        "estado": { "$in": [EstadoReserva::Sentada, EstadoReserva::Completada] },
+                    } },
+                    doc! { "$group": { "_id": null, "comensales": { "$sum": "$numero_personas" } } },
+                ])
+                .await
+                .map_err(|e| AppError::database("badge_counter", e))?;
+            let mut comensales = 0;
+            if cursor.advance().await.map_err(|e| AppError::database("badge_counter", e))? {
+                let documento = cursor.deserialize_current()
+                    .map_err(|e| AppError::Internal(format!("Error deserializando agregado: {}", e)))?;
+                comensales = total(&documento, "comensales");
+            }
+            Ok(comensales)
+        }
+    }
+}
+
+/// Contadores publicados de un restaurante, de la caché o recién calculados
+///
+/// # Errores
+/// - `NotFound`: El restaurante no existe o no publica contadores
+async fn load(repo: &MongoRepo, id_restaurante: ObjectId, now: i64) -> AppResult<Insignia> {
+    let en_cache = cache().lock().unwrap()
+        .get(&id_restaurante)
+        .filter(|(_, desde)| desde.elapsed() < CACHE_TTL)
+        .map(|(insignia, _)| insignia.clone());
+    let insignia = match en_cache {
+        Some(insignia) => insignia,
+        None => {
+            let restaurant = repo.restaurants()
+                .find_one(doc! { "_id": id_restaurante })
+                .await
+                .map_err(|e| AppError::database("load_badge", e))?;
+            let insignia = match restaurant.and_then(|restaurant| restaurant.configuracion.insignia) {
+                Some(configuracion) => {
+                    let mut contadores = Vec::new();
+                    for contador in configuracion.contadores {
+                        let total = count(repo, id_restaurante, contador).await?;
+                        contadores.push((contador, public_count(total)));
+                    }
+                    Some(Insignia { contadores, actualizado_en: now })
+                }
+                None => None,
+            };
+
+            let mut cache = cache().lock().unwrap();
+            if cache.len() >= CACHE_MAX {
+                cache.clear();
+            }
+            cache.insert(id_restaurante, (insignia.clone(), Instant::now()));
+            insignia
+        }
+    };
+
+    insignia.ok_or(AppError::NotFound("Restaurante no encontrado".to_string()))
+}
+
+/// Responde `cuerpo` con las cabeceras de caché, o `304` si el cliente ya lo tiene
+fn cached_response(req: &HttpRequest, content_type: &str, cuerpo: String) -> HttpResponse {
+    let etag = format!("\"{}\"", &hex::encode(Sha256::digest(cuerpo.as_bytes()))[..16]);
+    let revalidado = req.headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|valor| valor.to_str().ok())
+        .is_some_and(|valor| valor.split(',').any(|etiqueta| etiqueta.trim() == etag));
+    let mut res = if revalidado { HttpResponse::NotModified() } else { HttpResponse::Ok() };
+    res.insert_header((header::ETAG, etag.as_str()))
+        .insert_header((header::CACHE_CONTROL, format!("public, max-age={}", CACHE_TTL.as_secs())));
+    if revalidado {
+        return res.finish();
+    }
+    res.content_type(content_type).body(cuerpo)
+}
+
+/// ID del restaurante de la ruta
+fn restaurant_id(path: web::Path<String>) -> AppResult<ObjectId> {
+    ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))
+}
+
+/// Contador publicado en la respuesta
+#[derive(Serialize)]
+struct ContadorResponse {
+    contador: ContadorInsignia,
+    /// `null` por debajo del mínimo publicable
+    valor: Option<u64>,
+    texto: String,
+}
+
+/// Contadores publicados de un restaurante
+///
+/// En el orden de su configuración. Los totales están redondeados hacia
+/// abajo a la decena; por debajo del mínimo publicable, `valor` es `null`.
+///
+/// # Autenticación
+/// Ninguna.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "contadores": [
+///     { "contador": "reservas", "valor": 2430, "texto": "2.430 reservas gestionadas" },
+///     { "contador": "comensales", "valor": null, "texto": "comensales atendidos" }
+///   ],
+///   "actualizado_en": 1780000000
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de restaurante inválido
+/// - `404 Not Found`: Restaurante no encontrado o sin contadores publicados
+/// - `429 Too Many Requests`: Límite de peticiones públicas superado
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/public/restaurants/{id}/badge")]
+async fn badge_counters(
+    req: HttpRequest,
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let insignia = load(repo.get_ref(), restaurant_id(path)?, clock.timestamp()).await?;
+
+    let contadores: Vec<ContadorResponse> = insignia.contadores.iter()
+        .map(|&(contador, valor)| ContadorResponse { contador, valor, texto: counter_text(contador, valor) })
+        .collect();
+    let cuerpo = serde_json::json!({
+        "contadores": contadores,
+        "actualizado_en": insignia.actualizado_en,
+    });
+
+    Ok(cached_response(&req, "application/json", cuerpo.to_string()))
+}
+
+/// Parámetros de la insignia SVG
+#[derive(Deserialize)]
+struct BadgeQuery {
+    /// Contador que se muestra; por defecto, el primero de la configuración
+    contador: Option<ContadorInsignia>,
+}
+
+/// Insignia SVG con un contador publicado de un restaurante
+///
+/// Para incrustarla en la web del restaurante:
+///
+/// ```html
+/// <img src="https://api.pispas.es/public/restaurants/507f1f77bcf86cd799439011/badge.svg?contador=reservas" alt="Reservas gestionadas">
+/// ```
+///
+/// Por debajo del mínimo publicable muestra el nombre del contador sin
+/// cifra.
+///
+/// # Autenticación
+/// Ninguna.
+///
+/// # Parámetros
+/// - `contador` (opcional): `reservas` o `comensales`
+///
+/// # Errores
+/// - `400 Bad Request`: ID de restaurante o contador inválido
+/// - `404 Not Found`: Restaurante no encontrado o sin ese contador publicado
+/// - `429 Too Many Requests`: Límite de peticiones públicas superado
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/public/restaurants/{id}/badge.svg")]
+async fn badge_svg_image(
+    req: HttpRequest,
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    query: web::Query<BadgeQuery>,
+) -> AppResult<impl Responder> {
+    let insignia = load(repo.get_ref(), restaurant_id(path)?, clock.timestamp()).await?;
+
+    let &(contador, valor) = insignia.contadores.iter()
+        .find(|(contador, _)| query.contador.is_none_or(|pedido| pedido == *contador))
+        .ok_or(AppError::NotFound("Contador no publicado".to_string()))?;
+
+    Ok(cached_response(&req, "image/svg+xml", badge_svg("pispas", &counter_text(contador, valor))))
+}
+
+/// Configura las rutas de la insignia pública
+///
+/// # Rutas disponibles
+/// - `GET /public/restaurants/{id}/badge` - Contadores publicados
+/// - `GET /public/restaurants/{id}/badge.svg` - Insignia SVG con un contador
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(badge_counters);
+    cfg.service(badge_svg_image);
+}
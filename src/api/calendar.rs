@@ -0,0 +1,295 @@
+//! # Calendario de reservas
+//!
+//! Las reservas en formato iCalendar (ver [`crate::calendar`]):
+//!
+//! - El email de confirmación de las reservas del widget lleva la reserva
+//!   adjunta (`reserva.ics`, ver [`ics_attachment`]), para que el cliente
+//!   la añada a su calendario
+//! - `GET /public/reservations/{token}/ics` - La misma reserva desde su
+//!   enlace de gestión, para añadirla desde el móvil con un toque
+//! - `GET /reservations/calendar.ics` - Calendario con las próximas
+//!   reservas del restaurante (de hoy al horizonte del listado,
+//!   `horizonte_listado_dias`), para suscribirse desde Google Calendar u
+//!   Outlook
+//! - `POST /restaurants/calendar-token` - Crea o rota el token del
+//!   calendario y devuelve la URL de suscripción
+//! - `DELETE /restaurants/calendar-token` - Revoca el token
+//!
+//! Los clientes de calendario no envían cabeceras, así que el calendario
+//! acepta, además del token Bearer, el token secreto del restaurante en la
+//! URL (`?token=`). Rotarlo invalida las suscripciones anteriores. Las
+//! reservas canceladas siguen en el calendario como `STATUS:CANCELLED`,
+//! para que los calendarios suscritos las retiren.
+//!
+//! Los eventos llevan la zona horaria (`zona_horaria`) y la dirección
+//! (`direccion`) de la configuración del restaurante; los del cliente,
+//! además, el enlace para consultar y cancelar su reserva.
+
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{delete, get, post, web, FromRequest, HttpRequest, HttpResponse, Responder};
+use chrono::Duration;
+use mongodb::bson::doc;
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use super::{AppError, AppResult};
+use super::account_state::ensure_can_login;
+use super::auth::{AuthenticatedRestaurant, LeerReservas, PermisoConfiguracion, PermisoReservas};
+use super::public::{find_by_token, find_restaurant, public_base_url};
+use crate::availability::parse_inicio;
+use crate::calendar::{self, EventoCalendario};
+use crate::clock::Clock;
+use crate::db::{EstadoReserva, MongoRepo, Reserva, Restaurant};
+use crate::notifications::Adjunto;
+
+/// Parámetros del calendario
+#[derive(Deserialize)]
+struct CalendarQuery {
+    /// Token secreto del calendario, en lugar de la cabecera `Authorization`
+    token: Option<String>,
+}
+
+/// Respuesta con el token del calendario
+#[derive(Serialize)]
+struct CalendarTokenResponse {
+    token: String,
+    /// URL para suscribirse desde un cliente de calendario
+    url: String,
+}
+
+/// Evento de una reserva; `resumen` y `descripcion` dependen de quién lo ve
+fn reservation_event(reserva: &Reserva, restaurant: &Restaurant, resumen: String, descripcion: String) -> Option<EventoCalendario> {
+    let configuracion = &restaurant.configuracion;
+    let ubicacion = match &configuracion.direccion {
+        Some(direccion) => format!("{}, {}", restaurant.nombre, direccion),
+        None => restaurant.nombre.clone(),
+    };
+
+    Some(EventoCalendario {
+        uid: format!("{}@pispas", reserva.id?.to_hex()),
+        inicio: parse_inicio(&reserva.fecha, &reserva.hora)?,
+        zona_horaria: configuracion.zona(),
+        duracion_minutos: configuracion.duracion_minutos(reserva.numero_personas),
+        resumen,
+        descripcion,
+        ubicacion: Some(ubicacion),
+        url: None,
+        cancelado: reserva.estado == EstadoReserva::Cancelada,
+        actualizado: reserva.updated_at,
+    })
+}
+
+/// Calendario del cliente con su reserva y los enlaces de gestión del
+/// `token` (ver [`super::public::manage_token`])
+fn customer_calendar(reserva: &Reserva, restaurant: &Restaurant, token: &str) -> Option<String> {
+    let base = public_base_url();
+    let mut descripcion = format!("{} personas", reserva.numero_personas);
+    if let Some(localizador) = &reserva.localizador {
+        descripcion.push_str(&format!(", localizador {}", localizador));
+    }
+    descripcion.push_str(&format!("\nCancélala: {}/public/reservations/{}/cancel", base, token));
+
+    let evento = EventoCalendario {
+        url: Some(format!("{}/public/reservations/manage/{}", base, token)),
+        ..reservation_event(reserva, restaurant, format!("Reserva en {}", restaurant.nombre), descripcion)?
+    };
+    Some(calendar::calendar(&restaurant.nombre, &[evento]))
+}
+
+/// Reserva en formato iCalendar, para adjuntarla al email del cliente
+///
+/// # Retorna
+/// `None` si la reserva no tiene ID o su fecha u hora no son válidas
+pub fn ics_attachment(reserva: &Reserva, restaurant: &Restaurant, token: &str) -> Option<Adjunto> {
+    Some(Adjunto {
+        nombre: "reserva.ics".to_string(),
+        tipo: "text/calendar".to_string(),
+        contenido: customer_calendar(reserva, restaurant, token)?,
+    })
+}
+
+/// Descarga la reserva en formato iCalendar desde su enlace de gestión
+///
+/// # Respuesta
+/// `text/calendar` con la reserva, como el adjunto del email de
+/// confirmación.
+///
+/// # Errores
+/// - `404 Not Found`: Enlace inválido
+/// - `409 Conflict`: La reserva ya se ha anonimizado
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/public/reservations/{token}/ics")]
+async fn download_reservation_ics(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let token = path.into_inner();
+    let reserva = find_by_token(repo.get_ref(), &token).await?;
+    if reserva.anonimizada_en.is_some() {
+        return Err(AppError::Conflict("La reserva ya se ha anonimizado".to_string()));
+    }
+    let restaurant = find_restaurant(repo.get_ref(), reserva.id_restaurante).await?;
+    let ics = customer_calendar(&reserva, &restaurant, &token)
+        .ok_or(AppError::Internal("Fecha u hora de la reserva inválidas".to_string()))?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("reserva.ics".to_string())],
+        })
+        .body(ics))
+}
+
+/// Evento de una reserva en el calendario del restaurante
+fn staff_event(reserva: &Reserva, restaurant: &Restaurant) -> Option<EventoCalendario> {
+    let mut descripcion = format!(
+        "{} personas\nTeléfono: {}\nEstado: {}",
+        reserva.numero_personas, reserva.telefono_cliente, reserva.estado
+    );
+    if let Some(localizador) = &reserva.localizador {
+        descripcion.push_str(&format!("\nLocalizador: {}", localizador));
+    }
+    if let Some(notas) = &reserva.notas_cliente {
+        descripcion.push_str(&format!("\nNotas: {}", notas));
+    }
+
+    reservation_event(
+        reserva,
+        restaurant,
+        format!("{} ({})", reserva.nombre_cliente, reserva.numero_personas),
+        descripcion,
+    )
+}
+
+/// Restaurante de la petición: el del token del calendario o el del Bearer
+async fn calendar_owner(repo: &MongoRepo, req: &HttpRequest, token: Option<&str>) -> AppResult<Restaurant> {
+    let Some(token) = token else {
+        let auth = AuthenticatedRestaurant::<PermisoReservas, LeerReservas>::extract(req).await?;
+        return Ok(auth.restaurant);
+    };
+
+    let restaurant = repo.restaurants()
+        .find_one(doc! { "token_calendario": token })
+        .await
+        .map_err(|e| AppError::database("calendar_owner", e))?
+        .ok_or(AppError::Unauthorized("Token de calendario inválido".to_string()))?;
+    ensure_can_login(&restaurant)?;
+    Ok(restaurant)
+}
+
+/// Calendario iCalendar con las próximas reservas del restaurante
+///
+/// Incluye las reservas de hoy al horizonte del listado
+/// (`horizonte_listado_dias`), salvo las que esperan la verificación del
+/// cliente; las canceladas aparecen como canceladas.
+///
+/// # Autenticación
+/// Token Bearer de cualquier rol del personal (o clave con
+/// `reservations:read`), o el token del calendario en `?token=`.
+///
+/// # Respuesta
+/// `text/calendar`, para suscribirse desde un cliente de calendario.
+///
+/// # Errores
+/// - `401 Unauthorized`: Sin token, o token inválido o revocado
+/// - `403 Forbidden`: Cuenta suspendida
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/reservations/calendar.ics")]
+async fn reservations_calendar(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    query: web::Query<CalendarQuery>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    let restaurant = calendar_owner(repo.get_ref(), &req, query.token.as_deref()).await?;
+
+    let hoy = restaurant.configuracion.hora_local(clock.timestamp()).date();
+    let horizonte = hoy + Duration::days(i64::from(restaurant.configuracion.horizonte_listado_dias));
+    let options = FindOptions::builder()
+        .sort(doc! { "fecha": 1, "hora": 1 })
+        .build();
+    let mut cursor = repo.reservas()
+        .find(doc! {
+            "id_restaurante": restaurant.id,
+            "fecha": {
+                "$gte": hoy.format("%Y-%m-%d").to_string(),
+                "$lte": horizonte.format("%Y-%m-%d").to_string(),
+            },
+            "estado": { "$ne": EstadoReserva::SinConfirmar },
+        })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("reservations_calendar", e))?;
+
+    let mut eventos = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let reserva: Reserva = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?;
+        eventos.extend(staff_event(&reserva, &restaurant));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .body(calendar::calendar(&format!("Reservas de {}", restaurant.nombre), &eventos)))
+}
+
+/// Crea o rota el token del calendario de reservas
+///
+/// El token anterior deja de funcionar: las suscripciones existentes deben
+/// cambiar a la URL nueva.
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Respuesta
+/// ```json
+/// { "token": "...", "url": "https://reservas.example.com/reservations/calendar.ics?token=..." }
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o sin permiso de configuración
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/restaurants/calendar-token")]
+async fn rotate_calendar_token(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let token = Uuid::new_v4().simple().to_string();
+    repo.restaurants()
+        .update_one(doc! { "_id": auth.id() }, doc! { "$set": { "token_calendario": &token } })
+        .await
+        .map_err(|e| AppError::database("rotate_calendar_token", e))?;
+
+    let url = format!("{}/reservations/calendar.ics?token={}", public_base_url(), token);
+    Ok(HttpResponse::Ok().json(CalendarTokenResponse { token, url }))
+}
+
+/// Revoca el token del calendario de reservas
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o sin permiso de configuración
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/restaurants/calendar-token")]
+async fn revoke_calendar_token(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    repo.restaurants()
+        .update_one(doc! { "_id": auth.id() }, doc! { "$unset": { "token_calendario": "" } })
+        .await
+        .map_err(|e| AppError::database("revoke_calendar_token", e))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Configura las rutas del calendario de reservas
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(reservations_calendar);
+    cfg.service(download_reservation_ics);
+    cfg.service(rotate_calendar_token);
+    cfg.service(revoke_calendar_token);
+}
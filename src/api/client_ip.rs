@@ -0,0 +1,103 @@
+//! # IP del cliente
+//!
+//! Cualquier cliente puede enviar `X-Forwarded-For` o `Forwarded` con la IP
+//! que quiera, así que no sirven por sí solas para autorizar ni limitar por
+//! IP. [`client_ip`] parte de la dirección de la conexión y solo la sustituye
+//! por la de `X-Forwarded-For` cuando la conexión viene de uno de los
+//! proxies de confianza (el balanceador delante del servidor...).
+//!
+//! Con varios proxies encadenados, cada uno añade al final de
+//! `X-Forwarded-For` la dirección de la que recibió la petición; la IP del
+//! cliente es la última de la cadena que no es un proxy de confianza (ver
+//! [`resolve`]). `Forwarded` no se tiene en cuenta.
+//!
+//! La usan las IPs autorizadas ([`super::ip_allowlist`]), el límite de
+//! peticiones ([`super::rate_limit`]), el bloqueo de logins
+//! ([`super::login_lockout`]), los límites del widget ([`super::public`]) y
+//! la IP que se guarda en cada sesión ([`super::sessions`]).
+//!
+//! ## Configuración
+//!
+//! - `TRUSTED_PROXIES`: IPs o rangos CIDR de los proxies de confianza,
+//!   separados por comas (default: ninguno, se usa siempre la dirección de
+//!   la conexión). Las entradas inválidas se ignoran con un aviso en el log.
+
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+use actix_web::HttpRequest;
+use super::ip_allowlist::{ip_allowed, normalize_rule};
+
+/// Proxies de confianza de `TRUSTED_PROXIES`, normalizados
+fn trusted_proxies() -> &'static [String] {
+    static PROXIES: OnceLock<Vec<String>> = OnceLock::new();
+    PROXIES.get_or_init(|| {
+        env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|regla| !regla.trim().is_empty())
+            .filter_map(|regla| {
+                let normalizada = normalize_rule(regla);
+                if normalizada.is_none() {
+                    tracing::warn!(regla = regla.trim(), "Proxy de confianza inválido en TRUSTED_PROXIES; se ignora");
+                }
+                normalizada
+            })
+            .collect()
+    })
+}
+
+/// Interpreta una entrada de `X-Forwarded-For`, con o sin puerto
+fn parse_address(entrada: &str) -> Option<IpAddr> {
+    let entrada = entrada.trim();
+    entrada.parse::<IpAddr>().ok()
+        .or_else(|| entrada.parse::<SocketAddr>().ok().map(|direccion| direccion.ip()))
+}
+
+/// IP del cliente a partir de la dirección de la conexión y `X-Forwarded-For`
+///
+/// Recorre `X-Forwarded-For` de derecha a izquierda mientras la dirección
+/// actual sea un proxy de confianza, y se queda con la primera que no lo
+/// es. Si la conexión no viene de un proxy de confianza, la cabecera se
+/// ignora.
+///
+/// ```
+/// use pispas_reservation::api::client_ip::resolve;
+///
+/// let proxies = vec!["10.0.0.0/8".to_string()];
+/// let cliente = "203.0.113.7".parse().unwrap();
+/// let balanceador = "10.0.0.2".parse().unwrap();
+///
+/// // Sin proxy de por medio, la cabecera la pone el propio cliente
+/// assert_eq!(resolve(cliente, Some("192.0.2.1"), &proxies), cliente);
+/// // Detrás del balanceador, la última entrada que no es un proxy
+/// assert_eq!(resolve(balanceador, Some("192.0.2.1, 203.0.113.7, 10.0.0.5"), &proxies), cliente);
+/// assert_eq!(resolve(balanceador, None, &proxies), balanceador);
+/// ```
+pub fn resolve(conexion: IpAddr, forwarded_for: Option<&str>, proxies: &[String]) -> IpAddr {
+    let mut ip = conexion;
+    if let Some(cadena) = forwarded_for {
+        for entrada in cadena.rsplit(',') {
+            if !ip_allowed(ip, proxies) {
+                break;
+            }
+            match parse_address(entrada) {
+                Some(anterior) => ip = anterior,
+                None => break,
+            }
+        }
+    }
+    ip
+}
+
+/// IP del cliente de una petición (ver la documentación del módulo)
+///
+/// `None` si la conexión no tiene dirección remota (en las pruebas, o por
+/// un socket Unix).
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let conexion = req.peer_addr()?.ip();
+    let forwarded_for = req.headers()
+        .get("X-Forwarded-For")
+        .and_then(|valor| valor.to_str().ok());
+    Some(resolve(conexion, forwarded_for, trusted_proxies()))
+}
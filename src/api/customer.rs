@@ -0,0 +1,608 @@
+//! # API de Clientes (CRM)
+//!
+//! Este módulo maneja el registro de clientes de cada restaurante:
+//! - Alta/actualización automática del cliente con cada reserva
+//! - Listado de clientes
+//! - Fusión de clientes duplicados, con auditoría
+//! - Mesa preferida de cada cliente, aprendida o fijada por el personal
+//!
+//! Los clientes se identifican por email (normalizado a minúsculas) o, si la
+//! reserva no tiene email, por teléfono. Cada reserva guarda el `id_cliente`
+//! al que pertenece y el cliente lleva la cuenta de sus reservas no
+//! canceladas a las que se presentó (`visitas`) y de las que no se presentó
+//! (`no_shows`). Al crear una reserva, el panel recibe los no-shows previos
+//! del cliente, y el restaurante puede retener para revisión las reservas
+//! del widget de los clientes con varios (ver [`crate::risk`]).
+//!
+//! La mesa preferida se aprende del historial: es la mesa en la que el
+//! cliente tiene más visitas, siempre que sean al menos
+//! [`MIN_RESERVAS_PREFERENCIA`]. Si el personal la fija a mano, el historial
+//! deja de cambiarla hasta que se borre. La búsqueda de mesas disponibles la
+//! pone la primera cuando está libre (ver [`super::table`]).
+//!
+//! Todas las operaciones requieren autenticación mediante token Bearer.
+
+use actix_web::{get, post, put, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::options::{FindOptions, ReturnDocument};
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::fields::CamposRespuesta;
+use super::auth::{AuthenticatedRestaurant, EscribirClientes, LeerClientes, PermisoGestion};
+use crate::clock::Clock;
+use crate::db::{Cliente, EstadoReserva, FusionClientes, MongoRepo};
+use crate::journal;
+
+/// Reservas en la misma mesa necesarias para aprenderla como preferida
+pub const MIN_RESERVAS_PREFERENCIA: i32 = 2;
+
+/// Estructura de respuesta para un cliente
+#[derive(Serialize)]
+struct ClienteResponse {
+    id: String,
+    nombre: String,
+    emails: Vec<String>,
+    telefonos: Vec<String>,
+    visitas: u32,
+    no_shows: u32,
+    mesa_preferida: Option<String>,
+    preferencia_manual: bool,
+    created_at: i64,
+    updated_at: i64,
+}
+
+impl From<Cliente> for ClienteResponse {
+    fn from(cliente: Cliente) -> Self {
+        ClienteResponse {
+            id: cliente.id.map(|id| id.to_hex()).unwrap_or_default(),
+            nombre: cliente.nombre,
+            emails: cliente.emails,
+            telefonos: cliente.telefonos,
+            visitas: cliente.visitas,
+            no_shows: cliente.no_shows,
+            mesa_preferida: cliente.mesa_preferida.map(|id| id.to_hex()),
+            preferencia_manual: cliente.preferencia_manual,
+            created_at: cliente.created_at,
+            updated_at: cliente.updated_at,
+        }
+    }
+}
+
+/// Normaliza un email para identificar al cliente
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Normaliza un teléfono dejando solo los dígitos y el `+` inicial
+fn normalize_phone(telefono: &str) -> String {
+    let telefono = telefono.trim();
+    let digitos: String = telefono.chars().filter(|c| c.is_ascii_digit()).collect();
+    if telefono.starts_with('+') && !digitos.is_empty() {
+        format!("+{}", digitos)
+    } else {
+        digitos
+    }
+}
+
+/// Filtro que identifica al cliente por email o, si no hay, por teléfono
+///
+/// Devuelve `None` si no hay ni email ni teléfono.
+fn contact_filter(restaurante_id: ObjectId, email: &str, telefono: &str) -> Option<Document> {
+    let email = normalize_email(email);
+    let telefono = normalize_phone(telefono);
+
+    // `$elemMatch` en lugar de igualdad para que el upsert no copie el valor
+    // como escalar en el documento nuevo
+    if !email.is_empty() {
+        Some(doc! { "id_restaurante": restaurante_id, "emails": { "$elemMatch": { "$eq": email } } })
+    } else if !telefono.is_empty() {
+        Some(doc! { "id_restaurante": restaurante_id, "telefonos": { "$elemMatch": { "$eq": telefono } } })
+    } else {
+        None
+    }
+}
+
+/// Busca al cliente del restaurante con ese email o teléfono
+pub(super) async fn find_by_contact(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    email: &str,
+    telefono: &str,
+) -> AppResult<Option<Cliente>> {
+    let Some(filtro) = contact_filter(restaurante_id, email, telefono) else {
+        return Ok(None);
+    };
+
+    repo.clientes()
+        .find_one(filtro)
+        .await
+        .map_err(|e| AppError::database("find_customer_by_contact", e))
+}
+
+/// Registra una reserva en el CRM y devuelve el cliente al que pertenece
+///
+/// Busca al cliente del restaurante por email (o teléfono si no hay email),
+/// creándolo si no existe, y actualiza su nombre y datos de contacto. Si
+/// `contar_visita` es `true` además suma una visita.
+///
+/// Devuelve `None` si la reserva no tiene ni email ni teléfono (por ejemplo,
+/// reservas anonimizadas).
+pub(super) async fn link_customer(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    nombre: &str,
+    email: &str,
+    telefono: &str,
+    contar_visita: bool,
+    current_time: i64,
+) -> AppResult<Option<ObjectId>> {
+    let Some(filtro) = contact_filter(restaurante_id, email, telefono) else {
+        return Ok(None);
+    };
+    let email = normalize_email(email);
+    let telefono = normalize_phone(telefono);
+
+    let mut contacto = Document::new();
+    if !email.is_empty() {
+        contacto.insert("emails", email);
+    }
+    if !telefono.is_empty() {
+        contacto.insert("telefonos", telefono);
+    }
+
+    let cliente = repo.clientes()
+        .find_one_and_update(filtro, doc! {
+            "$setOnInsert": { "created_at": current_time },
+            "$set": { "nombre": nombre.trim(), "updated_at": current_time },
+            "$addToSet": contacto,
+            "$inc": { "visitas": if contar_visita { 1 } else { 0 } },
+        })
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("link_customer", e))?;
+
+    Ok(cliente.and_then(|cliente| cliente.id))
+}
+
+/// Resta una visita al cliente de una reserva cancelada o sin presentarse
+pub async fn discount_visit(repo: &MongoRepo, id_cliente: ObjectId) -> AppResult<()> {
+    repo.clientes()
+        .update_one(
+            doc! { "_id": id_cliente, "visitas": { "$gt": 0 } },
+            doc! { "$inc": { "visitas": -1 } },
+        )
+        .await
+        .map_err(|e| AppError::database("discount_visit", e))?;
+
+    Ok(())
+}
+
+/// Suma un no-show al cliente de una reserva a la que no se presentó
+pub async fn count_no_show(repo: &MongoRepo, id_cliente: ObjectId) -> AppResult<()> {
+    repo.clientes()
+        .update_one(doc! { "_id": id_cliente }, doc! { "$inc": { "no_shows": 1 } })
+        .await
+        .map_err(|e| AppError::database("count_no_show", e))?;
+
+    Ok(())
+}
+
+/// No-shows previos del cliente de una reserva (0 si no tiene cliente)
+pub(super) async fn previous_no_shows(repo: &MongoRepo, id_cliente: Option<ObjectId>) -> AppResult<u32> {
+    let Some(id_cliente) = id_cliente else {
+        return Ok(0);
+    };
+
+    let cliente = repo.clientes()
+        .find_one(doc! { "_id": id_cliente })
+        .await
+        .map_err(|e| AppError::database("previous_no_shows", e))?;
+    Ok(cliente.map_or(0, |cliente| cliente.no_shows))
+}
+
+/// Recalcula la mesa preferida de un cliente a partir de su historial
+///
+/// No cambia las preferencias fijadas por el personal. Los errores solo se
+/// registran: aprender la preferencia nunca debe hacer fallar una reserva.
+pub async fn learn_preference(repo: &MongoRepo, id_cliente: ObjectId) {
+    if let Err(e) = try_learn_preference(repo, id_cliente).await {
+        tracing::error!(cliente = %id_cliente, "Error aprendiendo la mesa preferida: {}", e);
+    }
+}
+
+async fn try_learn_preference(repo: &MongoRepo, id_cliente: ObjectId) -> AppResult<()> {
+    let pipeline = vec![
+        doc! { "$match": { "id_cliente": id_cliente, "estado": { "$nin": [EstadoReserva::Cancelada, EstadoReserva::NoShow] } } },
+        doc! { "$group": {
+            "_id": "$id_mesa",
+            "reservas": { "$sum": 1 },
+            "ultima": { "$max": "$created_at" },
+        } },
+        doc! { "$match": { "reservas": { "$gte": MIN_RESERVAS_PREFERENCIA } } },
+        doc! { "$sort": { "reservas": -1, "ultima": -1 } },
+        doc! { "$limit": 1 },
+    ];
+
+    let mut cursor = repo.reservas()
+        .aggregate(pipeline)
+        .await
+        .map_err(|e| AppError::database("learn_preference", e))?;
+
+    let mesa = if cursor.advance().await.map_err(cursor_error)? {
+        cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando agregado: {}", e)))?
+            .get_object_id("_id")
+            .ok()
+    } else {
+        None
+    };
+
+    let update = match mesa {
+        Some(mesa) => doc! { "$set": { "mesa_preferida": mesa } },
+        None => doc! { "$unset": { "mesa_preferida": "" } },
+    };
+
+    repo.clientes()
+        .update_one(doc! { "_id": id_cliente, "preferencia_manual": { "$ne": true } }, update)
+        .await
+        .map_err(|e| AppError::database("learn_preference", e))?;
+
+    Ok(())
+}
+
+fn cursor_error(e: mongodb::error::Error) -> AppError {
+    AppError::Internal(format!("Error iterando cursor: {}", e))
+}
+
+/// Lista los clientes del restaurante autenticado, por nombre
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439011",
+///     "nombre": "Juan Pérez",
+///     "emails": ["juan@email.com", "juanp@trabajo.com"],
+///     "telefonos": ["+34600000000"],
+///     "visitas": 7,
+///     "no_shows": 1,
+///     "mesa_preferida": "507f1f77bcf86cd799439021",
+///     "preferencia_manual": false,
+///     "created_at": 1717243200,
+///     "updated_at": 1719835200
+///   }
+/// ]
+/// ```
+///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/customers")]
+async fn list_customers(
+    repo: web::Data<MongoRepo>,
+    campos: CamposRespuesta,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerClientes>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let options = FindOptions::builder().sort(doc! { "nombre": 1 }).build();
+    let mut cursor = repo.clientes()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("list_customers", e))?;
+
+    let mut clientes = Vec::new();
+    while cursor.advance().await.map_err(cursor_error)? {
+        clientes.push(ClienteResponse::from(cursor.deserialize_current().map_err(cursor_error)?));
+    }
+
+    Ok(campos.respond(&clientes))
+}
+
+/// Estructura para fusionar dos clientes
+#[derive(Deserialize)]
+struct MergeCustomers {
+    /// Cliente que se conserva
+    id_destino: String,
+    /// Cliente que se absorbe y elimina
+    id_origen: String,
+}
+
+/// Busca un cliente del restaurante
+async fn find_customer(repo: &MongoRepo, restaurante_id: ObjectId, id: &str) -> AppResult<Cliente> {
+    let id = ObjectId::parse_str(id)
+        .map_err(|_| AppError::Validation("ID de cliente inválido".to_string()))?;
+
+    repo.clientes()
+        .find_one(doc! { "_id": id, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("find_customer", e))?
+        .ok_or(AppError::NotFound(format!("Cliente {} no encontrado", id.to_hex())))
+}
+
+/// Fusiona dos clientes duplicados
+///
+/// El cliente origen se absorbe en el destino:
+/// - Sus reservas pasan a pertenecer al destino
+/// - Sus emails y teléfonos se añaden a los del destino
+/// - Sus visitas y sus no-shows se suman a los del destino
+/// - Se elimina, guardando una copia en el registro de fusiones
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Cuerpo
+/// ```json
+/// {
+///   "id_destino": "507f1f77bcf86cd799439011",
+///   "id_origen": "507f1f77bcf86cd799439012"
+/// }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Clientes fusionados correctamente",
+///   "reservas_movidas": 3,
+///   "cliente": { "id": "507f1f77bcf86cd799439011", "nombre": "Juan Pérez", "visitas": 10, "...": "..." }
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: IDs inválidos o iguales
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Alguno de los clientes no existe en el restaurante
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/customers/merge")]
+async fn merge_customers(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<MergeCustomers>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirClientes>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    if data.id_destino == data.id_origen {
+        return Err(AppError::Validation("No se puede fusionar un cliente consigo mismo".to_string()));
+    }
+
+    let destino = find_customer(repo.get_ref(), restaurante_id, &data.id_destino).await?;
+    let origen = find_customer(repo.get_ref(), restaurante_id, &data.id_origen).await?;
+    let (id_destino, id_origen) = (destino.id.unwrap(), origen.id.unwrap());
+    let now = clock.timestamp();
+
+    let mut por_mover = Vec::new();
+    if journal::enabled(repo.get_ref()) {
+        let mut cursor = repo.reservas()
+            .find(doc! { "id_cliente": id_origen })
+            .projection(doc! { "_id": 1 })
+            .await
+            .map_err(|e| AppError::database("merge_customers", e))?;
+        while cursor.advance().await.map_err(|e| AppError::database("merge_customers", e))? {
+            por_mover.extend(cursor.current().get_object_id("_id").ok());
+        }
+    }
+    let movidas = repo.reservas()
+        .update_many(
+            doc! { "id_cliente": id_origen },
+            doc! { "$set": { "id_cliente": id_destino } },
+        )
+        .await
+        .map_err(|e| AppError::database("merge_customers", e))?;
+    for id_reserva in por_mover {
+        journal::append(repo.get_ref(), id_reserva, "fusionar_clientes", now).await;
+    }
+
+    let cliente = repo.clientes()
+        .find_one_and_update(
+            doc! { "_id": id_destino },
+            doc! {
+                "$addToSet": {
+                    "emails": { "$each": origen.emails.clone() },
+                    "telefonos": { "$each": origen.telefonos.clone() },
+                },
+                "$inc": { "visitas": i64::from(origen.visitas), "no_shows": i64::from(origen.no_shows) },
+                "$set": { "updated_at": now },
+            },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("merge_customers", e))?
+        .ok_or(AppError::NotFound(format!("Cliente {} no encontrado", id_destino.to_hex())))?;
+
+    let fusion = FusionClientes {
+        id: None,
+        id_restaurante: restaurante_id,
+        id_destino,
+        origen,
+        reservas_movidas: movidas.modified_count,
+        created_at: now,
+    };
+
+    repo.fusiones_clientes()
+        .insert_one(&fusion)
+        .await
+        .map_err(|e| AppError::database("merge_customers", e))?;
+
+    repo.clientes()
+        .delete_one(doc! { "_id": id_origen })
+        .await
+        .map_err(|e| AppError::database("merge_customers", e))?;
+
+    learn_preference(repo.get_ref(), id_destino).await;
+
+    tracing::info!(
+        restaurante = %restaurante_id,
+        destino = %id_destino,
+        origen = %id_origen,
+        reservas = movidas.modified_count,
+        "Clientes fusionados"
+    );
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Clientes fusionados correctamente",
+        "reservas_movidas": movidas.modified_count,
+        "cliente": ClienteResponse::from(cliente)
+    })))
+}
+
+/// Estructura para fijar la mesa preferida de un cliente
+#[derive(Deserialize)]
+struct SetPreference {
+    /// Mesa preferida; `null` vuelve a aprenderla del historial
+    id_mesa: Option<String>,
+}
+
+/// Fija o borra la mesa preferida de un cliente
+///
+/// Con una mesa, la preferencia queda fijada y el historial ya no la cambia.
+/// Con `null`, se borra la preferencia manual y se vuelve a aprender del
+/// historial de reservas.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Cuerpo
+/// ```json
+/// { "id_mesa": "507f1f77bcf86cd799439021" }
+/// ```
+///
+/// # Respuesta
+/// El cliente actualizado, con el mismo formato que `GET /customers`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID de cliente o de mesa inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Cliente o mesa no encontrados en el restaurante
+/// - `500 Internal Server Error`: Error de base de datos
+#[put("/customers/{id}/preference")]
+async fn set_preference(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<SetPreference>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirClientes>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let cliente = find_customer(repo.get_ref(), restaurante_id, &path.into_inner()).await?;
+    let id_cliente = cliente.id.unwrap();
+
+    match &data.id_mesa {
+        Some(id_mesa) => {
+            let id_mesa = ObjectId::parse_str(id_mesa)
+                .map_err(|_| AppError::Validation("ID de mesa inválido".to_string()))?;
+            repo.mesas()
+                .find_one(doc! { "_id": id_mesa, "id_restaurante": restaurante_id })
+                .await
+                .map_err(|e| AppError::database("set_preference", e))?
+                .ok_or(AppError::NotFound("Mesa no encontrada".to_string()))?;
+
+            repo.clientes()
+                .update_one(
+                    doc! { "_id": id_cliente },
+                    doc! { "$set": {
+                        "mesa_preferida": id_mesa,
+                        "preferencia_manual": true,
+                        "updated_at": clock.timestamp(),
+                    } },
+                )
+                .await
+                .map_err(|e| AppError::database("set_preference", e))?;
+        }
+        None => {
+            repo.clientes()
+                .update_one(
+                    doc! { "_id": id_cliente },
+                    doc! { "$set": { "preferencia_manual": false, "updated_at": clock.timestamp() } },
+                )
+                .await
+                .map_err(|e| AppError::database("set_preference", e))?;
+            learn_preference(repo.get_ref(), id_cliente).await;
+        }
+    }
+
+    let cliente = find_customer(repo.get_ref(), restaurante_id, &id_cliente.to_hex()).await?;
+    Ok(HttpResponse::Ok().json(ClienteResponse::from(cliente)))
+}
+
+/// Respuesta para una fusión registrada
+#[derive(Serialize)]
+struct FusionResponse {
+    id: String,
+    id_destino: String,
+    origen: ClienteResponse,
+    reservas_movidas: u64,
+    created_at: i64,
+}
+
+/// Lista las fusiones de clientes del restaurante, de la más reciente a la más antigua
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439013",
+///     "id_destino": "507f1f77bcf86cd799439011",
+///     "origen": { "id": "507f1f77bcf86cd799439012", "nombre": "J. Pérez", "...": "..." },
+///     "reservas_movidas": 3,
+///     "created_at": 1719835200
+///   }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/customers/merges")]
+async fn list_merges(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerClientes>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let mut cursor = repo.fusiones_clientes()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("list_merges", e))?;
+
+    let mut fusiones = Vec::new();
+    while cursor.advance().await.map_err(cursor_error)? {
+        let fusion = cursor.deserialize_current().map_err(cursor_error)?;
+        fusiones.push(FusionResponse {
+            id: fusion.id.map(|id| id.to_hex()).unwrap_or_default(),
+            id_destino: fusion.id_destino.to_hex(),
+            origen: ClienteResponse::from(fusion.origen),
+            reservas_movidas: fusion.reservas_movidas,
+            created_at: fusion.created_at,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(fusiones))
+}
+
+/// Configura las rutas de clientes
+///
+/// # Rutas disponibles
+/// - `GET /customers` - Listar clientes
+/// - `POST /customers/merge` - Fusionar dos clientes duplicados
+/// - `GET /customers/merges` - Historial de fusiones
+/// - `PUT /customers/{id}/preference` - Fijar o borrar la mesa preferida
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_customers);
+    cfg.service(merge_customers);
+    cfg.service(list_merges);
+    cfg.service(set_preference);
+}
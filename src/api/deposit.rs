@@ -0,0 +1,759 @@
+//! # API de Depósitos divididos
+//!
+//! Para grupos grandes el restaurante puede exigir un depósito y dividirlo en
+//! varias partes, cada una con su propio enlace de pago, para que cada
+//! comensal pague la suya:
+//! - Crear y consultar el depósito de una reserva (con token del restaurante)
+//! - Consultar una parte desde su enlace de pago (público)
+//! - Recibir la notificación de pago de la pasarela (webhook)
+//!
+//! Una reserva con depósito no se confirma hasta que lo pagado alcanza el
+//! umbral; al alcanzarlo, una reserva "pendiente" pasa a "confirmada".
+//!
+//! La pasarela notifica cada pago a `POST /payments/webhook` firmado con el
+//! secreto de la variable `PAYMENT_WEBHOOK_SECRET` (ver
+//! [`super::webhook_auth`]); si no está configurada, el webhook no está
+//! disponible. Los fallos continuados del webhook pueden generar una alerta
+//! (ver [`crate::jobs::alerts`]).
+//!
+//! ## Pago por adelantado
+//!
+//! Además, la configuración del restaurante puede exigir un pago único a los
+//! grupos a partir de cierto tamaño (`deposito`): las reservas nuevas de esos
+//! grupos llevan un [`Pago`] pendiente y no se confirman hasta que el
+//! personal lo marca como pagado. El cobro y el reembolso pasan por el
+//! proveedor de pagos configurado (ver [`crate::payments`]).
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId, to_bson};
+use mongodb::options::ReturnDocument;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use super::{reservation_history, AppError, AppResult};
+use super::public::public_base_url;
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{AuthenticatedRestaurant, EscribirReservas, LeerReservas, PermisoGestion};
+use super::webhook_auth::VerificadorWebhook;
+use crate::clock::Clock;
+use crate::db::{Deposito, EstadoPago, EstadoReserva, MongoRepo, Pago, Reserva};
+use crate::events::{self, TipoEvento};
+use crate::jobs::alerts;
+use crate::journal;
+use crate::payments::Payments;
+
+/// Número máximo de partes en que se puede dividir un depósito
+const MAX_PARTES: u32 = 50;
+
+/// Longitud máxima de la referencia de un pago
+const MAX_REFERENCIA: usize = 200;
+
+/// Estructura para crear el depósito de una reserva
+#[derive(Deserialize)]
+struct CreateDeposit {
+    /// Importe total en céntimos
+    importe_total_centimos: i64,
+    /// Número de partes; por defecto, una por comensal
+    partes: Option<u32>,
+    /// Porcentaje del total que confirma la reserva (1-100)
+    #[serde(default = "default_umbral")]
+    umbral_porcentaje: u32,
+}
+
+fn default_umbral() -> u32 {
+    100
+}
+
+/// Notificación de pago de la pasarela
+#[derive(Deserialize)]
+struct PaymentNotification {
+    /// Código del enlace de pago
+    codigo: String,
+}
+
+/// Cobro de un pago por adelantado
+#[derive(Deserialize, Default)]
+struct PaidPayment {
+    /// Referencia del cobro en el proveedor (datáfono, pasarela...)
+    referencia: Option<String>,
+}
+
+/// Estructura de respuesta para el pago por adelantado de una reserva
+#[derive(Serialize)]
+struct PaymentResponse {
+    id_reserva: String,
+    estado_reserva: EstadoReserva,
+    pago: Pago,
+}
+
+/// Estructura de respuesta para una parte del depósito
+#[derive(Serialize)]
+struct PartResponse {
+    codigo: String,
+    enlace: String,
+    importe_centimos: i64,
+    pagada: bool,
+    pagada_en: Option<i64>,
+}
+
+/// Estructura de respuesta para el depósito de una reserva
+#[derive(Serialize)]
+struct DepositResponse {
+    id_reserva: String,
+    estado_reserva: EstadoReserva,
+    estado: &'static str,
+    importe_total_centimos: i64,
+    umbral_centimos: i64,
+    pagado_centimos: i64,
+    partes: Vec<PartResponse>,
+}
+
+impl DepositResponse {
+    fn new(id_reserva: ObjectId, estado_reserva: EstadoReserva, deposito: Deposito) -> Self {
+        let base_url = public_base_url();
+        DepositResponse {
+            id_reserva: id_reserva.to_hex(),
+            estado_reserva,
+            estado: deposito.estado(),
+            importe_total_centimos: deposito.importe_total_centimos,
+            umbral_centimos: deposito.umbral_centimos,
+            pagado_centimos: deposito.pagado_centimos(),
+            partes: deposito.partes
+                .into_iter()
+                .map(|parte| PartResponse {
+                    enlace: format!("{}/public/payments/{}", base_url, parte.codigo),
+                    codigo: parte.codigo,
+                    importe_centimos: parte.importe_centimos,
+                    pagada: parte.pagada_en.is_some(),
+                    pagada_en: parte.pagada_en,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Busca una reserva del restaurante por su ID
+async fn find_reservation(repo: &MongoRepo, restaurante_id: ObjectId, id: String) -> AppResult<Reserva> {
+    let id = ObjectId::parse_str(id)
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+
+    repo.reservas()
+        .find_one(doc! { "_id": id, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("find_reservation", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada".to_string()))
+}
+
+/// Busca la reserva que contiene la parte de pago con ese código
+async fn find_by_code(repo: &MongoRepo, codigo: &str) -> AppResult<Reserva> {
+    repo.reservas()
+        .find_one(doc! { "deposito.partes.codigo": codigo })
+        .await
+        .map_err(|e| AppError::database("find_payment", e))?
+        .ok_or(AppError::NotFound("Enlace de pago no encontrado".to_string()))
+}
+
+/// Crea (o rehace) el depósito dividido de una reserva
+///
+/// Si la reserva ya tenía un depósito sin ningún pago, se sustituye y los
+/// enlaces anteriores dejan de funcionar.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante propietario.
+///
+/// # Cuerpo
+/// ```json
+/// {
+///   "importe_total_centimos": 12000,
+///   "partes": 8,
+///   "umbral_porcentaje": 75
+/// }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id_reserva": "507f1f77bcf86cd799439011",
+///   "estado_reserva": "pendiente",
+///   "estado": "pendiente",
+///   "importe_total_centimos": 12000,
+///   "umbral_centimos": 9000,
+///   "pagado_centimos": 0,
+///   "partes": [
+///     {
+///       "codigo": "3f2b...",
+///       "enlace": "https://reservas.ejemplo.com/public/payments/3f2b...",
+///       "importe_centimos": 1500,
+///       "pagada": false,
+///       "pagada_en": null
+///     }
+///   ]
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Importe, partes o umbral inválidos
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Reserva no encontrada
+/// - `409 Conflict`: La reserva ya no ocupa la mesa (cancelada, completada...) o el depósito ya tiene pagos
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/deposit")]
+async fn create_deposit(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<CreateDeposit>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let reserva = find_reservation(repo.get_ref(), restaurante_id, path.into_inner()).await?;
+
+    let partes = data.partes.unwrap_or(u32::try_from(reserva.numero_personas).unwrap_or(1));
+    if data.importe_total_centimos <= 0 {
+        return Err(AppError::validation_field("importe_total_centimos", "El importe debe ser mayor a 0"));
+    }
+    if !(1..=MAX_PARTES).contains(&partes) {
+        return Err(AppError::validation_field("partes", &format!(
+            "El número de partes debe estar entre 1 y {}", MAX_PARTES
+        )));
+    }
+    if i64::from(partes) > data.importe_total_centimos {
+        return Err(AppError::validation_field("partes", "Hay más partes que céntimos en el importe"));
+    }
+    if !(1..=100).contains(&data.umbral_porcentaje) {
+        return Err(AppError::validation_field("umbral_porcentaje", "El umbral debe estar entre 1 y 100"));
+    }
+
+    if !reserva.estado.is_active() {
+        return Err(AppError::Conflict(format!("No se puede pedir un depósito para una reserva {}", reserva.estado)));
+    }
+    if reserva.deposito.as_ref().is_some_and(|deposito| deposito.pagado_centimos() > 0) {
+        return Err(AppError::Conflict("El depósito ya tiene pagos y no se puede rehacer".to_string()));
+    }
+
+    let deposito = Deposito::new(
+        data.importe_total_centimos,
+        partes,
+        data.umbral_porcentaje,
+        |_| Uuid::new_v4().simple().to_string(),
+        clock.timestamp(),
+    );
+    let deposito_bson = to_bson(&deposito)
+        .map_err(|e| AppError::Internal(format!("Error serializando depósito: {}", e)))?;
+
+    // Solo se sustituye si nadie ha pagado entre la lectura y la escritura
+    let result = repo.reservas()
+        .update_one(
+            doc! {
+                "_id": reserva.id,
+                "deposito.partes.pagada_en": { "$exists": false }
+            },
+            doc! { "$set": { "deposito": deposito_bson, "updated_at": clock.timestamp() } },
+        )
+        .await
+        .map_err(|e| AppError::database("create_deposit", e))?;
+
+    if result.matched_count == 0 {
+        return Err(AppError::Conflict("El depósito ya tiene pagos y no se puede rehacer".to_string()));
+    }
+    if let Some(id_reserva) = reserva.id {
+        journal::append(repo.get_ref(), id_reserva, "crear_deposito", clock.timestamp()).await;
+    }
+
+    tracing::info!(
+        reserva = %reserva.id.unwrap_or_default(),
+        partes = partes,
+        importe = data.importe_total_centimos,
+        "Depósito dividido creado"
+    );
+
+    Ok(HttpResponse::Ok().json(DepositResponse::new(
+        reserva.id.unwrap_or_default(),
+        reserva.estado,
+        deposito,
+    )))
+}
+
+/// Consulta el depósito de una reserva y el estado de cada parte
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante propietario.
+///
+/// # Respuesta
+/// El mismo formato que `POST /reservations/{id}/deposit`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Reserva no encontrada o sin depósito
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/reservations/{id}/deposit")]
+async fn get_deposit(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerReservas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let reserva = find_reservation(repo.get_ref(), restaurante_id, path.into_inner()).await?;
+
+    let deposito = reserva.deposito
+        .ok_or(AppError::NotFound("La reserva no tiene depósito".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(DepositResponse::new(
+        reserva.id.unwrap_or_default(),
+        reserva.estado,
+        deposito,
+    )))
+}
+
+/// Consulta una parte del depósito desde su enlace de pago
+///
+/// Es la página a la que llega cada comensal; la pasarela de pago usa el
+/// `codigo` como referencia del cobro.
+///
+/// # Autenticación
+/// No requiere autenticación: el código del enlace es la credencial.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "codigo": "3f2b...",
+///   "importe_centimos": 1500,
+///   "pagada": false,
+///   "fecha": "2030-06-15",
+///   "hora": "21:00",
+///   "numero_personas": 8,
+///   "estado_deposito": "parcial"
+/// }
+/// ```
+///
+/// # Errores
+/// - `404 Not Found`: Enlace de pago no encontrado
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/public/payments/{codigo}")]
+async fn get_payment(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let codigo = path.into_inner();
+    let reserva = find_by_code(repo.get_ref(), &codigo).await?;
+    let deposito = reserva.deposito
+        .ok_or(AppError::NotFound("Enlace de pago no encontrado".to_string()))?;
+    let parte = deposito.partes
+        .iter()
+        .find(|parte| parte.codigo == codigo)
+        .ok_or(AppError::NotFound("Enlace de pago no encontrado".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "codigo": parte.codigo,
+        "importe_centimos": parte.importe_centimos,
+        "pagada": parte.pagada_en.is_some(),
+        "fecha": reserva.fecha,
+        "hora": reserva.hora,
+        "numero_personas": reserva.numero_personas,
+        "estado_deposito": deposito.estado()
+    })))
+}
+
+/// Registra el pago de una parte del depósito
+///
+/// Lo llama la pasarela de pago cuando un comensal completa el cobro. Es
+/// idempotente: repetir (firmada de nuevo) la notificación de una parte ya
+/// pagada no cambia nada. Cuando lo pagado alcanza el umbral, la reserva "pendiente" pasa a
+/// "confirmada".
+///
+/// # Autenticación
+/// Header `X-Webhook-Signature` con la firma del cuerpo con el secreto de
+/// `PAYMENT_WEBHOOK_SECRET` (ver [`super::webhook_auth`]).
+///
+/// # Cuerpo
+/// ```json
+/// { "codigo": "3f2b..." }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id_reserva": "507f1f77bcf86cd799439011",
+///   "estado_reserva": "confirmada",
+///   "estado_deposito": "parcial",
+///   "pagado_centimos": 9000
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Cuerpo inválido
+/// - `401 Unauthorized`: Falta la firma, no es válida, está caducada o la
+///   notificación está repetida
+/// - `404 Not Found`: Webhook no configurado o enlace de pago no encontrado
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/payments/webhook")]
+async fn payment_webhook(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    cuerpo: web::Bytes,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    let verificador = VerificadorWebhook::from_env("pagos", "PAYMENT_WEBHOOK_SECRET")
+        .ok_or(AppError::NotFound("Recurso no disponible".to_string()))?;
+
+    let now = clock.timestamp();
+    let resultado = register_payment(repo.get_ref(), &verificador, &cuerpo, &req, now).await;
+    alerts::record_webhook(resultado.is_ok(), now);
+
+    Ok(HttpResponse::Ok().json(resultado?))
+}
+
+/// Verifica la firma y registra el pago de una parte del depósito
+async fn register_payment(
+    repo: &MongoRepo,
+    verificador: &VerificadorWebhook,
+    cuerpo: &[u8],
+    req: &HttpRequest,
+    now: i64,
+) -> AppResult<serde_json::Value> {
+    verificador.verify(repo, req, cuerpo, now).await?;
+
+    let data: PaymentNotification = serde_json::from_slice(cuerpo)
+        .map_err(|e| AppError::Validation(format!("Notificación de pago inválida: {}", e)))?;
+    let codigo = data.codigo.as_str();
+
+    let pagada = repo.reservas()
+        .find_one_and_update(
+            doc! {
+                "deposito.partes": {
+                    "$elemMatch": { "codigo": codigo, "pagada_en": { "$exists": false } }
+                }
+            },
+            doc! { "$set": { "deposito.partes.$.pagada_en": now, "updated_at": now } },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("register_payment", e))?;
+
+    let mut reserva = match pagada {
+        Some(reserva) => {
+            tracing::info!(reserva = %reserva.id.unwrap_or_default(), "Parte del depósito pagada");
+            events::record(
+                repo,
+                TipoEvento::DepositoPagado,
+                Some(reserva.id_restaurante),
+                reserva.id,
+                doc! { "codigo": codigo },
+                now,
+            ).await;
+            reservation_history::record(repo, reservation_history::from_outside(
+                reserva.id_restaurante,
+                reserva.id.unwrap_or_default(),
+                reservation_history::AUTOR_SISTEMA,
+                "pagar_deposito",
+                None,
+                Some(&doc! { "deposito": { "partes": { codigo: { "pagada_en": now } } } }),
+                now,
+            )).await;
+            reserva
+        }
+        // Ya estaba pagada (notificación repetida) o el código no existe
+        None => find_by_code(repo, codigo).await?,
+    };
+
+    let deposito = reserva.deposito
+        .take()
+        .ok_or(AppError::NotFound("Enlace de pago no encontrado".to_string()))?;
+
+    if deposito.umbral_alcanzado() && reserva.estado == EstadoReserva::Pendiente {
+        let result = repo.reservas()
+            .update_one(
+                doc! { "_id": reserva.id, "estado": EstadoReserva::Pendiente },
+                doc! { "$set": { "estado": EstadoReserva::Confirmada, "updated_at": now } },
+            )
+            .await
+            .map_err(|e| AppError::database("confirm_by_deposit", e))?;
+
+        if result.modified_count > 0 {
+            tracing::info!(reserva = %reserva.id.unwrap_or_default(), "Reserva confirmada por depósito");
+            events::record(
+                repo,
+                TipoEvento::ReservaConfirmada,
+                Some(reserva.id_restaurante),
+                reserva.id,
+                doc! { "por_deposito": true },
+                now,
+            ).await;
+            reservation_history::record(repo, reservation_history::from_outside(
+                reserva.id_restaurante,
+                reserva.id.unwrap_or_default(),
+                reservation_history::AUTOR_SISTEMA,
+                "confirmar_reserva",
+                Some(&doc! { "estado": EstadoReserva::Pendiente }),
+                Some(&doc! { "estado": EstadoReserva::Confirmada }),
+                now,
+            )).await;
+            reserva.estado = EstadoReserva::Confirmada;
+        }
+    }
+
+    Ok(serde_json::json!({
+        "id_reserva": reserva.id.unwrap_or_default().to_hex(),
+        "estado_reserva": reserva.estado,
+        "estado_deposito": deposito.estado(),
+        "pagado_centimos": deposito.pagado_centimos()
+    }))
+}
+
+/// Busca una reserva del restaurante con pago por adelantado
+async fn find_with_payment(repo: &MongoRepo, restaurante_id: ObjectId, id: String) -> AppResult<(Reserva, Pago)> {
+    let reserva = find_reservation(repo, restaurante_id, id).await?;
+    let pago = reserva.pago
+        .clone()
+        .ok_or(AppError::NotFound("La reserva no tiene pago por adelantado".to_string()))?;
+    Ok((reserva, pago))
+}
+
+/// Marca como pagado el pago por adelantado de una reserva
+///
+/// Guarda el proveedor de pagos configurado y la referencia del cobro. Si
+/// la reserva estaba "pendiente" y no le queda nada por pagar (ver
+/// [`Reserva::pago_pendiente`]), pasa a "confirmada".
+///
+/// # Autenticación
+/// Requiere permiso de gestión.
+///
+/// # Cuerpo (opcional)
+/// ```json
+/// { "referencia": "TPV-2030-0042" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id_reserva": "507f1f77bcf86cd799439011",
+///   "estado_reserva": "confirmada",
+///   "pago": {
+///     "importe_centimos": 8000,
+///     "estado": "pagado",
+///     "proveedor": "manual",
+///     "referencia": "TPV-2030-0042",
+///     "pagado_en": 1718000000
+///   }
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido o referencia demasiado larga
+/// - `401 Unauthorized`: Token inválido o rol sin permiso
+/// - `404 Not Found`: Reserva no encontrada o sin pago por adelantado
+/// - `409 Conflict`: El pago ya no está pendiente o la reserva ya no ocupa la mesa
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/payment/paid")]
+async fn mark_payment_paid(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    payments: web::Data<Payments>,
+    path: web::Path<String>,
+    data: Option<web::Json<PaidPayment>>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let (mut reserva, anterior) = find_with_payment(repo.get_ref(), auth.id(), path.into_inner()).await?;
+    let referencia = data
+        .map(|data| data.into_inner())
+        .unwrap_or_default()
+        .referencia
+        .map(|referencia| referencia.trim().to_string())
+        .filter(|referencia| !referencia.is_empty());
+    if referencia.as_ref().is_some_and(|referencia| referencia.chars().count() > MAX_REFERENCIA) {
+        return Err(AppError::validation_field("referencia", &format!(
+            "La referencia no puede tener más de {} caracteres", MAX_REFERENCIA
+        )));
+    }
+    if anterior.estado != EstadoPago::Pendiente {
+        return Err(AppError::Conflict("El pago ya no está pendiente".to_string()));
+    }
+    if !reserva.estado.is_active() {
+        return Err(AppError::Conflict(format!("No se puede registrar el pago de una reserva {}", reserva.estado)));
+    }
+
+    let now = clock.timestamp();
+    let pago = Pago {
+        estado: EstadoPago::Pagado,
+        proveedor: Some(payments.provider_name().to_string()),
+        referencia,
+        pagado_en: Some(now),
+        ..anterior
+    };
+    let pago_bson = to_bson(&pago)
+        .map_err(|e| AppError::Internal(format!("Error serializando pago: {}", e)))?;
+
+    // Solo si nadie lo ha registrado entre la lectura y la escritura
+    let result = repo.reservas()
+        .update_one(
+            doc! { "_id": reserva.id, "pago.estado": "pendiente" },
+            doc! { "$set": { "pago": pago_bson, "updated_at": now } },
+        )
+        .await
+        .map_err(|e| AppError::database("mark_payment_paid", e))?;
+    if result.matched_count == 0 {
+        return Err(AppError::Conflict("El pago ya no está pendiente".to_string()));
+    }
+
+    let id = reserva.id.unwrap_or_default();
+    let autor = Autor::from(&auth);
+    tracing::info!(reserva = %id, importe = pago.importe_centimos, "Pago por adelantado registrado");
+    events::record(
+        repo.get_ref(),
+        TipoEvento::PagoRegistrado,
+        Some(auth.id()),
+        Some(id),
+        doc! { "importe_centimos": pago.importe_centimos, "proveedor": payments.provider_name() },
+        now,
+    ).await;
+    let antes = audit::snapshot(&reserva);
+    reserva.pago = Some(pago.clone());
+    audit::record(
+        repo.get_ref(),
+        &autor,
+        AccionAuditoria::RegistrarPago,
+        Some(id),
+        antes,
+        audit::snapshot(&reserva),
+        now,
+    ).await;
+
+    if reserva.estado == EstadoReserva::Pendiente && !reserva.pago_pendiente() {
+        let result = repo.reservas()
+            .update_one(
+                doc! { "_id": id, "estado": EstadoReserva::Pendiente },
+                doc! { "$set": { "estado": EstadoReserva::Confirmada, "updated_at": now } },
+            )
+            .await
+            .map_err(|e| AppError::database("confirm_by_payment", e))?;
+
+        if result.modified_count > 0 {
+            tracing::info!(reserva = %id, "Reserva confirmada por pago");
+            events::record(
+                repo.get_ref(),
+                TipoEvento::ReservaConfirmada,
+                Some(auth.id()),
+                Some(id),
+                doc! { "por_pago": true },
+                now,
+            ).await;
+            let antes = audit::snapshot(&reserva);
+            reserva.estado = EstadoReserva::Confirmada;
+            audit::record(
+                repo.get_ref(),
+                &autor,
+                AccionAuditoria::ConfirmarReserva,
+                Some(id),
+                antes,
+                audit::snapshot(&reserva),
+                now,
+            ).await;
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(PaymentResponse {
+        id_reserva: id.to_hex(),
+        estado_reserva: reserva.estado,
+        pago,
+    }))
+}
+
+/// Reembolsa el pago por adelantado de una reserva
+///
+/// Pide el reembolso al proveedor de pagos y, si lo acepta, marca el pago
+/// como reembolsado. No cambia el estado de la reserva: para anularla hay
+/// que cancelarla aparte.
+///
+/// # Autenticación
+/// Requiere permiso de gestión.
+///
+/// # Respuesta
+/// El mismo formato que `POST /reservations/{id}/payment/paid`, con el pago
+/// en estado `reembolsado` y su `reembolsado_en`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido
+/// - `401 Unauthorized`: Token inválido o rol sin permiso
+/// - `404 Not Found`: Reserva no encontrada o sin pago por adelantado
+/// - `409 Conflict`: El pago no está cobrado
+/// - `500 Internal Server Error`: Error de base de datos o del proveedor de pagos
+#[post("/reservations/{id}/payment/refund")]
+async fn refund_payment(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    payments: web::Data<Payments>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let (mut reserva, anterior) = find_with_payment(repo.get_ref(), auth.id(), path.into_inner()).await?;
+    if anterior.estado != EstadoPago::Pagado {
+        return Err(AppError::Conflict("Solo se puede reembolsar un pago cobrado".to_string()));
+    }
+
+    payments.refund(&anterior).await?;
+
+    let now = clock.timestamp();
+    let pago = Pago {
+        estado: EstadoPago::Reembolsado,
+        reembolsado_en: Some(now),
+        ..anterior
+    };
+    let pago_bson = to_bson(&pago)
+        .map_err(|e| AppError::Internal(format!("Error serializando pago: {}", e)))?;
+    let result = repo.reservas()
+        .update_one(
+            doc! { "_id": reserva.id, "pago.estado": "pagado" },
+            doc! { "$set": { "pago": pago_bson, "updated_at": now } },
+        )
+        .await
+        .map_err(|e| AppError::database("refund_payment", e))?;
+    if result.matched_count == 0 {
+        return Err(AppError::Conflict("Solo se puede reembolsar un pago cobrado".to_string()));
+    }
+
+    let id = reserva.id.unwrap_or_default();
+    tracing::info!(reserva = %id, importe = pago.importe_centimos, "Pago por adelantado reembolsado");
+    events::record(
+        repo.get_ref(),
+        TipoEvento::PagoReembolsado,
+        Some(auth.id()),
+        Some(id),
+        doc! { "importe_centimos": pago.importe_centimos },
+        now,
+    ).await;
+    let antes = audit::snapshot(&reserva);
+    reserva.pago = Some(pago.clone());
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::ReembolsarPago,
+        Some(id),
+        antes,
+        audit::snapshot(&reserva),
+        now,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(PaymentResponse {
+        id_reserva: id.to_hex(),
+        estado_reserva: reserva.estado,
+        pago,
+    }))
+}
+
+/// Configura las rutas de depósitos
+///
+/// # Rutas disponibles
+/// - `POST /reservations/{id}/deposit` - Crear depósito dividido
+/// - `GET /reservations/{id}/deposit` - Consultar depósito
+/// - `GET /public/payments/{codigo}` - Consultar una parte (público)
+/// - `POST /payments/webhook` - Registrar un pago (pasarela)
+/// - `POST /reservations/{id}/payment/paid` - Marcar pagado el pago por adelantado
+/// - `POST /reservations/{id}/payment/refund` - Reembolsar el pago por adelantado
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_deposit);
+    cfg.service(get_deposit);
+    cfg.service(get_payment);
+    cfg.service(payment_webhook);
+    cfg.service(mark_payment_paid);
+    cfg.service(refund_payment);
+}
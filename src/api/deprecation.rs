@@ -0,0 +1,185 @@
+//! # Rutas obsoletas
+//!
+//! Registro central de las rutas que se van a retirar ([`RUTAS_OBSOLETAS`]).
+//! Los handlers no cambian: el middleware [`signal_deprecation`] busca cada
+//! petición en el registro y, si su ruta está obsoleta, añade a la respuesta:
+//!
+//! - `Deprecation`: Desde cuándo está obsoleta (RFC 9745, `@<timestamp>`)
+//! - `Sunset`: Cuándo se retirará, si ya hay fecha (RFC 8594)
+//! - `Link`: La ruta que la sustituye, con `rel="successor-version"`
+//!
+//! `GET /public/deprecations` publica el mismo registro con la forma de los
+//! `paths` de OpenAPI (`deprecated: true`), para combinarlo con la
+//! especificación de la API o avisar a los integradores.
+//!
+//! Para marcar una ruta basta con añadirla al registro con el mismo patrón
+//! que en su macro (`/reservations/{id}`); retirarla del todo es quitar el
+//! handler y su entrada.
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{get, web, Error, HttpResponse, Responder};
+use chrono::{NaiveDate, NaiveTime};
+use serde_json::{json, Map, Value};
+
+/// Ruta obsoleta del registro
+#[derive(Debug, Clone, Copy)]
+pub struct RutaObsoleta {
+    /// Método HTTP (`GET`, `POST`...)
+    pub metodo: &'static str,
+    /// Patrón de la ruta, tal y como aparece en su macro
+    pub ruta: &'static str,
+    /// Fecha (`YYYY-MM-DD`) desde la que está obsoleta
+    pub desde: &'static str,
+    /// Fecha (`YYYY-MM-DD`) a partir de la que se retirará
+    pub retirada: Option<&'static str>,
+    /// Ruta que la sustituye
+    pub alternativa: Option<&'static str>,
+}
+
+/// Rutas obsoletas de la API
+pub const RUTAS_OBSOLETAS: &[RutaObsoleta] = &[
+    RutaObsoleta {
+        metodo: "GET",
+        ruta: "/restaurants/all",
+        desde: "2026-10-16",
+        retirada: Some("2027-04-16"),
+        alternativa: Some("/admin/restaurants"),
+    },
+];
+
+/// Busca una ruta en el registro
+pub fn find(metodo: &str, ruta: &str) -> Option<&'static RutaObsoleta> {
+    RUTAS_OBSOLETAS
+        .iter()
+        .find(|obsoleta| obsoleta.metodo.eq_ignore_ascii_case(metodo) && obsoleta.ruta == ruta)
+}
+
+/// Medianoche UTC de una fecha `YYYY-MM-DD`
+fn midnight(fecha: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    NaiveDate::parse_from_str(fecha, "%Y-%m-%d")
+        .ok()
+        .map(|fecha| fecha.and_time(NaiveTime::MIN).and_utc())
+}
+
+/// Cabeceras (en minúsculas) con las que se anuncia una ruta obsoleta
+///
+/// ```
+/// use pispas_reservation::api::deprecation::{headers, RutaObsoleta};
+///
+/// let ruta = RutaObsoleta {
+///     metodo: "GET",
+///     ruta: "/restaurants/all",
+///     desde: "2026-10-16",
+///     retirada: Some("2027-04-16"),
+///     alternativa: Some("/admin/restaurants"),
+/// };
+/// assert_eq!(headers(&ruta), vec![
+///     ("deprecation", "@1792108800".to_string()),
+///     ("sunset", "Fri, 16 Apr 2027 00:00:00 GMT".to_string()),
+///     ("link", "</admin/restaurants>; rel=\"successor-version\"".to_string()),
+/// ]);
+///
+/// let sin_fecha = RutaObsoleta { retirada: None, alternativa: None, ..ruta };
+/// assert_eq!(headers(&sin_fecha).len(), 1);
+/// ```
+pub fn headers(ruta: &RutaObsoleta) -> Vec<(&'static str, String)> {
+    let mut cabeceras = date_headers(ruta.desde, ruta.retirada);
+    if let Some(alternativa) = ruta.alternativa {
+        cabeceras.push(("link", format!("<{}>; rel=\"successor-version\"", alternativa)));
+    }
+    cabeceras
+}
+
+/// Cabeceras `Deprecation` y `Sunset` de algo obsoleto desde `desde` que
+/// se retirará en `retirada` (fechas `YYYY-MM-DD`)
+///
+/// Las usan también las respuestas sin sobre (ver [`super::envelope`]).
+///
+/// ```
+/// use pispas_reservation::api::deprecation::date_headers;
+///
+/// assert_eq!(date_headers("2026-10-16", None), vec![("deprecation", "@1792108800".to_string())]);
+/// ```
+pub fn date_headers(desde: &str, retirada: Option<&str>) -> Vec<(&'static str, String)> {
+    let mut cabeceras = Vec::new();
+    if let Some(desde) = midnight(desde) {
+        cabeceras.push(("deprecation", format!("@{}", desde.timestamp())));
+    }
+    if let Some(retirada) = retirada.and_then(midnight) {
+        cabeceras.push(("sunset", retirada.format("%a, %d %b %Y %H:%M:%S GMT").to_string()));
+    }
+    cabeceras
+}
+
+/// Middleware que anuncia las rutas obsoletas
+///
+/// Ver la documentación del módulo.
+pub async fn signal_deprecation(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let obsoleta = req
+        .match_pattern()
+        .and_then(|patron| find(req.method().as_str(), &patron));
+    if let Some(obsoleta) = obsoleta {
+        tracing::info!(metodo = obsoleta.metodo, ruta = obsoleta.ruta, "Uso de ruta obsoleta");
+    }
+
+    let mut res = next.call(req).await?.map_into_boxed_body();
+    for (nombre, valor) in obsoleta.map(headers).unwrap_or_default() {
+        if let Ok(valor) = HeaderValue::from_str(&valor) {
+            res.headers_mut().insert(HeaderName::from_static(nombre), valor);
+        }
+    }
+    Ok(res)
+}
+
+/// Rutas obsoletas con la forma de los `paths` de OpenAPI
+///
+/// # Autenticación
+/// Ninguna.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "paths": {
+///     "/restaurants/all": {
+///       "get": {
+///         "deprecated": true,
+///         "x-deprecated-since": "2026-10-16",
+///         "x-sunset": "2027-04-16",
+///         "x-successor": "/admin/restaurants"
+///       }
+///     }
+///   }
+/// }
+/// ```
+#[get("/public/deprecations")]
+async fn list_deprecations() -> impl Responder {
+    let mut paths = Map::new();
+    for obsoleta in RUTAS_OBSOLETAS {
+        let operaciones = paths
+            .entry(obsoleta.ruta)
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(operaciones) = operaciones {
+            operaciones.insert(obsoleta.metodo.to_ascii_lowercase(), json!({
+                "deprecated": true,
+                "x-deprecated-since": obsoleta.desde,
+                "x-sunset": obsoleta.retirada,
+                "x-successor": obsoleta.alternativa
+            }));
+        }
+    }
+    HttpResponse::Ok().json(json!({ "paths": paths }))
+}
+
+/// Configura la ruta del registro de rutas obsoletas
+///
+/// # Rutas disponibles
+/// - `GET /public/deprecations` - Rutas obsoletas en formato OpenAPI
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_deprecations);
+}
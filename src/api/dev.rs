@@ -0,0 +1,70 @@
+//! # API de desarrollo
+//!
+//! Endpoints de apoyo para tests end-to-end y demos. Solo se registran con
+//! `DEV_ROUTES=true` y solo responden cuando el proveedor de notificaciones
+//! es `memory`; en cualquier otro caso devuelven 404 como si no existieran.
+//!
+//! Los diagnósticos de la plataforma están en la API de administración
+//! (ver [`super::admin`]), protegida por su propia credencial.
+
+use std::env;
+use actix_web::{delete, get, web, HttpResponse, Responder};
+use super::{AppError, AppResult};
+use crate::notifications::{MemoryProvider, Notifier};
+
+/// Bandeja de salida en memoria o 404 si el proveedor no es `memory`
+fn outbox(notifier: &Notifier) -> AppResult<&MemoryProvider> {
+    notifier
+        .outbox()
+        .ok_or(AppError::NotFound("Recurso no disponible".to_string()))
+}
+
+/// Lista los emails y SMS enviados desde el arranque
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "canal": "email",
+///     "to": "juan@email.com",
+///     "subject": "Confirma tu reserva en La Tasca",
+///     "body": "Hola Juan, ..."
+///   },
+///   { "canal": "sms", "to": "+34 600 000 000", "body": "La Tasca: tu código..." }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `404 Not Found`: El proveedor de notificaciones no es `memory`
+#[get("/dev/notifications")]
+async fn list_notifications(notifier: web::Data<Notifier>) -> AppResult<impl Responder> {
+    Ok(HttpResponse::Ok().json(outbox(notifier.get_ref())?.sent()))
+}
+
+/// Vacía la bandeja de salida en memoria
+///
+/// # Errores
+/// - `404 Not Found`: El proveedor de notificaciones no es `memory`
+#[delete("/dev/notifications")]
+async fn clear_notifications(notifier: web::Data<Notifier>) -> AppResult<impl Responder> {
+    outbox(notifier.get_ref())?.clear();
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Indica si las rutas de desarrollo están activadas (`DEV_ROUTES=true`)
+pub fn enabled() -> bool {
+    env::var("DEV_ROUTES").is_ok_and(|v| v == "true")
+}
+
+/// Configura las rutas de desarrollo, si están activadas (ver [`enabled`])
+///
+/// # Rutas disponibles
+/// - `GET /dev/notifications` - Mensajes enviados (proveedor `memory`)
+/// - `DELETE /dev/notifications` - Vaciar la bandeja de salida
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    if !enabled() {
+        return;
+    }
+    cfg.service(list_notifications);
+    cfg.service(clear_notifications);
+}
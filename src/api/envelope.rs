@@ -0,0 +1,233 @@
+//! # Sobre de las respuestas
+//!
+//! Todas las respuestas JSON van dentro de un sobre, para que los clientes
+//! (el SDK, las integraciones, el frontend) cuenten con una forma estable:
+//!
+//! ```json
+//! {
+//!   "data": [{ "id": "507f1f77bcf86cd799439011", "hora": "21:00" }],
+//!   "meta": {
+//!     "request_id": "5d1c7a6e-3f0b-4b8e-9a51-0c2f7d9e8b14",
+//!     "paginacion": { "total": 240, "pagina": 1, "limite": 100 }
+//!   },
+//!   "warnings": []
+//! }
+//! ```
+//!
+//! - `data`: El cuerpo que devuelve el handler; `null` en los errores
+//! - `error`: Solo en los errores (4xx y 5xx), con el cuerpo de error de
+//!   siempre (ver [`super::errors`])
+//! - `meta.request_id`: ID de la petición (ver [`super::request_id`])
+//! - `meta.paginacion`: Solo en los listados paginados (ver [`paginate`])
+//! - `warnings`: Avisos para el integrador, siempre presente: que la ruta
+//!   está obsoleta (ver [`super::deprecation`]) o que la petición se hizo
+//!   suplantando a la cuenta (ver [`super::impersonation`])
+//!
+//! Los handlers no cambian: el middleware [`wrap_envelope`] construye el
+//! sobre con el cuerpo y las cabeceras de la respuesta. Las respuestas que no
+//! son JSON (calendarios, tickets, el frontend) y las vacías se devuelven tal
+//! cual, y las cabeceras (`X-Request-Id`, `X-Total-Count`...) se mantienen.
+//!
+//! ## Clientes sin sobre
+//!
+//! Los clientes anteriores al sobre siguen recibiendo el objeto o la lista
+//! tal cual si lo piden con `Accept: application/vnd.pispas.legacy+json`
+//! ([`TIPO_SIN_SOBRE`]). Esa forma está obsoleta: sus respuestas llevan las
+//! cabeceras `Deprecation` y `Sunset` con las fechas de [`SIN_SOBRE_DESDE`] y
+//! [`SIN_SOBRE_RETIRADA`], a partir de la cual se responderá siempre con
+//! sobre. Pedir el sobre explícitamente ([`TIPO_SOBRE`]) sigue siendo válido.
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpResponse};
+use serde::Serialize;
+use serde_json::{json, Value};
+use super::deprecation::{self, date_headers, RutaObsoleta};
+use super::request_id;
+
+/// Tipo de contenido del sobre en `Accept` (el que se usa si no se pide otro)
+pub const TIPO_SOBRE: &str = "application/vnd.pispas.envelope+json";
+
+/// Tipo de contenido con el que los clientes antiguos piden las respuestas
+/// sin sobre en `Accept`
+pub const TIPO_SIN_SOBRE: &str = "application/vnd.pispas.legacy+json";
+
+/// Fecha (`YYYY-MM-DD`) desde la que las respuestas sin sobre están obsoletas
+pub const SIN_SOBRE_DESDE: &str = "2026-10-16";
+
+/// Fecha (`YYYY-MM-DD`) a partir de la que se dejarán de servir las
+/// respuestas sin sobre
+pub const SIN_SOBRE_RETIRADA: &str = "2027-04-16";
+
+/// Cabecera con el número total de elementos de un listado paginado
+const CABECERA_TOTAL: &str = "x-total-count";
+
+/// Página devuelta por un listado paginado
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Paginacion {
+    /// Elementos que cumplen los filtros, en todas las páginas
+    pub total: u64,
+    /// Página devuelta, empezando en 1
+    pub pagina: u64,
+    /// Elementos por página
+    pub limite: i64,
+}
+
+/// Metadatos del sobre
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Default)]
+pub struct Meta {
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paginacion: Option<Paginacion>,
+}
+
+/// Marca una respuesta como página de un listado
+///
+/// Añade la cabecera `X-Total-Count` y la paginación que el sobre publica en
+/// `meta.paginacion`.
+pub fn paginate(response: &mut HttpResponse, paginacion: Paginacion) {
+    response.headers_mut().insert(
+        HeaderName::from_static(CABECERA_TOTAL),
+        HeaderValue::from(paginacion.total),
+    );
+    response.extensions_mut().insert(paginacion);
+}
+
+/// Si la respuesta va en el sobre: siempre, salvo que `Accept` pida la
+/// forma sin sobre
+///
+/// ```
+/// use actix_web::http::header::{HeaderMap, HeaderValue, ACCEPT};
+/// use pispas_reservation::api::envelope::wants_envelope;
+///
+/// let mut cabeceras = HeaderMap::new();
+/// assert!(wants_envelope(&cabeceras));
+/// cabeceras.insert(ACCEPT, HeaderValue::from_static("application/json"));
+/// assert!(wants_envelope(&cabeceras));
+/// cabeceras.insert(ACCEPT, HeaderValue::from_static("application/vnd.pispas.legacy+json, application/json"));
+/// assert!(!wants_envelope(&cabeceras));
+/// ```
+pub fn wants_envelope(cabeceras: &HeaderMap) -> bool {
+    !cabeceras.get_all(header::ACCEPT)
+        .filter_map(|valor| valor.to_str().ok())
+        .flat_map(|valor| valor.split(','))
+        .any(|tipo| tipo.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case(TIPO_SIN_SOBRE))
+}
+
+/// Aviso de una ruta obsoleta
+///
+/// ```
+/// use pispas_reservation::api::deprecation::RutaObsoleta;
+/// use pispas_reservation::api::envelope::deprecation_warning;
+///
+/// let ruta = RutaObsoleta {
+///     metodo: "GET",
+///     ruta: "/restaurants/all",
+///     desde: "2026-10-16",
+///     retirada: Some("2027-04-16"),
+///     alternativa: Some("/admin/restaurants"),
+/// };
+/// assert_eq!(
+///     deprecation_warning(&ruta),
+///     "GET /restaurants/all está obsoleta y se retirará el 2027-04-16; usa /admin/restaurants"
+/// );
+/// ```
+pub fn deprecation_warning(ruta: &RutaObsoleta) -> String {
+    let mut aviso = format!("{} {} está obsoleta", ruta.metodo, ruta.ruta);
+    if let Some(retirada) = ruta.retirada {
+        aviso.push_str(&format!(" y se retirará el {}", retirada));
+    }
+    if let Some(alternativa) = ruta.alternativa {
+        aviso.push_str(&format!("; usa {}", alternativa));
+    }
+    aviso
+}
+
+/// Mete un cuerpo JSON en el sobre
+///
+/// ```
+/// use serde_json::json;
+/// use pispas_reservation::api::envelope::{wrap, Meta};
+///
+/// let meta = Meta { request_id: Some("req-1".to_string()), paginacion: None };
+/// assert_eq!(
+///     wrap(json!([1, 2]), false, meta.clone(), Vec::new()),
+///     json!({ "data": [1, 2], "meta": { "request_id": "req-1" }, "warnings": [] })
+/// );
+/// assert_eq!(
+///     wrap(json!({ "error": "No encontrado" }), true, meta, Vec::new())["error"],
+///     json!({ "error": "No encontrado" })
+/// );
+/// ```
+pub fn wrap(cuerpo: Value, es_error: bool, meta: Meta, avisos: Vec<String>) -> Value {
+    let mut sobre = json!({
+        "data": if es_error { Value::Null } else { cuerpo.clone() },
+        "meta": meta,
+        "warnings": avisos,
+    });
+    if es_error {
+        sobre["error"] = cuerpo;
+    }
+    sobre
+}
+
+/// Middleware que mete las respuestas JSON en el sobre
+///
+/// Ver la documentación del módulo.
+pub async fn wrap_envelope(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    if !wants_envelope(req.headers()) {
+        tracing::info!(ruta = req.path(), "Respuesta sin sobre (obsoleta)");
+        let mut res = next.call(req).await?.map_into_boxed_body();
+        // Una ruta obsoleta ya lleva sus propias fechas
+        for (nombre, valor) in date_headers(SIN_SOBRE_DESDE, Some(SIN_SOBRE_RETIRADA)) {
+            let nombre = HeaderName::from_static(nombre);
+            if let (false, Ok(valor)) = (res.headers().contains_key(&nombre), HeaderValue::from_str(&valor)) {
+                res.headers_mut().insert(nombre, valor);
+            }
+        }
+        res.headers_mut().insert(header::VARY, HeaderValue::from_static("accept"));
+        return Ok(res);
+    }
+    let obsoleta = req
+        .match_pattern()
+        .and_then(|patron| deprecation::find(req.method().as_str(), &patron));
+    let request_id = request_id::current();
+
+    let res = next.call(req).await?;
+    let es_json = res.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|valor| valor.to_str().ok())
+        .is_some_and(|tipo| tipo.starts_with("application/json"));
+    if !es_json {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (mut res, cuerpo) = res.into_parts();
+    let bytes = body::to_bytes(cuerpo).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        actix_web::error::ErrorInternalServerError(e.to_string())
+    })?;
+    let Ok(cuerpo) = serde_json::from_slice::<Value>(&bytes) else {
+        return Ok(ServiceResponse::new(req, res.set_body(bytes).map_into_boxed_body()));
+    };
+
+    let mut avisos: Vec<String> = obsoleta.map(deprecation_warning).into_iter().collect();
+    if let Some(admin) = res.headers().get("x-suplantado-por").and_then(|valor| valor.to_str().ok()) {
+        avisos.push(format!("Petición hecha por {} suplantando a la cuenta", admin));
+    }
+    let meta = Meta {
+        request_id,
+        paginacion: res.extensions().get::<Paginacion>().copied(),
+    };
+    let sobre = wrap(cuerpo, res.status().is_client_error() || res.status().is_server_error(), meta, avisos);
+
+    res.headers_mut().insert(header::VARY, HeaderValue::from_static("accept"));
+    let res = res.set_body(sobre.to_string()).map_into_boxed_body();
+    Ok(ServiceResponse::new(req, res))
+}
@@ -2,10 +2,11 @@
 //!
 //! Este módulo muestra el poder de thiserror para crear jerarquías de errores rica
 
+use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
-use std::fmt;
 use std::error::Error; // ← Añadir esta importación
 use thiserror::Error;
+use super::request_id;
 
 /// Tipos de error de la aplicación con contexto mejorado
 #[derive(Error, Debug)]
@@ -44,6 +45,14 @@ pub enum AppError {
     #[error("No autorizado: {0}")]
     Unauthorized(String),
 
+    /// Operación prohibida, con un código estable para que los clientes
+    /// distingan el motivo ("cuenta_suspendida", "cuenta_solo_lectura"...)
+    #[error("Prohibido ({codigo}): {message}")]
+    Forbidden {
+        codigo: String,
+        message: String,
+    },
+
     /// Error de recurso no encontrado
     #[error("No encontrado: {resource_type} con ID '{id}'")]
     NotFoundWithId {
@@ -59,6 +68,30 @@ pub enum AppError {
     #[error("Conflicto: {0}")]
     Conflict(String),
 
+    /// Conflicto con un código estable para que los clientes distingan el
+    /// motivo ("cancelacion_tardia", "modificacion_tardia"...)
+    #[error("Conflicto ({codigo}): {message}")]
+    ConflictWithCode {
+        codigo: String,
+        message: String,
+    },
+
+    /// Demasiadas peticiones, con los segundos a esperar antes de reintentar
+    #[error("Demasiadas peticiones: {message}")]
+    TooManyRequests {
+        message: String,
+        retry_after: u64,
+    },
+
+    /// Recurso bloqueado temporalmente (por ejemplo, una cuenta tras varios
+    /// logins fallidos), con los segundos hasta que se desbloquea
+    #[error("Bloqueado ({codigo}): {message}")]
+    Locked {
+        codigo: String,
+        message: String,
+        retry_after: u64,
+    },
+
     /// Error interno con código de rastreo
     #[error("Error interno (trace: {trace_id}): {message}")]
     InternalWithTrace {
@@ -97,6 +130,22 @@ impl AppError {
         }
     }
 
+    /// Crea un error de operación prohibida con su código
+    pub fn forbidden(codigo: &str, message: &str) -> Self {
+        Self::Forbidden {
+            codigo: codigo.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Crea un error de conflicto con su código
+    pub fn conflict_with_code(codigo: &str, message: &str) -> Self {
+        Self::ConflictWithCode {
+            codigo: codigo.to_string(),
+            message: message.to_string(),
+        }
+    }
+
     /// Crea un error de no encontrado con ID
     pub fn not_found_id(resource_type: &str, id: &str) -> Self {
         Self::NotFoundWithId {
@@ -105,16 +154,54 @@ impl AppError {
         }
     }
 
+    /// Crea un error de demasiadas peticiones con tiempo de espera
+    pub fn too_many_requests(message: &str, retry_after: u64) -> Self {
+        Self::TooManyRequests {
+            message: message.to_string(),
+            retry_after,
+        }
+    }
+
+    /// Crea un error de recurso bloqueado con su código y tiempo de espera
+    pub fn locked(codigo: &str, message: &str, retry_after: u64) -> Self {
+        Self::Locked {
+            codigo: codigo.to_string(),
+            message: message.to_string(),
+            retry_after,
+        }
+    }
+
     /// Crea un error interno con trace ID
+    ///
+    /// Sin trace ID se usa el de la petición en curso (ver
+    /// [`super::request_id`]) para poder buscarlo en los logs, o uno nuevo
+    /// fuera de una petición.
     pub fn internal_trace(message: &str, trace_id: Option<String>) -> Self {
         Self::InternalWithTrace {
-            trace_id: trace_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            trace_id: trace_id
+                .or_else(request_id::current)
+                .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
             message: message.to_string(),
         }
     }
 }
 
 impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::ValidationWithField { .. } | Self::Validation(_) => StatusCode::BAD_REQUEST,
+            Self::UnauthorizedWithContext { .. } | Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden { .. } => StatusCode::FORBIDDEN,
+            Self::NotFoundWithId { .. } | Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) | Self::ConflictWithCode { .. } => StatusCode::CONFLICT,
+            Self::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Locked { .. } => StatusCode::LOCKED,
+            Self::Database { .. } | Self::InternalWithTrace { .. } | Self::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
     fn error_response(&self) -> HttpResponse {
         // Log detallado del error antes de responder
         match self {
@@ -128,6 +215,7 @@ impl ResponseError for AppError {
                 HttpResponse::InternalServerError().json(ErrorResponse {
                     error: "Error de base de datos".to_string(),
                     message: "Error interno del servidor".to_string(),
+                    request_id: request_id::current(),
                 })
             }
             Self::ValidationWithField { field, message } => {
@@ -139,6 +227,39 @@ impl ResponseError for AppError {
                 HttpResponse::BadRequest().json(ErrorResponse {
                     error: "Error de validación".to_string(),
                     message: format!("Campo '{}': {}", field, message),
+                    request_id: request_id::current(),
+                })
+            }
+            Self::Validation(message) => {
+                tracing::warn!(message = %message, "Validation error");
+                HttpResponse::BadRequest().json(ErrorResponse {
+                    error: "Error de validación".to_string(),
+                    message: message.clone(),
+                    request_id: request_id::current(),
+                })
+            }
+            Self::Unauthorized(reason) => {
+                tracing::warn!(reason = %reason, "Unauthorized access attempt");
+                HttpResponse::Unauthorized().json(ErrorResponse {
+                    error: "No autorizado".to_string(),
+                    message: reason.clone(),
+                    request_id: request_id::current(),
+                })
+            }
+            Self::NotFound(message) => {
+                tracing::info!(message = %message, "Resource not found");
+                HttpResponse::NotFound().json(ErrorResponse {
+                    error: "No encontrado".to_string(),
+                    message: message.clone(),
+                    request_id: request_id::current(),
+                })
+            }
+            Self::Conflict(message) => {
+                tracing::info!(message = %message, "Conflict");
+                HttpResponse::Conflict().json(ErrorResponse {
+                    error: "Conflicto".to_string(),
+                    message: message.clone(),
+                    request_id: request_id::current(),
                 })
             }
             Self::UnauthorizedWithContext { operation, reason } => {
@@ -150,8 +271,35 @@ impl ResponseError for AppError {
                 HttpResponse::Unauthorized().json(ErrorResponse {
                     error: "No autorizado".to_string(),
                     message: format!("Operación '{}': {}", operation, reason),
+                    request_id: request_id::current(),
                 })
             }
+            Self::Forbidden { codigo, message } => {
+                tracing::warn!(
+                    codigo = %codigo,
+                    message = %message,
+                    "Forbidden operation"
+                );
+                HttpResponse::Forbidden().json(serde_json::json!({
+                    "error": "Prohibido",
+                    "codigo": codigo,
+                    "message": message,
+                    "request_id": request_id::current()
+                }))
+            }
+            Self::ConflictWithCode { codigo, message } => {
+                tracing::info!(
+                    codigo = %codigo,
+                    message = %message,
+                    "Conflict"
+                );
+                HttpResponse::Conflict().json(serde_json::json!({
+                    "error": "Conflicto",
+                    "codigo": codigo,
+                    "message": message,
+                    "request_id": request_id::current()
+                }))
+            }
             Self::NotFoundWithId { resource_type, id } => {
                 tracing::info!(
                     resource_type = %resource_type,
@@ -161,8 +309,39 @@ impl ResponseError for AppError {
                 HttpResponse::NotFound().json(ErrorResponse {
                     error: "No encontrado".to_string(),
                     message: format!("{} con ID '{}' no encontrado", resource_type, id),
+                    request_id: request_id::current(),
                 })
             }
+            Self::TooManyRequests { message, retry_after } => {
+                tracing::warn!(
+                    message = %message,
+                    retry_after = %retry_after,
+                    "Too many requests"
+                );
+                HttpResponse::TooManyRequests()
+                    .append_header(("Retry-After", retry_after.to_string()))
+                    .json(ErrorResponse {
+                        error: "Demasiadas peticiones".to_string(),
+                        message: message.clone(),
+                        request_id: request_id::current(),
+                    })
+            }
+            Self::Locked { codigo, message, retry_after } => {
+                tracing::warn!(
+                    codigo = %codigo,
+                    retry_after = %retry_after,
+                    "Locked resource"
+                );
+                HttpResponse::build(StatusCode::LOCKED)
+                    .append_header(("Retry-After", retry_after.to_string()))
+                    .json(serde_json::json!({
+                        "error": "Bloqueado",
+                        "codigo": codigo,
+                        "message": message,
+                        "retry_after": retry_after,
+                        "request_id": request_id::current()
+                    }))
+            }
             Self::InternalWithTrace { trace_id, message } => {
                 tracing::error!(
                     trace_id = %trace_id,
@@ -172,18 +351,15 @@ impl ResponseError for AppError {
                 HttpResponse::InternalServerError().json(ErrorResponse {
                     error: "Error interno".to_string(),
                     message: format!("Error interno (trace: {})", trace_id),
+                    request_id: request_id::current(),
                 })
             }
-            // Fallback para otros errores
-            error => {
-                tracing::error!(
-                    error = %error,
-                    error_chain = ?error.source(),
-                    "General error"
-                );
+            Self::Internal(message) => {
+                tracing::error!(message = %message, "Internal error");
                 HttpResponse::InternalServerError().json(ErrorResponse {
                     error: "Error".to_string(),
-                    message: error.to_string(),
+                    message: self.to_string(),
+                    request_id: request_id::current(),
                 })
             }
         }
@@ -194,6 +370,9 @@ impl ResponseError for AppError {
 pub struct ErrorResponse {
     pub error: String,
     pub message: String,
+    /// ID de la petición, el mismo que la cabecera `X-Request-Id`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 pub type AppResult<T> = Result<T, AppError>;
@@ -0,0 +1,256 @@
+//! # Verificación de índices
+//!
+//! Ejecuta `explain` sobre las consultas más frecuentes de la API (tokens,
+//! disponibilidad, listados) y comprueba que el plan ganador usa el índice
+//! previsto. Pensado para ejecutarse contra una copia de producción antes de
+//! desplegar: un índice que se borra o una consulta que cambia de forma y
+//! deja de usarlo aparece aquí como `ok: false`.
+//!
+//! Con colecciones vacías o muy pequeñas el planificador puede elegir
+//! cualquier índice candidato, así que el resultado solo es fiable con datos
+//! representativos.
+//!
+//! Solo se registra con `DEV_ROUTES=true` (ver [`super::dev`]) y además
+//! requiere el token de administración.
+
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+use serde::Serialize;
+use super::{AppError, AppResult};
+use super::admin::require_admin;
+use super::dev;
+use crate::db::{EstadoReserva, MongoRepo};
+
+/// Consulta de la API con el índice que debería usar
+struct ConsultaCanonica {
+    nombre: &'static str,
+    coleccion: &'static str,
+    filtro: Document,
+    /// Nombre del índice previsto (el que genera MongoDB por defecto)
+    indice: &'static str,
+}
+
+/// Consultas que se verifican, con valores de ejemplo en los filtros
+fn canonical_queries() -> Vec<ConsultaCanonica> {
+    let id_restaurante = ObjectId::new();
+    vec![
+        ConsultaCanonica {
+            nombre: "token_sesion",
+            coleccion: "sesiones",
+            filtro: doc! { "token": "token" },
+            indice: "token_1",
+        },
+        ConsultaCanonica {
+            nombre: "token_clave_api",
+            coleccion: "claves_api",
+            filtro: doc! { "token": "live_token" },
+            indice: "token_1",
+        },
+        ConsultaCanonica {
+            nombre: "token_empleado",
+            coleccion: "empleados",
+            filtro: doc! { "access_token": "token" },
+            indice: "access_token_1",
+        },
+        ConsultaCanonica {
+            nombre: "disponibilidad_reservas",
+            coleccion: "reservas",
+            filtro: doc! {
+                "id_restaurante": id_restaurante,
+                "fecha": { "$in": ["2030-06-14", "2030-06-15"] },
+                "estado": { "$in": EstadoReserva::ACTIVOS.to_vec() }
+            },
+            indice: "id_restaurante_1_fecha_1",
+        },
+        ConsultaCanonica {
+            nombre: "disponibilidad_mesas",
+            coleccion: "mesas",
+            filtro: doc! { "id_restaurante": id_restaurante, "id_planta": ObjectId::new() },
+            indice: "id_restaurante_1_id_planta_1",
+        },
+        ConsultaCanonica {
+            nombre: "listado_reservas_fecha",
+            coleccion: "reservas",
+            filtro: doc! { "id_restaurante": id_restaurante, "fecha": "2030-06-15", "estado": "confirmada" },
+            indice: "id_restaurante_1_fecha_1",
+        },
+        ConsultaCanonica {
+            nombre: "localizador",
+            coleccion: "reservas",
+            filtro: doc! { "localizador": "ABCD2345" },
+            indice: "localizador_1",
+        },
+        ConsultaCanonica {
+            nombre: "estadisticas_diarias",
+            coleccion: "stats_daily",
+            filtro: doc! { "id_restaurante": id_restaurante, "fecha": { "$gte": "2030-05-01", "$lte": "2030-05-31" } },
+            indice: "id_restaurante_1_fecha_1",
+        },
+    ]
+}
+
+/// Etapas e índices de un plan de ejecución
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ResumenPlan {
+    /// Etapas del plan, de fuera hacia dentro ("FETCH", "IXSCAN"...)
+    pub etapas: Vec<String>,
+    /// Índices que recorre el plan
+    pub indices: Vec<String>,
+}
+
+/// Recorre un plan de `explain` y resume sus etapas e índices
+///
+/// Admite tanto el formato clásico (`inputStage`/`inputStages`) como el del
+/// motor SBE (`queryPlan`).
+///
+/// ```
+/// use mongodb::bson::doc;
+/// use pispas_reservation::api::explain::summarize_plan;
+///
+/// let plan = doc! {
+///     "stage": "FETCH",
+///     "inputStage": { "stage": "IXSCAN", "indexName": "token_1" }
+/// };
+/// let resumen = summarize_plan(&plan);
+/// assert_eq!(resumen.etapas, vec!["FETCH", "IXSCAN"]);
+/// assert_eq!(resumen.indices, vec!["token_1"]);
+///
+/// let resumen = summarize_plan(&doc! { "queryPlan": { "stage": "COLLSCAN" } });
+/// assert_eq!(resumen.etapas, vec!["COLLSCAN"]);
+/// assert!(resumen.indices.is_empty());
+/// ```
+pub fn summarize_plan(plan: &Document) -> ResumenPlan {
+    fn visit(valor: &Bson, resumen: &mut ResumenPlan) {
+        match valor {
+            Bson::Document(etapa) => {
+                if let Ok(nombre) = etapa.get_str("stage") {
+                    resumen.etapas.push(nombre.to_string());
+                }
+                if let Ok(indice) = etapa.get_str("indexName") {
+                    resumen.indices.push(indice.to_string());
+                }
+                for clave in ["queryPlan", "inputStage", "inputStages"] {
+                    if let Some(hijo) = etapa.get(clave) {
+                        visit(hijo, resumen);
+                    }
+                }
+            }
+            Bson::Array(etapas) => etapas.iter().for_each(|etapa| visit(etapa, resumen)),
+            _ => {}
+        }
+    }
+
+    let mut resumen = ResumenPlan::default();
+    visit(&Bson::Document(plan.clone()), &mut resumen);
+    resumen
+}
+
+/// Resultado de verificar una consulta
+#[derive(Serialize)]
+struct QueryCheck {
+    nombre: &'static str,
+    coleccion: &'static str,
+    indice_previsto: &'static str,
+    indices: Vec<String>,
+    etapas: Vec<String>,
+    /// Si el plan ganador usa el índice previsto
+    ok: bool,
+}
+
+/// Plan ganador de una consulta `find`
+async fn explain(repo: &MongoRepo, consulta: &ConsultaCanonica) -> AppResult<Document> {
+    let resultado = repo.database
+        .run_command(doc! {
+            "explain": { "find": consulta.coleccion, "filter": consulta.filtro.clone() },
+            "verbosity": "queryPlanner",
+        })
+        .await
+        .map_err(|e| AppError::database("explain", e))?;
+
+    resultado.get_document("queryPlanner")
+        .and_then(|planner| planner.get_document("winningPlan"))
+        .cloned()
+        .map_err(|e| AppError::Internal(format!("Respuesta de explain inesperada: {}", e)))
+}
+
+/// Comprueba que las consultas frecuentes usan su índice
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "ok": false,
+///   "consultas": [
+///     {
+///       "nombre": "token_sesion",
+///       "coleccion": "sesiones",
+///       "indice_previsto": "token_1",
+///       "indices": ["token_1"],
+///       "etapas": ["FETCH", "IXSCAN"],
+///       "ok": true
+///     },
+///     {
+///       "nombre": "localizador",
+///       "coleccion": "reservas",
+///       "indice_previsto": "localizador_1",
+///       "indices": [],
+///       "etapas": ["COLLSCAN"],
+///       "ok": false
+///     }
+///   ]
+/// }
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/admin/explain")]
+async fn check_indexes(
+    repo: web::Data<MongoRepo>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+
+    let mut consultas = Vec::new();
+    for consulta in canonical_queries() {
+        let resumen = summarize_plan(&explain(repo.get_ref(), &consulta).await?);
+        let ok = resumen.indices.iter().any(|indice| indice == consulta.indice);
+        if !ok {
+            tracing::warn!(
+                consulta = consulta.nombre,
+                indice = consulta.indice,
+                etapas = ?resumen.etapas,
+                "La consulta no usa el índice previsto"
+            );
+        }
+
+        consultas.push(QueryCheck {
+            nombre: consulta.nombre,
+            coleccion: consulta.coleccion,
+            indice_previsto: consulta.indice,
+            indices: resumen.indices,
+            etapas: resumen.etapas,
+            ok,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "ok": consultas.iter().all(|consulta| consulta.ok),
+        "consultas": consultas
+    })))
+}
+
+/// Configura la ruta de verificación de índices, si las rutas de desarrollo
+/// están activadas (ver [`dev::enabled`])
+///
+/// # Rutas disponibles
+/// - `GET /admin/explain` - Índices usados por las consultas frecuentes
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    if !dev::enabled() {
+        return;
+    }
+    cfg.service(check_indexes);
+}
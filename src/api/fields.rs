@@ -0,0 +1,111 @@
+//! # Selección de campos en los listados
+//!
+//! Los listados aceptan `fields=` con los campos de cada elemento que se
+//! quieren recibir, separados por comas:
+//!
+//! ```text
+//! GET /reservations?fecha=2030-06-15&fields=id,nombre_cliente,hora,estado
+//! ```
+//!
+//! Sirve a clientes ligeros (app del mostrador, reloj) que solo pintan unos
+//! pocos campos. Los nombres que no existen se ignoran y sin `fields` (o
+//! vacío) la respuesta es la completa. Solo se filtran los campos de primer
+//! nivel de cada elemento.
+//!
+//! Los handlers lo reciben como extractor:
+//!
+//! ```ignore
+//! #[get("/tables")]
+//! async fn get_tables(campos: CamposRespuesta, /* ... */) -> AppResult<impl Responder> {
+//!     // ...
+//!     Ok(campos.respond(&mesas))
+//! }
+//! ```
+
+use std::convert::Infallible;
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest, HttpResponse};
+use futures_util::future::{ready, Ready};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Deserialize)]
+struct FieldsQuery {
+    fields: Option<String>,
+}
+
+/// Campos pedidos con `fields=`; `None` si se quiere la respuesta completa
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CamposRespuesta(Option<Vec<String>>);
+
+impl CamposRespuesta {
+    /// Interpreta el valor de `fields`
+    ///
+    /// ```
+    /// use pispas_reservation::api::fields::CamposRespuesta;
+    ///
+    /// assert_eq!(CamposRespuesta::parse(" id, hora ,,"), CamposRespuesta::parse("id,hora"));
+    /// assert_eq!(CamposRespuesta::parse(""), CamposRespuesta::default());
+    /// ```
+    pub fn parse(valor: &str) -> Self {
+        let campos: Vec<String> = valor
+            .split(',')
+            .map(str::trim)
+            .filter(|campo| !campo.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        CamposRespuesta((!campos.is_empty()).then_some(campos))
+    }
+
+    /// Deja en cada objeto (o en cada objeto de una lista) solo los campos pedidos
+    ///
+    /// ```
+    /// use serde_json::json;
+    /// use pispas_reservation::api::fields::CamposRespuesta;
+    ///
+    /// let campos = CamposRespuesta::parse("id,hora,no_existe");
+    /// assert_eq!(
+    ///     campos.select(json!([{ "id": "1", "hora": "21:00", "estado": "confirmada" }])),
+    ///     json!([{ "id": "1", "hora": "21:00" }])
+    /// );
+    /// assert_eq!(CamposRespuesta::default().select(json!({ "id": "1" })), json!({ "id": "1" }));
+    /// ```
+    pub fn select(&self, valor: Value) -> Value {
+        let Some(campos) = &self.0 else {
+            return valor;
+        };
+
+        match valor {
+            Value::Array(elementos) => Value::Array(elementos.into_iter().map(|elemento| self.select(elemento)).collect()),
+            Value::Object(mut objeto) => {
+                objeto.retain(|clave, _| campos.iter().any(|campo| campo == clave));
+                Value::Object(objeto)
+            }
+            otro => otro,
+        }
+    }
+
+    /// Respuesta 200 con el cuerpo serializado y filtrado
+    pub fn respond<T: Serialize>(&self, cuerpo: &T) -> HttpResponse {
+        match (&self.0, serde_json::to_value(cuerpo)) {
+            (Some(_), Ok(valor)) => HttpResponse::Ok().json(self.select(valor)),
+            _ => HttpResponse::Ok().json(cuerpo),
+        }
+    }
+}
+
+impl FromRequest for CamposRespuesta {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let campos = web::Query::<FieldsQuery>::from_query(req.query_string())
+            .ok()
+            .and_then(|query| query.into_inner().fields)
+            .map(|fields| CamposRespuesta::parse(&fields))
+            .unwrap_or_default();
+
+        ready(Ok(campos))
+    }
+}
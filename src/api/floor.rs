@@ -0,0 +1,306 @@
+//! # API de Plantas
+//!
+//! Este módulo gestiona las plantas de los restaurantes con varios pisos;
+//! cada planta tiene su propio lienzo en el plano:
+//! - Listar, crear, renombrar/reordenar y eliminar plantas
+//!
+//! Las mesas indican su planta con `id_planta`; las que no tienen planta
+//! están en la planta principal. Las rutas de mesas (`/tables`,
+//! `/tables/available`, `/tables/clear`, `/tables/diff`) aceptan el
+//! parámetro `planta` para trabajar solo con las mesas de una planta.
+//!
+//! Todas las operaciones requieren autenticación mediante token Bearer.
+
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{FindOptions, ReturnDocument};
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::fields::CamposRespuesta;
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{AuthenticatedRestaurant, EscribirMesas, LeerMesas, PermisoGestion};
+use crate::clock::Clock;
+use crate::db::{MongoRepo, Planta};
+
+/// Estructura para crear o modificar una planta
+#[derive(Deserialize)]
+struct FloorInput {
+    /// Nombre visible de la planta
+    nombre: String,
+    /// Posición en el selector del plano
+    #[serde(default)]
+    orden: i32,
+}
+
+/// Estructura de respuesta para una planta
+#[derive(Serialize)]
+struct FloorResponse {
+    id: String,
+    nombre: String,
+    orden: i32,
+    created_at: i64,
+}
+
+impl From<Planta> for FloorResponse {
+    fn from(planta: Planta) -> Self {
+        FloorResponse {
+            id: planta.id.map(|id| id.to_hex()).unwrap_or_default(),
+            nombre: planta.nombre,
+            orden: planta.orden,
+            created_at: planta.created_at,
+        }
+    }
+}
+
+/// Comprueba que la planta indicada existe en el restaurante
+///
+/// # Retorna
+/// El `ObjectId` de la planta, o `None` si no se indica ninguna
+///
+/// # Errores
+/// - `Validation`: ID de planta inválido
+/// - `NotFound`: La planta no existe en el restaurante
+pub(super) async fn resolve_floor(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    planta: Option<&str>,
+) -> AppResult<Option<ObjectId>> {
+    let Some(planta) = planta else {
+        return Ok(None);
+    };
+
+    let id = ObjectId::parse_str(planta)
+        .map_err(|_| AppError::Validation("ID de planta inválido".to_string()))?;
+
+    repo.plantas()
+        .find_one(doc! { "_id": id, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("resolve_floor", e))?
+        .ok_or(AppError::NotFound("Planta no encontrada".to_string()))?;
+
+    Ok(Some(id))
+}
+
+fn validate_floor(data: &FloorInput) -> AppResult<String> {
+    let nombre = data.nombre.trim();
+    if nombre.is_empty() {
+        return Err(AppError::validation_field("nombre", "El nombre de la planta es requerido"));
+    }
+    Ok(nombre.to_string())
+}
+
+/// Lista las plantas del restaurante, en el orden del selector
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   { "id": "507f1f77bcf86cd799439011", "nombre": "Planta baja", "orden": 0, "created_at": 1717243200 },
+///   { "id": "507f1f77bcf86cd799439012", "nombre": "Primera planta", "orden": 1, "created_at": 1717243260 }
+/// ]
+/// ```
+///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/floors")]
+async fn list_floors(
+    repo: web::Data<MongoRepo>,
+    campos: CamposRespuesta,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerMesas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let options = FindOptions::builder().sort(doc! { "orden": 1, "created_at": 1 }).build();
+    let mut cursor = repo.plantas()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("list_floors", e))?;
+
+    let mut plantas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let planta = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando planta: {}", e)))?;
+        plantas.push(FloorResponse::from(planta));
+    }
+
+    Ok(campos.respond(&plantas))
+}
+
+/// Crea una planta
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Cuerpo
+/// ```json
+/// { "nombre": "Primera planta", "orden": 1 }
+/// ```
+///
+/// # Respuesta
+/// La planta creada, con el mismo formato que `GET /floors`.
+///
+/// # Errores
+/// - `400 Bad Request`: Nombre vacío
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/floors")]
+async fn create_floor(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<FloorInput>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let mut planta = Planta {
+        id: None,
+        id_restaurante: restaurante_id,
+        nombre: validate_floor(&data)?,
+        orden: data.orden,
+        created_at: clock.timestamp(),
+    };
+
+    let result = repo.plantas()
+        .insert_one(&planta)
+        .await
+        .map_err(|e| AppError::database("create_floor", e))?;
+    planta.id = result.inserted_id.as_object_id();
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::CrearPlanta,
+        planta.id,
+        None,
+        audit::snapshot(&planta),
+        planta.created_at,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(FloorResponse::from(planta)))
+}
+
+/// Renombra o reordena una planta
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante propietario.
+///
+/// # Cuerpo
+/// Igual que `POST /floors`.
+///
+/// # Respuesta
+/// La planta actualizada, con el mismo formato que `GET /floors`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido o nombre vacío
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Planta no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[put("/floors/{id}")]
+async fn update_floor(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<FloorInput>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de planta inválido".to_string()))?;
+    let nombre = validate_floor(&data)?;
+
+    let anterior = repo.plantas()
+        .find_one_and_update(
+            doc! { "_id": id, "id_restaurante": restaurante_id },
+            doc! { "$set": { "nombre": &nombre, "orden": data.orden } },
+        )
+        .return_document(ReturnDocument::Before)
+        .await
+        .map_err(|e| AppError::database("update_floor", e))?
+        .ok_or(AppError::NotFound("Planta no encontrada".to_string()))?;
+    let planta = Planta { nombre, orden: data.orden, ..anterior.clone() };
+
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::CambiarPlanta,
+        Some(id),
+        audit::snapshot(&anterior),
+        audit::snapshot(&planta),
+        clock.timestamp(),
+    ).await;
+
+    Ok(HttpResponse::Ok().json(FloorResponse::from(planta)))
+}
+
+/// Elimina una planta vacía
+///
+/// Para no dejar mesas huérfanas, la planta no se puede eliminar mientras
+/// tenga mesas; hay que moverlas o vaciarla antes
+/// (`DELETE /tables/clear?planta=...`).
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante propietario.
+///
+/// # Errores
+/// - `400 Bad Request`: ID de planta inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Planta no encontrada
+/// - `409 Conflict`: La planta todavía tiene mesas
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/floors/{id}")]
+async fn delete_floor(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de planta inválido".to_string()))?;
+
+    let mesas = repo.mesas()
+        .count_documents(doc! { "id_restaurante": restaurante_id, "id_planta": id })
+        .await
+        .map_err(|e| AppError::database("delete_floor", e))?;
+    if mesas > 0 {
+        return Err(AppError::Conflict(format!("La planta todavía tiene {} mesas", mesas)));
+    }
+
+    let planta = repo.plantas()
+        .find_one_and_delete(doc! { "_id": id, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("delete_floor", e))?
+        .ok_or(AppError::NotFound("Planta no encontrada".to_string()))?;
+
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::BorrarPlanta,
+        Some(id),
+        audit::snapshot(&planta),
+        None,
+        clock.timestamp(),
+    ).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Configura las rutas de plantas
+///
+/// # Rutas disponibles
+/// - `GET /floors` - Listar plantas
+/// - `POST /floors` - Crear planta
+/// - `PUT /floors/{id}` - Renombrar o reordenar planta
+/// - `DELETE /floors/{id}` - Eliminar planta vacía
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_floors);
+    cfg.service(create_floor);
+    cfg.service(update_floor);
+    cfg.service(delete_floor);
+}
@@ -0,0 +1,457 @@
+//! # Solicitudes de grupo
+//!
+//! Los grupos grandes y los eventos no reservan una mesa concreta: se
+//! guardan como solicitudes en la colección `solicitudes_grupo`, con los
+//! datos de contacto del cliente, y el personal las gestiona a mano.
+//!
+//! Llegan por dos caminos:
+//! - Directamente desde la web del restaurante
+//!   (`POST /public/restaurants/{id}/group-requests`)
+//! - Desde el widget, cuando una reserva supera el máximo de comensales
+//!   online del restaurante (`configuracion.max_personas_online`) y el
+//!   restaurante ha elegido `grupos_grandes: "solicitud"`; con `"rechazar"`
+//!   el widget devuelve un error que propone repartir el grupo en varias
+//!   reservas ([`crate::availability::split_party`])
+//!
+//! El personal las sigue con `GET /group-requests` y las hace avanzar con
+//! `PUT /group-requests/{id}`:
+//!
+//! ```text
+//! nueva ─┬─> en_conversacion ─┬─> convertida
+//!        │                    └─> rechazada
+//!        ├─> convertida
+//!        └─> rechazada
+//! ```
+//!
+//! Una solicitud `convertida` apunta a la reserva que el personal creó para
+//! el grupo (`id_reserva`).
+
+use actix_web::{get, post, put, web, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{FindOptions, ReturnDocument};
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{AuthenticatedRestaurant, EscribirReservas, LeerReservas, PermisoReservas};
+use super::fields::CamposRespuesta;
+use super::public::find_restaurant;
+use super::reservation::{validate_date, validate_email, validate_time};
+use crate::availability;
+use crate::clock::Clock;
+use crate::db::{EstadoSolicitudGrupo, MongoRepo, SolicitudGrupo};
+use crate::events::{self, TipoEvento};
+use crate::language;
+
+/// Longitud máxima del mensaje del cliente y de la nota del personal
+const LONGITUD_MAXIMA_TEXTO: usize = 2000;
+
+/// Datos de una solicitud de grupo nueva
+///
+/// Es también el cuerpo de `POST /public/restaurants/{id}/group-requests`.
+#[derive(Deserialize)]
+pub(super) struct NuevaSolicitud {
+    pub(super) nombre_cliente: String,
+    pub(super) email_cliente: String,
+    pub(super) telefono_cliente: String,
+    pub(super) numero_personas: i32,
+    /// Fecha deseada (formato YYYY-MM-DD)
+    pub(super) fecha: String,
+    /// Hora deseada (formato HH:MM), si la hay
+    #[serde(default)]
+    pub(super) hora: Option<String>,
+    /// Lo que cuenta el cliente del evento
+    #[serde(default)]
+    pub(super) mensaje: Option<String>,
+    /// Idioma del cliente; si no se envía, se deduce (ver [`crate::language`])
+    #[serde(default)]
+    pub(super) idioma: Option<String>,
+}
+
+/// Estructura de respuesta para una solicitud de grupo
+#[derive(Serialize)]
+struct SolicitudResponse {
+    id: String,
+    nombre_cliente: String,
+    email_cliente: String,
+    telefono_cliente: String,
+    numero_personas: i32,
+    fecha: String,
+    hora: Option<String>,
+    mensaje: Option<String>,
+    estado: EstadoSolicitudGrupo,
+    idioma: Option<String>,
+    id_reserva: Option<String>,
+    nota: Option<String>,
+    created_at: i64,
+    updated_at: i64,
+}
+
+impl From<SolicitudGrupo> for SolicitudResponse {
+    fn from(solicitud: SolicitudGrupo) -> Self {
+        SolicitudResponse {
+            id: solicitud.id.map(|id| id.to_hex()).unwrap_or_default(),
+            nombre_cliente: solicitud.nombre_cliente,
+            email_cliente: solicitud.email_cliente,
+            telefono_cliente: solicitud.telefono_cliente,
+            numero_personas: solicitud.numero_personas,
+            fecha: solicitud.fecha,
+            hora: solicitud.hora,
+            mensaje: solicitud.mensaje,
+            estado: solicitud.estado,
+            idioma: solicitud.idioma,
+            id_reserva: solicitud.id_reserva.map(|id| id.to_hex()),
+            nota: solicitud.nota,
+            created_at: solicitud.created_at,
+            updated_at: solicitud.updated_at,
+        }
+    }
+}
+
+/// Parámetros de consulta para listar solicitudes
+#[derive(Deserialize)]
+struct SolicitudesQuery {
+    /// Filtrar por estado ("nueva", "en_conversacion", "convertida", "rechazada")
+    estado: Option<EstadoSolicitudGrupo>,
+}
+
+/// Cambio de estado de una solicitud
+#[derive(Deserialize)]
+struct UpdateSolicitud {
+    /// Nuevo estado
+    estado: EstadoSolicitudGrupo,
+    /// Reserva creada para el grupo (obligatoria al pasar a "convertida")
+    id_reserva: Option<String>,
+    /// Nota del personal; sustituye a la anterior
+    nota: Option<String>,
+}
+
+/// Mensaje para el cliente de un grupo que supera el máximo online
+pub(super) fn guidance(personas: i32, maximo: i32) -> String {
+    let reparto = availability::split_party(personas, maximo)
+        .iter()
+        .map(i32::to_string)
+        .collect::<Vec<_>>()
+        .join(" + ");
+    format!(
+        "Las reservas online son de hasta {} personas. Para {}, contacta con el restaurante o haz varias reservas ({})",
+        maximo, personas, reparto
+    )
+}
+
+/// Recorta un texto libre y lo descarta si queda vacío
+fn clean_text(campo: &str, texto: Option<String>) -> AppResult<Option<String>> {
+    let texto = texto.map(|texto| texto.trim().to_string()).filter(|texto| !texto.is_empty());
+    if texto.as_ref().is_some_and(|texto| texto.chars().count() > LONGITUD_MAXIMA_TEXTO) {
+        return Err(AppError::validation_field(campo, &format!(
+            "Como mucho {} caracteres", LONGITUD_MAXIMA_TEXTO
+        )));
+    }
+    Ok(texto)
+}
+
+/// Valida y guarda una solicitud de grupo
+///
+/// # Errores
+/// - `Validation`: Faltan datos de contacto o la fecha u hora no son válidas
+/// - `Conflict`: El cliente ya tiene una solicitud abierta para ese día
+pub(super) async fn store(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    datos: NuevaSolicitud,
+    idioma: String,
+    now: i64,
+) -> AppResult<SolicitudGrupo> {
+    if datos.nombre_cliente.trim().is_empty() {
+        return Err(AppError::validation_field("nombre_cliente", "El nombre del cliente es requerido"));
+    }
+    if !validate_email(&datos.email_cliente) {
+        return Err(AppError::validation_field("email_cliente", "Email inválido"));
+    }
+    if datos.telefono_cliente.trim().is_empty() {
+        return Err(AppError::validation_field("telefono_cliente", "El teléfono del cliente es requerido"));
+    }
+    if datos.numero_personas <= 0 {
+        return Err(AppError::validation_field("numero_personas", "El número de personas debe ser mayor a 0"));
+    }
+    validate_date(&datos.fecha)?;
+    if let Some(hora) = &datos.hora {
+        validate_time(hora)?;
+    }
+    let mensaje = clean_text("mensaje", datos.mensaje)?;
+
+    let email = datos.email_cliente.trim().to_string();
+    let abierta = repo.solicitudes_grupo()
+        .find_one(doc! {
+            "id_restaurante": restaurante_id,
+            "email_cliente": &email,
+            "fecha": &datos.fecha,
+            "estado": { "$in": [EstadoSolicitudGrupo::Nueva, EstadoSolicitudGrupo::EnConversacion] },
+        })
+        .await
+        .map_err(|e| AppError::database("store_group_request", e))?;
+    if abierta.is_some() {
+        return Err(AppError::Conflict(
+            "Ya tienes una solicitud de grupo para ese día; el restaurante se pondrá en contacto contigo".to_string(),
+        ));
+    }
+
+    let mut solicitud = SolicitudGrupo {
+        id: None,
+        id_restaurante: restaurante_id,
+        nombre_cliente: datos.nombre_cliente.trim().to_string(),
+        email_cliente: email,
+        telefono_cliente: datos.telefono_cliente.trim().to_string(),
+        numero_personas: datos.numero_personas,
+        fecha: datos.fecha,
+        hora: datos.hora,
+        mensaje,
+        estado: EstadoSolicitudGrupo::Nueva,
+        idioma: Some(idioma),
+        id_reserva: None,
+        nota: None,
+        created_at: now,
+        updated_at: now,
+    };
+    let result = repo.solicitudes_grupo()
+        .insert_one(&solicitud)
+        .await
+        .map_err(|e| AppError::database("store_group_request", e))?;
+    solicitud.id = result.inserted_id.as_object_id();
+
+    events::record(
+        repo,
+        TipoEvento::SolicitudGrupoRecibida,
+        Some(restaurante_id),
+        solicitud.id,
+        doc! { "numero_personas": solicitud.numero_personas, "fecha": &solicitud.fecha },
+        now,
+    ).await;
+
+    Ok(solicitud)
+}
+
+/// Envía una solicitud de grupo o evento desde la web del restaurante
+///
+/// No necesita mesa ni autenticación. Si no se envía el `idioma`, se deduce
+/// como en las reservas del widget.
+///
+/// # Respuesta
+/// `202 Accepted`:
+/// ```json
+/// {
+///   "message": "Solicitud de grupo recibida; el restaurante se pondrá en contacto contigo",
+///   "id": "507f1f77bcf86cd799439015",
+///   "tipo": "solicitud_grupo",
+///   "estado": "nueva"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Datos de validación incorrectos
+/// - `404 Not Found`: Restaurante no encontrado
+/// - `409 Conflict`: El cliente ya tiene una solicitud abierta para ese día
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/public/restaurants/{id}/group-requests")]
+async fn submit_group_request(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<NuevaSolicitud>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    let restaurante_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
+    let restaurant = find_restaurant(repo.get_ref(), restaurante_id).await?;
+
+    let data = data.into_inner();
+    let idioma = language::detect(
+        data.idioma.as_deref(),
+        req.headers().get(header::ACCEPT_LANGUAGE).and_then(|valor| valor.to_str().ok()),
+        &data.telefono_cliente,
+        &restaurant.configuracion.idioma,
+    );
+    let solicitud = store(repo.get_ref(), restaurante_id, data, idioma, clock.timestamp()).await?;
+
+    Ok(accepted(&solicitud))
+}
+
+/// Respuesta al cliente que acaba de enviar una solicitud
+pub(super) fn accepted(solicitud: &SolicitudGrupo) -> HttpResponse {
+    HttpResponse::Accepted().json(serde_json::json!({
+        "message": "Solicitud de grupo recibida; el restaurante se pondrá en contacto contigo",
+        "id": solicitud.id.unwrap_or_default().to_hex(),
+        "tipo": "solicitud_grupo",
+        "estado": solicitud.estado
+    }))
+}
+
+/// Lista las solicitudes de grupo del restaurante, las más recientes primero
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Filtros disponibles
+/// - `estado`: "nueva", "en_conversacion", "convertida" o "rechazada"
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439015",
+///     "nombre_cliente": "Ana Ruiz",
+///     "email_cliente": "ana@email.com",
+///     "telefono_cliente": "+34 600 000 001",
+///     "numero_personas": 30,
+///     "fecha": "2030-06-20",
+///     "hora": "14:00",
+///     "mensaje": "Comida de empresa, con menú cerrado",
+///     "estado": "en_conversacion",
+///     "idioma": "es",
+///     "id_reserva": null,
+///     "nota": "Enviado presupuesto del menú de grupo",
+///     "created_at": 1717243200,
+///     "updated_at": 1717329600
+///   }
+/// ]
+/// ```
+///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
+/// # Errores
+/// - `400 Bad Request`: Estado desconocido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/group-requests")]
+async fn list_group_requests(
+    repo: web::Data<MongoRepo>,
+    query: web::Query<SolicitudesQuery>,
+    campos: CamposRespuesta,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerReservas>,
+) -> AppResult<impl Responder> {
+    let mut filtro = doc! { "id_restaurante": auth.id() };
+    if let Some(estado) = query.estado {
+        filtro.insert("estado", estado);
+    }
+
+    let options = FindOptions::builder().sort(doc! { "created_at": -1, "_id": -1 }).build();
+    let mut cursor = repo.solicitudes_grupo()
+        .find(filtro)
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("list_group_requests", e))?;
+
+    let mut solicitudes = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("list_group_requests", e))? {
+        let solicitud = cursor.deserialize_current().map_err(|e| AppError::database("list_group_requests", e))?;
+        solicitudes.push(SolicitudResponse::from(solicitud));
+    }
+
+    Ok(campos.respond(&solicitudes))
+}
+
+/// Cambia el estado de una solicitud de grupo
+///
+/// Solo se avanza: `nueva` → `en_conversacion` → `convertida` o
+/// `rechazada` (se puede saltar `en_conversacion`). Al pasar a
+/// `convertida` hay que indicar la reserva creada para el grupo. La `nota`
+/// se puede cambiar en cualquier momento repitiendo el estado actual.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Cuerpo
+/// ```json
+/// { "estado": "convertida", "id_reserva": "507f1f77bcf86cd799439011", "nota": "Menú de grupo B" }
+/// ```
+///
+/// # Respuesta
+/// La solicitud actualizada, con el formato de `GET /group-requests`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido, falta `id_reserva` o la reserva no es del restaurante
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Solicitud no encontrada
+/// - `409 Conflict`: La solicitud no puede pasar de su estado al pedido
+/// - `500 Internal Server Error`: Error de base de datos
+#[put("/group-requests/{id}")]
+async fn update_group_request(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<UpdateSolicitud>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de solicitud inválido".to_string()))?;
+    let data = data.into_inner();
+    let now = clock.timestamp();
+
+    let anterior = repo.solicitudes_grupo()
+        .find_one(doc! { "_id": id, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("update_group_request", e))?
+        .ok_or(AppError::NotFound("Solicitud de grupo no encontrada".to_string()))?;
+    if data.estado != anterior.estado && !anterior.estado.can_transition_to(data.estado) {
+        return Err(AppError::Conflict(format!(
+            "Una solicitud {} no puede pasar a {}", anterior.estado.as_str(), data.estado.as_str()
+        )));
+    }
+
+    let mut set = doc! { "estado": data.estado, "updated_at": now };
+    if let Some(nota) = clean_text("nota", data.nota)? {
+        set.insert("nota", nota);
+    }
+    if data.estado == EstadoSolicitudGrupo::Convertida && anterior.estado != EstadoSolicitudGrupo::Convertida {
+        let id_reserva = data.id_reserva
+            .as_deref()
+            .ok_or(AppError::validation_field("id_reserva", "Indica la reserva creada para el grupo"))?;
+        let id_reserva = ObjectId::parse_str(id_reserva)
+            .map_err(|_| AppError::validation_field("id_reserva", "ID de reserva inválido"))?;
+        let existe = repo.reservas()
+            .count_documents(doc! { "_id": id_reserva, "id_restaurante": restaurante_id })
+            .await
+            .map_err(|e| AppError::database("update_group_request", e))?;
+        if existe == 0 {
+            return Err(AppError::validation_field("id_reserva", "La reserva no existe en este restaurante"));
+        }
+        set.insert("id_reserva", id_reserva);
+    }
+
+    // Solo si nadie la ha cambiado desde que la hemos leído
+    let actualizada = repo.solicitudes_grupo()
+        .find_one_and_update(
+            doc! { "_id": id, "id_restaurante": restaurante_id, "updated_at": anterior.updated_at },
+            doc! { "$set": set },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("update_group_request", e))?
+        .ok_or(AppError::Conflict("La solicitud ha cambiado mientras tanto; vuelve a cargarla".to_string()))?;
+
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::CambiarSolicitudGrupo,
+        Some(id),
+        audit::snapshot(&anterior),
+        audit::snapshot(&actualizada),
+        now,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(SolicitudResponse::from(actualizada)))
+}
+
+/// Configura las rutas de las solicitudes de grupo
+///
+/// # Rutas disponibles
+/// - `POST /public/restaurants/{id}/group-requests` - Enviar una solicitud (sin autenticación)
+/// - `GET /group-requests` - Listar las solicitudes del restaurante
+/// - `PUT /group-requests/{id}` - Cambiar el estado o la nota de una solicitud
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(submit_group_request);
+    cfg.service(list_group_requests);
+    cfg.service(update_group_request);
+}
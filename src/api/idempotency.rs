@@ -0,0 +1,276 @@
+//! # Claves de idempotencia
+//!
+//! Las apps móviles con mala cobertura repiten los `POST` cuando no les
+//! llega la respuesta, y cada repetición crearía otra reserva. Con la
+//! cabecera `Idempotency-Key` en `POST /reservations` y `POST /tables`, las
+//! repeticiones reciben la respuesta original en lugar de crear otra vez:
+//!
+//! ```text
+//! POST /reservations
+//! Idempotency-Key: 5d1c7a6e-3f0b-4b8e-9a51-0c2f7d9e8b14
+//! ```
+//!
+//! - La clave es de cada restaurante y se recuerda 24 horas; un índice TTL
+//!   borra las caducadas
+//! - Una repetición con la misma clave y el mismo cuerpo responde con el
+//!   código y el cuerpo guardados, y la cabecera `Idempotent-Replayed: true`
+//! - La misma clave con otra ruta u otro cuerpo responde 400
+//! - Si la primera petición aún no ha terminado, responde 409; si no termina
+//!   en un minuto (el servidor se reinició a medias), la clave se libera
+//! - Los errores del servidor (5xx) no se guardan, para que se puedan
+//!   reintentar
+//!
+//! Sin la cabecera, o sin un token válido, la petición sigue como siempre.
+
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use mongodb::bson::{doc, oid::ObjectId};
+use sha2::{Digest, Sha256};
+use super::{AppError, AppResult};
+use super::request_log::bearer_token;
+use super::staff::resolve_token;
+use crate::clock::Clock;
+use crate::db::{is_duplicate_key, MongoRepo, PeticionIdempotente};
+
+/// Cabecera con la clave de idempotencia
+pub const CABECERA_CLAVE: &str = "Idempotency-Key";
+
+/// Rutas de creación que admiten la cabecera
+const RUTAS: &[&str] = &["/reservations", "/tables"];
+
+/// Longitud máxima de una clave
+const MAX_CLAVE: usize = 255;
+
+/// Tiempo durante el que se recuerda una clave
+const VIGENCIA_SEGUNDOS: i64 = 24 * 3600;
+
+/// Tiempo tras el que una petición que no ha terminado deja libre su clave
+const PLAZO_EN_CURSO_SEGUNDOS: i64 = 60;
+
+/// Huella de una petición: SHA-256 de su método, ruta y cuerpo
+///
+/// ```
+/// use pispas_reservation::api::idempotency::fingerprint;
+///
+/// let huella = fingerprint("POST", "/reservations", br#"{"hora":"21:00"}"#);
+/// assert_eq!(huella.len(), 64);
+/// assert_eq!(huella, fingerprint("POST", "/reservations", br#"{"hora":"21:00"}"#));
+/// assert_ne!(huella, fingerprint("POST", "/tables", br#"{"hora":"21:00"}"#));
+/// assert_ne!(huella, fingerprint("POST", "/reservations", br#"{"hora":"22:00"}"#));
+/// ```
+pub fn fingerprint(method: &str, path: &str, cuerpo: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update(b" ");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(cuerpo);
+    hex::encode(hasher.finalize())
+}
+
+/// Clave de idempotencia de la petición, si es una creación que la admite
+///
+/// # Errores
+/// - `Validation`: La clave está vacía o es demasiado larga
+fn request_key(req: &ServiceRequest) -> AppResult<Option<String>> {
+    if req.method() != Method::POST || !RUTAS.contains(&req.path()) {
+        return Ok(None);
+    }
+    let Some(valor) = req.headers().get(CABECERA_CLAVE) else {
+        return Ok(None);
+    };
+
+    let clave = valor.to_str().unwrap_or_default().trim();
+    if clave.is_empty() || clave.len() > MAX_CLAVE {
+        return Err(AppError::validation_field(
+            CABECERA_CLAVE,
+            &format!("La clave debe tener entre 1 y {} caracteres", MAX_CLAVE),
+        ));
+    }
+    Ok(Some(clave.to_string()))
+}
+
+/// Respuesta para una clave ya usada
+///
+/// # Errores
+/// - `Validation`: La clave se usó con otra petición
+/// - `Conflict`: La petición original aún no ha terminado
+fn replay(guardada: &PeticionIdempotente, huella: &str) -> AppResult<HttpResponse> {
+    if guardada.huella != huella {
+        return Err(AppError::validation_field(
+            CABECERA_CLAVE,
+            "La clave ya se usó con otra petición",
+        ));
+    }
+    let Some(estado) = guardada.estado else {
+        return Err(AppError::Conflict(
+            "La petición con esta clave aún se está procesando".to_string(),
+        ));
+    };
+
+    let estado = StatusCode::from_u16(estado).unwrap_or(StatusCode::OK);
+    Ok(HttpResponse::build(estado)
+        .content_type("application/json")
+        .insert_header(("Idempotent-Replayed", "true"))
+        .body(guardada.respuesta.clone().unwrap_or_default()))
+}
+
+/// Indica si la clave de `guardada` ya no vale en `now`: caducada, o de una
+/// petición que no terminó a tiempo
+fn abandoned(guardada: &PeticionIdempotente, now: i64) -> bool {
+    let plazo = match guardada.estado {
+        Some(_) => VIGENCIA_SEGUNDOS,
+        None => PLAZO_EN_CURSO_SEGUNDOS,
+    };
+    guardada.created_at < now - plazo
+}
+
+/// Reserva la clave para esta petición
+///
+/// Una clave caducada o abandonada (ver [`abandoned`]) se reutiliza; el
+/// índice TTL las borra igualmente, pero solo cada minuto.
+///
+/// # Retorna
+/// `None` si la clave es nueva, o la petición que ya la usa
+async fn claim_key(
+    repo: &MongoRepo,
+    id_restaurante: ObjectId,
+    clave: &str,
+    huella: &str,
+    now: i64,
+) -> AppResult<Option<PeticionIdempotente>> {
+    let peticiones = repo.peticiones_idempotentes();
+    let nueva = PeticionIdempotente {
+        id: None,
+        id_restaurante,
+        clave: clave.to_string(),
+        huella: huella.to_string(),
+        estado: None,
+        respuesta: None,
+        created_at: now,
+        expira_en: Some(mongodb::bson::DateTime::from_millis((now + VIGENCIA_SEGUNDOS) * 1000)),
+    };
+    match peticiones.insert_one(&nueva).await {
+        Ok(_) => return Ok(None),
+        Err(e) if is_duplicate_key(&e) => {}
+        Err(e) => return Err(AppError::database("claim_idempotency_key", e)),
+    }
+
+    let filtro = doc! { "id_restaurante": id_restaurante, "clave": clave };
+    let Some(guardada) = peticiones
+        .find_one(filtro.clone())
+        .await
+        .map_err(|e| AppError::database("claim_idempotency_key", e))?
+    else {
+        return Ok(None);
+    };
+    if !abandoned(&guardada, now) {
+        return Ok(Some(guardada));
+    }
+
+    // Solo la reutiliza una petición, si varias llegan a la vez
+    let reutilizada = peticiones
+        .replace_one(doc! { "_id": guardada.id, "created_at": guardada.created_at }, &nueva)
+        .await
+        .map_err(|e| AppError::database("claim_idempotency_key", e))?;
+    if reutilizada.modified_count == 1 {
+        return Ok(None);
+    }
+    peticiones
+        .find_one(filtro)
+        .await
+        .map_err(|e| AppError::database("claim_idempotency_key", e))
+}
+
+/// Libera la clave que una petición reservó en `reservada_en` y no ha
+/// terminado bien, para que se pueda reintentar
+async fn release_key(repo: &MongoRepo, id_restaurante: ObjectId, clave: &str, reservada_en: i64) {
+    let filtro = doc! { "id_restaurante": id_restaurante, "clave": clave, "created_at": reservada_en };
+    if let Err(e) = repo.peticiones_idempotentes().delete_one(filtro).await {
+        tracing::warn!("No se pudo liberar la clave de idempotencia: {}", e);
+    }
+}
+
+/// Middleware que aplica las claves de idempotencia
+///
+/// Ver la documentación del módulo.
+pub async fn replay_idempotent(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let clave = match request_key(&req) {
+        Ok(Some(clave)) => clave,
+        Ok(None) => return next.call(req).await.map(ServiceResponse::map_into_boxed_body),
+        Err(error) => return Ok(req.error_response(error)),
+    };
+    let (Some(token), Some(repo), Some(clock)) = (
+        bearer_token(&req),
+        req.app_data::<web::Data<MongoRepo>>().cloned(),
+        req.app_data::<web::Data<dyn Clock>>().cloned(),
+    ) else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+    let Ok(identidad) = resolve_token(repo.get_ref(), &token, clock.timestamp()).await else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+    let Some(id_restaurante) = identidad.restaurant.id else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+
+    let cuerpo = req.extract::<web::Bytes>().await?;
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(cuerpo.clone());
+    req.set_payload(payload.into());
+
+    let huella = fingerprint(req.method().as_str(), req.path(), &cuerpo);
+    let reservada_en = clock.timestamp();
+    match claim_key(repo.get_ref(), id_restaurante, &clave, &huella, reservada_en).await {
+        Ok(None) => {}
+        Ok(Some(guardada)) => return Ok(match replay(&guardada, &huella) {
+            Ok(respuesta) => req.into_response(respuesta),
+            Err(error) => req.error_response(error),
+        }),
+        Err(error) => return Ok(req.error_response(error)),
+    }
+
+    let res = match next.call(req).await {
+        Ok(res) => res.map_into_boxed_body(),
+        Err(error) => {
+            release_key(repo.get_ref(), id_restaurante, &clave, reservada_en).await;
+            return Err(error);
+        }
+    };
+    let estado = res.status();
+    if estado.is_server_error() {
+        release_key(repo.get_ref(), id_restaurante, &clave, reservada_en).await;
+        return Ok(res);
+    }
+
+    let (http_req, response) = res.into_parts();
+    let (response, cuerpo) = response.into_parts();
+    let cuerpo = match body::to_bytes(cuerpo).await {
+        Ok(cuerpo) => cuerpo,
+        Err(e) => {
+            release_key(repo.get_ref(), id_restaurante, &clave, reservada_en).await;
+            return Err(actix_web::error::ErrorInternalServerError(e.to_string()));
+        }
+    };
+
+    // Si tardó tanto que otra petición reutilizó la clave, la respuesta es suya
+    let filtro = doc! { "id_restaurante": id_restaurante, "clave": &clave, "created_at": reservada_en };
+    let guardado = repo.peticiones_idempotentes()
+        .update_one(filtro, doc! { "$set": {
+            "estado": i32::from(estado.as_u16()),
+            "respuesta": String::from_utf8_lossy(&cuerpo).into_owned(),
+        } })
+        .await;
+    if let Err(e) = guardado {
+        tracing::warn!("No se pudo guardar la respuesta idempotente: {}", e);
+    }
+
+    let response = response.set_body(cuerpo).map_into_boxed_body();
+    Ok(ServiceResponse::new(http_req, response))
+}
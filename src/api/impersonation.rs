@@ -0,0 +1,207 @@
+//! # Suplantación del propietario por el soporte
+//!
+//! Para ver lo mismo que ve un propietario, el soporte pide con el token de
+//! administración un token de suplantación de su restaurante:
+//!
+//! - `POST /admin/impersonate/{restaurant_id}` - Abre una sesión de suplantación
+//!
+//! El token actúa como el propietario, pero:
+//!
+//! - Caduca a los pocos minutos (30 por defecto, como mucho 240) y no se
+//!   puede renovar; para seguir hay que pedir otro.
+//! - Puede limitarse a unos alcances, igual que las claves de API (ver
+//!   [`super::auth`]).
+//! - Cada operación que hace queda en el registro de auditoría con
+//!   `suplantada_por` (ver [`super::audit`]).
+//! - Todas las respuestas a las peticiones autenticadas con él llevan la
+//!   cabecera `X-Suplantado-Por` con quién del soporte lo usa, para que el
+//!   panel muestre un aviso. `GET /staff/me` lo devuelve también en
+//!   `suplantada_por`.
+//!
+//! La sesión aparece entre las del propietario (`GET /restaurants/sessions`),
+//! que puede cerrarla como cualquier otra.
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{post, web, Error, HttpMessage, HttpRequest, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use serde::Deserialize;
+use serde_json::json;
+use super::{AppError, AppResult};
+use super::admin::require_admin;
+use super::sessions;
+use crate::clock::Clock;
+use crate::db::{Alcance, MongoRepo, Suplantacion};
+use crate::events::{self, TipoEvento};
+
+/// Cabecera que marca las respuestas a un token de suplantación
+pub const CABECERA: &str = "X-Suplantado-Por";
+
+/// Minutos de validez del token si no se indican
+const DURACION_DEFECTO_MINUTOS: u32 = 30;
+
+/// Máximo de minutos de validez del token
+const DURACION_MAXIMA_MINUTOS: u32 = 240;
+
+/// Longitud máxima del nombre de quien suplanta
+const MAX_ADMIN: usize = 64;
+
+/// Marca que deja [`super::auth::AuthenticatedRestaurant`] en las peticiones
+/// autenticadas con un token de suplantación
+pub(super) struct Suplantada(pub String);
+
+/// Cuerpo de `POST /admin/impersonate/{restaurant_id}`
+#[derive(Deserialize)]
+struct NuevaSuplantacion {
+    /// Quién del soporte va a usar el token
+    admin: String,
+    /// Por qué (la incidencia que se atiende...)
+    motivo: String,
+    minutos: Option<u32>,
+    alcances: Option<Vec<Alcance>>,
+}
+
+/// Comprueba que el nombre de quien suplanta se puede enviar en la cabecera
+///
+/// ```
+/// use pispas_reservation::api::impersonation::valid_admin;
+///
+/// assert!(valid_admin("lucia.soporte"));
+/// assert!(!valid_admin(""));
+/// assert!(!valid_admin("lucía"));
+/// ```
+pub fn valid_admin(admin: &str) -> bool {
+    !admin.is_empty()
+        && admin.len() <= MAX_ADMIN
+        && admin.chars().all(|c| c.is_ascii_graphic() || c == ' ')
+}
+
+/// Abre una sesión de suplantación del propietario de un restaurante
+///
+/// # Autenticación
+/// Requiere el token de administración (`ADMIN_TOKEN`).
+///
+/// # Cuerpo
+/// ```json
+/// {
+///   "admin": "lucia",
+///   "motivo": "Incidencia 4821: no ve las reservas del widget",
+///   "minutos": 30,
+///   "alcances": ["reservations:read", "settings:read"]
+/// }
+/// ```
+///
+/// `minutos` (por defecto 30, como mucho 240) y `alcances` (por defecto,
+/// todo lo que puede el propietario) son opcionales.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "access_token": "uuid-token",
+///   "id_restaurante": "507f1f77bcf86cd799439012",
+///   "admin": "lucia",
+///   "alcances": ["reservations:read", "settings:read"],
+///   "expires_at": 1718001800
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido, `admin` o `motivo` vacíos, duración
+///   fuera de rango o lista de alcances vacía
+/// - `401 Unauthorized`: Token de administración ausente o inválido
+/// - `404 Not Found`: La API de administración no está habilitada o el
+///   restaurante no existe
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/admin/impersonate/{restaurant_id}")]
+async fn impersonate(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<NuevaSuplantacion>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    require_admin(&req)?;
+    let id = ObjectId::parse_str(path.as_str())
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
+
+    let admin = data.admin.trim();
+    if !valid_admin(admin) {
+        return Err(AppError::validation_field(
+            "admin",
+            &format!("Indica quién suplanta, con hasta {} caracteres ASCII", MAX_ADMIN),
+        ));
+    }
+    let motivo = data.motivo.trim();
+    if motivo.is_empty() {
+        return Err(AppError::validation_field("motivo", "Indica el motivo de la suplantación"));
+    }
+    let minutos = data.minutos.unwrap_or(DURACION_DEFECTO_MINUTOS);
+    if !(1..=DURACION_MAXIMA_MINUTOS).contains(&minutos) {
+        return Err(AppError::validation_field(
+            "minutos",
+            &format!("La duración debe estar entre 1 y {} minutos", DURACION_MAXIMA_MINUTOS),
+        ));
+    }
+    if data.alcances.as_ref().is_some_and(|alcances| alcances.is_empty()) {
+        return Err(AppError::validation_field("alcances", "Indica al menos un alcance"));
+    }
+
+    repo.restaurants()
+        .find_one(doc! { "_id": id })
+        .await
+        .map_err(|e| AppError::database("impersonate", e))?
+        .ok_or(AppError::not_found_id("Restaurante", path.as_str()))?;
+
+    let now = clock.timestamp();
+    let suplantacion = Suplantacion {
+        admin: admin.to_string(),
+        motivo: motivo.to_string(),
+        alcances: data.alcances.clone(),
+        expires_at: now + i64::from(minutos) * 60,
+    };
+    let expires_at = suplantacion.expires_at;
+    let token = sessions::open_impersonation(repo.get_ref(), id, &req, suplantacion, now).await?;
+
+    events::record(
+        repo.get_ref(),
+        TipoEvento::SuplantacionIniciada,
+        Some(id),
+        Some(id),
+        doc! { "admin": admin, "motivo": motivo, "expires_at": expires_at },
+        now,
+    ).await;
+    tracing::warn!(id_restaurante = %id, admin, motivo, minutos, "Suplantación del propietario iniciada");
+
+    Ok(HttpResponse::Ok().json(json!({
+        "access_token": token,
+        "id_restaurante": id.to_hex(),
+        "admin": admin,
+        "alcances": data.alcances,
+        "expires_at": expires_at
+    })))
+}
+
+/// Middleware que marca las respuestas a los tokens de suplantación
+///
+/// Ver la documentación del módulo.
+pub async fn mark_impersonation(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let mut res = next.call(req).await?.map_into_boxed_body();
+    let admin = res.request().extensions().get::<Suplantada>().map(|suplantada| suplantada.0.clone());
+    if let Some(valor) = admin.and_then(|admin| HeaderValue::from_str(&admin).ok()) {
+        res.headers_mut().insert(HeaderName::from_static("x-suplantado-por"), valor);
+    }
+    Ok(res)
+}
+
+/// Configura las rutas de suplantación
+///
+/// # Rutas disponibles
+/// - `POST /admin/impersonate/{restaurant_id}` - Token de suplantación del propietario
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(impersonate);
+}
@@ -0,0 +1,184 @@
+//! # IPs autorizadas de la API de gestión
+//!
+//! Cada restaurante puede limitar desde qué direcciones se usa su API de
+//! gestión con la lista `ips_permitidas` de su configuración
+//! (`PUT /restaurants/settings`), con IPs sueltas o rangos CIDR:
+//!
+//! ```json
+//! { "ips_permitidas": ["203.0.113.7", "198.51.100.0/24", "2001:db8::/32"] }
+//! ```
+//!
+//! El middleware [`check_ip_allowlist`] se aplica a las peticiones con
+//! `Authorization: Bearer` de un restaurante (del propietario, su personal o
+//! sus claves de API):
+//!
+//! - Con la lista vacía (por defecto) se admite cualquier IP
+//! - Con lista, una petición desde otra IP (o sin dirección remota conocida)
+//!   responde 403 con el código `ip_no_autorizada`
+//!
+//! Las rutas públicas (`/public/*`), los logins y las peticiones sin token no
+//! se limitan. La IP es la de la conexión, o la de `X-Forwarded-For` solo si
+//! la conexión viene de un proxy de confianza (`TRUSTED_PROXIES`, ver
+//! [`super::client_ip`]).
+//!
+//! La lista se guarda en caché unos segundos por token; al cambiar la
+//! configuración se olvida con [`forget_restaurant`].
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use mongodb::bson::oid::ObjectId;
+use super::AppError;
+use super::client_ip::client_ip;
+use super::request_log::bearer_token;
+use super::staff::resolve_token;
+use crate::clock::Clock;
+use crate::db::MongoRepo;
+
+/// Tiempo durante el que se recuerda la lista de un token
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Máximo de tokens en caché; al superarlo se vacía
+const CACHE_MAX: usize = 10_000;
+
+/// Caché token → restaurante, IPs autorizadas y cuándo se consultaron
+type Cache = Mutex<HashMap<String, (ObjectId, Vec<String>, Instant)>>;
+
+fn cache() -> &'static Cache {
+    static CACHE: OnceLock<Cache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Olvida las listas en caché de los tokens de un restaurante
+///
+/// Se llama al cambiar la configuración para que se aplique en la siguiente
+/// petición.
+pub fn forget_restaurant(id: ObjectId) {
+    cache().lock().unwrap().retain(|_, (restaurante, _, _)| *restaurante != id);
+}
+
+/// Interpreta una IP o un rango CIDR como dirección de red y longitud del prefijo
+fn parse_rule(regla: &str) -> Option<(IpAddr, u8)> {
+    let (ip, prefijo) = match regla.trim().split_once('/') {
+        Some((ip, prefijo)) => (ip.parse::<IpAddr>().ok()?, Some(prefijo.parse::<u8>().ok()?)),
+        None => (regla.trim().parse::<IpAddr>().ok()?, None),
+    };
+    let max = if ip.is_ipv4() { 32 } else { 128 };
+    let prefijo = prefijo.unwrap_or(max);
+    if prefijo > max {
+        return None;
+    }
+
+    Some((network(ip, prefijo), prefijo))
+}
+
+/// Dirección de red de una IP con un prefijo no mayor que su longitud
+fn network(ip: IpAddr, prefijo: u8) -> IpAddr {
+    match ip {
+        IpAddr::V4(ip) => IpAddr::V4(Ipv4Addr::from(u32::from(ip) & u32::MAX.checked_shl(32 - u32::from(prefijo)).unwrap_or(0))),
+        IpAddr::V6(ip) => IpAddr::V6(Ipv6Addr::from(u128::from(ip) & u128::MAX.checked_shl(128 - u32::from(prefijo)).unwrap_or(0))),
+    }
+}
+
+/// Normaliza una IP o un rango CIDR
+///
+/// Las IPs sueltas quedan sin prefijo y los rangos con su dirección de red.
+/// Devuelve `None` si no es una IP o un rango válido.
+///
+/// ```
+/// use pispas_reservation::api::ip_allowlist::normalize_rule;
+///
+/// assert_eq!(normalize_rule(" 203.0.113.7 ").as_deref(), Some("203.0.113.7"));
+/// assert_eq!(normalize_rule("203.0.113.7/32").as_deref(), Some("203.0.113.7"));
+/// assert_eq!(normalize_rule("198.51.100.25/24").as_deref(), Some("198.51.100.0/24"));
+/// assert_eq!(normalize_rule("2001:DB8::1/32").as_deref(), Some("2001:db8::/32"));
+/// assert_eq!(normalize_rule("198.51.100.0/33"), None);
+/// assert_eq!(normalize_rule("oficina"), None);
+/// ```
+pub fn normalize_rule(regla: &str) -> Option<String> {
+    let (red, prefijo) = parse_rule(regla)?;
+    let max = if red.is_ipv4() { 32 } else { 128 };
+    Some(if prefijo == max { red.to_string() } else { format!("{}/{}", red, prefijo) })
+}
+
+/// Comprueba si una IP está en alguna de las reglas (las inválidas se ignoran)
+///
+/// ```
+/// use pispas_reservation::api::ip_allowlist::ip_allowed;
+///
+/// let reglas = vec!["203.0.113.7".to_string(), "198.51.100.0/24".to_string()];
+/// assert!(ip_allowed("203.0.113.7".parse().unwrap(), &reglas));
+/// assert!(ip_allowed("198.51.100.200".parse().unwrap(), &reglas));
+/// assert!(!ip_allowed("203.0.113.8".parse().unwrap(), &reglas));
+/// assert!(!ip_allowed("::1".parse().unwrap(), &reglas));
+/// ```
+pub fn ip_allowed(ip: IpAddr, reglas: &[String]) -> bool {
+    reglas.iter()
+        .filter_map(|regla| parse_rule(regla))
+        .any(|(red, prefijo)| red.is_ipv4() == ip.is_ipv4() && network(ip, prefijo) == red)
+}
+
+/// IPs autorizadas del restaurante de un token
+///
+/// `None` si el token no es de ningún restaurante o no se pudo consultar.
+async fn allowed_ips(repo: &MongoRepo, token: &str, now: i64) -> Option<Vec<String>> {
+    if let Some((_, ips, desde)) = cache().lock().unwrap().get(token) {
+        if desde.elapsed() < CACHE_TTL {
+            return Some(ips.clone());
+        }
+    }
+
+    let restaurant = match resolve_token(repo, token, now).await {
+        Ok(identidad) => identidad.restaurant,
+        Err(AppError::Unauthorized(_)) => return None,
+        Err(e) => {
+            tracing::warn!("No se pudieron consultar las IPs autorizadas: {}", e);
+            return None;
+        }
+    };
+    let id = restaurant.id?;
+    let ips = restaurant.configuracion.ips_permitidas;
+
+    let mut cache = cache().lock().unwrap();
+    if cache.len() >= CACHE_MAX {
+        cache.clear();
+    }
+    cache.insert(token.to_string(), (id, ips.clone(), Instant::now()));
+    Some(ips)
+}
+
+/// Middleware que aplica las IPs autorizadas de cada restaurante
+///
+/// Ver la documentación del módulo.
+pub async fn check_ip_allowlist(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let token = bearer_token(&req).filter(|_| !req.path().starts_with("/public/"));
+    let (Some(token), Some(repo), Some(clock)) = (
+        token,
+        req.app_data::<web::Data<MongoRepo>>(),
+        req.app_data::<web::Data<dyn Clock>>(),
+    ) else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+
+    if let Some(ips) = allowed_ips(repo.get_ref(), &token, clock.timestamp()).await {
+        let ip = client_ip(req.request());
+        let autorizada = ips.is_empty() || ip.is_some_and(|ip| ip_allowed(ip, &ips));
+        if !autorizada {
+            let desde = ip.map(|ip| ip.to_string()).unwrap_or_else(|| "una dirección desconocida".to_string());
+            return Ok(req.error_response(AppError::forbidden(
+                "ip_no_autorizada",
+                &format!("La API de este restaurante no admite peticiones desde {}", desde),
+            )));
+        }
+    }
+
+    next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+}
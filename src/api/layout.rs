@@ -0,0 +1,429 @@
+//! # API de Distribuciones de mesas
+//!
+//! Este módulo gestiona las distribuciones con nombre que un restaurante
+//! usa en días concretos (la terraza abierta de viernes a domingo, el
+//! salón montado para un evento...):
+//! - Listar, crear, modificar y eliminar distribuciones
+//! - Consultar la distribución que se usa en una fecha
+//!
+//! Cada distribución indica qué mesas están en servicio y cuándo se usa:
+//! unos días de la semana y/o unas fechas concretas, que tienen prioridad.
+//! Dos distribuciones no pueden compartir día ni fecha. Los días sin
+//! distribución se reserva con todas las mesas reservables.
+//!
+//! La creación y modificación de reservas (panel y widget) y la búsqueda de
+//! mesas disponibles usan la distribución de la fecha de la reserva (ver
+//! [`crate::availability::active_layout`]): una mesa fuera de ella no se
+//! puede reservar ese día.
+//!
+//! Todas las operaciones requieren autenticación mediante token Bearer.
+
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use chrono::NaiveDate;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::fields::CamposRespuesta;
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{AuthenticatedRestaurant, EscribirMesas, LeerMesas, PermisoGestion, PermisoReservas};
+use super::reservation::validate_date;
+use crate::availability;
+use crate::clock::Clock;
+use crate::db::{normalize_name, Distribucion, MongoRepo};
+
+/// Estructura para crear o modificar una distribución
+#[derive(Deserialize)]
+struct LayoutInput {
+    /// Nombre visible ("Terraza de fin de semana")
+    nombre: String,
+    /// Nombres de las mesas en servicio
+    mesas: Vec<String>,
+    /// Días de la semana en que se usa (1 = lunes ... 7 = domingo)
+    #[serde(default)]
+    dias_semana: Vec<u32>,
+    /// Fechas concretas (YYYY-MM-DD) en que se usa
+    #[serde(default)]
+    fechas: Vec<String>,
+}
+
+/// Estructura de respuesta para una distribución
+#[derive(Serialize)]
+struct LayoutResponse {
+    id: String,
+    nombre: String,
+    mesas: Vec<String>,
+    dias_semana: Vec<u32>,
+    fechas: Vec<String>,
+    created_at: i64,
+}
+
+impl From<Distribucion> for LayoutResponse {
+    fn from(distribucion: Distribucion) -> Self {
+        LayoutResponse {
+            id: distribucion.id.map(|id| id.to_hex()).unwrap_or_default(),
+            nombre: distribucion.nombre,
+            mesas: distribucion.mesas,
+            dias_semana: distribucion.dias_semana,
+            fechas: distribucion.fechas,
+            created_at: distribucion.created_at,
+        }
+    }
+}
+
+/// Parámetros de consulta de la distribución de una fecha
+#[derive(Deserialize)]
+struct ActiveQuery {
+    /// Fecha a consultar (formato YYYY-MM-DD)
+    fecha: String,
+}
+
+/// Carga las distribuciones de un restaurante, de la más antigua a la más nueva
+pub(super) async fn load_layouts(repo: &MongoRepo, restaurante_id: ObjectId) -> AppResult<Vec<Distribucion>> {
+    let options = FindOptions::builder().sort(doc! { "created_at": 1 }).build();
+    let mut cursor = repo.distribuciones()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("load_layouts", e))?;
+
+    let mut distribuciones = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        distribuciones.push(cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando distribución: {}", e)))?);
+    }
+
+    Ok(distribuciones)
+}
+
+/// Distribución de mesas que usa un restaurante en una fecha
+///
+/// La usan la creación de reservas y la búsqueda de mesas disponibles.
+pub(super) async fn load_active_layout(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    fecha: NaiveDate,
+) -> AppResult<Option<Distribucion>> {
+    let distribuciones = load_layouts(repo, restaurante_id).await?;
+    Ok(availability::active_layout(&distribuciones, fecha).cloned())
+}
+
+/// Valida una distribución y la construye con sus mesas y fechas normalizadas
+///
+/// # Errores
+/// - `Validation`: Nombre vacío, sin mesas, mesas que no existen en el
+///   restaurante, o días de la semana o fechas inválidos
+/// - `Conflict`: Otra distribución ya se usa alguno de esos días o fechas
+async fn validate_layout(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    data: &LayoutInput,
+    id: Option<ObjectId>,
+    now: i64,
+) -> AppResult<Distribucion> {
+    let nombre = data.nombre.trim();
+    if nombre.is_empty() {
+        return Err(AppError::validation_field("nombre", "El nombre de la distribución es requerido"));
+    }
+    if data.mesas.is_empty() {
+        return Err(AppError::validation_field("mesas", "La distribución debe tener al menos una mesa"));
+    }
+    if let Some(dia) = data.dias_semana.iter().find(|dia| !(1..=7).contains(*dia)) {
+        return Err(AppError::validation_field("dias_semana", &format!(
+            "Día de la semana inválido: {} (usa 1 = lunes ... 7 = domingo)", dia
+        )));
+    }
+
+    let mut fechas = Vec::new();
+    for fecha in &data.fechas {
+        let fecha = validate_date(fecha.trim())?.format(availability::FORMATO_FECHA).to_string();
+        if !fechas.contains(&fecha) {
+            fechas.push(fecha);
+        }
+    }
+    let mut dias_semana = data.dias_semana.clone();
+    dias_semana.sort_unstable();
+    dias_semana.dedup();
+
+    // Las mesas se guardan por nombre normalizado y deben existir
+    let existentes: Vec<String> = repo.mesas()
+        .distinct("nombre", doc! { "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("validate_layout", e))?
+        .iter()
+        .filter_map(|nombre| nombre.as_str().map(normalize_name))
+        .collect();
+    let mut mesas = Vec::new();
+    for mesa in &data.mesas {
+        let normalizado = normalize_name(mesa);
+        if !existentes.contains(&normalizado) {
+            return Err(AppError::validation_field("mesas", &format!("La mesa '{}' no existe", mesa)));
+        }
+        if !mesas.contains(&normalizado) {
+            mesas.push(normalizado);
+        }
+    }
+
+    for otra in load_layouts(repo, restaurante_id).await? {
+        if otra.id.is_some() && otra.id == id {
+            continue;
+        }
+        if let Some(dia) = dias_semana.iter().find(|dia| otra.dias_semana.contains(dia)) {
+            return Err(AppError::Conflict(format!(
+                "La distribución '{}' ya se usa el día {} de la semana", otra.nombre, dia
+            )));
+        }
+        if let Some(fecha) = fechas.iter().find(|fecha| otra.fechas.contains(fecha)) {
+            return Err(AppError::Conflict(format!(
+                "La distribución '{}' ya se usa el {}", otra.nombre, fecha
+            )));
+        }
+    }
+
+    Ok(Distribucion {
+        id,
+        id_restaurante: restaurante_id,
+        nombre: nombre.to_string(),
+        mesas,
+        dias_semana,
+        fechas,
+        created_at: now,
+    })
+}
+
+/// Lista las distribuciones del restaurante
+///
+/// # Autenticación
+/// Requiere permiso `Gestion` (encargado o propietario).
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439011",
+///     "nombre": "Terraza de fin de semana",
+///     "mesas": ["salon 1", "salon 2", "terraza 1", "terraza 2"],
+///     "dias_semana": [5, 6, 7],
+///     "fechas": ["2030-08-15"],
+///     "created_at": 1717243200
+///   }
+/// ]
+/// ```
+///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/layouts")]
+async fn list_layouts(
+    repo: web::Data<MongoRepo>,
+    campos: CamposRespuesta,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerMesas>,
+) -> AppResult<impl Responder> {
+    let distribuciones: Vec<LayoutResponse> = load_layouts(repo.get_ref(), auth.id())
+        .await?
+        .into_iter()
+        .map(LayoutResponse::from)
+        .collect();
+
+    Ok(campos.respond(&distribuciones))
+}
+
+/// Distribución que se usa en una fecha
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// ```json
+/// { "fecha": "2030-06-14", "distribucion": { "id": "507f1f77bcf86cd799439011", "nombre": "Terraza de fin de semana", "...": "..." } }
+/// ```
+///
+/// `distribucion` es `null` si ese día se reserva con todas las mesas.
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/layouts/active")]
+async fn get_active_layout(
+    repo: web::Data<MongoRepo>,
+    query: web::Query<ActiveQuery>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerMesas>,
+) -> AppResult<impl Responder> {
+    let fecha = validate_date(&query.fecha)?;
+    let distribucion = load_active_layout(repo.get_ref(), auth.id(), fecha).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "fecha": fecha.format(availability::FORMATO_FECHA).to_string(),
+        "distribucion": distribucion.map(LayoutResponse::from)
+    })))
+}
+
+/// Crea una distribución
+///
+/// # Autenticación
+/// Requiere permiso `Gestion` (encargado o propietario).
+///
+/// # Cuerpo
+/// ```json
+/// {
+///   "nombre": "Terraza de fin de semana",
+///   "mesas": ["Salón 1", "Salón 2", "Terraza 1", "Terraza 2"],
+///   "dias_semana": [5, 6, 7]
+/// }
+/// ```
+///
+/// # Respuesta
+/// La distribución creada, con el mismo formato que `GET /layouts`.
+///
+/// # Errores
+/// - `400 Bad Request`: Nombre vacío, sin mesas, mesas inexistentes, o días
+///   o fechas inválidos
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `409 Conflict`: Otra distribución ya se usa alguno de esos días o fechas
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/layouts")]
+async fn create_layout(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<LayoutInput>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
+) -> AppResult<impl Responder> {
+    let now = clock.timestamp();
+    let mut distribucion = validate_layout(repo.get_ref(), auth.id(), &data, None, now).await?;
+
+    let result = repo.distribuciones()
+        .insert_one(&distribucion)
+        .await
+        .map_err(|e| AppError::database("create_layout", e))?;
+    distribucion.id = result.inserted_id.as_object_id();
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::CrearDistribucion,
+        distribucion.id,
+        None,
+        audit::snapshot(&distribucion),
+        now,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(LayoutResponse::from(distribucion)))
+}
+
+/// Modifica una distribución
+///
+/// # Autenticación
+/// Requiere permiso `Gestion` (encargado o propietario).
+///
+/// # Cuerpo
+/// Igual que `POST /layouts`; reemplaza la distribución completa.
+///
+/// # Respuesta
+/// La distribución actualizada, con el mismo formato que `GET /layouts`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido o datos inválidos como en `POST /layouts`
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Distribución no encontrada
+/// - `409 Conflict`: Otra distribución ya se usa alguno de esos días o fechas
+/// - `500 Internal Server Error`: Error de base de datos
+#[put("/layouts/{id}")]
+async fn update_layout(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<LayoutInput>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de distribución inválido".to_string()))?;
+
+    let anterior = repo.distribuciones()
+        .find_one(doc! { "_id": id, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("update_layout", e))?
+        .ok_or(AppError::NotFound("Distribución no encontrada".to_string()))?;
+    let now = clock.timestamp();
+    let distribucion = Distribucion {
+        created_at: anterior.created_at,
+        ..validate_layout(repo.get_ref(), restaurante_id, &data, Some(id), now).await?
+    };
+
+    repo.distribuciones()
+        .replace_one(doc! { "_id": id, "id_restaurante": restaurante_id }, &distribucion)
+        .await
+        .map_err(|e| AppError::database("update_layout", e))?;
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::CambiarDistribucion,
+        Some(id),
+        audit::snapshot(&anterior),
+        audit::snapshot(&distribucion),
+        now,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(LayoutResponse::from(distribucion)))
+}
+
+/// Elimina una distribución
+///
+/// Sus días pasan a reservarse con todas las mesas; las reservas ya hechas
+/// no cambian.
+///
+/// # Autenticación
+/// Requiere permiso `Gestion` (encargado o propietario).
+///
+/// # Errores
+/// - `400 Bad Request`: ID de distribución inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Distribución no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/layouts/{id}")]
+async fn delete_layout(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
+) -> AppResult<impl Responder> {
+    let id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de distribución inválido".to_string()))?;
+
+    let distribucion = repo.distribuciones()
+        .find_one_and_delete(doc! { "_id": id, "id_restaurante": auth.id() })
+        .await
+        .map_err(|e| AppError::database("delete_layout", e))?
+        .ok_or(AppError::NotFound("Distribución no encontrada".to_string()))?;
+
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::BorrarDistribucion,
+        Some(id),
+        audit::snapshot(&distribucion),
+        None,
+        clock.timestamp(),
+    ).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Configura las rutas de distribuciones
+///
+/// # Rutas disponibles
+/// - `GET /layouts` - Listar distribuciones
+/// - `GET /layouts/active` - Distribución que se usa en una fecha
+/// - `POST /layouts` - Crear distribución
+/// - `PUT /layouts/{id}` - Modificar distribución
+/// - `DELETE /layouts/{id}` - Eliminar distribución
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_layouts);
+    cfg.service(get_active_layout);
+    cfg.service(create_layout);
+    cfg.service(update_layout);
+    cfg.service(delete_layout);
+}
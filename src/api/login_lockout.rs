@@ -0,0 +1,178 @@
+//! # Bloqueo tras logins fallidos
+//!
+//! Complementa el límite de peticiones por IP ([`super::rate_limit`]) contra
+//! los ataques de fuerza bruta al login de restaurantes
+//! (`POST /restaurants/login`). Los fallos se cuentan en la colección
+//! `intentos_login`, por nombre de restaurante y por IP, de forma que se
+//! aplican aunque el servidor tenga varias instancias:
+//!
+//! - Tras `LOGIN_MAX_FALLOS` fallos de un mismo nombre en la ventana, su
+//!   login queda bloqueado y responde `423 Locked` con el código
+//!   `login_bloqueado`
+//! - Tras `LOGIN_MAX_FALLOS_IP` fallos desde una misma IP (con cualquier
+//!   nombre), esa IP responde `429 Too Many Requests`. La IP es la de la
+//!   conexión, o la de `X-Forwarded-For` solo detrás de un proxy de
+//!   confianza (ver [`super::client_ip`]), para que no se pueda esquivar el
+//!   bloqueo ni bloquear la IP de otro con esa cabecera
+//!
+//! Ambas respuestas llevan la cabecera `Retry-After`. Mientras dura el
+//! bloqueo se rechaza incluso la contraseña correcta; un login correcto
+//! fuera del bloqueo pone a cero los fallos del nombre.
+//!
+//! ## Configuración
+//!
+//! - `LOGIN_MAX_FALLOS`: Fallos por nombre antes de bloquear (default: 5, 0 desactiva)
+//! - `LOGIN_MAX_FALLOS_IP`: Fallos por IP antes de bloquear (default: 20, 0 desactiva)
+//! - `LOGIN_VENTANA_MINUTOS`: Ventana en la que se cuentan los fallos (default: 15)
+//! - `LOGIN_BLOQUEO_MINUTOS`: Duración del bloqueo (default: 15)
+
+use std::env;
+use mongodb::bson::doc;
+use super::{AppError, AppResult};
+use crate::db::{normalize_name, MongoRepo};
+
+/// Política de bloqueo leída del entorno
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoliticaBloqueo {
+    /// Fallos por nombre antes de bloquear (0 desactiva)
+    pub max_fallos: u32,
+    /// Fallos por IP antes de bloquear (0 desactiva)
+    pub max_fallos_ip: u32,
+    pub ventana_segundos: i64,
+    pub bloqueo_segundos: i64,
+}
+
+impl PoliticaBloqueo {
+    /// Lee la política de las variables `LOGIN_*` (ver la documentación del módulo)
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+
+        PoliticaBloqueo {
+            max_fallos: var("LOGIN_MAX_FALLOS", 5),
+            max_fallos_ip: var("LOGIN_MAX_FALLOS_IP", 20),
+            ventana_segundos: var::<i64>("LOGIN_VENTANA_MINUTOS", 15).max(1) * 60,
+            bloqueo_segundos: var::<i64>("LOGIN_BLOQUEO_MINUTOS", 15).max(1) * 60,
+        }
+    }
+}
+
+/// Clave de los fallos de un nombre de restaurante
+fn clave_nombre(nombre: &str) -> String {
+    format!("nombre:{}", normalize_name(nombre))
+}
+
+/// Clave de los fallos de una IP
+fn clave_ip(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+/// Comprueba que ni el nombre ni la IP están bloqueados
+///
+/// # Errores
+/// - `Locked` (`login_bloqueado`): El nombre está bloqueado
+/// - `TooManyRequests`: La IP está bloqueada
+/// - `Database`: Error consultando los fallos
+pub async fn check(
+    repo: &MongoRepo,
+    politica: &PoliticaBloqueo,
+    nombre: &str,
+    ip: Option<&str>,
+    now: i64,
+) -> AppResult<()> {
+    if politica.max_fallos > 0 {
+        let bloqueo = repo.intentos_login()
+            .find_one(doc! { "clave": clave_nombre(nombre), "bloqueado_hasta": { "$gt": now } })
+            .await
+            .map_err(|e| AppError::database("check_login_lockout", e))?;
+        if let Some(bloqueo) = bloqueo {
+            return Err(AppError::locked(
+                "login_bloqueado",
+                "Demasiados intentos fallidos; el login de este restaurante está bloqueado temporalmente",
+                (bloqueo.bloqueado_hasta - now) as u64,
+            ));
+        }
+    }
+
+    if let (true, Some(ip)) = (politica.max_fallos_ip > 0, ip) {
+        let bloqueo = repo.intentos_login()
+            .find_one(doc! { "clave": clave_ip(ip), "bloqueado_hasta": { "$gt": now } })
+            .await
+            .map_err(|e| AppError::database("check_login_lockout", e))?;
+        if let Some(bloqueo) = bloqueo {
+            return Err(AppError::too_many_requests(
+                "Demasiados intentos fallidos desde esta dirección; espera antes de reintentar",
+                (bloqueo.bloqueado_hasta - now) as u64,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Suma un fallo a una clave y la bloquea al llegar a `max_fallos`
+///
+/// Los fallos fuera de la ventana empiezan una cuenta nueva, y al bloquear
+/// la cuenta vuelve a cero para después del bloqueo.
+async fn add_failure(repo: &MongoRepo, politica: &PoliticaBloqueo, clave: String, max_fallos: u32, now: i64) -> AppResult<()> {
+    let pipeline = vec![
+        doc! { "$set": {
+            "en_ventana": { "$gt": [{ "$ifNull": ["$ventana_desde", 0] }, now - politica.ventana_segundos] },
+        } },
+        doc! { "$set": {
+            "fallos": { "$cond": ["$en_ventana", { "$add": ["$fallos", 1] }, 1] },
+            "ventana_desde": { "$cond": ["$en_ventana", "$ventana_desde", now] },
+        } },
+        doc! { "$set": {
+            "bloquear": { "$gte": ["$fallos", i64::from(max_fallos)] },
+        } },
+        doc! { "$set": {
+            "bloqueado_hasta": { "$cond": ["$bloquear", now + politica.bloqueo_segundos, { "$ifNull": ["$bloqueado_hasta", 0] }] },
+            "fallos": { "$cond": ["$bloquear", 0, "$fallos"] },
+        } },
+        doc! { "$unset": ["en_ventana", "bloquear"] },
+    ];
+
+    repo.intentos_login()
+        .update_one(doc! { "clave": &clave }, pipeline)
+        .upsert(true)
+        .await
+        .map_err(|e| AppError::database("record_login_failure", e))?;
+
+    Ok(())
+}
+
+/// Registra un login fallido del nombre y la IP
+///
+/// # Errores
+/// - `Database`: Error guardando los fallos
+pub async fn record_failure(
+    repo: &MongoRepo,
+    politica: &PoliticaBloqueo,
+    nombre: &str,
+    ip: Option<&str>,
+    now: i64,
+) -> AppResult<()> {
+    if politica.max_fallos > 0 {
+        add_failure(repo, politica, clave_nombre(nombre), politica.max_fallos, now).await?;
+    }
+    if let (true, Some(ip)) = (politica.max_fallos_ip > 0, ip) {
+        add_failure(repo, politica, clave_ip(ip), politica.max_fallos_ip, now).await?;
+    }
+
+    Ok(())
+}
+
+/// Pone a cero los fallos de un nombre tras un login correcto
+///
+/// # Errores
+/// - `Database`: Error borrando los fallos
+pub async fn record_success(repo: &MongoRepo, nombre: &str) -> AppResult<()> {
+    repo.intentos_login()
+        .delete_one(doc! { "clave": clave_nombre(nombre) })
+        .await
+        .map_err(|e| AppError::database("reset_login_failures", e))?;
+
+    Ok(())
+}
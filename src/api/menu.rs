@@ -0,0 +1,413 @@
+//! # API de Menú y preselección
+//!
+//! Este módulo gestiona las opciones de menú que los clientes pueden elegir
+//! por adelantado al reservar (menús degustación, maridajes, platos por
+//! encargo...):
+//! - Listar, crear y desactivar opciones de menú
+//! - Validar la preselección enviada al crear una reserva
+//! - Resumen para cocina de la preselección de un día
+//!
+//! Todas las operaciones requieren autenticación mediante token Bearer; el
+//! widget consulta las opciones activas en `GET /public/restaurants/{id}/menu-options`.
+
+use std::collections::BTreeMap;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::fields::CamposRespuesta;
+use super::auth::{AuthenticatedRestaurant, EscribirMenu, LeerMenu, PermisoGestion};
+use super::reservation::validate_date;
+use crate::clock::Clock;
+use crate::db::{EstadoReserva, MongoRepo, OpcionMenu, SeleccionMenu};
+
+/// Máximo de unidades de una opción en una reserva
+const MAX_CANTIDAD: u32 = 100;
+
+/// Estructura para crear una opción de menú
+#[derive(Deserialize)]
+struct CreateMenuOption {
+    nombre: String,
+    #[serde(default)]
+    descripcion: String,
+    /// Precio en céntimos (opcional)
+    precio_centimos: Option<i64>,
+}
+
+/// Estructura de respuesta para una opción de menú
+#[derive(Serialize)]
+pub(super) struct MenuOptionResponse {
+    id: String,
+    nombre: String,
+    descripcion: String,
+    precio_centimos: Option<i64>,
+    activa: bool,
+}
+
+impl From<OpcionMenu> for MenuOptionResponse {
+    fn from(opcion: OpcionMenu) -> Self {
+        MenuOptionResponse {
+            id: opcion.id.map(|id| id.to_hex()).unwrap_or_default(),
+            nombre: opcion.nombre,
+            descripcion: opcion.descripcion,
+            precio_centimos: opcion.precio_centimos,
+            activa: opcion.activa,
+        }
+    }
+}
+
+/// Opción elegida en el cuerpo de una reserva
+#[derive(Deserialize)]
+pub(super) struct SeleccionInput {
+    /// ID de la opción de menú
+    id_opcion: String,
+    /// Unidades de la opción
+    cantidad: u32,
+}
+
+/// Carga las opciones de menú de un restaurante, por nombre
+pub(super) async fn load_options(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    solo_activas: bool,
+) -> AppResult<Vec<OpcionMenu>> {
+    let mut filtro = doc! { "id_restaurante": restaurante_id };
+    if solo_activas {
+        filtro.insert("activa", true);
+    }
+
+    let options = FindOptions::builder().sort(doc! { "nombre": 1 }).build();
+    let mut cursor = repo.opciones_menu()
+        .find(filtro)
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("load_menu_options", e))?;
+
+    let mut opciones = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        opciones.push(cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando opción de menú: {}", e)))?);
+    }
+
+    Ok(opciones)
+}
+
+/// Valida la preselección de una reserva contra las opciones del restaurante
+///
+/// Las opciones repetidas se agrupan sumando sus cantidades.
+///
+/// # Errores
+/// - `Validation`: ID inválido, cantidad fuera de rango u opción inexistente o inactiva
+pub(super) async fn resolve_preselection(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    seleccion: &[SeleccionInput],
+) -> AppResult<Vec<SeleccionMenu>> {
+    if seleccion.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let opciones = load_options(repo, restaurante_id, true).await?;
+    let mut resultado: Vec<SeleccionMenu> = Vec::new();
+
+    for item in seleccion {
+        let id_opcion = ObjectId::parse_str(&item.id_opcion)
+            .map_err(|_| AppError::validation_field("preseleccion", "ID de opción inválido"))?;
+
+        if item.cantidad == 0 || item.cantidad > MAX_CANTIDAD {
+            return Err(AppError::validation_field("preseleccion", &format!(
+                "La cantidad debe estar entre 1 y {}", MAX_CANTIDAD
+            )));
+        }
+
+        let opcion = opciones.iter()
+            .find(|opcion| opcion.id == Some(id_opcion))
+            .ok_or(AppError::validation_field("preseleccion", &format!(
+                "La opción {} no existe o no está disponible", item.id_opcion
+            )))?;
+
+        match resultado.iter_mut().find(|s| s.id_opcion == id_opcion) {
+            Some(existente) => existente.cantidad = (existente.cantidad + item.cantidad).min(MAX_CANTIDAD),
+            None => resultado.push(SeleccionMenu {
+                id_opcion,
+                nombre: opcion.nombre.clone(),
+                cantidad: item.cantidad,
+            }),
+        }
+    }
+
+    Ok(resultado)
+}
+
+/// Lista las opciones de menú del restaurante autenticado (activas e inactivas)
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439011",
+///     "nombre": "Menú degustación",
+///     "descripcion": "9 pases",
+///     "precio_centimos": 8500,
+///     "activa": true
+///   }
+/// ]
+/// ```
+///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/menu-options")]
+async fn list_menu_options(
+    repo: web::Data<MongoRepo>,
+    campos: CamposRespuesta,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerMenu>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let opciones: Vec<MenuOptionResponse> = load_options(repo.get_ref(), restaurante_id, false)
+        .await?
+        .into_iter()
+        .map(MenuOptionResponse::from)
+        .collect();
+
+    Ok(campos.respond(&opciones))
+}
+
+/// Crea una opción de menú
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Validaciones
+/// - El nombre no puede estar vacío
+/// - El precio, si se indica, no puede ser negativo
+///
+/// # Respuesta
+/// La opción creada, con el mismo formato que `GET /menu-options`.
+///
+/// # Errores
+/// - `400 Bad Request`: Datos de validación incorrectos
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/menu-options")]
+async fn create_menu_option(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<CreateMenuOption>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMenu>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    if data.nombre.trim().is_empty() {
+        return Err(AppError::validation_field("nombre", "El nombre no puede estar vacío"));
+    }
+    if data.precio_centimos.is_some_and(|precio| precio < 0) {
+        return Err(AppError::validation_field("precio_centimos", "El precio no puede ser negativo"));
+    }
+
+    let mut opcion = OpcionMenu {
+        id: None,
+        id_restaurante: restaurante_id,
+        nombre: data.nombre.trim().to_string(),
+        descripcion: data.descripcion.trim().to_string(),
+        precio_centimos: data.precio_centimos,
+        activa: true,
+        created_at: clock.timestamp(),
+    };
+
+    let result = repo.opciones_menu()
+        .insert_one(&opcion)
+        .await
+        .map_err(|e| AppError::database("create_menu_option", e))?;
+    opcion.id = result.inserted_id.as_object_id();
+
+    Ok(HttpResponse::Ok().json(MenuOptionResponse::from(opcion)))
+}
+
+/// Desactiva una opción de menú
+///
+/// La opción deja de ofrecerse en nuevas reservas; las reservas que ya la
+/// incluyen la conservan.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante propietario.
+///
+/// # Errores
+/// - `400 Bad Request`: ID de opción inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Opción no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/menu-options/{id}")]
+async fn deactivate_menu_option(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMenu>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de opción inválido".to_string()))?;
+
+    let result = repo.opciones_menu()
+        .update_one(
+            doc! { "_id": id, "id_restaurante": restaurante_id },
+            doc! { "$set": { "activa": false } },
+        )
+        .await
+        .map_err(|e| AppError::database("deactivate_menu_option", e))?;
+
+    if result.matched_count == 0 {
+        return Err(AppError::NotFound("Opción de menú no encontrada".to_string()));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Parámetros del resumen de cocina
+#[derive(Deserialize)]
+struct KitchenQuery {
+    /// Fecha a consultar (formato YYYY-MM-DD)
+    fecha: String,
+}
+
+/// Opción elegida (o total de unidades de una opción) con el ID como texto
+#[derive(Serialize)]
+pub(super) struct SeleccionResponse {
+    id_opcion: String,
+    nombre: String,
+    cantidad: u32,
+}
+
+impl From<SeleccionMenu> for SeleccionResponse {
+    fn from(seleccion: SeleccionMenu) -> Self {
+        SeleccionResponse {
+            id_opcion: seleccion.id_opcion.to_hex(),
+            nombre: seleccion.nombre,
+            cantidad: seleccion.cantidad,
+        }
+    }
+}
+
+/// Reserva con preselección en el resumen de cocina
+#[derive(Serialize)]
+struct KitchenReservation {
+    id: String,
+    hora: String,
+    id_mesa: String,
+    nombre_cliente: String,
+    numero_personas: i32,
+    preseleccion: Vec<SeleccionResponse>,
+}
+
+/// Resumen para cocina de la preselección de un día
+///
+/// Incluye solo las reservas no canceladas con alguna opción elegida,
+/// ordenadas por hora, y el total de unidades de cada opción.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "fecha": "2024-06-15",
+///   "totales": [
+///     { "id_opcion": "507f1f77bcf86cd799439011", "nombre": "Menú degustación", "cantidad": 6 }
+///   ],
+///   "reservas": [
+///     {
+///       "id": "507f1f77bcf86cd799439012",
+///       "hora": "21:00",
+///       "id_mesa": "507f1f77bcf86cd799439013",
+///       "nombre_cliente": "Juan Pérez",
+///       "numero_personas": 4,
+///       "preseleccion": [
+///         { "id_opcion": "507f1f77bcf86cd799439011", "nombre": "Menú degustación", "cantidad": 4 }
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/kitchen/preorders")]
+async fn kitchen_preorders(
+    repo: web::Data<MongoRepo>,
+    query: web::Query<KitchenQuery>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerMenu>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    validate_date(&query.fecha)?;
+
+    let options = FindOptions::builder().sort(doc! { "hora": 1 }).build();
+    let mut cursor = repo.reservas()
+        .find(doc! {
+            "id_restaurante": restaurante_id,
+            "fecha": &query.fecha,
+            "estado": { "$nin": [EstadoReserva::Cancelada, EstadoReserva::NoShow] },
+            "preseleccion.0": { "$exists": true },
+        })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("kitchen_preorders", e))?;
+
+    let mut totales: BTreeMap<String, SeleccionResponse> = BTreeMap::new();
+    let mut reservas = Vec::new();
+
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let reserva = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?;
+
+        for seleccion in &reserva.preseleccion {
+            totales.entry(seleccion.id_opcion.to_hex())
+                .or_insert_with(|| SeleccionResponse {
+                    id_opcion: seleccion.id_opcion.to_hex(),
+                    nombre: seleccion.nombre.clone(),
+                    cantidad: 0,
+                })
+                .cantidad += seleccion.cantidad;
+        }
+
+        reservas.push(KitchenReservation {
+            id: reserva.id.map(|id| id.to_hex()).unwrap_or_default(),
+            hora: reserva.hora,
+            id_mesa: reserva.id_mesa.to_hex(),
+            nombre_cliente: reserva.nombre_cliente,
+            numero_personas: reserva.numero_personas,
+            preseleccion: reserva.preseleccion.into_iter().map(SeleccionResponse::from).collect(),
+        });
+    }
+
+    let mut totales: Vec<SeleccionResponse> = totales.into_values().collect();
+    totales.sort_by(|a, b| a.nombre.cmp(&b.nombre));
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "fecha": query.fecha,
+        "totales": totales,
+        "reservas": reservas
+    })))
+}
+
+/// Configura las rutas de menú
+///
+/// # Rutas disponibles
+/// - `GET /menu-options` - Listar opciones de menú
+/// - `POST /menu-options` - Crear opción de menú
+/// - `DELETE /menu-options/{id}` - Desactivar opción de menú
+/// - `GET /kitchen/preorders?fecha=...` - Resumen de preselección para cocina
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_menu_options);
+    cfg.service(create_menu_option);
+    cfg.service(deactivate_menu_option);
+    cfg.service(kitchen_preorders);
+}
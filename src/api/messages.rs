@@ -0,0 +1,508 @@
+//! # Mensajes de reservas
+//!
+//! Hilo de mensajes entre el cliente y el restaurante sobre una reserva,
+//! para resolver dudas (una trona, un retraso, una alergia) sin llamadas:
+//!
+//! - El cliente escribe y lee el hilo desde su enlace de gestión
+//!   (`/public/reservations/{token}/messages`, ver [`super::public`])
+//! - El personal responde desde el panel (`/reservations/{id}/messages`) y
+//!   ve qué reservas tienen mensajes sin leer (`/reservations/messages/unread`)
+//!
+//! Cada mensaje se marca como leído cuando la otra parte abre el hilo. Al
+//! restaurante se le avisa por email (a `email_alertas` o, si no hay, al
+//! email de la cuenta) del primer mensaje sin leer de cada reserva, no de
+//! cada uno; al cliente, de cada respuesta, con el enlace al hilo.
+//!
+//! Para frenar abusos, el cliente no puede acumular más de
+//! [`MAX_MENSAJES_SIN_LEER`] mensajes que el restaurante no haya leído. Los
+//! mensajes se borran con la anonimización de las reservas antiguas (ver
+//! [`crate::jobs::anonymization`]).
+
+use actix_web::{get, post, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::auth::{AuthenticatedRestaurant, EscribirReservas, LeerReservas, PermisoReservas};
+use super::public::{find_by_token, find_restaurant, manage_token, public_base_url};
+use crate::clock::Clock;
+use crate::db::{AutorMensaje, MensajeReserva, MongoRepo, Reserva, Restaurant};
+use crate::notifications::{EmailMessage, Notifier};
+
+/// Caracteres máximos de un mensaje
+const LONGITUD_MAXIMA_MENSAJE: usize = 1000;
+
+/// Mensajes del cliente sin leer por el restaurante que puede acumular
+pub const MAX_MENSAJES_SIN_LEER: u64 = 5;
+
+/// Cuerpo para escribir un mensaje
+#[derive(Deserialize)]
+struct NewMessage {
+    texto: String,
+}
+
+/// Mensaje en la respuesta
+#[derive(Serialize)]
+struct MessageResponse {
+    id: String,
+    autor: AutorMensaje,
+    /// Usuario del personal que respondió; no se muestra al cliente
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usuario: Option<String>,
+    texto: String,
+    leido_en: Option<i64>,
+    created_at: i64,
+}
+
+impl From<MensajeReserva> for MessageResponse {
+    fn from(mensaje: MensajeReserva) -> Self {
+        MessageResponse {
+            id: mensaje.id.map(|id| id.to_hex()).unwrap_or_default(),
+            autor: mensaje.autor,
+            usuario: mensaje.usuario,
+            texto: mensaje.texto,
+            leido_en: mensaje.leido_en,
+            created_at: mensaje.created_at,
+        }
+    }
+}
+
+/// Reserva con mensajes del cliente sin leer
+#[derive(Serialize)]
+struct UnreadResponse {
+    id_reserva: String,
+    no_leidos: u64,
+    /// Momento del último mensaje sin leer
+    ultimo_mensaje_en: i64,
+}
+
+/// Valida el texto de un mensaje; lo devuelve sin espacios alrededor
+fn validate_text(texto: &str) -> AppResult<String> {
+    let texto = texto.trim();
+    if texto.is_empty() {
+        return Err(AppError::validation_field("texto", "El mensaje no puede estar vacío"));
+    }
+    if texto.chars().count() > LONGITUD_MAXIMA_MENSAJE {
+        return Err(AppError::validation_field(
+            "texto",
+            &format!("El mensaje no puede superar {} caracteres", LONGITUD_MAXIMA_MENSAJE),
+        ));
+    }
+    Ok(texto.to_string())
+}
+
+/// Marca como leídos los mensajes de una reserva que escribió `autor`
+///
+/// # Retorna
+/// Cuántos había sin leer
+async fn mark_read(repo: &MongoRepo, id_reserva: ObjectId, autor: AutorMensaje, now: i64) -> AppResult<u64> {
+    repo.mensajes_reserva()
+        .update_many(
+            doc! { "id_reserva": id_reserva, "autor": autor, "leido_en": { "$exists": false } },
+            doc! { "$set": { "leido_en": now } },
+        )
+        .await
+        .map(|result| result.modified_count)
+        .map_err(|e| AppError::database("mark_messages_read", e))
+}
+
+/// Mensajes de una reserva, del más antiguo al más reciente
+///
+/// Marca antes como leídos los que escribió `autor_leido`, y devuelve
+/// cuántos había sin leer.
+async fn load_thread(
+    repo: &MongoRepo,
+    id_reserva: ObjectId,
+    autor_leido: AutorMensaje,
+    now: i64,
+) -> AppResult<(Vec<MensajeReserva>, u64)> {
+    let leidos = mark_read(repo, id_reserva, autor_leido, now).await?;
+
+    let mut cursor = repo.mensajes_reserva()
+        .find(doc! { "id_reserva": id_reserva })
+        .sort(doc! { "created_at": 1, "_id": 1 })
+        .await
+        .map_err(|e| AppError::database("load_messages", e))?;
+    let mut mensajes = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("load_messages", e))? {
+        mensajes.push(cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando mensaje: {}", e)))?);
+    }
+
+    Ok((mensajes, leidos))
+}
+
+/// Mensajes del cliente de una reserva que el restaurante no ha leído
+async fn count_unread(repo: &MongoRepo, id_reserva: ObjectId) -> AppResult<u64> {
+    repo.mensajes_reserva()
+        .count_documents(doc! {
+            "id_reserva": id_reserva,
+            "autor": AutorMensaje::Cliente,
+            "leido_en": { "$exists": false },
+        })
+        .await
+        .map_err(|e| AppError::database("count_unread_messages", e))
+}
+
+/// Guarda un mensaje
+async fn insert_message(repo: &MongoRepo, mut mensaje: MensajeReserva) -> AppResult<MensajeReserva> {
+    let result = repo.mensajes_reserva()
+        .insert_one(&mensaje)
+        .await
+        .map_err(|e| AppError::database("create_message", e))?;
+    mensaje.id = result.inserted_id.as_object_id();
+    Ok(mensaje)
+}
+
+/// Enlace del hilo de mensajes de una reserva para el cliente
+fn thread_link(token: &str) -> String {
+    format!("{}/public/reservations/{}/messages", public_base_url(), token)
+}
+
+/// Avisa al restaurante de un mensaje nuevo de un cliente
+///
+/// Los fallos se registran y no interrumpen la operación.
+async fn notify_restaurant(notifier: &Notifier, restaurant: &Restaurant, reserva: &Reserva, texto: &str) {
+    let Some(destinatario) = restaurant.configuracion.email_alertas.as_ref().or(restaurant.email.as_ref()) else {
+        return;
+    };
+
+    let result = notifier.send_email(EmailMessage {
+        to: destinatario.clone(),
+        subject: format!("Mensaje de {} sobre su reserva", reserva.nombre_cliente),
+        body: format!(
+            "{} ha escrito sobre su reserva del {} a las {}:\n\n{}\n\nRespóndele desde el panel de reservas.\n",
+            reserva.nombre_cliente, reserva.fecha, reserva.hora, texto
+        ),
+        adjuntos: Vec::new(),
+        request_id: None,
+    }).await;
+    if let Err(e) = result {
+        tracing::error!(reserva = ?reserva.id, "Error avisando al restaurante de un mensaje: {}", e);
+    }
+}
+
+/// Envía al cliente la respuesta del restaurante con el enlace al hilo
+///
+/// Los fallos se registran y no interrumpen la operación.
+async fn notify_customer(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    restaurant: &Restaurant,
+    reserva: &Reserva,
+    texto: &str,
+    now: i64,
+) {
+    if reserva.email_cliente.is_empty() {
+        return;
+    }
+    let token = match manage_token(repo, reserva, now).await {
+        Ok(token) => token,
+        Err(e) => {
+            tracing::error!("Error guardando el token de gestión: {}", e);
+            return;
+        }
+    };
+
+    let result = notifier.send_email(EmailMessage {
+        to: reserva.email_cliente.clone(),
+        subject: format!("{} ha respondido sobre tu reserva", restaurant.nombre),
+        body: format!(
+            "Hola {},\n\n{} ha respondido sobre tu reserva del {} a las {}:\n\n{}\n\nContesta aquí: {}\n",
+            reserva.nombre_cliente, restaurant.nombre, reserva.fecha, reserva.hora, texto, thread_link(&token)
+        ),
+        adjuntos: Vec::new(),
+        request_id: None,
+    }).await;
+    if let Err(e) = result {
+        tracing::error!(reserva = ?reserva.id, "Error enviando la respuesta al cliente: {}", e);
+    }
+}
+
+/// Busca una reserva del restaurante autenticado
+async fn find_reservation(repo: &MongoRepo, id: &str, id_restaurante: ObjectId) -> AppResult<Reserva> {
+    let id = ObjectId::parse_str(id)
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    repo.reservas()
+        .find_one(doc! { "_id": id, "id_restaurante": id_restaurante })
+        .await
+        .map_err(|e| AppError::database("find_reservation", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada".to_string()))
+}
+
+/// Hilo de mensajes de una reserva
+///
+/// Abrir el hilo marca como leídos los mensajes del cliente.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439011",
+///     "autor": "cliente",
+///     "texto": "¿Tenéis trona?",
+///     "leido_en": 1718000300,
+///     "created_at": 1718000000
+///   },
+///   {
+///     "id": "507f1f77bcf86cd799439012",
+///     "autor": "restaurante",
+///     "usuario": "ana",
+///     "texto": "Sí, os la dejamos preparada",
+///     "leido_en": null,
+///     "created_at": 1718000400
+///   }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Reserva no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/reservations/{id}/messages")]
+async fn list_messages(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerReservas>,
+) -> AppResult<impl Responder> {
+    let reserva = find_reservation(repo.get_ref(), &path.into_inner(), auth.id()).await?;
+    let (mensajes, _) = load_thread(repo.get_ref(), reserva.id.unwrap(), AutorMensaje::Cliente, clock.timestamp()).await?;
+
+    let mensajes: Vec<MessageResponse> = mensajes.into_iter().map(MessageResponse::from).collect();
+    Ok(HttpResponse::Ok().json(mensajes))
+}
+
+/// Responde al cliente en el hilo de una reserva
+///
+/// El cliente recibe la respuesta por email con el enlace al hilo.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Cuerpo
+/// ```json
+/// { "texto": "Sí, os la dejamos preparada" }
+/// ```
+///
+/// # Respuesta
+/// El mensaje creado, con el formato de `GET /reservations/{id}/messages`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido o texto vacío o demasiado largo
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Reserva no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/messages")]
+async fn reply_message(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<NewMessage>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let texto = validate_text(&data.texto)?;
+    let reserva = find_reservation(repo.get_ref(), &path.into_inner(), auth.id()).await?;
+    let now = clock.timestamp();
+
+    let id_reserva = reserva.id.unwrap();
+
+    // Quien responde ya ha leído lo que el cliente escribió
+    mark_read(repo.get_ref(), id_reserva, AutorMensaje::Cliente, now).await?;
+    let mensaje = insert_message(repo.get_ref(), MensajeReserva {
+        id: None,
+        id_restaurante: auth.id(),
+        id_reserva,
+        autor: AutorMensaje::Restaurante,
+        usuario: Some(auth.usuario.clone()),
+        texto,
+        leido_en: None,
+        created_at: now,
+    }).await?;
+    notify_customer(repo.get_ref(), notifier.get_ref(), &auth.restaurant, &reserva, &mensaje.texto, now).await;
+
+    Ok(HttpResponse::Ok().json(MessageResponse::from(mensaje)))
+}
+
+/// Reservas con mensajes del cliente sin leer, las más recientes primero
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "total": 3,
+///   "reservas": [
+///     { "id_reserva": "507f1f77bcf86cd799439011", "no_leidos": 2, "ultimo_mensaje_en": 1718000400 },
+///     { "id_reserva": "507f1f77bcf86cd799439012", "no_leidos": 1, "ultimo_mensaje_en": 1717990000 }
+///   ]
+/// }
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/reservations/messages/unread")]
+async fn unread_messages(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerReservas>,
+) -> AppResult<impl Responder> {
+    let pipeline = vec![
+        doc! { "$match": {
+            "id_restaurante": auth.id(),
+            "autor": AutorMensaje::Cliente,
+            "leido_en": { "$exists": false },
+        } },
+        doc! { "$group": {
+            "_id": "$id_reserva",
+            "no_leidos": { "$sum": 1 },
+            "ultimo_mensaje_en": { "$max": "$created_at" },
+        } },
+        doc! { "$sort": { "ultimo_mensaje_en": -1, "_id": 1 } },
+    ];
+    let mut cursor = repo.mensajes_reserva()
+        .aggregate(pipeline)
+        .await
+        .map_err(|e| AppError::database("unread_messages", e))?;
+
+    let mut total = 0;
+    let mut reservas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("unread_messages", e))? {
+        let grupo = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error leyendo mensajes sin leer: {}", e)))?;
+        let (Ok(id_reserva), Ok(no_leidos)) = (grupo.get_object_id("_id"), grupo.get_i32("no_leidos")) else {
+            continue;
+        };
+        let no_leidos = no_leidos.max(0) as u64;
+        total += no_leidos;
+        reservas.push(UnreadResponse {
+            id_reserva: id_reserva.to_hex(),
+            no_leidos,
+            ultimo_mensaje_en: grupo.get_i64("ultimo_mensaje_en").unwrap_or_default(),
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "total": total, "reservas": reservas })))
+}
+
+/// Hilo de mensajes de una reserva desde su enlace de gestión
+///
+/// Abrir el hilo marca como leídas las respuestas del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "localizador": "K7Q2MX9D",
+///   "restaurante": "La Tasca",
+///   "no_leidos": 1,
+///   "mensajes": [
+///     { "id": "507f1f77bcf86cd799439011", "autor": "cliente", "texto": "¿Tenéis trona?", "leido_en": 1718000300, "created_at": 1718000000 },
+///     { "id": "507f1f77bcf86cd799439012", "autor": "restaurante", "texto": "Sí", "leido_en": 1718000500, "created_at": 1718000400 }
+///   ]
+/// }
+/// ```
+///
+/// `no_leidos` cuenta las respuestas que no se habían leído hasta ahora.
+///
+/// # Errores
+/// - `404 Not Found`: Enlace inválido
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/public/reservations/{token}/messages")]
+async fn view_public_messages(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let reserva = find_by_token(repo.get_ref(), &path.into_inner()).await?;
+    let restaurant = find_restaurant(repo.get_ref(), reserva.id_restaurante).await?;
+    let (mensajes, no_leidos) =
+        load_thread(repo.get_ref(), reserva.id.unwrap(), AutorMensaje::Restaurante, clock.timestamp()).await?;
+
+    let mensajes: Vec<MessageResponse> = mensajes
+        .into_iter()
+        .map(|mensaje| MessageResponse { usuario: None, ..MessageResponse::from(mensaje) })
+        .collect();
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "localizador": reserva.localizador,
+        "restaurante": restaurant.nombre,
+        "no_leidos": no_leidos,
+        "mensajes": mensajes
+    })))
+}
+
+/// Escribe al restaurante desde el enlace de gestión de una reserva
+///
+/// # Cuerpo
+/// ```json
+/// { "texto": "¿Tenéis trona?" }
+/// ```
+///
+/// # Respuesta
+/// El mensaje creado, con el formato de `GET /public/reservations/{token}/messages`.
+///
+/// # Errores
+/// - `400 Bad Request`: Texto vacío o demasiado largo
+/// - `404 Not Found`: Enlace inválido
+/// - `409 Conflict`: La reserva está anonimizada, o hay demasiados mensajes
+///   sin leer (`demasiados_mensajes`)
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/public/reservations/{token}/messages")]
+async fn send_public_message(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<NewMessage>,
+) -> AppResult<impl Responder> {
+    let texto = validate_text(&data.texto)?;
+    let reserva = find_by_token(repo.get_ref(), &path.into_inner()).await?;
+    if reserva.anonimizada_en.is_some() {
+        return Err(AppError::Conflict("La reserva ya no admite mensajes".to_string()));
+    }
+    let id_reserva = reserva.id.unwrap();
+    let sin_leer = count_unread(repo.get_ref(), id_reserva).await?;
+    if sin_leer >= MAX_MENSAJES_SIN_LEER {
+        return Err(AppError::conflict_with_code(
+            "demasiados_mensajes",
+            "Espera a que el restaurante lea tus mensajes antes de escribir más",
+        ));
+    }
+
+    let mensaje = insert_message(repo.get_ref(), MensajeReserva {
+        id: None,
+        id_restaurante: reserva.id_restaurante,
+        id_reserva,
+        autor: AutorMensaje::Cliente,
+        usuario: None,
+        texto,
+        leido_en: None,
+        created_at: clock.timestamp(),
+    }).await?;
+    if sin_leer == 0 {
+        let restaurant = find_restaurant(repo.get_ref(), reserva.id_restaurante).await?;
+        notify_restaurant(notifier.get_ref(), &restaurant, &reserva, &mensaje.texto).await;
+    }
+
+    Ok(HttpResponse::Ok().json(MessageResponse::from(mensaje)))
+}
+
+/// Configura las rutas de mensajes de reservas
+///
+/// # Rutas disponibles
+/// - `GET /reservations/messages/unread` - Reservas con mensajes sin leer
+/// - `GET /reservations/{id}/messages` - Hilo de una reserva
+/// - `POST /reservations/{id}/messages` - Responder al cliente
+/// - `GET /public/reservations/{token}/messages` - Hilo desde el enlace de gestión
+/// - `POST /public/reservations/{token}/messages` - Escribir al restaurante
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(unread_messages);
+    cfg.service(list_messages);
+    cfg.service(reply_message);
+    cfg.service(view_public_messages);
+    cfg.service(send_public_message);
+}
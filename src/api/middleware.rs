@@ -11,7 +11,7 @@ use std::error::Error as StdError;
 /// - `context`: Contexto opcional para añadir información
 ///
 /// # Ejemplo
-/// ```rust
+/// ```ignore
 /// use crate::api::middleware::log_error_chain;
 ///
 /// if let Err(e) = some_operation().await {
@@ -49,7 +49,7 @@ where
 /// Extension trait para Results que añade logging automático de error chains
 ///
 /// # Ejemplo de uso
-/// ```rust
+/// ```ignore
 /// use crate::api::middleware::ErrorLogExt;
 ///
 /// some_operation()
@@ -115,7 +115,7 @@ where
 /// Macro helper para logging de errores contextualizados
 ///
 /// # Ejemplo
-/// ```rust
+/// ```ignore
 /// log_error_context!(result, "during user authentication");
 /// ```
 #[macro_export]
@@ -6,21 +6,108 @@
 //!
 //! - [`restaurant`] - Gestión de restaurantes (registro, login, listado)
 //! - [`table`] - Gestión de mesas (crear, listar, eliminar)
+//! - [`floor`] - Plantas del restaurante (varios lienzos en el plano)
+//! - [`layout`] - Distribuciones de mesas por día de la semana o fecha
+//! - [`plan`] - Snapshots del plano y comparación entre versiones
+//! - [`recycle_bin`] - Confirmación y papelera del vaciado de mesas
 //! - [`reservation`] - Gestión de reservas (crear, confirmar, cancelar)
+//! - [`reservation_history`] - Historial de cambios de cada reserva
+//! - [`messages`] - Mensajes entre el cliente y el restaurante sobre cada reserva
+//! - [`calendar`] - Reservas en formato iCalendar (adjunto y calendario suscribible)
+//! - [`group_request`] - Solicitudes de grupos y eventos
+//! - [`customer`] - Clientes de cada restaurante (CRM)
+//! - [`slot_rules`] - Franjas horarias bloqueadas
+//! - [`menu`] - Opciones de menú y preselección en reservas
+//! - [`deposit`] - Depósitos divididos en enlaces de pago
+//! - [`staff`] - Cuentas de personal y permisos por rol
+//! - [`auth`] - Extractor del restaurante autenticado por token Bearer
+//! - [`sessions`] - Sesiones abiertas del propietario y su revocación
+//! - [`oauth`] - Inicio de sesión de propietarios con Google (OAuth2)
+//! - [`api_keys`] - Claves de API para integradores
+//! - [`webhooks`] - Suscripciones a los eventos del restaurante por webhook
+//! - [`sandbox`] - Base de datos de pruebas para las claves de API de pruebas
+//! - [`shift`] - Turnos del personal y notas de traspaso
+//! - [`stats`] - Estadísticas diarias precalculadas y comparativa con otros restaurantes
+//! - [`sync`] - Sincronización de los cambios hechos sin conexión
 //! - [`visual`] - Endpoints para el plano visual
+//! - [`public`] - Endpoints públicos del widget (sin autenticación)
+//! - [`status`] - Estado del servicio para la página de estado pública
+//! - [`badge`] - Insignia pública con los contadores que publica cada restaurante
+//! - [`deprecation`] - Registro de rutas obsoletas y sus cabeceras `Deprecation`/`Sunset`
+//! - [`dev`] - Endpoints de apoyo para tests y demos
+//! - [`admin`] - Mantenimiento de la plataforma (token de administración)
+//! - [`impersonation`] - Suplantación del propietario por el soporte, con aviso y auditoría
+//! - [`explain`] - Verificación de los índices de las consultas frecuentes
+//! - [`audit`] - Registro de auditoría de las operaciones del panel
 //! - [`errors`] - Manejo de errores de la aplicación
+//! - [`envelope`] - Sobre de las respuestas con `data`, `meta` y `warnings`
+//! - [`fields`] - Selección de campos en los listados (`fields=`)
+//! - [`idempotency`] - Claves de idempotencia en las creaciones (`Idempotency-Key`)
+//! - [`request_id`] - ID de cada petición en los logs y en las respuestas de error
+//! - [`request_log`] - Registro opcional de peticiones y respuestas
+//! - [`rate_limit`] - Límite de peticiones por IP y por token
+//! - [`login_lockout`] - Bloqueo temporal tras logins fallidos
+//! - [`account_state`] - Suspensión y modo solo lectura de las cuentas
+//! - [`widget_origin`] - Webs autorizadas a usar el widget de cada restaurante
+//! - [`ip_allowlist`] - IPs autorizadas a usar la API de gestión de cada restaurante
+//! - [`webhook_auth`] - Firma y protección contra repeticiones de los webhooks entrantes
 
 pub mod restaurant;
+pub mod account_deletion;
 pub mod reservation;
+pub mod reservation_history;
+pub mod reservation_purge;
+pub mod messages;
+pub mod calendar;
+pub mod group_request;
+pub mod customer;
+pub mod slot_rules;
+pub mod menu;
+pub mod deposit;
+pub mod staff;
+pub mod auth;
+pub mod sessions;
+pub mod oauth;
+pub mod api_keys;
+pub mod webhooks;
+pub mod sandbox;
+pub mod shift;
+pub mod stats;
+pub mod sync;
 pub mod table;
+pub mod floor;
+pub mod layout;
+pub mod plan;
+pub mod recycle_bin;
 pub mod visual;
+pub mod public;
+pub mod badge;
+pub mod status;
+pub mod deprecation;
+pub mod dev;
+pub mod admin;
+pub mod impersonation;
+pub mod explain;
+pub mod audit;
 pub mod errors;
-mod middleware;
+pub mod envelope;
+pub mod fields;
+pub mod idempotency;
+pub mod middleware;
+pub mod request_id;
+pub mod request_log;
+pub mod rate_limit;
+pub mod login_lockout;
+pub mod account_state;
+pub mod widget_origin;
+pub mod ip_allowlist;
+pub mod client_ip;
+pub mod webhook_auth;
 
 // Re-exportar tipos comunes para facilitar su uso
 pub use errors::{AppError, AppResult, ErrorResponse, ResultExt};
 
-use actix_web::web;
+use actix_web::{middleware::from_fn, web};
 
 /// Configura todas las rutas de la API
 ///
@@ -28,10 +115,50 @@ use actix_web::web;
 ///
 /// ## Rutas configuradas
 ///
-/// - `/restaurants/*` - Ver [`restaurant::routes`]
-/// - `/tables/*` - Ver [`table::routes`]
-/// - `/reservations/*` - Ver [`reservation::routes`]
+/// - `/restaurants/*` - Ver [`restaurant::routes`], [`account_deletion::routes`], [`sessions::routes`],
+///   [`api_keys::routes`], [`webhooks::routes`] y [`calendar::routes`] (el token del calendario)
+/// - `/auth/google/*` - Ver [`oauth::routes`]
+/// - `/tables/*` - Ver [`table::routes`] y [`recycle_bin::routes`]
+/// - `/layouts/*` - Ver [`layout::routes`]
+/// - `/reservations/*` - Ver [`reservation::routes`], [`reservation_history::routes`], [`reservation_purge::routes`],
+///   [`messages::routes`] (también el hilo público de cada reserva), [`calendar::routes`]
+///   y [`table::routes`] (la asignación automática de mesa)
+/// - `/group-requests/*` - Ver [`group_request::routes`] (también el envío público de solicitudes)
+/// - `/customers/*` - Ver [`customer::routes`]
+/// - `/slot-rules/*` - Ver [`slot_rules::routes`]
+/// - `/menu-options/*`, `/kitchen/*` - Ver [`menu::routes`]
+/// - `/staff/*` - Ver [`staff::routes`]
+/// - `/shifts/*` - Ver [`shift::routes`]
+/// - `/stats/*` - Ver [`stats::routes`]
+/// - `/sync/*` - Ver [`sync::routes`]
 /// - `/visual/*` - Ver [`visual::routes`]
+/// - `/public/*` - Ver [`public::routes`] (y [`status::routes`] para `/public/status`,
+///   [`deprecation::routes`] para `/public/deprecations`, [`badge::routes`] para la
+///   insignia de cada restaurante)
+/// - `/dev/*` - Ver [`dev::routes`] (solo con `DEV_ROUTES=true`)
+/// - `/admin/*` - Ver [`admin::routes`], [`account_deletion::routes`], [`impersonation::routes`] y
+///   [`explain::routes`] (este, solo con `DEV_ROUTES=true`)
+/// - `/audit` - Ver [`audit::routes`]
+///
+/// Las rutas se agrupan en un scope raíz envuelto por los middlewares de la
+/// API ([`request_id::assign_request_id`], que da a cada petición el ID que
+/// aparece en sus logs y en sus errores; [`envelope::wrap_envelope`], que
+/// mete las respuestas en el sobre salvo a los clientes antiguos que piden
+/// la forma sin sobre;
+/// [`deprecation::signal_deprecation`],
+/// que anuncia las rutas obsoletas en todas sus respuestas; [`sandbox::route_sandbox`], que lleva las claves de pruebas a la base
+/// de datos de pruebas antes que nada; [`rate_limit::limit_requests`], que rechaza las peticiones antes de
+/// registrarlas, [`request_log::log_requests`] y
+/// [`account_state::enforce_account_state`], que rechaza las de cuentas
+/// suspendidas o de solo lectura; [`ip_allowlist::check_ip_allowlist`], que
+/// rechaza las de gestión desde IPs no autorizadas; [`widget_origin::check_widget_origin`], que
+/// rechaza el widget fuera de las webs autorizadas y responde al CORS;
+/// [`sessions::touch_session`], que anota el último uso de cada sesión;
+/// [`idempotency::replay_idempotent`], que responde a las creaciones repetidas
+/// con la respuesta original; y [`impersonation::mark_impersonation`], que
+/// marca las respuestas a los tokens de suplantación). Como ese scope responde 404 a cualquier
+/// ruta que no sea suya, los servicios ajenos a la API (archivos
+/// estáticos...) deben registrarse antes.
 ///
 /// # Parámetros
 ///
@@ -47,8 +174,54 @@ use actix_web::web;
 ///     .configure(api::init_routes);
 /// ```
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
-    reservation::routes(cfg);
-    restaurant::routes(cfg);
-    table::routes(cfg);
-    visual::routes(cfg);
+    cfg.service(
+        web::scope("")
+            .wrap(from_fn(impersonation::mark_impersonation))
+            .wrap(from_fn(idempotency::replay_idempotent))
+            .wrap(from_fn(sessions::touch_session))
+            .wrap(from_fn(ip_allowlist::check_ip_allowlist))
+            .wrap(from_fn(account_state::enforce_account_state))
+            .wrap(from_fn(widget_origin::check_widget_origin))
+            .wrap(from_fn(request_log::log_requests))
+            .wrap(from_fn(rate_limit::limit_requests))
+            .wrap(from_fn(sandbox::route_sandbox))
+            .wrap(from_fn(deprecation::signal_deprecation))
+            .wrap(from_fn(envelope::wrap_envelope))
+            .wrap(from_fn(request_id::assign_request_id))
+            .configure(reservation::routes)
+            .configure(reservation_history::routes)
+            .configure(reservation_purge::routes)
+            .configure(messages::routes)
+            .configure(calendar::routes)
+            .configure(group_request::routes)
+            .configure(customer::routes)
+            .configure(slot_rules::routes)
+            .configure(menu::routes)
+            .configure(deposit::routes)
+            .configure(staff::routes)
+            .configure(shift::routes)
+            .configure(stats::routes)
+            .configure(sync::routes)
+            .configure(restaurant::routes)
+            .configure(account_deletion::routes)
+            .configure(sessions::routes)
+            .configure(api_keys::routes)
+            .configure(webhooks::routes)
+            .configure(oauth::routes)
+            .configure(table::routes)
+            .configure(floor::routes)
+            .configure(layout::routes)
+            .configure(plan::routes)
+            .configure(recycle_bin::routes)
+            .configure(visual::routes)
+            .configure(public::routes)
+            .configure(badge::routes)
+            .configure(status::routes)
+            .configure(deprecation::routes)
+            .configure(dev::routes)
+            .configure(admin::routes)
+            .configure(impersonation::routes)
+            .configure(explain::routes)
+            .configure(audit::routes),
+    );
 }
\ No newline at end of file
@@ -0,0 +1,340 @@
+//! # Inicio de sesión con Google (OAuth2)
+//!
+//! Flujo *authorization code* de OAuth2 para que los propietarios entren con
+//! su cuenta de Google:
+//!
+//! 1. `GET /auth/google/start` guarda un `state` de un solo uso y redirige a
+//!    la pantalla de consentimiento de Google
+//! 2. Google vuelve a `GET /auth/google/callback` con un `code`, que se canjea
+//!    por la identidad de la cuenta (`sub` y email verificado)
+//! 3. La identidad se vincula a un [`Restaurant`] y se devuelve su token de
+//!    acceso, igual que en `POST /restaurants/login`
+//!
+//! ## Vinculación
+//!
+//! - Si `start` se llama con el token del propietario (`Authorization: Bearer`),
+//!   la cuenta de Google se vincula a ese restaurante
+//! - Si no, se usa el restaurante ya vinculado a esa cuenta de Google o, la
+//!   primera vez, el único restaurante cuyo email coincide con el email
+//!   verificado de Google
+//!
+//! ## Configuración
+//!
+//! - `GOOGLE_CLIENT_ID`, `GOOGLE_CLIENT_SECRET`: credenciales del cliente OAuth
+//!   (sin definir, las rutas responden 404)
+//! - `GOOGLE_REDIRECT_URI`: URL del callback registrada en Google
+//!   (default: `PUBLIC_BASE_URL` + `/auth/google/callback`)
+//! - `GOOGLE_AUTH_URL`, `GOOGLE_TOKEN_URL`, `GOOGLE_USERINFO_URL`: endpoints de
+//!   Google (default: los de producción; se sustituyen en los tests)
+
+use std::env;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use mongodb::bson::doc;
+use mongodb::options::ReturnDocument;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use super::{AppError, AppResult};
+use super::account_state::ensure_can_login;
+use super::auth::extract_token;
+use super::public::public_base_url;
+use super::sessions;
+use super::staff::{authorize, Permiso};
+use crate::clock::Clock;
+use crate::db::{EstadoOAuth, MongoRepo, Restaurant};
+use crate::events::{self, TipoEvento};
+
+/// Segundos que tiene el usuario para completar el inicio de sesión en Google
+const DURACION_ESTADO: i64 = 10 * 60;
+
+/// Cliente OAuth de Google
+#[derive(Debug, Clone)]
+pub struct ConfigGoogle {
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,
+    pub auth_url: String,
+    pub token_url: String,
+    pub userinfo_url: String,
+}
+
+impl ConfigGoogle {
+    /// Lee la configuración del entorno; `None` si faltan las credenciales
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+
+        Some(ConfigGoogle {
+            client_id: var("GOOGLE_CLIENT_ID")?,
+            client_secret: var("GOOGLE_CLIENT_SECRET")?,
+            redirect_uri: var("GOOGLE_REDIRECT_URI")
+                .unwrap_or_else(|| format!("{}/auth/google/callback", public_base_url())),
+            auth_url: var("GOOGLE_AUTH_URL")
+                .unwrap_or_else(|| "https://accounts.google.com/o/oauth2/v2/auth".to_string()),
+            token_url: var("GOOGLE_TOKEN_URL")
+                .unwrap_or_else(|| "https://oauth2.googleapis.com/token".to_string()),
+            userinfo_url: var("GOOGLE_USERINFO_URL")
+                .unwrap_or_else(|| "https://openidconnect.googleapis.com/v1/userinfo".to_string()),
+        })
+    }
+
+    /// URL de la pantalla de consentimiento de Google para un `state`
+    ///
+    /// # Errores
+    /// - `Internal`: Si `auth_url` no es una URL válida
+    pub fn authorization_url(&self, state: &str) -> AppResult<String> {
+        reqwest::Url::parse_with_params(&self.auth_url, &[
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("response_type", "code"),
+            ("scope", "openid email"),
+            ("state", state),
+            ("prompt", "select_account"),
+        ])
+            .map(String::from)
+            .map_err(|e| AppError::Internal(format!("GOOGLE_AUTH_URL inválida: {}", e)))
+    }
+
+    /// Canjea el código de autorización por la identidad de la cuenta
+    ///
+    /// # Errores
+    /// - `Unauthorized`: Si Google rechaza el código (usado, caducado...)
+    /// - `Internal`: Si Google no responde o la respuesta no es la esperada
+    async fn exchange_code(&self, code: &str) -> AppResult<IdentidadGoogle> {
+        let error = |e: reqwest::Error| AppError::Internal(format!("Error consultando a Google: {}", e));
+        let client = reqwest::Client::new();
+
+        let respuesta = client.post(&self.token_url)
+            .form(&[
+                ("code", code),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("grant_type", "authorization_code"),
+            ])
+            .send()
+            .await
+            .map_err(error)?;
+        if respuesta.status().is_client_error() {
+            return Err(AppError::Unauthorized("Google ha rechazado el código de autorización".to_string()));
+        }
+        let token: TokenGoogle = respuesta.error_for_status().map_err(error)?.json().await.map_err(error)?;
+
+        client.get(&self.userinfo_url)
+            .bearer_auth(&token.access_token)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(error)?
+            .json()
+            .await
+            .map_err(error)
+    }
+}
+
+/// Respuesta del endpoint de tokens de Google (solo los campos usados)
+#[derive(Deserialize)]
+struct TokenGoogle {
+    access_token: String,
+}
+
+/// Identidad de una cuenta de Google según el endpoint `userinfo`
+#[derive(Debug, Deserialize)]
+struct IdentidadGoogle {
+    /// Identificador estable de la cuenta
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+}
+
+/// Parámetros con los que Google vuelve al callback
+#[derive(Deserialize)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    /// Motivo si el usuario no ha dado permiso ("access_denied")
+    error: Option<String>,
+}
+
+/// Error de las rutas cuando Google no está configurado
+fn no_disponible() -> AppError {
+    AppError::NotFound("Recurso no disponible".to_string())
+}
+
+/// Inicia el inicio de sesión con Google
+///
+/// Redirige (302) a la pantalla de consentimiento de Google. Con el token
+/// del propietario en `Authorization: Bearer`, al volver se vincula la cuenta
+/// de Google a su restaurante en lugar de buscarlo por email.
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o que no es del propietario
+/// - `404 Not Found`: Google no configurado
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/auth/google/start")]
+async fn google_start(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    let config = ConfigGoogle::from_env().ok_or_else(no_disponible)?;
+
+    let now = clock.timestamp();
+    let id_restaurante = if req.headers().contains_key("authorization") {
+        let token = extract_token(&req)?;
+        authorize(repo.get_ref(), &token, Permiso::Configuracion, now).await?.id
+    } else {
+        None
+    };
+
+    let estado = EstadoOAuth {
+        id: None,
+        state: Uuid::new_v4().to_string(),
+        id_restaurante,
+        expires_at: now + DURACION_ESTADO,
+        created_at: now,
+    };
+    let url = config.authorization_url(&estado.state)?;
+
+    repo.estados_oauth()
+        .delete_many(doc! { "expires_at": { "$lt": now } })
+        .await
+        .map_err(|e| AppError::database("delete_oauth_states", e))?;
+    repo.estados_oauth()
+        .insert_one(&estado)
+        .await
+        .map_err(|e| AppError::database("create_oauth_state", e))?;
+
+    Ok(HttpResponse::Found().append_header(("Location", url)).finish())
+}
+
+/// Completa el inicio de sesión con Google
+///
+/// Consume el `state` creado por `GET /auth/google/start`, canjea el código
+/// con Google y abre una sesión del restaurante vinculado (ver
+/// [`super::sessions`]).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "access_token": "uuid-token",
+///   "id_restaurante": "mongodb-object-id",
+///   "message": "Login exitoso"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Faltan `code` o `state`
+/// - `401 Unauthorized`: El usuario no ha dado permiso, el `state` es inválido
+///   o ha caducado, o Google rechaza el código
+/// - `403 Forbidden`: La cuenta del restaurante está suspendida
+/// - `404 Not Found`: Google no configurado, o ningún restaurante corresponde
+///   a la cuenta de Google
+/// - `409 Conflict`: La cuenta de Google ya está vinculada a otro restaurante,
+///   o su email es el de varios restaurantes
+/// - `500 Internal Server Error`: Error de base de datos o de Google
+#[get("/auth/google/callback")]
+async fn google_callback(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    req: HttpRequest,
+    query: web::Query<CallbackQuery>,
+) -> AppResult<impl Responder> {
+    let config = ConfigGoogle::from_env().ok_or_else(no_disponible)?;
+
+    if let Some(error) = &query.error {
+        return Err(AppError::Unauthorized(format!("Google no ha autorizado el inicio de sesión: {}", error)));
+    }
+    let (Some(code), Some(state)) = (&query.code, &query.state) else {
+        return Err(AppError::Validation("Faltan los parámetros code y state".to_string()));
+    };
+
+    let now = clock.timestamp();
+    let estado = repo.estados_oauth()
+        .find_one_and_delete(doc! { "state": state, "expires_at": { "$gt": now } })
+        .await
+        .map_err(|e| AppError::database("consume_oauth_state", e))?
+        .ok_or(AppError::Unauthorized("Inicio de sesión inválido o caducado; vuelve a empezar".to_string()))?;
+
+    let identidad = config.exchange_code(code).await?;
+    let restaurant = resolve_restaurant(repo.get_ref(), &estado, &identidad, now).await?;
+    ensure_can_login(&restaurant)?;
+    let id = restaurant.id.unwrap();
+    let access_token = sessions::open(repo.get_ref(), id, &req, now).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "access_token": access_token,
+        "id_restaurante": id.to_hex(),
+        "message": "Login exitoso"
+    })))
+}
+
+/// Busca el restaurante de una cuenta de Google, vinculándola si hace falta
+///
+/// # Errores
+/// - `Conflict`: La cuenta ya está vinculada a otro restaurante, o su email
+///   es el de varios restaurantes
+/// - `NotFound`: Ningún restaurante corresponde a la cuenta
+async fn resolve_restaurant(
+    repo: &MongoRepo,
+    estado: &EstadoOAuth,
+    identidad: &IdentidadGoogle,
+    now: i64,
+) -> AppResult<Restaurant> {
+    let vinculado = repo.restaurants()
+        .find_one(doc! { "google_sub": &identidad.sub })
+        .await
+        .map_err(|e| AppError::database("find_restaurant_by_google", e))?;
+
+    let filtro = match (estado.id_restaurante, vinculado) {
+        (Some(id), Some(restaurant)) if restaurant.id == Some(id) => return Ok(restaurant),
+        (Some(_), Some(_)) => {
+            return Err(AppError::Conflict("Esa cuenta de Google ya está vinculada a otro restaurante".to_string()));
+        }
+        (None, Some(restaurant)) => return Ok(restaurant),
+        (Some(id), None) => doc! { "_id": id },
+        (None, None) => {
+            let email = identidad.email.as_deref()
+                .filter(|_| identidad.email_verified)
+                .ok_or_else(sin_cuenta)?;
+            let filtro = doc! { "email": email, "google_sub": { "$exists": false } };
+            let coincidencias = repo.restaurants()
+                .count_documents(filtro.clone())
+                .await
+                .map_err(|e| AppError::database("count_restaurants_by_email", e))?;
+            if coincidencias > 1 {
+                return Err(AppError::Conflict(
+                    "Varios restaurantes usan ese email; vincula la cuenta de Google desde cada uno de ellos".to_string(),
+                ));
+            }
+            filtro
+        }
+    };
+
+    let restaurant = repo.restaurants()
+        .find_one_and_update(filtro, doc! { "$set": { "google_sub": &identidad.sub } })
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("link_google_account", e))?
+        .ok_or_else(sin_cuenta)?;
+    events::record(repo, TipoEvento::CuentaGoogleVinculada, restaurant.id, restaurant.id, doc! {}, now).await;
+
+    Ok(restaurant)
+}
+
+/// Error cuando ningún restaurante corresponde a la cuenta de Google
+fn sin_cuenta() -> AppError {
+    AppError::NotFound(
+        "No hay ningún restaurante con el email de esa cuenta de Google; regístrate o vincúlala desde tu cuenta".to_string(),
+    )
+}
+
+/// Configura las rutas de inicio de sesión con Google
+///
+/// # Rutas disponibles
+/// - `GET /auth/google/start` - Redirigir a Google
+/// - `GET /auth/google/callback` - Volver de Google con el código
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(google_start);
+    cfg.service(google_callback);
+}
@@ -0,0 +1,260 @@
+//! # API de Snapshots del plano
+//!
+//! Este módulo permite guardar copias del plano de mesas y comparar
+//! distribuciones:
+//! - Guardar el plano actual como snapshot
+//! - Listar los snapshots guardados
+//! - Comparar dos snapshots, o un snapshot con el plano actual
+//!
+//! La comparación (ver [`crate::plan::diff`]) alimenta la pantalla de
+//! "revisar cambios" del editor del plano; con `planta` se limita a las
+//! mesas de una planta (ver [`super::floor`]).
+//!
+//! Todas las operaciones requieren autenticación mediante token Bearer.
+
+use actix_web::{get, post, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::floor::resolve_floor;
+use super::auth::{AuthenticatedRestaurant, EscribirMesas, LeerMesas, PermisoGestion};
+use crate::clock::Clock;
+use crate::db::{Mesa, MongoRepo, SnapshotPlano};
+use crate::plan;
+
+/// Referencia al plano actual en `GET /tables/diff`
+const PLANO_ACTUAL: &str = "current";
+
+/// Estructura para guardar un snapshot
+#[derive(Deserialize)]
+struct CreateSnapshot {
+    /// Nombre descriptivo ("Antes de la terraza de verano")
+    #[serde(default)]
+    nombre: String,
+}
+
+/// Estructura de respuesta para un snapshot (sin las mesas)
+#[derive(Serialize)]
+struct SnapshotResponse {
+    id: String,
+    nombre: String,
+    mesas: usize,
+    created_at: i64,
+}
+
+impl From<SnapshotPlano> for SnapshotResponse {
+    fn from(snapshot: SnapshotPlano) -> Self {
+        SnapshotResponse {
+            id: snapshot.id.map(|id| id.to_hex()).unwrap_or_default(),
+            nombre: snapshot.nombre,
+            mesas: snapshot.mesas.len(),
+            created_at: snapshot.created_at,
+        }
+    }
+}
+
+/// Parámetros de consulta para comparar planos
+#[derive(Deserialize)]
+struct DiffQuery {
+    /// Snapshot de partida (ID o `current`)
+    from: String,
+    /// Snapshot de llegada (ID o `current`, por defecto `current`)
+    to: Option<String>,
+    /// Comparar solo las mesas de una planta
+    planta: Option<String>,
+}
+
+fn cursor_error(e: mongodb::error::Error) -> AppError {
+    AppError::Internal(format!("Error iterando cursor: {}", e))
+}
+
+/// Carga las mesas actuales de un restaurante
+async fn load_tables(repo: &MongoRepo, restaurante_id: ObjectId) -> AppResult<Vec<Mesa>> {
+    let options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+    let mut cursor = repo.mesas()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("load_tables", e))?;
+
+    let mut mesas = Vec::new();
+    while cursor.advance().await.map_err(cursor_error)? {
+        mesas.push(cursor.deserialize_current().map_err(cursor_error)?);
+    }
+
+    Ok(mesas)
+}
+
+/// Carga las mesas de un snapshot del restaurante o, con `current`, las actuales
+async fn load_plan(repo: &MongoRepo, restaurante_id: ObjectId, referencia: &str) -> AppResult<Vec<Mesa>> {
+    if referencia == PLANO_ACTUAL {
+        return load_tables(repo, restaurante_id).await;
+    }
+
+    let id = ObjectId::parse_str(referencia)
+        .map_err(|_| AppError::Validation(format!(
+            "Snapshot inválido: {} (usa un ID o '{}')", referencia, PLANO_ACTUAL
+        )))?;
+
+    let snapshot = repo.snapshots_plano()
+        .find_one(doc! { "_id": id, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("load_snapshot", e))?
+        .ok_or(AppError::NotFound(format!("Snapshot {} no encontrado", referencia)))?;
+
+    Ok(snapshot.mesas)
+}
+
+/// Guarda el plano actual como snapshot
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Cuerpo
+/// ```json
+/// { "nombre": "Antes de la terraza de verano" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id": "507f1f77bcf86cd799439011",
+///   "nombre": "Antes de la terraza de verano",
+///   "mesas": 14,
+///   "created_at": 1717243200
+/// }
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/tables/snapshots")]
+async fn create_snapshot(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<CreateSnapshot>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let mut snapshot = SnapshotPlano {
+        id: None,
+        id_restaurante: restaurante_id,
+        nombre: data.nombre.trim().to_string(),
+        mesas: load_tables(repo.get_ref(), restaurante_id).await?,
+        created_at: clock.timestamp(),
+    };
+
+    let result = repo.snapshots_plano()
+        .insert_one(&snapshot)
+        .await
+        .map_err(|e| AppError::database("create_snapshot", e))?;
+    snapshot.id = result.inserted_id.as_object_id();
+
+    Ok(HttpResponse::Ok().json(SnapshotResponse::from(snapshot)))
+}
+
+/// Lista los snapshots del restaurante, del más reciente al más antiguo
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// Lista de snapshots con el mismo formato que `POST /tables/snapshots`.
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/tables/snapshots")]
+async fn list_snapshots(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerMesas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let options = FindOptions::builder().sort(doc! { "created_at": -1 }).build();
+    let mut cursor = repo.snapshots_plano()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("list_snapshots", e))?;
+
+    let mut snapshots = Vec::new();
+    while cursor.advance().await.map_err(cursor_error)? {
+        snapshots.push(SnapshotResponse::from(cursor.deserialize_current().map_err(cursor_error)?));
+    }
+
+    Ok(HttpResponse::Ok().json(snapshots))
+}
+
+/// Compara dos versiones del plano
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Parámetros de query
+/// - `from`: ID del snapshot de partida, o `current`
+/// - `to`: ID del snapshot de llegada, o `current` (por defecto)
+/// - `planta` (opcional): Comparar solo las mesas de esa planta
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "from": "507f1f77bcf86cd799439011",
+///   "to": "current",
+///   "anadidas": [
+///     { "id": "507f1f77bcf86cd799439021", "nombre": "Terraza 1",
+///       "geometria": { "pos_x": 40.0, "pos_y": 300.0, "size_x": 80.0, "size_y": 80.0 } }
+///   ],
+///   "eliminadas": [],
+///   "movidas": [
+///     { "id": "507f1f77bcf86cd799439022", "nombre": "Mesa 4",
+///       "antes": { "pos_x": 100.0, "pos_y": 100.0, "size_x": 80.0, "size_y": 80.0 },
+///       "despues": { "pos_x": 220.0, "pos_y": 100.0, "size_x": 80.0, "size_y": 80.0 } }
+///   ]
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Referencia de snapshot inválida
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Snapshot o planta no encontrados en el restaurante
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/tables/diff")]
+async fn diff_plans(
+    repo: web::Data<MongoRepo>,
+    query: web::Query<DiffQuery>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerMesas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let to = query.to.as_deref().unwrap_or(PLANO_ACTUAL);
+    let mut antes = load_plan(repo.get_ref(), restaurante_id, &query.from).await?;
+    let mut despues = load_plan(repo.get_ref(), restaurante_id, to).await?;
+    if let Some(planta) = resolve_floor(repo.get_ref(), restaurante_id, query.planta.as_deref()).await? {
+        antes.retain(|mesa| mesa.id_planta == Some(planta));
+        despues.retain(|mesa| mesa.id_planta == Some(planta));
+    }
+    let diferencias = plan::diff(&antes, &despues);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "from": query.from,
+        "to": to,
+        "anadidas": diferencias.anadidas,
+        "eliminadas": diferencias.eliminadas,
+        "movidas": diferencias.movidas
+    })))
+}
+
+/// Configura las rutas de snapshots del plano
+///
+/// # Rutas disponibles
+/// - `POST /tables/snapshots` - Guardar el plano actual
+/// - `GET /tables/snapshots` - Listar snapshots
+/// - `GET /tables/diff` - Comparar dos versiones del plano
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(create_snapshot);
+    cfg.service(list_snapshots);
+    cfg.service(diff_plans);
+}
@@ -0,0 +1,1412 @@
+//! # API pública del widget
+//!
+//! Endpoints sin autenticación que usa el widget de reservas embebido en la
+//! web del restaurante:
+//! - Consultar las opciones de menú que se pueden preseleccionar
+//! - Consultar las horas libres de varios días a la vez (el calendario del widget)
+//! - Crear reservas en nombre del cliente (fuera de las franjas bloqueadas)
+//! - Verificar el email (enlace mágico) o el teléfono (código SMS) del cliente
+//! - Consultar una reserva con su localizador y el email del cliente
+//! - Confirmar la asistencia o cancelar una reserva desde los enlaces de
+//!   gestión que recibe el cliente por email (y escribir al restaurante,
+//!   ver [`super::messages`])
+//!
+//! Para frenar reservas en ráfaga con emails distintos desde un mismo
+//! dispositivo, cada reserva guarda la IP del cliente y, si el widget la
+//! envía, su sesión (header `X-Widget-Session`), y se limitan las reservas y
+//! los emails distintos por IP en una ventana de tiempo: cambiar de sesión
+//! no esquiva los límites, y `X-Forwarded-For` solo cuenta detrás de un
+//! proxy de confianza (ver [`super::client_ip`]). Los intentos bloqueados se registran en
+//! `widget_violaciones`.
+//!
+//! Si el restaurante exige verificación, la reserva se crea en estado
+//! "sin_confirmar" y no pasa a "pendiente" (o "confirmada" si el restaurante
+//! confirma automáticamente) hasta que el cliente verifica su contacto.
+
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::options::ReturnDocument;
+use chrono::{Duration, NaiveDate, NaiveTime};
+use std::env;
+use uuid::Uuid;
+use super::{audit, reservation_history, AppError, AppResult};
+use super::client_ip::client_ip;
+use super::customer::{discount_visit, learn_preference, link_customer, previous_no_shows};
+use super::group_request::{self, NuevaSolicitud};
+use super::menu::{load_options, resolve_preselection, MenuOptionResponse};
+use super::layout::load_layouts;
+use super::reservation::{check_cancellation, load_ocupaciones, new_reserva, validate_date, validate_new_reservation, MakeReservation};
+use super::slot_rules::load_rules;
+use crate::availability::{self, CapacidadMesa, Ocupacion};
+use crate::clock::Clock;
+use crate::db::{EstadoReserva, Mesa, MetodoVerificacion, MongoRepo, PoliticaGruposGrandes, Reserva, Restaurant, VerificacionCliente, ViolacionWidget};
+use crate::events::{self, TipoEvento};
+use crate::journal;
+use crate::language;
+use crate::risk;
+use crate::notifications::{EmailMessage, Notifier, SmsMessage};
+
+/// Días máximos de una consulta de disponibilidad
+const MAX_DIAS_DISPONIBILIDAD: usize = 62;
+
+/// Minutos entre las horas que se ofrecen en la consulta de disponibilidad
+const PASO_DISPONIBILIDAD_MINUTOS: u32 = 30;
+
+/// Validez del enlace mágico enviado por email (24 horas)
+const EXPIRACION_EMAIL_SEGUNDOS: i64 = 24 * 60 * 60;
+
+/// Validez del código enviado por SMS (15 minutos)
+const EXPIRACION_SMS_SEGUNDOS: i64 = 15 * 60;
+
+/// Intentos fallidos permitidos antes de bloquear un código SMS
+const MAX_INTENTOS_VERIFICACION: u32 = 5;
+
+/// Consultas por localizador permitidas por IP en la ventana
+const MAX_CONSULTAS_LOCALIZADOR: u32 = 10;
+
+/// Ventana de las consultas por localizador (15 minutos)
+const VENTANA_CONSULTAS_SEGUNDOS: i64 = 15 * 60;
+
+/// Consultas fallidas de un mismo localizador antes de bloquearlo durante
+/// la ventana, desde cualquier IP
+const MAX_FALLOS_LOCALIZADOR: u32 = 5;
+
+/// Header con el identificador de sesión generado por el widget
+const WIDGET_SESSION_HEADER: &str = "X-Widget-Session";
+
+/// Límites anti-duplicados por dispositivo del widget
+///
+/// Configurables mediante variables de entorno:
+/// - `WIDGET_VENTANA_SEGUNDOS`: ventana de observación (default: 3600)
+/// - `WIDGET_MAX_RESERVAS`: reservas máximas por dispositivo en la ventana (default: 5)
+/// - `WIDGET_MAX_EMAILS`: emails distintos máximos por dispositivo en la ventana (default: 3)
+struct DeviceLimits {
+    ventana_segundos: i64,
+    max_reservas: u64,
+    max_emails: usize,
+}
+
+impl DeviceLimits {
+    fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        }
+
+        DeviceLimits {
+            ventana_segundos: var("WIDGET_VENTANA_SEGUNDOS", 3600),
+            max_reservas: var("WIDGET_MAX_RESERVAS", 5),
+            max_emails: var("WIDGET_MAX_EMAILS", 3),
+        }
+    }
+}
+
+/// Cuerpo de la consulta de una reserva
+#[derive(Deserialize)]
+struct LookupReservation {
+    /// Localizador de la reserva
+    localizador: String,
+    /// Email con el que se hizo la reserva
+    email: String,
+}
+
+/// Cuerpo de la consulta de disponibilidad de varios días
+#[derive(Deserialize)]
+struct AvailabilityBatch {
+    /// Número de comensales
+    personas: i32,
+    /// Fechas a consultar (formato YYYY-MM-DD)
+    #[serde(default)]
+    fechas: Vec<String>,
+    /// Primer día de un rango, en lugar de `fechas`
+    desde: Option<String>,
+    /// Último día (incluido) del rango
+    hasta: Option<String>,
+}
+
+/// Disponibilidad de un día en la consulta de varios días
+#[derive(Serialize)]
+struct DayAvailability {
+    fecha: String,
+    /// Horas (HH:MM) a las que hay alguna mesa libre para el grupo
+    horas: Vec<String>,
+}
+
+/// Cuerpo de la verificación por código
+#[derive(Deserialize)]
+struct VerifyCode {
+    /// Código recibido por SMS
+    codigo: String,
+}
+
+/// URL base pública usada para construir los enlaces enviados a clientes
+///
+/// Se configura con la variable de entorno `PUBLIC_BASE_URL`
+/// (default: http://localhost:8080).
+pub(super) fn public_base_url() -> String {
+    env::var("PUBLIC_BASE_URL")
+        .unwrap_or_else(|_| "http://localhost:8080".to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Estado al que pasa una reserva pública ya verificada
+///
+/// Con un pago por adelantado pendiente no se confirma sola: se confirma al
+/// marcarlo como pagado (ver [`super::deposit`]). Tampoco si el cliente
+/// tiene los no-shows previos que el restaurante revisa (ver
+/// [`risk::requiere_revision`]).
+fn estado_verificado(restaurant: &Restaurant, pago_pendiente: bool, no_shows: u32) -> EstadoReserva {
+    let revision = risk::requiere_revision(&restaurant.configuracion.riesgo, no_shows);
+    if restaurant.confirmar_automaticamente && !pago_pendiente && !revision {
+        EstadoReserva::Confirmada
+    } else {
+        EstadoReserva::Pendiente
+    }
+}
+
+/// Genera un código numérico de 6 dígitos para SMS
+fn generate_sms_code() -> String {
+    format!("{:06}", Uuid::new_v4().as_u128() % 1_000_000)
+}
+
+/// Identifica el dispositivo que hace la petición
+///
+/// Siempre empieza por la IP del cliente (`ip:<ip>`, ver
+/// [`super::client_ip`]); el identificador de sesión del widget, si viene en
+/// la petición, se añade detrás (`ip:<ip>|sesion:<sesion>`) para distinguir
+/// los dispositivos de una misma IP al revisar los bloqueos.
+///
+/// `None` si la conexión no tiene dirección remota: esas peticiones no se
+/// agrupan en un dispositivo común.
+fn device_id(req: &HttpRequest) -> Option<String> {
+    let ip = format!("ip:{}", client_ip(req)?);
+    let session = req.headers()
+        .get(WIDGET_SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty());
+
+    Some(match session {
+        Some(session) => format!("{}|sesion:{}", ip, session.chars().take(128).collect::<String>()),
+        None => ip,
+    })
+}
+
+/// Filtro de los dispositivos con la IP de `dispositivo`, con cualquier sesión
+///
+/// ```text
+/// ip:10.0.0.1              sí
+/// ip:10.0.0.1|sesion:abc   sí
+/// ip:10.0.0.10             no
+/// ```
+fn same_ip(dispositivo: &str) -> Document {
+    let ip = dispositivo.split('|').next().unwrap_or(dispositivo);
+    // Las sesiones van tras `|`; `}` es el carácter siguiente
+    doc! { "$or": [
+        { "dispositivo": ip },
+        { "dispositivo": { "$gt": format!("{}|", ip), "$lt": format!("{}}}", ip) } },
+    ] }
+}
+
+/// Comprueba las heurísticas anti-duplicados de un dispositivo
+///
+/// Bloquea la reserva si la IP del dispositivo ya alcanzó el máximo de
+/// reservas en la ventana, o si el email es nuevo y ya alcanzó el máximo de
+/// emails distintos. Los bloqueos se registran para su revisión.
+///
+/// Sin dispositivo (petición sin dirección remota) no hay nada que limitar.
+///
+/// # Errores
+/// - `TooManyRequests`: Si el dispositivo supera alguno de los límites
+async fn check_device_limits(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    dispositivo: Option<&str>,
+    email: &str,
+    now: i64,
+) -> AppResult<()> {
+    let Some(dispositivo) = dispositivo else {
+        return Ok(());
+    };
+    let limits = DeviceLimits::from_env();
+    let mut filter = same_ip(dispositivo);
+    filter.insert("created_at", doc! { "$gte": now - limits.ventana_segundos });
+
+    let reservas = repo.reservas()
+        .count_documents(filter.clone())
+        .await
+        .map_err(|e| AppError::database("check_device_limits", e))?;
+
+    let emails: Vec<String> = repo.reservas()
+        .distinct("email_cliente", filter)
+        .await
+        .map_err(|e| AppError::database("check_device_limits", e))?
+        .into_iter()
+        .filter_map(|email| email.as_str().map(str::to_lowercase))
+        .collect();
+
+    let motivo = if reservas >= limits.max_reservas {
+        Some(format!("{} reservas en {} segundos", reservas, limits.ventana_segundos))
+    } else if !emails.contains(&email.to_lowercase()) && emails.len() >= limits.max_emails {
+        Some(format!("{} emails distintos en {} segundos", emails.len() + 1, limits.ventana_segundos))
+    } else {
+        None
+    };
+
+    let Some(motivo) = motivo else {
+        return Ok(());
+    };
+
+    tracing::warn!(dispositivo = %dispositivo, motivo = %motivo, "Reserva del widget bloqueada");
+
+    let violacion = ViolacionWidget {
+        id: None,
+        id_restaurante: restaurante_id,
+        dispositivo: dispositivo.to_string(),
+        motivo,
+        email_cliente: email.to_string(),
+        created_at: now,
+    };
+
+    if let Err(e) = repo.widget_violaciones().insert_one(violacion).await {
+        tracing::error!("Error registrando violación del widget: {}", e);
+    }
+
+    Err(AppError::too_many_requests(
+        "Demasiadas reservas desde este dispositivo, inténtalo más tarde",
+        limits.ventana_segundos as u64,
+    ))
+}
+
+/// Cuenta una consulta por localizador desde `ip` y la limita
+///
+/// Sin límite, el par localizador + email se podría adivinar a base de
+/// intentos. La cuenta se guarda en MongoDB, como los fallos de login, para
+/// que valga en todas las instancias y sobreviva a los reinicios.
+///
+/// # Errores
+/// - `TooManyRequests`: Si la IP agotó sus consultas en la ventana
+/// - `Database`: Error guardando la consulta
+async fn check_lookup_limit(repo: &MongoRepo, ip: &str, now: i64) -> AppResult<()> {
+    // Las consultas fuera de la ventana empiezan una cuenta nueva
+    let pipeline = vec![
+        doc! { "$set": {
+            "en_ventana": { "$gt": [{ "$ifNull": ["$ventana_desde", 0] }, now - VENTANA_CONSULTAS_SEGUNDOS] },
+        } },
+        doc! { "$set": {
+            "consultas": { "$cond": ["$en_ventana", { "$add": ["$consultas", 1] }, 1] },
+            "ventana_desde": { "$cond": ["$en_ventana", "$ventana_desde", now] },
+        } },
+        doc! { "$unset": ["en_ventana"] },
+    ];
+    let consultas = repo.consultas_localizador()
+        .find_one_and_update(doc! { "ip": ip }, pipeline)
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("check_lookup_limit", e))?
+        .ok_or(AppError::Internal("Consulta por localizador no guardada".to_string()))?;
+
+    if consultas.consultas > MAX_CONSULTAS_LOCALIZADOR {
+        let espera = consultas.ventana_desde + VENTANA_CONSULTAS_SEGUNDOS - now;
+        return Err(AppError::too_many_requests(
+            "Demasiadas consultas desde esta dirección, inténtalo más tarde",
+            espera.max(1) as u64,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Comprueba que el localizador no está bloqueado por consultas fallidas
+///
+/// El límite por IP no basta si el atacante reparte los intentos sobre un
+/// mismo localizador entre muchas IPs; los fallos se cuentan también por
+/// localizador (ver [`record_lookup_failure`]).
+///
+/// # Errores
+/// - `TooManyRequests`: Si el localizador está bloqueado
+/// - `Database`: Error consultando los fallos
+async fn check_locator_lock(repo: &MongoRepo, localizador: &str, now: i64) -> AppResult<()> {
+    let bloqueo = repo.fallos_localizador()
+        .find_one(doc! { "localizador": localizador, "bloqueado_hasta": { "$gt": now } })
+        .await
+        .map_err(|e| AppError::database("check_locator_lock", e))?;
+
+    match bloqueo {
+        Some(bloqueo) => Err(AppError::too_many_requests(
+            "Demasiadas consultas fallidas de este localizador, inténtalo más tarde",
+            (bloqueo.bloqueado_hasta - now) as u64,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Suma una consulta fallida al localizador y lo bloquea al llegar a
+/// `MAX_FALLOS_LOCALIZADOR`
+///
+/// Se cuentan también los localizadores que no existen, para que la
+/// respuesta no revele cuáles son válidos.
+///
+/// # Errores
+/// - `Database`: Error guardando el fallo
+async fn record_lookup_failure(repo: &MongoRepo, localizador: &str, now: i64) -> AppResult<()> {
+    // Como en el bloqueo de logins: los fallos fuera de la ventana empiezan
+    // una cuenta nueva y al bloquear la cuenta vuelve a cero
+    let pipeline = vec![
+        doc! { "$set": {
+            "en_ventana": { "$gt": [{ "$ifNull": ["$ventana_desde", 0] }, now - VENTANA_CONSULTAS_SEGUNDOS] },
+        } },
+        doc! { "$set": {
+            "fallos": { "$cond": ["$en_ventana", { "$add": ["$fallos", 1] }, 1] },
+            "ventana_desde": { "$cond": ["$en_ventana", "$ventana_desde", now] },
+        } },
+        doc! { "$set": {
+            "bloquear": { "$gte": ["$fallos", i64::from(MAX_FALLOS_LOCALIZADOR)] },
+        } },
+        doc! { "$set": {
+            "bloqueado_hasta": { "$cond": ["$bloquear", now + VENTANA_CONSULTAS_SEGUNDOS, { "$ifNull": ["$bloqueado_hasta", 0] }] },
+            "fallos": { "$cond": ["$bloquear", 0, "$fallos"] },
+        } },
+        doc! { "$unset": ["en_ventana", "bloquear"] },
+    ];
+
+    repo.fallos_localizador()
+        .update_one(doc! { "localizador": localizador }, pipeline)
+        .upsert(true)
+        .await
+        .map_err(|e| AppError::database("record_lookup_failure", e))?;
+
+    Ok(())
+}
+
+/// Estado de una reserva tal como se muestra al cliente
+fn reservation_status(reserva: &Reserva, restaurant: &Restaurant) -> serde_json::Value {
+    json!({
+        "localizador": reserva.localizador,
+        "restaurante": restaurant.nombre,
+        "estado": reserva.estado,
+        "fecha": reserva.fecha,
+        "hora": reserva.hora,
+        "numero_personas": reserva.numero_personas,
+        "notas_cliente": reserva.notas_cliente
+    })
+}
+
+/// Token de los enlaces de gestión de una reserva
+///
+/// Es un UUID aleatorio guardado en la reserva, que no se puede deducir de
+/// sus datos; se crea la primera vez que se pide.
+pub async fn manage_token(repo: &MongoRepo, reserva: &Reserva, now: i64) -> AppResult<String> {
+    if let Some(token) = &reserva.token_gestion {
+        return Ok(token.clone());
+    }
+
+    let token = Uuid::new_v4().to_string();
+    let result = repo.reservas()
+        .update_one(
+            doc! { "_id": reserva.id, "token_gestion": { "$exists": false } },
+            doc! { "$set": { "token_gestion": &token } },
+        )
+        .await
+        .map_err(|e| AppError::database("manage_token", e))?;
+    if result.modified_count == 1 {
+        if let Some(id_reserva) = reserva.id {
+            journal::append(repo, id_reserva, "crear_token_gestion", now).await;
+        }
+        return Ok(token);
+    }
+
+    // Otra petición lo ha creado mientras tanto
+    repo.reservas()
+        .find_one(doc! { "_id": reserva.id })
+        .await
+        .map_err(|e| AppError::database("manage_token", e))?
+        .and_then(|reserva| reserva.token_gestion)
+        .ok_or(AppError::NotFound("Reserva no encontrada".to_string()))
+}
+
+/// Enlaces de gestión de una reserva, en el formato de los emails
+pub fn manage_links(token: &str) -> String {
+    let base = public_base_url();
+    format!(
+        "Consulta tu reserva: {base}/public/reservations/manage/{token}\n\
+         Confirma que vienes: {base}/public/reservations/{token}/confirm\n\
+         Cancélala: {base}/public/reservations/{token}/cancel\n\
+         Escríbenos: {base}/public/reservations/{token}/messages\n\
+         Añádela a tu calendario: {base}/public/reservations/{token}/ics\n"
+    )
+}
+
+/// Envía al email de la reserva sus enlaces para consultarla, confirmarla
+/// y cancelarla
+///
+/// Es también el email de confirmación de las reservas del widget, con la
+/// reserva adjunta en formato iCalendar. Los fallos se registran y no
+/// interrumpen la operación.
+///
+/// # Retorna
+/// `true` si el email se envió
+async fn send_manage_link(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    restaurant: &Restaurant,
+    reserva: &Reserva,
+    now: i64,
+) -> bool {
+    let token = match manage_token(repo, reserva, now).await {
+        Ok(token) => token,
+        Err(e) => {
+            tracing::error!("Error guardando el token de gestión: {}", e);
+            return false;
+        }
+    };
+
+    let result = notifier.send_email(EmailMessage {
+        to: reserva.email_cliente.clone(),
+        subject: format!("Tu reserva en {}", restaurant.nombre),
+        body: format!(
+            "Hola {},\n\nTu reserva del {} a las {} para {} personas está {}.\n\n{}",
+            reserva.nombre_cliente, reserva.fecha, reserva.hora, reserva.numero_personas, reserva.estado,
+            manage_links(&token)
+        ),
+        adjuntos: super::calendar::ics_attachment(reserva, restaurant, &token).into_iter().collect(),
+        request_id: None,
+    }).await;
+
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            tracing::error!("Error enviando el enlace de gestión: {}", e);
+            false
+        }
+    }
+}
+
+/// Busca un restaurante por su ID público
+pub(super) async fn find_restaurant(repo: &MongoRepo, id: ObjectId) -> AppResult<Restaurant> {
+    repo.restaurants()
+        .find_one(doc! { "_id": id })
+        .await
+        .map_err(|e| AppError::database("find_restaurant", e))?
+        .ok_or(AppError::NotFound("Restaurante no encontrado".to_string()))
+}
+
+/// Envía al cliente el enlace o código de verificación de su reserva
+async fn send_verification(
+    notifier: &Notifier,
+    restaurant: &Restaurant,
+    reserva: &Reserva,
+    verificacion: &VerificacionCliente,
+) -> AppResult<()> {
+    match verificacion.metodo {
+        MetodoVerificacion::Email => {
+            let link = format!("{}/public/reservations/verify/{}", public_base_url(), verificacion.codigo);
+            notifier.send_email(EmailMessage {
+                to: reserva.email_cliente.clone(),
+                subject: format!("Confirma tu reserva en {}", restaurant.nombre),
+                body: format!(
+                    "Hola {},\n\nPara completar tu reserva del {} a las {} para {} personas, abre este enlace:\n{}\n",
+                    reserva.nombre_cliente, reserva.fecha, reserva.hora, reserva.numero_personas, link
+                ),
+                adjuntos: Vec::new(),
+                request_id: None,
+            }).await
+        }
+        MetodoVerificacion::Telefono => {
+            notifier.send_sms(SmsMessage {
+                to: reserva.telefono_cliente.clone(),
+                body: format!("{}: tu código de reserva es {}", restaurant.nombre, verificacion.codigo),
+                request_id: None,
+            }).await
+        }
+        MetodoVerificacion::Ninguna => Ok(()),
+    }
+}
+
+/// Marca una reserva "sin_confirmar" como verificada y envía al cliente el
+/// email de confirmación con sus enlaces de gestión
+///
+/// # Errores
+/// - `NotFound`: Si la reserva ya no está pendiente de verificación
+async fn complete_verification(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    reserva: &Reserva,
+    now: i64,
+) -> AppResult<EstadoReserva> {
+    let restaurant = find_restaurant(repo, reserva.id_restaurante).await?;
+    let no_shows = previous_no_shows(repo, reserva.id_cliente).await?;
+    let estado = estado_verificado(&restaurant, reserva.pago_pendiente(), no_shows);
+
+    // Las que quedan pendientes esperan al restaurante desde ahora
+    let mut set = doc! { "estado": estado, "updated_at": now };
+    if estado == EstadoReserva::Pendiente {
+        set.insert("pendiente_desde", now);
+    }
+    let result = repo.reservas()
+        .update_one(
+            doc! { "_id": reserva.id, "estado": EstadoReserva::SinConfirmar },
+            doc! {
+                "$set": set,
+                "$unset": { "verificacion": "" }
+            },
+        )
+        .await
+        .map_err(|e| AppError::database("complete_verification", e))?;
+
+    if result.modified_count == 0 {
+        return Err(AppError::NotFound("Reserva no encontrada o ya verificada".to_string()));
+    }
+    reservation_history::record(repo, reservation_history::from_outside(
+        reserva.id_restaurante,
+        reserva.id.unwrap(),
+        reservation_history::AUTOR_CLIENTE,
+        "verificar_reserva",
+        Some(&doc! { "estado": EstadoReserva::SinConfirmar }),
+        Some(&doc! { "estado": estado }),
+        now,
+    )).await;
+    if estado == EstadoReserva::Confirmada {
+        events::record(repo, TipoEvento::ReservaConfirmada, Some(restaurant.id.unwrap()), reserva.id, doc! {}, now).await;
+    }
+    send_manage_link(repo, notifier, &restaurant, &Reserva { estado, ..reserva.clone() }, now).await;
+
+    Ok(estado)
+}
+
+/// Lista las opciones de menú activas de un restaurante
+///
+/// El widget las muestra para que el cliente pueda elegirlas al reservar
+/// (campo `preseleccion` de la reserva).
+///
+/// # Respuesta
+/// Mismo formato que `GET /menu-options`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID de restaurante inválido
+/// - `404 Not Found`: Restaurante no encontrado
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/public/restaurants/{id}/menu-options")]
+async fn list_public_menu_options(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
+    find_restaurant(repo.get_ref(), restaurante_id).await?;
+
+    let opciones: Vec<MenuOptionResponse> = load_options(repo.get_ref(), restaurante_id, true)
+        .await?
+        .into_iter()
+        .map(MenuOptionResponse::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(opciones))
+}
+
+/// Fechas de una consulta de disponibilidad, sin repetir y en orden
+///
+/// # Errores
+/// - `Validation`: Fechas mal formadas, `fechas` y rango a la vez o
+///   ninguno, rango invertido o más de [`MAX_DIAS_DISPONIBILIDAD`] días
+fn batch_dates(data: &AvailabilityBatch) -> AppResult<Vec<NaiveDate>> {
+    let mut fechas = match (&data.desde, &data.hasta) {
+        (None, None) if !data.fechas.is_empty() => data.fechas.iter()
+            .map(|fecha| validate_date(fecha))
+            .collect::<AppResult<Vec<_>>>()?,
+        (Some(desde), Some(hasta)) if data.fechas.is_empty() => {
+            let (desde, hasta) = (validate_date(desde)?, validate_date(hasta)?);
+            if hasta < desde {
+                return Err(AppError::validation_field("hasta", "Debe ser igual o posterior a `desde`"));
+            }
+            desde.iter_days()
+                .take_while(|fecha| *fecha <= hasta)
+                .take(MAX_DIAS_DISPONIBILIDAD + 1)
+                .collect()
+        }
+        _ => return Err(AppError::validation_field(
+            "fechas",
+            "Indica una lista de fechas o un rango con `desde` y `hasta`",
+        )),
+    };
+    fechas.sort();
+    fechas.dedup();
+
+    if fechas.len() > MAX_DIAS_DISPONIBILIDAD {
+        return Err(AppError::validation_field(
+            "fechas",
+            &format!("Como mucho {} días por consulta", MAX_DIAS_DISPONIBILIDAD),
+        ));
+    }
+    Ok(fechas)
+}
+
+/// Consulta las horas libres de varios días para un grupo
+///
+/// Pensada para el calendario del widget: con una sola petición sabe qué
+/// días y a qué horas puede reservar un grupo de `personas` comensales.
+/// Las horas van cada 30 minutos dentro de los turnos del restaurante (todo
+/// el día si no tiene turnos) y se aplican las mismas reglas que al crear
+/// una reserva desde el widget: capacidad de las mesas, reservas que se
+/// solapan (con el margen de limpieza), distribución de mesas de cada día,
+/// franjas bloqueadas y antelación mínima y máxima.
+///
+/// Las mesas, las distribuciones, las franjas y las reservas de todos los
+/// días se cargan una sola vez por consulta.
+///
+/// Los grupos de más de `max_personas_online` comensales no reservan mesa
+/// desde el widget, así que no tienen horas libres ningún día.
+///
+/// # Cuerpo
+/// ```json
+/// { "personas": 4, "desde": "2030-06-01", "hasta": "2030-06-30" }
+/// ```
+///
+/// O bien una lista de fechas: `{ "personas": 4, "fechas": ["2030-06-14", "2030-06-15"] }`.
+/// Como mucho 62 días por consulta.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "personas": 4,
+///   "dias": [
+///     { "fecha": "2030-06-01", "horas": ["13:00", "13:30", "21:00"] },
+///     { "fecha": "2030-06-02", "horas": [] }
+///   ]
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de restaurante o fechas inválidos, demasiados
+///   días o número de personas fuera de rango
+/// - `404 Not Found`: Restaurante no encontrado
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/public/restaurants/{id}/availability/batch")]
+async fn public_availability_batch(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<AvailabilityBatch>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
+    let restaurant = find_restaurant(repo.get_ref(), restaurante_id).await?;
+    let configuracion = &restaurant.configuracion;
+
+    if data.personas <= 0 {
+        return Err(AppError::validation_field("personas", "El número de personas debe ser mayor a 0"));
+    }
+    if let Some(maximo) = configuracion.limites.max_personas.filter(|maximo| data.personas > *maximo) {
+        return Err(AppError::validation_field("personas", &format!(
+            "Como mucho {} personas por reserva", maximo
+        )));
+    }
+    let fechas = batch_dates(&data)?;
+    let grupo_grande = configuracion.max_personas_online.is_some_and(|maximo| data.personas > maximo);
+
+    let (Some(primera), Some(ultima)) = (fechas.first(), fechas.last()) else {
+        unreachable!("una consulta válida tiene al menos una fecha");
+    };
+    let duracion = configuracion.ocupacion_minutos(data.personas);
+    let intervalo = Ocupacion {
+        id_mesa: ObjectId::new(),
+        inicio: primera.and_time(NaiveTime::MIN),
+        fin: (*ultima + Duration::days(1)).and_time(NaiveTime::MIN) + Duration::minutes(i64::from(duracion)),
+    };
+    let ocupaciones = load_ocupaciones(repo.get_ref(), restaurante_id, &intervalo, configuracion, None).await?;
+    let distribuciones = load_layouts(repo.get_ref(), restaurante_id).await?;
+    let reglas = load_rules(repo.get_ref(), restaurante_id).await?;
+    let mut mesas = Vec::new();
+    let mut cursor = repo.mesas()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("public_availability_batch", e))?;
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let mesa: Mesa = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando mesa: {}", e)))?;
+        mesas.push(mesa);
+    }
+
+    let ahora = configuracion.hora_local(clock.timestamp());
+    let dias: Vec<DayAvailability> = fechas.into_iter()
+        .map(|fecha| {
+            let distribucion = availability::active_layout(&distribuciones, fecha);
+            let capacidades: Vec<CapacidadMesa> = mesas.iter()
+                .filter(|mesa| distribucion.is_none_or(|distribucion| distribucion.incluye(mesa)))
+                .map(CapacidadMesa::from)
+                .collect();
+            let horas = availability::day_starts(&configuracion.turnos, fecha, PASO_DISPONIBILIDAD_MINUTOS)
+                .into_iter()
+                .filter(|inicio| {
+                    !grupo_grande
+                        && configuracion.limites.dentro_de_plazo(*inicio, ahora)
+                        && availability::blocking_rule(&reglas, *inicio).is_none()
+                        && !availability::available_tables(&capacidades, &ocupaciones, *inicio, duracion, data.personas).is_empty()
+                })
+                .map(|inicio| inicio.format(availability::FORMATO_HORA).to_string())
+                .collect();
+            DayAvailability { fecha: fecha.format(availability::FORMATO_FECHA).to_string(), horas }
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({ "personas": data.personas, "dias": dias })))
+}
+
+/// Crea una reserva desde el widget público de un restaurante
+///
+/// Aplica las mismas validaciones que `POST /reservations`, las franjas
+/// bloqueadas del restaurante (`/slot-rules`) y los límites anti-duplicados
+/// por dispositivo. Según la
+/// configuración `verificacion_cliente` del restaurante:
+/// - `ninguna`: la reserva queda "pendiente" (o "confirmada" si el restaurante
+///   confirma automáticamente)
+/// - `email`: se envía un enlace mágico y la reserva queda "sin_confirmar"
+/// - `telefono`: se envía un código por SMS y la reserva queda "sin_confirmar"
+///
+/// El cliente puede dejar sus peticiones en `notas_cliente`; `notas_internas`
+/// se ignora, porque son del personal.
+///
+/// Si el widget no envía el `idioma` del cliente, se deduce de la cabecera
+/// `Accept-Language` y del prefijo del teléfono, y si no del idioma del
+/// restaurante (ver [`crate::language`]).
+///
+/// Los grupos de más de `max_personas_online` comensales no reservan mesa:
+/// con `grupos_grandes: "rechazar"` la petición falla con un mensaje que
+/// propone repartir el grupo, y con `"solicitud"` se guarda una solicitud de
+/// grupo (ver [`super::group_request`]) y se responde `202 Accepted`:
+/// ```json
+/// {
+///   "message": "Solicitud de grupo recibida; el restaurante se pondrá en contacto contigo",
+///   "id": "507f1f77bcf86cd799439015",
+///   "tipo": "solicitud_grupo",
+///   "estado": "nueva"
+/// }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Reserva creada, revisa tu email para confirmarla",
+///   "id": "507f1f77bcf86cd799439011",
+///   "estado": "sin_confirmar",
+///   "localizador": "K7Q2MX9D",
+///   "verificacion": "email",
+///   "pago_centimos": null
+/// }
+/// ```
+///
+/// Si la configuración del restaurante exige pago por adelantado a grupos de
+/// ese tamaño (`deposito`), `pago_centimos` es el importe a pagar y la
+/// reserva no se confirma hasta que el personal lo marca como pagado.
+///
+/// Las reservas que no necesitan verificación reciben enseguida el email de
+/// confirmación con sus enlaces para consultarla, confirmarla y cancelarla;
+/// las demás, al verificarse.
+///
+/// # Errores
+/// - `400 Bad Request`: Datos de validación incorrectos o grupo más grande que el máximo online
+/// - `404 Not Found`: Restaurante o mesa no encontrados
+/// - `409 Conflict`: Ya existe una reserva para esa fecha/hora o la franja está bloqueada
+/// - `429 Too Many Requests`: Demasiadas reservas desde el mismo dispositivo
+/// - `500 Internal Server Error`: Error de base de datos o de envío
+#[post("/public/restaurants/{id}/reservations")]
+async fn create_public_reservation(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<MakeReservation>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    let restaurante_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
+    let restaurant = find_restaurant(repo.get_ref(), restaurante_id).await?;
+    let now = clock.timestamp();
+    let dispositivo = device_id(&req);
+    let idioma = language::detect(
+        data.idioma.as_deref(),
+        req.headers().get(header::ACCEPT_LANGUAGE).and_then(|valor| valor.to_str().ok()),
+        &data.telefono_cliente,
+        &restaurant.configuracion.idioma,
+    );
+
+    let configuracion = &restaurant.configuracion;
+    if let Some(maximo) = configuracion.max_personas_online.filter(|maximo| data.numero_personas > *maximo) {
+        if configuracion.grupos_grandes == PoliticaGruposGrandes::Rechazar {
+            return Err(AppError::validation_field(
+                "numero_personas",
+                &group_request::guidance(data.numero_personas, maximo),
+            ));
+        }
+        check_device_limits(repo.get_ref(), restaurante_id, dispositivo.as_deref(), &data.email_cliente, now).await?;
+        let data = data.into_inner();
+        let solicitud = group_request::store(repo.get_ref(), restaurante_id, NuevaSolicitud {
+            nombre_cliente: data.nombre_cliente,
+            email_cliente: data.email_cliente,
+            telefono_cliente: data.telefono_cliente,
+            numero_personas: data.numero_personas,
+            fecha: data.fecha,
+            hora: Some(data.hora),
+            mensaje: data.notas_cliente,
+            idioma: None,
+        }, idioma, now).await?;
+        return Ok(group_request::accepted(&solicitud));
+    }
+
+    let id_mesa = validate_new_reservation(repo.get_ref(), &restaurant, &data, now).await?;
+
+    let reglas = load_rules(repo.get_ref(), restaurante_id).await?;
+    let inicio = availability::parse_inicio(&data.fecha, &data.hora)
+        .ok_or(AppError::Validation("Fecha u hora inválidas".to_string()))?;
+    if let Some(regla) = availability::blocking_rule(&reglas, inicio) {
+        return Err(AppError::Conflict(match regla.motivo.as_str() {
+            "" => "No se aceptan reservas en esa franja horaria".to_string(),
+            motivo => format!("No se aceptan reservas en esa franja horaria: {}", motivo),
+        }));
+    }
+
+    check_device_limits(repo.get_ref(), restaurante_id, dispositivo.as_deref(), &data.email_cliente, now).await?;
+
+    let metodo = restaurant.configuracion.verificacion_cliente;
+    let verificacion = match metodo {
+        MetodoVerificacion::Ninguna => None,
+        MetodoVerificacion::Email => Some(VerificacionCliente {
+            metodo,
+            codigo: Uuid::new_v4().to_string(),
+            expira_en: now + EXPIRACION_EMAIL_SEGUNDOS,
+            intentos: 0,
+        }),
+        MetodoVerificacion::Telefono => Some(VerificacionCliente {
+            metodo,
+            codigo: generate_sms_code(),
+            expira_en: now + EXPIRACION_SMS_SEGUNDOS,
+            intentos: 0,
+        }),
+    };
+
+    let preseleccion = resolve_preselection(repo.get_ref(), restaurante_id, &data.preseleccion).await?;
+    let id_cliente = link_customer(
+        repo.get_ref(),
+        restaurante_id,
+        &data.nombre_cliente,
+        &data.email_cliente,
+        &data.telefono_cliente,
+        true,
+        now,
+    ).await?;
+    let pago = restaurant.configuracion.deposito.and_then(|deposito| deposito.pago(data.numero_personas));
+    let estado = if verificacion.is_some() {
+        EstadoReserva::SinConfirmar
+    } else {
+        estado_verificado(&restaurant, pago.is_some(), previous_no_shows(repo.get_ref(), id_cliente).await?)
+    };
+    let mut reserva = new_reserva(restaurante_id, id_mesa, &data, estado, now);
+    reserva.canal = "publico".to_string();
+    reserva.pago = pago;
+    // Las notas internas son del personal, nunca del widget
+    reserva.notas_internas = None;
+    reserva.verificacion = verificacion.clone();
+    reserva.dispositivo = dispositivo;
+    reserva.idioma = Some(idioma);
+    reserva.preseleccion = preseleccion;
+    reserva.id_cliente = id_cliente;
+
+    let result = repo.reservas()
+        .insert_one(&reserva)
+        .await
+        .map_err(|e| AppError::Internal(format!("Error guardando reserva: {}", e)))?;
+    let id = result.inserted_id.as_object_id().unwrap();
+    if let Some(id_cliente) = reserva.id_cliente {
+        learn_preference(repo.get_ref(), id_cliente).await;
+    }
+    reservation_history::record(repo.get_ref(), reservation_history::from_outside(
+        restaurante_id,
+        id,
+        reservation_history::AUTOR_CLIENTE,
+        "crear_reserva",
+        None,
+        audit::snapshot(&reserva).as_ref(),
+        now,
+    )).await;
+    events::record(
+        repo.get_ref(),
+        TipoEvento::ReservaCreada,
+        Some(restaurante_id),
+        Some(id),
+        doc! {
+            "origen": "widget",
+            "fecha": &reserva.fecha,
+            "hora": &reserva.hora,
+            "numero_personas": reserva.numero_personas,
+        },
+        now,
+    ).await;
+
+    let message = match &verificacion {
+        Some(verificacion) => {
+            send_verification(notifier.get_ref(), &restaurant, &reserva, verificacion).await?;
+            if metodo == MetodoVerificacion::Email {
+                "Reserva creada, revisa tu email para confirmarla"
+            } else {
+                "Reserva creada, introduce el código enviado a tu teléfono"
+            }
+        }
+        None => {
+            reserva.id = Some(id);
+            send_manage_link(repo.get_ref(), notifier.get_ref(), &restaurant, &reserva, now).await;
+            "Reserva creada correctamente"
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": message,
+        "id": id.to_hex(),
+        "estado": estado,
+        "localizador": reserva.localizador,
+        "verificacion": verificacion.map(|v| v.metodo),
+        "pago_centimos": reserva.pago.as_ref().map(|pago| pago.importe_centimos)
+    })))
+}
+
+/// Verifica el email del cliente mediante el enlace mágico
+///
+/// El cliente recibe después el email de confirmación de la reserva.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Reserva verificada correctamente",
+///   "id": "507f1f77bcf86cd799439011",
+///   "estado": "pendiente"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Enlace caducado
+/// - `404 Not Found`: Enlace inválido o reserva ya verificada
+#[get("/public/reservations/verify/{token}")]
+async fn verify_email_link(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let token = path.into_inner();
+
+    let reserva = repo.reservas()
+        .find_one(doc! {
+            "verificacion.codigo": &token,
+            "verificacion.metodo": "email",
+            "estado": "sin_confirmar"
+        })
+        .await
+        .map_err(|e| AppError::database("verify_email_link", e))?
+        .ok_or(AppError::NotFound("Enlace de verificación inválido".to_string()))?;
+
+    let expira_en = reserva.verificacion.as_ref().map(|v| v.expira_en).unwrap_or(0);
+    let now = clock.timestamp();
+    if expira_en < now {
+        return Err(AppError::Validation("El enlace de verificación ha caducado".to_string()));
+    }
+
+    let estado = complete_verification(repo.get_ref(), notifier.get_ref(), &reserva, now).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Reserva verificada correctamente",
+        "id": reserva.id.unwrap().to_hex(),
+        "estado": estado
+    })))
+}
+
+/// Verifica el teléfono del cliente con el código recibido por SMS
+///
+/// Tras 5 intentos fallidos el código queda bloqueado. El cliente recibe
+/// después el email de confirmación de la reserva.
+///
+/// # Errores
+/// - `400 Bad Request`: Código incorrecto, caducado o bloqueado
+/// - `404 Not Found`: Reserva no encontrada o ya verificada
+#[post("/public/reservations/{id}/verify")]
+async fn verify_sms_code(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<VerifyCode>,
+) -> AppResult<impl Responder> {
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+
+    let reserva = repo.reservas()
+        .find_one(doc! {
+            "_id": reservation_id,
+            "verificacion.metodo": "telefono",
+            "estado": "sin_confirmar"
+        })
+        .await
+        .map_err(|e| AppError::database("verify_sms_code", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada o ya verificada".to_string()))?;
+
+    let verificacion = reserva.verificacion.clone().unwrap();
+
+    if verificacion.intentos >= MAX_INTENTOS_VERIFICACION {
+        return Err(AppError::Validation("Demasiados intentos, solicita una nueva reserva".to_string()));
+    }
+
+    let now = clock.timestamp();
+    if verificacion.expira_en < now {
+        return Err(AppError::Validation("El código de verificación ha caducado".to_string()));
+    }
+
+    if data.codigo.trim() != verificacion.codigo {
+        repo.reservas()
+            .update_one(
+                doc! { "_id": reservation_id },
+                doc! { "$inc": { "verificacion.intentos": 1 } },
+            )
+            .await
+            .map_err(|e| AppError::database("verify_sms_code", e))?;
+        journal::append(repo.get_ref(), reservation_id, "intento_verificacion", now).await;
+
+        return Err(AppError::validation_field("codigo", "Código de verificación incorrecto"));
+    }
+
+    let estado = complete_verification(repo.get_ref(), notifier.get_ref(), &reserva, now).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Reserva verificada correctamente",
+        "id": reservation_id.to_hex(),
+        "estado": estado
+    })))
+}
+
+/// Consulta el estado de una reserva con su localizador y el email del cliente
+///
+/// Para clientes que han perdido el enlace de su reserva. El enlace de
+/// gestión no se devuelve en la respuesta: se reenvía al email de la
+/// reserva, de modo que solo lo recibe su titular. Las reservas que aún no
+/// han verificado el contacto ("sin_confirmar") no reciben enlace.
+///
+/// Cada IP puede hacer 10 consultas cada 15 minutos, y un localizador se
+/// bloquea durante 15 minutos tras 5 consultas fallidas, desde cualquier IP,
+/// para que repartir los intentos entre muchas IPs no sirva para adivinar
+/// el email. Mientras está bloqueado se rechaza incluso el email correcto.
+///
+/// # Cuerpo
+/// ```json
+/// { "localizador": "K7Q2MX9D", "email": "juan@email.com" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "localizador": "K7Q2MX9D",
+///   "restaurante": "La Tasca",
+///   "estado": "confirmada",
+///   "fecha": "2024-12-25",
+///   "hora": "20:00",
+///   "numero_personas": 2,
+///   "notas_cliente": "Cumpleaños, trona",
+///   "enlace_enviado": true
+/// }
+/// ```
+///
+/// # Errores
+/// - `404 Not Found`: No hay ninguna reserva con ese localizador y email
+/// - `429 Too Many Requests`: Demasiadas consultas desde la misma IP, o
+///   localizador bloqueado por consultas fallidas
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/public/reservations/lookup")]
+async fn lookup_reservation(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<LookupReservation>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    let now = clock.timestamp();
+    if let Some(ip) = client_ip(&req) {
+        check_lookup_limit(repo.get_ref(), &ip.to_string(), now).await?;
+    }
+    let localizador = data.localizador.trim().to_uppercase();
+    check_locator_lock(repo.get_ref(), &localizador, now).await?;
+
+    let reserva = repo.reservas()
+        .find_one(doc! { "localizador": &localizador })
+        .await
+        .map_err(|e| AppError::database("lookup_reservation", e))?
+        .filter(|reserva| reserva.email_cliente.eq_ignore_ascii_case(data.email.trim()));
+    let Some(reserva) = reserva else {
+        record_lookup_failure(repo.get_ref(), &localizador, now).await?;
+        return Err(AppError::NotFound("No hay ninguna reserva con ese localizador y email".to_string()));
+    };
+    let restaurant = find_restaurant(repo.get_ref(), reserva.id_restaurante).await?;
+
+    let enlace_enviado = reserva.estado != EstadoReserva::SinConfirmar
+        && send_manage_link(repo.get_ref(), notifier.get_ref(), &restaurant, &reserva, clock.timestamp()).await;
+
+    let mut respuesta = reservation_status(&reserva, &restaurant);
+    respuesta["enlace_enviado"] = json!(enlace_enviado);
+
+    Ok(HttpResponse::Ok().json(respuesta))
+}
+
+/// Muestra una reserva a través de su enlace de gestión
+///
+/// # Respuesta
+/// Igual que `POST /public/reservations/lookup`, sin `enlace_enviado`.
+///
+/// # Errores
+/// - `404 Not Found`: Enlace inválido
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/public/reservations/manage/{token}")]
+async fn view_managed_reservation(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let reserva = repo.reservas()
+        .find_one(doc! { "token_gestion": path.into_inner() })
+        .await
+        .map_err(|e| AppError::database("view_managed_reservation", e))?
+        .ok_or(AppError::NotFound("Enlace de gestión inválido".to_string()))?;
+    let restaurant = find_restaurant(repo.get_ref(), reserva.id_restaurante).await?;
+
+    Ok(HttpResponse::Ok().json(reservation_status(&reserva, &restaurant)))
+}
+
+/// Busca una reserva por el token de sus enlaces de gestión
+pub(super) async fn find_by_token(repo: &MongoRepo, token: &str) -> AppResult<Reserva> {
+    repo.reservas()
+        .find_one(doc! { "token_gestion": token })
+        .await
+        .map_err(|e| AppError::database("find_by_token", e))?
+        .ok_or(AppError::NotFound("Enlace de gestión inválido".to_string()))
+}
+
+/// Muestra la reserva que se va a cancelar desde su enlace
+///
+/// El enlace del email solo muestra la reserva; la cancela `POST` sobre la
+/// misma ruta, para que abrir el enlace (o que lo abra un filtro antispam)
+/// no la cancele.
+///
+/// # Respuesta
+/// Igual que `GET /public/reservations/manage/{token}`.
+///
+/// # Errores
+/// - `404 Not Found`: Enlace inválido
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/public/reservations/{token}/cancel")]
+async fn view_cancel_link(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let reserva = find_by_token(repo.get_ref(), &path.into_inner()).await?;
+    let restaurant = find_restaurant(repo.get_ref(), reserva.id_restaurante).await?;
+
+    Ok(HttpResponse::Ok().json(reservation_status(&reserva, &restaurant)))
+}
+
+/// Cancela una reserva desde su enlace de gestión
+///
+/// Se aplica la política de cancelación del restaurante igual que al
+/// cancelar desde el panel (ver `POST /reservations/{id}/cancel`). El
+/// cambio queda en el historial de la reserva como hecho por el cliente.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Reserva cancelada correctamente",
+///   "localizador": "K7Q2MX9D",
+///   "estado": "cancelada",
+///   "cancelacion_tardia": false
+/// }
+/// ```
+///
+/// # Errores
+/// - `404 Not Found`: Enlace inválido
+/// - `409 Conflict`: La reserva ya no se puede cancelar, o el plazo de
+///   cancelación ha terminado (`cancelacion_tardia`)
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/public/reservations/{token}/cancel")]
+async fn cancel_by_link(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let reserva = find_by_token(repo.get_ref(), &path.into_inner()).await?;
+    if !reserva.estado.can_transition_to(EstadoReserva::Cancelada) {
+        return Err(AppError::Conflict(format!("No se puede cancelar una reserva {}", reserva.estado)));
+    }
+    let restaurant = find_restaurant(repo.get_ref(), reserva.id_restaurante).await?;
+    let now = clock.timestamp();
+    let tardia = check_cancellation(&restaurant, &reserva, now)?;
+
+    // Solo si no ha cambiado de estado ni de hora mientras tanto
+    let mut set = doc! { "estado": EstadoReserva::Cancelada, "updated_at": now };
+    if tardia {
+        set.insert("cancelacion_tardia", true);
+    }
+    let result = repo.reservas()
+        .update_one(
+            doc! {
+                "_id": reserva.id,
+                "estado": reserva.estado,
+                "fecha": &reserva.fecha,
+                "hora": &reserva.hora
+            },
+            doc! { "$set": set },
+        )
+        .await
+        .map_err(|e| AppError::database("cancel_by_link", e))?;
+    if result.modified_count == 0 {
+        return Err(AppError::Conflict("La reserva ha cambiado mientras tanto".to_string()));
+    }
+
+    let id = reserva.id.unwrap();
+    reservation_history::record(repo.get_ref(), reservation_history::from_outside(
+        reserva.id_restaurante,
+        id,
+        reservation_history::AUTOR_CLIENTE,
+        "cancelar_reserva",
+        Some(&doc! { "estado": reserva.estado }),
+        Some(&doc! { "estado": EstadoReserva::Cancelada }),
+        now,
+    )).await;
+    events::record(
+        repo.get_ref(),
+        TipoEvento::ReservaCancelada,
+        Some(reserva.id_restaurante),
+        Some(id),
+        doc! { "estado_anterior": reserva.estado, "tardia": tardia, "origen": "cliente" },
+        now,
+    ).await;
+    if let Some(id_cliente) = reserva.id_cliente {
+        discount_visit(repo.get_ref(), id_cliente).await?;
+        learn_preference(repo.get_ref(), id_cliente).await;
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Reserva cancelada correctamente",
+        "localizador": reserva.localizador,
+        "estado": EstadoReserva::Cancelada,
+        "cancelacion_tardia": tardia
+    })))
+}
+
+/// Muestra la reserva cuya asistencia se va a confirmar desde su enlace
+///
+/// Como con la cancelación, la confirmación es `POST` sobre la misma ruta.
+///
+/// # Respuesta
+/// Igual que `GET /public/reservations/manage/{token}`.
+///
+/// # Errores
+/// - `404 Not Found`: Enlace inválido
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/public/reservations/{token}/confirm")]
+async fn view_confirm_link(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let reserva = find_by_token(repo.get_ref(), &path.into_inner()).await?;
+    let restaurant = find_restaurant(repo.get_ref(), reserva.id_restaurante).await?;
+
+    Ok(HttpResponse::Ok().json(reservation_status(&reserva, &restaurant)))
+}
+
+/// Confirma desde su enlace de gestión que el cliente vendrá
+///
+/// No cambia el estado de la reserva, que sigue dependiendo del
+/// restaurante: se anota `confirmada_por_cliente_en`, que el personal ve en
+/// los listados para no tener que llamar. Confirmar de nuevo no cambia
+/// nada. Si la reserva cambia de fecha u hora, hay que volver a confirmar.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Asistencia confirmada, te esperamos",
+///   "localizador": "K7Q2MX9D",
+///   "estado": "pendiente",
+///   "confirmada_por_cliente_en": 1717243200
+/// }
+/// ```
+///
+/// # Errores
+/// - `404 Not Found`: Enlace inválido
+/// - `409 Conflict`: La reserva no está pendiente ni confirmada
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/public/reservations/{token}/confirm")]
+async fn confirm_by_link(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let reserva = find_by_token(repo.get_ref(), &path.into_inner()).await?;
+    if !matches!(reserva.estado, EstadoReserva::Pendiente | EstadoReserva::Confirmada) {
+        return Err(AppError::Conflict(format!("No se puede confirmar una reserva {}", reserva.estado)));
+    }
+
+    let confirmada_en = match reserva.confirmada_por_cliente_en {
+        Some(momento) => momento,
+        None => {
+            let now = clock.timestamp();
+            repo.reservas()
+                .update_one(
+                    doc! { "_id": reserva.id, "confirmada_por_cliente_en": { "$exists": false } },
+                    doc! { "$set": { "confirmada_por_cliente_en": now } },
+                )
+                .await
+                .map_err(|e| AppError::database("confirm_by_link", e))?;
+            reservation_history::record(repo.get_ref(), reservation_history::from_outside(
+                reserva.id_restaurante,
+                reserva.id.unwrap(),
+                reservation_history::AUTOR_CLIENTE,
+                "confirmar_asistencia",
+                None,
+                Some(&doc! { "confirmada_por_cliente_en": now }),
+                now,
+            )).await;
+            now
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Asistencia confirmada, te esperamos",
+        "localizador": reserva.localizador,
+        "estado": reserva.estado,
+        "confirmada_por_cliente_en": confirmada_en
+    })))
+}
+
+/// Configura las rutas públicas del widget
+///
+/// # Rutas disponibles
+/// - `GET /public/restaurants/{id}/menu-options` - Opciones de menú activas
+/// - `POST /public/restaurants/{id}/availability/batch` - Horas libres de varios días
+/// - `POST /public/restaurants/{id}/reservations` - Crear reserva desde el widget
+/// - `GET /public/reservations/verify/{token}` - Verificar email (enlace mágico)
+/// - `POST /public/reservations/{id}/verify` - Verificar teléfono (código SMS)
+/// - `POST /public/reservations/lookup` - Consultar reserva por localizador y email
+/// - `GET /public/reservations/manage/{token}` - Ver reserva desde su enlace de gestión
+/// - `GET/POST /public/reservations/{token}/cancel` - Ver y cancelar reserva desde su enlace
+/// - `GET/POST /public/reservations/{token}/confirm` - Ver reserva y confirmar asistencia desde su enlace
+///
+/// # Autenticación
+/// Ninguna: son rutas abiertas al público.
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_public_menu_options);
+    cfg.service(public_availability_batch);
+    cfg.service(create_public_reservation);
+    cfg.service(verify_email_link);
+    cfg.service(lookup_reservation);
+    cfg.service(view_managed_reservation);
+    cfg.service(view_cancel_link);
+    cfg.service(cancel_by_link);
+    cfg.service(view_confirm_link);
+    cfg.service(confirm_by_link);
+    cfg.service(verify_sms_code);
+}
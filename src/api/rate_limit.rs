@@ -0,0 +1,302 @@
+//! # Límite de peticiones
+//!
+//! Middleware que frena los ataques de fuerza bruta y el abuso de la API con
+//! cubos de tokens (*token buckets*) en memoria:
+//! - Por IP en el login, el registro y la recuperación de cuentas
+//!   (`/restaurants/login`, `/restaurants/register`, `/staff/login`,
+//!   `/restaurants/forgot-password`, `/restaurants/reset-password`,
+//!   `/restaurants/claim`, `/restaurants/claim/verify`, `/auth/google/*`)
+//! - Por IP en las rutas públicas del widget (`/public/*`)
+//! - Por token en las peticiones autenticadas con `Authorization: Bearer`.
+//!   Solo los tokens de alguna sesión, empleado o clave de API tienen cubo
+//!   propio; los demás cuentan como intentos de adivinar un token y gastan
+//!   un cubo por IP con el límite del login
+//!
+//! La IP es la de la conexión, o la de `X-Forwarded-For` solo si la conexión
+//! viene de un proxy de confianza (ver [`super::client_ip`]).
+//!
+//! Al agotar un cubo se responde `429 Too Many Requests` con la cabecera
+//! `Retry-After`.
+//!
+//! ## Configuración
+//!
+//! Peticiones por minuto de cada cubo (0 desactiva el límite):
+//! - `RATE_LIMIT_LOGIN`: login, registro y recuperación por IP (default: 10)
+//! - `RATE_LIMIT_PUBLICO`: rutas públicas por IP (default: 60)
+//! - `RATE_LIMIT_TOKEN`: peticiones autenticadas por token (default: 300)
+//!
+//! Los cubos viven en la memoria del proceso: con varias instancias detrás
+//! de un balanceador, cada una aplica el límite por separado. Las peticiones
+//! sin dirección remota conocida no tienen límite por IP.
+//!
+//! Cada límite guarda como mucho `MAX_CUBOS` cubos. Al llenarse se descartan
+//! primero los que ya se han recargado del todo y, si no basta, los que
+//! llevan más tiempo sin usarse; nunca se vacían todos a la vez, para que
+//! abrir muchos cubos nuevos no devuelva las peticiones a quien ya está
+//! limitado.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use super::{AppError, AppResult};
+use super::client_ip::client_ip;
+use super::request_log::bearer_token;
+use super::staff::resolve_token;
+use crate::clock::Clock;
+use crate::db::MongoRepo;
+
+/// Máximo de cubos por límite; al superarlo se descartan los llenos y los
+/// que llevan más tiempo sin usarse
+const MAX_CUBOS: usize = 10_000;
+
+/// Cubos que se descartan de una vez cuando no basta con los llenos
+const DESCARTE_ANTIGUOS: usize = MAX_CUBOS / 10;
+
+/// Tiempo durante el que se recuerda que un token es válido
+const TOKENS_TTL: Duration = Duration::from_secs(30);
+
+/// Rutas de login, registro y recuperación de cuentas, limitadas por IP
+const RUTAS_LOGIN: &[&str] = &[
+    "/restaurants/login",
+    "/restaurants/register",
+    "/staff/login",
+    "/restaurants/forgot-password",
+    "/restaurants/reset-password",
+    "/restaurants/claim",
+    "/restaurants/claim/verify",
+    "/auth/google/start",
+    "/auth/google/callback",
+];
+
+/// Cubo de tokens de una clave (IP o token de acceso)
+#[derive(Debug, Clone, Copy)]
+struct Cubo {
+    tokens: f64,
+    actualizado: Instant,
+}
+
+/// Conjunto de cubos con la misma capacidad y ritmo de recarga
+///
+/// Cada clave empieza con el cubo lleno (`por_minuto` peticiones seguidas) y
+/// recupera `por_minuto` tokens por minuto.
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use pispas_reservation::api::rate_limit::Limitador;
+///
+/// let mut limitador = Limitador::new(2);
+/// let ahora = Instant::now();
+/// assert!(limitador.check("ip:1.2.3.4", ahora).is_ok());
+/// assert!(limitador.check("ip:1.2.3.4", ahora).is_ok());
+/// assert_eq!(limitador.check("ip:1.2.3.4", ahora), Err(30));
+/// assert!(limitador.check("ip:1.2.3.4", ahora + Duration::from_secs(30)).is_ok());
+/// ```
+#[derive(Debug)]
+pub struct Limitador {
+    por_minuto: u32,
+    cubos: HashMap<String, Cubo>,
+}
+
+impl Limitador {
+    /// Crea un limitador de `por_minuto` peticiones por minuto y clave
+    pub fn new(por_minuto: u32) -> Self {
+        Limitador {
+            por_minuto: por_minuto.max(1),
+            cubos: HashMap::new(),
+        }
+    }
+
+    fn recarga_por_segundo(&self) -> f64 {
+        f64::from(self.por_minuto) / 60.0
+    }
+
+    fn recargar(&self, cubo: Cubo, ahora: Instant) -> Cubo {
+        let segundos = ahora.saturating_duration_since(cubo.actualizado).as_secs_f64();
+        Cubo {
+            tokens: (cubo.tokens + segundos * self.recarga_por_segundo()).min(f64::from(self.por_minuto)),
+            actualizado: ahora,
+        }
+    }
+
+    /// Consume un token de la clave
+    ///
+    /// # Errores
+    /// Los segundos que faltan para el siguiente token si el cubo está vacío
+    pub fn check(&mut self, clave: &str, ahora: Instant) -> Result<(), u64> {
+        if self.cubos.len() >= MAX_CUBOS && !self.cubos.contains_key(clave) {
+            self.descartar(ahora);
+        }
+
+        let lleno = Cubo { tokens: f64::from(self.por_minuto), actualizado: ahora };
+        let cubo = self.cubos.get(clave).copied().unwrap_or(lleno);
+        let mut cubo = self.recargar(cubo, ahora);
+
+        let resultado = if cubo.tokens >= 1.0 {
+            cubo.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - cubo.tokens) / self.recarga_por_segundo()).ceil().max(1.0) as u64)
+        };
+
+        self.cubos.insert(clave.to_string(), cubo);
+        resultado
+    }
+
+    /// Hace sitio para un cubo nuevo
+    ///
+    /// Descarta los cubos que ya se han recargado del todo (equivalen a uno
+    /// nuevo) y, si siguen sin caber, los [`DESCARTE_ANTIGUOS`] que llevan
+    /// más tiempo sin usarse. Un cliente limitado que sigue insistiendo
+    /// actualiza su cubo en cada petición, así que no está entre ellos.
+    fn descartar(&mut self, ahora: Instant) {
+        let por_minuto = f64::from(self.por_minuto);
+        let recarga = self.recarga_por_segundo();
+        self.cubos.retain(|_, cubo| {
+            let segundos = ahora.saturating_duration_since(cubo.actualizado).as_secs_f64();
+            cubo.tokens + segundos * recarga < por_minuto
+        });
+
+        if self.cubos.len() >= MAX_CUBOS {
+            let mut usos: Vec<Instant> = self.cubos.values().map(|cubo| cubo.actualizado).collect();
+            let (_, &mut limite, _) = usos.select_nth_unstable(DESCARTE_ANTIGUOS);
+            self.cubos.retain(|_, cubo| cubo.actualizado >= limite);
+        }
+    }
+}
+
+/// Límites configurados; `None` si están desactivados
+struct Limites {
+    login: Option<Limitador>,
+    publico: Option<Limitador>,
+    token: Option<Limitador>,
+}
+
+impl Limites {
+    fn from_env() -> Self {
+        fn limitador(name: &str, default: u32) -> Option<Limitador> {
+            let por_minuto = env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+            (por_minuto > 0).then(|| Limitador::new(por_minuto))
+        }
+
+        Limites {
+            login: limitador("RATE_LIMIT_LOGIN", 10),
+            publico: limitador("RATE_LIMIT_PUBLICO", 60),
+            token: limitador("RATE_LIMIT_TOKEN", 300),
+        }
+    }
+}
+
+fn limites() -> &'static Mutex<Limites> {
+    static LIMITES: OnceLock<Mutex<Limites>> = OnceLock::new();
+    LIMITES.get_or_init(|| Mutex::new(Limites::from_env()))
+}
+
+/// Tokens válidos vistos hace poco y cuándo se comprobaron
+fn known_tokens() -> &'static Mutex<HashMap<String, Instant>> {
+    static TOKENS: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    TOKENS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Comprueba si un token se ha visto válido hace menos de [`TOKENS_TTL`]
+fn recently_known(token: &str) -> bool {
+    known_tokens().lock().unwrap()
+        .get(token)
+        .is_some_and(|desde| desde.elapsed() < TOKENS_TTL)
+}
+
+/// Comprueba si un token es de alguna sesión, empleado o clave de API, y
+/// lo recuerda si lo es
+///
+/// Sin base de datos (en las pruebas del middleware) ningún token es válido.
+async fn resolve_known(req: &ServiceRequest, token: &str) -> AppResult<bool> {
+    let (Some(repo), Some(clock)) = (
+        req.app_data::<web::Data<MongoRepo>>(),
+        req.app_data::<web::Data<dyn Clock>>(),
+    ) else {
+        return Ok(false);
+    };
+    match resolve_token(repo.get_ref(), token, clock.timestamp()).await {
+        Ok(_) => {}
+        Err(AppError::Unauthorized(_)) => return Ok(false),
+        Err(e) => return Err(e),
+    }
+
+    let mut tokens = known_tokens().lock().unwrap();
+    if tokens.len() >= MAX_CUBOS {
+        tokens.retain(|_, desde| desde.elapsed() < TOKENS_TTL);
+    }
+    tokens.insert(token.to_string(), Instant::now());
+    Ok(true)
+}
+
+/// Consume un token del cubo `clave` de uno de los límites, si está activo
+///
+/// # Errores
+/// Los segundos a esperar si el cubo está vacío
+fn check_bucket(limitador: impl FnOnce(&mut Limites) -> Option<&mut Limitador>, clave: &str) -> Result<(), u64> {
+    let mut limites = limites().lock().unwrap_or_else(|e| e.into_inner());
+    match limitador(&mut limites) {
+        Some(limitador) => limitador.check(clave, Instant::now()),
+        None => Ok(()),
+    }
+}
+
+/// Comprueba los cubos que aplican a la petición
+///
+/// # Errores
+/// Los segundos a esperar si alguno de ellos está vacío
+async fn check_request(req: &ServiceRequest) -> Result<(), u64> {
+    let path = req.path();
+    let ip = client_ip(req.request());
+
+    if let Some(ip) = ip {
+        if RUTAS_LOGIN.contains(&path) {
+            check_bucket(|limites| limites.login.as_mut(), &format!("ip:{}", ip))?;
+        } else if path.starts_with("/public/") {
+            check_bucket(|limites| limites.publico.as_mut(), &format!("ip:{}", ip))?;
+        }
+    }
+
+    let Some(token) = bearer_token(req) else {
+        return Ok(());
+    };
+    if !recently_known(&token) {
+        // Se limita antes de consultar la base de datos, para que probar
+        // tokens no cueste una consulta por intento
+        if let Some(ip) = ip {
+            check_bucket(|limites| limites.login.as_mut(), &format!("token-ip:{}", ip))?;
+        }
+        match resolve_known(req, &token).await {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => {
+                tracing::warn!("No se pudo comprobar el token para el límite de peticiones: {}", e);
+                return Ok(());
+            }
+        }
+    }
+
+    check_bucket(|limites| limites.token.as_mut(), &token)
+}
+
+/// Middleware de límite de peticiones
+///
+/// Responde 429 con `Retry-After` sin llegar al handler si la petición
+/// agota alguno de sus cubos.
+pub async fn limit_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    if let Err(retry_after) = check_request(&req).await {
+        tracing::warn!(path = %req.path(), retry_after, "Límite de peticiones superado");
+        let error = AppError::too_many_requests("Demasiadas peticiones, inténtalo más tarde", retry_after);
+        return Ok(req.error_response(error));
+    }
+
+    next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+}
@@ -0,0 +1,218 @@
+//! # Papelera del plano
+//!
+//! `DELETE /tables/clear` borra de golpe todas las mesas (o las de una
+//! planta). Para que un error no sea irreversible:
+//!
+//! - La operación pide confirmación: la primera llamada no borra nada y
+//!   responde `428 Precondition Required` con un token de confirmación; hay
+//!   que repetirla con `?confirmacion=<token>` antes de
+//!   [`VIGENCIA_CONFIRMACION_SEGUNDOS`]. Cada token sirve una vez y solo para
+//!   la misma planta
+//! - Las mesas borradas se guardan en la colección `papelera_mesas`, y
+//!   `POST /tables/undo-last` restaura el último borrado durante
+//!   [`VENTANA_DESHACER_SEGUNDOS`], con los mismos IDs para que sus reservas
+//!   vuelvan a apuntar a ellas
+//!
+//! Cada mesa borrada o restaurada deja su entrada en la auditoría
+//! (`borrar_mesa` y `restaurar_mesa`, ver [`super::audit`]).
+//!
+//! El mismo paso de confirmación protege el borrado definitivo de reservas
+//! (ver [`super::reservation_purge`]).
+
+use actix_web::{post, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOneOptions;
+use uuid::Uuid;
+use super::{AppError, AppResult};
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{AuthenticatedRestaurant, EscribirMesas, PermisoGestion};
+use crate::clock::Clock;
+use crate::db::{is_duplicate_key, normalize_name, ConfirmacionBorrado, Mesa, MesasBorradas, MongoRepo};
+
+/// Segundos que vale un token de confirmación (5 minutos)
+pub const VIGENCIA_CONFIRMACION_SEGUNDOS: i64 = 5 * 60;
+
+/// Segundos durante los que se puede deshacer un borrado (15 minutos)
+pub const VENTANA_DESHACER_SEGUNDOS: i64 = 15 * 60;
+
+/// Operación de `DELETE /tables/clear` en las confirmaciones
+pub(super) const VACIAR_MESAS: &str = "vaciar_mesas";
+
+/// Crea el token que confirma una operación destructiva
+///
+/// # Retorna
+/// La confirmación guardada, con su token y su caducidad
+pub(super) async fn request_confirmation(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    operacion: &str,
+    id_planta: Option<ObjectId>,
+    now: i64,
+) -> AppResult<ConfirmacionBorrado> {
+    let confirmacion = ConfirmacionBorrado {
+        id: None,
+        id_restaurante: restaurante_id,
+        token: Uuid::new_v4().simple().to_string(),
+        operacion: operacion.to_string(),
+        id_planta,
+        expires_at: now + VIGENCIA_CONFIRMACION_SEGUNDOS,
+        created_at: now,
+    };
+    repo.confirmaciones_borrado()
+        .insert_one(&confirmacion)
+        .await
+        .map_err(|e| AppError::database("request_confirmation", e))?;
+    Ok(confirmacion)
+}
+
+/// Consume el token de confirmación de una operación destructiva
+///
+/// # Errores
+/// - `Validation`: El token no existe, ya se usó, ha caducado o es de otra
+///   operación, restaurante o planta
+pub(super) async fn consume_confirmation(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    operacion: &str,
+    id_planta: Option<ObjectId>,
+    token: &str,
+    now: i64,
+) -> AppResult<()> {
+    repo.confirmaciones_borrado()
+        .find_one_and_delete(doc! {
+            "token": token,
+            "id_restaurante": restaurante_id,
+            "operacion": operacion,
+            "id_planta": id_planta,
+            "expires_at": { "$gt": now },
+        })
+        .await
+        .map_err(|e| AppError::database("consume_confirmation", e))?
+        .ok_or(AppError::validation_field("confirmacion", "Confirmación inválida o caducada; repite la operación sin ella"))?;
+    Ok(())
+}
+
+/// Guarda en la papelera las mesas de un borrado
+///
+/// # Retorna
+/// Hasta cuándo se puede deshacer el borrado
+pub(super) async fn store(repo: &MongoRepo, autor: &Autor, mesas: Vec<Mesa>, now: i64) -> AppResult<i64> {
+    let borradas = MesasBorradas {
+        id: None,
+        id_restaurante: autor.id_restaurante,
+        mesas,
+        usuario: autor.usuario.clone(),
+        expires_at: now + VENTANA_DESHACER_SEGUNDOS,
+        created_at: now,
+    };
+    repo.papelera_mesas()
+        .insert_one(&borradas)
+        .await
+        .map_err(|e| AppError::database("store_deleted_tables", e))?;
+    Ok(borradas.expires_at)
+}
+
+/// Restaura las mesas del último `DELETE /tables/clear`
+///
+/// Solo dentro de la ventana para deshacer; las mesas vuelven con sus IDs y
+/// sus plantas. Un borrado restaurado sale de la papelera, así que una
+/// segunda llamada no restaura el anterior si ya ha pasado su ventana.
+///
+/// # Autenticación
+/// Requiere permiso `Gestion` (encargado o propietario).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Se restauraron 5 mesas correctamente",
+///   "mesas": 5
+/// }
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o rol sin permiso
+/// - `404 Not Found`: No hay ningún borrado que se pueda deshacer
+/// - `409 Conflict`: Desde el borrado se ha creado una mesa con el mismo
+///   nombre, o se ha eliminado la planta de alguna mesa
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/tables/undo-last")]
+async fn undo_last(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let now = clock.timestamp();
+
+    let options = FindOneOptions::builder().sort(doc! { "created_at": -1, "_id": -1 }).build();
+    let borradas = repo.papelera_mesas()
+        .find_one(doc! { "id_restaurante": restaurante_id, "expires_at": { "$gt": now } })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("undo_last", e))?
+        .ok_or(AppError::NotFound("No hay ningún borrado de mesas que deshacer".to_string()))?;
+
+    let nombres: Vec<String> = repo.mesas()
+        .distinct("nombre", doc! { "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("undo_last", e))?
+        .into_iter()
+        .filter_map(|nombre| nombre.as_str().map(normalize_name))
+        .collect();
+    if let Some(mesa) = borradas.mesas.iter().find(|mesa| nombres.contains(&normalize_name(&mesa.nombre))) {
+        return Err(AppError::Conflict(format!(
+            "Ya existe otra mesa llamada '{}'; renómbrala o bórrala antes de deshacer", mesa.nombre
+        )));
+    }
+
+    let mut plantas: Vec<ObjectId> = borradas.mesas.iter().filter_map(|mesa| mesa.id_planta).collect();
+    plantas.sort();
+    plantas.dedup();
+    if !plantas.is_empty() {
+        let existentes = repo.plantas()
+            .count_documents(doc! { "_id": { "$in": &plantas }, "id_restaurante": restaurante_id })
+            .await
+            .map_err(|e| AppError::database("undo_last", e))?;
+        if existentes < plantas.len() as u64 {
+            return Err(AppError::Conflict(
+                "La planta de alguna mesa se ha eliminado después del borrado".to_string(),
+            ));
+        }
+    }
+
+    // Quien saque el borrado de la papelera es quien lo restaura
+    repo.papelera_mesas()
+        .find_one_and_delete(doc! { "_id": borradas.id })
+        .await
+        .map_err(|e| AppError::database("undo_last", e))?
+        .ok_or(AppError::Conflict("El borrado ya se está deshaciendo".to_string()))?;
+
+    if !borradas.mesas.is_empty() {
+        if let Err(e) = repo.mesas().insert_many(&borradas.mesas).await {
+            if is_duplicate_key(&e) {
+                return Err(AppError::Conflict(
+                    "Alguna de las mesas ya se ha vuelto a crear".to_string(),
+                ));
+            }
+            return Err(AppError::database("undo_last", e));
+        }
+    }
+
+    let autor = Autor::from(&auth);
+    for mesa in &borradas.mesas {
+        audit::record(repo.get_ref(), &autor, AccionAuditoria::RestaurarMesa, mesa.id, None, audit::snapshot(mesa), now).await;
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": format!("Se restauraron {} mesas correctamente", borradas.mesas.len()),
+        "mesas": borradas.mesas.len()
+    })))
+}
+
+/// Configura las rutas de la papelera del plano
+///
+/// # Rutas disponibles
+/// - `POST /tables/undo-last` - Deshacer el último vaciado de mesas
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(undo_last);
+}
@@ -0,0 +1,109 @@
+//! # ID de petición
+//!
+//! Middleware que asigna a cada petición un ID para relacionar lo que ve el
+//! cliente con los logs del servidor:
+//!
+//! - Si la petición trae `X-Request-Id` (de un balanceador o del propio
+//!   cliente) y es válido, se usa ese; si no, se genera un UUID
+//! - Todo lo que se registra con `tracing` durante la petición va dentro del
+//!   span `peticion`, con el campo `request_id`
+//! - La respuesta lleva la cabecera `X-Request-Id`, y los cuerpos de error
+//!   el campo `request_id` (ver [`super::errors`]). Los errores internos usan
+//!   el mismo ID como código de rastreo (`trace`)
+//!
+//! Un ID válido tiene de 1 a 128 caracteres alfanuméricos, `-`, `_` o `.`.
+//!
+//! ## Trabajos en segundo plano
+//!
+//! El ID sigue a lo que la petición deja pendiente, para poder rastrear una
+//! reserva desde la petición hasta el email que se envió (o no):
+//!
+//! - Las reservas y los eventos de dominio guardan el ID de la petición que
+//!   los creó (`request_id`)
+//! - Los emails y SMS llevan el ID de la petición en curso (ver
+//!   [`crate::notifications::Notifier`])
+//! - Los recordatorios ([`crate::jobs::reminders`]) y las entregas de
+//!   webhooks, también los reintentos ([`crate::jobs::webhook_delivery`]),
+//!   se ejecutan con [`resume`] dentro del ID guardado, así que sus logs y
+//!   sus mensajes llevan el de la petición original. Los webhooks lo envían
+//!   además en la cabecera `X-Request-Id`
+
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::Error;
+use std::future::Future;
+use tracing::Instrument;
+
+/// Cabecera con el ID de la petición
+pub const CABECERA: &str = "X-Request-Id";
+
+/// Longitud máxima de un ID recibido
+const MAX_LONGITUD: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// ID de la petición en curso, si se está atendiendo dentro del middleware
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(String::clone).ok()
+}
+
+/// Ejecuta un trabajo en segundo plano como parte de la petición `id`
+///
+/// Dentro, [`current`] devuelve `id` y los logs van en el span `peticion`
+/// con ese `request_id`. Sin ID (datos anteriores al rastreo) el trabajo se
+/// ejecuta tal cual.
+pub async fn resume<F: Future>(id: Option<String>, trabajo: F) -> F::Output {
+    match id {
+        Some(id) => {
+            let span = tracing::info_span!("peticion", request_id = %id);
+            REQUEST_ID.scope(id, trabajo.instrument(span)).await
+        }
+        None => trabajo.await,
+    }
+}
+
+/// Comprueba si un `X-Request-Id` recibido se puede usar tal cual
+///
+/// ```
+/// use pispas_reservation::api::request_id::is_valid;
+///
+/// assert!(is_valid("5d1c7a6e-3f0b-4b8e-9a51-0c2f7d9e8b14"));
+/// assert!(is_valid("lb.req_42"));
+/// assert!(!is_valid(""));
+/// assert!(!is_valid("con espacios"));
+/// assert!(!is_valid(&"a".repeat(129)));
+/// ```
+pub fn is_valid(id: &str) -> bool {
+    (1..=MAX_LONGITUD).contains(&id.len())
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Middleware que asigna el ID de la petición
+///
+/// Ver la documentación del módulo.
+pub async fn assign_request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let id = req.headers()
+        .get(CABECERA)
+        .and_then(|valor| valor.to_str().ok())
+        .filter(|id| is_valid(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let span = tracing::info_span!("peticion", request_id = %id, method = %req.method(), path = %req.path());
+    let respuesta = REQUEST_ID
+        .scope(id.clone(), next.call(req).instrument(span))
+        .await;
+
+    let mut respuesta = respuesta?.map_into_boxed_body();
+    if let Ok(valor) = HeaderValue::from_str(&id) {
+        respuesta.headers_mut().insert(HeaderName::from_static("x-request-id"), valor);
+    }
+    Ok(respuesta)
+}
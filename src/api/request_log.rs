@@ -0,0 +1,361 @@
+//! # Registro de peticiones y respuestas
+//!
+//! Middleware opcional que registra con `tracing` (target `http_log`) la
+//! petición y la respuesta completas, para depurar integraciones.
+//!
+//! ## Activación
+//!
+//! - Por restaurante: con `registro_peticiones` en su configuración
+//!   (`PUT /restaurants/settings`). Se aplica a las peticiones autenticadas
+//!   con su token de acceso, que es la clave de API del restaurante.
+//! - Global: con la variable de entorno `REQUEST_LOG_ALL=true` se registran
+//!   todas las peticiones, incluidas las públicas del widget.
+//!
+//! ## Datos ocultos
+//!
+//! Antes de registrar nada se ocultan contraseñas, tokens, códigos de
+//! verificación, emails y teléfonos, tanto en los cuerpos JSON como en la
+//! query string, las cabeceras y los parámetros de la ruta. Los cuerpos que
+//! no son JSON no se registran, solo su tamaño.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use mongodb::bson::doc;
+use serde_json::Value;
+use crate::db::MongoRepo;
+
+/// Texto que sustituye a los valores ocultos
+pub const REDACTED: &str = "[OCULTO]";
+
+/// Máximo de caracteres registrados por cuerpo
+const MAX_BODY_CHARS: usize = 4096;
+
+/// Tiempo durante el que se recuerda si un token tiene el registro activo
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Máximo de tokens en caché; al superarlo se vacía (evita que tokens
+/// inventados la hagan crecer sin límite)
+const CACHE_MAX_TOKENS: usize = 10_000;
+
+/// Claves cuyo valor se oculta por completo
+const SECRET_KEYS: &[&str] = &["password", "token", "secret", "authorization", "codigo", "cookie"];
+
+/// Claves cuyo valor es un email
+const EMAIL_KEYS: &[&str] = &["email", "correo"];
+
+/// Claves cuyo valor es un teléfono
+const PHONE_KEYS: &[&str] = &["telefono", "phone", "movil"];
+
+/// Tipo de dato personal asociado a una clave
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sensible {
+    Secreto,
+    Email,
+    Telefono,
+}
+
+fn classify_key(key: &str) -> Option<Sensible> {
+    let key = key.to_lowercase();
+    let contains_any = |keys: &[&str]| keys.iter().any(|k| key.contains(k));
+
+    if contains_any(SECRET_KEYS) {
+        Some(Sensible::Secreto)
+    } else if contains_any(EMAIL_KEYS) {
+        Some(Sensible::Email)
+    } else if contains_any(PHONE_KEYS) {
+        Some(Sensible::Telefono)
+    } else {
+        None
+    }
+}
+
+fn redact_value(tipo: Sensible, value: &str) -> String {
+    match tipo {
+        Sensible::Secreto => REDACTED.to_string(),
+        Sensible::Email => mask_email(value),
+        Sensible::Telefono => mask_phone(value),
+    }
+}
+
+/// Oculta un email dejando la primera letra y el dominio
+///
+/// ```
+/// use pispas_reservation::api::request_log::mask_email;
+///
+/// assert_eq!(mask_email("ana.garcia@example.com"), "a***@example.com");
+/// ```
+pub fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((usuario, dominio)) => {
+            let inicial: String = usuario.chars().take(1).collect();
+            format!("{}***@{}", inicial, dominio)
+        }
+        None => REDACTED.to_string(),
+    }
+}
+
+/// Oculta un teléfono dejando solo sus tres últimos dígitos
+///
+/// ```
+/// use pispas_reservation::api::request_log::mask_phone;
+///
+/// assert_eq!(mask_phone("+34 600 123 456"), "***456");
+/// ```
+pub fn mask_phone(telefono: &str) -> String {
+    let digitos: Vec<char> = telefono.chars().filter(|c| c.is_ascii_digit()).collect();
+    let visibles: String = digitos[digitos.len().saturating_sub(3)..].iter().collect();
+    format!("***{}", visibles)
+}
+
+/// Oculta los datos sensibles de un documento JSON
+///
+/// Los valores se clasifican por el nombre de su clave; además, cualquier
+/// texto con forma de email se oculta aunque su clave no lo indique.
+pub fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match (classify_key(key), &*value) {
+                    (Some(tipo), Value::String(texto)) => *value = Value::String(redact_value(tipo, texto)),
+                    (Some(tipo), Value::Number(numero)) => {
+                        *value = Value::String(redact_value(tipo, &numero.to_string()))
+                    }
+                    _ => redact_json(value),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        Value::String(texto) if texto.contains('@') => *texto = mask_loose_emails(texto),
+        _ => {}
+    }
+}
+
+fn looks_like_email(texto: &str) -> bool {
+    match texto.split_once('@') {
+        Some((usuario, dominio)) => {
+            !usuario.is_empty() && dominio.contains('.') && !texto.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+/// Oculta las palabras con forma de email dentro de un texto libre
+fn mask_loose_emails(texto: &str) -> String {
+    texto
+        .split(' ')
+        .map(|palabra| {
+            if looks_like_email(palabra) {
+                mask_email(palabra)
+            } else {
+                palabra.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Oculta los datos sensibles de una query string (`a=1&b=2`)
+pub fn redact_query(query: &str) -> String {
+    query
+        .split('&')
+        .filter(|par| !par.is_empty())
+        .map(|par| match par.split_once('=') {
+            Some((key, value)) => match classify_key(key) {
+                Some(tipo) => format!("{}={}", key, redact_value(tipo, value)),
+                None if looks_like_email(value) => format!("{}={}", key, mask_email(value)),
+                None => par.to_string(),
+            },
+            None => par.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Representación registrable de un cuerpo, con los datos sensibles ocultos
+pub fn redact_body(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return String::new();
+    }
+
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(mut value) => {
+            redact_json(&mut value);
+            truncate(value.to_string())
+        }
+        Err(_) => format!("<{} bytes no JSON, no registrados>", bytes.len()),
+    }
+}
+
+fn truncate(mut texto: String) -> String {
+    if let Some((corte, _)) = texto.char_indices().nth(MAX_BODY_CHARS) {
+        texto.truncate(corte);
+        texto.push('…');
+    }
+    texto
+}
+
+/// Caché token → registro activo, para no consultar MongoDB en cada petición
+fn cache() -> &'static Mutex<HashMap<String, (bool, Instant)>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, (bool, Instant)>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Olvida el estado en caché de un token
+///
+/// Se llama al cambiar la configuración para que el cambio se aplique en la
+/// siguiente petición en lugar de esperar a que caduque la caché.
+pub fn forget_token(token: &str) {
+    cache().lock().unwrap().remove(token);
+}
+
+fn log_all() -> bool {
+    static LOG_ALL: OnceLock<bool> = OnceLock::new();
+    *LOG_ALL.get_or_init(|| {
+        env::var("REQUEST_LOG_ALL")
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+            .unwrap_or(false)
+    })
+}
+
+pub(super) fn bearer_token(req: &ServiceRequest) -> Option<String> {
+    req.headers()
+        .get("Authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::to_string)
+}
+
+/// Consulta si el token es de una sesión de un restaurante con el registro activo
+async fn registro_activo(repo: &MongoRepo, token: &str) -> mongodb::error::Result<bool> {
+    let Some(sesion) = repo.sesiones().find_one(doc! { "token": token }).await? else {
+        return Ok(false);
+    };
+    let restaurant = repo.restaurants()
+        .find_one(doc! { "_id": sesion.id_restaurante, "configuracion.registro_peticiones": true })
+        .await?;
+    Ok(restaurant.is_some())
+}
+
+/// Indica si el restaurante dueño del token tiene el registro activo
+async fn token_enabled(repo: &MongoRepo, token: &str) -> bool {
+    if let Some((activo, desde)) = cache().lock().unwrap().get(token) {
+        if desde.elapsed() < CACHE_TTL {
+            return *activo;
+        }
+    }
+
+    let activo = match registro_activo(repo, token).await {
+        Ok(activo) => activo,
+        Err(e) => {
+            tracing::warn!("No se pudo consultar el registro de peticiones: {}", e);
+            false
+        }
+    };
+
+    let mut cache = cache().lock().unwrap();
+    if cache.len() >= CACHE_MAX_TOKENS {
+        cache.clear();
+    }
+    cache.insert(token.to_string(), (activo, Instant::now()));
+    activo
+}
+
+async fn should_log(req: &ServiceRequest) -> bool {
+    if log_all() {
+        return true;
+    }
+
+    match (bearer_token(req), req.app_data::<web::Data<MongoRepo>>()) {
+        (Some(token), Some(repo)) => token_enabled(repo.get_ref(), &token).await,
+        _ => false,
+    }
+}
+
+/// Ruta de la petición con los parámetros sensibles ocultos
+///
+/// Usa los parámetros ya resueltos por el router, de forma que por ejemplo
+/// `/public/reservations/verify/{token}` no deja el token en el log.
+fn redacted_path(res: &ServiceResponse<BoxBody>) -> String {
+    let request = res.request();
+    let mut path = request.path().to_string();
+
+    for (name, value) in request.match_info().iter() {
+        if let Some(tipo) = classify_key(name) {
+            path = path.replace(value, &redact_value(tipo, value));
+        }
+    }
+
+    match request.query_string() {
+        "" => path,
+        query => format!("{}?{}", path, redact_query(query)),
+    }
+}
+
+fn redacted_headers(req: &ServiceRequest) -> Vec<String> {
+    req.headers()
+        .iter()
+        .map(|(name, value)| {
+            let value = value.to_str().unwrap_or("<no ASCII>");
+            match classify_key(name.as_str()) {
+                Some(tipo) => format!("{}: {}", name, redact_value(tipo, value)),
+                None => format!("{}: {}", name, value),
+            }
+        })
+        .collect()
+}
+
+/// Middleware de registro de peticiones y respuestas
+///
+/// Si el registro no está activo para la petición, la pasa sin tocar. Si lo
+/// está, lee el cuerpo completo, lo devuelve a la petición para que los
+/// handlers lo consuman con normalidad, y registra petición y respuesta
+/// cuando esta termina.
+pub async fn log_requests(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    if !should_log(&req).await {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    }
+
+    let inicio = Instant::now();
+    let method = req.method().to_string();
+    let headers = redacted_headers(&req);
+
+    let request_body = req.extract::<web::Bytes>().await?;
+    let (_, mut payload) = actix_http::h1::Payload::create(true);
+    payload.unread_data(request_body.clone());
+    req.set_payload(payload.into());
+
+    let res = next.call(req).await?.map_into_boxed_body();
+    let path = redacted_path(&res);
+    let status = res.status().as_u16();
+
+    let (http_req, response) = res.into_parts();
+    let (response, response_body) = response.into_parts();
+    let response_body = body::to_bytes(response_body)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.to_string()))?;
+
+    tracing::info!(
+        target: "http_log",
+        method = %method,
+        path = %path,
+        status,
+        duracion_ms = inicio.elapsed().as_millis() as u64,
+        headers = ?headers,
+        request_body = %redact_body(&request_body),
+        response_body = %redact_body(&response_body),
+        "Petición registrada"
+    );
+
+    let response = response.set_body(response_body).map_into_boxed_body();
+    Ok(ServiceResponse::new(http_req, response))
+}
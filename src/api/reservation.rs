@@ -1,41 +1,103 @@
 //! # API de Reservas
 //!
 //! Este módulo maneja todas las operaciones relacionadas con reservas:
-//! - Crear nuevas reservas
+//! - Crear nuevas reservas y sentar a clientes sin reserva
 //! - Listar reservas con filtros opcionales
+//! - Modificar reservas (mesa, hora, comensales, datos del cliente)
+//! - Mover reservas a otra mesa desde el plano
 //! - Confirmar reservas pendientes
-//! - Cancelar reservas
+//! - Cancelar reservas (y borrar definitivamente las canceladas, ver
+//!   [`super::reservation_purge`])
+//! - Seguir la reserva en sala: registrar la llegada del cliente, sentarlo,
+//!   completarla o marcar que no se presentó (ver [`EstadoReserva`])
+//! - Agrupar las reservas de un día por turno de servicio
+//! - Traspasar reservas a otro local del mismo grupo
+//! - Marcar reservas con retención legal por una disputa
+//! - Generar el ticket que se imprime a la llegada (ESC/POS)
 //!
 //! Todas las operaciones requieren autenticación mediante token Bearer.
 
-use actix_web::{post, get, web, HttpResponse, Responder, HttpRequest};
+use actix_web::{post, get, put, web, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
-use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::options::{FindOptions, ReturnDocument};
 use chrono::{NaiveDate, NaiveTime};
-use super::{AppError, AppResult};
-use super::restaurant::validate_access_token;
-use crate::db::{MongoRepo, Reserva, Mesa};
+use uuid::Uuid;
+use super::{request_id, AppError, AppResult};
+use super::fields::CamposRespuesta;
+use super::envelope::{self, Paginacion};
+use super::customer::{count_no_show, discount_visit, learn_preference, link_customer, previous_no_shows};
+use super::menu::{resolve_preselection, SeleccionInput, SeleccionResponse};
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{AuthenticatedRestaurant, EscribirReservas, LeerReservas, PermisoGestion, PermisoReservas};
+use super::layout::load_active_layout;
+use super::shift;
+use crate::availability::{self, Ocupacion};
+use crate::clock::Clock;
+use crate::db::{is_duplicate_key, localizador, Configuracion, EstadoReserva, MongoRepo, Pago, Reserva, Restaurant, RetencionLegal, Turno};
+use crate::events::{self, TipoEvento};
+use crate::language;
+use crate::notifications::{EmailMessage, Notifier};
+use crate::risk::{self, FactoresRiesgo, Riesgo};
+use crate::ticket::{self, DatosTicket};
+
+/// Reservas devueltas por defecto en cada página de `GET /reservations`
+const LIMITE_DEFECTO: i64 = 100;
+
+/// Máximo de reservas por página
+const LIMITE_MAXIMO: i64 = 500;
+
+/// Máximo de días del listado por defecto de `GET /reservations` (ver
+/// `horizonte_listado_dias` en la configuración)
+pub(super) const HORIZONTE_LISTADO_MAXIMO: u32 = 366;
+
+/// Motivo por el que no se confirma una reserva con pagos pendientes
+pub(super) const PAGO_PENDIENTE: &str =
+    "La reserva tiene pagos pendientes: el depósito no alcanza el umbral o falta el pago por adelantado";
+
+/// Orden por defecto de `GET /reservations`: las más recientes primero
+const ORDEN_DEFECTO: &str = "-fecha";
+
+/// Longitud mínima y máxima del texto de búsqueda (`q`) de `GET /reservations`
+const LONGITUD_BUSQUEDA: std::ops::RangeInclusive<usize> = 2..=100;
 
 /// Estructura para crear una nueva reserva
 ///
 /// Contiene toda la información necesaria para realizar una reserva:
 /// mesa, datos del cliente, fecha/hora y número de comensales.
 #[derive(Deserialize)]
-struct MakeReservation {
+pub(super) struct MakeReservation {
     /// ID de la mesa a reservar (ObjectId como string)
-    id_mesa: String,
+    pub(super) id_mesa: String,
     /// Nombre completo del cliente
-    nombre_cliente: String,
+    pub(super) nombre_cliente: String,
     /// Email del cliente (usado para confirmaciones)
-    email_cliente: String,
+    pub(super) email_cliente: String,
     /// Teléfono del cliente
-    telefono_cliente: String,
+    pub(super) telefono_cliente: String,
     /// Número de comensales
-    numero_personas: i32,
+    pub(super) numero_personas: i32,
     /// Fecha de la reserva (formato YYYY-MM-DD)
-    fecha: String,
+    pub(super) fecha: String,
     /// Hora de la reserva (formato HH:MM)
-    hora: String,
+    pub(super) hora: String,
+    /// Opciones de menú elegidas por adelantado (opcional)
+    #[serde(default)]
+    pub(super) preseleccion: Vec<SeleccionInput>,
+    /// UUID generado por el cliente para crearla sin conexión (opcional,
+    /// solo en el panel)
+    #[serde(default)]
+    pub(super) uuid: Option<String>,
+    /// Idioma en el que escribir al cliente (opcional, ej. "en"); sin él se
+    /// deduce (ver [`crate::language`])
+    #[serde(default)]
+    pub(super) idioma: Option<String>,
+    /// Peticiones del cliente, que él mismo puede ver (opcional)
+    #[serde(default)]
+    pub(super) notas_cliente: Option<String>,
+    /// Notas del personal (opcional, solo en el panel)
+    #[serde(default)]
+    pub(super) notas_internas: Option<String>,
 }
 
 /// Estructura de respuesta para una reserva
@@ -43,7 +105,7 @@ struct MakeReservation {
 /// Versión simplificada del modelo Reserva para envío al frontend,
 /// con ObjectIds convertidos a strings.
 #[derive(Serialize)]
-struct ReservationResponse {
+pub(super) struct ReservationResponse {
     /// ID único de la reserva (ObjectId convertido a string)
     id: String,
     /// ID del restaurante (ObjectId convertido a string)
@@ -62,8 +124,45 @@ struct ReservationResponse {
     fecha: String,
     /// Hora de la reserva
     hora: String,
-    /// Estado actual ("pendiente", "confirmada", "cancelada")
-    estado: String,
+    /// Estado actual (ver [`EstadoReserva`])
+    estado: EstadoReserva,
+    /// Origen de la reserva ("interno", "publico" o "sala")
+    canal: String,
+    /// Opciones de menú elegidas por adelantado
+    preseleccion: Vec<SeleccionResponse>,
+    /// Código con el que el cliente consulta su reserva
+    localizador: Option<String>,
+    /// Retención legal, si la reserva está en disputa
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retencion_legal: Option<RetencionLegal>,
+    /// UUID del cliente con el que se creó, si lo tiene
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uuid: Option<String>,
+    /// Idioma en el que se escribe al cliente
+    #[serde(skip_serializing_if = "Option::is_none")]
+    idioma: Option<String>,
+    /// Peticiones del cliente
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notas_cliente: Option<String>,
+    /// Notas del personal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notas_internas: Option<String>,
+    /// Pago por adelantado, si el restaurante lo exige (ver [`super::deposit`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pago: Option<Pago>,
+    /// Cancelada fuera del plazo de la política de cancelación
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cancelacion_tardia: bool,
+    /// Riesgo de que el cliente no se presente; solo en los listados y para
+    /// las reservas que aún no han llegado (ver [`crate::risk`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    riesgo: Option<Riesgo>,
+    /// Momento (timestamp unix) en que se envió el recordatorio al cliente
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recordatorio_enviado_en: Option<i64>,
+    /// Momento (timestamp unix) en que el cliente confirmó que vendrá
+    #[serde(skip_serializing_if = "Option::is_none")]
+    confirmada_por_cliente_en: Option<i64>,
 }
 
 /// Parámetros de consulta para listar reservas
@@ -71,34 +170,80 @@ struct ReservationResponse {
 struct ReservationQuery {
     /// Filtrar por fecha específica (formato YYYY-MM-DD)
     fecha: Option<String>,
-    /// Filtrar por estado ("pendiente", "confirmada", "cancelada")
+    /// Desde esta fecha, incluida (formato YYYY-MM-DD)
+    fecha_desde: Option<String>,
+    /// Hasta esta fecha, incluida (formato YYYY-MM-DD)
+    fecha_hasta: Option<String>,
+    /// Filtrar por estado, o por varios separados por comas
+    /// ("sin_confirmar", "pendiente", "confirmada", "sentada", "completada",
+    /// "no_show", "cancelada")
     estado: Option<String>,
+    /// Filtrar por mesa (ObjectId como string)
+    id_mesa: Option<String>,
+    /// Buscar por nombre, email o teléfono del cliente
+    q: Option<String>,
+    /// Orden: "fecha", "created_at" o "nombre_cliente", con "-" delante
+    /// para orden descendente (por defecto "-fecha")
+    sort: Option<String>,
+    /// Página a devolver, empezando en 1
+    page: Option<u64>,
+    /// Reservas por página (por defecto 100, como mucho 500)
+    limit: Option<i64>,
 }
 
-/// Extrae el token Bearer del header Authorization
-///
-/// # Parámetros
-/// - `req`: Request HTTP que contiene los headers
-///
-/// # Retorna
-/// El token extraído sin el prefijo "Bearer "
-///
-/// # Errores
-/// - `Unauthorized`: Si falta el header, es inválido o no tiene el formato correcto
-fn extract_token(req: &HttpRequest) -> AppResult<String> {
-    let auth_header = req.headers()
-        .get("authorization")
-        .ok_or(AppError::Unauthorized("Falta header Authorization".to_string()))?;
+/// Parámetros de consulta para agrupar reservas por turno
+#[derive(Deserialize)]
+struct ShiftQuery {
+    /// Fecha a consultar (formato YYYY-MM-DD)
+    fecha: String,
+    /// Devolver solo el grupo de este turno
+    turno: Option<String>,
+}
+
+/// Grupo de reservas de un turno con sus totales
+#[derive(Serialize)]
+struct ShiftGroup {
+    /// Nombre del turno (o "sin_turno" para las reservas fuera de todos los turnos)
+    nombre: String,
+    /// Hora de inicio del turno
+    hora_inicio: Option<String>,
+    /// Hora de fin del turno
+    hora_fin: Option<String>,
+    /// Número de reservas no canceladas del turno
+    total_reservas: u32,
+    /// Número de comensales de las reservas no canceladas del turno
+    total_personas: i32,
+    /// Reservas del turno ordenadas por hora (incluye canceladas)
+    reservas: Vec<ReservationResponse>,
+    /// Personal que ha empezado el turno (ver [`super::shift`])
+    personal: Vec<String>,
+}
 
-    let auth_str = auth_header
-        .to_str()
-        .map_err(|_| AppError::Unauthorized("Header Authorization inválido".to_string()))?;
+impl ShiftGroup {
+    fn new(nombre: &str, turno: Option<&Turno>) -> Self {
+        ShiftGroup {
+            nombre: nombre.to_string(),
+            hora_inicio: turno.map(|t| t.hora_inicio.clone()),
+            hora_fin: turno.map(|t| t.hora_fin.clone()),
+            total_reservas: 0,
+            total_personas: 0,
+            reservas: Vec::new(),
+            personal: Vec::new(),
+        }
+    }
 
-    if !auth_str.starts_with("Bearer ") {
-        return Err(AppError::Unauthorized("Formato de token inválido".to_string()));
+    fn push(&mut self, reserva: ReservationResponse) {
+        if reserva.estado != EstadoReserva::Cancelada {
+            self.total_reservas += 1;
+            self.total_personas += reserva.numero_personas;
+        }
+        self.reservas.push(reserva);
     }
+}
 
-    Ok(auth_str[7..].to_string())
+/// Recorta una nota de la reserva y la descarta si queda vacía
+pub(super) fn clean_note(nota: &Option<String>) -> Option<String> {
+    nota.as_deref().map(str::trim).filter(|nota| !nota.is_empty()).map(String::from)
 }
 
 /// Valida un email de forma básica
@@ -111,7 +256,7 @@ fn extract_token(req: &HttpRequest) -> AppResult<String> {
 ///
 /// # Nota
 /// Esta es una validación muy básica, en producción se debería usar una librería especializada
-fn validate_email(email: &str) -> bool {
+pub(super) fn validate_email(email: &str) -> bool {
     email.contains('@') && email.contains('.')
 }
 
@@ -125,7 +270,7 @@ fn validate_email(email: &str) -> bool {
 ///
 /// # Errores
 /// - `Validation`: Si el formato de fecha es incorrecto
-fn validate_date(date_str: &str) -> AppResult<NaiveDate> {
+pub(super) fn validate_date(date_str: &str) -> AppResult<NaiveDate> {
     NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
         .map_err(|_| AppError::Validation("Formato de fecha inválido, use YYYY-MM-DD".to_string()))
 }
@@ -140,11 +285,24 @@ fn validate_date(date_str: &str) -> AppResult<NaiveDate> {
 ///
 /// # Errores
 /// - `Validation`: Si el formato de hora es incorrecto
-fn validate_time(time_str: &str) -> AppResult<NaiveTime> {
+pub(super) fn validate_time(time_str: &str) -> AppResult<NaiveTime> {
     NaiveTime::parse_from_str(time_str, "%H:%M")
         .map_err(|_| AppError::Validation("Formato de hora inválido, use HH:MM".to_string()))
 }
 
+/// Valida un UUID generado por el cliente y lo normaliza
+///
+/// # Retorna
+/// El UUID en minúsculas y con guiones
+///
+/// # Errores
+/// - `Validation`: Si no es un UUID
+pub(super) fn validate_uuid(uuid: &str) -> AppResult<String> {
+    Uuid::parse_str(uuid.trim())
+        .map(|uuid| uuid.hyphenated().to_string())
+        .map_err(|_| AppError::validation_field("uuid", "UUID inválido"))
+}
+
 /// Convierte un modelo Reserva interno a la respuesta del API
 impl From<Reserva> for ReservationResponse {
     fn from(reserva: Reserva) -> Self {
@@ -159,6 +317,19 @@ impl From<Reserva> for ReservationResponse {
             fecha: reserva.fecha,
             hora: reserva.hora,
             estado: reserva.estado,
+            canal: reserva.canal,
+            preseleccion: reserva.preseleccion.into_iter().map(SeleccionResponse::from).collect(),
+            localizador: reserva.localizador,
+            retencion_legal: reserva.retencion_legal,
+            uuid: reserva.uuid,
+            idioma: reserva.idioma,
+            notas_cliente: reserva.notas_cliente,
+            notas_internas: reserva.notas_internas,
+            pago: reserva.pago,
+            cancelacion_tardia: reserva.cancelacion_tardia,
+            riesgo: None,
+            recordatorio_enviado_en: reserva.recordatorio_enviado_en,
+            confirmada_por_cliente_en: reserva.confirmada_por_cliente_en,
         }
     }
 }
@@ -166,18 +337,46 @@ impl From<Reserva> for ReservationResponse {
 /// Crea una nueva reserva
 ///
 /// # Autenticación
-/// Requiere token Bearer válido del restaurante.
+/// Requiere permiso `Reservas` (cualquier rol del personal).
 ///
 /// # Validaciones
 /// - Nombre del cliente no puede estar vacío
 /// - Email debe tener formato válido básico
-/// - Teléfono no puede estar vacío
-/// - Número de personas debe ser mayor a 0
+/// - Teléfono no puede estar vacío, salvo que el restaurante lo haga
+///   opcional (`limites.telefono_obligatorio`)
+/// - Número de personas debe ser mayor a 0 y no pasar de `limites.max_personas`
 /// - Fecha debe ser válida (YYYY-MM-DD)
 /// - Hora debe ser válida (HH:MM)
+/// - Fecha y hora deben respetar la antelación mínima y máxima del
+///   restaurante (`limites.antelacion_minima_minutos` y
+///   `limites.antelacion_maxima_dias`)
 /// - La mesa debe existir y pertenecer al restaurante
+/// - Si ese día tiene una distribución de mesas, la mesa debe estar en ella
+///   (ver [`super::layout`])
 /// - El número de personas debe estar dentro de la capacidad de la mesa
-/// - No debe existir otra reserva activa para la misma mesa/fecha/hora
+/// - La mesa no debe tener otra reserva activa que se solape, considerando
+///   que cada reserva ocupa la mesa la duración de su número de comensales
+///   (`duracion_reserva_minutos` o `duraciones_grupo`) más
+///   `margen_limpieza_minutos` (configuración)
+///
+/// - Las opciones de `preseleccion`, si se envían, deben ser opciones de menú
+///   activas del restaurante (ver [`super::menu`])
+/// - El `uuid`, si se envía, debe ser un UUID válido
+/// - El `idioma`, si se envía, debe estar soportado (ver [`crate::language`]);
+///   si no, se deduce del prefijo del teléfono o se usa el del restaurante
+/// - Las notas, si se envían, no pueden pasar de `limites.longitud_maxima_notas`
+///   caracteres (500 por defecto)
+///
+/// # Notas
+/// `notas_cliente` son las peticiones del cliente ("cumpleaños, trona"), que
+/// también ve él al consultar su reserva; `notas_internas` son solo para el
+/// personal y no aparecen en ninguna respuesta ni mensaje al cliente.
+///
+/// # Creación condicional
+/// Las apps que crean reservas sin conexión pueden enviar su propio `uuid`.
+/// Si el restaurante ya tiene una reserva con ese UUID no se crea otra: se
+/// responde con la existente (`"message": "La reserva ya existía"`), de
+/// forma que la sincronización se puede repetir sin duplicar reservas.
 ///
 /// # Parámetros
 /// - `repo`: Repositorio MongoDB
@@ -189,26 +388,368 @@ impl From<Reserva> for ReservationResponse {
 /// {
 ///   "message": "Reserva creada correctamente",
 ///   "id": "507f1f77bcf86cd799439011",
-///   "estado": "pendiente"
+///   "estado": "pendiente",
+///   "localizador": "K7Q2MX9D",
+///   "uuid": "3f2b8c1e-6d4a-4f7b-9a0e-2c5d8e1f4a6b",
+///   "no_shows_previos": 2
 /// }
 /// ```
 ///
+/// `no_shows_previos` son las reservas anteriores del cliente (por su email
+/// o teléfono, ver [`super::customer`]) a las que no se presentó, para que
+/// el personal le pida confirmar o un depósito.
+///
 /// # Errores
 /// - `400 Bad Request`: Datos de validación incorrectos
 /// - `401 Unauthorized`: Token inválido o falta autorización
 /// - `403 Forbidden`: No tienes permiso para hacer reservas en esta mesa
 /// - `404 Not Found`: Mesa no encontrada
-/// - `409 Conflict`: Ya existe una reserva para esa fecha/hora
+/// - `409 Conflict`: Ya existe una reserva para esa fecha/hora, o la mesa no
+///   está en servicio ese día
 /// - `500 Internal Server Error`: Error de base de datos
 #[post("/reservations")]
 async fn make_reservation(
     repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
     data: web::Json<MakeReservation>,
-    req: HttpRequest,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let autor = Autor::from(&auth);
+    let (reserva, creada) = create_reservation(repo.get_ref(), &auth.restaurant, &autor, &data, clock.timestamp()).await?;
+    if !creada {
+        return Ok(existing_reservation_response(reserva));
+    }
+
+    let no_shows = previous_no_shows(repo.get_ref(), reserva.id_cliente).await?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Reserva creada correctamente",
+        "id": reserva.id.map(|id| id.to_hex()),
+        "estado": reserva.estado,
+        "localizador": reserva.localizador,
+        "uuid": reserva.uuid,
+        "no_shows_previos": no_shows
+    })))
+}
+
+/// Crea una reserva del panel, o devuelve la que ya tiene su UUID
+///
+/// Aplica las validaciones de [`make_reservation`], registra la reserva en
+/// el CRM y deja constancia en los eventos y la auditoría. La usan el
+/// handler y la sincronización de las apps sin conexión ([`super::sync`]).
+///
+/// # Retorna
+/// La reserva guardada (con su ID) y si se ha creado ahora (`false` si ya
+/// existía una con ese UUID)
+pub(super) async fn create_reservation(
+    repo: &MongoRepo,
+    restaurant: &Restaurant,
+    autor: &Autor,
+    data: &MakeReservation,
+    now: i64,
+) -> AppResult<(Reserva, bool)> {
+    let restaurante_id = restaurant.id.unwrap();
+
+    // Un UUID ya sincronizado devuelve la reserva existente sin crear otra
+    let uuid = data.uuid.as_deref().map(validate_uuid).transpose()?;
+    if let Some(uuid) = &uuid {
+        if let Some(existente) = find_by_uuid(repo, restaurante_id, uuid).await? {
+            return Ok((existente, false));
+        }
+    }
+
+    let id_mesa = validate_new_reservation(repo, restaurant, data, now).await?;
+
+    // Crear la nueva reserva y registrarla en el CRM
+    let mut reserva = new_reserva(restaurante_id, id_mesa, data, EstadoReserva::Pendiente, now);
+    reserva.uuid = uuid.clone();
+    reserva.pago = restaurant.configuracion.deposito.and_then(|deposito| deposito.pago(data.numero_personas));
+    reserva.idioma = Some(language::detect(
+        data.idioma.as_deref(),
+        None,
+        &data.telefono_cliente,
+        &restaurant.configuracion.idioma,
+    ));
+    reserva.preseleccion = resolve_preselection(repo, restaurante_id, &data.preseleccion).await?;
+    reserva.id_cliente = link_customer(
+        repo,
+        restaurante_id,
+        &data.nombre_cliente,
+        &data.email_cliente,
+        &data.telefono_cliente,
+        true,
+        reserva.created_at,
+    ).await?;
+
+    let datos = doc! {
+        "origen": "panel",
+        "fecha": &reserva.fecha,
+        "hora": &reserva.hora,
+        "numero_personas": reserva.numero_personas,
+    };
+    let despues = audit::snapshot(&reserva);
+    let result = match repo.reservas().insert_one(&reserva).await {
+        Ok(result) => result,
+        Err(e) => {
+            // Otra petición con el mismo UUID se ha adelantado
+            if let (true, Some(uuid)) = (is_duplicate_key(&e), &uuid) {
+                if let Some(existente) = find_by_uuid(repo, restaurante_id, uuid).await? {
+                    return Ok((existente, false));
+                }
+            }
+            return Err(AppError::Internal(format!("Error guardando reserva: {}", e)));
+        }
+    };
+    let id = result.inserted_id.as_object_id().unwrap();
+    reserva.id = Some(id);
+    if let Some(id_cliente) = reserva.id_cliente {
+        learn_preference(repo, id_cliente).await;
+    }
+    events::record(repo, TipoEvento::ReservaCreada, Some(restaurante_id), Some(id), datos, now).await;
+    audit::record(repo, autor, AccionAuditoria::CrearReserva, Some(id), None, despues, now).await;
+
+    Ok((reserva, true))
+}
+
+/// Reserva de un restaurante con un UUID del cliente
+pub(super) async fn find_by_uuid(repo: &MongoRepo, restaurante_id: ObjectId, uuid: &str) -> AppResult<Option<Reserva>> {
+    repo.reservas()
+        .find_one(doc! { "id_restaurante": restaurante_id, "uuid": uuid })
+        .await
+        .map_err(|e| AppError::database("find_reservation_by_uuid", e))
+}
+
+/// Respuesta de [`make_reservation`] para una reserva que ya existía con ese UUID
+fn existing_reservation_response(reserva: Reserva) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "La reserva ya existía",
+        "id": reserva.id.map(|id| id.to_hex()),
+        "estado": reserva.estado,
+        "localizador": reserva.localizador,
+        "uuid": reserva.uuid
+    }))
+}
+
+/// Nombre de las reservas de clientes sin reserva que no lo dan
+const NOMBRE_SIN_RESERVA: &str = "Cliente sin reserva";
+
+/// Estructura para sentar a un cliente sin reserva
+#[derive(Deserialize)]
+struct WalkIn {
+    /// ID de la mesa en la que se sienta (ObjectId como string)
+    id_mesa: String,
+    /// Número de comensales
+    numero_personas: i32,
+    /// Nombre del cliente (opcional)
+    #[serde(default)]
+    nombre_cliente: Option<String>,
+    /// Email del cliente (opcional)
+    #[serde(default)]
+    email_cliente: Option<String>,
+    /// Teléfono del cliente (opcional)
+    #[serde(default)]
+    telefono_cliente: Option<String>,
+    /// Fecha de llegada (YYYY-MM-DD); por defecto, la del servidor
+    #[serde(default)]
+    fecha: Option<String>,
+    /// Hora de llegada (HH:MM); por defecto, la del servidor
+    #[serde(default)]
+    hora: Option<String>,
+    /// Notas del personal (opcional)
+    #[serde(default)]
+    notas_internas: Option<String>,
+}
+
+/// Sienta a un cliente sin reserva
+///
+/// Crea una reserva del canal `"sala"` directamente en estado "sentada", que
+/// ocupa la mesa desde la hora de llegada durante la duración de su número
+/// de comensales (configuración) como cualquier otra. Los datos del cliente son
+/// opcionales: sin nombre se guarda como "Cliente sin reserva", el email solo
+/// se valida si se envía y, con email o teléfono, la visita cuenta en el CRM.
+/// No se envía ningún mensaje al cliente.
+///
+/// La fecha y la hora son las del servidor salvo que el panel envíe las
+/// suyas, que es lo normal si el restaurante no está en la zona horaria del
+/// servidor.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Cuerpo
+/// ```json
+/// { "id_mesa": "507f1f77bcf86cd799439012", "numero_personas": 2, "hora": "21:10", "notas_internas": "Terraza si se libera" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Cliente sentado",
+///   "id": "507f1f77bcf86cd799439011",
+///   "estado": "sentada",
+///   "localizador": "K7Q2MX9D",
+///   "fecha": "2030-06-15",
+///   "hora": "21:10",
+///   "ocupada_hasta": "22:40"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Mesa, comensales, fecha, hora o email inválidos
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `403 Forbidden`: La mesa es de otro restaurante
+/// - `404 Not Found`: Mesa no encontrada
+/// - `409 Conflict`: La mesa está ocupada o reservada antes de que pase la
+///   duración de la reserva, o no está en servicio ese día
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/walk-in")]
+async fn walk_in(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<WalkIn>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
 ) -> AppResult<impl Responder> {
-    let token = extract_token(&req)?;
-    let restaurante_id = validate_access_token(repo.get_ref(), &token).await?;
+    let restaurant = &auth.restaurant;
+    let restaurante_id = auth.id();
+    let now = clock.timestamp();
+    let data = data.into_inner();
+
+    let email = data.email_cliente.map(|email| email.trim().to_string()).unwrap_or_default();
+    if !email.is_empty() && !validate_email(&email) {
+        return Err(AppError::validation_field("email_cliente", "Email inválido"));
+    }
+    let ahora = clock.now();
+    let datos = MakeReservation {
+        id_mesa: data.id_mesa,
+        nombre_cliente: data.nombre_cliente
+            .map(|nombre| nombre.trim().to_string())
+            .filter(|nombre| !nombre.is_empty())
+            .unwrap_or_else(|| NOMBRE_SIN_RESERVA.to_string()),
+        email_cliente: email,
+        telefono_cliente: data.telefono_cliente.map(|telefono| telefono.trim().to_string()).unwrap_or_default(),
+        numero_personas: data.numero_personas,
+        fecha: data.fecha.unwrap_or_else(|| ahora.format(availability::FORMATO_FECHA).to_string()),
+        hora: data.hora.unwrap_or_else(|| ahora.format(availability::FORMATO_HORA).to_string()),
+        preseleccion: Vec::new(),
+        uuid: None,
+        idioma: None,
+        notas_cliente: None,
+        notas_internas: data.notas_internas,
+    };
+    let id_mesa = validate_table_slot(repo.get_ref(), restaurant, &datos, None).await?;
+
+    let mut reserva = new_reserva(restaurante_id, id_mesa, &datos, EstadoReserva::Sentada, now);
+    reserva.canal = "sala".to_string();
+    reserva.llegada_en = Some(now);
+    reserva.sentada_en = Some(now);
+    reserva.idioma = Some(language::detect(None, None, &datos.telefono_cliente, &restaurant.configuracion.idioma));
+    reserva.id_cliente = link_customer(
+        repo.get_ref(),
+        restaurante_id,
+        &datos.nombre_cliente,
+        &datos.email_cliente,
+        &datos.telefono_cliente,
+        true,
+        now,
+    ).await?;
+
+    let despues = audit::snapshot(&reserva);
+    let id = repo.reservas()
+        .insert_one(&reserva)
+        .await
+        .map_err(|e| AppError::database("walk_in", e))?
+        .inserted_id
+        .as_object_id()
+        .unwrap();
+    if let Some(id_cliente) = reserva.id_cliente {
+        learn_preference(repo.get_ref(), id_cliente).await;
+    }
+    events::record(
+        repo.get_ref(),
+        TipoEvento::ReservaCreada,
+        Some(restaurante_id),
+        Some(id),
+        doc! {
+            "origen": "sala",
+            "fecha": &reserva.fecha,
+            "hora": &reserva.hora,
+            "numero_personas": reserva.numero_personas,
+        },
+        now,
+    ).await;
+    audit::record(repo.get_ref(), &Autor::from(&auth), AccionAuditoria::CrearReserva, Some(id), None, despues, now).await;
+
+    let ocupacion = Ocupacion::from_reserva(&reserva, restaurant.configuracion.duracion_minutos(reserva.numero_personas));
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Cliente sentado",
+        "id": id.to_hex(),
+        "estado": reserva.estado,
+        "localizador": reserva.localizador,
+        "fecha": reserva.fecha,
+        "hora": reserva.hora,
+        "ocupada_hasta": ocupacion.map(|ocupacion| ocupacion.fin.format(availability::FORMATO_HORA).to_string())
+    })))
+}
+
+/// Valida los datos de una nueva reserva para un restaurante
+///
+/// Aplica las validaciones de formato, límites del restaurante, capacidad
+/// de la mesa y conflicto de horario descritas en [`make_reservation`], y
+/// el plazo de antelación en `momento`. La usan tanto el panel como las
+/// reservas públicas del widget.
+///
+/// # Retorna
+/// El `ObjectId` de la mesa reservada
+///
+/// # Errores
+/// - `Validation`: Datos de entrada incorrectos, fuera de los límites del
+///   restaurante o de la capacidad de la mesa
+/// - `NotFound`: La mesa no existe
+/// - `Unauthorized`: La mesa pertenece a otro restaurante
+/// - `Conflict`: La mesa ya tiene una reserva activa que se solapa en el
+///   tiempo, o no está en la distribución de mesas de ese día
+pub(super) async fn validate_new_reservation(
+    repo: &MongoRepo,
+    restaurant: &Restaurant,
+    data: &MakeReservation,
+    momento: i64,
+) -> AppResult<ObjectId> {
+    check_booking_window(restaurant, data, momento)?;
+    validate_reservation(repo, restaurant, data, None).await
+}
+
+/// Comprueba que en `momento` se puede reservar para la fecha y hora de
+/// `data`, según la antelación mínima y máxima del restaurante
+///
+/// # Errores
+/// - `Validation`: Fecha u hora inválidas o fuera de plazo
+fn check_booking_window(restaurant: &Restaurant, data: &MakeReservation, momento: i64) -> AppResult<()> {
+    let limites = &restaurant.configuracion.limites;
+    let inicio = validate_date(&data.fecha)?.and_time(validate_time(&data.hora)?);
+    if limites.dentro_de_plazo(inicio, restaurant.configuracion.hora_local(momento)) {
+        return Ok(());
+    }
+
+    let mensaje = match (limites.antelacion_minima_minutos, limites.antelacion_maxima_dias) {
+        (Some(minutos), Some(dias)) => format!(
+            "Se reserva con al menos {} minutos y como mucho {} días de antelación", minutos, dias
+        ),
+        (Some(minutos), None) => format!("Se reserva con al menos {} minutos de antelación", minutos),
+        (None, Some(dias)) => format!("Se reserva con como mucho {} días de antelación", dias),
+        (None, None) => unreachable!("sin plazos cualquier momento está dentro de plazo"),
+    };
+    Err(AppError::validation_field("fecha", &mensaje))
+}
 
+/// Valida los datos de una reserva nueva o modificada
+///
+/// Como [`validate_new_reservation`], pero sin contar la reserva `excluir`
+/// (la que se está modificando) al buscar conflictos de horario.
+async fn validate_reservation(
+    repo: &MongoRepo,
+    restaurant: &Restaurant,
+    data: &MakeReservation,
+    excluir: Option<ObjectId>,
+) -> AppResult<ObjectId> {
     // Validaciones de entrada
     if data.nombre_cliente.trim().is_empty() {
         return Err(AppError::Validation("El nombre del cliente es requerido".to_string()));
@@ -218,17 +759,54 @@ async fn make_reservation(
         return Err(AppError::Validation("Email inválido".to_string()));
     }
 
-    if data.telefono_cliente.trim().is_empty() {
+    if restaurant.configuracion.limites.telefono_obligatorio && data.telefono_cliente.trim().is_empty() {
         return Err(AppError::Validation("El teléfono del cliente es requerido".to_string()));
     }
 
+    validate_table_slot(repo, restaurant, data, excluir).await
+}
+
+/// Valida la mesa, los comensales, la fecha y la hora de una reserva
+///
+/// Todas las validaciones de [`validate_reservation`] salvo las de los datos
+/// del cliente, que los clientes sin reserva ([`walk_in`]) no tienen por qué
+/// dar.
+async fn validate_table_slot(
+    repo: &MongoRepo,
+    restaurant: &Restaurant,
+    data: &MakeReservation,
+    excluir: Option<ObjectId>,
+) -> AppResult<ObjectId> {
+    let restaurante_id = restaurant.id.unwrap();
+    let limites = &restaurant.configuracion.limites;
+
     if data.numero_personas <= 0 {
         return Err(AppError::Validation("El número de personas debe ser mayor a 0".to_string()));
     }
 
+    if let Some(maximo) = limites.max_personas.filter(|maximo| data.numero_personas > *maximo) {
+        return Err(AppError::validation_field("numero_personas", &format!(
+            "Como mucho {} personas por reserva", maximo
+        )));
+    }
+
+    for (campo, nota) in [("notas_cliente", &data.notas_cliente), ("notas_internas", &data.notas_internas)] {
+        if clean_note(nota).is_some_and(|nota| nota.chars().count() > limites.longitud_maxima_notas as usize) {
+            return Err(AppError::validation_field(campo, &format!(
+                "Como mucho {} caracteres", limites.longitud_maxima_notas
+            )));
+        }
+    }
+
+    if data.idioma.as_deref().is_some_and(|idioma| language::normalize(idioma).is_none()) {
+        return Err(AppError::validation_field("idioma", &format!(
+            "Idioma no soportado; use uno de: {}", language::IDIOMAS.join(", ")
+        )));
+    }
+
     // Validar formato de fecha y hora
-    let _fecha = validate_date(&data.fecha)?;
-    let _hora = validate_time(&data.hora)?;
+    let fecha = validate_date(&data.fecha)?;
+    let hora = validate_time(&data.hora)?;
 
     // Convertir id_mesa a ObjectId
     let id_mesa = ObjectId::parse_str(&data.id_mesa)
@@ -248,6 +826,16 @@ async fn make_reservation(
         return Err(AppError::Unauthorized("No tienes permiso para hacer reservas en esta mesa".to_string()));
     }
 
+    // Con una distribución ese día, solo se reservan sus mesas
+    if let Some(distribucion) = load_active_layout(repo, restaurante_id, fecha).await? {
+        if !distribucion.incluye(&mesa) {
+            return Err(AppError::Conflict(format!(
+                "La mesa '{}' no está en servicio el {} (distribución '{}')",
+                mesa.nombre, data.fecha, distribucion.nombre
+            )));
+        }
+    }
+
     // Verificar capacidad de la mesa
     if let Some(min) = mesa.min_personas {
         if data.numero_personas < min {
@@ -261,25 +849,67 @@ async fn make_reservation(
         }
     }
 
-    // Verificar que no haya conflicto de horario
-    let reservas = repo.reservas();
-    let existing = reservas
-        .find_one(doc! {
-            "id_mesa": id_mesa,
-            "fecha": &data.fecha,
-            "hora": &data.hora,
-            "estado": {"$ne": "cancelada"}
-        })
+    // Verificar que no haya conflicto de horario con la duración y el margen configurados
+    let duracion = restaurant.configuracion.ocupacion_minutos(data.numero_personas);
+    let candidata = Ocupacion::new(id_mesa, fecha.and_time(hora), duracion);
+    let ocupaciones = load_ocupaciones(repo, restaurante_id, &candidata, &restaurant.configuracion, excluir).await?;
+
+    if availability::has_conflict(&candidata, &ocupaciones) {
+        return Err(AppError::Conflict("Ya existe una reserva para esta mesa en este horario".to_string()));
+    }
+
+    Ok(id_mesa)
+}
+
+/// Carga las ocupaciones activas del restaurante que pueden solaparse con un intervalo
+///
+/// Solo consulta las fechas candidatas (ver [`availability::candidate_dates`]);
+/// la decisión de si hay solape la toma el motor de disponibilidad. Cada
+/// reserva ocupa su mesa según su número de comensales (ver
+/// [`Configuracion::ocupacion_minutos`]). La reserva `excluir`, si se
+/// indica, no cuenta.
+pub(super) async fn load_ocupaciones(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    intervalo: &Ocupacion,
+    configuracion: &Configuracion,
+    excluir: Option<ObjectId>,
+) -> AppResult<Vec<Ocupacion>> {
+    let fechas = availability::candidate_dates(intervalo.inicio, intervalo.fin);
+
+    let mut filtro = doc! {
+        "id_restaurante": restaurante_id,
+        "fecha": { "$in": fechas },
+        "estado": { "$in": EstadoReserva::ACTIVOS.to_vec() }
+    };
+    if let Some(excluir) = excluir {
+        filtro.insert("_id", doc! { "$ne": excluir });
+    }
+
+    let mut cursor = repo.reservas()
+        .find(filtro)
         .await
         .map_err(|e| AppError::Internal(format!("Error verificando conflicto: {}", e)))?;
 
-    if existing.is_some() {
-        return Err(AppError::Conflict("Ya existe una reserva para esta mesa en este horario".to_string()));
+    let mut ocupaciones = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let reserva = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?;
+        ocupaciones.extend(Ocupacion::from_reserva(&reserva, configuracion.ocupacion_minutos(reserva.numero_personas)));
     }
 
-    // Crear la nueva reserva
-    let current_time = MongoRepo::current_timestamp();
-    let reserva = Reserva {
+    Ok(ocupaciones)
+}
+
+/// Construye el documento de una nueva reserva a partir de datos ya validados
+pub(super) fn new_reserva(
+    restaurante_id: ObjectId,
+    id_mesa: ObjectId,
+    data: &MakeReservation,
+    estado: EstadoReserva,
+    current_time: i64,
+) -> Reserva {
+    Reserva {
         id: None,
         id_restaurante: restaurante_id,
         id_mesa,
@@ -289,31 +919,184 @@ async fn make_reservation(
         numero_personas: data.numero_personas,
         fecha: data.fecha.clone(),
         hora: data.hora.clone(),
-        estado: "pendiente".to_string(),
+        estado,
         created_at: current_time,
         updated_at: current_time,
+        canal: "interno".to_string(),
+        verificacion: None,
+        dispositivo: None,
+        anonimizada_en: None,
+        id_cliente: None,
+        preseleccion: Vec::new(),
+        deposito: None,
+        pago: None,
+        cancelacion_tardia: false,
+        pendiente_desde: None,
+        confirmada_en: None,
+        llegada_en: None,
+        sentada_en: None,
+        completada_en: None,
+        transferida_desde: None,
+        localizador: Some(localizador(Uuid::new_v4().as_u128())),
+        token_gestion: None,
+        retencion_legal: None,
+        uuid: None,
+        idioma: None,
+        notas_cliente: clean_note(&data.notas_cliente),
+        notas_internas: clean_note(&data.notas_internas),
+        recordatorio_enviado_en: None,
+        confirmada_por_cliente_en: None,
+        request_id: request_id::current(),
+    }
+}
+
+/// Escapa los caracteres especiales de una expresión regular
+fn escape_regex(texto: &str) -> String {
+    let mut escapado = String::with_capacity(texto.len());
+    for c in texto.chars() {
+        if "\\.+*?()|[]{}^$#&-~".contains(c) {
+            escapado.push('\\');
+        }
+        escapado.push(c);
+    }
+    escapado
+}
+
+/// Filtro de la búsqueda `q` de `GET /reservations`
+///
+/// Busca el texto, sin distinguir mayúsculas, dentro del nombre, el email o
+/// el teléfono del cliente. Cada rama usa su índice
+/// `{ id_restaurante, <campo> }`, que acota el recorrido al restaurante.
+fn search_filter(q: &str) -> Document {
+    let patron = escape_regex(q);
+    let ramas: Vec<Document> = ["nombre_cliente", "email_cliente", "telefono_cliente"]
+        .into_iter()
+        .map(|campo| doc! { campo: { "$regex": &patron, "$options": "i" } })
+        .collect();
+    doc! { "$or": ramas }
+}
+
+/// Criterio de orden de `GET /reservations` para un valor de `sort`
+///
+/// El `_id` desempata, para que las páginas no se solapen.
+fn sort_document(sort: &str) -> Option<Document> {
+    let (campo, direccion) = match sort.trim().strip_prefix('-') {
+        Some(campo) => (campo, -1),
+        None => (sort.trim(), 1),
     };
+    match campo {
+        "fecha" => Some(doc! { "fecha": direccion, "hora": direccion, "_id": direccion }),
+        "created_at" | "nombre_cliente" => Some(doc! { campo: direccion, "_id": direccion }),
+        _ => None,
+    }
+}
 
-    let result = reservas
-        .insert_one(reserva)
-        .await
-        .map_err(|e| AppError::Internal(format!("Error guardando reserva: {}", e)))?;
+/// Estados en los que el cliente aún puede no presentarse
+const ESTADOS_CON_RIESGO: [EstadoReserva; 3] = [
+    EstadoReserva::SinConfirmar,
+    EstadoReserva::Pendiente,
+    EstadoReserva::Confirmada,
+];
 
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "message": "Reserva creada correctamente",
-        "id": result.inserted_id.as_object_id().unwrap().to_hex(),
-        "estado": "pendiente"
-    })))
+/// Convierte una lista de reservas en respuestas con su riesgo de no
+/// presentarse (ver [`crate::risk`])
+///
+/// Los no-shows previos de todos los clientes se cuentan en una sola
+/// consulta.
+async fn with_risk(
+    repo: &MongoRepo,
+    restaurant: &Restaurant,
+    reservas: Vec<Reserva>,
+) -> AppResult<Vec<ReservationResponse>> {
+    let clientes: std::collections::HashSet<ObjectId> = reservas
+        .iter()
+        .filter(|reserva| ESTADOS_CON_RIESGO.contains(&reserva.estado))
+        .filter_map(|reserva| reserva.id_cliente)
+        .collect();
+
+    let mut no_shows = std::collections::HashMap::new();
+    if !clientes.is_empty() {
+        let pipeline = vec![
+            doc! { "$match": {
+                "id_restaurante": restaurant.id,
+                "id_cliente": { "$in": clientes.into_iter().collect::<Vec<_>>() },
+                "estado": EstadoReserva::NoShow,
+            } },
+            doc! { "$group": { "_id": "$id_cliente", "total": { "$sum": 1 } } },
+        ];
+        let mut cursor = repo.reservas()
+            .aggregate(pipeline)
+            .await
+            .map_err(|e| AppError::database("with_risk", e))?;
+        while cursor.advance().await.map_err(|e| AppError::database("with_risk", e))? {
+            let documento = cursor.deserialize_current()
+                .map_err(|e| AppError::Internal(format!("Error deserializando agregado: {}", e)))?;
+            if let (Ok(id), Ok(total)) = (documento.get_object_id("_id"), documento.get_i32("total")) {
+                no_shows.insert(id, total.max(0) as u32);
+            }
+        }
+    }
+
+    let reglas = &restaurant.configuracion.riesgo;
+    Ok(reservas
+        .into_iter()
+        .map(|reserva| {
+            let riesgo = ESTADOS_CON_RIESGO.contains(&reserva.estado).then(|| {
+                risk::evaluar(reglas, &FactoresRiesgo {
+                    no_shows: reserva.id_cliente.and_then(|id| no_shows.get(&id).copied()).unwrap_or(0),
+                    numero_personas: reserva.numero_personas,
+                    antelacion_minutos: availability::parse_inicio(&reserva.fecha, &reserva.hora)
+                        .map(|inicio| (inicio - restaurant.configuracion.hora_local(reserva.created_at)).num_minutes()),
+                    telefono: &reserva.telefono_cliente,
+                })
+            });
+            ReservationResponse { riesgo, ..ReservationResponse::from(reserva) }
+        })
+        .collect())
 }
 
 /// Lista las reservas de un restaurante con filtros opcionales
 ///
 /// # Autenticación
-/// Requiere token Bearer válido del restaurante.
+/// Requiere permiso `Reservas` (cualquier rol del personal).
 ///
 /// # Filtros disponibles
 /// - `fecha`: Filtrar por fecha específica (formato YYYY-MM-DD)
-/// - `estado`: Filtrar por estado ("pendiente", "confirmada", "cancelada")
+/// - `fecha_desde` / `fecha_hasta`: Rango de fechas, ambas incluidas
+/// - `estado`: Filtrar por estado ("sin_confirmar", "pendiente", "confirmada", "sentada",
+///   "completada", "no_show", "cancelada"); admite varios separados por comas
+///   (`estado=pendiente,confirmada`)
+/// - `id_mesa`: Filtrar por mesa
+/// - `q`: Buscar por cliente: reservas cuyo nombre, email o teléfono
+///   contiene el texto, sin distinguir mayúsculas (entre 2 y 100 caracteres)
+///
+/// Los filtros se combinan: una reserva tiene que cumplirlos todos.
+///
+/// Sin `fecha`, `fecha_desde` ni `fecha_hasta` solo se listan las reservas
+/// próximas: de hoy a `horizonte_listado_dias` días después (30 por
+/// defecto, ver `PUT /restaurants/settings`). Las anteriores a hoy o más
+/// lejanas hay que pedirlas con fechas explícitas. Con `q` no hay límite
+/// de fechas por defecto: la búsqueda abarca todo el historial del cliente.
+///
+/// # Paginación
+/// - `page`: Página a devolver, empezando en 1 (por defecto 1)
+/// - `limit`: Reservas por página (por defecto 100, como mucho 500)
+///
+/// # Orden
+/// `sort` admite `fecha` (fecha y hora del servicio), `created_at` (cuándo
+/// se hizo la reserva) y `nombre_cliente`; con `-` delante el orden es
+/// descendente. Por defecto, `-fecha`.
+///
+/// La cabecera `X-Total-Count` lleva el número total de reservas que
+/// cumplen los filtros, para saber cuántas páginas hay; con el sobre de
+/// respuesta, va también en `meta.paginacion` (ver [`super::envelope`]).
+///
+/// # Riesgo
+/// Las reservas sin confirmar, pendientes y confirmadas llevan `riesgo`:
+/// una puntuación de 0 a 100 de que el cliente no se presente y los motivos
+/// que la suman (no-shows previos, grupo muy grande, reserva de última hora,
+/// teléfono inalcanzable), para decidir a quién llamar antes. Las reglas son
+/// las de `configuracion.riesgo` (ver [`crate::risk`]).
 ///
 /// # Parámetros
 /// - `repo`: Repositorio MongoDB
@@ -321,7 +1104,7 @@ async fn make_reservation(
 /// - `req`: Request HTTP con el token de autorización
 ///
 /// # Respuesta
-/// Lista de reservas ordenadas por fecha/hora (más recientes primero):
+/// Una página de reservas en el orden pedido:
 /// ```json
 /// [
 ///   {
@@ -334,59 +1117,258 @@ async fn make_reservation(
 ///     "numero_personas": 2,
 ///     "fecha": "2024-12-25",
 ///     "hora": "20:00",
-///     "estado": "pendiente"
+///     "estado": "pendiente",
+///     "riesgo": { "puntuacion": 45, "motivos": ["no_shows_previos", "ultimo_momento"] }
 ///   }
 /// ]
 /// ```
 ///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
 /// # Errores
+/// - `400 Bad Request`: Fechas, `id_mesa`, `q`, `sort`, `page` o `limit` inválidos
 /// - `401 Unauthorized`: Token inválido o falta autorización
 /// - `500 Internal Server Error`: Error de base de datos
 #[get("/reservations")]
 async fn get_reservations(
     repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    campos: CamposRespuesta,
     query: web::Query<ReservationQuery>,
-    req: HttpRequest,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerReservas>,
 ) -> AppResult<impl Responder> {
-    let token = extract_token(&req)?;
-    let user_id = validate_access_token(repo.get_ref(), &token).await?;
+    let user_id = auth.id();
 
     // Construir filtro dinámico basado en parámetros
     let mut filter = doc! { "id_restaurante": user_id };
 
+    let mut fechas = Document::new();
     if let Some(fecha) = &query.fecha {
-        filter.insert("fecha", fecha);
+        validate_date(fecha).map_err(|_| AppError::validation_field("fecha", "Formato de fecha inválido, use YYYY-MM-DD"))?;
+        fechas.insert("$eq", fecha);
+    }
+    if let Some(desde) = &query.fecha_desde {
+        validate_date(desde).map_err(|_| AppError::validation_field("fecha_desde", "Formato de fecha inválido, use YYYY-MM-DD"))?;
+        fechas.insert("$gte", desde);
+    }
+    if let Some(hasta) = &query.fecha_hasta {
+        validate_date(hasta).map_err(|_| AppError::validation_field("fecha_hasta", "Formato de fecha inválido, use YYYY-MM-DD"))?;
+        fechas.insert("$lte", hasta);
+    }
+    let busqueda = query.q.as_deref().map(str::trim);
+    if fechas.is_empty() && busqueda.is_none() {
+        // Sin fechas, solo las próximas: así un panel no descarga años de reservas
+        let configuracion = &auth.restaurant.configuracion;
+        let hoy = configuracion.hora_local(clock.timestamp()).date();
+        let horizonte = hoy + chrono::Duration::days(i64::from(configuracion.horizonte_listado_dias));
+        fechas.insert("$gte", hoy.format("%Y-%m-%d").to_string());
+        fechas.insert("$lte", horizonte.format("%Y-%m-%d").to_string());
+    }
+    if !fechas.is_empty() {
+        filter.insert("fecha", fechas);
+    }
+
+    if let Some(q) = busqueda {
+        if !LONGITUD_BUSQUEDA.contains(&q.chars().count()) {
+            return Err(AppError::validation_field(
+                "q",
+                &format!(
+                    "La búsqueda debe tener entre {} y {} caracteres",
+                    LONGITUD_BUSQUEDA.start(),
+                    LONGITUD_BUSQUEDA.end(),
+                ),
+            ));
+        }
+        filter.extend(search_filter(q));
     }
 
     if let Some(estado) = &query.estado {
-        filter.insert("estado", estado);
+        let estados: Vec<&str> = estado
+            .split(',')
+            .map(str::trim)
+            .filter(|estado| !estado.is_empty())
+            .collect();
+        if let Some(desconocido) = estados.iter().find(|estado| EstadoReserva::parse(estado).is_none()) {
+            return Err(AppError::validation_field("estado", &format!("Estado desconocido: '{}'", desconocido)));
+        }
+        match estados.as_slice() {
+            [] => {}
+            [estado] => { filter.insert("estado", *estado); }
+            _ => { filter.insert("estado", doc! { "$in": estados }); }
+        }
+    }
+
+    if let Some(id_mesa) = &query.id_mesa {
+        let id_mesa = ObjectId::parse_str(id_mesa)
+            .map_err(|_| AppError::validation_field("id_mesa", "ID de mesa inválido"))?;
+        filter.insert("id_mesa", id_mesa);
+    }
+
+    let orden = sort_document(query.sort.as_deref().unwrap_or(ORDEN_DEFECTO))
+        .ok_or(AppError::validation_field(
+            "sort",
+            "Orden no válido; use fecha, created_at o nombre_cliente, con '-' delante para orden descendente",
+        ))?;
+
+    let pagina = query.page.unwrap_or(1);
+    if pagina == 0 {
+        return Err(AppError::validation_field("page", "La página debe ser 1 o mayor"));
+    }
+    let limite = query.limit.unwrap_or(LIMITE_DEFECTO);
+    if !(1..=LIMITE_MAXIMO).contains(&limite) {
+        return Err(AppError::validation_field(
+            "limit",
+            &format!("El límite debe estar entre 1 y {}", LIMITE_MAXIMO),
+        ));
     }
 
     let reservas = repo.reservas();
+    let total = reservas
+        .count_documents(filter.clone())
+        .await
+        .map_err(|e| AppError::database("get_reservations", e))?;
+
+    let options = FindOptions::builder()
+        .sort(orden)
+        .skip((pagina - 1).saturating_mul(limite as u64))
+        .limit(limite)
+        .build();
     let cursor = reservas
         .find(filter)
+        .with_options(options)
         .await
         .map_err(|e| AppError::Internal(format!("Error obteniendo reservas: {}", e)))?;
 
-    let mut results = Vec::new();
+    let mut reservas_pagina = Vec::new();
     let mut cursor = cursor;
 
     while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
         let reserva = cursor.deserialize_current()
             .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?;
-        results.push(ReservationResponse::from(reserva));
+        reservas_pagina.push(reserva);
+    }
+    let results = with_risk(repo.get_ref(), &auth.restaurant, reservas_pagina).await?;
+
+    let mut response = campos.respond(&results);
+    envelope::paginate(&mut response, Paginacion { total, pagina, limite });
+    Ok(response)
+}
+
+/// Lista las reservas de un día agrupadas por los turnos del restaurante
+///
+/// Cada reserva se asigna al primer turno de la configuración que contiene
+/// su hora; las que no encajan en ninguno se devuelven en el grupo
+/// `sin_turno`, que solo aparece si tiene reservas.
+///
+/// Es la carga inicial del panel del día: incluye también el personal de
+/// cada turno y las notas de traspaso del día (ver [`super::shift`]). Las
+/// reservas llevan su `riesgo` como en `GET /reservations`.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Parámetros
+/// - `fecha`: Fecha a consultar (formato YYYY-MM-DD)
+/// - `turno` (opcional): Devolver solo el grupo de ese turno; las notas
+///   del día se devuelven todas
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "fecha": "2024-12-25",
+///   "turnos": [
+///     {
+///       "nombre": "Cena",
+///       "hora_inicio": "20:00",
+///       "hora_fin": "23:59",
+///       "total_reservas": 1,
+///       "total_personas": 2,
+///       "reservas": [ ... ],
+///       "personal": ["ana", "luis"]
+///     }
+///   ],
+///   "notas": [
+///     { "id": "507f1f77bcf86cd799439021", "turno": "Comida", "autor": "marta",
+///       "texto": "La mesa 4 pidió trona para la cena", "id_reservas": [],
+///       "created_at": 1735125000 }
+///   ]
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/reservations/by-shift")]
+async fn get_reservations_by_shift(
+    repo: web::Data<MongoRepo>,
+    query: web::Query<ShiftQuery>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerReservas>,
+) -> AppResult<impl Responder> {
+    let restaurant = auth.restaurant;
+    validate_date(&query.fecha)?;
+
+    let turnos = &restaurant.configuracion.turnos;
+    let mut grupos: Vec<ShiftGroup> = turnos
+        .iter()
+        .map(|turno| ShiftGroup::new(&turno.nombre, Some(turno)))
+        .collect();
+    let mut sin_turno = ShiftGroup::new("sin_turno", None);
+
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! { "hora": 1 })
+        .build();
+
+    let mut cursor = repo.reservas()
+        .find(doc! { "id_restaurante": restaurant.id, "fecha": &query.fecha })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::Internal(format!("Error obteniendo reservas: {}", e)))?;
+
+    let mut reservas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        reservas.push(cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?);
+    }
+
+    for reserva in with_risk(repo.get_ref(), &restaurant, reservas).await? {
+        match turnos.iter().position(|turno| turno.contiene(&reserva.hora)) {
+            Some(i) => grupos[i].push(reserva),
+            None => sin_turno.push(reserva),
+        }
+    }
+
+    if !sin_turno.reservas.is_empty() {
+        grupos.push(sin_turno);
+    }
+
+    let restaurante_id = restaurant.id.unwrap();
+    for registro in shift::load_shift_records(repo.get_ref(), restaurante_id, &query.fecha).await? {
+        if let Some(grupo) = grupos.iter_mut().find(|grupo| grupo.nombre == registro.turno) {
+            grupo.personal.push(registro.usuario);
+        }
+    }
+    if let Some(turno) = &query.turno {
+        grupos.retain(|grupo| &grupo.nombre == turno);
     }
 
-    Ok(HttpResponse::Ok().json(results))
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "fecha": query.fecha,
+        "turnos": grupos,
+        "notas": shift::load_notes(repo.get_ref(), restaurante_id, &query.fecha).await?
+    })))
 }
 
 /// Confirma una reserva pendiente
 ///
 /// Cambia el estado de una reserva de "pendiente" a "confirmada".
-/// Solo se pueden confirmar reservas que estén en estado "pendiente".
+/// Solo se pueden confirmar reservas que estén en estado "pendiente" y, si
+/// tienen depósito (ver [`super::deposit`]), cuyo pago alcance el umbral;
+/// si tienen pago por adelantado, una vez pagado.
 ///
 /// # Autenticación
-/// Requiere token Bearer válido del restaurante propietario.
+/// Requiere permiso `Reservas` (cualquier rol del personal).
 ///
 /// # Parámetros
 /// - `repo`: Repositorio MongoDB
@@ -407,22 +1389,34 @@ async fn get_reservations(
 /// - `401 Unauthorized`: Token inválido o falta autorización
 /// - `403 Forbidden`: No tienes permiso para confirmar reservas de este restaurante
 /// - `404 Not Found`: Reserva no encontrada o ya procesada
+/// - `409 Conflict`: El depósito aún no alcanza el umbral o falta el pago por adelantado
 /// - `500 Internal Server Error`: Error de base de datos
 #[post("/reservations/{id}/confirm")]
 async fn confirm_reservation(
     repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
     path: web::Path<String>,
-    req: HttpRequest,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
 ) -> AppResult<impl Responder> {
-    let token = extract_token(&req)?;
-    let user_id = validate_access_token(repo.get_ref(), &token).await?;
-    let reservation_id = ObjectId::parse_str(&path.into_inner())
+    let user_id = auth.id();
+    let reservation_id = ObjectId::parse_str(path.into_inner())
         .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
 
-    // Actualizar la reserva solo si es del restaurante y está pendiente
+    // Una reserva con depósito solo se confirma al alcanzar el umbral, y
+    // una con pago por adelantado, al pagarlo
     let reservas = repo.reservas();
-    let result = reservas
-        .update_one(
+    let anterior = reservas
+        .find_one(doc! { "_id": reservation_id, "id_restaurante": user_id })
+        .await
+        .map_err(|e| AppError::database("confirm_reservation", e))?;
+    if anterior.as_ref().is_some_and(Reserva::pago_pendiente) {
+        return Err(AppError::Conflict(PAGO_PENDIENTE.to_string()));
+    }
+
+    // Actualizar la reserva solo si es del restaurante y está pendiente
+    let now = clock.timestamp();
+    let confirmada = reservas
+        .find_one_and_update(
             doc! {
                 "_id": reservation_id,
                 "id_restaurante": user_id,
@@ -431,16 +1425,25 @@ async fn confirm_reservation(
             doc! {
                 "$set": {
                     "estado": "confirmada",
-                    "updated_at": MongoRepo::current_timestamp()
+                    "confirmada_en": now,
+                    "updated_at": now
                 }
             }
         )
+        .return_document(ReturnDocument::After)
         .await
-        .map_err(|e| AppError::Internal(format!("Error confirmando reserva: {}", e)))?;
-
-    if result.modified_count == 0 {
-        return Err(AppError::NotFound("Reserva no encontrada o ya procesada".to_string()));
-    }
+        .map_err(|e| AppError::Internal(format!("Error confirmando reserva: {}", e)))?
+        .ok_or(AppError::NotFound("Reserva no encontrada o ya procesada".to_string()))?;
+    events::record(repo.get_ref(), TipoEvento::ReservaConfirmada, Some(user_id), Some(reservation_id), doc! {}, now).await;
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::ConfirmarReserva,
+        Some(reservation_id),
+        anterior.as_ref().and_then(audit::snapshot),
+        audit::snapshot(&confirmada),
+        now,
+    ).await;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Reserva confirmada correctamente",
@@ -449,13 +1452,66 @@ async fn confirm_reservation(
     })))
 }
 
-/// Cancela una reserva
+/// Motivo por el que se rechaza una cancelación fuera de plazo
+pub(super) const CANCELACION_TARDIA: &str =
+    "El plazo para cancelar la reserva ha terminado según la política de cancelación del restaurante";
+
+/// Aplica la política de cancelación a una reserva que se cancela en `momento`
+///
+/// # Retorna
+/// Si la cancelación es tardía y la política la acepta marcándola
+///
+/// # Errores
+/// - `ConflictWithCode` (`cancelacion_tardia`): Si es tardía y la política
+///   no admite cancelaciones tardías
+pub(super) fn check_cancellation(restaurant: &Restaurant, reserva: &Reserva, momento: i64) -> AppResult<bool> {
+    let Some(politica) = restaurant.configuracion.politica_cancelacion else {
+        return Ok(false);
+    };
+    let Some(inicio) = availability::parse_inicio(&reserva.fecha, &reserva.hora) else {
+        return Ok(false);
+    };
+    if !politica.cancelacion_tardia(inicio, restaurant.configuracion.hora_local(momento)) {
+        return Ok(false);
+    }
+    if politica.marcar_tardias {
+        Ok(true)
+    } else {
+        Err(AppError::conflict_with_code("cancelacion_tardia", CANCELACION_TARDIA))
+    }
+}
+
+/// Aplica el plazo de modificación de la política de cancelación a una
+/// reserva que se modifica en `momento`
+///
+/// # Errores
+/// - `ConflictWithCode` (`modificacion_tardia`): Si el plazo ha terminado
+fn check_modification(restaurant: &Restaurant, reserva: &Reserva, momento: i64) -> AppResult<()> {
+    let tardia = restaurant.configuracion.politica_cancelacion
+        .zip(availability::parse_inicio(&reserva.fecha, &reserva.hora))
+        .is_some_and(|(politica, inicio)| politica.modificacion_tardia(inicio, restaurant.configuracion.hora_local(momento)));
+    if tardia {
+        return Err(AppError::conflict_with_code(
+            "modificacion_tardia",
+            "El plazo para cambiar la fecha, la hora o los comensales de la reserva ha terminado",
+        ));
+    }
+    Ok(())
+}
+
+/// Cancela una reserva
 ///
 /// Cambia el estado de una reserva a "cancelada". Una vez cancelada,
-/// la reserva no se puede reactivar ni modificar.
+/// la reserva no se puede reactivar ni modificar. Las reservas con el
+/// cliente ya sentado o terminadas no se cancelan.
+///
+/// Con `politica_cancelacion` en la configuración, cancelar a menos de
+/// `horas_cancelacion` horas de la reserva se rechaza con `409` y código
+/// `cancelacion_tardia` o, con `marcar_tardias`, se acepta y la reserva
+/// queda con `cancelacion_tardia: true` para los informes.
 ///
 /// # Autenticación
-/// Requiere token Bearer válido del restaurante propietario.
+/// Requiere permiso `Reservas` (cualquier rol del personal).
 ///
 /// # Parámetros
 /// - `repo`: Repositorio MongoDB
@@ -467,7 +1523,8 @@ async fn confirm_reservation(
 /// {
 ///   "message": "Reserva cancelada correctamente",
 ///   "id": "507f1f77bcf86cd799439011",
-///   "estado": "cancelada"
+///   "estado": "cancelada",
+///   "cancelacion_tardia": false
 /// }
 /// ```
 ///
@@ -475,56 +1532,1391 @@ async fn confirm_reservation(
 /// - `400 Bad Request`: ID de reserva inválido
 /// - `401 Unauthorized`: Token inválido o falta autorización
 /// - `403 Forbidden`: No tienes permiso para cancelar reservas de este restaurante
-/// - `404 Not Found`: Reserva no encontrada o ya cancelada
+/// - `404 Not Found`: Reserva no encontrada, ya cancelada o que ya no se puede cancelar
+/// - `409 Conflict`: Fuera del plazo de cancelación (`cancelacion_tardia`)
 /// - `500 Internal Server Error`: Error de base de datos
 #[post("/reservations/{id}/cancel")]
 async fn cancel_reservation(
     repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
     path: web::Path<String>,
-    req: HttpRequest,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
 ) -> AppResult<impl Responder> {
-    let token = extract_token(&req)?;
-    let user_id = validate_access_token(repo.get_ref(), &token).await?;
-    let reservation_id = ObjectId::parse_str(&path.into_inner())
+    let user_id = auth.id();
+    let reservation_id = ObjectId::parse_str(path.into_inner())
         .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
 
-    // Actualizar la reserva solo si es del restaurante y no está ya cancelada
+    let now = clock.timestamp();
     let reservas = repo.reservas();
-    let result = reservas
-        .update_one(
+    let no_cancelable = || AppError::NotFound("Reserva no encontrada o que ya no se puede cancelar".to_string());
+    let reserva = reservas
+        .find_one(doc! {
+            "_id": reservation_id,
+            "id_restaurante": user_id,
+            "estado": { "$in": EstadoReserva::sources_of(EstadoReserva::Cancelada) }
+        })
+        .await
+        .map_err(|e| AppError::database("cancel_reservation", e))?
+        .ok_or_else(no_cancelable)?;
+    let tardia = check_cancellation(&auth.restaurant, &reserva, now)?;
+
+    // Actualizar la reserva solo si aún se puede cancelar y no ha cambiado
+    // de hora mientras tanto
+    let mut set = doc! { "estado": "cancelada", "updated_at": now };
+    if tardia {
+        set.insert("cancelacion_tardia", true);
+    }
+    let cancelada = reservas
+        .find_one_and_update(
             doc! {
                 "_id": reservation_id,
-                "id_restaurante": user_id,
-                "estado": {"$ne": "cancelada"}
+                "estado": { "$in": EstadoReserva::sources_of(EstadoReserva::Cancelada) },
+                "fecha": &reserva.fecha,
+                "hora": &reserva.hora
             },
-            doc! {
-                "$set": {
-                    "estado": "cancelada",
-                    "updated_at": MongoRepo::current_timestamp()
+            doc! { "$set": set },
+        )
+        .await
+        .map_err(|e| AppError::Internal(format!("Error cancelando reserva: {}", e)))?
+        .ok_or_else(no_cancelable)?;
+    events::record(
+        repo.get_ref(),
+        TipoEvento::ReservaCancelada,
+        Some(user_id),
+        Some(reservation_id),
+        doc! { "estado_anterior": cancelada.estado, "tardia": tardia },
+        now,
+    ).await;
+    let antes = audit::snapshot(&cancelada);
+    let despues = antes.clone().map(|mut reserva| {
+        reserva.insert("estado", "cancelada");
+        if tardia {
+            reserva.insert("cancelacion_tardia", true);
+        }
+        reserva
+    });
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::CancelarReserva,
+        Some(reservation_id),
+        antes,
+        despues,
+        now,
+    ).await;
+
+    if let Some(id_cliente) = cancelada.id_cliente {
+        discount_visit(repo.get_ref(), id_cliente).await?;
+        learn_preference(repo.get_ref(), id_cliente).await;
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Reserva cancelada correctamente",
+        "id": reservation_id.to_hex(),
+        "estado": "cancelada",
+        "cancelacion_tardia": tardia
+    })))
+}
+
+/// Máximo de reservas en una operación masiva
+const MAX_OPERACION_MASIVA: usize = 100;
+
+/// Acción de una operación masiva
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AccionMasiva {
+    Confirmar,
+    Cancelar,
+}
+
+/// Datos de `POST /reservations/bulk`
+#[derive(Deserialize)]
+struct BulkOperation {
+    /// IDs de las reservas (como mucho 100)
+    ids: Vec<String>,
+    accion: AccionMasiva,
+}
+
+/// Resultado de una reserva en una operación masiva
+#[derive(Serialize)]
+struct ResultadoMasivo {
+    id: String,
+    ok: bool,
+    /// Estado de la reserva tras la operación, si existe
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estado: Option<EstadoReserva>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ResultadoMasivo {
+    fn fallida(id: String, estado: Option<EstadoReserva>, error: &str) -> Self {
+        ResultadoMasivo { id, ok: false, estado, error: Some(error.to_string()) }
+    }
+}
+
+/// Confirma o cancela varias reservas a la vez
+///
+/// Aplica las mismas reglas que `POST /reservations/{id}/confirm` y
+/// `POST /reservations/{id}/cancel` (incluida la política de cancelación)
+/// a cada reserva, pero cambia todas las
+/// que se pueden con una sola escritura. Las que no se pueden cambiar no
+/// impiden el resto: cada ID tiene su resultado, en el mismo orden. Los IDs
+/// repetidos se tratan una sola vez.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Body
+/// ```json
+/// { "ids": ["507f1f77bcf86cd799439011", "507f1f77bcf86cd799439012"], "accion": "confirmar" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "procesadas": 1,
+///   "fallidas": 1,
+///   "resultados": [
+///     { "id": "507f1f77bcf86cd799439011", "ok": true, "estado": "confirmada" },
+///     { "id": "507f1f77bcf86cd799439012", "ok": false, "estado": "cancelada", "error": "Una reserva cancelada no puede pasar a confirmada" }
+///   ]
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Sin IDs, más de 100 o acción desconocida
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/bulk")]
+async fn bulk_update_reservations(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<BulkOperation>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let data = data.into_inner();
+    if data.ids.is_empty() {
+        return Err(AppError::validation_field("ids", "Indica al menos una reserva"));
+    }
+    if data.ids.len() > MAX_OPERACION_MASIVA {
+        return Err(AppError::validation_field("ids", &format!(
+            "Una operación masiva admite como máximo {} reservas", MAX_OPERACION_MASIVA
+        )));
+    }
+
+    let restaurante_id = auth.id();
+    let autor = Autor::from(&auth);
+    let destino = match data.accion {
+        AccionMasiva::Confirmar => EstadoReserva::Confirmada,
+        AccionMasiva::Cancelar => EstadoReserva::Cancelada,
+    };
+    // Confirmar, como en la ruta individual, solo desde "pendiente"
+    let origenes = match data.accion {
+        AccionMasiva::Confirmar => vec![EstadoReserva::Pendiente],
+        AccionMasiva::Cancelar => EstadoReserva::sources_of(EstadoReserva::Cancelada),
+    };
+
+    let mut ids = Vec::new();
+    for id in &data.ids {
+        if let Ok(id) = ObjectId::parse_str(id) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+
+    let reservas = repo.reservas();
+    let mut anteriores = std::collections::HashMap::new();
+    let mut cursor = reservas
+        .find(doc! { "_id": { "$in": &ids }, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("bulk_find_reservations", e))?;
+    while cursor.advance().await.map_err(|e| AppError::database("bulk_find_reservations", e))? {
+        let reserva: Reserva = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?;
+        anteriores.insert(reserva.id.unwrap(), reserva);
+    }
+
+    // Las que no se pueden cambiar por sus pagos o por la política de
+    // cancelación, con el motivo, y las cancelaciones tardías que se marcan
+    let now = clock.timestamp();
+    let mut rechazadas = std::collections::HashMap::new();
+    let mut tardias = Vec::new();
+    for reserva in anteriores.values().filter(|reserva| origenes.contains(&reserva.estado)) {
+        let id = reserva.id.unwrap();
+        match destino {
+            EstadoReserva::Confirmada if reserva.pago_pendiente() => {
+                rechazadas.insert(id, PAGO_PENDIENTE);
+            }
+            EstadoReserva::Cancelada => match check_cancellation(&auth.restaurant, reserva, now) {
+                Ok(true) => tardias.push(id),
+                Ok(false) => {}
+                Err(_) => {
+                    rechazadas.insert(id, CANCELACION_TARDIA);
                 }
+            },
+            _ => {}
+        }
+    }
+    let aplicables: Vec<ObjectId> = anteriores.values()
+        .filter(|reserva| origenes.contains(&reserva.estado))
+        .filter_map(|reserva| reserva.id)
+        .filter(|id| !rechazadas.contains_key(id))
+        .collect();
+
+    // Una escritura para todas (y otra para las tardías); solo cambian las
+    // que siguen en un estado de origen válido
+    let grupos = [
+        (aplicables.iter().filter(|id| !tardias.contains(id)).copied().collect::<Vec<_>>(), false),
+        (tardias.clone(), true),
+    ];
+    for (grupo, tardia) in grupos {
+        if grupo.is_empty() {
+            continue;
+        }
+        let mut set = doc! { "estado": destino, "updated_at": now };
+        if destino == EstadoReserva::Confirmada {
+            set.insert("confirmada_en", now);
+        }
+        if tardia {
+            set.insert("cancelacion_tardia", true);
+        }
+        reservas
+            .update_many(
+                doc! {
+                    "_id": { "$in": &grupo },
+                    "id_restaurante": restaurante_id,
+                    "estado": { "$in": &origenes }
+                },
+                doc! { "$set": set },
+            )
+            .await
+            .map_err(|e| AppError::database("bulk_update_reservations", e))?;
+    }
+    let mut cambiadas = std::collections::HashSet::new();
+    let mut cursor = reservas
+        .find(doc! { "_id": { "$in": &aplicables }, "estado": destino, "updated_at": now })
+        .projection(doc! { "_id": 1 })
+        .await
+        .map_err(|e| AppError::database("bulk_find_reservations", e))?;
+    while cursor.advance().await.map_err(|e| AppError::database("bulk_find_reservations", e))? {
+        if let Ok(id) = cursor.current().get_object_id("_id") {
+            cambiadas.insert(id);
+        }
+    }
+
+    let (evento, accion) = match data.accion {
+        AccionMasiva::Confirmar => (TipoEvento::ReservaConfirmada, AccionAuditoria::ConfirmarReserva),
+        AccionMasiva::Cancelar => (TipoEvento::ReservaCancelada, AccionAuditoria::CancelarReserva),
+    };
+    let mut resultados = Vec::new();
+    let mut tratadas = std::collections::HashSet::new();
+    for texto in data.ids {
+        let Ok(id) = ObjectId::parse_str(&texto) else {
+            resultados.push(ResultadoMasivo::fallida(texto, None, "ID de reserva inválido"));
+            continue;
+        };
+        if !tratadas.insert(id) {
+            continue;
+        }
+        let Some(anterior) = anteriores.get(&id) else {
+            resultados.push(ResultadoMasivo::fallida(texto, None, "Reserva no encontrada"));
+            continue;
+        };
+        if !cambiadas.contains(&id) {
+            let error = if let Some(motivo) = rechazadas.get(&id) {
+                motivo.to_string()
+            } else if aplicables.contains(&id) {
+                "El estado de la reserva ha cambiado mientras tanto".to_string()
+            } else {
+                format!("Una reserva {} no puede pasar a {}", anterior.estado, destino)
+            };
+            resultados.push(ResultadoMasivo::fallida(texto, Some(anterior.estado), &error));
+            continue;
+        }
+
+        events::record(
+            repo.get_ref(),
+            evento,
+            Some(restaurante_id),
+            Some(id),
+            doc! { "estado_anterior": anterior.estado, "masiva": true },
+            now,
+        ).await;
+        let antes = audit::snapshot(anterior);
+        let despues = antes.clone().map(|mut reserva| {
+            reserva.insert("estado", destino);
+            if tardias.contains(&id) {
+                reserva.insert("cancelacion_tardia", true);
             }
+            reserva
+        });
+        audit::record(repo.get_ref(), &autor, accion, Some(id), antes, despues, now).await;
+        if destino == EstadoReserva::Cancelada {
+            if let Some(id_cliente) = anterior.id_cliente {
+                discount_visit(repo.get_ref(), id_cliente).await?;
+                learn_preference(repo.get_ref(), id_cliente).await;
+            }
+        }
+        resultados.push(ResultadoMasivo { id: texto, ok: true, estado: Some(destino), error: None });
+    }
+
+    let procesadas = resultados.iter().filter(|resultado| resultado.ok).count();
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "procesadas": procesadas,
+        "fallidas": resultados.len() - procesadas,
+        "resultados": resultados
+    })))
+}
+
+/// Cambia el estado de una reserva del personal de sala
+///
+/// Comprueba con [`EstadoReserva::can_transition_to`] que el cambio es
+/// válido y lo aplica solo si nadie ha cambiado el estado mientras tanto.
+///
+/// # Errores
+/// - `404 Not Found`: La reserva no existe o es de otro restaurante
+/// - `409 Conflict`: La reserva no puede pasar de su estado actual a `destino`
+async fn change_state(
+    repo: &MongoRepo,
+    autor: &Autor,
+    reservation_id: ObjectId,
+    destino: EstadoReserva,
+    now: i64,
+) -> AppResult<Reserva> {
+    let anterior = find_for_state(repo, autor.id_restaurante, reservation_id).await?;
+    apply_state(repo, autor, reservation_id, anterior, destino, Document::new(), now).await
+}
+
+/// Carga la reserva de un restaurante cuyo estado se va a cambiar
+///
+/// # Errores
+/// - `404 Not Found`: La reserva no existe o es de otro restaurante
+async fn find_for_state(repo: &MongoRepo, restaurante_id: ObjectId, reservation_id: ObjectId) -> AppResult<Reserva> {
+    repo.reservas()
+        .find_one(doc! { "_id": reservation_id, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("change_state", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada".to_string()))
+}
+
+/// Aplica un cambio de estado a una reserva ya cargada
+///
+/// Como [`change_state`], guardando además `extra` en la reserva.
+///
+/// # Errores
+/// - `409 Conflict`: La reserva no puede pasar de su estado actual a `destino`
+async fn apply_state(
+    repo: &MongoRepo,
+    autor: &Autor,
+    reservation_id: ObjectId,
+    anterior: Reserva,
+    destino: EstadoReserva,
+    extra: Document,
+    now: i64,
+) -> AppResult<Reserva> {
+    let restaurante_id = autor.id_restaurante;
+    if !anterior.estado.can_transition_to(destino) {
+        return Err(AppError::Conflict(format!(
+            "Una reserva {} no puede pasar a {}", anterior.estado, destino
+        )));
+    }
+
+    let mut cambios = doc! { "estado": destino, "updated_at": now };
+    match destino {
+        EstadoReserva::Sentada => cambios.insert("sentada_en", now),
+        EstadoReserva::Completada => cambios.insert("completada_en", now),
+        _ => None,
+    };
+    cambios.extend(extra);
+    let actualizada = repo.reservas()
+        .find_one_and_update(
+            doc! { "_id": reservation_id, "id_restaurante": restaurante_id, "estado": anterior.estado },
+            doc! { "$set": cambios },
         )
+        .return_document(ReturnDocument::After)
         .await
-        .map_err(|e| AppError::Internal(format!("Error cancelando reserva: {}", e)))?;
+        .map_err(|e| AppError::database("change_state", e))?
+        .ok_or(AppError::Conflict("El estado de la reserva ha cambiado mientras tanto".to_string()))?;
 
-    if result.modified_count == 0 {
-        return Err(AppError::NotFound("Reserva no encontrada o ya cancelada".to_string()));
+    let (evento, accion) = match destino {
+        EstadoReserva::Sentada => (TipoEvento::ReservaSentada, AccionAuditoria::SentarReserva),
+        EstadoReserva::Completada => (TipoEvento::ReservaCompletada, AccionAuditoria::CompletarReserva),
+        _ => (TipoEvento::ReservaNoShow, AccionAuditoria::MarcarNoShow),
+    };
+    events::record(
+        repo,
+        evento,
+        Some(restaurante_id),
+        Some(reservation_id),
+        doc! { "estado_anterior": anterior.estado },
+        now,
+    ).await;
+    audit::record(
+        repo,
+        autor,
+        accion,
+        Some(reservation_id),
+        audit::snapshot(&anterior),
+        audit::snapshot(&actualizada),
+        now,
+    ).await;
+
+    Ok(actualizada)
+}
+
+/// Sienta al cliente de una reserva
+///
+/// La reserva pasa de "pendiente" o "confirmada" a "sentada" y sigue
+/// ocupando la mesa hasta que se completa.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Cliente sentado",
+///   "id": "507f1f77bcf86cd799439011",
+///   "estado": "sentada"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Reserva no encontrada
+/// - `409 Conflict`: La reserva no está pendiente ni confirmada
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/seat")]
+async fn seat_reservation(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    let reserva = change_state(repo.get_ref(), &Autor::from(&auth), reservation_id, EstadoReserva::Sentada, clock.timestamp()).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Cliente sentado",
+        "id": reservation_id.to_hex(),
+        "estado": reserva.estado
+    })))
+}
+
+/// Registra la llegada del cliente de una reserva
+///
+/// Es lo que marca la tablet del mostrador: guarda el momento de llegada
+/// (`llegada_en`) y sienta al cliente, así que la mesa pasa a ocupada en el
+/// plano en vivo (`GET /visual/eta`) hasta que se completa la reserva. Si
+/// otro grupo sigue sentado en la mesa, no se sienta a nadie: hay que
+/// completar esa reserva o mover esta a otra mesa
+/// (`POST /reservations/{id}/move`).
+///
+/// `retraso_minutos` compara la llegada con la hora de la reserva; es
+/// negativo si el cliente llega antes.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Llegada registrada",
+///   "id": "507f1f77bcf86cd799439011",
+///   "estado": "sentada",
+///   "id_mesa": "507f1f77bcf86cd799439012",
+///   "llegada_en": 1906974000,
+///   "retraso_minutos": 10
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Reserva no encontrada
+/// - `409 Conflict`: La reserva no está pendiente ni confirmada, o
+///   (`mesa_ocupada`) hay otro grupo sentado en la mesa
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/checkin")]
+async fn check_in_reservation(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    let restaurante_id = auth.id();
+    let now = clock.timestamp();
+
+    let anterior = find_for_state(repo.get_ref(), restaurante_id, reservation_id).await?;
+    if anterior.estado.can_transition_to(EstadoReserva::Sentada) {
+        let ocupada = repo.reservas()
+            .find_one(doc! {
+                "id_restaurante": restaurante_id,
+                "id_mesa": anterior.id_mesa,
+                "estado": EstadoReserva::Sentada,
+                "_id": { "$ne": reservation_id },
+            })
+            .await
+            .map_err(|e| AppError::database("check_in_reservation", e))?;
+        if let Some(ocupada) = ocupada {
+            return Err(AppError::conflict_with_code(
+                "mesa_ocupada",
+                &format!(
+                    "La mesa sigue ocupada por la reserva de {} a las {}; complétala o mueve esta reserva a otra mesa",
+                    ocupada.nombre_cliente, ocupada.hora
+                ),
+            ));
+        }
     }
+    let retraso_minutos = availability::parse_inicio(&anterior.fecha, &anterior.hora)
+        .map(|inicio| (auth.restaurant.configuracion.hora_local(now) - inicio).num_minutes());
+    let reserva = apply_state(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        reservation_id,
+        anterior,
+        EstadoReserva::Sentada,
+        doc! { "llegada_en": now },
+        now,
+    ).await?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
-        "message": "Reserva cancelada correctamente",
+        "message": "Llegada registrada",
+        "id": reservation_id.to_hex(),
+        "estado": reserva.estado,
+        "id_mesa": reserva.id_mesa.to_hex(),
+        "llegada_en": reserva.llegada_en,
+        "retraso_minutos": retraso_minutos
+    })))
+}
+
+/// Completa una reserva
+///
+/// El cliente sentado se ha ido: la reserva pasa a "completada" y deja
+/// libre la mesa.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Reserva completada",
+///   "id": "507f1f77bcf86cd799439011",
+///   "estado": "completada"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Reserva no encontrada
+/// - `409 Conflict`: El cliente de la reserva no está sentado
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/complete")]
+async fn complete_reservation(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    let reserva = change_state(repo.get_ref(), &Autor::from(&auth), reservation_id, EstadoReserva::Completada, clock.timestamp()).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Reserva completada",
         "id": reservation_id.to_hex(),
-        "estado": "cancelada"
+        "estado": reserva.estado
     })))
 }
 
+/// Marca que el cliente de una reserva no se presentó
+///
+/// La reserva pasa de "pendiente" o "confirmada" a "no_show" y deja libre
+/// la mesa. Como al cancelar, la visita no cuenta en la ficha del cliente,
+/// que en cambio suma un no-show (ver [`super::customer`]).
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Reserva marcada como no presentada",
+///   "id": "507f1f77bcf86cd799439011",
+///   "estado": "no_show"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Reserva no encontrada
+/// - `409 Conflict`: La reserva no está pendiente ni confirmada
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/no-show")]
+async fn no_show_reservation(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    let reserva = change_state(repo.get_ref(), &Autor::from(&auth), reservation_id, EstadoReserva::NoShow, clock.timestamp()).await?;
+
+    if let Some(id_cliente) = reserva.id_cliente {
+        discount_visit(repo.get_ref(), id_cliente).await?;
+        count_no_show(repo.get_ref(), id_cliente).await?;
+        learn_preference(repo.get_ref(), id_cliente).await;
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Reserva marcada como no presentada",
+        "id": reservation_id.to_hex(),
+        "estado": reserva.estado
+    })))
+}
+
+/// Estructura para modificar una reserva
+///
+/// Todos los campos son opcionales: los que no se envían conservan su valor.
+#[derive(Deserialize)]
+struct UpdateReservation {
+    /// Nueva mesa (ObjectId como string)
+    id_mesa: Option<String>,
+    /// Nuevo nombre del cliente
+    nombre_cliente: Option<String>,
+    /// Nuevo email del cliente
+    email_cliente: Option<String>,
+    /// Nuevo teléfono del cliente
+    telefono_cliente: Option<String>,
+    /// Nuevo número de comensales
+    numero_personas: Option<i32>,
+    /// Nueva fecha (formato YYYY-MM-DD)
+    fecha: Option<String>,
+    /// Nueva hora (formato HH:MM)
+    hora: Option<String>,
+    /// Nuevas peticiones del cliente; vacías, se quitan
+    notas_cliente: Option<String>,
+    /// Nuevas notas del personal; vacías, se quitan
+    notas_internas: Option<String>,
+}
+
+/// Modifica una reserva
+///
+/// Cambia la mesa, la fecha y hora, el número de comensales, los datos del
+/// cliente o las notas sin tener que cancelarla y crearla de nuevo. Una nota
+/// vacía (`""`) se quita. La reserva conserva su estado, su localizador, su
+/// depósito y su preselección de menú. Si cambia la fecha o la hora, el
+/// cliente recibe de nuevo el recordatorio (ver [`crate::jobs::reminders`])
+/// y tiene que volver a confirmar que vendrá.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Validaciones
+/// Las mismas que al crear una reserva (ver [`make_reservation`]), aplicadas
+/// a la reserva resultante. La propia reserva no cuenta como conflicto de
+/// horario, de modo que se puede alargar o mover dentro de su intervalo.
+///
+/// Con `horas_modificacion` en la política de cancelación, la fecha, la hora
+/// y los comensales ya no se pueden cambiar a menos de esas horas de la
+/// reserva (`409` con código `modificacion_tardia`); la mesa, los datos del
+/// cliente y las notas, sí.
+///
+/// # Cuerpo
+/// ```json
+/// { "hora": "21:30", "numero_personas": 4 }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Reserva modificada correctamente",
+///   "reserva": { "id": "507f1f77bcf86cd799439011", "hora": "21:30", "numero_personas": 4, "...": "..." }
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva o datos inválidos, o la mesa no admite el grupo
+/// - `401 Unauthorized`: Token inválido o la mesa es de otro restaurante
+/// - `404 Not Found`: Reserva o mesa no encontrada
+/// - `409 Conflict`: La mesa está ocupada a esa hora, la reserva está
+///   cancelada, anonimizada o ha cambiado durante la modificación, o el
+///   plazo de modificación ha terminado (`modificacion_tardia`)
+/// - `500 Internal Server Error`: Error de base de datos
+#[put("/reservations/{id}")]
+async fn update_reservation(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<UpdateReservation>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let user_id = auth.id();
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+
+    let reserva = repo.reservas()
+        .find_one(doc! { "_id": reservation_id, "id_restaurante": user_id })
+        .await
+        .map_err(|e| AppError::database("update_reservation", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada".to_string()))?;
+    if !reserva.estado.is_active() {
+        return Err(AppError::Conflict(format!("No se puede modificar una reserva {}", reserva.estado)));
+    }
+    if reserva.anonimizada_en.is_some() {
+        return Err(AppError::Conflict("La reserva ya se ha anonimizado".to_string()));
+    }
+
+    let data = data.into_inner();
+    let nueva = MakeReservation {
+        id_mesa: data.id_mesa.unwrap_or_else(|| reserva.id_mesa.to_hex()),
+        nombre_cliente: data.nombre_cliente.unwrap_or_else(|| reserva.nombre_cliente.clone()),
+        email_cliente: data.email_cliente.unwrap_or_else(|| reserva.email_cliente.clone()),
+        telefono_cliente: data.telefono_cliente.unwrap_or_else(|| reserva.telefono_cliente.clone()),
+        numero_personas: data.numero_personas.unwrap_or(reserva.numero_personas),
+        fecha: data.fecha.unwrap_or_else(|| reserva.fecha.clone()),
+        hora: data.hora.unwrap_or_else(|| reserva.hora.clone()),
+        preseleccion: Vec::new(),
+        uuid: None,
+        idioma: None,
+        notas_cliente: data.notas_cliente.or_else(|| reserva.notas_cliente.clone()),
+        notas_internas: data.notas_internas.or_else(|| reserva.notas_internas.clone()),
+    };
+    let reprogramada = nueva.fecha != reserva.fecha
+        || nueva.hora != reserva.hora
+        || nueva.numero_personas != reserva.numero_personas;
+    if reprogramada {
+        check_modification(&auth.restaurant, &reserva, clock.timestamp())?;
+    }
+    if nueva.fecha != reserva.fecha || nueva.hora != reserva.hora {
+        check_booking_window(&auth.restaurant, &nueva, clock.timestamp())?;
+    }
+    let id_mesa = validate_reservation(repo.get_ref(), &auth.restaurant, &nueva, Some(reservation_id)).await?;
+
+    // Con otros datos de contacto la visita pasa al cliente que corresponda
+    let now = clock.timestamp();
+    let contacto_cambiado = nueva.nombre_cliente != reserva.nombre_cliente
+        || nueva.email_cliente != reserva.email_cliente
+        || nueva.telefono_cliente != reserva.telefono_cliente;
+    let mut id_cliente = reserva.id_cliente;
+    if contacto_cambiado {
+        if let Some(anterior) = reserva.id_cliente {
+            discount_visit(repo.get_ref(), anterior).await?;
+        }
+        id_cliente = link_customer(
+            repo.get_ref(),
+            user_id,
+            &nueva.nombre_cliente,
+            &nueva.email_cliente,
+            &nueva.telefono_cliente,
+            true,
+            now,
+        ).await?;
+    }
+
+    let mut set = doc! {
+        "id_mesa": id_mesa,
+        "nombre_cliente": &nueva.nombre_cliente,
+        "email_cliente": &nueva.email_cliente,
+        "telefono_cliente": &nueva.telefono_cliente,
+        "numero_personas": nueva.numero_personas,
+        "fecha": &nueva.fecha,
+        "hora": &nueva.hora,
+        "updated_at": now,
+    };
+    if let Some(id_cliente) = id_cliente {
+        set.insert("id_cliente", id_cliente);
+    }
+    let mut unset = Document::new();
+    for (campo, nota) in [("notas_cliente", &nueva.notas_cliente), ("notas_internas", &nueva.notas_internas)] {
+        match clean_note(nota) {
+            Some(nota) => set.insert(campo, nota),
+            None => unset.insert(campo, ""),
+        };
+    }
+    // El recordatorio y la confirmación del cliente eran para la fecha y
+    // hora anteriores
+    if nueva.fecha != reserva.fecha || nueva.hora != reserva.hora {
+        unset.insert("recordatorio_enviado_en", "");
+        unset.insert("confirmada_por_cliente_en", "");
+    }
+    let mut cambios = doc! { "$set": set };
+    if !unset.is_empty() {
+        cambios.insert("$unset", unset);
+    }
+
+    // Solo si nadie la ha cambiado desde que se leyó
+    let modificada = repo.reservas()
+        .find_one_and_update(
+            doc! {
+                "_id": reservation_id,
+                "id_restaurante": user_id,
+                "updated_at": reserva.updated_at,
+                "estado": reserva.estado,
+            },
+            cambios,
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("update_reservation", e))?
+        .ok_or(AppError::Conflict("La reserva ha cambiado durante la modificación".to_string()))?;
+
+    for cliente in [reserva.id_cliente, modificada.id_cliente].into_iter().flatten() {
+        learn_preference(repo.get_ref(), cliente).await;
+    }
+    events::record(
+        repo.get_ref(),
+        TipoEvento::ReservaModificada,
+        Some(user_id),
+        Some(reservation_id),
+        doc! {
+            "fecha": &modificada.fecha,
+            "hora": &modificada.hora,
+            "numero_personas": modificada.numero_personas,
+        },
+        now,
+    ).await;
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::ModificarReserva,
+        Some(reservation_id),
+        audit::snapshot(&reserva),
+        audit::snapshot(&modificada),
+        now,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Reserva modificada correctamente",
+        "reserva": ReservationResponse::from(modificada)
+    })))
+}
+
+/// Estructura para mover una reserva a otra mesa
+#[derive(Deserialize)]
+struct MoveReservation {
+    /// Mesa de destino (ObjectId como string)
+    id_mesa: String,
+}
+
+/// Mueve una reserva a otra mesa del restaurante
+///
+/// Pensado para reasignar mesas arrastrando en el plano: solo cambia la
+/// mesa, también de reservas ya sentadas. La mesa de destino debe admitir el
+/// grupo, estar en la distribución de ese día y estar libre durante la
+/// reserva (la propia reserva no cuenta como conflicto).
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Cuerpo
+/// ```json
+/// { "id_mesa": "507f1f77bcf86cd799439021" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Reserva movida correctamente",
+///   "reserva": { "id": "507f1f77bcf86cd799439011", "id_mesa": "507f1f77bcf86cd799439021", "...": "..." }
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: IDs inválidos, la reserva ya está en esa mesa o la
+///   mesa no admite el grupo
+/// - `401 Unauthorized`: Token inválido o la mesa es de otro restaurante
+/// - `404 Not Found`: Reserva o mesa no encontrada
+/// - `409 Conflict`: La mesa está ocupada a esa hora o no está en servicio
+///   ese día, o la reserva ya no está activa, está anonimizada o ha cambiado
+///   durante el cambio
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/move")]
+async fn move_reservation(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    data: web::Json<MoveReservation>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let user_id = auth.id();
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+
+    let reserva = repo.reservas()
+        .find_one(doc! { "_id": reservation_id, "id_restaurante": user_id })
+        .await
+        .map_err(|e| AppError::database("move_reservation", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada".to_string()))?;
+    if !reserva.estado.is_active() {
+        return Err(AppError::Conflict(format!("No se puede mover una reserva {}", reserva.estado)));
+    }
+    if reserva.anonimizada_en.is_some() {
+        return Err(AppError::Conflict("La reserva ya se ha anonimizado".to_string()));
+    }
+    if data.id_mesa == reserva.id_mesa.to_hex() {
+        return Err(AppError::validation_field("id_mesa", "La reserva ya está en esa mesa"));
+    }
+
+    // Solo cuentan la mesa y el horario: los datos del cliente no cambian
+    let destino = MakeReservation {
+        id_mesa: data.id_mesa.clone(),
+        nombre_cliente: reserva.nombre_cliente.clone(),
+        email_cliente: reserva.email_cliente.clone(),
+        telefono_cliente: reserva.telefono_cliente.clone(),
+        numero_personas: reserva.numero_personas,
+        fecha: reserva.fecha.clone(),
+        hora: reserva.hora.clone(),
+        preseleccion: Vec::new(),
+        uuid: None,
+        idioma: None,
+        notas_cliente: None,
+        notas_internas: None,
+    };
+    let id_mesa = validate_table_slot(repo.get_ref(), &auth.restaurant, &destino, Some(reservation_id)).await?;
+
+    let now = clock.timestamp();
+    let movida = repo.reservas()
+        .find_one_and_update(
+            doc! {
+                "_id": reservation_id,
+                "id_restaurante": user_id,
+                "updated_at": reserva.updated_at,
+                "estado": reserva.estado,
+            },
+            doc! { "$set": { "id_mesa": id_mesa, "updated_at": now } },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("move_reservation", e))?
+        .ok_or(AppError::Conflict("La reserva ha cambiado durante el cambio de mesa".to_string()))?;
+
+    if let Some(id_cliente) = movida.id_cliente {
+        learn_preference(repo.get_ref(), id_cliente).await;
+    }
+    events::record(
+        repo.get_ref(),
+        TipoEvento::ReservaModificada,
+        Some(user_id),
+        Some(reservation_id),
+        doc! { "id_mesa_anterior": reserva.id_mesa, "id_mesa": id_mesa },
+        now,
+    ).await;
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::MoverReserva,
+        Some(reservation_id),
+        audit::snapshot(&reserva),
+        audit::snapshot(&movida),
+        now,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Reserva movida correctamente",
+        "reserva": ReservationResponse::from(movida)
+    })))
+}
+
+/// Estructura para traspasar una reserva a otro local del grupo
+#[derive(Deserialize)]
+struct TransferReservation {
+    /// Local de destino (ObjectId como string)
+    id_restaurante: String,
+    /// Mesa del local de destino
+    id_mesa: String,
+    /// Nueva fecha (opcional, por defecto la misma)
+    fecha: Option<String>,
+    /// Nueva hora (opcional, por defecto la misma)
+    hora: Option<String>,
+}
+
+/// Traspasa una reserva a otro local del mismo grupo
+///
+/// Comprueba la disponibilidad de la mesa de destino con las mismas reglas
+/// que al crear una reserva, mueve la reserva (conservando su estado y su
+/// depósito), la registra en el CRM del local de destino y avisa al cliente
+/// del cambio de local por email. La preselección de menú se descarta,
+/// porque las opciones de menú son de cada local.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante que tiene la reserva.
+///
+/// # Cuerpo
+/// ```json
+/// {
+///   "id_restaurante": "507f1f77bcf86cd799439013",
+///   "id_mesa": "507f1f77bcf86cd799439021",
+///   "hora": "21:30"
+/// }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Reserva traspasada correctamente",
+///   "reserva": { "id": "507f1f77bcf86cd799439011", "id_restaurante": "507f1f77bcf86cd799439013", "...": "..." }
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: IDs, fecha u hora inválidos, o la mesa no admite el grupo
+/// - `401 Unauthorized`: Token inválido o el destino no es del mismo grupo
+/// - `404 Not Found`: Reserva, local o mesa no encontrados
+/// - `409 Conflict`: La mesa de destino está ocupada, o la reserva está cancelada
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/transfer")]
+async fn transfer_reservation(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    notifier: web::Data<Notifier>,
+    path: web::Path<String>,
+    data: web::Json<TransferReservation>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let autor = Autor::from(&auth);
+    let origen = auth.restaurant;
+    let origen_id = origen.id.unwrap();
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    let destino_id = ObjectId::parse_str(&data.id_restaurante)
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
+
+    let reserva = repo.reservas()
+        .find_one(doc! { "_id": reservation_id, "id_restaurante": origen_id })
+        .await
+        .map_err(|e| AppError::database("transfer_reservation", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada".to_string()))?;
+    // Solo se traspasan las reservas que aún no han empezado
+    if !reserva.estado.can_transition_to(EstadoReserva::Cancelada) {
+        return Err(AppError::Conflict(format!("No se puede traspasar una reserva {}", reserva.estado)));
+    }
+
+    let destino = repo.restaurants()
+        .find_one(doc! { "_id": destino_id })
+        .await
+        .map_err(|e| AppError::database("transfer_reservation", e))?
+        .ok_or(AppError::NotFound("Restaurante de destino no encontrado".to_string()))?;
+    if destino_id == origen_id {
+        return Err(AppError::Validation("La reserva ya está en ese local".to_string()));
+    }
+    if origen.id_grupo.is_none() || destino.id_grupo != origen.id_grupo {
+        return Err(AppError::Unauthorized(
+            "El restaurante de destino no pertenece al mismo grupo".to_string(),
+        ));
+    }
+
+    let nueva = MakeReservation {
+        id_mesa: data.id_mesa.clone(),
+        nombre_cliente: reserva.nombre_cliente.clone(),
+        email_cliente: reserva.email_cliente.clone(),
+        telefono_cliente: reserva.telefono_cliente.clone(),
+        numero_personas: reserva.numero_personas,
+        fecha: data.fecha.clone().unwrap_or_else(|| reserva.fecha.clone()),
+        hora: data.hora.clone().unwrap_or_else(|| reserva.hora.clone()),
+        preseleccion: Vec::new(),
+        uuid: None,
+        idioma: None,
+        notas_cliente: None,
+        notas_internas: None,
+    };
+    let now = clock.timestamp();
+    let id_mesa = validate_new_reservation(repo.get_ref(), &destino, &nueva, now).await?;
+
+    let id_cliente = link_customer(
+        repo.get_ref(),
+        destino_id,
+        &nueva.nombre_cliente,
+        &nueva.email_cliente,
+        &nueva.telefono_cliente,
+        true,
+        now,
+    ).await?;
+
+    let mut set = doc! {
+        "id_restaurante": destino_id,
+        "id_mesa": id_mesa,
+        "fecha": &nueva.fecha,
+        "hora": &nueva.hora,
+        "transferida_desde": origen_id,
+        "updated_at": now,
+    };
+    if let Some(id_cliente) = id_cliente {
+        set.insert("id_cliente", id_cliente);
+    }
+
+    let traspasada = repo.reservas()
+        .find_one_and_update(
+            doc! { "_id": reservation_id, "id_restaurante": origen_id, "estado": reserva.estado },
+            doc! { "$set": set, "$unset": { "preseleccion": "" } },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("transfer_reservation", e))?
+        .ok_or(AppError::Conflict("La reserva ha cambiado durante el traspaso".to_string()))?;
+
+    if let Some(id_cliente) = reserva.id_cliente {
+        discount_visit(repo.get_ref(), id_cliente).await?;
+        learn_preference(repo.get_ref(), id_cliente).await;
+    }
+    if let Some(id_cliente) = id_cliente {
+        learn_preference(repo.get_ref(), id_cliente).await;
+    }
+
+    tracing::info!(
+        reserva = %reservation_id,
+        origen = %origen_id,
+        destino = %destino_id,
+        "Reserva traspasada a otro local"
+    );
+    events::record(
+        repo.get_ref(),
+        TipoEvento::ReservaTraspasada,
+        Some(destino_id),
+        Some(reservation_id),
+        doc! { "desde": origen_id, "fecha": &traspasada.fecha, "hora": &traspasada.hora },
+        now,
+    ).await;
+    audit::record(
+        repo.get_ref(),
+        &autor,
+        AccionAuditoria::TraspasarReserva,
+        Some(reservation_id),
+        audit::snapshot(&reserva),
+        audit::snapshot(&traspasada),
+        now,
+    ).await;
+
+    // El traspaso ya está hecho: un fallo al avisar solo se registra
+    if !traspasada.email_cliente.is_empty() {
+        let aviso = notifier.send_email(EmailMessage {
+            to: traspasada.email_cliente.clone(),
+            subject: format!("Tu reserva ahora es en {}", destino.nombre),
+            body: format!(
+                "Hola {},\n\nTu reserva para {} personas se ha trasladado de {} a {}.\nNueva fecha: {} a las {}.\n\nTe esperamos.\n",
+                traspasada.nombre_cliente,
+                traspasada.numero_personas,
+                origen.nombre,
+                destino.nombre,
+                traspasada.fecha,
+                traspasada.hora,
+            ),
+            adjuntos: Vec::new(),
+            request_id: None,
+        }).await;
+        if let Err(e) = aviso {
+            tracing::error!(reserva = %reservation_id, "Error avisando del traspaso: {}", e);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "message": "Reserva traspasada correctamente",
+        "reserva": ReservationResponse::from(traspasada)
+    })))
+}
+
+/// Estructura para marcar una reserva con retención legal
+#[derive(Deserialize)]
+struct LegalHoldRequest {
+    /// Motivo de la retención (reclamación, disputa del pago...)
+    motivo: String,
+}
+
+/// Marca una reserva con retención legal
+///
+/// Mientras dure, el trabajo de anonimización no toca la reserva (ver
+/// [`crate::jobs::anonymization`]). Solo la administración puede levantarla,
+/// con `DELETE /admin/reservations/{id}/legal-hold`.
+///
+/// # Autenticación
+/// Requiere permiso `Gestion` (encargado o propietario).
+///
+/// # Cuerpo
+/// ```json
+/// { "motivo": "Reclamación del cliente por el depósito" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id": "507f1f77bcf86cd799439011",
+///   "retencion_legal": { "motivo": "Reclamación del cliente por el depósito", "usuario": "propietario", "created_at": 1717243200 }
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido o motivo vacío
+/// - `401 Unauthorized`: Token inválido o rol sin permiso de gestión
+/// - `404 Not Found`: Reserva no encontrada
+/// - `409 Conflict`: La reserva ya tiene retención legal, o ya se ha anonimizado
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/{id}/legal-hold")]
+async fn place_legal_hold(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirReservas>,
+    data: web::Json<LegalHoldRequest>,
+) -> AppResult<impl Responder> {
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    let motivo = data.motivo.trim();
+    if motivo.is_empty() {
+        return Err(AppError::validation_field("motivo", "El motivo de la retención es requerido"));
+    }
+
+    let now = clock.timestamp();
+    let retencion = RetencionLegal {
+        motivo: motivo.to_string(),
+        usuario: auth.usuario.clone(),
+        created_at: now,
+    };
+    let retencion_doc = mongodb::bson::to_document(&retencion)
+        .map_err(|e| AppError::Internal(format!("Error serializando retención: {}", e)))?;
+
+    let reserva = repo.reservas()
+        .find_one(doc! { "_id": reservation_id, "id_restaurante": auth.id() })
+        .await
+        .map_err(|e| AppError::database("find_reservation", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada".to_string()))?;
+    if reserva.anonimizada_en.is_some() {
+        return Err(AppError::Conflict("La reserva ya se ha anonimizado".to_string()));
+    }
+
+    // Solo si no la tiene ya, y antes de que la anonimización llegue a ella
+    let marcada = repo.reservas()
+        .update_one(
+            doc! {
+                "_id": reservation_id,
+                "retencion_legal": { "$exists": false },
+                "anonimizada_en": { "$exists": false },
+            },
+            doc! { "$set": { "retencion_legal": retencion_doc } },
+        )
+        .await
+        .map_err(|e| AppError::database("place_legal_hold", e))?;
+    if marcada.modified_count == 0 {
+        return Err(AppError::Conflict("La reserva ya tiene retención legal".to_string()));
+    }
+
+    events::record(
+        repo.get_ref(),
+        TipoEvento::RetencionLegalMarcada,
+        Some(auth.id()),
+        Some(reservation_id),
+        doc! { "usuario": &auth.usuario },
+        now,
+    ).await;
+    let retenida = Reserva { retencion_legal: Some(retencion.clone()), ..reserva.clone() };
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::RetenerReserva,
+        Some(reservation_id),
+        audit::snapshot(&reserva),
+        audit::snapshot(&retenida),
+        now,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "id": reservation_id.to_hex(),
+        "retencion_legal": retencion
+    })))
+}
+
+/// Parámetros de consulta del ticket de una reserva
+#[derive(Deserialize)]
+struct TicketQuery {
+    /// "escpos" (por defecto) o "texto"
+    formato: Option<String>,
+}
+
+/// Ticket de una reserva para imprimir a su llegada
+///
+/// Compone el ticket con la plantilla `ticket` de la configuración del
+/// restaurante (ver [`crate::ticket`]):
+///
+/// - `formato=escpos` (por defecto): bytes ESC/POS
+///   (`application/octet-stream`) para enviar tal cual a la impresora de
+///   tickets o al puente de impresión
+/// - `formato=texto`: el mismo ticket como texto plano, para previsualizarlo
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva o formato inválidos
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Reserva no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/reservations/{id}/ticket")]
+async fn get_reservation_ticket(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+    query: web::Query<TicketQuery>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerReservas>,
+) -> AppResult<impl Responder> {
+    let reservation_id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    let texto = match query.formato.as_deref() {
+        None | Some("escpos") => false,
+        Some("texto") => true,
+        Some(_) => return Err(AppError::validation_field("formato", "Debe ser 'escpos' o 'texto'")),
+    };
+
+    let reserva = repo.reservas()
+        .find_one(doc! { "_id": reservation_id, "id_restaurante": auth.id() })
+        .await
+        .map_err(|e| AppError::database("get_reservation_ticket", e))?
+        .ok_or(AppError::NotFound("Reserva no encontrada".to_string()))?;
+    let mesa = repo.mesas()
+        .find_one(doc! { "_id": reserva.id_mesa })
+        .await
+        .map_err(|e| AppError::database("get_reservation_ticket", e))?
+        .map(|mesa| mesa.nombre)
+        .unwrap_or_default();
+
+    let plantilla = &auth.restaurant.configuracion.ticket;
+    let datos = DatosTicket::new(&auth.restaurant.nombre, &reserva, &mesa);
+    Ok(if texto {
+        HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(ticket::render_text(plantilla, &datos))
+    } else {
+        HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .body(ticket::render_escpos(plantilla, &datos))
+    })
+}
+
 /// Configura las rutas relacionadas con reservas
 ///
 /// # Rutas disponibles
 /// - `POST /reservations` - Crear nueva reserva
+/// - `POST /reservations/walk-in` - Sentar a un cliente sin reserva
 /// - `GET /reservations` - Listar reservas con filtros opcionales
+/// - `GET /reservations/by-shift` - Reservas de un día agrupadas por turno
+/// - `PUT /reservations/{id}` - Modificar reserva
 /// - `POST /reservations/{id}/confirm` - Confirmar reserva pendiente
 /// - `POST /reservations/{id}/cancel` - Cancelar reserva
+/// - `POST /reservations/bulk` - Confirmar o cancelar varias reservas a la vez
+/// - `POST /reservations/{id}/seat` - Sentar al cliente
+/// - `POST /reservations/{id}/checkin` - Registrar la llegada del cliente y sentarlo
+/// - `POST /reservations/{id}/complete` - Completar reserva
+/// - `POST /reservations/{id}/no-show` - Marcar que el cliente no se presentó
+/// - `POST /reservations/{id}/transfer` - Traspasar a otro local del grupo
+/// - `POST /reservations/{id}/legal-hold` - Marcar con retención legal
+/// - `GET /reservations/{id}/ticket` - Ticket para imprimir (ESC/POS o texto)
 ///
 /// # Autenticación
 /// Todas las rutas requieren autenticación Bearer token.
@@ -533,7 +2925,19 @@ async fn cancel_reservation(
 /// - `cfg`: Configuración del servicio Actix Web donde se registran las rutas
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(make_reservation);
+    cfg.service(walk_in);
     cfg.service(get_reservations);
+    cfg.service(get_reservations_by_shift);
+    cfg.service(update_reservation);
+    cfg.service(move_reservation);
     cfg.service(confirm_reservation);
     cfg.service(cancel_reservation);
+    cfg.service(bulk_update_reservations);
+    cfg.service(seat_reservation);
+    cfg.service(check_in_reservation);
+    cfg.service(complete_reservation);
+    cfg.service(no_show_reservation);
+    cfg.service(transfer_reservation);
+    cfg.service(place_legal_hold);
+    cfg.service(get_reservation_ticket);
 }
\ No newline at end of file
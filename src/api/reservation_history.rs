@@ -0,0 +1,177 @@
+//! # Historial de reservas
+//!
+//! Cada cambio en una reserva deja un [`EventoReserva`] en la colección
+//! `reservation_events`, para poder reconstruir quién cambió qué y cuándo
+//! ante una reclamación del cliente:
+//!
+//! - Las operaciones del panel sobre reservas, con su usuario y rol: se
+//!   anotan a la vez que su entrada de auditoría (ver [`super::audit::record`])
+//! - Las del cliente (`cliente`): la reserva desde el widget y la
+//!   verificación de su email o teléfono
+//! - Las de la pasarela de pago (`sistema`): cada parte del depósito pagada
+//!   y la confirmación al alcanzar el umbral
+//!
+//! Los cambios se calculan igual que en la auditoría ([`super::audit::diff`]),
+//! así que tampoco guardan los datos de contacto de los clientes. El
+//! personal de gestión lo consulta con `GET /reservations/{id}/history`.
+//! Registrar un evento nunca hace fallar la operación. Con el diario de
+//! reservas activo, cada evento anota además la reserva completa en él (ver
+//! [`crate::journal`]).
+
+use actix_web::{get, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use serde::Serialize;
+use super::{AppError, AppResult};
+use super::audit::{diff, Autor, CambioResponse};
+use super::auth::{AuthenticatedRestaurant, LeerReservas, PermisoGestion};
+use crate::db::{CambioCampo, EventoReserva, MongoRepo, Rol};
+use crate::journal;
+
+/// Autor de los cambios hechos por el cliente
+pub const AUTOR_CLIENTE: &str = "cliente";
+
+/// Autor de los cambios automáticos (pagos...)
+pub const AUTOR_SISTEMA: &str = "sistema";
+
+/// Evento de una operación del panel sobre una reserva
+pub(super) fn from_staff(
+    autor: &Autor,
+    id_reserva: ObjectId,
+    accion: &str,
+    cambios: Vec<CambioCampo>,
+    now: i64,
+) -> EventoReserva {
+    EventoReserva {
+        id: None,
+        id_restaurante: autor.id_restaurante,
+        id_reserva,
+        autor: autor.usuario.clone(),
+        rol: Some(autor.rol),
+        accion: accion.to_string(),
+        cambios,
+        created_at: now,
+    }
+}
+
+/// Evento de un cambio hecho fuera del panel (por el cliente o el sistema)
+///
+/// Los cambios son los campos que difieren entre `antes` y `despues` (ver
+/// [`diff`]).
+pub(super) fn from_outside(
+    id_restaurante: ObjectId,
+    id_reserva: ObjectId,
+    autor: &str,
+    accion: &str,
+    antes: Option<&Document>,
+    despues: Option<&Document>,
+    now: i64,
+) -> EventoReserva {
+    EventoReserva {
+        id: None,
+        id_restaurante,
+        id_reserva,
+        autor: autor.to_string(),
+        rol: None,
+        accion: accion.to_string(),
+        cambios: diff(antes, despues),
+        created_at: now,
+    }
+}
+
+/// Guarda un evento en el historial de su reserva
+///
+/// Con el diario de reservas activo, anota también en él la reserva tal
+/// como queda (ver [`journal::append`]). Si MongoDB rechaza la escritura, el
+/// error queda en el log.
+pub(super) async fn record(repo: &MongoRepo, evento: EventoReserva) {
+    if let Err(e) = repo.eventos_reserva().insert_one(&evento).await {
+        tracing::error!(accion = %evento.accion, reserva = %evento.id_reserva, "Error registrando el historial de la reserva: {}", e);
+    }
+    journal::append(repo, evento.id_reserva, &evento.accion, evento.created_at).await;
+}
+
+/// Evento del historial en la respuesta
+#[derive(Serialize)]
+struct EventoResponse {
+    accion: String,
+    autor: String,
+    rol: Option<Rol>,
+    cambios: Vec<CambioResponse>,
+    created_at: i64,
+}
+
+/// Historial de cambios de una reserva, del más antiguo al más reciente
+///
+/// # Autenticación
+/// Requiere token Bearer con permiso de gestión.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "accion": "crear_reserva",
+///     "autor": "cliente",
+///     "rol": null,
+///     "cambios": [{ "campo": "hora", "antes": null, "despues": "21:00" }],
+///     "created_at": 1718000000
+///   },
+///   {
+///     "accion": "modificar_reserva",
+///     "autor": "ana",
+///     "rol": "encargado",
+///     "cambios": [{ "campo": "hora", "antes": "21:00", "despues": "21:30" }],
+///     "created_at": 1718003600
+///   }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID de reserva inválido
+/// - `401 Unauthorized`: Token inválido o rol sin permiso
+/// - `404 Not Found`: Reserva no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/reservations/{id}/history")]
+async fn reservation_history(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerReservas>,
+) -> AppResult<impl Responder> {
+    let id_reserva = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    let existe = repo.reservas()
+        .count_documents(doc! { "_id": id_reserva, "id_restaurante": auth.id() })
+        .await
+        .map_err(|e| AppError::database("reservation_history", e))?;
+    if existe == 0 {
+        return Err(AppError::NotFound("Reserva no encontrada".to_string()));
+    }
+
+    let mut cursor = repo.eventos_reserva()
+        .find(doc! { "id_reserva": id_reserva, "id_restaurante": auth.id() })
+        .sort(doc! { "created_at": 1, "_id": 1 })
+        .await
+        .map_err(|e| AppError::database("reservation_history", e))?;
+
+    let mut historial = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("reservation_history", e))? {
+        let evento = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando evento de reserva: {}", e)))?;
+        historial.push(EventoResponse {
+            accion: evento.accion,
+            autor: evento.autor,
+            rol: evento.rol,
+            cambios: evento.cambios.into_iter().map(CambioResponse::from).collect(),
+            created_at: evento.created_at,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(historial))
+}
+
+/// Configura la ruta del historial de reservas
+///
+/// # Rutas disponibles
+/// - `GET /reservations/{id}/history` - Historial de cambios de una reserva
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(reservation_history);
+}
@@ -0,0 +1,273 @@
+//! # Borrado definitivo de reservas
+//!
+//! Las reservas canceladas (por ejemplo, las de prueba) se quedan para
+//! siempre en los listados y en las estadísticas. Estas rutas las borran de
+//! verdad, con varias salvaguardas:
+//!
+//! - Solo se borran reservas canceladas o del widget que el cliente nunca
+//!   verificó y cuyo código ya caducó, sin cambios desde hace
+//!   [`DIAS_RETENCION_BORRADO`] días y sin retención legal
+//! - Como `DELETE /tables/clear`, piden confirmación: la primera llamada no
+//!   borra nada y responde `428 Precondition Required` con un token que hay
+//!   que enviar en la segunda con `?confirmacion=<token>` (ver
+//!   [`super::recycle_bin`])
+//!
+//! Se borran también los mensajes de cada reserva; su historial y la
+//! auditoría se conservan, con una entrada `borrar_reserva` por reserva.
+//!
+//! ## Endpoints
+//! - `DELETE /reservations/purge` - Borra todas las reservas que cumplen las condiciones
+//! - `DELETE /reservations/{id}` - Borra una reserva
+
+use actix_web::{delete, web, HttpResponse, Responder};
+use actix_web::http::StatusCode;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use serde::Deserialize;
+use serde_json::json;
+use super::{AppError, AppResult};
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{AuthenticatedRestaurant, EscribirReservas, PermisoGestion};
+use super::customer::discount_visit;
+use super::recycle_bin;
+use crate::clock::Clock;
+use crate::db::{EstadoReserva, MongoRepo, Reserva};
+
+/// Días sin cambios que tiene que llevar una reserva para poder borrarla
+pub const DIAS_RETENCION_BORRADO: i64 = 30;
+
+/// Operación de `DELETE /reservations/purge` en las confirmaciones
+const PURGAR_RESERVAS: &str = "purgar_reservas";
+
+#[derive(Deserialize)]
+struct PurgeQuery {
+    /// Token de confirmación devuelto por la primera llamada
+    confirmacion: Option<String>,
+}
+
+/// Operación de `DELETE /reservations/{id}` en las confirmaciones, que solo
+/// sirve para esa reserva
+fn delete_operation(id: ObjectId) -> String {
+    format!("borrar_reserva:{}", id.to_hex())
+}
+
+/// Filtro de las reservas de un restaurante que se pueden borrar
+fn deletable_filter(id_restaurante: ObjectId, now: i64) -> Document {
+    doc! {
+        "id_restaurante": id_restaurante,
+        "updated_at": { "$lte": now - DIAS_RETENCION_BORRADO * 86_400 },
+        "retencion_legal": { "$exists": false },
+        "$or": [
+            { "estado": EstadoReserva::Cancelada },
+            { "estado": EstadoReserva::SinConfirmar, "verificacion.expira_en": { "$lte": now } },
+        ],
+    }
+}
+
+/// Borra las reservas ya comprobadas, con sus mensajes
+///
+/// Las reservas sin verificar aún contaban como visita de su cliente, que se
+/// descuenta; las canceladas ya la descontaron al cancelarse.
+///
+/// # Retorna
+/// El número de reservas borradas
+async fn delete_reservations(
+    repo: &MongoRepo,
+    autor: &Autor,
+    reservas: Vec<Reserva>,
+    now: i64,
+) -> AppResult<u64> {
+    let mut filtro = deletable_filter(autor.id_restaurante, now);
+    let ids: Vec<ObjectId> = reservas.iter().filter_map(|reserva| reserva.id).collect();
+    filtro.insert("_id", doc! { "$in": &ids });
+    let result = repo.reservas()
+        .delete_many(filtro)
+        .await
+        .map_err(|e| AppError::database("delete_reservations", e))?;
+    repo.mensajes_reserva()
+        .delete_many(doc! { "id_restaurante": autor.id_restaurante, "id_reserva": { "$in": &ids } })
+        .await
+        .map_err(|e| AppError::database("delete_reservation_messages", e))?;
+
+    for reserva in &reservas {
+        if let (EstadoReserva::SinConfirmar, Some(id_cliente)) = (reserva.estado, reserva.id_cliente) {
+            discount_visit(repo, id_cliente).await?;
+        }
+        audit::record(repo, autor, AccionAuditoria::BorrarReserva, reserva.id, audit::snapshot(reserva), None, now).await;
+    }
+    tracing::info!(id_restaurante = %autor.id_restaurante, reservas = result.deleted_count, "Reservas borradas definitivamente");
+
+    Ok(result.deleted_count)
+}
+
+/// Borra definitivamente todas las reservas que se pueden borrar
+///
+/// Son las canceladas y las del widget sin verificar con el código caducado,
+/// sin cambios desde hace 30 días y sin retención legal. Se hace en dos
+/// pasos: sin `confirmacion` no se borra nada y se responde `428` con el
+/// número de reservas y el token que hay que enviar en la segunda llamada.
+///
+/// # Autenticación
+/// Requiere permiso `Gestion` (encargado o propietario) y, con una clave de
+/// API, el alcance `reservations:write`.
+///
+/// # Respuesta
+/// Sin `confirmacion` (`428 Precondition Required`):
+/// ```json
+/// {
+///   "message": "Se van a borrar 12 reservas; repite la petición con la confirmación",
+///   "reservas": 12,
+///   "confirmacion": "9b2f4c0e8d7a4b1f9e6c3a5d2b8f0e1c",
+///   "expires_at": 1718000300
+/// }
+/// ```
+///
+/// Con `confirmacion`:
+/// ```json
+/// { "message": "Se borraron 12 reservas", "reservas": 12 }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Confirmación inválida, ya usada o caducada
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/reservations/purge")]
+async fn purge_reservations(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    query: web::Query<PurgeQuery>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let id_restaurante = auth.id();
+    let now = clock.timestamp();
+    let filtro = deletable_filter(id_restaurante, now);
+
+    let Some(token) = &query.confirmacion else {
+        let total = repo.reservas()
+            .count_documents(filtro)
+            .await
+            .map_err(|e| AppError::database("count_deletable_reservations", e))?;
+        let confirmacion = recycle_bin::request_confirmation(repo.get_ref(), id_restaurante, PURGAR_RESERVAS, None, now).await?;
+        return Ok(HttpResponse::build(StatusCode::PRECONDITION_REQUIRED).json(json!({
+            "message": format!("Se van a borrar {} reservas; repite la petición con la confirmación", total),
+            "reservas": total,
+            "confirmacion": confirmacion.token,
+            "expires_at": confirmacion.expires_at
+        })));
+    };
+    recycle_bin::consume_confirmation(repo.get_ref(), id_restaurante, PURGAR_RESERVAS, None, token, now).await?;
+
+    let mut cursor = repo.reservas()
+        .find(filtro)
+        .await
+        .map_err(|e| AppError::database("find_deletable_reservations", e))?;
+    let mut reservas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("find_deletable_reservations", e))? {
+        reservas.push(cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?);
+    }
+    let borradas = delete_reservations(repo.get_ref(), &Autor::from(&auth), reservas, now).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": format!("Se borraron {} reservas", borradas),
+        "reservas": borradas
+    })))
+}
+
+/// Borra definitivamente una reserva
+///
+/// Solo si está cancelada, o es del widget sin verificar con el código
+/// caducado, no ha cambiado en 30 días y no tiene retención legal. Como
+/// `DELETE /reservations/purge`, sin `confirmacion` no se borra nada y se
+/// responde `428` con el token, que solo sirve para esta reserva.
+///
+/// # Autenticación
+/// Requiere permiso `Gestion` (encargado o propietario) y, con una clave de
+/// API, el alcance `reservations:write`.
+///
+/// # Respuesta
+/// Sin `confirmacion` (`428 Precondition Required`):
+/// ```json
+/// {
+///   "message": "La reserva se va a borrar definitivamente; repite la petición con la confirmación",
+///   "confirmacion": "9b2f4c0e8d7a4b1f9e6c3a5d2b8f0e1c",
+///   "expires_at": 1718000300
+/// }
+/// ```
+///
+/// Con `confirmacion`:
+/// ```json
+/// { "message": "Reserva borrada", "id": "507f1f77bcf86cd799439011" }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido, o confirmación inválida, ya usada o
+///   caducada
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: La reserva no existe o es de otro restaurante
+/// - `409 Conflict` (`reserva_no_borrable`): La reserva no cumple las
+///   condiciones para borrarla
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/reservations/{id}")]
+async fn delete_reservation(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    query: web::Query<PurgeQuery>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    let id = ObjectId::parse_str(path.as_str())
+        .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+    let id_restaurante = auth.id();
+    let now = clock.timestamp();
+
+    let mut filtro = deletable_filter(id_restaurante, now);
+    filtro.insert("_id", id);
+    let reserva = repo.reservas()
+        .find_one(filtro)
+        .await
+        .map_err(|e| AppError::database("find_deletable_reservation", e))?;
+    let Some(reserva) = reserva else {
+        let existe = repo.reservas()
+            .count_documents(doc! { "_id": id, "id_restaurante": id_restaurante })
+            .await
+            .map_err(|e| AppError::database("find_deletable_reservation", e))?;
+        return Err(if existe > 0 {
+            AppError::conflict_with_code(
+                "reserva_no_borrable",
+                &format!(
+                    "Solo se pueden borrar las reservas canceladas o sin verificar, sin cambios en {} días y sin retención legal",
+                    DIAS_RETENCION_BORRADO
+                ),
+            )
+        } else {
+            AppError::NotFound("Reserva no encontrada".to_string())
+        });
+    };
+
+    let operacion = delete_operation(id);
+    let Some(token) = &query.confirmacion else {
+        let confirmacion = recycle_bin::request_confirmation(repo.get_ref(), id_restaurante, &operacion, None, now).await?;
+        return Ok(HttpResponse::build(StatusCode::PRECONDITION_REQUIRED).json(json!({
+            "message": "La reserva se va a borrar definitivamente; repite la petición con la confirmación",
+            "confirmacion": confirmacion.token,
+            "expires_at": confirmacion.expires_at
+        })));
+    };
+    recycle_bin::consume_confirmation(repo.get_ref(), id_restaurante, &operacion, None, token, now).await?;
+
+    delete_reservations(repo.get_ref(), &Autor::from(&auth), vec![reserva], now).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Reserva borrada",
+        "id": id.to_hex()
+    })))
+}
+
+/// Configura las rutas de borrado definitivo de reservas
+///
+/// `DELETE /reservations/purge` se registra antes que
+/// `DELETE /reservations/{id}` para que `purge` no se tome por un ID.
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(purge_reservations);
+    cfg.service(delete_reservation);
+}
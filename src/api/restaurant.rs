@@ -2,18 +2,79 @@
 //!
 //! Este módulo maneja todas las operaciones relacionadas con restaurantes:
 //! - Registro de nuevos restaurantes
-//! - Login y autenticación
+//! - Login y autenticación (cada login abre una sesión, ver [`super::sessions`])
+//! - Recuperación de contraseña por email
+//! - Reclamación de una cuenta ya registrada con el mismo OBJID de Pispas
 //! - Listado de restaurantes
-//! - Validación de tokens de acceso
+//! - Configuración del restaurante (turnos de servicio)
+//! - Revisión de reservas bloqueadas del widget
+//! - Grupos de locales del mismo propietario
+//! - Validación de tokens de acceso (ver [`super::staff`] para los roles)
 
-use actix_web::{post, get, web, HttpResponse, Responder};
+use actix_web::{post, get, put, delete, web, HttpRequest, HttpResponse, Responder};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use mongodb::bson::{doc, oid::ObjectId};
 use uuid::Uuid;
-use super::{AppError, AppResult};
+use super::{badge, AppError, AppResult};
 use super::middleware::ErrorLogExt; // ← Añadir este import
-use crate::db::{MongoRepo, Restaurant};
+use super::client_ip::client_ip;
+use super::login_lockout::{self, PoliticaBloqueo};
+use super::request_log;
+use super::widget_origin::{self, normalize_origin};
+use super::ip_allowlist::{self, normalize_rule};
+use super::sessions;
+use super::reservation::{validate_email, validate_time, HORIZONTE_LISTADO_MAXIMO};
+use super::account_state::ensure_can_login;
+use super::admin::require_admin;
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{AuthenticatedRestaurant, LeerAjustes, PermisoConfiguracion, PermisoGestion, PermisoReservas};
+use super::staff::{authorize, Permiso};
+use crate::clock::Clock;
+use crate::db::{is_duplicate_key, normalize_name, Configuracion, EstadoCuenta, MetodoVerificacion, MongoRepo, Reclamacion, ReglaAlerta, Restaurant, TokenRecuperacion};
+use crate::events::{self, TipoEvento};
+use crate::language;
+use crate::notifications::{EmailMessage, Notifier};
+use crate::risk;
+
+/// Segundos de validez de un token de recuperación de contraseña
+const DURACION_TOKEN_RECUPERACION: i64 = 3600;
+
+/// Máximo de horas de los plazos de la política de cancelación, de la
+/// última hora de las reglas de riesgo, del recordatorio y de la antelación
+/// mínima de las reservas (30 días)
+const MAX_HORAS_PLAZO: u32 = 720;
+
+/// Máximo de minutos de la tolerancia de retraso (cuatro horas)
+const MAX_MINUTOS_TOLERANCIA: u32 = 240;
+
+/// Máximo de la antelación máxima configurable de las reservas (dos años)
+const MAX_ANTELACION_DIAS: u32 = 730;
+
+/// Máximo de la longitud de las notas configurable de las reservas
+const MAX_LONGITUD_NOTAS: u32 = 5000;
+
+/// Máximo del margen de limpieza entre reservas de una mesa
+const MAX_MARGEN_LIMPIEZA_MINUTOS: u32 = 120;
+
+/// Máximo de reglas de duración por tamaño de grupo
+const MAX_DURACIONES_GRUPO: usize = 10;
+
+/// Segundos de validez de un código de reclamación de cuenta
+const DURACION_RECLAMACION: i64 = 15 * 60;
+
+/// Intentos de verificación permitidos por código de reclamación
+const MAX_INTENTOS_RECLAMACION: u32 = 5;
+
+/// Segundos mínimos entre dos códigos de reclamación de una misma cuenta
+const ESPERA_REENVIO_RECLAMACION: i64 = 60;
+
+/// Intentos fallidos, sumando todos los códigos, antes de bloquear la
+/// reclamación de una cuenta
+const MAX_FALLOS_RECLAMACION: u32 = 10;
+
+/// Segundos que dura el bloqueo de la reclamación de una cuenta (24 horas)
+const BLOQUEO_RECLAMACION: i64 = 24 * 60 * 60;
 
 /// Estructura para el registro de restaurantes
 #[derive(Deserialize)]
@@ -26,6 +87,9 @@ struct RegisterRestaurant {
     password: String,
     /// Si las reservas se confirman automáticamente
     confirmar_automaticamente: bool,
+    /// Email de la cuenta, necesario para recuperar la contraseña
+    #[serde(default)]
+    email: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -34,21 +98,44 @@ struct LoginRequest {
     password: String,
 }
 
-#[derive(Serialize)]
-struct RestaurantInfo {
-    id: String,
-    nombre: String,
+/// Estructura para pedir la recuperación de la contraseña
+#[derive(Deserialize)]
+struct ForgotPasswordRequest {
+    /// Nombre del restaurante, como en el login
+    name: String,
+}
+
+/// Estructura para restablecer la contraseña
+#[derive(Deserialize)]
+struct ResetPasswordRequest {
+    /// Token recibido por email
+    token: String,
+    /// Nueva contraseña
+    password: String,
+}
+
+/// Estructura para reclamar una cuenta existente
+#[derive(Deserialize)]
+struct ClaimRequest {
+    /// OBJID de Pispas de la cuenta
     objid_pispas: String,
-    confirmar_automaticamente: bool,
 }
 
-// Para debug - incluir contraseñas
+/// Estructura para verificar una reclamación
+#[derive(Deserialize)]
+struct VerifyClaimRequest {
+    objid_pispas: String,
+    /// Código recibido por email
+    codigo: String,
+    /// Nueva contraseña de la cuenta
+    password: String,
+}
+
 #[derive(Serialize)]
-struct RestaurantInfoWithPassword {
+struct RestaurantInfo {
     id: String,
     nombre: String,
     objid_pispas: String,
-    password: String,
     confirmar_automaticamente: bool,
 }
 
@@ -57,7 +144,8 @@ struct RestaurantInfoWithPassword {
 /// # Parámetros
 ///
 /// - `repo`: Referencia al repositorio MongoDB
-/// - `data`: Datos del restaurante a registrar
+/// - `data`: Datos del restaurante a registrar; `email` es opcional, pero
+///   sin él no se puede recuperar la contraseña
 ///
 /// # Respuesta
 ///
@@ -72,15 +160,18 @@ struct RestaurantInfoWithPassword {
 /// # Errores
 ///
 /// - `400 Bad Request`: Datos de validación incorrectos
-/// - `409 Conflict`: El restaurante ya existe
+/// - `409 Conflict`: El restaurante ya existe; si coincide el OBJID de
+///   Pispas, el dueño puede recuperar la cuenta con `POST /restaurants/claim`
 /// - `500 Internal Server Error`: Error de base de datos
 #[post("/restaurants/register")]
 async fn register_restaurant(
     repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    req: HttpRequest,
     data: web::Json<RegisterRestaurant>,
 ) -> AppResult<impl Responder> {
     // Validación básica
-    if data.name.is_empty() {
+    if data.name.trim().is_empty() {
         return Err(AppError::Validation("El nombre del restaurante es requerido".to_string()));
     }
 
@@ -92,13 +183,20 @@ async fn register_restaurant(
         return Err(AppError::Validation("El OBJID de Pispas es requerido".to_string()));
     }
 
-    // Verificar si el restaurante ya existe
+    let email = data.email.as_deref().map(str::trim).filter(|email| !email.is_empty());
+    if email.is_some_and(|email| !validate_email(email)) {
+        return Err(AppError::Validation("Email inválido".to_string()));
+    }
+
+    // Verificar si el restaurante ya existe (nombre sin distinguir mayúsculas ni acentos)
     let restaurants = repo.restaurants();
+    let nombre_normalizado = normalize_name(&data.name);
 
     let existing = restaurants
         .find_one(doc! {
             "$or": [
                 {"nombre": &data.name},
+                {"nombre_normalizado": &nombre_normalizado},
                 {"objid_pispas": &data.objid_pispas}
             ]
         }) // ← Añadir None como segundo argumento
@@ -106,20 +204,34 @@ async fn register_restaurant(
         .log_error_context("checking if restaurant exists")
         .map_err(|e| AppError::database("check_restaurant_exists", e))?;
 
-    if existing.is_some() {
-        return Err(AppError::Conflict("El restaurante ya existe".to_string()));
+    match existing {
+        Some(existing) if existing.objid_pispas == data.objid_pispas => {
+            return Err(AppError::Conflict(
+                "Ya hay una cuenta con ese OBJID de Pispas; si es tuya, recupérala con POST /restaurants/claim".to_string(),
+            ));
+        }
+        Some(_) => return Err(AppError::Conflict("El restaurante ya existe".to_string())),
+        None => {}
     }
 
-    let access_token = Uuid::new_v4().to_string();
+    let now = clock.timestamp();
 
     let restaurant = Restaurant {
         id: None,
         objid_pispas: data.objid_pispas.clone(),
-        nombre: data.name.clone(),
+        nombre: data.name.trim().to_string(),
+        nombre_normalizado,
         password: data.password.clone(),
         confirmar_automaticamente: data.confirmar_automaticamente,
-        access_token: access_token.clone(),
-        created_at: MongoRepo::current_timestamp(),
+        created_at: now,
+        configuracion: Configuracion::default(),
+        id_grupo: None,
+        email: email.map(str::to_string),
+        google_sub: None,
+        estado: EstadoCuenta::Activo,
+        motivo_estado: None,
+        borrado: None,
+        token_calendario: None,
     };
 
     let result = restaurants
@@ -127,17 +239,45 @@ async fn register_restaurant(
         .await
         .log_error_context("inserting new restaurant")
         .map_err(|e| AppError::database("register_restaurant", e))?;
+    let id = result.inserted_id.as_object_id().unwrap();
+    events::record(repo.get_ref(), TipoEvento::RestauranteRegistrado, Some(id), Some(id), doc! {}, now).await;
+    let access_token = sessions::open(repo.get_ref(), id, &req, now).await?;
 
     Ok(HttpResponse::Ok().json(json!({
         "access_token": access_token,
         "message": "Restaurante registrado correctamente",
-        "id": result.inserted_id.as_object_id().unwrap().to_hex()
+        "id": id.to_hex()
     })))
 }
 
+/// Inicia sesión con el nombre y la contraseña del restaurante
+///
+/// Cada login abre una sesión nueva con su propio token, que el propietario
+/// puede revocar desde `DELETE /restaurants/sessions/{id}`. Tras varios
+/// intentos fallidos se bloquea temporalmente el nombre o la IP (ver
+/// [`super::login_lockout`]).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "access_token": "uuid-token",
+///   "id_restaurante": "mongodb-object-id",
+///   "message": "Login exitoso"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Faltan el nombre o la contraseña
+/// - `401 Unauthorized`: Credenciales incorrectas
+/// - `403 Forbidden`: La cuenta del restaurante está suspendida
+/// - `423 Locked`: Login del restaurante bloqueado por intentos fallidos
+/// - `429 Too Many Requests`: IP bloqueada por intentos fallidos
+/// - `500 Internal Server Error`: Error de base de datos
 #[post("/restaurants/login")]
 async fn login_restaurant(
     repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    req: HttpRequest,
     data: web::Json<LoginRequest>,
 ) -> AppResult<impl Responder> {
     // Validación básica
@@ -145,6 +285,11 @@ async fn login_restaurant(
         return Err(AppError::Validation("Nombre y contraseña son requeridos".to_string()));
     }
 
+    let now = clock.timestamp();
+    let politica = PoliticaBloqueo::from_env();
+    let ip = client_ip(&req).map(|ip| ip.to_string());
+    login_lockout::check(repo.get_ref(), &politica, &data.name, ip.as_deref(), now).await?;
+
     let restaurants = repo.restaurants();
 
     let restaurant = restaurants
@@ -157,20 +302,434 @@ async fn login_restaurant(
 
     match restaurant {
         Some(restaurant) => {
+            ensure_can_login(&restaurant)?;
+            login_lockout::record_success(repo.get_ref(), &data.name).await?;
+            let id = restaurant.id.unwrap();
+            let access_token = sessions::open(repo.get_ref(), id, &req, now).await?;
             Ok(HttpResponse::Ok().json(json!({
-                "access_token": restaurant.access_token,
-                "id_restaurante": restaurant.id.unwrap().to_hex(),
+                "access_token": access_token,
+                "id_restaurante": id.to_hex(),
                 "message": "Login exitoso"
             })))
         }
-        None => Err(AppError::Unauthorized("Credenciales incorrectas".to_string()))
+        None => {
+            login_lockout::record_failure(repo.get_ref(), &politica, &data.name, ip.as_deref(), now).await?;
+            Err(AppError::Unauthorized("Credenciales incorrectas".to_string()))
+        }
+    }
+}
+
+/// Envía por email un token para restablecer la contraseña
+///
+/// Invalida los tokens pedidos antes para el mismo restaurante. La respuesta
+/// es la misma exista o no el restaurante, para no revelar qué nombres están
+/// registrados; sin email en la cuenta no se envía nada.
+///
+/// # Cuerpo
+/// ```json
+/// { "name": "La Tasca" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// { "message": "Si el restaurante tiene email, recibirá las instrucciones" }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Falta el nombre
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/restaurants/forgot-password")]
+async fn forgot_password(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<ForgotPasswordRequest>,
+) -> AppResult<impl Responder> {
+    if data.name.trim().is_empty() {
+        return Err(AppError::Validation("El nombre del restaurante es requerido".to_string()));
+    }
+
+    let restaurant = repo.restaurants()
+        .find_one(doc! {
+            "$or": [
+                {"nombre": &data.name},
+                {"nombre_normalizado": normalize_name(&data.name)}
+            ]
+        })
+        .await
+        .map_err(|e| AppError::database("find_restaurant_for_reset", e))?;
+
+    if let Some((restaurant, email)) = restaurant.and_then(|r| r.email.clone().map(|email| (r, email))) {
+        let now = clock.timestamp();
+        let token = Uuid::new_v4().to_string();
+
+        repo.tokens_recuperacion()
+            .delete_many(doc! { "id_restaurante": restaurant.id })
+            .await
+            .map_err(|e| AppError::database("delete_reset_tokens", e))?;
+        repo.tokens_recuperacion()
+            .insert_one(TokenRecuperacion {
+                id: None,
+                id_restaurante: restaurant.id.unwrap(),
+                token: token.clone(),
+                expires_at: now + DURACION_TOKEN_RECUPERACION,
+                created_at: now,
+            })
+            .await
+            .map_err(|e| AppError::database("create_reset_token", e))?;
+
+        let envio = notifier.send_email(EmailMessage {
+            to: email,
+            subject: format!("Recupera la contraseña de {}", restaurant.nombre),
+            body: format!(
+                "Hola,\n\nPara elegir una nueva contraseña usa este código en los próximos {} minutos:\n{}\n\nSi no lo has pedido tú, ignora este mensaje.\n",
+                DURACION_TOKEN_RECUPERACION / 60, token
+            ),
+            adjuntos: Vec::new(),
+            request_id: None,
+        }).await;
+        if let Err(e) = envio {
+            tracing::error!("Error enviando el token de recuperación: {}", e);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Si el restaurante tiene email, recibirá las instrucciones"
+    })))
+}
+
+/// Restablece la contraseña con un token de recuperación
+///
+/// El token se consume al usarlo. También se cierran todas las sesiones
+/// abiertas con la contraseña anterior y se abre una nueva.
+///
+/// # Cuerpo
+/// ```json
+/// { "token": "uuid-token", "password": "nueva-contraseña" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "access_token": "uuid-token",
+///   "id_restaurante": "mongodb-object-id",
+///   "message": "Contraseña actualizada"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Contraseña de menos de 6 caracteres
+/// - `401 Unauthorized`: Token inválido, usado o caducado
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/restaurants/reset-password")]
+async fn reset_password(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    req: HttpRequest,
+    data: web::Json<ResetPasswordRequest>,
+) -> AppResult<impl Responder> {
+    if data.password.len() < 6 {
+        return Err(AppError::Validation("La contraseña debe tener al menos 6 caracteres".to_string()));
+    }
+
+    let now = clock.timestamp();
+    let token = repo.tokens_recuperacion()
+        .find_one_and_delete(doc! {
+            "token": &data.token,
+            "expires_at": { "$gt": now }
+        })
+        .await
+        .map_err(|e| AppError::database("consume_reset_token", e))?
+        .ok_or(AppError::Unauthorized("Token de recuperación inválido o caducado".to_string()))?;
+
+    let result = repo.restaurants()
+        .update_one(
+            doc! { "_id": token.id_restaurante },
+            doc! { "$set": { "password": &data.password } },
+        )
+        .await
+        .map_err(|e| AppError::database("reset_password", e))?;
+
+    if result.matched_count == 0 {
+        return Err(AppError::Unauthorized("Token de recuperación inválido o caducado".to_string()));
+    }
+    sessions::revoke_all(repo.get_ref(), token.id_restaurante).await?;
+    let access_token = sessions::open(repo.get_ref(), token.id_restaurante, &req, now).await?;
+    events::record(
+        repo.get_ref(),
+        TipoEvento::ContrasenaRestablecida,
+        Some(token.id_restaurante),
+        Some(token.id_restaurante),
+        doc! {},
+        now,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "access_token": access_token,
+        "id_restaurante": token.id_restaurante.to_hex(),
+        "message": "Contraseña actualizada"
+    })))
+}
+
+/// Oculta parte de un email para mostrarlo sin revelarlo ("d***@latasca.es")
+fn mask_email(email: &str) -> String {
+    match email.split_once('@') {
+        Some((usuario, dominio)) => format!("{}***@{}", usuario.chars().next().unwrap_or('*'), dominio),
+        None => "***".to_string(),
+    }
+}
+
+/// Rechaza la reclamación de una cuenta bloqueada por intentos fallidos
+///
+/// # Errores
+/// - `Locked` (`reclamacion_bloqueada`): La reclamación está bloqueada
+fn check_claim_lock(reclamacion: &Reclamacion, now: i64) -> AppResult<()> {
+    if reclamacion.bloqueada_hasta > now {
+        return Err(AppError::locked(
+            "reclamacion_bloqueada",
+            "Demasiados códigos incorrectos; la recuperación de esta cuenta está bloqueada temporalmente",
+            (reclamacion.bloqueada_hasta - now) as u64,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Inicia la reclamación de una cuenta ya registrada
+///
+/// Pensado para quien intenta registrar un restaurante cuyo OBJID de Pispas
+/// ya tiene cuenta: envía un código de 6 dígitos al email de esa cuenta,
+/// con el que su dueño puede fijar una nueva contraseña en
+/// `POST /restaurants/claim/verify`. Pedir un código nuevo invalida el
+/// anterior.
+///
+/// Contra los intentos de adivinar el código, solo se puede pedir uno cada
+/// minuto, y los intentos fallidos se suman entre códigos: tras 10, la
+/// reclamación de la cuenta queda bloqueada durante 24 horas.
+///
+/// # Cuerpo
+/// ```json
+/// { "objid_pispas": "PISPAS-001" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "message": "Código enviado al email de la cuenta",
+///   "email": "d***@latasca.es"
+/// }
+/// ```
+///
+/// # Errores
+/// - `404 Not Found`: No hay ninguna cuenta con ese OBJID
+/// - `409 Conflict`: La cuenta no tiene email al que enviar el código
+/// - `423 Locked` (`reclamacion_bloqueada`): Demasiados intentos fallidos
+/// - `429 Too Many Requests`: Ya se pidió un código hace menos de un minuto
+/// - `500 Internal Server Error`: Error de base de datos o de envío
+#[post("/restaurants/claim")]
+async fn claim_restaurant(
+    repo: web::Data<MongoRepo>,
+    notifier: web::Data<Notifier>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<ClaimRequest>,
+) -> AppResult<impl Responder> {
+    let restaurant = repo.restaurants()
+        .find_one(doc! { "objid_pispas": &data.objid_pispas })
+        .await
+        .map_err(|e| AppError::database("find_restaurant_to_claim", e))?
+        .ok_or(AppError::NotFound("No hay ninguna cuenta con ese OBJID de Pispas".to_string()))?;
+    let email = restaurant.email.clone().ok_or(AppError::Conflict(
+        "La cuenta no tiene email de contacto; contacta con soporte para recuperarla".to_string(),
+    ))?;
+
+    let now = clock.timestamp();
+    let restaurante_id = restaurant.id.unwrap();
+    let espera_reenvio = || AppError::too_many_requests(
+        "Ya se ha enviado un código hace poco; espera antes de pedir otro",
+        ESPERA_REENVIO_RECLAMACION as u64,
+    );
+    let anterior = repo.reclamaciones()
+        .find_one(doc! { "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("find_claim", e))?;
+    if let Some(anterior) = &anterior {
+        check_claim_lock(anterior, now)?;
+        if anterior.created_at + ESPERA_REENVIO_RECLAMACION > now {
+            return Err(espera_reenvio());
+        }
+    }
+
+    // Solo se reemplaza el código: los fallos y el bloqueo se conservan. Si
+    // otra petición acaba de crear un código o de bloquear la cuenta, el
+    // filtro no coincide y el upsert choca con el índice único
+    let codigo = format!("{:06}", Uuid::new_v4().as_u128() % 1_000_000);
+    let resultado = repo.reclamaciones()
+        .update_one(
+            doc! {
+                "id_restaurante": restaurante_id,
+                "created_at": { "$not": { "$gt": now - ESPERA_REENVIO_RECLAMACION } },
+                "bloqueada_hasta": { "$not": { "$gt": now } },
+            },
+            doc! { "$set": {
+                "codigo": &codigo,
+                "intentos": 0,
+                "expires_at": now + DURACION_RECLAMACION,
+                "created_at": now,
+            } },
+        )
+        .upsert(true)
+        .await;
+    match resultado {
+        Ok(_) => {}
+        Err(e) if is_duplicate_key(&e) => return Err(espera_reenvio()),
+        Err(e) => return Err(AppError::database("create_claim", e)),
+    }
+
+    notifier.send_email(EmailMessage {
+        to: email.clone(),
+        subject: format!("Código para recuperar la cuenta de {}", restaurant.nombre),
+        body: format!(
+            "Hola,\n\nAlguien ha intentado registrar de nuevo {}. Si eres tú, usa este código en los próximos {} minutos para recuperar la cuenta:\n{}\n\nSi no, ignora este mensaje.\n",
+            restaurant.nombre, DURACION_RECLAMACION / 60, codigo
+        ),
+        adjuntos: Vec::new(),
+        request_id: None,
+    }).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Código enviado al email de la cuenta",
+        "email": mask_email(&email)
+    })))
+}
+
+/// Completa la reclamación de una cuenta con el código recibido
+///
+/// Fija la nueva contraseña, cierra todas las sesiones abiertas con la
+/// contraseña anterior y abre una nueva.
+///
+/// # Cuerpo
+/// ```json
+/// { "objid_pispas": "PISPAS-001", "codigo": "482913", "password": "nueva-contraseña" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "access_token": "uuid-token",
+///   "id_restaurante": "mongodb-object-id",
+///   "message": "Cuenta recuperada"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Contraseña de menos de 6 caracteres o código incorrecto
+/// - `401 Unauthorized`: No hay reclamación activa, ha caducado o se han
+///   agotado los intentos
+/// - `423 Locked` (`reclamacion_bloqueada`): Demasiados intentos fallidos,
+///   sumando todos los códigos pedidos
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/restaurants/claim/verify")]
+async fn verify_claim(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    req: HttpRequest,
+    data: web::Json<VerifyClaimRequest>,
+) -> AppResult<impl Responder> {
+    if data.password.len() < 6 {
+        return Err(AppError::Validation("La contraseña debe tener al menos 6 caracteres".to_string()));
+    }
+
+    let sin_reclamacion = || AppError::Unauthorized("No hay ninguna reclamación activa para esa cuenta".to_string());
+    let restaurant = repo.restaurants()
+        .find_one(doc! { "objid_pispas": &data.objid_pispas })
+        .await
+        .map_err(|e| AppError::database("find_restaurant_to_claim", e))?
+        .ok_or_else(sin_reclamacion)?;
+    let restaurante_id = restaurant.id.unwrap();
+    let reclamacion = repo.reclamaciones()
+        .find_one(doc! { "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("find_claim", e))?
+        .ok_or_else(sin_reclamacion)?;
+
+    let now = clock.timestamp();
+    check_claim_lock(&reclamacion, now)?;
+    if reclamacion.intentos >= MAX_INTENTOS_RECLAMACION || reclamacion.expires_at < now {
+        return Err(AppError::Unauthorized(
+            "El código ha caducado o se han agotado los intentos; solicita uno nuevo".to_string(),
+        ));
+    }
+    if data.codigo.trim() != reclamacion.codigo {
+        // Al llegar a MAX_FALLOS_RECLAMACION se bloquea la cuenta y los
+        // fallos vuelven a cero para después del bloqueo
+        let pipeline = vec![
+            doc! { "$set": {
+                "intentos": { "$add": [{ "$ifNull": ["$intentos", 0] }, 1] },
+                "fallos": { "$add": [{ "$ifNull": ["$fallos", 0] }, 1] },
+            } },
+            doc! { "$set": {
+                "bloquear": { "$gte": ["$fallos", i64::from(MAX_FALLOS_RECLAMACION)] },
+            } },
+            doc! { "$set": {
+                "bloqueada_hasta": { "$cond": ["$bloquear", now + BLOQUEO_RECLAMACION, { "$ifNull": ["$bloqueada_hasta", 0] }] },
+                "fallos": { "$cond": ["$bloquear", 0, "$fallos"] },
+            } },
+            doc! { "$unset": ["bloquear"] },
+        ];
+        repo.reclamaciones()
+            .update_one(doc! { "_id": reclamacion.id }, pipeline)
+            .await
+            .map_err(|e| AppError::database("verify_claim", e))?;
+        return Err(AppError::validation_field("codigo", "Código incorrecto"));
     }
+
+    // Consumir el código antes de cambiar la contraseña: solo vale una vez
+    let consumida = repo.reclamaciones()
+        .delete_one(doc! { "_id": reclamacion.id })
+        .await
+        .map_err(|e| AppError::database("consume_claim", e))?;
+    if consumida.deleted_count == 0 {
+        return Err(sin_reclamacion());
+    }
+
+    repo.restaurants()
+        .update_one(
+            doc! { "_id": restaurante_id },
+            doc! { "$set": { "password": &data.password } },
+        )
+        .await
+        .map_err(|e| AppError::database("claim_restaurant", e))?;
+    repo.tokens_recuperacion()
+        .delete_many(doc! { "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("delete_reset_tokens", e))?;
+    sessions::revoke_all(repo.get_ref(), restaurante_id).await?;
+    let access_token = sessions::open(repo.get_ref(), restaurante_id, &req, now).await?;
+    events::record(repo.get_ref(), TipoEvento::CuentaReclamada, Some(restaurante_id), Some(restaurante_id), doc! {}, now).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "access_token": access_token,
+        "id_restaurante": restaurante_id.to_hex(),
+        "message": "Cuenta recuperada"
+    })))
 }
 
+/// Lista todos los restaurantes
+///
+/// Obsoleta: sustituida por `GET /admin/restaurants` (ver
+/// [`super::deprecation::RUTAS_OBSOLETAS`]). Como esta, requiere el token de
+/// administración (`ADMIN_TOKEN`).
+///
+/// # Errores
+/// - `401 Unauthorized`: Falta el token de administración o no coincide
+/// - `404 Not Found`: `ADMIN_TOKEN` no está configurado
+/// - `500 Internal Server Error`: Error de base de datos
 #[get("/restaurants/all")]
 async fn list_restaurants(
     repo: web::Data<MongoRepo>,
+    req: HttpRequest,
 ) -> AppResult<impl Responder> {
+    require_admin(&req)?;
     let restaurants = repo.restaurants();
 
     let cursor = restaurants
@@ -197,62 +756,693 @@ async fn list_restaurants(
     Ok(HttpResponse::Ok().json(results))
 }
 
-// Endpoint de debug con contraseñas
-#[get("/restaurants/all/debug")]
-async fn list_restaurants_with_passwords(
+/// Valida una configuración antes de guardarla
+///
+/// # Errores
+/// - `Validation`: Si la duración de las reservas o el margen de limpieza
+///   están fuera de rango, si las duraciones por tamaño de grupo no van de
+///   menor a mayor, si algún turno no tiene nombre, tiene horas mal
+///   formadas, inicio igual a fin o nombre repetido, si algún origen del widget o IP autorizada no
+///   es válido, si el ancho del ticket está fuera de rango, si el idioma
+///   no está soportado, si el máximo de comensales online no es positivo
+///   o si el horizonte del listado de reservas, el pago por adelantado,
+///   los plazos de la política de cancelación o los límites de las
+///   reservas están fuera de rango, si la zona horaria no existe o si la
+///   insignia no tiene contadores o los repite
+fn validate_configuracion(configuracion: &Configuracion) -> AppResult<()> {
+    if !(15..=600).contains(&configuracion.duracion_reserva_minutos) {
+        return Err(AppError::validation_field(
+            "duracion_reserva_minutos",
+            "Debe estar entre 15 y 600 minutos",
+        ));
+    }
+    if configuracion.duraciones_grupo.len() > MAX_DURACIONES_GRUPO {
+        return Err(AppError::validation_field(
+            "duraciones_grupo",
+            &format!("Como máximo {} reglas", MAX_DURACIONES_GRUPO),
+        ));
+    }
+    let mut desde_anterior = 0;
+    for regla in &configuracion.duraciones_grupo {
+        if regla.desde_personas <= desde_anterior {
+            return Err(AppError::validation_field(
+                "duraciones_grupo",
+                "Los comensales de cada regla deben ser positivos y mayores que los de la anterior",
+            ));
+        }
+        if !(15..=600).contains(&regla.minutos) {
+            return Err(AppError::validation_field(
+                "duraciones_grupo",
+                "Los minutos de cada regla deben estar entre 15 y 600",
+            ));
+        }
+        desde_anterior = regla.desde_personas;
+    }
+    if configuracion.margen_limpieza_minutos > MAX_MARGEN_LIMPIEZA_MINUTOS {
+        return Err(AppError::validation_field(
+            "margen_limpieza_minutos",
+            &format!("Debe estar entre 0 y {} minutos", MAX_MARGEN_LIMPIEZA_MINUTOS),
+        ));
+    }
+
+    let mut nombres = Vec::new();
+
+    for turno in &configuracion.turnos {
+        if turno.nombre.trim().is_empty() {
+            return Err(AppError::validation_field("turnos", "Todos los turnos deben tener nombre"));
+        }
+
+        validate_time(&turno.hora_inicio)?;
+        validate_time(&turno.hora_fin)?;
+
+        if turno.hora_inicio == turno.hora_fin {
+            return Err(AppError::validation_field("turnos", &format!(
+                "El turno '{}' debe tener horas de inicio y fin distintas", turno.nombre
+            )));
+        }
+
+        let nombre = normalize_name(&turno.nombre);
+        if nombres.contains(&nombre) {
+            return Err(AppError::validation_field("turnos", &format!(
+                "El turno '{}' está repetido", turno.nombre
+            )));
+        }
+        nombres.push(nombre);
+    }
+
+    if let Some(email) = &configuracion.email_alertas {
+        if !validate_email(email) {
+            return Err(AppError::validation_field("email_alertas", "Email inválido"));
+        }
+    }
+
+    if let Some(origen) = configuracion.origenes_widget.iter().find(|origen| normalize_origin(origen).is_none()) {
+        return Err(AppError::validation_field("origenes_widget", &format!(
+            "'{}' no es un origen válido (por ejemplo, https://latasca.es)", origen
+        )));
+    }
+
+    if let Some(ip) = configuracion.ips_permitidas.iter().find(|ip| normalize_rule(ip).is_none()) {
+        return Err(AppError::validation_field("ips_permitidas", &format!(
+            "'{}' no es una IP ni un rango CIDR válido (por ejemplo, 203.0.113.0/24)", ip
+        )));
+    }
+
+    if !(24..=64).contains(&configuracion.ticket.ancho) {
+        return Err(AppError::validation_field("ticket", "El ancho del ticket debe estar entre 24 y 64 caracteres"));
+    }
+
+    if language::normalize(&configuracion.idioma).is_none() {
+        return Err(AppError::validation_field("idioma", &format!(
+            "Idioma no soportado; use uno de: {}", language::IDIOMAS.join(", ")
+        )));
+    }
+
+    if configuracion.max_personas_online.is_some_and(|max| max < 1) {
+        return Err(AppError::validation_field("max_personas_online", "Debe ser al menos 1"));
+    }
+
+    if !(1..=HORIZONTE_LISTADO_MAXIMO).contains(&configuracion.horizonte_listado_dias) {
+        return Err(AppError::validation_field(
+            "horizonte_listado_dias",
+            &format!("Debe estar entre 1 y {} días", HORIZONTE_LISTADO_MAXIMO),
+        ));
+    }
+
+    if let Some(deposito) = &configuracion.deposito {
+        if deposito.min_personas < 1 || deposito.importe_por_persona_centimos <= 0 {
+            return Err(AppError::validation_field(
+                "deposito",
+                "El pago por adelantado necesita un mínimo de comensales y un importe por persona mayores que 0",
+            ));
+        }
+    }
+
+    if let Some(politica) = &configuracion.politica_cancelacion {
+        let horas = [Some(politica.horas_cancelacion), politica.horas_modificacion];
+        if horas.into_iter().flatten().any(|horas| horas > MAX_HORAS_PLAZO) {
+            return Err(AppError::validation_field("politica_cancelacion", &format!(
+                "Los plazos de cancelación y modificación no pueden pasar de {} horas", MAX_HORAS_PLAZO
+            )));
+        }
+    }
+
+    let riesgo = &configuracion.riesgo;
+    let puntos = [
+        riesgo.puntos_por_no_show,
+        riesgo.puntos_grupo_grande,
+        riesgo.puntos_ultimo_momento,
+        riesgo.puntos_telefono_inalcanzable,
+    ];
+    if puntos.into_iter().any(|puntos| puntos > risk::MAX_PUNTUACION) {
+        return Err(AppError::validation_field("riesgo", &format!(
+            "Los puntos de cada factor de riesgo no pueden pasar de {}", risk::MAX_PUNTUACION
+        )));
+    }
+    if riesgo.personas_grupo_grande < 1 || riesgo.horas_ultimo_momento > MAX_HORAS_PLAZO {
+        return Err(AppError::validation_field("riesgo", &format!(
+            "Un grupo grande necesita al menos 1 comensal y la última hora no puede pasar de {} horas", MAX_HORAS_PLAZO
+        )));
+    }
+    if riesgo.no_shows_revision == Some(0) {
+        return Err(AppError::validation_field("riesgo", "La revisión necesita al menos 1 no-show previo"));
+    }
+
+    if configuracion.recordatorio_horas.is_some_and(|horas| !(1..=MAX_HORAS_PLAZO).contains(&horas)) {
+        return Err(AppError::validation_field("recordatorio_horas", &format!(
+            "El recordatorio se envía entre 1 y {} horas antes de la reserva", MAX_HORAS_PLAZO
+        )));
+    }
+
+    if configuracion.tolerancia_retraso.is_some_and(|tolerancia| !(1..=MAX_MINUTOS_TOLERANCIA).contains(&tolerancia.minutos)) {
+        return Err(AppError::validation_field("tolerancia_retraso", &format!(
+            "La tolerancia de retraso debe estar entre 1 y {} minutos", MAX_MINUTOS_TOLERANCIA
+        )));
+    }
+
+    if let Some(insignia) = &configuracion.insignia {
+        if insignia.contadores.is_empty() {
+            return Err(AppError::validation_field("insignia", "Indica al menos un contador"));
+        }
+        if insignia.contadores.iter().enumerate().any(|(i, contador)| insignia.contadores[..i].contains(contador)) {
+            return Err(AppError::validation_field("insignia", "Hay contadores repetidos"));
+        }
+    }
+
+    let limites = &configuracion.limites;
+    if limites.max_personas.is_some_and(|max| max < 1) {
+        return Err(AppError::validation_field("limites", "El máximo de comensales debe ser al menos 1"));
+    }
+    if !(1..=MAX_LONGITUD_NOTAS).contains(&limites.longitud_maxima_notas) {
+        return Err(AppError::validation_field("limites", &format!(
+            "La longitud máxima de las notas debe estar entre 1 y {} caracteres", MAX_LONGITUD_NOTAS
+        )));
+    }
+    if limites.antelacion_minima_minutos.is_some_and(|minutos| minutos > MAX_HORAS_PLAZO * 60)
+        || limites.antelacion_maxima_dias.is_some_and(|dias| !(1..=MAX_ANTELACION_DIAS).contains(&dias))
+    {
+        return Err(AppError::validation_field("limites", &format!(
+            "La antelación mínima no puede pasar de {} horas y la máxima debe estar entre 1 y {} días",
+            MAX_HORAS_PLAZO, MAX_ANTELACION_DIAS
+        )));
+    }
+    if !limites.telefono_obligatorio && configuracion.verificacion_cliente == MetodoVerificacion::Telefono {
+        return Err(AppError::validation_field(
+            "limites",
+            "La verificación por teléfono necesita que el teléfono sea obligatorio",
+        ));
+    }
+
+    if configuracion.zona_horaria.as_deref().is_some_and(|zona| zona.parse::<chrono_tz::Tz>().is_err()) {
+        return Err(AppError::validation_field(
+            "zona_horaria",
+            "Zona horaria desconocida; use un nombre IANA como Europe/Madrid",
+        ));
+    }
+
+    if configuracion.benchmark && configuracion.ciudad.as_deref().is_none_or(|ciudad| ciudad.trim().is_empty()) {
+        return Err(AppError::validation_field("ciudad", "Indica la ciudad para participar en la comparativa"));
+    }
+
+    for regla in &configuracion.alertas {
+        match regla {
+            ReglaAlerta::Cancelaciones { max, ventana_minutos } => {
+                if *max == 0 || !(1..=1440).contains(ventana_minutos) {
+                    return Err(AppError::validation_field(
+                        "alertas",
+                        "Las alertas de cancelaciones necesitan un máximo mayor que 0 y una ventana de 1 a 1440 minutos",
+                    ));
+                }
+            }
+            ReglaAlerta::ConfirmacionLenta { max_mediana_minutos, ventana_horas } => {
+                if *max_mediana_minutos == 0 || !(1..=168).contains(ventana_horas) {
+                    return Err(AppError::validation_field(
+                        "alertas",
+                        "Las alertas de confirmación lenta necesitan una mediana máxima mayor que 0 y una ventana de 1 a 168 horas",
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Obtiene la configuración del restaurante autenticado
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "turnos": [
+///     { "nombre": "Comida", "hora_inicio": "13:00", "hora_fin": "16:30" },
+///     { "nombre": "Cena", "hora_inicio": "20:00", "hora_fin": "23:59" }
+///   ],
+///   "verificacion_cliente": "ninguna",
+///   "duracion_reserva_minutos": 90,
+///   "duraciones_grupo": [
+///     { "desde_personas": 3, "minutos": 105 },
+///     { "desde_personas": 7, "minutos": 120 }
+///   ],
+///   "margen_limpieza_minutos": 15,
+///   "registro_peticiones": false,
+///   "email_alertas": "encargado@latasca.es",
+///   "alertas": [
+///     { "tipo": "cancelaciones", "max": 5, "ventana_minutos": 60 },
+///     { "tipo": "confirmacion_lenta", "max_mediana_minutos": 30, "ventana_horas": 24 }
+///   ],
+///   "origenes_widget": ["https://latasca.es"],
+///   "ips_permitidas": ["203.0.113.0/24"],
+///   "ticket": { "ancho": 42, "cabecera": null, "campos": ["hora", "nombre", "personas", "mesa", "preseleccion"], "pie": null, "cortar": true },
+///   "idioma": "es",
+///   "max_personas_online": 8,
+///   "grupos_grandes": "solicitud",
+///   "ciudad": "Madrid",
+///   "direccion": "Calle Mayor 1, Madrid",
+///   "zona_horaria": "Europe/Madrid",
+///   "benchmark": true,
+///   "horizonte_listado_dias": 30,
+///   "deposito": { "min_personas": 8, "importe_por_persona_centimos": 1000 },
+///   "politica_cancelacion": { "horas_cancelacion": 2, "horas_modificacion": 4, "marcar_tardias": false },
+///   "riesgo": {
+///     "puntos_por_no_show": 30,
+///     "personas_grupo_grande": 8,
+///     "puntos_grupo_grande": 15,
+///     "horas_ultimo_momento": 2,
+///     "puntos_ultimo_momento": 15,
+///     "puntos_telefono_inalcanzable": 25,
+///     "no_shows_revision": 2
+///   },
+///   "recordatorio_horas": 24,
+///   "limites": {
+///     "max_personas": 20,
+///     "longitud_maxima_notas": 500,
+///     "antelacion_minima_minutos": 30,
+///     "antelacion_maxima_dias": 90,
+///     "telefono_obligatorio": true
+///   },
+///   "tolerancia_retraso": { "minutos": 15, "accion": "no_show" },
+///   "insignia": { "contadores": ["reservas"] }
+/// }
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/restaurants/settings")]
+async fn get_settings(
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerAjustes>,
+) -> AppResult<impl Responder> {
+    let restaurant = auth.restaurant;
+
+    Ok(HttpResponse::Ok().json(restaurant.configuracion))
+}
+
+/// Reemplaza la configuración del restaurante autenticado
+///
+/// El cuerpo tiene el mismo formato que la respuesta de `GET /restaurants/settings`;
+/// los campos omitidos toman su valor por defecto.
+///
+/// Con `registro_peticiones` activo se registran las peticiones y respuestas
+/// completas del restaurante (ver [`super::request_log`]). Las `alertas` se
+/// envían a `email_alertas` (ver [`crate::jobs::alerts`]). Con
+/// `origenes_widget` el widget solo funciona en esas webs (ver
+/// [`super::widget_origin`]); con `ips_permitidas`, la API de gestión solo
+/// admite peticiones desde esas IPs o rangos (ver [`super::ip_allowlist`]).
+/// Ambas listas se guardan normalizadas y sin repetir. El `ticket` es el
+/// formato de `GET /reservations/{id}/ticket` (ver [`crate::ticket`]). El
+/// `idioma` es el que se usa con los clientes cuyo idioma no se conoce (ver
+/// [`crate::language`]); se guarda sin región (`en-GB` → `en`). Las reservas
+/// del widget de más de `max_personas_online` comensales se rechazan
+/// (`grupos_grandes: "rechazar"`) o se guardan como solicitudes de grupo
+/// (`"solicitud"`, ver [`super::group_request`]). Con `benchmark` el
+/// restaurante entra en la comparativa anónima de su `ciudad`, que es
+/// obligatoria, y puede consultarla en `GET /stats/benchmark`. Sin fechas,
+/// `GET /reservations` lista desde hoy hasta `horizonte_listado_dias` días
+/// después. Con `deposito`, las reservas nuevas de `min_personas` o más
+/// comensales tienen que pagar por adelantado `importe_por_persona_centimos`
+/// por comensal para confirmarse (ver [`super::deposit`]). La
+/// `politica_cancelacion` fija los plazos para cancelar y modificar las
+/// reservas (ver `POST /reservations/{id}/cancel`). Las reglas de `riesgo`
+/// puntúan el riesgo de no presentarse que acompaña a las reservas en los
+/// listados (ver [`crate::risk`]); con `no_shows_revision`, las reservas del
+/// widget de los clientes con esos no-shows previos no se confirman solas. Con `recordatorio_horas`, los clientes
+/// reciben un email ese número de horas antes de su reserva (ver
+/// [`crate::jobs::reminders`]). Los `limites` se aplican a las reservas del
+/// panel y del widget: comensales máximos, longitud de las notas,
+/// antelación mínima y máxima (sin ellas, sin plazos) y si el teléfono es
+/// obligatorio. La `direccion` y la `zona_horaria` (nombre IANA) se usan
+/// en las reservas que los clientes añaden a su calendario (ver
+/// [`super::calendar`]). Las reservas ocupan su mesa
+/// `duracion_reserva_minutos`, salvo las de los grupos que alcanzan el
+/// `desde_personas` de alguna regla de `duraciones_grupo`, que ocupan los
+/// `minutos` de la mayor que alcanzan; las reglas van de menor a mayor. Con
+/// `margen_limpieza_minutos` cada reserva deja su mesa ocupada ese tiempo
+/// más tras su duración, para recogerla (ver [`crate::availability`]). Con
+/// `tolerancia_retraso`, las reservas confirmadas cuyo cliente no ha llegado
+/// `minutos` después de su hora se marcan como no presentadas (`accion:
+/// "no_show"`) o se cancelan para liberar la mesa (`"liberar"`), avisando al
+/// personal (ver [`crate::jobs::late_arrivals`]). Con `insignia`, sus
+/// `contadores` se publican sin autenticación para incrustarlos en la web
+/// del restaurante (ver [`super::badge`]).
+///
+/// # Autenticación
+/// Requiere permiso `Configuracion` (propietario).
+///
+/// # Errores
+/// - `400 Bad Request`: Configuración inválida
+/// - `401 Unauthorized`: Token inválido o rol sin permiso
+/// - `500 Internal Server Error`: Error de base de datos
+#[put("/restaurants/settings")]
+async fn update_settings(
     repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<Configuracion>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
 ) -> AppResult<impl Responder> {
-    // ⚠️ ADVERTENCIA: ESTO ES SOLO PARA DEBUG ⚠️
-    let restaurants = repo.restaurants();
+    let restaurante_id = auth.id();
 
-    let cursor = restaurants
-        .find(mongodb::bson::Document::new()) // ← Añadir None como segundo argumento
+    let mut configuracion = data.into_inner();
+    validate_configuracion(&configuracion)?;
+    let mut origenes = Vec::new();
+    for origen in configuracion.origenes_widget.iter().filter_map(|origen| normalize_origin(origen)) {
+        if !origenes.contains(&origen) {
+            origenes.push(origen);
+        }
+    }
+    configuracion.origenes_widget = origenes;
+    let mut ips = Vec::new();
+    for ip in configuracion.ips_permitidas.iter().filter_map(|ip| normalize_rule(ip)) {
+        if !ips.contains(&ip) {
+            ips.push(ip);
+        }
+    }
+    configuracion.ips_permitidas = ips;
+    configuracion.idioma = language::normalize(&configuracion.idioma).unwrap_or_default().to_string();
+    configuracion.ciudad = configuracion.ciudad
+        .map(|ciudad| ciudad.trim().to_string())
+        .filter(|ciudad| !ciudad.is_empty());
+
+    let configuracion_doc = mongodb::bson::to_document(&configuracion)
+        .map_err(|e| AppError::Internal(format!("Error serializando configuración: {}", e)))?;
+
+    repo.restaurants()
+        .update_one(
+            doc! { "_id": restaurante_id },
+            doc! { "$set": { "configuracion": configuracion_doc } },
+        )
         .await
-        .log_error_context("listing restaurants for debug")
-        .map_err(|e| AppError::database("list_restaurants_debug", e))?;
+        .log_error_context("updating restaurant settings")
+        .map_err(|e| AppError::database("update_settings", e))?;
 
-    let mut results = Vec::new();
-    let mut cursor = cursor;
+    request_log::forget_token(&auth.token);
+    widget_origin::forget_restaurant(restaurante_id);
+    ip_allowlist::forget_restaurant(restaurante_id);
+    badge::forget_restaurant(restaurante_id);
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::CambiarConfiguracion,
+        Some(restaurante_id),
+        audit::snapshot(&auth.restaurant.configuracion),
+        audit::snapshot(&configuracion),
+        clock.timestamp(),
+    ).await;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Configuración actualizada correctamente",
+        "configuracion": configuracion
+    })))
+}
+
+/// Respuesta para un intento de reserva bloqueado en el widget
+#[derive(Serialize)]
+struct ViolacionInfo {
+    id: String,
+    dispositivo: String,
+    motivo: String,
+    email_cliente: String,
+    created_at: i64,
+}
+
+/// Lista los intentos de reserva bloqueados en el widget del restaurante
+///
+/// Devuelve los 100 más recientes para que el restaurante pueda revisar
+/// posibles reservas falsas o clientes legítimos bloqueados.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439011",
+///     "dispositivo": "sesion:3f2a...",
+///     "motivo": "4 emails distintos en 3600 segundos",
+///     "email_cliente": "otro@email.com",
+///     "created_at": 1735150000
+///   }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/restaurants/widget-violations")]
+async fn list_widget_violations(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerAjustes>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let options = mongodb::options::FindOptions::builder()
+        .sort(doc! { "created_at": -1 })
+        .limit(100)
+        .build();
 
+    let mut cursor = repo.widget_violaciones()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .with_options(options)
+        .await
+        .log_error_context("listing widget violations")
+        .map_err(|e| AppError::database("list_widget_violations", e))?;
+
+    let mut results = Vec::new();
     while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
-        let restaurant = cursor.deserialize_current()
-            .map_err(|e| AppError::Internal(format!("Error deserializando restaurant: {}", e)))?;
+        let violacion = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando violación: {}", e)))?;
 
-        results.push(RestaurantInfoWithPassword {
-            id: restaurant.id.unwrap().to_hex(),
-            nombre: restaurant.nombre,
-            objid_pispas: restaurant.objid_pispas,
-            password: restaurant.password,
-            confirmar_automaticamente: restaurant.confirmar_automaticamente,
+        results.push(ViolacionInfo {
+            id: violacion.id.unwrap().to_hex(),
+            dispositivo: violacion.dispositivo,
+            motivo: violacion.motivo,
+            email_cliente: violacion.email_cliente,
+            created_at: violacion.created_at,
         });
     }
 
     Ok(HttpResponse::Ok().json(results))
 }
 
+/// Estructura para unir otro local al grupo del restaurante
+#[derive(Deserialize)]
+struct JoinGroup {
+    /// Token de acceso del otro local, que demuestra que es del mismo propietario
+    token_local: String,
+}
+
+/// Respuesta para un local del grupo
+#[derive(Serialize)]
+struct LocalInfo {
+    id: String,
+    nombre: String,
+}
+
+/// Lista los locales del grupo del restaurante autenticado (él incluido)
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id_grupo": "507f1f77bcf86cd799439011",
+///   "locales": [
+///     { "id": "507f1f77bcf86cd799439012", "nombre": "La Tasca Centro" },
+///     { "id": "507f1f77bcf86cd799439013", "nombre": "La Tasca Playa" }
+///   ]
+/// }
+/// ```
+///
+/// Si el restaurante no pertenece a ningún grupo, `id_grupo` es `null` y la
+/// lista solo lo contiene a él.
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/restaurants/group")]
+async fn get_group(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerAjustes>,
+) -> AppResult<impl Responder> {
+    let restaurant = auth.restaurant;
+
+    let filtro = match restaurant.id_grupo {
+        Some(id_grupo) => doc! { "id_grupo": id_grupo },
+        None => doc! { "_id": restaurant.id },
+    };
+
+    let options = mongodb::options::FindOptions::builder().sort(doc! { "nombre": 1 }).build();
+    let mut cursor = repo.restaurants()
+        .find(filtro)
+        .with_options(options)
+        .await
+        .log_error_context("listing restaurant group")
+        .map_err(|e| AppError::database("get_group", e))?;
+
+    let mut locales = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let local = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando restaurant: {}", e)))?;
+        locales.push(LocalInfo {
+            id: local.id.unwrap().to_hex(),
+            nombre: local.nombre,
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id_grupo": restaurant.id_grupo.map(|id| id.to_hex()),
+        "locales": locales
+    })))
+}
+
+/// Une otro local al grupo del restaurante autenticado
+///
+/// Presentar el token de acceso del otro local demuestra que los dos son del
+/// mismo propietario. Si ninguno tenía grupo se crea uno nuevo; si solo uno
+/// lo tenía, el otro se une a él.
+///
+/// # Autenticación
+/// Requiere permiso `Configuracion` (propietario) en los dos locales.
+///
+/// # Cuerpo
+/// ```json
+/// { "token_local": "uuid-token-del-otro-local" }
+/// ```
+///
+/// # Respuesta
+/// El grupo resultante, con el mismo formato que `GET /restaurants/group`
+/// pero solo con el `id_grupo`.
+///
+/// # Errores
+/// - `400 Bad Request`: El token es del propio restaurante
+/// - `401 Unauthorized`: Alguno de los tokens es inválido
+/// - `409 Conflict`: Los dos locales ya están en grupos distintos
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/restaurants/group")]
+async fn join_group(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<JoinGroup>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let restaurant = auth.restaurant;
+    let otro = authorize(repo.get_ref(), &data.token_local, Permiso::Configuracion, clock.timestamp()).await?;
+
+    if restaurant.id == otro.id {
+        return Err(AppError::Validation("No se puede agrupar un local consigo mismo".to_string()));
+    }
+
+    let id_grupo = match (restaurant.id_grupo, otro.id_grupo) {
+        (Some(a), Some(b)) if a != b => {
+            return Err(AppError::Conflict(
+                "Los locales ya pertenecen a grupos distintos; uno debe salir del suyo antes".to_string(),
+            ));
+        }
+        (Some(id), _) | (_, Some(id)) => id,
+        (None, None) => ObjectId::new(),
+    };
+
+    repo.restaurants()
+        .update_many(
+            doc! { "_id": { "$in": [restaurant.id, otro.id] } },
+            doc! { "$set": { "id_grupo": id_grupo } },
+        )
+        .await
+        .log_error_context("joining restaurant group")
+        .map_err(|e| AppError::database("join_group", e))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Locales agrupados correctamente",
+        "id_grupo": id_grupo.to_hex()
+    })))
+}
+
+/// Saca al restaurante autenticado de su grupo de locales
+///
+/// # Autenticación
+/// Requiere permiso `Configuracion` (propietario).
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/restaurants/group")]
+async fn leave_group(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    repo.restaurants()
+        .update_one(doc! { "_id": restaurante_id }, doc! { "$unset": { "id_grupo": "" } })
+        .await
+        .log_error_context("leaving restaurant group")
+        .map_err(|e| AppError::database("leave_group", e))?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Busca el restaurante completo asociado a un token de acceso
+///
+/// Acepta también tokens del personal con permiso de gestión; las rutas
+/// abiertas a camareros usan [`super::staff::authorize`] directamente.
+///
+/// # Errores
+/// - `Unauthorized`: Si el token no corresponde a ningún restaurante o el
+///   rol no tiene permiso de gestión
+pub async fn find_by_token(
+    repo: &MongoRepo,
+    token: &str,
+    now: i64,
+) -> AppResult<Restaurant> {
+    authorize(repo, token, Permiso::Gestion, now).await
+}
+
 // Nueva función para validar token con MongoDB
 pub async fn validate_access_token(
     repo: &MongoRepo,
     token: &str,
+    now: i64,
 ) -> AppResult<ObjectId> {
-    let restaurants = repo.restaurants();
-
-    let restaurant = restaurants
-        .find_one(doc! { "access_token": token }) // ← Añadir None como segundo argumento
-        .await
-        .log_error_context("validating access token")
-        .map_err(|e| AppError::database("validate_token", e))?;
-
-    match restaurant {
-        Some(restaurant) => Ok(restaurant.id.unwrap()),
-        None => Err(AppError::Unauthorized("Token inválido".to_string()))
-    }
+    let restaurant = find_by_token(repo, token, now).await?;
+    Ok(restaurant.id.unwrap())
 }
 
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(register_restaurant);
     cfg.service(login_restaurant);
+    cfg.service(forgot_password);
+    cfg.service(reset_password);
+    cfg.service(claim_restaurant);
+    cfg.service(verify_claim);
     cfg.service(list_restaurants);
-    // SOLO para debug local:
-    cfg.service(list_restaurants_with_passwords);
+    cfg.service(get_settings);
+    cfg.service(update_settings);
+    cfg.service(list_widget_violations);
+    cfg.service(get_group);
+    cfg.service(join_group);
+    cfg.service(leave_group);
 }
\ No newline at end of file
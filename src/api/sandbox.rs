@@ -0,0 +1,114 @@
+//! # Modo de pruebas (*sandbox*) para integradores
+//!
+//! Las claves de API creadas con `sandbox` (ver [`super::api_keys`]) tienen
+//! tokens con el prefijo [`PREFIJO_SANDBOX`]. Las peticiones con uno de esos
+//! tokens se atienden con la misma API, pero contra una base de datos aparte
+//! en el mismo servidor ([`MongoRepo::sandbox`]): las reservas, clientes y
+//! cambios del plano de un integrador en pruebas nunca llegan a los datos
+//! reales, y los trabajos en segundo plano (anonimización, alertas, envío de
+//! eventos) no las ven.
+//!
+//! El middleware [`route_sandbox`] sustituye el `MongoRepo` de la petición
+//! por el de pruebas antes de los demás middlewares y de los handlers, y
+//! marca la respuesta con la cabecera `X-Sandbox: true`.
+//!
+//! Al crear una clave de pruebas se copian a la base de datos de pruebas el
+//! restaurante, sus plantas y sus mesas ([`provision`]), sin la contraseña
+//! ni los datos de contacto de la cuenta. Volver a crear una clave actualiza
+//! la copia sin borrar lo creado en pruebas.
+
+use std::rc::Rc;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{Extensions, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use mongodb::bson::doc;
+use super::{AppError, AppResult};
+use super::request_log::bearer_token;
+use crate::db::{MongoRepo, Restaurant};
+
+/// Prefijo de los tokens de las claves de API de pruebas
+pub const PREFIJO_SANDBOX: &str = "sbx_";
+
+/// Copia el restaurante, sus plantas y sus mesas a la base de datos de pruebas
+///
+/// Crea antes los índices de la base de datos de pruebas, por si es la
+/// primera vez que se usa.
+///
+/// # Errores
+/// - `Database`: Error leyendo los datos reales o escribiendo la copia
+pub async fn provision(repo: &MongoRepo, restaurant: &Restaurant) -> AppResult<()> {
+    let sandbox = repo.sandbox();
+    sandbox.create_indexes().await?;
+
+    let id_restaurante = restaurant.id.unwrap();
+    let copia = Restaurant {
+        password: String::new(),
+        email: None,
+        google_sub: None,
+        id_grupo: None,
+        token_calendario: None,
+        ..restaurant.clone()
+    };
+    sandbox.restaurants()
+        .replace_one(doc! { "_id": id_restaurante }, copia)
+        .upsert(true)
+        .await
+        .map_err(|e| AppError::database("provision_sandbox", e))?;
+
+    let mut plantas = repo.plantas()
+        .find(doc! { "id_restaurante": id_restaurante })
+        .await
+        .map_err(|e| AppError::database("provision_sandbox", e))?;
+    while plantas.advance().await.map_err(|e| AppError::database("provision_sandbox", e))? {
+        let planta = plantas.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando planta: {}", e)))?;
+        sandbox.plantas()
+            .replace_one(doc! { "_id": planta.id }, planta)
+            .upsert(true)
+            .await
+            .map_err(|e| AppError::database("provision_sandbox", e))?;
+    }
+
+    let mut mesas = repo.mesas()
+        .find(doc! { "id_restaurante": id_restaurante })
+        .await
+        .map_err(|e| AppError::database("provision_sandbox", e))?;
+    while mesas.advance().await.map_err(|e| AppError::database("provision_sandbox", e))? {
+        let mesa = mesas.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando mesa: {}", e)))?;
+        sandbox.mesas()
+            .replace_one(doc! { "_id": mesa.id }, mesa)
+            .upsert(true)
+            .await
+            .map_err(|e| AppError::database("provision_sandbox", e))?;
+    }
+
+    tracing::info!(restaurante = %id_restaurante, "Restaurante copiado a la base de datos de pruebas");
+    Ok(())
+}
+
+/// Middleware que atiende las claves de pruebas con la base de datos de pruebas
+///
+/// Ver la documentación del módulo.
+pub async fn route_sandbox(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let es_sandbox = bearer_token(&req).is_some_and(|token| token.starts_with(PREFIJO_SANDBOX));
+    let repo = req.app_data::<web::Data<MongoRepo>>()
+        .filter(|repo| !repo.is_sandbox())
+        .map(|repo| repo.sandbox());
+    let (true, Some(sandbox)) = (es_sandbox, repo) else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+
+    let mut datos = Extensions::new();
+    datos.insert(web::Data::new(sandbox));
+    req.add_data_container(Rc::new(datos));
+
+    let mut respuesta = next.call(req).await?.map_into_boxed_body();
+    respuesta.headers_mut().insert(HeaderName::from_static("x-sandbox"), HeaderValue::from_static("true"));
+    Ok(respuesta)
+}
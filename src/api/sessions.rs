@@ -0,0 +1,267 @@
+//! # Sesiones del propietario
+//!
+//! Cada login del propietario (contraseña, Google, recuperación o
+//! reclamación de la cuenta) abre una sesión con su propio token de acceso,
+//! guardada en la colección `sesiones` junto con la IP y el `User-Agent` del
+//! dispositivo. Así el propietario puede ver desde dónde está conectado y
+//! cerrar una sesión concreta sin afectar a las demás:
+//!
+//! - `GET /restaurants/sessions` - Sesiones abiertas, la más reciente primero
+//! - `DELETE /restaurants/sessions/{id}` - Revoca una sesión
+//!
+//! Restablecer la contraseña o reclamar la cuenta cierra todas las sesiones
+//! anteriores. Los tokens del personal no son sesiones: cada cuenta de
+//! personal tiene un único token (ver [`super::staff`]).
+//!
+//! La última vez que se usó cada sesión se actualiza en el middleware
+//! [`touch_session`], como mucho una vez por minuto y token.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{delete, get, web, Error, HttpRequest, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use serde::Serialize;
+use uuid::Uuid;
+use super::{AppError, AppResult};
+use super::auth::{AuthenticatedRestaurant, PermisoConfiguracion};
+use super::client_ip::client_ip;
+use super::request_log::bearer_token;
+use crate::clock::Clock;
+use crate::db::{MongoRepo, Sesion, Suplantacion};
+use crate::events::{self, TipoEvento};
+
+/// Segundos mínimos entre dos actualizaciones de `last_used_at` de un token
+const RESOLUCION_ULTIMO_USO: i64 = 60;
+
+/// Máximo de tokens recordados por [`touch_session`]; al superarlo se vacía
+const CACHE_MAX: usize = 10_000;
+
+/// Longitud máxima del `User-Agent` guardado
+const MAX_USER_AGENT: usize = 256;
+
+/// Sesión tal como se muestra al propietario
+#[derive(Serialize)]
+struct SesionResponse {
+    id: String,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    created_at: i64,
+    last_used_at: i64,
+    /// Si es la sesión con la que se ha hecho la petición
+    actual: bool,
+    /// Quién del soporte la abrió, si es una suplantación
+    suplantada_por: Option<String>,
+}
+
+/// Abre una sesión del propietario de un restaurante y devuelve su token
+///
+/// # Errores
+/// - `Database`: Error guardando la sesión
+pub async fn open(repo: &MongoRepo, id_restaurante: ObjectId, req: &HttpRequest, now: i64) -> AppResult<String> {
+    insert(repo, id_restaurante, req, None, now).await
+}
+
+/// Abre una sesión de suplantación del propietario (ver [`super::impersonation`])
+///
+/// # Errores
+/// - `Database`: Error guardando la sesión
+pub(super) async fn open_impersonation(
+    repo: &MongoRepo,
+    id_restaurante: ObjectId,
+    req: &HttpRequest,
+    suplantacion: Suplantacion,
+    now: i64,
+) -> AppResult<String> {
+    insert(repo, id_restaurante, req, Some(suplantacion), now).await
+}
+
+async fn insert(
+    repo: &MongoRepo,
+    id_restaurante: ObjectId,
+    req: &HttpRequest,
+    suplantacion: Option<Suplantacion>,
+    now: i64,
+) -> AppResult<String> {
+    let token = Uuid::new_v4().to_string();
+    let user_agent = req.headers()
+        .get("User-Agent")
+        .and_then(|valor| valor.to_str().ok())
+        .map(|valor| valor.chars().take(MAX_USER_AGENT).collect());
+    let ip = client_ip(req).map(|ip| ip.to_string());
+
+    repo.sesiones()
+        .insert_one(Sesion {
+            id: None,
+            id_restaurante,
+            token: token.clone(),
+            ip,
+            user_agent,
+            created_at: now,
+            last_used_at: now,
+            suplantacion,
+        })
+        .await
+        .map_err(|e| AppError::database("open_session", e))?;
+
+    Ok(token)
+}
+
+/// Cierra todas las sesiones del propietario de un restaurante
+///
+/// # Errores
+/// - `Database`: Error borrando las sesiones
+pub async fn revoke_all(repo: &MongoRepo, id_restaurante: ObjectId) -> AppResult<u64> {
+    let result = repo.sesiones()
+        .delete_many(doc! { "id_restaurante": id_restaurante })
+        .await
+        .map_err(|e| AppError::database("revoke_sessions", e))?;
+    Ok(result.deleted_count)
+}
+
+/// Lista las sesiones abiertas del propietario
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "mongodb-object-id",
+///     "ip": "203.0.113.7",
+///     "user_agent": "Mozilla/5.0 ...",
+///     "created_at": 1718000000,
+///     "last_used_at": 1718003600,
+///     "actual": true,
+///     "suplantada_por": null
+///   }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o de una cuenta de personal
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/restaurants/sessions")]
+async fn list_sessions(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let mut cursor = repo.sesiones()
+        .find(doc! { "id_restaurante": auth.id() })
+        .with_options(FindOptions::builder().sort(doc! { "last_used_at": -1 }).build())
+        .await
+        .map_err(|e| AppError::database("list_sessions", e))?;
+
+    let mut sesiones = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("list_sessions", e))? {
+        let sesion = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando sesión: {}", e)))?;
+        sesiones.push(SesionResponse {
+            id: sesion.id.map(|id| id.to_hex()).unwrap_or_default(),
+            actual: sesion.token == auth.token,
+            ip: sesion.ip,
+            user_agent: sesion.user_agent,
+            created_at: sesion.created_at,
+            last_used_at: sesion.last_used_at,
+            suplantada_por: sesion.suplantacion.map(|suplantacion| suplantacion.admin),
+        });
+    }
+
+    Ok(HttpResponse::Ok().json(sesiones))
+}
+
+/// Revoca una sesión del propietario
+///
+/// Su token deja de funcionar en la siguiente petición. Se puede revocar la
+/// sesión actual (cerrar sesión).
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Errores
+/// - `400 Bad Request`: ID de sesión inválido
+/// - `401 Unauthorized`: Token inválido o de una cuenta de personal
+/// - `404 Not Found`: La sesión no existe o es de otro restaurante
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/restaurants/sessions/{id}")]
+async fn revoke_session(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+    path: web::Path<String>,
+) -> AppResult<impl Responder> {
+    let id = ObjectId::parse_str(path.as_str())
+        .map_err(|_| AppError::Validation("ID de sesión inválido".to_string()))?;
+
+    let sesion = repo.sesiones()
+        .find_one_and_delete(doc! { "_id": id, "id_restaurante": auth.id() })
+        .await
+        .map_err(|e| AppError::database("revoke_session", e))?
+        .ok_or(AppError::NotFound("Sesión no encontrada".to_string()))?;
+
+    events::record(
+        repo.get_ref(),
+        TipoEvento::SesionRevocada,
+        Some(auth.id()),
+        Some(id),
+        doc! { "actual": sesion.token == auth.token },
+        clock.timestamp(),
+    ).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Instante en que se actualizó por última vez cada token
+fn cache() -> &'static Mutex<HashMap<String, Instant>> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Middleware que anota la última vez que se usa cada sesión
+///
+/// Los tokens que no son de ninguna sesión (personal, administración) no
+/// modifican nada. Un fallo al anotar queda en el log y no afecta a la
+/// petición.
+pub async fn touch_session(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    if let (Some(token), Some(repo), Some(clock)) = (
+        bearer_token(&req),
+        req.app_data::<web::Data<MongoRepo>>(),
+        req.app_data::<web::Data<dyn Clock>>(),
+    ) {
+        let reciente = cache().lock().unwrap().get(&token)
+            .is_some_and(|desde| desde.elapsed() < Duration::from_secs(RESOLUCION_ULTIMO_USO as u64));
+        if !reciente {
+            let now = clock.timestamp();
+            let result = repo.sesiones()
+                .update_one(
+                    doc! { "token": &token, "last_used_at": { "$lte": now - RESOLUCION_ULTIMO_USO } },
+                    doc! { "$set": { "last_used_at": now } },
+                )
+                .await;
+            if let Err(e) = result {
+                tracing::warn!("No se pudo anotar el uso de la sesión: {}", e);
+            }
+
+            let mut cache = cache().lock().unwrap();
+            if cache.len() >= CACHE_MAX {
+                cache.clear();
+            }
+            cache.insert(token, Instant::now());
+        }
+    }
+
+    next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+}
+
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_sessions);
+    cfg.service(revoke_session);
+}
@@ -0,0 +1,366 @@
+//! # API de Turnos del personal
+//!
+//! Este módulo registra quién trabaja cada turno y las notas de traspaso
+//! entre turnos:
+//! - Empezar un turno (registro de la persona que lo trabaja)
+//! - Listar quién trabajó cada turno de un día
+//! - Dejar notas de traspaso, opcionalmente ligadas a reservas del día
+//! - Listar las notas de un día
+//!
+//! El panel del día (`GET /reservations/by-shift`) incluye las notas del día
+//! y el personal de cada turno, de forma que el equipo de la cena ve lo que
+//! dejó anotado el de la comida.
+//!
+//! Todas las operaciones requieren permiso `Reservas` (cualquier rol del
+//! personal, ver [`super::staff`]).
+
+use actix_web::{get, post, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{FindOptions, ReturnDocument};
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::auth::{AuthenticatedRestaurant, EscribirTurnos, LeerTurnos, PermisoReservas};
+use super::reservation::validate_date;
+use crate::clock::Clock;
+use crate::db::{MongoRepo, NotaTraspaso, RegistroTurno, Restaurant};
+
+/// Longitud máxima de una nota de traspaso
+const MAX_CARACTERES_NOTA: usize = 2000;
+
+/// Parámetros de consulta con la fecha del día
+#[derive(Deserialize)]
+struct DayQuery {
+    /// Fecha a consultar (formato YYYY-MM-DD)
+    fecha: String,
+}
+
+/// Estructura para empezar un turno
+#[derive(Deserialize)]
+struct StartShift {
+    fecha: String,
+    /// Nombre del turno en la configuración del restaurante
+    turno: String,
+}
+
+/// Estructura para dejar una nota de traspaso
+#[derive(Deserialize)]
+struct NewNote {
+    fecha: String,
+    /// Turno en el que se escribe la nota
+    turno: String,
+    texto: String,
+    /// Reservas del día a las que se refiere la nota
+    #[serde(default)]
+    id_reservas: Vec<String>,
+}
+
+/// Estructura de respuesta para un registro de turno
+#[derive(Serialize)]
+struct ShiftRecordResponse {
+    turno: String,
+    usuario: String,
+    inicio: i64,
+}
+
+impl From<RegistroTurno> for ShiftRecordResponse {
+    fn from(registro: RegistroTurno) -> Self {
+        ShiftRecordResponse {
+            turno: registro.turno,
+            usuario: registro.usuario,
+            inicio: registro.inicio,
+        }
+    }
+}
+
+/// Estructura de respuesta para una nota de traspaso
+#[derive(Serialize)]
+pub(super) struct NoteResponse {
+    id: String,
+    turno: String,
+    autor: String,
+    texto: String,
+    id_reservas: Vec<String>,
+    created_at: i64,
+}
+
+impl From<NotaTraspaso> for NoteResponse {
+    fn from(nota: NotaTraspaso) -> Self {
+        NoteResponse {
+            id: nota.id.map(|id| id.to_hex()).unwrap_or_default(),
+            turno: nota.turno,
+            autor: nota.autor,
+            texto: nota.texto,
+            id_reservas: nota.id_reservas.into_iter().map(ObjectId::to_hex).collect(),
+            created_at: nota.created_at,
+        }
+    }
+}
+
+fn cursor_error(e: mongodb::error::Error) -> AppError {
+    AppError::Internal(format!("Error iterando cursor: {}", e))
+}
+
+/// Comprueba que el turno existe en la configuración del restaurante
+fn validate_shift(restaurant: &Restaurant, turno: &str) -> AppResult<String> {
+    let turno = turno.trim();
+    if !restaurant.configuracion.turnos.iter().any(|t| t.nombre == turno) {
+        return Err(AppError::validation_field("turno", "El turno no existe en la configuración del restaurante"));
+    }
+    Ok(turno.to_string())
+}
+
+/// Carga quién trabaja cada turno de un día, por orden de llegada
+pub(super) async fn load_shift_records(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    fecha: &str,
+) -> AppResult<Vec<RegistroTurno>> {
+    let options = FindOptions::builder().sort(doc! { "inicio": 1 }).build();
+    let mut cursor = repo.registros_turno()
+        .find(doc! { "id_restaurante": restaurante_id, "fecha": fecha })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("load_shift_records", e))?;
+
+    let mut registros = Vec::new();
+    while cursor.advance().await.map_err(cursor_error)? {
+        registros.push(cursor.deserialize_current().map_err(cursor_error)?);
+    }
+
+    Ok(registros)
+}
+
+/// Carga las notas de traspaso de un día, de la más antigua a la más reciente
+pub(super) async fn load_notes(
+    repo: &MongoRepo,
+    restaurante_id: ObjectId,
+    fecha: &str,
+) -> AppResult<Vec<NoteResponse>> {
+    let options = FindOptions::builder().sort(doc! { "created_at": 1 }).build();
+    let mut cursor = repo.notas_traspaso()
+        .find(doc! { "id_restaurante": restaurante_id, "fecha": fecha })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("load_notes", e))?;
+
+    let mut notas = Vec::new();
+    while cursor.advance().await.map_err(cursor_error)? {
+        notas.push(NoteResponse::from(cursor.deserialize_current().map_err(cursor_error)?));
+    }
+
+    Ok(notas)
+}
+
+/// Registra que quien hace la petición empieza un turno
+///
+/// Repetir la llamada para el mismo turno no crea otro registro.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Cuerpo
+/// ```json
+/// { "fecha": "2024-12-25", "turno": "Cena" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// { "turno": "Cena", "usuario": "ana", "inicio": 1735149600 }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida o turno inexistente
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/shifts/start")]
+async fn start_shift(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<StartShift>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirTurnos>,
+) -> AppResult<impl Responder> {
+    let identidad = auth;
+    validate_date(&data.fecha)?;
+    let turno = validate_shift(&identidad.restaurant, &data.turno)?;
+
+    let registro = repo.registros_turno()
+        .find_one_and_update(
+            doc! {
+                "id_restaurante": identidad.restaurant.id,
+                "fecha": &data.fecha,
+                "turno": &turno,
+                "usuario": &identidad.usuario
+            },
+            doc! { "$setOnInsert": { "inicio": clock.timestamp() } },
+        )
+        .upsert(true)
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("start_shift", e))?
+        .ok_or(AppError::Internal("El registro de turno no se guardó".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ShiftRecordResponse::from(registro)))
+}
+
+/// Lista quién trabaja cada turno de un día
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// Lista de registros con el mismo formato que `POST /shifts/start`.
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/shifts")]
+async fn list_shift_records(
+    repo: web::Data<MongoRepo>,
+    query: web::Query<DayQuery>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerTurnos>,
+) -> AppResult<impl Responder> {
+    let restaurant = auth.restaurant;
+    validate_date(&query.fecha)?;
+
+    let registros: Vec<ShiftRecordResponse> = load_shift_records(repo.get_ref(), restaurant.id.unwrap(), &query.fecha)
+        .await?
+        .into_iter()
+        .map(ShiftRecordResponse::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(registros))
+}
+
+/// Deja una nota de traspaso para los turnos siguientes
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Cuerpo
+/// ```json
+/// {
+///   "fecha": "2024-12-25",
+///   "turno": "Comida",
+///   "texto": "La mesa 4 pidió trona para la cena",
+///   "id_reservas": ["507f1f77bcf86cd799439011"]
+/// }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "id": "507f1f77bcf86cd799439021",
+///   "turno": "Comida",
+///   "autor": "ana",
+///   "texto": "La mesa 4 pidió trona para la cena",
+///   "id_reservas": ["507f1f77bcf86cd799439011"],
+///   "created_at": 1735125000
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha, turno o texto inválidos, o reservas que no
+///   son del restaurante en esa fecha
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/shifts/notes")]
+async fn create_note(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<NewNote>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirTurnos>,
+) -> AppResult<impl Responder> {
+    let identidad = auth;
+    let restaurante_id = identidad.restaurant.id.unwrap();
+    validate_date(&data.fecha)?;
+    let turno = validate_shift(&identidad.restaurant, &data.turno)?;
+
+    let texto = data.texto.trim();
+    if texto.is_empty() {
+        return Err(AppError::validation_field("texto", "La nota no puede estar vacía"));
+    }
+    if texto.chars().count() > MAX_CARACTERES_NOTA {
+        return Err(AppError::validation_field("texto", &format!(
+            "La nota no puede superar {} caracteres", MAX_CARACTERES_NOTA
+        )));
+    }
+
+    let mut id_reservas = Vec::new();
+    for id in &data.id_reservas {
+        let id = ObjectId::parse_str(id)
+            .map_err(|_| AppError::Validation(format!("ID de reserva inválido: {}", id)))?;
+        if !id_reservas.contains(&id) {
+            id_reservas.push(id);
+        }
+    }
+    let encontradas = repo.reservas()
+        .count_documents(doc! {
+            "_id": { "$in": &id_reservas },
+            "id_restaurante": restaurante_id,
+            "fecha": &data.fecha
+        })
+        .await
+        .map_err(|e| AppError::database("create_note", e))?;
+    if encontradas != id_reservas.len() as u64 {
+        return Err(AppError::validation_field("id_reservas", "Alguna reserva no es de este restaurante en esa fecha"));
+    }
+
+    let mut nota = NotaTraspaso {
+        id: None,
+        id_restaurante: restaurante_id,
+        fecha: data.fecha.clone(),
+        turno,
+        autor: identidad.usuario,
+        texto: texto.to_string(),
+        id_reservas,
+        created_at: clock.timestamp(),
+    };
+
+    let result = repo.notas_traspaso()
+        .insert_one(&nota)
+        .await
+        .map_err(|e| AppError::database("create_note", e))?;
+    nota.id = result.inserted_id.as_object_id();
+
+    Ok(HttpResponse::Ok().json(NoteResponse::from(nota)))
+}
+
+/// Lista las notas de traspaso de un día
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// Lista de notas, de la más antigua a la más reciente, con el mismo
+/// formato que `POST /shifts/notes`.
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/shifts/notes")]
+async fn list_notes(
+    repo: web::Data<MongoRepo>,
+    query: web::Query<DayQuery>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerTurnos>,
+) -> AppResult<impl Responder> {
+    let restaurant = auth.restaurant;
+    validate_date(&query.fecha)?;
+
+    Ok(HttpResponse::Ok().json(load_notes(repo.get_ref(), restaurant.id.unwrap(), &query.fecha).await?))
+}
+
+/// Configura las rutas de turnos del personal
+///
+/// # Rutas disponibles
+/// - `POST /shifts/start` - Empezar un turno
+/// - `GET /shifts` - Personal de cada turno de un día
+/// - `POST /shifts/notes` - Dejar una nota de traspaso
+/// - `GET /shifts/notes` - Notas de traspaso de un día
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(start_shift);
+    cfg.service(list_shift_records);
+    cfg.service(create_note);
+    cfg.service(list_notes);
+}
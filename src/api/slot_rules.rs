@@ -0,0 +1,251 @@
+//! # API de Bloqueos de franjas
+//!
+//! Este módulo gestiona las reglas con las que un restaurante bloquea franjas
+//! horarias concretas, independientemente de sus turnos:
+//! - Listar, crear y eliminar reglas
+//!
+//! Las reglas se aplican en las reservas públicas del widget y en la
+//! búsqueda de mesas disponibles (ver [`crate::availability::blocking_rule`]).
+//! Las reservas creadas desde el panel no se bloquean, para que el personal
+//! pueda hacer excepciones.
+//!
+//! Todas las operaciones requieren autenticación mediante token Bearer.
+
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::fields::CamposRespuesta;
+use super::auth::{AuthenticatedRestaurant, EscribirAjustes, LeerAjustes, PermisoGestion};
+use super::reservation::{validate_date, validate_time};
+use crate::clock::Clock;
+use crate::db::{MongoRepo, ReglaBloqueo};
+
+/// Estructura para crear una regla de bloqueo
+#[derive(Deserialize)]
+struct CreateSlotRule {
+    /// Días de la semana (1 = lunes ... 7 = domingo); vacío para todos
+    #[serde(default)]
+    dias_semana: Vec<u32>,
+    /// Fecha concreta (YYYY-MM-DD) en lugar de días de la semana
+    fecha: Option<String>,
+    /// Hora de inicio (HH:MM, incluida)
+    hora_inicio: String,
+    /// Hora de fin (HH:MM, excluida)
+    hora_fin: String,
+    /// Motivo mostrado al cliente
+    #[serde(default)]
+    motivo: String,
+}
+
+/// Estructura de respuesta para una regla de bloqueo
+#[derive(Serialize)]
+struct SlotRuleResponse {
+    id: String,
+    dias_semana: Vec<u32>,
+    fecha: Option<String>,
+    hora_inicio: String,
+    hora_fin: String,
+    motivo: String,
+    created_at: i64,
+}
+
+impl From<ReglaBloqueo> for SlotRuleResponse {
+    fn from(regla: ReglaBloqueo) -> Self {
+        SlotRuleResponse {
+            id: regla.id.map(|id| id.to_hex()).unwrap_or_default(),
+            dias_semana: regla.dias_semana,
+            fecha: regla.fecha,
+            hora_inicio: regla.hora_inicio,
+            hora_fin: regla.hora_fin,
+            motivo: regla.motivo,
+            created_at: regla.created_at,
+        }
+    }
+}
+
+/// Carga las reglas de bloqueo de un restaurante
+pub(super) async fn load_rules(repo: &MongoRepo, restaurante_id: ObjectId) -> AppResult<Vec<ReglaBloqueo>> {
+    let mut cursor = repo.reglas_bloqueo()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("load_slot_rules", e))?;
+
+    let mut reglas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        reglas.push(cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando regla: {}", e)))?);
+    }
+
+    Ok(reglas)
+}
+
+/// Valida los datos de una regla de bloqueo
+fn validate_rule(data: &CreateSlotRule) -> AppResult<()> {
+    if let Some(dia) = data.dias_semana.iter().find(|dia| !(1..=7).contains(*dia)) {
+        return Err(AppError::validation_field("dias_semana", &format!(
+            "Día de la semana inválido: {} (usa 1 = lunes ... 7 = domingo)", dia
+        )));
+    }
+
+    if let Some(fecha) = &data.fecha {
+        validate_date(fecha)?;
+    }
+
+    let inicio = validate_time(&data.hora_inicio)?;
+    let fin = validate_time(&data.hora_fin)?;
+    if inicio >= fin {
+        return Err(AppError::validation_field(
+            "hora_fin",
+            "La hora de fin debe ser posterior a la de inicio",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Lista las reglas de bloqueo del restaurante autenticado
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439011",
+///     "dias_semana": [6],
+///     "fecha": null,
+///     "hora_inicio": "21:00",
+///     "hora_fin": "21:30",
+///     "motivo": "Cambio de turno en cocina",
+///     "created_at": 1717243200
+///   }
+/// ]
+/// ```
+///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/slot-rules")]
+async fn list_slot_rules(
+    repo: web::Data<MongoRepo>,
+    campos: CamposRespuesta,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerAjustes>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    let reglas: Vec<SlotRuleResponse> = load_rules(repo.get_ref(), restaurante_id)
+        .await?
+        .into_iter()
+        .map(SlotRuleResponse::from)
+        .collect();
+
+    Ok(campos.respond(&reglas))
+}
+
+/// Crea una regla de bloqueo
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante.
+///
+/// # Cuerpo
+/// ```json
+/// {
+///   "dias_semana": [6],
+///   "hora_inicio": "21:00",
+///   "hora_fin": "21:30",
+///   "motivo": "Cambio de turno en cocina"
+/// }
+/// ```
+///
+/// # Validaciones
+/// - Los días de la semana deben estar entre 1 y 7
+/// - La fecha, si se indica, debe ser válida (YYYY-MM-DD)
+/// - Las horas deben ser válidas (HH:MM) y el fin posterior al inicio
+///
+/// # Respuesta
+/// La regla creada, con el mismo formato que `GET /slot-rules`.
+///
+/// # Errores
+/// - `400 Bad Request`: Datos de validación incorrectos
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/slot-rules")]
+async fn create_slot_rule(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<CreateSlotRule>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirAjustes>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+
+    validate_rule(&data)?;
+    let data = data.into_inner();
+
+    let mut regla = ReglaBloqueo {
+        id: None,
+        id_restaurante: restaurante_id,
+        dias_semana: data.dias_semana,
+        fecha: data.fecha,
+        hora_inicio: data.hora_inicio,
+        hora_fin: data.hora_fin,
+        motivo: data.motivo.trim().to_string(),
+        created_at: clock.timestamp(),
+    };
+
+    let result = repo.reglas_bloqueo()
+        .insert_one(&regla)
+        .await
+        .map_err(|e| AppError::database("create_slot_rule", e))?;
+    regla.id = result.inserted_id.as_object_id();
+
+    Ok(HttpResponse::Ok().json(SlotRuleResponse::from(regla)))
+}
+
+/// Elimina una regla de bloqueo
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante propietario.
+///
+/// # Errores
+/// - `400 Bad Request`: ID de regla inválido
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Regla no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/slot-rules/{id}")]
+async fn delete_slot_rule(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirAjustes>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de regla inválido".to_string()))?;
+
+    let result = repo.reglas_bloqueo()
+        .delete_one(doc! { "_id": id, "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("delete_slot_rule", e))?;
+
+    if result.deleted_count == 0 {
+        return Err(AppError::NotFound("Regla no encontrada".to_string()));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Configura las rutas de bloqueos de franjas
+///
+/// # Rutas disponibles
+/// - `GET /slot-rules` - Listar reglas
+/// - `POST /slot-rules` - Crear regla
+/// - `DELETE /slot-rules/{id}` - Eliminar regla
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_slot_rules);
+    cfg.service(create_slot_rule);
+    cfg.service(delete_slot_rule);
+}
@@ -0,0 +1,544 @@
+//! # API de Personal
+//!
+//! Este módulo gestiona las cuentas de personal de cada restaurante y sus
+//! permisos:
+//! - Login del personal (cada cuenta tiene su propio token de acceso)
+//! - Alta, listado y baja de cuentas (solo el propietario)
+//! - Resolución de tokens a restaurante y rol para el resto de la API
+//!
+//! ## Roles y permisos
+//!
+//! | Permiso          | Camarero | Encargado | Propietario |
+//! |------------------|:--------:|:---------:|:-----------:|
+//! | `Reservas`       | ✓        | ✓         | ✓           |
+//! | `Gestion`        |          | ✓         | ✓           |
+//! | `Configuracion`  |          |           | ✓           |
+//!
+//! Los tokens de las sesiones del propio restaurante (los de
+//! `/restaurants/login`, ver [`super::sessions`]) actúan como propietario, y
+//! las claves de API ([`super::api_keys`]) con el rol que se les dio y, si
+//! los tienen, limitadas a sus alcances (ver [`super::auth`]).
+//! [`super::restaurant::find_by_token`] y
+//! [`super::restaurant::validate_access_token`] exigen `Gestion`; las rutas
+//! abiertas a camareros o reservadas al propietario usan [`authorize`] con
+//! su permiso.
+
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+use super::{AppError, AppResult};
+use super::fields::CamposRespuesta;
+use super::middleware::ErrorLogExt;
+use super::account_state::ensure_can_login;
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{extract_token, AuthenticatedRestaurant, PermisoConfiguracion};
+use crate::clock::Clock;
+use crate::db::{Alcance, Empleado, MongoRepo, Restaurant, Rol};
+
+/// Permiso que exige una ruta
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permiso {
+    /// Crear, listar, confirmar y cancelar reservas; consultar mesas
+    Reservas,
+    /// Plano, menú, clientes, bloqueos, depósitos y traspasos
+    Gestion,
+    /// Configuración del restaurante, grupos de locales y personal
+    Configuracion,
+}
+
+impl Permiso {
+    /// Rol mínimo que tiene este permiso
+    pub fn rol_minimo(self) -> Rol {
+        match self {
+            Permiso::Reservas => Rol::Camarero,
+            Permiso::Gestion => Rol::Encargado,
+            Permiso::Configuracion => Rol::Propietario,
+        }
+    }
+
+    /// Indica si un rol tiene este permiso
+    ///
+    /// ```
+    /// use pispas_reservation::api::staff::Permiso;
+    /// use pispas_reservation::db::Rol;
+    ///
+    /// assert!(Permiso::Reservas.permite(Rol::Camarero));
+    /// assert!(!Permiso::Gestion.permite(Rol::Camarero));
+    /// assert!(Permiso::Gestion.permite(Rol::Propietario));
+    /// ```
+    pub fn permite(self, rol: Rol) -> bool {
+        rol >= self.rol_minimo()
+    }
+}
+
+/// Usuario con el que actúa el token del propio restaurante
+pub const USUARIO_PROPIETARIO: &str = "propietario";
+
+/// Prefijo del usuario con que actúa una clave de API (`api:<nombre>`)
+pub const PREFIJO_USUARIO_API: &str = "api:";
+
+/// Quién está detrás de un token de acceso
+#[derive(Debug, Clone)]
+pub struct Identidad {
+    pub restaurant: Restaurant,
+    pub rol: Rol,
+    /// Usuario del personal, o [`USUARIO_PROPIETARIO`] con el token del restaurante
+    pub usuario: String,
+    /// Alcances de la clave de API o de la suplantación; `None` si no tiene
+    /// más límite que el rol
+    pub alcances: Option<Vec<Alcance>>,
+    /// Quién del soporte actúa, si el token es de una suplantación (ver
+    /// [`super::impersonation`])
+    pub suplantada_por: Option<String>,
+}
+
+/// Resuelve un token de acceso al restaurante, rol y usuario con que actúa
+///
+/// Las sesiones de suplantación dejan de valer en cuanto caducan (`now`).
+///
+/// # Errores
+/// - `Unauthorized`: El token no es de ninguna sesión del propietario en
+///   vigor, de ningún empleado ni de ninguna clave de API
+pub async fn resolve_token(repo: &MongoRepo, token: &str, now: i64) -> AppResult<Identidad> {
+    let sesion = repo.sesiones()
+        .find_one(doc! {
+            "token": token,
+            "$or": [
+                { "suplantacion": { "$exists": false } },
+                { "suplantacion.expires_at": { "$gt": now } },
+            ],
+        })
+        .await
+        .log_error_context("loading session by token")
+        .map_err(|e| AppError::database("find_by_token", e))?;
+    if let Some(sesion) = sesion {
+        let restaurant = repo.restaurants()
+            .find_one(doc! { "_id": sesion.id_restaurante })
+            .await
+            .log_error_context("loading restaurant by token")
+            .map_err(|e| AppError::database("find_by_token", e))?
+            .ok_or(AppError::Unauthorized("Token inválido".to_string()))?;
+        let (alcances, suplantada_por) = match sesion.suplantacion {
+            Some(suplantacion) => (suplantacion.alcances, Some(suplantacion.admin)),
+            None => (None, None),
+        };
+        return Ok(Identidad {
+            restaurant,
+            rol: Rol::Propietario,
+            usuario: USUARIO_PROPIETARIO.to_string(),
+            alcances,
+            suplantada_por,
+        });
+    }
+
+    let clave = repo.claves_api()
+        .find_one(doc! { "token": token })
+        .await
+        .log_error_context("loading api key by token")
+        .map_err(|e| AppError::database("find_by_token", e))?;
+    if let Some(clave) = clave {
+        let restaurant = repo.restaurants()
+            .find_one(doc! { "_id": clave.id_restaurante })
+            .await
+            .map_err(|e| AppError::database("find_by_token", e))?
+            .ok_or(AppError::Unauthorized("Token inválido".to_string()))?;
+        return Ok(Identidad {
+            restaurant,
+            rol: clave.rol,
+            usuario: format!("{}{}", PREFIJO_USUARIO_API, clave.nombre),
+            alcances: clave.alcances,
+            suplantada_por: None,
+        });
+    }
+
+    let empleado = repo.empleados()
+        .find_one(doc! { "access_token": token })
+        .await
+        .log_error_context("loading staff by token")
+        .map_err(|e| AppError::database("find_staff_by_token", e))?
+        .ok_or(AppError::Unauthorized("Token inválido".to_string()))?;
+
+    let restaurant = repo.restaurants()
+        .find_one(doc! { "_id": empleado.id_restaurante })
+        .await
+        .map_err(|e| AppError::database("find_by_token", e))?
+        .ok_or(AppError::Unauthorized("Token inválido".to_string()))?;
+
+    Ok(Identidad { restaurant, rol: empleado.rol, usuario: empleado.usuario, alcances: None, suplantada_por: None })
+}
+
+/// Resuelve el token y comprueba que su rol tiene el permiso indicado
+///
+/// Las claves de API con alcances no pasan: estas rutas no declaran alcance.
+///
+/// # Errores
+/// - `Unauthorized`: Token inválido o rol sin el permiso
+/// - `Forbidden`: Clave de API con alcances
+pub async fn authorize(repo: &MongoRepo, token: &str, permiso: Permiso, now: i64) -> AppResult<Restaurant> {
+    authorize_identity(repo, token, permiso, None, now).await.map(|identidad| identidad.restaurant)
+}
+
+/// Igual que [`authorize`] pero devuelve también quién actúa y admite las
+/// claves de API que tienen el `alcance` de la ruta
+///
+/// # Errores
+/// - `Unauthorized`: Token inválido o rol sin el permiso
+/// - `Forbidden`: Clave de API sin el alcance de la ruta
+pub async fn authorize_identity(
+    repo: &MongoRepo,
+    token: &str,
+    permiso: Permiso,
+    alcance: Option<Alcance>,
+    now: i64,
+) -> AppResult<Identidad> {
+    let identidad = resolve_token(repo, token, now).await?;
+
+    if !permiso.permite(identidad.rol) {
+        return Err(AppError::Unauthorized(format!(
+            "Tu rol ({}) no permite esta operación",
+            rol_nombre(identidad.rol)
+        )));
+    }
+    if !alcance_permitido(identidad.alcances.as_deref(), alcance) {
+        return Err(AppError::forbidden(
+            "alcance_insuficiente",
+            "La clave de API no tiene alcance para esta operación",
+        ));
+    }
+
+    Ok(identidad)
+}
+
+/// Indica si un token con `alcances` puede llamar a una ruta que exige `alcance`
+///
+/// ```
+/// use pispas_reservation::api::staff::alcance_permitido;
+/// use pispas_reservation::db::Alcance;
+///
+/// let mostrador = [Alcance::LeerReservas, Alcance::EscribirReservas];
+/// assert!(alcance_permitido(Some(&mostrador), Some(Alcance::LeerReservas)));
+/// assert!(!alcance_permitido(Some(&mostrador), Some(Alcance::EscribirMesas)));
+/// assert!(!alcance_permitido(Some(&mostrador), None));
+/// assert!(alcance_permitido(None, None));
+/// ```
+pub fn alcance_permitido(alcances: Option<&[Alcance]>, alcance: Option<Alcance>) -> bool {
+    match (alcances, alcance) {
+        (None, _) => true,
+        (Some(alcances), Some(alcance)) => alcances.contains(&alcance),
+        (Some(_), None) => false,
+    }
+}
+
+fn rol_nombre(rol: Rol) -> &'static str {
+    match rol {
+        Rol::Camarero => "camarero",
+        Rol::Encargado => "encargado",
+        Rol::Propietario => "propietario",
+    }
+}
+
+/// Estructura para el login del personal
+#[derive(Deserialize)]
+struct StaffLogin {
+    id_restaurante: String,
+    usuario: String,
+    password: String,
+}
+
+/// Estructura para dar de alta una cuenta de personal
+#[derive(Deserialize)]
+struct NewStaff {
+    usuario: String,
+    password: String,
+    rol: Rol,
+}
+
+/// Estructura de respuesta para una cuenta de personal
+#[derive(Serialize)]
+struct StaffResponse {
+    id: String,
+    usuario: String,
+    rol: Rol,
+    created_at: i64,
+}
+
+impl From<Empleado> for StaffResponse {
+    fn from(empleado: Empleado) -> Self {
+        StaffResponse {
+            id: empleado.id.map(|id| id.to_hex()).unwrap_or_default(),
+            usuario: empleado.usuario,
+            rol: empleado.rol,
+            created_at: empleado.created_at,
+        }
+    }
+}
+
+/// Login de una cuenta de personal
+///
+/// # Cuerpo
+/// ```json
+/// { "id_restaurante": "507f1f77bcf86cd799439011", "usuario": "ana", "password": "secreto123" }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "access_token": "uuid-token",
+///   "id_restaurante": "507f1f77bcf86cd799439011",
+///   "rol": "camarero",
+///   "message": "Login exitoso"
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Datos incompletos o ID de restaurante inválido
+/// - `401 Unauthorized`: Credenciales incorrectas
+/// - `403 Forbidden`: La cuenta del restaurante está suspendida
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/staff/login")]
+async fn login_staff(
+    repo: web::Data<MongoRepo>,
+    data: web::Json<StaffLogin>,
+) -> AppResult<impl Responder> {
+    if data.usuario.is_empty() || data.password.is_empty() {
+        return Err(AppError::Validation("Usuario y contraseña son requeridos".to_string()));
+    }
+    let id_restaurante = ObjectId::parse_str(&data.id_restaurante)
+        .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
+
+    let empleado = repo.empleados()
+        .find_one(doc! {
+            "id_restaurante": id_restaurante,
+            "usuario": data.usuario.trim(),
+            "password": &data.password
+        })
+        .await
+        .map_err(|e| AppError::database("login_staff", e))?
+        .ok_or(AppError::Unauthorized("Credenciales incorrectas".to_string()))?;
+    let restaurant = repo.restaurants()
+        .find_one(doc! { "_id": id_restaurante })
+        .await
+        .map_err(|e| AppError::database("login_staff", e))?
+        .ok_or(AppError::Unauthorized("Credenciales incorrectas".to_string()))?;
+    ensure_can_login(&restaurant)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "access_token": empleado.access_token,
+        "id_restaurante": id_restaurante.to_hex(),
+        "rol": empleado.rol,
+        "message": "Login exitoso"
+    })))
+}
+
+/// Rol con el que actúa el token de la petición
+///
+/// Lo usa el panel para ocultar las acciones que el rol no permite.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante o de una cuenta de personal.
+///
+/// # Respuesta
+/// ```json
+/// { "id_restaurante": "507f1f77bcf86cd799439011", "usuario": "ana", "rol": "encargado", "alcances": null, "suplantada_por": null }
+/// ```
+///
+/// `alcances` es la lista de alcances de una clave de API que los tiene;
+/// `suplantada_por`, quién del soporte usa el token, para mostrar el aviso.
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/staff/me")]
+async fn whoami(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    req: HttpRequest,
+) -> AppResult<impl Responder> {
+    let token = extract_token(&req)?;
+    let identidad = resolve_token(repo.get_ref(), &token, clock.timestamp()).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "id_restaurante": identidad.restaurant.id.unwrap().to_hex(),
+        "usuario": identidad.usuario,
+        "rol": identidad.rol,
+        "alcances": identidad.alcances,
+        "suplantada_por": identidad.suplantada_por
+    })))
+}
+
+/// Lista las cuentas de personal del restaurante
+///
+/// # Autenticación
+/// Requiere permiso `Configuracion` (propietario).
+///
+/// # Respuesta
+/// ```json
+/// [
+///   { "id": "507f1f77bcf86cd799439021", "usuario": "ana", "rol": "camarero", "created_at": 1717243200 }
+/// ]
+/// ```
+///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o rol sin permiso
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/staff")]
+async fn list_staff(
+    repo: web::Data<MongoRepo>,
+    campos: CamposRespuesta,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let restaurant = auth.restaurant;
+
+    let options = FindOptions::builder().sort(doc! { "usuario": 1 }).build();
+    let mut cursor = repo.empleados()
+        .find(doc! { "id_restaurante": restaurant.id })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("list_staff", e))?;
+
+    let mut empleados = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let empleado = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando empleado: {}", e)))?;
+        empleados.push(StaffResponse::from(empleado));
+    }
+
+    Ok(campos.respond(&empleados))
+}
+
+/// Da de alta una cuenta de personal
+///
+/// # Autenticación
+/// Requiere permiso `Configuracion` (propietario).
+///
+/// # Cuerpo
+/// ```json
+/// { "usuario": "ana", "password": "secreto123", "rol": "camarero" }
+/// ```
+///
+/// # Respuesta
+/// La cuenta creada, con el mismo formato que `GET /staff`.
+///
+/// # Errores
+/// - `400 Bad Request`: Usuario vacío o reservado, o contraseña demasiado corta
+/// - `401 Unauthorized`: Token inválido o rol sin permiso
+/// - `409 Conflict`: Ya existe una cuenta con ese usuario
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/staff")]
+async fn create_staff(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<NewStaff>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let autor = Autor::from(&auth);
+    let restaurant = auth.restaurant;
+    let id_restaurante = restaurant.id.unwrap();
+
+    let usuario = data.usuario.trim().to_string();
+    if usuario.is_empty() {
+        return Err(AppError::validation_field("usuario", "El usuario es requerido"));
+    }
+    if usuario.eq_ignore_ascii_case(USUARIO_PROPIETARIO) {
+        return Err(AppError::validation_field("usuario", "Ese nombre de usuario está reservado"));
+    }
+    if data.password.len() < 6 {
+        return Err(AppError::validation_field("password", "La contraseña debe tener al menos 6 caracteres"));
+    }
+
+    let existente = repo.empleados()
+        .find_one(doc! { "id_restaurante": id_restaurante, "usuario": &usuario })
+        .await
+        .map_err(|e| AppError::database("create_staff", e))?;
+    if existente.is_some() {
+        return Err(AppError::Conflict(format!("Ya existe una cuenta con el usuario '{}'", usuario)));
+    }
+
+    let mut empleado = Empleado {
+        id: None,
+        id_restaurante,
+        usuario,
+        password: data.password.clone(),
+        rol: data.rol,
+        access_token: Uuid::new_v4().to_string(),
+        created_at: clock.timestamp(),
+    };
+
+    let result = repo.empleados()
+        .insert_one(&empleado)
+        .await
+        .map_err(|e| AppError::database("create_staff", e))?;
+    empleado.id = result.inserted_id.as_object_id();
+    audit::record(
+        repo.get_ref(),
+        &autor,
+        AccionAuditoria::CrearEmpleado,
+        empleado.id,
+        None,
+        audit::snapshot(&empleado),
+        empleado.created_at,
+    ).await;
+
+    Ok(HttpResponse::Ok().json(StaffResponse::from(empleado)))
+}
+
+/// Da de baja una cuenta de personal; su token deja de funcionar
+///
+/// # Autenticación
+/// Requiere permiso `Configuracion` (propietario).
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido
+/// - `401 Unauthorized`: Token inválido o rol sin permiso
+/// - `404 Not Found`: Cuenta no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/staff/{id}")]
+async fn delete_staff(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let id = ObjectId::parse_str(path.into_inner())
+        .map_err(|_| AppError::Validation("ID de cuenta inválido".to_string()))?;
+
+    let empleado = repo.empleados()
+        .find_one_and_delete(doc! { "_id": id, "id_restaurante": auth.id() })
+        .await
+        .map_err(|e| AppError::database("delete_staff", e))?
+        .ok_or(AppError::NotFound("Cuenta no encontrada".to_string()))?;
+
+    audit::record(
+        repo.get_ref(),
+        &Autor::from(&auth),
+        AccionAuditoria::BorrarEmpleado,
+        Some(id),
+        audit::snapshot(&empleado),
+        None,
+        clock.timestamp(),
+    ).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Configura las rutas de personal
+///
+/// # Rutas disponibles
+/// - `POST /staff/login` - Login del personal
+/// - `GET /staff/me` - Rol del token actual
+/// - `GET /staff` - Listar cuentas
+/// - `POST /staff` - Crear cuenta
+/// - `DELETE /staff/{id}` - Eliminar cuenta
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(login_staff);
+    cfg.service(whoami);
+    cfg.service(list_staff);
+    cfg.service(create_staff);
+    cfg.service(delete_staff);
+}
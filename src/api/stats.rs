@@ -0,0 +1,447 @@
+//! # API de Estadísticas
+//!
+//! Analítica del restaurante por días: reservas por canal, comensales,
+//! cancelaciones y no presentados. Se lee de las estadísticas precalculadas
+//! por [`crate::jobs::rollups`], así que no incluye el día en curso y los
+//! cambios en reservas pasadas aparecen tras la siguiente pasada del
+//! trabajo.
+//!
+//! Los restaurantes que lo aceptan pueden además compararse con otros de su
+//! ciudad (`GET /stats/benchmark`, ver [`crate::benchmark`]), y todos pueden
+//! medir cuánto tardan en confirmar las reservas pendientes
+//! (`GET /stats/confirmations`, ver [`crate::sla`]).
+
+use std::collections::{BTreeMap, HashMap};
+use actix_web::{get, web, HttpResponse, Responder};
+use chrono::{Duration, NaiveDate, NaiveTime};
+use mongodb::bson::{doc, oid::ObjectId, Bson, Document};
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+use super::{AppError, AppResult};
+use super::auth::{AuthenticatedRestaurant, LeerEstadisticas, PermisoGestion};
+use super::reservation::validate_date;
+use crate::availability::FORMATO_FECHA;
+use crate::benchmark::{self, Comparacion, Metricas, Totales};
+use crate::clock::Clock;
+use crate::db::{normalize_name, EstadisticaDiaria, MongoRepo};
+use crate::sla::{self, Percentiles};
+
+/// Días que se devuelven si no se indica `desde`
+const DIAS_POR_DEFECTO: i64 = 30;
+
+/// Días máximos de una consulta
+const MAX_DIAS: i64 = 366;
+
+/// Parámetros de consulta con el rango de fechas
+#[derive(Deserialize)]
+struct StatsQuery {
+    /// Primer día (YYYY-MM-DD); por defecto, 30 días antes de `hasta`
+    desde: Option<String>,
+    /// Último día (YYYY-MM-DD); por defecto, ayer
+    hasta: Option<String>,
+}
+
+/// Estadísticas de un día, o la suma de todo el rango (sin `fecha`)
+#[derive(Serialize)]
+struct DayStats {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    fecha: String,
+    reservas: u64,
+    por_canal: BTreeMap<String, u64>,
+    comensales: u64,
+    cancelaciones: u64,
+    no_presentadas: u64,
+}
+
+impl From<EstadisticaDiaria> for DayStats {
+    fn from(estadistica: EstadisticaDiaria) -> Self {
+        DayStats {
+            fecha: estadistica.fecha,
+            reservas: estadistica.reservas,
+            por_canal: estadistica.por_canal,
+            comensales: estadistica.comensales,
+            cancelaciones: estadistica.cancelaciones,
+            no_presentadas: estadistica.no_presentadas,
+        }
+    }
+}
+
+/// Valida el rango de fechas de una consulta y completa el que falte
+///
+/// # Errores
+/// - `Validation`: Fecha inválida, `desde` posterior a `hasta` o rango de
+///   más de [`MAX_DIAS`] días
+fn date_range(query: &StatsQuery, clock: &dyn Clock) -> AppResult<(NaiveDate, NaiveDate)> {
+    let hasta = match &query.hasta {
+        Some(hasta) => validate_date(hasta)?,
+        None => clock.now().date_naive() - Duration::days(1),
+    };
+    let desde = match &query.desde {
+        Some(desde) => validate_date(desde)?,
+        None => hasta - Duration::days(DIAS_POR_DEFECTO - 1),
+    };
+    if desde > hasta {
+        return Err(AppError::validation_field("desde", "La fecha inicial es posterior a la final"));
+    }
+    if (hasta - desde).num_days() >= MAX_DIAS {
+        return Err(AppError::validation_field("desde", &format!(
+            "El rango no puede superar {} días", MAX_DIAS
+        )));
+    }
+    Ok((desde, hasta))
+}
+
+/// Estadísticas diarias del restaurante en un rango de fechas
+///
+/// Solo aparecen los días con reservas.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante (permiso `Gestion`).
+///
+/// # Parámetros de query
+/// - `desde` (opcional): Primer día; por defecto, 30 días antes de `hasta`
+/// - `hasta` (opcional): Último día; por defecto, ayer
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "desde": "2030-05-02",
+///   "hasta": "2030-05-31",
+///   "dias": [
+///     {
+///       "fecha": "2030-05-30",
+///       "reservas": 12,
+///       "por_canal": { "interno": 5, "publico": 7 },
+///       "comensales": 38,
+///       "cancelaciones": 2,
+///       "no_presentadas": 1
+///     }
+///   ],
+///   "totales": {
+///     "reservas": 12,
+///     "por_canal": { "interno": 5, "publico": 7 },
+///     "comensales": 38,
+///     "cancelaciones": 2,
+///     "no_presentadas": 1
+///   }
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida, `desde` posterior a `hasta` o rango
+///   de más de 366 días
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/stats/daily")]
+async fn daily_stats(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    query: web::Query<StatsQuery>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerEstadisticas>,
+) -> AppResult<impl Responder> {
+    let (desde, hasta) = date_range(&query, clock.get_ref())?;
+    let desde = desde.format(FORMATO_FECHA).to_string();
+    let hasta = hasta.format(FORMATO_FECHA).to_string();
+    let mut cursor = repo.estadisticas_diarias()
+        .find(doc! { "id_restaurante": auth.id(), "fecha": { "$gte": &desde, "$lte": &hasta } })
+        .with_options(FindOptions::builder().sort(doc! { "fecha": 1 }).build())
+        .await
+        .map_err(|e| AppError::database("daily_stats", e))?;
+
+    let mut totales = EstadisticaDiaria::new(auth.id(), "", 0);
+    let mut dias = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let estadistica = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando estadística: {}", e)))?;
+
+        totales.reservas += estadistica.reservas;
+        totales.comensales += estadistica.comensales;
+        totales.cancelaciones += estadistica.cancelaciones;
+        totales.no_presentadas += estadistica.no_presentadas;
+        for (canal, reservas) in &estadistica.por_canal {
+            *totales.por_canal.entry(canal.clone()).or_default() += reservas;
+        }
+        dias.push(DayStats::from(estadistica));
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "desde": desde,
+        "hasta": hasta,
+        "dias": dias,
+        "totales": DayStats::from(totales)
+    })))
+}
+
+/// Comparativa de cada cifra del restaurante con los demás
+#[derive(Serialize)]
+struct BenchmarkMetrics {
+    ocupacion: Option<Comparacion>,
+    comensales_por_dia: Option<Comparacion>,
+    tasa_cancelacion: Option<Comparacion>,
+    tasa_no_show: Option<Comparacion>,
+}
+
+/// Lee un total de un documento agregado, sea cual sea su tipo numérico
+pub(super) fn total(documento: &Document, campo: &str) -> u64 {
+    match documento.get(campo) {
+        Some(Bson::Int32(valor)) => (*valor).max(0) as u64,
+        Some(Bson::Int64(valor)) => (*valor).max(0) as u64,
+        Some(Bson::Double(valor)) => valor.max(0.0) as u64,
+        _ => 0,
+    }
+}
+
+/// Ejecuta una agregación agrupada por restaurante
+async fn aggregate_by_restaurant(
+    coleccion: mongodb::Collection<Document>,
+    pipeline: Vec<Document>,
+) -> AppResult<HashMap<ObjectId, Document>> {
+    let mut cursor = coleccion
+        .aggregate(pipeline)
+        .await
+        .map_err(|e| AppError::database("benchmark", e))?;
+
+    let mut resultados = HashMap::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let documento = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando agregado: {}", e)))?;
+        if let Ok(id) = documento.get_object_id("_id") {
+            resultados.insert(id, documento);
+        }
+    }
+    Ok(resultados)
+}
+
+/// Compara las cifras del restaurante con las de otros de su ciudad
+///
+/// Solo para restaurantes con `benchmark` activo en su configuración, y solo
+/// frente a otros que también lo tengan, con la misma `ciudad` (sin
+/// distinguir mayúsculas ni tildes) y al menos 20 reservas en el rango. Con
+/// menos de 5 de esos restaurantes no se publica ninguna cifra
+/// (`suficientes_datos: false`). Cada cifra se da como la del restaurante,
+/// los cuartiles de los demás y el percentil en el que queda:
+///
+/// - `ocupacion`: Comensales por plaza y día, con las plazas de las mesas
+///   reservables (`null` si el restaurante no tiene mesas con capacidad)
+/// - `comensales_por_dia`: Comensales por día del rango
+/// - `tasa_cancelacion` y `tasa_no_show`: Parte de las reservas canceladas y
+///   no presentadas
+///
+/// Igual que `GET /stats/daily`, no incluye el día en curso.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante (permiso `Gestion`).
+///
+/// # Parámetros de query
+/// - `desde` (opcional): Primer día; por defecto, 30 días antes de `hasta`
+/// - `hasta` (opcional): Último día; por defecto, ayer
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "desde": "2030-05-02",
+///   "hasta": "2030-05-31",
+///   "ciudad": "Madrid",
+///   "suficientes_datos": true,
+///   "participantes": 7,
+///   "metricas": {
+///     "ocupacion": { "propio": 1.3, "p25": 0.9, "mediana": 1.1, "p75": 1.45, "percentil": 64 },
+///     "comensales_por_dia": { "propio": 52.0, "p25": 38.5, "mediana": 47.0, "p75": 61.2, "percentil": 57 },
+///     "tasa_cancelacion": { "propio": 0.08, "p25": 0.06, "mediana": 0.1, "p75": 0.12, "percentil": 29 },
+///     "tasa_no_show": { "propio": 0.02, "p25": 0.01, "mediana": 0.03, "p75": 0.05, "percentil": 36 }
+///   }
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida, `desde` posterior a `hasta` o rango
+///   de más de 366 días
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `403 Forbidden`: El restaurante no participa en la comparativa
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/stats/benchmark")]
+async fn benchmark_stats(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    query: web::Query<StatsQuery>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerEstadisticas>,
+) -> AppResult<impl Responder> {
+    let configuracion = &auth.restaurant.configuracion;
+    let ciudad = match configuracion.ciudad.as_ref().filter(|_| configuracion.benchmark) {
+        Some(ciudad) => ciudad.clone(),
+        None => return Err(AppError::forbidden(
+            "benchmark_desactivado",
+            "Activa `benchmark` en la configuración del restaurante para compararte con otros",
+        )),
+    };
+    let (desde, hasta) = date_range(&query, clock.get_ref())?;
+    let dias = (hasta - desde).num_days() as u64 + 1;
+    let desde = desde.format(FORMATO_FECHA).to_string();
+    let hasta = hasta.format(FORMATO_FECHA).to_string();
+    let restaurante_id = auth.id();
+
+    // Los demás participantes de la ciudad; solo se leen su ID y su ciudad
+    let candidatos = aggregate_by_restaurant(repo.restaurants().clone_with_type(), vec![
+        doc! { "$match": {
+            "configuracion.benchmark": true,
+            "estado": { "$ne": "suspendido" },
+            "_id": { "$ne": restaurante_id },
+        } },
+        doc! { "$project": { "ciudad": "$configuracion.ciudad" } },
+    ]).await?;
+    let ciudad_normalizada = normalize_name(&ciudad);
+    let mut ids: Vec<ObjectId> = candidatos.into_iter()
+        .filter(|(_, documento)| documento.get_str("ciudad").is_ok_and(|otra| normalize_name(otra) == ciudad_normalizada))
+        .map(|(id, _)| id)
+        .collect();
+    ids.push(restaurante_id);
+
+    let estadisticas = aggregate_by_restaurant(repo.estadisticas_diarias().clone_with_type(), vec![
+        doc! { "$match": { "id_restaurante": { "$in": &ids }, "fecha": { "$gte": &desde, "$lte": &hasta } } },
+        doc! { "$group": {
+            "_id": "$id_restaurante",
+            "reservas": { "$sum": "$reservas" },
+            "comensales": { "$sum": "$comensales" },
+            "cancelaciones": { "$sum": "$cancelaciones" },
+            "no_presentadas": { "$sum": "$no_presentadas" },
+        } },
+    ]).await?;
+    let plazas = aggregate_by_restaurant(repo.mesas().clone_with_type(), vec![
+        doc! { "$match": { "id_restaurante": { "$in": &ids }, "reservable": true } },
+        doc! { "$group": { "_id": "$id_restaurante", "plazas": { "$sum": { "$ifNull": ["$max_personas", 0] } } } },
+    ]).await?;
+
+    let metricas = |id: &ObjectId| {
+        let mut totales = Totales::default();
+        if let Some(documento) = estadisticas.get(id) {
+            totales.reservas = total(documento, "reservas");
+            totales.comensales = total(documento, "comensales");
+            totales.cancelaciones = total(documento, "cancelaciones");
+            totales.no_presentadas = total(documento, "no_presentadas");
+        }
+        totales.plazas = plazas.get(id).map(|documento| total(documento, "plazas")).unwrap_or(0);
+        (totales.reservas, Metricas::new(&totales, dias))
+    };
+    let propias = metricas(&restaurante_id).1;
+    let otras: Vec<Metricas> = ids.iter()
+        .filter(|id| **id != restaurante_id)
+        .map(metricas)
+        .filter(|(reservas, _)| *reservas >= benchmark::MIN_RESERVAS)
+        .map(|(_, metricas)| metricas)
+        .collect();
+
+    let ocupaciones: Vec<f64> = otras.iter().filter_map(|otra| otra.ocupacion).collect();
+    let valores = |cifra: fn(&Metricas) -> f64| otras.iter().map(cifra).collect::<Vec<f64>>();
+    let suficientes = otras.len() >= benchmark::MIN_PARTICIPANTES;
+    let comparativa = BenchmarkMetrics {
+        ocupacion: propias.ocupacion.and_then(|propia| benchmark::compare(propia, &ocupaciones)),
+        comensales_por_dia: benchmark::compare(propias.comensales_por_dia, &valores(|m| m.comensales_por_dia)),
+        tasa_cancelacion: benchmark::compare(propias.tasa_cancelacion, &valores(|m| m.tasa_cancelacion)),
+        tasa_no_show: benchmark::compare(propias.tasa_no_show, &valores(|m| m.tasa_no_show)),
+    };
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "desde": desde,
+        "hasta": hasta,
+        "ciudad": ciudad,
+        "suficientes_datos": suficientes,
+        "participantes": suficientes.then_some(otras.len()),
+        "metricas": comparativa
+    })))
+}
+
+/// Percentiles de las esperas de un día, o de todo el rango (sin `fecha`)
+#[derive(Serialize)]
+struct DayConfirmations {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    fecha: String,
+    #[serde(flatten)]
+    percentiles: Percentiles,
+}
+
+/// Tiempo que tarda el restaurante en confirmar las reservas pendientes
+///
+/// Percentiles, en minutos, de la espera de las reservas que el personal
+/// confirmó en el rango, desde que quedaron pendientes hasta que se
+/// confirmaron (ver [`crate::sla`]); por día de confirmación y en total.
+/// Solo aparecen los días con confirmaciones, y `totales` es `null` si no
+/// hay ninguna. Las reservas que se confirman solas o al pagar el depósito
+/// no cuentan.
+///
+/// Se calcula sobre las reservas, así que, a diferencia de
+/// `GET /stats/daily`, `hasta` puede ser hoy.
+///
+/// # Autenticación
+/// Requiere token Bearer válido del restaurante (permiso `Gestion`).
+///
+/// # Parámetros de query
+/// - `desde` (opcional): Primer día; por defecto, 30 días antes de `hasta`
+/// - `hasta` (opcional): Último día; por defecto, ayer
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "desde": "2030-05-02",
+///   "hasta": "2030-05-31",
+///   "dias": [
+///     { "fecha": "2030-05-30", "confirmadas": 9, "p50_minutos": 12, "p90_minutos": 95, "p95_minutos": 140, "max_minutos": 140 }
+///   ],
+///   "totales": { "confirmadas": 9, "p50_minutos": 12, "p90_minutos": 95, "p95_minutos": 140, "max_minutos": 140 }
+/// }
+/// ```
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha inválida, `desde` posterior a `hasta` o rango
+///   de más de 366 días
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/stats/confirmations")]
+async fn confirmation_stats(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    query: web::Query<StatsQuery>,
+    auth: AuthenticatedRestaurant<PermisoGestion, LeerEstadisticas>,
+) -> AppResult<impl Responder> {
+    let (desde, hasta) = date_range(&query, clock.get_ref())?;
+    let inicio = desde.and_time(NaiveTime::MIN).and_utc().timestamp();
+    let fin = (hasta + Duration::days(1)).and_time(NaiveTime::MIN).and_utc().timestamp();
+    let mut cursor = repo.reservas()
+        .find(doc! { "id_restaurante": auth.id(), "confirmada_en": { "$gte": inicio, "$lt": fin } })
+        .await
+        .map_err(|e| AppError::database("confirmation_stats", e))?;
+
+    let mut por_dia: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let reserva = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?;
+        let (Some(espera), Some(confirmada_en)) = (sla::espera_minutos(&reserva), reserva.confirmada_en) else {
+            continue;
+        };
+        let fecha = chrono::DateTime::from_timestamp(confirmada_en, 0).unwrap_or_default().format(FORMATO_FECHA).to_string();
+        por_dia.entry(fecha).or_default().push(espera);
+    }
+
+    let totales = sla::summarize(por_dia.values().flatten().copied().collect());
+    let dias: Vec<DayConfirmations> = por_dia.into_iter()
+        .filter_map(|(fecha, esperas)| sla::summarize(esperas).map(|percentiles| DayConfirmations { fecha, percentiles }))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "desde": desde.format(FORMATO_FECHA).to_string(),
+        "hasta": hasta.format(FORMATO_FECHA).to_string(),
+        "dias": dias,
+        "totales": totales.map(|percentiles| DayConfirmations { fecha: String::new(), percentiles })
+    })))
+}
+
+/// Configura las rutas de estadísticas
+///
+/// # Rutas disponibles
+/// - `GET /stats/daily` - Estadísticas diarias en un rango de fechas
+/// - `GET /stats/benchmark` - Comparativa con otros restaurantes de la ciudad
+/// - `GET /stats/confirmations` - Tiempo de confirmación de las reservas pendientes
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(daily_stats);
+    cfg.service(benchmark_stats);
+    cfg.service(confirmation_stats);
+}
@@ -0,0 +1,194 @@
+//! # Estado del servicio
+//!
+//! `GET /public/status` resume, para la página de estado pública, si el
+//! servicio está funcionando. Cada componente sale de las mismas
+//! comprobaciones internas que el monitor de alertas
+//! ([`crate::jobs::alerts`]):
+//!
+//! - `api`: Siempre operativa si responde
+//! - `base_datos`: Ping a MongoDB; degradada por encima de
+//!   `ALERTA_LATENCIA_BD_MS` (default: 1000 ms), caída si no responde
+//! - `pagos`: Degradado si el webhook de pagos falla desde hace
+//!   `ALERTA_WEBHOOK_MINUTOS` (default: 5 minutos)
+//! - `trabajos`: Degradado si la última ejecución de algún trabajo
+//!   programado falló (ver [`crate::jobs::last_runs`])
+//!
+//! El aviso de mantenimiento y la incidencia declarada los pone la
+//! administración con `PUT /admin/status`. Las comprobaciones son del proceso
+//! que responde: con varias instancias, cada una da las suyas.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use actix_web::{get, web, HttpResponse, Responder};
+use mongodb::bson::doc;
+use serde::Serialize;
+use serde_json::json;
+use super::AppResult;
+use crate::clock::Clock;
+use crate::db::MongoRepo;
+use crate::jobs::{self, alerts};
+
+/// `_id` del documento de [`crate::db::EstadoPlataforma`]
+pub const ID_ESTADO_PLATAFORMA: &str = "plataforma";
+
+/// Latencia de MongoDB a partir de la que se considera degradada, si no se
+/// configura `ALERTA_LATENCIA_BD_MS`
+const LATENCIA_DEGRADADA_MS: u64 = 1000;
+
+/// Minutos de fallos del webhook de pagos para considerarlo degradado, si no
+/// se configura `ALERTA_WEBHOOK_MINUTOS`
+const MINUTOS_WEBHOOK_DEGRADADO: i64 = 5;
+
+/// Tiempo máximo de espera a MongoDB antes de darla por caída
+const ESPERA_MAXIMA_BD: Duration = Duration::from_secs(2);
+
+/// Salud de un componente, de mejor a peor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Salud {
+    Operativo,
+    Degradado,
+    Caido,
+}
+
+impl Salud {
+    /// Nombre con el que se publica
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Salud::Operativo => "operativo",
+            Salud::Degradado => "degradado",
+            Salud::Caido => "caido",
+        }
+    }
+}
+
+/// Estado general del servicio: `mantenimiento` durante el mantenimiento y,
+/// si no, el del peor componente
+///
+/// ```
+/// use pispas_reservation::api::status::{overall, Salud};
+///
+/// assert_eq!(overall(false, &[Salud::Operativo, Salud::Operativo]), "operativo");
+/// assert_eq!(overall(false, &[Salud::Operativo, Salud::Degradado]), "degradado");
+/// assert_eq!(overall(false, &[Salud::Caido, Salud::Degradado]), "caido");
+/// assert_eq!(overall(true, &[Salud::Caido]), "mantenimiento");
+/// ```
+pub fn overall(mantenimiento: bool, componentes: &[Salud]) -> &'static str {
+    if mantenimiento {
+        return "mantenimiento";
+    }
+    componentes.iter().max().copied().unwrap_or(Salud::Operativo).as_str()
+}
+
+/// Momento en que se montaron las rutas, para el tiempo en marcha
+fn inicio() -> &'static Instant {
+    static INICIO: OnceLock<Instant> = OnceLock::new();
+    INICIO.get_or_init(Instant::now)
+}
+
+/// Estado del servicio para la página de estado
+///
+/// La `incidencia` está activa si la administración ha declarado una
+/// (`mensaje` es su descripción) o si algún componente está caído. La
+/// respuesta no se guarda en caché (`Cache-Control: no-store`) y siempre es
+/// `200 OK`, aunque el servicio esté degradado.
+///
+/// # Autenticación
+/// Ninguna.
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "estado": "degradado",
+///   "mantenimiento": false,
+///   "incidencia": true,
+///   "mensaje": "Retrasos en los pagos con tarjeta",
+///   "activo_desde": 1780000000,
+///   "uptime_segundos": 86400,
+///   "componentes": {
+///     "api": { "estado": "operativo" },
+///     "base_datos": { "estado": "operativo", "latencia_ms": 3 },
+///     "pagos": { "estado": "degradado" },
+///     "trabajos": { "estado": "operativo" }
+///   },
+///   "actualizado_en": 1780086400
+/// }
+/// ```
+///
+/// # Errores
+/// - `429 Too Many Requests`: Límite de peticiones públicas superado
+#[get("/public/status")]
+async fn public_status(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+) -> AppResult<impl Responder> {
+    let now = clock.timestamp();
+    let monitor = alerts::ConfigMonitor::from_env();
+
+    let comienzo = Instant::now();
+    let ping = tokio::time::timeout(ESPERA_MAXIMA_BD, repo.ping()).await;
+    let latencia_ms = comienzo.elapsed().as_millis() as u64;
+    let base_datos = match ping {
+        Ok(Ok(())) if latencia_ms > monitor.latencia_bd_ms.unwrap_or(LATENCIA_DEGRADADA_MS) => Salud::Degradado,
+        Ok(Ok(())) => Salud::Operativo,
+        Ok(Err(_)) | Err(_) => Salud::Caido,
+    };
+
+    let minutos_webhook = monitor.webhook_minutos.unwrap_or(MINUTOS_WEBHOOK_DEGRADADO);
+    let pagos = match alerts::webhook_failing_since() {
+        Some(desde) if now - desde >= minutos_webhook * 60 => Salud::Degradado,
+        _ => Salud::Operativo,
+    };
+
+    let trabajos = if jobs::last_runs().iter().all(|ejecucion| ejecucion.ok) {
+        Salud::Operativo
+    } else {
+        Salud::Degradado
+    };
+
+    // Sin base de datos no se sabe si hay mantenimiento
+    let plataforma = if base_datos == Salud::Caido {
+        None
+    } else {
+        tokio::time::timeout(ESPERA_MAXIMA_BD, repo.estado_plataforma().find_one(doc! { "_id": ID_ESTADO_PLATAFORMA }))
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .flatten()
+    };
+    let mantenimiento = plataforma.as_ref().is_some_and(|plataforma| plataforma.mantenimiento);
+    let mensaje = plataforma.and_then(|plataforma| plataforma.incidencia);
+
+    let componentes = [Salud::Operativo, base_datos, pagos, trabajos];
+    let uptime = inicio().elapsed().as_secs() as i64;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(json!({
+            "estado": overall(mantenimiento, &componentes),
+            "mantenimiento": mantenimiento,
+            "incidencia": mensaje.is_some() || componentes.contains(&Salud::Caido),
+            "mensaje": mensaje,
+            "activo_desde": now - uptime,
+            "uptime_segundos": uptime,
+            "componentes": {
+                "api": { "estado": Salud::Operativo },
+                "base_datos": {
+                    "estado": base_datos,
+                    "latencia_ms": (base_datos != Salud::Caido).then_some(latencia_ms)
+                },
+                "pagos": { "estado": pagos },
+                "trabajos": { "estado": trabajos }
+            },
+            "actualizado_en": now
+        })))
+}
+
+/// Configura la ruta del estado del servicio
+///
+/// # Rutas disponibles
+/// - `GET /public/status` - Estado del servicio para la página de estado
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    inicio();
+    cfg.service(public_status);
+}
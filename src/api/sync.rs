@@ -0,0 +1,454 @@
+//! # Sincronización de las apps sin conexión
+//!
+//! Las tablets del mostrador siguen trabajando sin conexión y, al
+//! recuperarla, envían en un lote los cambios que han hecho con
+//! `POST /sync/push`. Cada cambio (*mutación*) se aplica en orden y recibe su
+//! propio resultado, de forma que un cambio rechazado no impide aplicar los
+//! demás.
+//!
+//! ## Mutaciones
+//!
+//! - `crear_reserva`: los mismos datos que `POST /reservations`, con el
+//!   `uuid` que generó la app
+//! - `actualizar_reserva`: cambia los datos de contacto del cliente o el
+//!   estado (`confirmada` o `cancelada`) de una reserva identificada por su
+//!   `uuid` o su `id`. Lleva la `version` de la reserva que conocía la app (su
+//!   `updated_at`) y `modificado_en`, el momento del cambio en la app
+//!
+//! Las mesas se sincronizan con `POST /tables` y su `uuid`, y los cambios de
+//! fecha, hora o mesa de una reserva con los endpoints del panel.
+//!
+//! ## Resolución de conflictos
+//!
+//! 1. Una creación cuyo `uuid` ya existe no crea nada: resultado `existente`
+//! 2. Una creación que no pasa las validaciones (mesa ocupada, fuera de
+//!    capacidad...) se rechaza: resultado `rechazada`, con el error
+//! 3. Una reserva cancelada en el servidor no vuelve a activarse: cualquier
+//!    cambio sobre ella es un `conflicto` (salvo otra cancelación, que no
+//!    cambia nada). Lo mismo con las completadas o con el cliente que no se
+//!    presentó, y no se cancela una reserva con el cliente ya sentado
+//! 4. Cualquier otra cancelación de la app se aplica siempre
+//! 5. Si la reserva no ha cambiado desde la `version` de la app, el cambio
+//!    se aplica
+//! 6. Si ha cambiado, gana el cambio más reciente: se aplica si
+//!    `modificado_en` es posterior al último cambio en el servidor y si no es
+//!    un `conflicto`. Un `modificado_en` en el futuro cuenta como ahora
+//!
+//! Los conflictos devuelven la copia del servidor para que la app la
+//! muestre. Los resultados aplicados devuelven la nueva `version`.
+//!
+//! Reintentar un lote completo es seguro: las creaciones se reconocen por su
+//! `uuid` y las actualizaciones ya aplicadas no se repiten.
+
+use std::future::Future;
+use actix_web::{post, web, HttpResponse, Responder, ResponseError};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use super::{AppError, AppResult};
+use super::audit::{self, AccionAuditoria, Autor};
+use super::auth::{AuthenticatedRestaurant, EscribirReservas, PermisoReservas};
+use super::customer::{discount_visit, learn_preference};
+use super::reservation::{check_cancellation, create_reservation, find_by_uuid, validate_email, validate_uuid, MakeReservation, ReservationResponse, PAGO_PENDIENTE};
+use crate::clock::Clock;
+use crate::db::{EstadoReserva, MongoRepo, Reserva, Restaurant};
+use crate::events::{self, TipoEvento};
+
+/// Máximo de mutaciones por lote
+const MAX_MUTACIONES: usize = 100;
+
+/// Mutación hecha sin conexión
+#[derive(Deserialize)]
+#[serde(tag = "tipo", rename_all = "snake_case")]
+enum Mutacion {
+    CrearReserva(MakeReservation),
+    ActualizarReserva(CambiosReserva),
+}
+
+/// Cambios sobre una reserva existente
+#[derive(Deserialize)]
+struct CambiosReserva {
+    /// UUID de la app (si la creó ella) o ID de la reserva
+    uuid: Option<String>,
+    id: Option<String>,
+    /// `updated_at` de la reserva que conocía la app
+    version: i64,
+    /// Momento del cambio en la app (timestamp unix)
+    modificado_en: i64,
+    nombre_cliente: Option<String>,
+    email_cliente: Option<String>,
+    telefono_cliente: Option<String>,
+    /// Nuevo estado: "confirmada" o "cancelada"
+    estado: Option<String>,
+}
+
+/// Lote de mutaciones
+#[derive(Deserialize)]
+struct PushRequest {
+    mutaciones: Vec<Value>,
+}
+
+/// Resultado de una mutación
+#[derive(Serialize)]
+struct ResultadoMutacion {
+    /// Posición de la mutación en el lote
+    indice: usize,
+    /// "creada", "existente", "aplicada", "conflicto" o "rechazada"
+    resultado: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uuid: Option<String>,
+    /// `updated_at` de la reserva tras la mutación
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<i64>,
+    /// Copia del servidor, en los conflictos
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reserva: Option<ReservationResponse>,
+    /// Código HTTP y mensaje del error, en las rechazadas
+    #[serde(skip_serializing_if = "Option::is_none")]
+    codigo_http: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ResultadoMutacion {
+    fn new(indice: usize, resultado: &'static str, reserva: &Reserva) -> Self {
+        ResultadoMutacion {
+            indice,
+            resultado,
+            id: reserva.id.map(|id| id.to_hex()),
+            uuid: reserva.uuid.clone(),
+            version: Some(reserva.updated_at),
+            reserva: None,
+            codigo_http: None,
+            error: None,
+        }
+    }
+
+    fn conflict(indice: usize, reserva: Reserva) -> Self {
+        ResultadoMutacion {
+            reserva: Some(ReservationResponse::from(reserva.clone())),
+            ..ResultadoMutacion::new(indice, "conflicto", &reserva)
+        }
+    }
+
+    fn rejected(indice: usize, error: &AppError) -> Self {
+        ResultadoMutacion {
+            indice,
+            resultado: "rechazada",
+            id: None,
+            uuid: None,
+            version: None,
+            reserva: None,
+            codigo_http: Some(error.status_code().as_u16()),
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// Qué hacer con un cambio de la app sobre una reserva
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolucion {
+    /// Aplicar el cambio
+    Aplicar,
+    /// El cambio ya está aplicado (cancelar una reserva cancelada)
+    SinCambios,
+    /// La copia del servidor gana
+    Conflicto,
+}
+
+/// Aplica las reglas de resolución de conflictos (ver la documentación del módulo)
+///
+/// - `estado`: estado de la reserva en el servidor
+/// - `actualizada`: `updated_at` de la reserva en el servidor
+/// - `version`: `updated_at` que conocía la app
+/// - `modificado_en`: momento del cambio en la app, ya limitado a ahora
+/// - `cancela`: si el cambio cancela la reserva
+///
+/// ```
+/// use pispas_reservation::api::sync::{resolve, Resolucion};
+/// use pispas_reservation::db::EstadoReserva::*;
+///
+/// // Sin cambios en el servidor desde la versión de la app
+/// assert_eq!(resolve(Pendiente, 100, 100, 50, false), Resolucion::Aplicar);
+/// // Cambiada después en el servidor: gana el cambio más reciente
+/// assert_eq!(resolve(Pendiente, 200, 100, 150, false), Resolucion::Conflicto);
+/// assert_eq!(resolve(Pendiente, 200, 100, 250, false), Resolucion::Aplicar);
+/// // Las cancelaciones de la app siempre se aplican
+/// assert_eq!(resolve(Confirmada, 200, 100, 150, true), Resolucion::Aplicar);
+/// // Y las del servidor nunca se deshacen
+/// assert_eq!(resolve(Cancelada, 100, 100, 250, false), Resolucion::Conflicto);
+/// assert_eq!(resolve(Cancelada, 100, 100, 250, true), Resolucion::SinCambios);
+/// // Las reservas en curso o terminadas ya no se cancelan
+/// assert_eq!(resolve(Sentada, 100, 100, 250, true), Resolucion::Conflicto);
+/// assert_eq!(resolve(NoShow, 100, 100, 250, false), Resolucion::Conflicto);
+/// ```
+pub fn resolve(estado: EstadoReserva, actualizada: i64, version: i64, modificado_en: i64, cancela: bool) -> Resolucion {
+    if estado == EstadoReserva::Cancelada {
+        return if cancela { Resolucion::SinCambios } else { Resolucion::Conflicto };
+    }
+    if estado.is_final() || (cancela && !estado.can_transition_to(EstadoReserva::Cancelada)) {
+        return Resolucion::Conflicto;
+    }
+    if cancela || version == actualizada || modificado_en > actualizada {
+        Resolucion::Aplicar
+    } else {
+        Resolucion::Conflicto
+    }
+}
+
+/// Valida los cambios y los convierte en el `$set` de la reserva
+///
+/// # Errores
+/// - `Validation`: Campos vacíos, email inválido o estado no admitido
+fn changes_document(cambios: &CambiosReserva) -> AppResult<Document> {
+    let mut set = Document::new();
+    if let Some(nombre) = &cambios.nombre_cliente {
+        if nombre.trim().is_empty() {
+            return Err(AppError::validation_field("nombre_cliente", "El nombre del cliente es requerido"));
+        }
+        set.insert("nombre_cliente", nombre.trim());
+    }
+    if let Some(email) = &cambios.email_cliente {
+        if !validate_email(email) {
+            return Err(AppError::validation_field("email_cliente", "Email inválido"));
+        }
+        set.insert("email_cliente", email.trim());
+    }
+    if let Some(telefono) = &cambios.telefono_cliente {
+        if telefono.trim().is_empty() {
+            return Err(AppError::validation_field("telefono_cliente", "El teléfono del cliente es requerido"));
+        }
+        set.insert("telefono_cliente", telefono.trim());
+    }
+    if let Some(estado) = &cambios.estado {
+        if estado != "confirmada" && estado != "cancelada" {
+            return Err(AppError::validation_field("estado", "Solo se puede confirmar o cancelar"));
+        }
+        set.insert("estado", estado);
+    }
+    Ok(set)
+}
+
+/// Busca la reserva de una actualización por su UUID o su ID
+async fn find_target(repo: &MongoRepo, restaurante_id: ObjectId, cambios: &CambiosReserva) -> AppResult<Reserva> {
+    let reserva = match (&cambios.uuid, &cambios.id) {
+        (Some(uuid), _) => find_by_uuid(repo, restaurante_id, &validate_uuid(uuid)?).await?,
+        (None, Some(id)) => {
+            let id = ObjectId::parse_str(id)
+                .map_err(|_| AppError::Validation("ID de reserva inválido".to_string()))?;
+            repo.reservas()
+                .find_one(doc! { "_id": id, "id_restaurante": restaurante_id })
+                .await
+                .map_err(|e| AppError::database("sync_find_reservation", e))?
+        }
+        (None, None) => return Err(AppError::Validation("Falta el uuid o el id de la reserva".to_string())),
+    };
+    reserva.ok_or(AppError::NotFound("Reserva no encontrada".to_string()))
+}
+
+/// Aplica una actualización según las reglas de resolución de conflictos
+async fn apply_update(
+    repo: &MongoRepo,
+    autor: &Autor,
+    restaurant: &Restaurant,
+    indice: usize,
+    cambios: CambiosReserva,
+    now: i64,
+) -> AppResult<ResultadoMutacion> {
+    let mut set = changes_document(&cambios)?;
+    let reserva = find_target(repo, restaurant.id.unwrap(), &cambios).await?;
+    let id = reserva.id.unwrap();
+    let nuevo_estado = cambios.estado
+        .as_deref()
+        .and_then(EstadoReserva::parse)
+        .filter(|estado| *estado != reserva.estado);
+    let cancela = nuevo_estado == Some(EstadoReserva::Cancelada);
+
+    match resolve(reserva.estado, reserva.updated_at, cambios.version, cambios.modificado_en.min(now), cancela) {
+        Resolucion::Conflicto => return Ok(ResultadoMutacion::conflict(indice, reserva)),
+        Resolucion::SinCambios => return Ok(ResultadoMutacion::new(indice, "aplicada", &reserva)),
+        Resolucion::Aplicar => {}
+    }
+
+    // El plazo de cancelación cuenta hasta el momento del cambio en la app
+    if cancela && check_cancellation(restaurant, &reserva, cambios.modificado_en.min(now))? {
+        set.insert("cancelacion_tardia", true);
+    }
+    if nuevo_estado == Some(EstadoReserva::Confirmada) {
+        if reserva.estado != EstadoReserva::Pendiente {
+            return Err(AppError::Conflict(format!("No se puede confirmar una reserva {}", reserva.estado)));
+        }
+        if reserva.pago_pendiente() {
+            return Err(AppError::Conflict(PAGO_PENDIENTE.to_string()));
+        }
+        set.insert("confirmada_en", now);
+    }
+
+    // Solo si nadie la ha cambiado desde que la hemos leído
+    set.insert("updated_at", now);
+    let actualizada = repo.reservas()
+        .find_one_and_update(
+            doc! { "_id": id, "updated_at": reserva.updated_at },
+            doc! { "$set": set },
+        )
+        .return_document(mongodb::options::ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("sync_update_reservation", e))?;
+    let Some(actualizada) = actualizada else {
+        let actual = find_target(repo, restaurant.id.unwrap(), &cambios).await?;
+        return Ok(ResultadoMutacion::conflict(indice, actual));
+    };
+
+    let (accion, evento) = match nuevo_estado {
+        Some(EstadoReserva::Confirmada) => (AccionAuditoria::ConfirmarReserva, Some(TipoEvento::ReservaConfirmada)),
+        Some(EstadoReserva::Cancelada) => (AccionAuditoria::CancelarReserva, Some(TipoEvento::ReservaCancelada)),
+        _ => (AccionAuditoria::ModificarReserva, None),
+    };
+    if let Some(evento) = evento {
+        let datos = doc! { "estado_anterior": reserva.estado, "origen": "sync" };
+        events::record(repo, evento, restaurant.id, Some(id), datos, now).await;
+    }
+    audit::record(repo, autor, accion, Some(id), audit::snapshot(&reserva), audit::snapshot(&actualizada), now).await;
+    if let (true, Some(id_cliente)) = (cancela, actualizada.id_cliente) {
+        discount_visit(repo, id_cliente).await?;
+        learn_preference(repo, id_cliente).await;
+    }
+
+    Ok(ResultadoMutacion::new(indice, "aplicada", &actualizada))
+}
+
+/// Aplica una mutación del lote
+async fn apply(
+    repo: &MongoRepo,
+    autor: &Autor,
+    restaurant: &Restaurant,
+    indice: usize,
+    mutacion: Value,
+    now: i64,
+) -> AppResult<ResultadoMutacion> {
+    let mutacion = serde_json::from_value::<Mutacion>(mutacion)
+        .map_err(|e| AppError::Validation(format!("Mutación inválida: {}", e)))?;
+
+    match mutacion {
+        Mutacion::CrearReserva(datos) => {
+            if datos.uuid.is_none() {
+                return Err(AppError::validation_field("uuid", "Las reservas sincronizadas necesitan un uuid"));
+            }
+            let (reserva, creada) = create_reservation(repo, restaurant, autor, &datos, now).await?;
+            Ok(ResultadoMutacion::new(indice, if creada { "creada" } else { "existente" }, &reserva))
+        }
+        Mutacion::ActualizarReserva(cambios) => apply_update(repo, autor, restaurant, indice, cambios, now).await,
+    }
+}
+
+/// Aplica un lote de cambios hechos sin conexión
+///
+/// Las mutaciones se aplican en orden; ver las reglas de resolución de
+/// conflictos en la documentación del módulo.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Cuerpo
+/// ```json
+/// {
+///   "mutaciones": [
+///     {
+///       "tipo": "crear_reserva",
+///       "uuid": "3f2b8c1e-6d4a-4f7b-9a0e-2c5d8e1f4a6b",
+///       "id_mesa": "507f1f77bcf86cd799439011",
+///       "nombre_cliente": "Juan Pérez",
+///       "email_cliente": "juan@email.com",
+///       "telefono_cliente": "+34 600 000 000",
+///       "numero_personas": 2,
+///       "fecha": "2030-06-15",
+///       "hora": "21:00"
+///     },
+///     {
+///       "tipo": "actualizar_reserva",
+///       "uuid": "3f2b8c1e-6d4a-4f7b-9a0e-2c5d8e1f4a6b",
+///       "version": 1718000000,
+///       "modificado_en": 1718000300,
+///       "estado": "confirmada"
+///     }
+///   ]
+/// }
+/// ```
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "resultados": [
+///     { "indice": 0, "resultado": "creada", "id": "507f1f77bcf86cd799439021", "uuid": "3f2b8c1e-...", "version": 1718000400 },
+///     { "indice": 1, "resultado": "aplicada", "id": "507f1f77bcf86cd799439021", "uuid": "3f2b8c1e-...", "version": 1718000400 }
+///   ]
+/// }
+/// ```
+///
+/// Las mutaciones rechazadas llevan `codigo_http` y `error`, y los conflictos
+/// la copia del servidor en `reserva`.
+///
+/// # Errores
+/// - `400 Bad Request`: Más de 100 mutaciones en el lote
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos (las mutaciones
+///   anteriores quedan aplicadas; el lote se puede reintentar)
+#[post("/sync/push")]
+async fn push(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<PushRequest>,
+    auth: AuthenticatedRestaurant<PermisoReservas, EscribirReservas>,
+) -> AppResult<impl Responder> {
+    if data.mutaciones.len() > MAX_MUTACIONES {
+        return Err(AppError::validation_field("mutaciones", &format!(
+            "Un lote admite como máximo {} mutaciones", MAX_MUTACIONES
+        )));
+    }
+
+    let autor = Autor::from(&auth);
+    let now = clock.timestamp();
+    let resultados: Vec<ResultadoMutacion> = apply_batch(data.into_inner().mutaciones, |indice, mutacion| {
+        apply(repo.get_ref(), &autor, &auth.restaurant, indice, mutacion, now)
+    })
+        .await?
+        .into_iter()
+        .enumerate()
+        .map(|(indice, resultado)| resultado.unwrap_or_else(|error| ResultadoMutacion::rejected(indice, &error)))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "resultados": resultados })))
+}
+
+/// Aplica en orden las mutaciones de un lote con `aplicar`
+///
+/// Una mutación rechazada (un error `4xx`) queda con su error y el lote
+/// sigue con las demás; un error del servidor interrumpe el lote, con las
+/// anteriores ya aplicadas.
+///
+/// # Retorna
+/// El resultado de cada mutación, en el orden del lote
+pub async fn apply_batch<M, T, F, Fut>(mutaciones: Vec<M>, mut aplicar: F) -> AppResult<Vec<AppResult<T>>>
+where
+    F: FnMut(usize, M) -> Fut,
+    Fut: Future<Output = AppResult<T>>,
+{
+    let mut resultados = Vec::new();
+    for (indice, mutacion) in mutaciones.into_iter().enumerate() {
+        match aplicar(indice, mutacion).await {
+            Err(error) if error.status_code().is_server_error() => return Err(error),
+            resultado => resultados.push(resultado),
+        }
+    }
+    Ok(resultados)
+}
+
+/// Configura las rutas de sincronización
+///
+/// # Rutas disponibles
+/// - `POST /sync/push` - Aplica un lote de cambios hechos sin conexión
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(push);
+}
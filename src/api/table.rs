@@ -3,16 +3,35 @@
 //! Este módulo maneja todas las operaciones relacionadas con mesas:
 //! - Crear nuevas mesas en el plano del restaurante
 //! - Listar mesas de un restaurante
-//! - Eliminar todas las mesas de un restaurante (clear)
+//! - Eliminar todas las mesas de un restaurante (clear), con confirmación y
+//!   la posibilidad de deshacerlo (ver [`super::recycle_bin`])
+//! - Buscar mesas disponibles para una fecha, hora y número de personas
+//! - Elegir la mejor mesa libre para una reserva (`POST /reservations/auto-assign`)
+//!
+//! En restaurantes con varias plantas (ver [`super::floor`]) cada mesa
+//! pertenece a una; el parámetro `planta` limita las operaciones a las
+//! mesas de esa planta.
 //!
 //! Todas las operaciones requieren autenticación mediante token Bearer.
 
-use actix_web::{get, post, delete, web, HttpResponse, Responder, HttpRequest};
+use actix_web::{get, post, delete, web, HttpResponse, Responder};
+use actix_web::http::StatusCode;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use mongodb::bson::{doc, oid::ObjectId};
 use super::{AppError, AppResult};
-use super::restaurant::validate_access_token;
-use crate::db::{MongoRepo, Mesa};
+use super::fields::CamposRespuesta;
+use super::customer::find_by_contact;
+use super::floor::resolve_floor;
+use super::layout::load_active_layout;
+use super::audit::{self, AccionAuditoria, Autor};
+use super::recycle_bin;
+use super::auth::{AuthenticatedRestaurant, EscribirMesas, LeerMesas, PermisoGestion, PermisoReservas};
+use super::reservation::{load_ocupaciones, validate_date, validate_time, validate_uuid};
+use super::slot_rules::load_rules;
+use crate::availability::{self, CapacidadMesa, Ocupacion};
+use crate::clock::Clock;
+use crate::db::{is_duplicate_key, normalize_name, MongoRepo, Mesa, Restaurant};
 
 /// Estructura para crear una nueva mesa
 ///
@@ -42,6 +61,10 @@ struct NewTable {
     min_personas: Option<i32>,
     /// Número máximo de personas (opcional)
     max_personas: Option<i32>,
+    /// Planta de la mesa (opcional, por defecto la principal)
+    id_planta: Option<String>,
+    /// UUID generado por el cliente para crearla sin conexión (opcional)
+    uuid: Option<String>,
 }
 
 /// Estructura de respuesta para una mesa
@@ -74,6 +97,11 @@ struct MesaResponse {
     min_personas: Option<i32>,
     /// Número máximo de personas
     max_personas: Option<i32>,
+    /// Planta de la mesa (`null` para la principal)
+    id_planta: Option<String>,
+    /// UUID del cliente con el que se creó, si lo tiene
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uuid: Option<String>,
 }
 
 /// Parámetros de consulta para operaciones con mesas
@@ -81,32 +109,54 @@ struct MesaResponse {
 struct QueryParams {
     /// ID del restaurante
     id_restaurante: String,
+    /// Limitar a las mesas de una planta
+    planta: Option<String>,
 }
 
-/// Extrae el token Bearer del header Authorization
-///
-/// # Parámetros
-/// - `req`: Request HTTP que contiene los headers
-///
-/// # Retorna
-/// El token extraído sin el prefijo "Bearer "
-///
-/// # Errores
-/// - `Unauthorized`: Si falta el header, es inválido o no tiene el formato correcto
-fn extract_token(req: &HttpRequest) -> AppResult<String> {
-    let auth_header = req.headers()
-        .get("authorization")
-        .ok_or(AppError::Unauthorized("Falta header Authorization".to_string()))?;
-
-    let auth_str = auth_header
-        .to_str()
-        .map_err(|_| AppError::Unauthorized("Header Authorization inválido".to_string()))?;
-
-    if !auth_str.starts_with("Bearer ") {
-        return Err(AppError::Unauthorized("Formato de token inválido".to_string()));
-    }
+/// Parámetros de consulta para vaciar el plano
+#[derive(Deserialize)]
+struct ClearQuery {
+    /// ID del restaurante
+    id_restaurante: String,
+    /// Limitar a las mesas de una planta
+    planta: Option<String>,
+    /// Token de confirmación devuelto por la primera llamada
+    confirmacion: Option<String>,
+}
 
-    Ok(auth_str[7..].to_string())
+/// Parámetros de consulta para buscar mesas disponibles
+#[derive(Deserialize)]
+struct AvailabilityQuery {
+    /// Fecha de la reserva (formato YYYY-MM-DD)
+    fecha: String,
+    /// Hora de la reserva (formato HH:MM)
+    hora: String,
+    /// Número de comensales
+    personas: i32,
+    /// Email del cliente, para poner primero su mesa preferida
+    email: Option<String>,
+    /// Teléfono del cliente, si no hay email
+    telefono: Option<String>,
+    /// Limitar a las mesas de una planta
+    planta: Option<String>,
+}
+
+/// Cuerpo de la asignación automática de mesa
+#[derive(Deserialize)]
+struct AutoAssign {
+    /// Fecha de la reserva (formato YYYY-MM-DD)
+    fecha: String,
+    /// Hora de la reserva (formato HH:MM)
+    hora: String,
+    /// Número de comensales
+    numero_personas: i32,
+    /// Plantas preferidas, de más a menos; las demás mesas van detrás
+    #[serde(default)]
+    plantas: Vec<String>,
+    /// Email del cliente, para preferir su mesa habitual
+    email_cliente: Option<String>,
+    /// Teléfono del cliente, si no hay email
+    telefono_cliente: Option<String>,
 }
 
 /// Convierte un modelo Mesa interno a la respuesta del API
@@ -125,42 +175,63 @@ impl From<Mesa> for MesaResponse {
             reservable: mesa.reservable,
             min_personas: mesa.min_personas,
             max_personas: mesa.max_personas,
+            id_planta: mesa.id_planta.map(|id| id.to_hex()),
+            uuid: mesa.uuid,
         }
     }
 }
 
 /// Elimina todas las mesas de un restaurante
 ///
-/// **⚠️ Operación destructiva**: Esta función elimina permanentemente todas las mesas
-/// del restaurante especificado.
+/// **⚠️ Operación destructiva**: Esta función elimina todas las mesas del
+/// restaurante especificado. Se hace en dos pasos (ver
+/// [`super::recycle_bin`]): sin `confirmacion` no se borra nada y se
+/// responde `428` con el token que hay que enviar en la segunda llamada.
+/// Las mesas borradas se pueden restaurar con `POST /tables/undo-last`
+/// hasta `deshacer_hasta`.
 ///
 /// # Autenticación
 /// Requiere token Bearer válido del restaurante propietario.
 ///
 /// # Parámetros
 /// - `repo`: Repositorio MongoDB
-/// - `query`: ID del restaurante
+/// - `query`: ID del restaurante, opcionalmente la `planta` a vaciar y el
+///   token de `confirmacion`
 /// - `req`: Request HTTP con el token de autorización
 ///
 /// # Respuesta
+/// Sin `confirmacion` (`428 Precondition Required`):
 /// ```json
 /// {
-///   "message": "Se eliminaron 5 mesas correctamente"
+///   "message": "Se van a eliminar 5 mesas; repite la petición con la confirmación",
+///   "mesas": 5,
+///   "confirmacion": "9b2f4c0e8d7a4b1f9e6c3a5d2b8f0e1c",
+///   "expires_at": 1718000300
+/// }
+/// ```
+///
+/// Con `confirmacion`:
+/// ```json
+/// {
+///   "message": "Se eliminaron 5 mesas correctamente",
+///   "deshacer_hasta": 1718000900
 /// }
 /// ```
 ///
 /// # Errores
+/// - `400 Bad Request`: Confirmación inválida, ya usada o caducada
 /// - `401 Unauthorized`: Token inválido o falta autorización
 /// - `403 Forbidden`: No tienes permiso para modificar este restaurante
+/// - `404 Not Found`: Planta no encontrada
 /// - `500 Internal Server Error`: Error de base de datos
 #[delete("/tables/clear")]
 async fn clear_tables(
     repo: web::Data<MongoRepo>,
-    query: web::Query<QueryParams>,
-    req: HttpRequest,
+    clock: web::Data<dyn Clock>,
+    query: web::Query<ClearQuery>,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
 ) -> AppResult<impl Responder> {
-    let token = extract_token(&req)?;
-    let user_id = validate_access_token(repo.get_ref(), &token).await?;
+    let user_id = auth.id();
 
     let id_restaurante = ObjectId::parse_str(&query.id_restaurante)
         .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
@@ -170,14 +241,66 @@ async fn clear_tables(
         return Err(AppError::Unauthorized("No tienes permiso para modificar este restaurante".to_string()));
     }
 
+    let mut filtro = doc! { "id_restaurante": id_restaurante };
+    let planta = resolve_floor(repo.get_ref(), id_restaurante, query.planta.as_deref()).await?;
+    if let Some(planta) = planta {
+        filtro.insert("id_planta", planta);
+    }
+
+    let now = clock.timestamp();
+    let Some(token) = &query.confirmacion else {
+        let total = repo.mesas()
+            .count_documents(filtro)
+            .await
+            .map_err(|e| AppError::database("clear_tables", e))?;
+        let confirmacion = recycle_bin::request_confirmation(
+            repo.get_ref(),
+            id_restaurante,
+            recycle_bin::VACIAR_MESAS,
+            planta,
+            now,
+        ).await?;
+        return Ok(HttpResponse::build(StatusCode::PRECONDITION_REQUIRED).json(serde_json::json!({
+            "message": format!("Se van a eliminar {} mesas; repite la petición con la confirmación", total),
+            "mesas": total,
+            "confirmacion": confirmacion.token,
+            "expires_at": confirmacion.expires_at
+        })));
+    };
+    recycle_bin::consume_confirmation(
+        repo.get_ref(),
+        id_restaurante,
+        recycle_bin::VACIAR_MESAS,
+        planta,
+        token,
+        now,
+    ).await?;
+
     let mesas = repo.mesas();
+    let mut cursor = mesas
+        .find(filtro.clone())
+        .await
+        .map_err(|e| AppError::database("clear_tables", e))?;
+    let mut borradas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("clear_tables", e))? {
+        borradas.push(cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando mesa: {}", e)))?);
+    }
+
     let result = mesas
-        .delete_many(doc! { "id_restaurante": id_restaurante })
+        .delete_many(filtro)
         .await
         .map_err(|e| AppError::Internal(format!("Error eliminando mesas: {}", e)))?;
 
+    let autor = Autor::from(&auth);
+    for mesa in &borradas {
+        audit::record(repo.get_ref(), &autor, AccionAuditoria::BorrarMesa, mesa.id, audit::snapshot(mesa), None, now).await;
+    }
+    let deshacer_hasta = recycle_bin::store(repo.get_ref(), &autor, borradas, now).await?;
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
-        "message": format!("Se eliminaron {} mesas correctamente", result.deleted_count)
+        "message": format!("Se eliminaron {} mesas correctamente", result.deleted_count),
+        "deshacer_hasta": deshacer_hasta
     })))
 }
 
@@ -191,6 +314,13 @@ async fn clear_tables(
 /// - La forma debe ser "cuadrado" o "circulo"
 /// - Si se especifican min/max personas, min no puede ser mayor que max
 /// - No puede existir otra mesa con el mismo nombre en el restaurant
+///   (sin distinguir mayúsculas, acentos ni espacios sobrantes)
+/// - El `uuid`, si se envía, debe ser un UUID válido
+///
+/// # Creación condicional
+/// Con un `uuid` que el restaurante ya tiene no se crea otra mesa: se
+/// responde con la existente (`"message": "La mesa ya existía"`), para que
+/// las apps sin conexión puedan repetir la sincronización.
 ///
 /// # Parámetros
 /// - `repo`: Repositorio MongoDB
@@ -201,7 +331,8 @@ async fn clear_tables(
 /// ```json
 /// {
 ///   "message": "Mesa creada correctamente",
-///   "id": "507f1f77bcf86cd799439011"
+///   "id": "507f1f77bcf86cd799439011",
+///   "uuid": null
 /// }
 /// ```
 ///
@@ -209,16 +340,17 @@ async fn clear_tables(
 /// - `400 Bad Request`: Datos de validación incorrectos
 /// - `401 Unauthorized`: Token inválido o falta autorización
 /// - `403 Forbidden`: No tienes permiso para crear mesas en este restaurante
+/// - `404 Not Found`: Planta no encontrada
 /// - `409 Conflict`: Ya existe una mesa con ese nombre
 /// - `500 Internal Server Error`: Error de base de datos
 #[post("/tables")]
 async fn create_table(
     repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
     data: web::Json<NewTable>,
-    req: HttpRequest,
+    auth: AuthenticatedRestaurant<PermisoGestion, EscribirMesas>,
 ) -> AppResult<impl Responder> {
-    let token = extract_token(&req)?;
-    let user_id = validate_access_token(repo.get_ref(), &token).await?;
+    let user_id = auth.id();
 
     let id_restaurante = ObjectId::parse_str(&data.id_restaurante)
         .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
@@ -228,8 +360,16 @@ async fn create_table(
         return Err(AppError::Unauthorized("No tienes permiso para crear mesas en este restaurante".to_string()));
     }
 
+    // Un UUID ya sincronizado devuelve la mesa existente sin crear otra
+    let uuid = data.uuid.as_deref().map(validate_uuid).transpose()?;
+    if let Some(uuid) = &uuid {
+        if let Some(existente) = find_by_uuid(repo.get_ref(), id_restaurante, uuid).await? {
+            return Ok(existing_table_response(existente));
+        }
+    }
+
     // Validaciones
-    if data.nombre.is_empty() {
+    if data.nombre.trim().is_empty() {
         return Err(AppError::Validation("El nombre de la mesa es requerido".to_string()));
     }
 
@@ -243,25 +383,33 @@ async fn create_table(
         }
     }
 
-    // Verificar que no exista otra mesa con el mismo nombre en el restaurante
+    let id_planta = resolve_floor(repo.get_ref(), id_restaurante, data.id_planta.as_deref()).await?;
+
+    // Verificar que no exista otra mesa con el mismo nombre normalizado en el restaurante
+    let nombre = data.nombre.trim().to_string();
+    let nombre_normalizado = normalize_name(&nombre);
     let mesas = repo.mesas();
     let existing = mesas
         .find_one(doc! {
             "id_restaurante": id_restaurante,
-            "nombre": &data.nombre
+            "$or": [
+                {"nombre": &nombre},
+                {"nombre_normalizado": &nombre_normalizado}
+            ]
         })
         .await
         .map_err(|e| AppError::Internal(format!("Error verificando mesa existente: {}", e)))?;
 
     if existing.is_some() {
-        return Err(AppError::Conflict(format!("Ya existe una mesa con el nombre '{}'", data.nombre)));
+        return Err(AppError::Conflict(format!("Ya existe una mesa con el nombre '{}'", nombre)));
     }
 
     let mesa = Mesa {
         id: None,
         id_restaurante,
         tipo: data.tipo.clone(),
-        nombre: data.nombre.clone(),
+        nombre,
+        nombre_normalizado,
         pos_x: data.pos_x,
         pos_y: data.pos_y,
         size_x: data.size_x,
@@ -270,28 +418,60 @@ async fn create_table(
         reservable: data.reservable,
         min_personas: data.min_personas,
         max_personas: data.max_personas,
-        created_at: MongoRepo::current_timestamp(),
+        id_planta,
+        uuid: uuid.clone(),
+        created_at: clock.timestamp(),
     };
 
-    let result = mesas
-        .insert_one(mesa)
-        .await
-        .map_err(|e| AppError::Internal(format!("Error guardando mesa: {}", e)))?;
+    let despues = audit::snapshot(&mesa);
+    let created_at = mesa.created_at;
+    let result = match mesas.insert_one(mesa).await {
+        Ok(result) => result,
+        Err(e) => {
+            // Otra petición con el mismo UUID se ha adelantado
+            if let (true, Some(uuid)) = (is_duplicate_key(&e), &uuid) {
+                if let Some(existente) = find_by_uuid(repo.get_ref(), id_restaurante, uuid).await? {
+                    return Ok(existing_table_response(existente));
+                }
+            }
+            return Err(AppError::Internal(format!("Error guardando mesa: {}", e)));
+        }
+    };
+    let id = result.inserted_id.as_object_id();
+    audit::record(repo.get_ref(), &Autor::from(&auth), AccionAuditoria::CrearMesa, id, None, despues, created_at).await;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "message": "Mesa creada correctamente",
-        "id": result.inserted_id.as_object_id().unwrap().to_hex()
+        "id": result.inserted_id.as_object_id().unwrap().to_hex(),
+        "uuid": uuid
     })))
 }
 
+/// Mesa de un restaurante con un UUID del cliente
+async fn find_by_uuid(repo: &MongoRepo, id_restaurante: ObjectId, uuid: &str) -> AppResult<Option<Mesa>> {
+    repo.mesas()
+        .find_one(doc! { "id_restaurante": id_restaurante, "uuid": uuid })
+        .await
+        .map_err(|e| AppError::database("find_table_by_uuid", e))
+}
+
+/// Respuesta de [`create_table`] para una mesa que ya existía con ese UUID
+fn existing_table_response(mesa: Mesa) -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({
+        "message": "La mesa ya existía",
+        "id": mesa.id.map(|id| id.to_hex()),
+        "uuid": mesa.uuid
+    }))
+}
+
 /// Obtiene todas las mesas de un restaurante
 ///
 /// # Autenticación
-/// Requiere token Bearer válido del restaurante propietario.
+/// Requiere permiso `Reservas` (cualquier rol del personal).
 ///
 /// # Parámetros
 /// - `repo`: Repositorio MongoDB
-/// - `query`: ID del restaurante
+/// - `query`: ID del restaurante y, opcionalmente, la `planta`
 /// - `req`: Request HTTP con el token de autorización
 ///
 /// # Respuesta
@@ -310,23 +490,28 @@ async fn create_table(
 ///     "forma": "cuadrado",
 ///     "reservable": true,
 ///     "min_personas": 2,
-///     "max_personas": 4
+///     "max_personas": 4,
+///     "id_planta": null
 ///   }
 /// ]
 /// ```
 ///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
 /// # Errores
 /// - `401 Unauthorized`: Token inválido o falta autorización
 /// - `403 Forbidden`: No tienes permiso para ver las mesas de este restaurante
+/// - `404 Not Found`: Planta no encontrada
 /// - `500 Internal Server Error`: Error de base de datos
 #[get("/tables")]
 async fn get_tables(
     repo: web::Data<MongoRepo>,
+    campos: CamposRespuesta,
     query: web::Query<QueryParams>,
-    req: HttpRequest,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerMesas>,
 ) -> AppResult<impl Responder> {
-    let token = extract_token(&req)?;
-    let user_id = validate_access_token(repo.get_ref(), &token).await?;
+    let user_id = auth.id();
 
     let id_restaurante = ObjectId::parse_str(&query.id_restaurante)
         .map_err(|_| AppError::Validation("ID de restaurante inválido".to_string()))?;
@@ -336,9 +521,14 @@ async fn get_tables(
         return Err(AppError::Unauthorized("No tienes permiso para ver las mesas de este restaurante".to_string()));
     }
 
+    let mut filtro = doc! { "id_restaurante": id_restaurante };
+    if let Some(planta) = resolve_floor(repo.get_ref(), id_restaurante, query.planta.as_deref()).await? {
+        filtro.insert("id_planta", planta);
+    }
+
     let mesas = repo.mesas();
     let cursor = mesas
-        .find(doc! { "id_restaurante": id_restaurante })
+        .find(filtro)
         .await
         .map_err(|e| AppError::Internal(format!("Error obteniendo mesas: {}", e)))?;
 
@@ -351,7 +541,228 @@ async fn get_tables(
         results.push(MesaResponse::from(mesa));
     }
 
-    Ok(HttpResponse::Ok().json(results))
+    Ok(campos.respond(&results))
+}
+
+/// Mesas del restaurante en servicio en la fecha de `inicio` (las de la
+/// distribución de ese día, si la tiene) y las ocupaciones que pueden
+/// chocar con una reserva de `personas` comensales que empiece en `inicio`
+///
+/// Con `planta`, solo las mesas de esa planta.
+async fn load_candidates(
+    repo: &MongoRepo,
+    restaurant: &Restaurant,
+    inicio: NaiveDateTime,
+    personas: i32,
+    planta: Option<ObjectId>,
+) -> AppResult<(Vec<Mesa>, Vec<Ocupacion>)> {
+    let id_restaurante = restaurant.id.unwrap();
+
+    let mut filtro = doc! { "id_restaurante": id_restaurante };
+    if let Some(planta) = planta {
+        filtro.insert("id_planta", planta);
+    }
+
+    let mut cursor = repo.mesas()
+        .find(filtro)
+        .await
+        .map_err(|e| AppError::Internal(format!("Error obteniendo mesas: {}", e)))?;
+
+    let distribucion = load_active_layout(repo, id_restaurante, inicio.date()).await?;
+    let mut mesas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let mesa: Mesa = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando mesa: {}", e)))?;
+        if distribucion.as_ref().is_none_or(|distribucion| distribucion.incluye(&mesa)) {
+            mesas.push(mesa);
+        }
+    }
+
+    let duracion = restaurant.configuracion.ocupacion_minutos(personas);
+    let intervalo = Ocupacion::new(ObjectId::new(), inicio, duracion);
+    let ocupaciones = load_ocupaciones(repo, id_restaurante, &intervalo, &restaurant.configuracion, None).await?;
+
+    Ok((mesas, ocupaciones))
+}
+
+/// Busca las mesas disponibles para una reserva
+///
+/// Devuelve las mesas reservables del restaurante que admiten el número de
+/// personas indicado y no tienen ninguna reserva activa que se solape con
+/// el intervalo `[hora, hora + duración)`, con la duración de ese número de
+/// comensales (`duracion_reserva_minutos` o `duraciones_grupo`) y contando
+/// el `margen_limpieza_minutos` tras cada reserva. Usa las mismas reglas que la
+/// creación de reservas (ver [`crate::availability`]).
+///
+/// Si la hora cae en una franja bloqueada (`/slot-rules`) no hay ninguna
+/// mesa disponible y la lista está vacía. Si ese día tiene una distribución
+/// de mesas (ver [`super::layout`]), solo se buscan las mesas que están en ella.
+///
+/// Si se indica el cliente (`email` o `telefono`) y tiene una mesa preferida
+/// libre (ver [`super::customer`]), esa mesa aparece la primera.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Parámetros
+/// - `fecha`: Fecha de la reserva (formato YYYY-MM-DD)
+/// - `hora`: Hora de la reserva (formato HH:MM)
+/// - `personas`: Número de comensales
+/// - `email`, `telefono` (opcionales): Cliente que reserva
+/// - `planta` (opcional): Buscar solo en esa planta
+///
+/// # Respuesta
+/// Lista de mesas con el mismo formato que `GET /tables`.
+///
+/// Acepta `fields` para devolver solo algunos campos de cada elemento
+/// (ver [`super::fields`]).
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha, hora o número de personas inválidos
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/tables/available")]
+async fn get_available_tables(
+    repo: web::Data<MongoRepo>,
+    campos: CamposRespuesta,
+    query: web::Query<AvailabilityQuery>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerMesas>,
+) -> AppResult<impl Responder> {
+    let restaurant = auth.restaurant;
+    let id_restaurante = restaurant.id.unwrap();
+
+    let inicio = validate_date(&query.fecha)?.and_time(validate_time(&query.hora)?);
+    if query.personas <= 0 {
+        return Err(AppError::Validation("El número de personas debe ser mayor a 0".to_string()));
+    }
+
+    let reglas = load_rules(repo.get_ref(), id_restaurante).await?;
+    if availability::blocking_rule(&reglas, inicio).is_some() {
+        return Ok(HttpResponse::Ok().json(Vec::<MesaResponse>::new()));
+    }
+
+    let planta = resolve_floor(repo.get_ref(), id_restaurante, query.planta.as_deref()).await?;
+    let (mesas, ocupaciones) = load_candidates(repo.get_ref(), &restaurant, inicio, query.personas, planta).await?;
+    let duracion = restaurant.configuracion.ocupacion_minutos(query.personas);
+
+    let capacidades: Vec<CapacidadMesa> = mesas.iter().map(CapacidadMesa::from).collect();
+    let libres = availability::available_tables(&capacidades, &ocupaciones, inicio, duracion, query.personas);
+
+    let mut mesas: Vec<Mesa> = mesas
+        .into_iter()
+        .filter(|mesa| mesa.id.is_some_and(|id| libres.contains(&id)))
+        .collect();
+
+    // La mesa preferida del cliente, si está libre, va la primera
+    let cliente = find_by_contact(
+        repo.get_ref(),
+        id_restaurante,
+        query.email.as_deref().unwrap_or_default(),
+        query.telefono.as_deref().unwrap_or_default(),
+    ).await?;
+    if let Some(preferida) = cliente.and_then(|cliente| cliente.mesa_preferida) {
+        mesas.sort_by_key(|mesa| mesa.id != Some(preferida));
+    }
+
+    let results: Vec<MesaResponse> = mesas.into_iter().map(MesaResponse::from).collect();
+
+    Ok(campos.respond(&results))
+}
+
+/// Elige la mejor mesa libre para una reserva
+///
+/// Busca entre las mismas mesas que `GET /tables/available`, con las mismas
+/// reglas que la creación de reservas (capacidad, solapes, franjas
+/// bloqueadas y distribución del día), y elige, por este orden:
+///
+/// 1. La mesa habitual del cliente (`email_cliente` o `telefono_cliente`),
+///    si está libre
+/// 2. Las mesas de las `plantas` preferidas, en su orden; después, las demás
+/// 3. La que deja menos sillas vacías (ver [`availability::best_table`])
+///
+/// No crea la reserva: la mesa elegida se usa como `id_mesa` en
+/// `POST /reservations`.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Cuerpo
+/// ```json
+/// { "fecha": "2030-06-15", "hora": "21:00", "numero_personas": 4, "plantas": ["665b1a000000000000000001"] }
+/// ```
+///
+/// # Respuesta
+/// La mesa elegida, con el mismo formato que `GET /tables`.
+///
+/// # Errores
+/// - `400 Bad Request`: Fecha, hora o número de personas inválidos
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `404 Not Found`: Alguna planta preferida no existe
+/// - `409 Conflict`: La hora cae en una franja bloqueada, o no hay ninguna
+///   mesa libre (código `sin_mesa_libre`)
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/reservations/auto-assign")]
+async fn auto_assign_table(
+    repo: web::Data<MongoRepo>,
+    data: web::Json<AutoAssign>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerMesas>,
+) -> AppResult<impl Responder> {
+    let restaurant = auth.restaurant;
+    let id_restaurante = restaurant.id.unwrap();
+
+    let inicio = validate_date(&data.fecha)?.and_time(validate_time(&data.hora)?);
+    if data.numero_personas <= 0 {
+        return Err(AppError::Validation("El número de personas debe ser mayor a 0".to_string()));
+    }
+
+    let reglas = load_rules(repo.get_ref(), id_restaurante).await?;
+    if let Some(regla) = availability::blocking_rule(&reglas, inicio) {
+        return Err(AppError::Conflict(match regla.motivo.as_str() {
+            "" => "No se aceptan reservas en esa franja horaria".to_string(),
+            motivo => format!("No se aceptan reservas en esa franja horaria: {}", motivo),
+        }));
+    }
+
+    let mut plantas = Vec::new();
+    for planta in &data.plantas {
+        plantas.extend(resolve_floor(repo.get_ref(), id_restaurante, Some(planta)).await?);
+    }
+    let preferida = find_by_contact(
+        repo.get_ref(),
+        id_restaurante,
+        data.email_cliente.as_deref().unwrap_or_default(),
+        data.telefono_cliente.as_deref().unwrap_or_default(),
+    ).await?.and_then(|cliente| cliente.mesa_preferida);
+
+    let (mesas, ocupaciones) = load_candidates(repo.get_ref(), &restaurant, inicio, data.numero_personas, None).await?;
+    let capacidades: Vec<CapacidadMesa> = mesas.iter().map(CapacidadMesa::from).collect();
+    // 0 para la mesa habitual, 1.. para las plantas preferidas y el resto detrás
+    let preferencia = |id_mesa: ObjectId| {
+        if Some(id_mesa) == preferida {
+            return 0;
+        }
+        let planta = mesas.iter().find(|mesa| mesa.id == Some(id_mesa)).and_then(|mesa| mesa.id_planta);
+        1 + planta
+            .and_then(|planta| plantas.iter().position(|preferida| *preferida == planta))
+            .unwrap_or(plantas.len())
+    };
+    let elegida = availability::best_table(
+        &capacidades,
+        &ocupaciones,
+        inicio,
+        restaurant.configuracion.ocupacion_minutos(data.numero_personas),
+        data.numero_personas,
+        preferencia,
+    ).ok_or(AppError::conflict_with_code(
+        "sin_mesa_libre",
+        "No hay ninguna mesa libre para ese número de personas a esa hora",
+    ))?;
+
+    let mesa = mesas.into_iter()
+        .find(|mesa| mesa.id == Some(elegida))
+        .ok_or(AppError::Internal("Mesa elegida no encontrada".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(MesaResponse::from(mesa)))
 }
 
 /// Configura las rutas relacionadas con mesas
@@ -359,12 +770,16 @@ async fn get_tables(
 /// # Rutas disponibles
 /// - `POST /tables` - Crear nueva mesa
 /// - `GET /tables` - Listar mesas de un restaurante
-/// - `DELETE /tables/clear` - Eliminar todas las mesas
+/// - `GET /tables/available` - Buscar mesas disponibles
+/// - `POST /reservations/auto-assign` - Elegir la mejor mesa libre para una reserva
+/// - `DELETE /tables/clear` - Eliminar todas las mesas (con confirmación)
 ///
 /// # Parámetros
 /// - `cfg`: Configuración del servicio Actix Web
 pub fn routes(cfg: &mut web::ServiceConfig) {
     cfg.service(create_table);
     cfg.service(get_tables);
+    cfg.service(get_available_tables);
+    cfg.service(auto_assign_table);
     cfg.service(clear_tables);
 }
\ No newline at end of file
@@ -1,10 +1,181 @@
 use actix_web::{get, HttpResponse, Responder, web};
+use chrono::Duration;
+use mongodb::bson::{doc, oid::ObjectId};
+use serde::Serialize;
+use std::collections::HashMap;
+use super::{AppError, AppResult};
+use super::auth::{AuthenticatedRestaurant, LeerReservas, PermisoReservas};
+use crate::availability;
+use crate::clock::Clock;
+use crate::db::{EstadoReserva, MongoRepo, Reserva};
+use crate::eta::{self, Rotacion};
+
+/// Días de historial de rotaciones con los que se estima la duración
+const DIAS_HISTORIAL: i64 = 90;
+
+/// Rotaciones más recientes que se tienen en cuenta como máximo
+const MAX_ROTACIONES: i64 = 500;
 
 #[get("/visual")]
 async fn get_visual() -> impl Responder {
     HttpResponse::Ok().body("Plano visual en construcción")
 }
 
+/// Previsión de una mesa ocupada
+#[derive(Serialize)]
+struct EtaMesa {
+    id_mesa: String,
+    nombre_mesa: Option<String>,
+    id_reserva: String,
+    numero_personas: i32,
+    /// Momento (timestamp unix) en que se sentó al cliente
+    sentada_en: i64,
+    duracion_estimada_minutos: u32,
+    /// Rotaciones del historial en que se basa la duración; 0 si es la
+    /// duración configurada
+    muestras: usize,
+    /// Momento (timestamp unix) en que se espera que quede libre
+    libre_en: i64,
+    minutos_restantes: i64,
+    /// La mesa ya ha superado su duración estimada
+    retrasada: bool,
+}
+
+/// Momento en que se sentó al cliente de una reserva
+///
+/// Las reservas sentadas antes de guardar `sentada_en` usan su hora de
+/// llegada o, si no se puede leer, la de su última modificación.
+fn sentada_en(reserva: &Reserva) -> i64 {
+    reserva.sentada_en
+        .or_else(|| availability::parse_inicio(&reserva.fecha, &reserva.hora).map(|inicio| inicio.and_utc().timestamp()))
+        .unwrap_or(reserva.updated_at)
+}
+
+/// Estima cuándo quedará libre cada mesa ocupada
+///
+/// Para cada reserva sentada, la duración esperada es la mediana de las
+/// rotaciones (de sentada a completada) de los últimos 90 días de los grupos
+/// de tamaño parecido; sin historial suficiente se usa la duración
+/// configurada para su número de comensales (ver [`crate::eta`]). Sirve para dar esperas
+/// realistas a los clientes sin reserva: las mesas vienen ordenadas de la
+/// que antes se libera a la que más tarda.
+///
+/// # Autenticación
+/// Requiere permiso `Reservas` (cualquier rol del personal).
+///
+/// # Respuesta
+/// ```json
+/// {
+///   "generado_en": 1906977600,
+///   "mesas": [
+///     {
+///       "id_mesa": "507f1f77bcf86cd799439012",
+///       "nombre_mesa": "Mesa 4",
+///       "id_reserva": "507f1f77bcf86cd799439011",
+///       "numero_personas": 2,
+///       "sentada_en": 1906974000,
+///       "duracion_estimada_minutos": 75,
+///       "muestras": 42,
+///       "libre_en": 1906978500,
+///       "minutos_restantes": 15,
+///       "retrasada": false
+///     }
+///   ]
+/// }
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o falta autorización
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/visual/eta")]
+async fn get_eta(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    auth: AuthenticatedRestaurant<PermisoReservas, LeerReservas>,
+) -> AppResult<impl Responder> {
+    let restaurante_id = auth.id();
+    let now = clock.timestamp();
+    let configuracion = &auth.restaurant.configuracion;
+
+    let historial = rotaciones(repo.get_ref(), restaurante_id, now).await?;
+
+    let mut nombres = HashMap::new();
+    let mut cursor = repo.mesas()
+        .find(doc! { "id_restaurante": restaurante_id })
+        .await
+        .map_err(|e| AppError::database("get_eta", e))?;
+    while cursor.advance().await.map_err(|e| AppError::database("get_eta", e))? {
+        let mesa = cursor.deserialize_current().map_err(|e| AppError::database("get_eta", e))?;
+        if let Some(id) = mesa.id {
+            nombres.insert(id, mesa.nombre);
+        }
+    }
+
+    let mut mesas = Vec::new();
+    let mut cursor = repo.reservas()
+        .find(doc! { "id_restaurante": restaurante_id, "estado": EstadoReserva::Sentada })
+        .await
+        .map_err(|e| AppError::database("get_eta", e))?;
+    while cursor.advance().await.map_err(|e| AppError::database("get_eta", e))? {
+        let reserva = cursor.deserialize_current().map_err(|e| AppError::database("get_eta", e))?;
+        let Some(id_reserva) = reserva.id else { continue };
+        let sentada_en = sentada_en(&reserva);
+        let duracion = eta::duracion_tipica(
+            &historial,
+            reserva.numero_personas,
+            configuracion.duracion_minutos(reserva.numero_personas),
+        );
+        let libre_en = eta::libre_en(sentada_en, duracion.minutos, now);
+        mesas.push(EtaMesa {
+            id_mesa: reserva.id_mesa.to_hex(),
+            nombre_mesa: nombres.get(&reserva.id_mesa).cloned(),
+            id_reserva: id_reserva.to_hex(),
+            numero_personas: reserva.numero_personas,
+            sentada_en,
+            duracion_estimada_minutos: duracion.minutos,
+            muestras: duracion.muestras,
+            libre_en,
+            minutos_restantes: (libre_en - now) / 60,
+            retrasada: sentada_en + i64::from(duracion.minutos) * 60 < now,
+        });
+    }
+    mesas.sort_by_key(|mesa| mesa.libre_en);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "generado_en": now,
+        "mesas": mesas,
+    })))
+}
+
+/// Rotaciones completadas del restaurante en los últimos [`DIAS_HISTORIAL`] días
+async fn rotaciones(repo: &MongoRepo, restaurante_id: ObjectId, now: i64) -> AppResult<Vec<Rotacion>> {
+    let desde = now - Duration::days(DIAS_HISTORIAL).num_seconds();
+    let mut cursor = repo.reservas()
+        .find(doc! {
+            "id_restaurante": restaurante_id,
+            "estado": EstadoReserva::Completada,
+            "sentada_en": { "$exists": true },
+            "completada_en": { "$gte": desde },
+        })
+        .sort(doc! { "completada_en": -1 })
+        .limit(MAX_ROTACIONES)
+        .await
+        .map_err(|e| AppError::database("get_eta", e))?;
+
+    let mut rotaciones = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("get_eta", e))? {
+        let reserva = cursor.deserialize_current().map_err(|e| AppError::database("get_eta", e))?;
+        if let (Some(sentada_en), Some(completada_en)) = (reserva.sentada_en, reserva.completada_en) {
+            rotaciones.push(Rotacion {
+                numero_personas: reserva.numero_personas,
+                minutos: (completada_en - sentada_en) / 60,
+            });
+        }
+    }
+    Ok(rotaciones)
+}
+
 pub fn routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(get_visual);
+    cfg.service(get_visual)
+        .service(get_eta);
 }
@@ -0,0 +1,175 @@
+//! # Verificación de webhooks entrantes
+//!
+//! Las integraciones que nos llaman (pasarela de pago, TPV, canales de
+//! reserva) firman cada notificación con un secreto compartido. Todas usan
+//! el mismo esquema, al estilo de Stripe:
+//!
+//! ```text
+//! X-Webhook-Signature: t=1718000000,v1=<hex(HMAC-SHA256(secreto, "1718000000." + cuerpo))>
+//! ```
+//!
+//! [`VerificadorWebhook::verify`] comprueba, en este orden:
+//!
+//! - Que la firma corresponde al cuerpo exacto recibido. Puede haber varios
+//!   `v1` (rotación del secreto en la integración); basta con que coincida uno
+//! - Que `t` no se aleja del reloj del servidor más de
+//!   `WEBHOOK_TOLERANCIA_SEGUNDOS` (default: 300)
+//! - Que la misma firma no se ha aceptado ya: las firmas aceptadas se guardan
+//!   en la colección `webhooks_recibidos` mientras dura la tolerancia, de
+//!   forma que una notificación capturada no se puede reenviar
+//!
+//! Cualquier fallo responde 401 sin llegar a la lógica del endpoint. Las
+//! integraciones que reintentan una notificación deben volver a firmarla con
+//! un `t` nuevo; la lógica de cada endpoint sigue siendo idempotente.
+
+use std::env;
+use actix_web::HttpRequest;
+use hmac::{Hmac, KeyInit, Mac};
+use mongodb::bson::doc;
+use sha2::Sha256;
+use super::{AppError, AppResult};
+use crate::db::{is_duplicate_key, MongoRepo, WebhookRecibido};
+
+/// Cabecera con la firma de la notificación
+pub const CABECERA_FIRMA: &str = "X-Webhook-Signature";
+
+/// Tolerancia por defecto entre `t` y el reloj del servidor
+const TOLERANCIA_DEFECTO: i64 = 300;
+
+/// Firma hexadecimal de un cuerpo con un timestamp
+///
+/// ```
+/// use pispas_reservation::api::webhook_auth::sign;
+///
+/// let firma = sign("secreto", 1718000000, br#"{"codigo":"abc"}"#);
+/// assert_eq!(firma.len(), 64);
+/// assert_ne!(firma, sign("secreto", 1718000001, br#"{"codigo":"abc"}"#));
+/// assert_ne!(firma, sign("otro", 1718000000, br#"{"codigo":"abc"}"#));
+/// ```
+pub fn sign(secreto: &str, timestamp: i64, cuerpo: &[u8]) -> String {
+    hex::encode(mac(secreto, timestamp, cuerpo).finalize().into_bytes())
+}
+
+/// HMAC de `"<timestamp>.<cuerpo>"` sin finalizar
+fn mac(secreto: &str, timestamp: i64, cuerpo: &[u8]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(secreto.as_bytes())
+        .expect("HMAC admite claves de cualquier longitud");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(cuerpo);
+    mac
+}
+
+/// Separa la cabecera de firma en el timestamp y las firmas `v1`
+///
+/// Devuelve `None` si falta `t`, no es un número o no hay ninguna `v1`.
+///
+/// ```
+/// use pispas_reservation::api::webhook_auth::parse_signature;
+///
+/// assert_eq!(
+///     parse_signature("t=1718000000,v1=ab12,v1=cd34"),
+///     Some((1718000000, vec!["ab12".to_string(), "cd34".to_string()]))
+/// );
+/// assert_eq!(parse_signature("v1=ab12"), None);
+/// assert_eq!(parse_signature("t=1718000000"), None);
+/// ```
+pub fn parse_signature(cabecera: &str) -> Option<(i64, Vec<String>)> {
+    let mut timestamp = None;
+    let mut firmas = Vec::new();
+    for parte in cabecera.split(',') {
+        match parte.trim().split_once('=') {
+            Some(("t", valor)) => timestamp = Some(valor.parse().ok()?),
+            Some(("v1", valor)) => firmas.push(valor.to_string()),
+            _ => {}
+        }
+    }
+
+    match (timestamp, firmas.is_empty()) {
+        (Some(timestamp), false) => Some((timestamp, firmas)),
+        _ => None,
+    }
+}
+
+/// Verificador de las notificaciones de una integración
+#[derive(Clone)]
+pub struct VerificadorWebhook {
+    /// Nombre de la integración ("pagos"...), que separa sus firmas de las
+    /// de las demás
+    origen: &'static str,
+    secreto: String,
+    tolerancia_segundos: i64,
+}
+
+impl VerificadorWebhook {
+    pub fn new(origen: &'static str, secreto: &str, tolerancia_segundos: i64) -> Self {
+        VerificadorWebhook {
+            origen,
+            secreto: secreto.to_string(),
+            tolerancia_segundos,
+        }
+    }
+
+    /// Verificador con el secreto de la variable `variable` y la tolerancia
+    /// de `WEBHOOK_TOLERANCIA_SEGUNDOS`
+    ///
+    /// Devuelve `None` si el secreto no está configurado: la integración no
+    /// está disponible.
+    pub fn from_env(origen: &'static str, variable: &str) -> Option<Self> {
+        let secreto = env::var(variable).ok().filter(|secreto| !secreto.is_empty())?;
+        let tolerancia = env::var("WEBHOOK_TOLERANCIA_SEGUNDOS")
+            .ok()
+            .and_then(|valor| valor.parse().ok())
+            .unwrap_or(TOLERANCIA_DEFECTO);
+
+        Some(Self::new(origen, &secreto, tolerancia))
+    }
+
+    /// Comprueba la firma, la antigüedad y que la notificación no se repite
+    ///
+    /// # Errores
+    /// - `UnauthorizedWithContext`: Falta la firma, no es válida, está fuera
+    ///   de la tolerancia o ya se había aceptado
+    /// - `Database`: Error guardando la firma aceptada
+    pub async fn verify(&self, repo: &MongoRepo, req: &HttpRequest, cuerpo: &[u8], now: i64) -> AppResult<()> {
+        let rechazar = |motivo: &str| AppError::unauthorized_operation(&format!("webhook_{}", self.origen), motivo);
+
+        let cabecera = req.headers()
+            .get(CABECERA_FIRMA)
+            .and_then(|valor| valor.to_str().ok())
+            .ok_or_else(|| rechazar("Falta la firma de la notificación"))?;
+        let (timestamp, firmas) = parse_signature(cabecera)
+            .ok_or_else(|| rechazar("Cabecera de firma con formato inválido"))?;
+
+        let firma = firmas.iter()
+            .find(|firma| {
+                hex::decode(firma).is_ok_and(|bytes| mac(&self.secreto, timestamp, cuerpo).verify_slice(&bytes).is_ok())
+            })
+            .ok_or_else(|| rechazar("Firma inválida"))?;
+
+        if (now - timestamp).abs() > self.tolerancia_segundos {
+            return Err(rechazar("La notificación está fuera del margen de tiempo permitido"));
+        }
+
+        // Las firmas fuera de la tolerancia ya se rechazan por antigüedad
+        let webhooks = repo.webhooks_recibidos();
+        webhooks
+            .delete_many(doc! { "created_at": { "$lt": now - 2 * self.tolerancia_segundos } })
+            .await
+            .map_err(|e| AppError::database("verify_webhook", e))?;
+
+        let recibido = WebhookRecibido {
+            id: None,
+            clave: format!("{}:{}", self.origen, firma.to_lowercase()),
+            created_at: now,
+        };
+        match webhooks.insert_one(recibido).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_duplicate_key(&e) => {
+                tracing::warn!(origen = self.origen, "Notificación de webhook repetida rechazada");
+                Err(rechazar("Notificación repetida"))
+            }
+            Err(e) => Err(AppError::database("verify_webhook", e)),
+        }
+    }
+}
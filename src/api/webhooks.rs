@@ -0,0 +1,326 @@
+//! # Suscripciones de webhook
+//!
+//! Un restaurante puede recibir sus eventos de dominio (reservas creadas,
+//! canceladas, sentadas...) en sus propios sistemas con un `POST` a una URL.
+//! El propietario gestiona las suscripciones:
+//!
+//! - `GET /restaurants/webhooks` - Suscripciones del restaurante
+//! - `POST /restaurants/webhooks` - Crea una; el secreto solo se muestra aquí
+//! - `PUT /restaurants/webhooks/{id}` - Cambia la URL, los eventos o la plantilla
+//! - `DELETE /restaurants/webhooks/{id}` - Borra una suscripción
+//!
+//! Cada suscripción elige los tipos de evento que recibe (`eventos`; sin
+//! ellos, todos) y la forma del cuerpo (`plantilla`, ver
+//! [`crate::webhooks`]), de modo que un receptor que espera un JSON
+//! concreto puede consumir los eventos sin un servicio adaptador.
+//!
+//! Las entregas se firman con el secreto de la suscripción con el mismo
+//! esquema que los webhooks entrantes (ver [`super::webhook_auth`]) y las
+//! hace [`crate::jobs::webhook_delivery`]. Una suscripción nueva recibe los
+//! eventos posteriores a su creación.
+
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder};
+use mongodb::bson::{doc, oid::ObjectId, to_bson};
+use mongodb::options::{FindOptions, ReturnDocument};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use super::{AppError, AppResult};
+use super::auth::{AuthenticatedRestaurant, PermisoConfiguracion};
+use crate::clock::Clock;
+use crate::db::{MongoRepo, PlantillaWebhook, SuscripcionWebhook};
+use crate::events::TipoEvento;
+use crate::webhooks;
+
+/// Prefijo de los secretos de firma
+const PREFIJO_SECRETO: &str = "whsec_";
+
+/// Estructura para crear una suscripción
+#[derive(Deserialize)]
+struct NewWebhook {
+    url: String,
+    /// Tipos de evento que recibe (por defecto, todos)
+    #[serde(default)]
+    eventos: Vec<String>,
+    /// Forma del cuerpo (por defecto, el evento completo)
+    #[serde(default)]
+    plantilla: PlantillaWebhook,
+}
+
+/// Estructura para modificar una suscripción; los campos ausentes no cambian
+#[derive(Deserialize)]
+struct UpdateWebhook {
+    url: Option<String>,
+    eventos: Option<Vec<String>>,
+    plantilla: Option<PlantillaWebhook>,
+}
+
+/// Respuesta para una suscripción
+#[derive(Serialize)]
+struct WebhookResponse {
+    id: String,
+    url: String,
+    eventos: Vec<String>,
+    plantilla: PlantillaWebhook,
+    /// Error de la última entrega, si falló
+    ultimo_error: Option<String>,
+    /// Secreto de firma completo, solo al crearla
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secreto: Option<String>,
+    created_at: i64,
+}
+
+impl From<SuscripcionWebhook> for WebhookResponse {
+    fn from(suscripcion: SuscripcionWebhook) -> Self {
+        WebhookResponse {
+            id: suscripcion.id.map(|id| id.to_hex()).unwrap_or_default(),
+            url: suscripcion.url,
+            eventos: suscripcion.eventos,
+            plantilla: suscripcion.plantilla,
+            ultimo_error: suscripcion.ultimo_error,
+            secreto: None,
+            created_at: suscripcion.created_at,
+        }
+    }
+}
+
+/// Valida la URL de destino; devuelve la URL sin espacios
+fn validate_url(url: &str) -> AppResult<String> {
+    let url = url.trim();
+    let valida = reqwest::Url::parse(url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some());
+    if !valida {
+        return Err(AppError::validation_field("url", "La URL debe ser http:// o https://"));
+    }
+    Ok(url.to_string())
+}
+
+/// Valida los tipos de evento de una suscripción
+fn validate_events(eventos: &[String]) -> AppResult<()> {
+    if let Some(desconocido) = eventos.iter().find(|evento| TipoEvento::parse(evento).is_none()) {
+        return Err(AppError::validation_field("eventos", &format!("Tipo de evento desconocido: {}", desconocido)));
+    }
+    Ok(())
+}
+
+/// Valida las rutas de una plantilla `campos`
+fn validate_template(plantilla: &PlantillaWebhook) -> AppResult<()> {
+    if let PlantillaWebhook::Campos { campos } = plantilla {
+        if campos.is_empty() {
+            return Err(AppError::validation_field("plantilla", "Indica al menos un campo"));
+        }
+        if let Some((destino, origen)) = campos.iter().find(|(destino, origen)| {
+            !webhooks::valid_path(destino) || !webhooks::valid_path(origen)
+        }) {
+            return Err(AppError::validation_field(
+                "plantilla",
+                &format!("Ruta de campo inválida: {} -> {}", destino, origen),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn parse_id(id: &str) -> AppResult<ObjectId> {
+    ObjectId::parse_str(id).map_err(|_| AppError::Validation("ID de suscripción inválido".to_string()))
+}
+
+/// Lista las suscripciones de webhook del restaurante
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Respuesta
+/// ```json
+/// [
+///   {
+///     "id": "507f1f77bcf86cd799439011",
+///     "url": "https://tpv.example.com/hooks/reservas",
+///     "eventos": ["reserva_creada", "reserva_cancelada"],
+///     "plantilla": { "formato": "minimo" },
+///     "ultimo_error": null,
+///     "created_at": 1718000000
+///   }
+/// ]
+/// ```
+///
+/// # Errores
+/// - `401 Unauthorized`: Token inválido o sin permiso de configuración
+/// - `500 Internal Server Error`: Error de base de datos
+#[get("/restaurants/webhooks")]
+async fn list_webhooks(
+    repo: web::Data<MongoRepo>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let mut cursor = repo.suscripciones_webhook()
+        .find(doc! { "id_restaurante": auth.id() })
+        .with_options(FindOptions::builder().sort(doc! { "created_at": 1 }).build())
+        .await
+        .map_err(|e| AppError::database("list_webhooks", e))?;
+
+    let mut suscripciones = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::database("list_webhooks", e))? {
+        let suscripcion: SuscripcionWebhook = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando suscripción: {}", e)))?;
+        suscripciones.push(WebhookResponse::from(suscripcion));
+    }
+
+    Ok(HttpResponse::Ok().json(suscripciones))
+}
+
+/// Crea una suscripción de webhook
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Cuerpo
+/// ```json
+/// {
+///   "url": "https://tpv.example.com/hooks/reservas",
+///   "eventos": ["reserva_creada"],
+///   "plantilla": {
+///     "formato": "campos",
+///     "campos": { "booking.id": "id_entidad", "booking.date": "datos.fecha" }
+///   }
+/// }
+/// ```
+///
+/// # Respuesta
+/// La suscripción con el mismo formato que `GET /restaurants/webhooks` y el
+/// `secreto` de firma, que no se vuelve a mostrar.
+///
+/// # Errores
+/// - `400 Bad Request`: URL inválida, tipo de evento desconocido o plantilla inválida
+/// - `401 Unauthorized`: Token inválido o sin permiso de configuración
+/// - `500 Internal Server Error`: Error de base de datos
+#[post("/restaurants/webhooks")]
+async fn create_webhook(
+    repo: web::Data<MongoRepo>,
+    clock: web::Data<dyn Clock>,
+    data: web::Json<NewWebhook>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let data = data.into_inner();
+    let url = validate_url(&data.url)?;
+    validate_events(&data.eventos)?;
+    validate_template(&data.plantilla)?;
+
+    let mut suscripcion = SuscripcionWebhook {
+        id: None,
+        id_restaurante: auth.id(),
+        url,
+        secreto: format!("{}{}", PREFIJO_SECRETO, Uuid::new_v4().simple()),
+        eventos: data.eventos,
+        plantilla: data.plantilla,
+        ultimo_id: ObjectId::new(),
+        ultimo_error: None,
+        created_at: clock.timestamp(),
+    };
+    let result = repo.suscripciones_webhook()
+        .insert_one(&suscripcion)
+        .await
+        .map_err(|e| AppError::database("create_webhook", e))?;
+    suscripcion.id = result.inserted_id.as_object_id();
+
+    let secreto = suscripcion.secreto.clone();
+    Ok(HttpResponse::Ok().json(WebhookResponse { secreto: Some(secreto), ..WebhookResponse::from(suscripcion) }))
+}
+
+/// Modifica una suscripción de webhook
+///
+/// El secreto no cambia; para rotarlo, crea otra suscripción y borra esta.
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Cuerpo
+/// Los campos de `POST /restaurants/webhooks` que cambian, por ejemplo
+/// `{ "plantilla": { "formato": "completo" } }`.
+///
+/// # Errores
+/// - `400 Bad Request`: ID, URL, tipo de evento o plantilla inválidos
+/// - `401 Unauthorized`: Token inválido o sin permiso de configuración
+/// - `404 Not Found`: Suscripción no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[put("/restaurants/webhooks/{id}")]
+async fn update_webhook(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+    data: web::Json<UpdateWebhook>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let id = parse_id(path.as_str())?;
+    let data = data.into_inner();
+
+    let mut cambios = doc! {};
+    if let Some(url) = &data.url {
+        cambios.insert("url", validate_url(url)?);
+    }
+    if let Some(eventos) = &data.eventos {
+        validate_events(eventos)?;
+        cambios.insert("eventos", eventos.clone());
+    }
+    if let Some(plantilla) = &data.plantilla {
+        validate_template(plantilla)?;
+        cambios.insert("plantilla", to_bson(plantilla)
+            .map_err(|e| AppError::Internal(format!("Error serializando plantilla: {}", e)))?);
+    }
+
+    let filtro = doc! { "_id": id, "id_restaurante": auth.id() };
+    let suscripcion = if cambios.is_empty() {
+        repo.suscripciones_webhook().find_one(filtro).await
+    } else {
+        repo.suscripciones_webhook()
+            .find_one_and_update(filtro, doc! { "$set": cambios })
+            .return_document(ReturnDocument::After)
+            .await
+    };
+    let suscripcion = suscripcion
+        .map_err(|e| AppError::database("update_webhook", e))?
+        .ok_or_else(|| AppError::NotFound("Suscripción no encontrada".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(WebhookResponse::from(suscripcion)))
+}
+
+/// Borra una suscripción de webhook; deja de recibir eventos
+///
+/// # Autenticación
+/// Requiere el token del propietario.
+///
+/// # Errores
+/// - `400 Bad Request`: ID inválido
+/// - `401 Unauthorized`: Token inválido o sin permiso de configuración
+/// - `404 Not Found`: Suscripción no encontrada
+/// - `500 Internal Server Error`: Error de base de datos
+#[delete("/restaurants/webhooks/{id}")]
+async fn delete_webhook(
+    repo: web::Data<MongoRepo>,
+    path: web::Path<String>,
+    auth: AuthenticatedRestaurant<PermisoConfiguracion>,
+) -> AppResult<impl Responder> {
+    let id = parse_id(path.as_str())?;
+
+    let borradas = repo.suscripciones_webhook()
+        .delete_one(doc! { "_id": id, "id_restaurante": auth.id() })
+        .await
+        .map_err(|e| AppError::database("delete_webhook", e))?
+        .deleted_count;
+    if borradas == 0 {
+        return Err(AppError::NotFound("Suscripción no encontrada".to_string()));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Configura las rutas de suscripciones de webhook
+///
+/// # Rutas disponibles
+/// - `GET /restaurants/webhooks` - Listar suscripciones
+/// - `POST /restaurants/webhooks` - Crear suscripción
+/// - `PUT /restaurants/webhooks/{id}` - Modificar suscripción
+/// - `DELETE /restaurants/webhooks/{id}` - Borrar suscripción
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(list_webhooks);
+    cfg.service(create_webhook);
+    cfg.service(update_webhook);
+    cfg.service(delete_webhook);
+}
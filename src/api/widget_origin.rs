@@ -0,0 +1,185 @@
+//! # Orígenes autorizados del widget
+//!
+//! Cada restaurante puede limitar las webs en las que funciona su widget con
+//! la lista `origenes_widget` de su configuración (`PUT /restaurants/settings`):
+//!
+//! ```json
+//! { "origenes_widget": ["https://latasca.es", "https://www.latasca.es"] }
+//! ```
+//!
+//! El middleware [`check_widget_origin`] se aplica a las rutas del widget
+//! `/public/restaurants/{id}/*`:
+//!
+//! - Con la lista vacía (por defecto) el widget funciona en cualquier web
+//! - Con lista, una petición con cabecera `Origin` que no esté en ella
+//!   responde 403 con el código `origen_no_autorizado`. Las peticiones sin
+//!   `Origin` (servidor a servidor, la propia web) no se bloquean
+//! - Las respuestas autorizadas llevan las cabeceras CORS para ese origen y
+//!   `Content-Security-Policy: frame-ancestors` con la lista, de forma que el
+//!   navegador tampoco deja incrustar el widget en otras webs
+//! - Las peticiones *preflight* (`OPTIONS` con
+//!   `Access-Control-Request-Method`) se responden aquí, sin llegar a ningún
+//!   handler
+//!
+//! Los enlaces de los clientes (`/public/reservations/*`) no son de ningún
+//! restaurante concreto y no se limitan.
+//!
+//! La lista se guarda en caché unos segundos por restaurante; al cambiar la
+//! configuración se olvida con [`forget_restaurant`].
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpResponse};
+use mongodb::bson::{doc, oid::ObjectId};
+use super::AppError;
+use super::account_state::public_restaurant_id;
+use crate::db::MongoRepo;
+
+/// Tiempo durante el que se recuerda la lista de un restaurante
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Máximo de restaurantes en caché; al superarlo se vacía
+const CACHE_MAX: usize = 10_000;
+
+/// Cabeceras que puede enviar el widget
+const CABECERAS_PERMITIDAS: &str = "Content-Type, X-Widget-Session";
+
+/// Métodos de las rutas del widget
+const METODOS_PERMITIDOS: &str = "GET, POST, OPTIONS";
+
+/// Caché restaurante → orígenes autorizados y cuándo se consultaron
+type Cache = Mutex<HashMap<ObjectId, (Vec<String>, Instant)>>;
+
+fn cache() -> &'static Cache {
+    static CACHE: OnceLock<Cache> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Olvida la lista en caché de un restaurante
+///
+/// Se llama al cambiar la configuración para que se aplique en la siguiente
+/// petición.
+pub fn forget_restaurant(id: ObjectId) {
+    cache().lock().unwrap().remove(&id);
+}
+
+/// Normaliza un origen a `esquema://host[:puerto]`
+///
+/// Devuelve `None` si no es un origen `http` o `https` válido (con ruta,
+/// query, credenciales...).
+///
+/// ```
+/// use pispas_reservation::api::widget_origin::normalize_origin;
+///
+/// assert_eq!(normalize_origin("https://LaTasca.es/").as_deref(), Some("https://latasca.es"));
+/// assert_eq!(normalize_origin("https://latasca.es:443").as_deref(), Some("https://latasca.es"));
+/// assert_eq!(normalize_origin("http://localhost:3000").as_deref(), Some("http://localhost:3000"));
+/// assert_eq!(normalize_origin("https://latasca.es/reservas"), None);
+/// assert_eq!(normalize_origin("latasca.es"), None);
+/// ```
+pub fn normalize_origin(origin: &str) -> Option<String> {
+    let url = reqwest::Url::parse(origin.trim()).ok()?;
+    let valido = matches!(url.scheme(), "http" | "https")
+        && url.host_str().is_some()
+        && url.path() == "/"
+        && url.query().is_none()
+        && url.fragment().is_none()
+        && url.username().is_empty()
+        && url.password().is_none();
+
+    valido.then(|| url.origin().ascii_serialization())
+}
+
+/// Orígenes autorizados de un restaurante (vacío si no limita o no existe)
+async fn allowed_origins(repo: &MongoRepo, id: ObjectId) -> Vec<String> {
+    if let Some((origenes, desde)) = cache().lock().unwrap().get(&id) {
+        if desde.elapsed() < CACHE_TTL {
+            return origenes.clone();
+        }
+    }
+
+    let origenes = match repo.restaurants().find_one(doc! { "_id": id }).await {
+        Ok(restaurant) => restaurant.map(|r| r.configuracion.origenes_widget).unwrap_or_default(),
+        Err(e) => {
+            tracing::warn!("No se pudieron consultar los orígenes del widget: {}", e);
+            return Vec::new();
+        }
+    };
+
+    let mut cache = cache().lock().unwrap();
+    if cache.len() >= CACHE_MAX {
+        cache.clear();
+    }
+    cache.insert(id, (origenes.clone(), Instant::now()));
+    origenes
+}
+
+/// Añade las cabeceras CORS y `frame-ancestors` de una respuesta autorizada
+fn add_headers(headers: &mut HeaderMap, origin: Option<&str>, origenes: &[String]) {
+    let mut insertar = |nombre: &'static str, valor: &str| {
+        if let Ok(valor) = HeaderValue::from_str(valor) {
+            headers.insert(HeaderName::from_static(nombre), valor);
+        }
+    };
+
+    match origin {
+        Some(origin) => {
+            insertar("access-control-allow-origin", origin);
+            insertar("vary", "Origin");
+        }
+        None if origenes.is_empty() => insertar("access-control-allow-origin", "*"),
+        None => insertar("vary", "Origin"),
+    }
+    insertar("access-control-allow-headers", CABECERAS_PERMITIDAS);
+    insertar("access-control-allow-methods", METODOS_PERMITIDOS);
+    if !origenes.is_empty() {
+        insertar("content-security-policy", &format!("frame-ancestors {}", origenes.join(" ")));
+    }
+}
+
+/// Middleware que aplica los orígenes autorizados del widget
+///
+/// Ver la documentación del módulo.
+pub async fn check_widget_origin(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let (Some(id), Some(repo)) = (public_restaurant_id(req.path()), req.app_data::<web::Data<MongoRepo>>()) else {
+        return next.call(req).await.map(ServiceResponse::map_into_boxed_body);
+    };
+
+    let origenes = allowed_origins(repo.get_ref(), id).await;
+    let origin = req.headers()
+        .get("Origin")
+        .and_then(|valor| valor.to_str().ok())
+        .map(str::to_string);
+
+    if let Some(origin) = &origin {
+        let autorizado = origenes.is_empty()
+            || normalize_origin(origin).is_some_and(|origin| origenes.contains(&origin));
+        if !autorizado {
+            return Ok(req.error_response(AppError::forbidden(
+                "origen_no_autorizado",
+                &format!("El widget de este restaurante no está autorizado en {}", origin),
+            )));
+        }
+    }
+
+    let preflight = req.method() == Method::OPTIONS
+        && req.headers().contains_key("Access-Control-Request-Method");
+    if preflight {
+        let mut respuesta = HttpResponse::NoContent().finish();
+        add_headers(respuesta.headers_mut(), origin.as_deref(), &origenes);
+        return Ok(req.into_response(respuesta));
+    }
+
+    let mut respuesta = next.call(req).await?.map_into_boxed_body();
+    add_headers(respuesta.headers_mut(), origin.as_deref(), &origenes);
+    Ok(respuesta)
+}
@@ -0,0 +1,337 @@
+//! # Motor de disponibilidad
+//!
+//! Reglas puras (sin base de datos) que deciden si una mesa está libre:
+//!
+//! - Cada reserva activa ocupa su mesa durante el intervalo semiabierto
+//!   `[inicio, inicio + duración)`, donde `inicio` combina `fecha` y `hora`
+//!   y la duración depende del número de comensales e incluye el margen de
+//!   limpieza del restaurante (ver
+//!   [`Configuracion::ocupacion_minutos`](crate::db::Configuracion::ocupacion_minutos)).
+//! - Dos reservas de la misma mesa entran en conflicto si sus intervalos se
+//!   solapan; las que terminan justo cuando empieza otra no se solapan.
+//! - Las reservas canceladas, completadas o sin presentarse no ocupan la
+//!   mesa (ver [`EstadoReserva::is_active`](crate::db::EstadoReserva::is_active)).
+//! - Una reserva no puede empezar dentro de una franja bloqueada por una
+//!   [`ReglaBloqueo`] del restaurante.
+//! - Si el día de la reserva tiene una [`Distribucion`] (ver
+//!   [`active_layout`]), solo se reservan las mesas que están en ella.
+//! - Los grupos más grandes que el máximo online del restaurante se pueden
+//!   repartir en varias reservas ([`split_party`]).
+//!
+//! Los handlers cargan de MongoDB las reservas candidatas y delegan aquí la
+//! decisión, de forma que las mismas reglas se aplican al crear reservas y
+//! al buscar mesas disponibles, y se pueden probar con tests de propiedades.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use mongodb::bson::oid::ObjectId;
+use crate::db::{Distribucion, Mesa, ReglaBloqueo, Reserva, Turno};
+
+/// Formato de fecha de las reservas
+pub const FORMATO_FECHA: &str = "%Y-%m-%d";
+
+/// Formato de hora de las reservas
+pub const FORMATO_HORA: &str = "%H:%M";
+
+/// Intervalo durante el que una reserva ocupa una mesa
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ocupacion {
+    /// Mesa ocupada
+    pub id_mesa: ObjectId,
+    /// Inicio del intervalo (incluido)
+    pub inicio: NaiveDateTime,
+    /// Fin del intervalo (excluido)
+    pub fin: NaiveDateTime,
+}
+
+impl Ocupacion {
+    /// Crea una ocupación de `duracion_minutos` a partir de `inicio`
+    pub fn new(id_mesa: ObjectId, inicio: NaiveDateTime, duracion_minutos: u32) -> Self {
+        Ocupacion {
+            id_mesa,
+            inicio,
+            fin: inicio + Duration::minutes(i64::from(duracion_minutos)),
+        }
+    }
+
+    /// Ocupación de una reserva, o `None` si no está activa o tiene una
+    /// fecha/hora mal formada
+    pub fn from_reserva(reserva: &Reserva, duracion_minutos: u32) -> Option<Self> {
+        if !reserva.estado.is_active() {
+            return None;
+        }
+
+        let inicio = parse_inicio(&reserva.fecha, &reserva.hora)?;
+        Some(Ocupacion::new(reserva.id_mesa, inicio, duracion_minutos))
+    }
+
+    /// Indica si dos ocupaciones de la misma mesa se solapan
+    pub fn conflicts_with(&self, other: &Ocupacion) -> bool {
+        self.id_mesa == other.id_mesa && overlaps(self.inicio, self.fin, other.inicio, other.fin)
+    }
+}
+
+/// Capacidad de una mesa relevante para la disponibilidad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacidadMesa {
+    pub id_mesa: ObjectId,
+    pub reservable: bool,
+    pub min_personas: Option<i32>,
+    pub max_personas: Option<i32>,
+}
+
+impl CapacidadMesa {
+    /// Indica si la mesa admite reservas para `personas` comensales
+    pub fn fits(&self, personas: i32) -> bool {
+        self.reservable
+            && personas > 0
+            && self.min_personas.is_none_or(|min| personas >= min)
+            && self.max_personas.is_none_or(|max| personas <= max)
+    }
+}
+
+impl From<&Mesa> for CapacidadMesa {
+    fn from(mesa: &Mesa) -> Self {
+        CapacidadMesa {
+            id_mesa: mesa.id.unwrap_or_default(),
+            reservable: mesa.reservable,
+            min_personas: mesa.min_personas,
+            max_personas: mesa.max_personas,
+        }
+    }
+}
+
+/// Combina fecha (YYYY-MM-DD) y hora (HH:MM) en un instante
+pub fn parse_inicio(fecha: &str, hora: &str) -> Option<NaiveDateTime> {
+    let fecha = NaiveDate::parse_from_str(fecha, FORMATO_FECHA).ok()?;
+    let hora = NaiveTime::parse_from_str(hora, FORMATO_HORA).ok()?;
+    Some(fecha.and_time(hora))
+}
+
+/// Fechas (YYYY-MM-DD) cuyas reservas pueden solaparse con un intervalo
+///
+/// Incluye el día anterior al inicio para cubrir las reservas que cruzan la
+/// medianoche, y todos los días hasta el fin del intervalo.
+pub fn candidate_dates(inicio: NaiveDateTime, fin: NaiveDateTime) -> Vec<String> {
+    let mut fecha = inicio.date() - Duration::days(1);
+    let mut fechas = Vec::new();
+
+    while fecha <= fin.date() {
+        fechas.push(fecha.format(FORMATO_FECHA).to_string());
+        fecha += Duration::days(1);
+    }
+
+    fechas
+}
+
+/// Indica si dos intervalos semiabiertos `[inicio, fin)` se solapan
+pub fn overlaps(
+    a_inicio: NaiveDateTime,
+    a_fin: NaiveDateTime,
+    b_inicio: NaiveDateTime,
+    b_fin: NaiveDateTime,
+) -> bool {
+    a_inicio < b_fin && b_inicio < a_fin
+}
+
+/// Indica si una ocupación candidata choca con alguna de las existentes
+pub fn has_conflict(candidata: &Ocupacion, ocupaciones: &[Ocupacion]) -> bool {
+    ocupaciones.iter().any(|ocupacion| candidata.conflicts_with(ocupacion))
+}
+
+/// Mesas libres para una reserva de `personas` comensales en `inicio`
+///
+/// Devuelve, en el mismo orden que `mesas`, las que admiten ese número de
+/// personas y no tienen ninguna ocupación solapada con el intervalo.
+pub fn available_tables(
+    mesas: &[CapacidadMesa],
+    ocupaciones: &[Ocupacion],
+    inicio: NaiveDateTime,
+    duracion_minutos: u32,
+    personas: i32,
+) -> Vec<ObjectId> {
+    mesas
+        .iter()
+        .filter(|mesa| mesa.fits(personas))
+        .filter(|mesa| {
+            let candidata = Ocupacion::new(mesa.id_mesa, inicio, duracion_minutos);
+            !has_conflict(&candidata, ocupaciones)
+        })
+        .map(|mesa| mesa.id_mesa)
+        .collect()
+}
+
+/// Mesa libre que mejor encaja con una reserva de `personas` comensales
+///
+/// Entre las mesas libres (ver [`available_tables`]) elige la de menor
+/// `preferencia` (0 la más preferida) y, a igual preferencia, la que deja
+/// menos sillas vacías; las mesas sin máximo van detrás de las que lo
+/// tienen. A igualdad, la primera de `mesas`.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use mongodb::bson::oid::ObjectId;
+/// use pispas_reservation::availability::{best_table, CapacidadMesa};
+///
+/// let mesa = |max_personas| CapacidadMesa {
+///     id_mesa: ObjectId::new(),
+///     reservable: true,
+///     min_personas: None,
+///     max_personas,
+/// };
+/// let (grande, justa, sin_maximo) = (mesa(Some(8)), mesa(Some(4)), mesa(None));
+/// let mesas = [grande, sin_maximo, justa];
+/// let inicio = NaiveDate::from_ymd_opt(2030, 6, 15).unwrap().and_hms_opt(21, 0, 0).unwrap();
+///
+/// assert_eq!(best_table(&mesas, &[], inicio, 90, 3, |_| 0), Some(justa.id_mesa));
+/// assert_eq!(best_table(&mesas, &[], inicio, 90, 6, |_| 0), Some(grande.id_mesa));
+/// // La preferencia pesa más que el ajuste
+/// assert_eq!(best_table(&mesas, &[], inicio, 90, 3, |id| usize::from(id != grande.id_mesa)), Some(grande.id_mesa));
+/// assert_eq!(best_table(&mesas, &[], inicio, 90, 12, |_| 0), Some(sin_maximo.id_mesa));
+/// ```
+pub fn best_table(
+    mesas: &[CapacidadMesa],
+    ocupaciones: &[Ocupacion],
+    inicio: NaiveDateTime,
+    duracion_minutos: u32,
+    personas: i32,
+    preferencia: impl Fn(ObjectId) -> usize,
+) -> Option<ObjectId> {
+    let libres = available_tables(mesas, ocupaciones, inicio, duracion_minutos, personas);
+    mesas
+        .iter()
+        .filter(|mesa| libres.contains(&mesa.id_mesa))
+        .min_by_key(|mesa| (
+            preferencia(mesa.id_mesa),
+            mesa.max_personas.map_or(i32::MAX, |max| max - personas),
+        ))
+        .map(|mesa| mesa.id_mesa)
+}
+
+/// Horas de inicio de un día, cada `paso_minutos` desde las 00:00, que caen
+/// dentro de algún turno; sin turnos, todas las del día
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use pispas_reservation::availability::day_starts;
+/// use pispas_reservation::db::Turno;
+///
+/// let turno = |hora_inicio: &str, hora_fin: &str| Turno {
+///     nombre: "Cena".to_string(),
+///     hora_inicio: hora_inicio.to_string(),
+///     hora_fin: hora_fin.to_string(),
+/// };
+/// let fecha = NaiveDate::from_ymd_opt(2030, 6, 15).unwrap();
+/// let horas = |turnos: &[Turno]| -> Vec<String> {
+///     day_starts(turnos, fecha, 30).iter().map(|inicio| inicio.format("%H:%M").to_string()).collect()
+/// };
+///
+/// assert_eq!(horas(&[turno("20:00", "21:30")]), ["20:00", "20:30", "21:00"]);
+/// // Un turno que cruza la medianoche da horas al principio y al final del día
+/// assert_eq!(horas(&[turno("23:00", "00:30")]), ["00:00", "23:00", "23:30"]);
+/// assert_eq!(horas(&[]).len(), 48);
+/// ```
+pub fn day_starts(turnos: &[Turno], fecha: NaiveDate, paso_minutos: u32) -> Vec<NaiveDateTime> {
+    let paso = Duration::minutes(i64::from(paso_minutos.max(1)));
+    let mut inicio = fecha.and_time(NaiveTime::MIN);
+    let mut inicios = Vec::new();
+
+    while inicio.date() == fecha {
+        let hora = inicio.format(FORMATO_HORA).to_string();
+        if turnos.is_empty() || turnos.iter().any(|turno| turno.contiene(&hora)) {
+            inicios.push(inicio);
+        }
+        inicio += paso;
+    }
+
+    inicios
+}
+
+/// Indica si una regla de bloqueo impide empezar una reserva en `inicio`
+///
+/// La regla aplica en su fecha concreta o, si no tiene, en sus días de la
+/// semana (todos si la lista está vacía), y bloquea las horas de inicio en
+/// `[hora_inicio, hora_fin)`.
+pub fn rule_blocks(regla: &ReglaBloqueo, inicio: NaiveDateTime) -> bool {
+    let aplica_hoy = match &regla.fecha {
+        Some(fecha) => NaiveDate::parse_from_str(fecha, FORMATO_FECHA).ok() == Some(inicio.date()),
+        None => {
+            regla.dias_semana.is_empty()
+                || regla.dias_semana.contains(&inicio.weekday().number_from_monday())
+        }
+    };
+
+    let hora = inicio.time();
+    let franja = (
+        NaiveTime::parse_from_str(&regla.hora_inicio, FORMATO_HORA),
+        NaiveTime::parse_from_str(&regla.hora_fin, FORMATO_HORA),
+    );
+
+    match franja {
+        (Ok(desde), Ok(hasta)) => aplica_hoy && desde <= hora && hora < hasta,
+        _ => false,
+    }
+}
+
+/// Primera regla que bloquea una reserva que empiece en `inicio`
+pub fn blocking_rule(reglas: &[ReglaBloqueo], inicio: NaiveDateTime) -> Option<&ReglaBloqueo> {
+    reglas.iter().find(|regla| rule_blocks(regla, inicio))
+}
+
+/// Distribución de mesas que se usa en una fecha
+///
+/// Una distribución con esa fecha concreta tiene prioridad sobre las que se
+/// usan ese día de la semana. `None` si ninguna se usa ese día: se reserva
+/// con todas las mesas.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use mongodb::bson::oid::ObjectId;
+/// use pispas_reservation::availability::active_layout;
+/// use pispas_reservation::db::Distribucion;
+///
+/// let distribucion = |nombre: &str, dias_semana: Vec<u32>, fechas: Vec<&str>| Distribucion {
+///     id: None,
+///     id_restaurante: ObjectId::new(),
+///     nombre: nombre.to_string(),
+///     mesas: Vec::new(),
+///     dias_semana,
+///     fechas: fechas.into_iter().map(str::to_string).collect(),
+///     created_at: 0,
+/// };
+/// let distribuciones = vec![
+///     distribucion("Terraza", vec![5, 6, 7], vec![]),
+///     distribucion("Boda", vec![], vec!["2030-06-15"]),
+/// ];
+/// let dia = |fecha: &str| NaiveDate::parse_from_str(fecha, "%Y-%m-%d").unwrap();
+///
+/// assert_eq!(active_layout(&distribuciones, dia("2030-06-14")).unwrap().nombre, "Terraza"); // viernes
+/// assert_eq!(active_layout(&distribuciones, dia("2030-06-15")).unwrap().nombre, "Boda");
+/// assert!(active_layout(&distribuciones, dia("2030-06-17")).is_none()); // lunes
+/// ```
+pub fn active_layout(distribuciones: &[Distribucion], fecha: NaiveDate) -> Option<&Distribucion> {
+    let texto = fecha.format(FORMATO_FECHA).to_string();
+    distribuciones.iter()
+        .find(|distribucion| distribucion.fechas.contains(&texto))
+        .or_else(|| {
+            let dia = fecha.weekday().number_from_monday();
+            distribuciones.iter().find(|distribucion| distribucion.dias_semana.contains(&dia))
+        })
+}
+
+/// Reparte un grupo en el menor número de reservas de como mucho `maximo`
+/// comensales, lo más parecidas posible
+///
+/// ```
+/// use pispas_reservation::availability::split_party;
+///
+/// assert_eq!(split_party(14, 8), vec![7, 7]);
+/// assert_eq!(split_party(17, 8), vec![6, 6, 5]);
+/// assert_eq!(split_party(5, 8), vec![5]);
+/// ```
+pub fn split_party(personas: i32, maximo: i32) -> Vec<i32> {
+    let maximo = maximo.max(1);
+    let reservas = (personas + maximo - 1) / maximo;
+    (0..reservas)
+        .map(|i| personas / reservas + i32::from(i < personas % reservas))
+        .collect()
+}
@@ -0,0 +1,131 @@
+//! # Comparativa entre restaurantes
+//!
+//! Reglas puras (sin base de datos) de `GET /stats/benchmark`, que compara
+//! las cifras de un restaurante con las de otros de su ciudad. Solo parte de
+//! las estadísticas diarias ([`crate::db::EstadisticaDiaria`]), que no
+//! tienen datos de clientes, y solo entre restaurantes que lo han aceptado
+//! (`configuracion.benchmark`).
+//!
+//! Para que las cifras de los demás no se puedan deducir:
+//!
+//! - Solo cuentan los restaurantes con al menos [`MIN_RESERVAS`] reservas
+//!   en el periodo
+//! - Sin al menos [`MIN_PARTICIPANTES`] de ellos, no se publica nada
+//! - Solo se publican cuartiles y la posición del propio restaurante, nunca
+//!   cifras de un restaurante concreto, y redondeados a dos decimales
+
+use serde::Serialize;
+
+/// Otros restaurantes necesarios para publicar una comparativa
+pub const MIN_PARTICIPANTES: usize = 5;
+
+/// Reservas que necesita un restaurante en el periodo para entrar en la
+/// comparativa de los demás
+pub const MIN_RESERVAS: u64 = 20;
+
+/// Totales de un restaurante en el periodo comparado
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Totales {
+    pub reservas: u64,
+    pub comensales: u64,
+    pub cancelaciones: u64,
+    pub no_presentadas: u64,
+    /// Suma de las plazas (`max_personas`) de las mesas reservables
+    pub plazas: u64,
+}
+
+/// Cifras comparables de un restaurante
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metricas {
+    /// Comensales por plaza y día; `None` sin mesas con capacidad
+    pub ocupacion: Option<f64>,
+    pub comensales_por_dia: f64,
+    /// Parte de las reservas que se cancelaron (0 a 1)
+    pub tasa_cancelacion: f64,
+    /// Parte de las reservas que no se presentaron (0 a 1)
+    pub tasa_no_show: f64,
+}
+
+impl Metricas {
+    /// Cifras de unos totales a lo largo de `dias` días
+    ///
+    /// ```
+    /// use pispas_reservation::benchmark::{Metricas, Totales};
+    ///
+    /// let totales = Totales { reservas: 40, comensales: 120, cancelaciones: 4, no_presentadas: 2, plazas: 20 };
+    /// let metricas = Metricas::new(&totales, 30);
+    ///
+    /// assert_eq!(metricas.comensales_por_dia, 4.0);
+    /// assert_eq!(metricas.ocupacion, Some(0.2));
+    /// assert_eq!(metricas.tasa_cancelacion, 0.1);
+    /// assert_eq!(metricas.tasa_no_show, 0.05);
+    /// ```
+    pub fn new(totales: &Totales, dias: u64) -> Self {
+        let dias = dias.max(1) as f64;
+        let proporcion = |parte: u64| if totales.reservas == 0 { 0.0 } else { parte as f64 / totales.reservas as f64 };
+        let comensales_por_dia = totales.comensales as f64 / dias;
+        Metricas {
+            ocupacion: (totales.plazas > 0).then(|| comensales_por_dia / totales.plazas as f64),
+            comensales_por_dia,
+            tasa_cancelacion: proporcion(totales.cancelaciones),
+            tasa_no_show: proporcion(totales.no_presentadas),
+        }
+    }
+}
+
+/// Posición de una cifra del restaurante entre las de los demás
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Comparacion {
+    pub propio: f64,
+    pub p25: f64,
+    pub mediana: f64,
+    pub p75: f64,
+    /// Porcentaje de los demás con una cifra menor (los empates cuentan la mitad)
+    pub percentil: u8,
+}
+
+/// Percentil `p` (0 a 1) de unas cifras ordenadas, interpolando entre ellas
+fn percentile(ordenadas: &[f64], p: f64) -> f64 {
+    let posicion = p * (ordenadas.len() - 1) as f64;
+    let (abajo, arriba) = (posicion.floor() as usize, posicion.ceil() as usize);
+    ordenadas[abajo] + (ordenadas[arriba] - ordenadas[abajo]) * (posicion - abajo as f64)
+}
+
+/// Redondea a dos decimales
+fn round(valor: f64) -> f64 {
+    (valor * 100.0).round() / 100.0
+}
+
+/// Compara una cifra con las de los demás restaurantes
+///
+/// # Retorna
+/// `None` si hay menos de [`MIN_PARTICIPANTES`] cifras con las que comparar
+///
+/// ```
+/// use pispas_reservation::benchmark::compare;
+///
+/// let comparacion = compare(3.0, &[1.0, 2.0, 3.0, 4.0, 5.0]).unwrap();
+/// assert_eq!((comparacion.p25, comparacion.mediana, comparacion.p75), (2.0, 3.0, 4.0));
+/// assert_eq!(comparacion.percentil, 50);
+///
+/// assert!(compare(3.0, &[1.0, 2.0, 4.0, 5.0]).is_none());
+/// ```
+pub fn compare(propio: f64, otras: &[f64]) -> Option<Comparacion> {
+    if otras.len() < MIN_PARTICIPANTES {
+        return None;
+    }
+    let mut ordenadas = otras.to_vec();
+    ordenadas.sort_by(f64::total_cmp);
+
+    let menores = ordenadas.iter().filter(|otra| **otra < propio).count() as f64;
+    let iguales = ordenadas.iter().filter(|otra| **otra == propio).count() as f64;
+    let percentil = ((menores + iguales / 2.0) * 100.0 / ordenadas.len() as f64).round() as u8;
+
+    Some(Comparacion {
+        propio: round(propio),
+        p25: round(percentile(&ordenadas, 0.25)),
+        mediana: round(percentile(&ordenadas, 0.5)),
+        p75: round(percentile(&ordenadas, 0.75)),
+        percentil,
+    })
+}
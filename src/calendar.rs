@@ -0,0 +1,170 @@
+//! # Calendarios iCalendar
+//!
+//! Reglas puras (sin base de datos) para escribir reservas en formato
+//! iCalendar (RFC 5545, ficheros `.ics`): el adjunto del email de
+//! confirmación, que el cliente añade a su calendario con un clic, y el
+//! calendario de cada restaurante al que el propietario se suscribe desde
+//! Google Calendar u Outlook (ver [`crate::api::calendar`]).
+//!
+//! Con la zona horaria del restaurante, las horas se escriben en UTC
+//! (`20300615T190000Z`), y cada calendario las muestra en la hora local de
+//! quien lo consulta; sin ella, como hora local flotante
+//! (`20300615T210000`, sin zona), que los calendarios muestran tal cual.
+//! Cada evento lleva un `UID` estable, así que al descargar de nuevo el
+//! `.ics` o actualizar la suscripción los cambios de hora sustituyen al
+//! evento anterior en lugar de duplicarlo.
+
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Identificador del producto que genera los calendarios
+const PRODID: &str = "-//Pispas//Reservas//ES";
+
+/// Longitud máxima de una línea, en bytes, antes de plegarla
+const LONGITUD_LINEA: usize = 75;
+
+/// Evento de calendario de una reserva
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventoCalendario {
+    /// Identificador estable del evento
+    pub uid: String,
+    /// Inicio en hora local del restaurante
+    pub inicio: NaiveDateTime,
+    /// Zona horaria del restaurante; sin ella, hora flotante
+    pub zona_horaria: Option<Tz>,
+    pub duracion_minutos: u32,
+    pub resumen: String,
+    pub descripcion: String,
+    pub ubicacion: Option<String>,
+    /// Página de la reserva (`URL`)
+    pub url: Option<String>,
+    /// Si la reserva está cancelada (`STATUS:CANCELLED`)
+    pub cancelado: bool,
+    /// Última modificación (timestamp unix), para `DTSTAMP`
+    pub actualizado: i64,
+}
+
+/// Escapa un texto para una propiedad iCalendar
+///
+/// ```
+/// use pispas_reservation::calendar::escape_text;
+///
+/// assert_eq!(escape_text("Mesa 1, terraza; junto a la ventana\nTrona"), "Mesa 1\\, terraza\\; junto a la ventana\\nTrona");
+/// assert_eq!(escape_text("C:\\ruta"), "C:\\\\ruta");
+/// ```
+pub fn escape_text(texto: &str) -> String {
+    let mut escapado = String::with_capacity(texto.len());
+    for c in texto.chars() {
+        match c {
+            '\\' => escapado.push_str("\\\\"),
+            ';' => escapado.push_str("\\;"),
+            ',' => escapado.push_str("\\,"),
+            '\n' => escapado.push_str("\\n"),
+            '\r' => {}
+            c => escapado.push(c),
+        }
+    }
+    escapado
+}
+
+/// Añade una línea de contenido plegada a 75 bytes y terminada en CRLF
+///
+/// Las continuaciones empiezan por un espacio; nunca se parte un carácter.
+fn push_line(ics: &mut String, linea: &str) {
+    let mut inicio = 0;
+    let mut limite = LONGITUD_LINEA;
+    for (i, c) in linea.char_indices() {
+        if i + c.len_utf8() - inicio > limite {
+            ics.push_str(&linea[inicio..i]);
+            ics.push_str("\r\n ");
+            inicio = i;
+            // El espacio inicial cuenta en la longitud de la continuación
+            limite = LONGITUD_LINEA - 1;
+        }
+    }
+    ics.push_str(&linea[inicio..]);
+    ics.push_str("\r\n");
+}
+
+/// Hora local en la zona dada (en UTC) o flotante
+///
+/// Una hora que no existe en la zona (el salto del cambio de hora) se
+/// escribe flotante.
+fn format_local(momento: NaiveDateTime, zona: Option<Tz>) -> String {
+    match zona.and_then(|zona| zona.from_local_datetime(&momento).earliest()) {
+        Some(local) => local.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string(),
+        None => momento.format("%Y%m%dT%H%M%S").to_string(),
+    }
+}
+
+fn format_utc(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0).unwrap_or_default().format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Calendario iCalendar con los eventos dados
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use pispas_reservation::calendar::{calendar, EventoCalendario};
+///
+/// let evento = EventoCalendario {
+///     uid: "665b1a000000000000000001@pispas".to_string(),
+///     inicio: NaiveDate::from_ymd_opt(2030, 6, 15).unwrap().and_hms_opt(21, 0, 0).unwrap(),
+///     zona_horaria: None,
+///     duracion_minutos: 90,
+///     resumen: "Reserva en La Tasca".to_string(),
+///     descripcion: "4 personas, localizador K7Q2MX9D".to_string(),
+///     ubicacion: None,
+///     url: None,
+///     cancelado: false,
+///     actualizado: 1_900_000_000,
+/// };
+/// let ics = calendar("La Tasca", &[evento.clone()]);
+/// assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+/// assert!(ics.contains("\r\nDTSTART:20300615T210000\r\nDTEND:20300615T223000\r\n"));
+/// assert!(ics.contains("\r\nDESCRIPTION:4 personas\\, localizador K7Q2MX9D\r\n"));
+/// assert!(ics.contains("\r\nSTATUS:CONFIRMED\r\n"));
+/// assert!(ics.ends_with("END:VCALENDAR\r\n"));
+///
+/// // Con la zona del restaurante, en UTC (Madrid en verano es UTC+2)
+/// let madrid = EventoCalendario { zona_horaria: Some(chrono_tz::Europe::Madrid), ..evento.clone() };
+/// let ics = calendar("La Tasca", &[madrid]);
+/// assert!(ics.contains("\r\nDTSTART:20300615T190000Z\r\nDTEND:20300615T203000Z\r\n"));
+///
+/// // Las líneas largas se pliegan sin partir caracteres
+/// let largo = EventoCalendario { descripcion: "ñ".repeat(100), ..evento };
+/// let ics = calendar("La Tasca", &[largo]);
+/// assert!(ics.split("\r\n").all(|linea| linea.len() <= 75));
+/// assert!(ics.replace("\r\n ", "").contains(&format!("DESCRIPTION:{}\r\n", "ñ".repeat(100))));
+/// ```
+pub fn calendar(nombre: &str, eventos: &[EventoCalendario]) -> String {
+    let mut ics = String::new();
+    push_line(&mut ics, "BEGIN:VCALENDAR");
+    push_line(&mut ics, "VERSION:2.0");
+    push_line(&mut ics, &format!("PRODID:{}", PRODID));
+    push_line(&mut ics, "CALSCALE:GREGORIAN");
+    push_line(&mut ics, "METHOD:PUBLISH");
+    push_line(&mut ics, &format!("X-WR-CALNAME:{}", escape_text(nombre)));
+
+    for evento in eventos {
+        let fin = evento.inicio + Duration::minutes(i64::from(evento.duracion_minutos));
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(&mut ics, &format!("UID:{}", evento.uid));
+        push_line(&mut ics, &format!("DTSTAMP:{}", format_utc(evento.actualizado)));
+        push_line(&mut ics, &format!("DTSTART:{}", format_local(evento.inicio, evento.zona_horaria)));
+        push_line(&mut ics, &format!("DTEND:{}", format_local(fin, evento.zona_horaria)));
+        push_line(&mut ics, &format!("SUMMARY:{}", escape_text(&evento.resumen)));
+        push_line(&mut ics, &format!("DESCRIPTION:{}", escape_text(&evento.descripcion)));
+        if let Some(ubicacion) = &evento.ubicacion {
+            push_line(&mut ics, &format!("LOCATION:{}", escape_text(ubicacion)));
+        }
+        if let Some(url) = &evento.url {
+            push_line(&mut ics, &format!("URL:{}", url));
+        }
+        push_line(&mut ics, if evento.cancelado { "STATUS:CANCELLED" } else { "STATUS:CONFIRMED" });
+        push_line(&mut ics, "END:VEVENT");
+    }
+
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
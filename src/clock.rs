@@ -0,0 +1,69 @@
+//! # Reloj de la aplicación
+//!
+//! Abstracción sobre la hora actual. Los handlers reciben el reloj como
+//! `web::Data<dyn Clock>` en lugar de llamar a `chrono::Utc::now()`, de forma
+//! que los tests pueden congelar y avanzar el tiempo con [`FixedClock`] para
+//! probar caducidades, recordatorios y similares.
+
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Mutex;
+
+/// Fuente de la hora actual
+pub trait Clock: Send + Sync {
+    /// Instante actual en UTC
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Instante actual como timestamp unix (segundos)
+    fn timestamp(&self) -> i64 {
+        self.now().timestamp()
+    }
+}
+
+/// Reloj real del sistema
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// Reloj congelado que solo avanza cuando se le indica
+///
+/// # Ejemplo
+/// ```
+/// use chrono::{Duration, TimeZone, Utc};
+/// use pispas_reservation::clock::{Clock, FixedClock};
+///
+/// let clock = FixedClock::new(Utc.with_ymd_and_hms(2030, 6, 15, 20, 0, 0).unwrap());
+/// clock.advance(Duration::minutes(30));
+/// assert_eq!(clock.now(), Utc.with_ymd_and_hms(2030, 6, 15, 20, 30, 0).unwrap());
+/// ```
+#[derive(Debug)]
+pub struct FixedClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl FixedClock {
+    /// Crea un reloj detenido en el instante indicado
+    pub fn new(now: DateTime<Utc>) -> Self {
+        FixedClock { now: Mutex::new(now) }
+    }
+
+    /// Mueve el reloj a un instante concreto
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Avanza el reloj la duración indicada
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
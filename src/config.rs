@@ -0,0 +1,161 @@
+//! # Configuración del servidor
+//!
+//! Direcciones en las que escucha el servidor HTTP ([`ConfigServidor`]):
+//!
+//! - `BIND_ADDRESS`: Una o varias direcciones TCP separadas por comas
+//!   (default: `0.0.0.0:8080`). Las IPv6 van entre corchetes:
+//!   `0.0.0.0:8080,[::]:8080`
+//! - `BIND_UNIX_SOCKET`: Ruta de un socket Unix en el que escuchar además,
+//!   p. ej. como upstream de nginx (default: sin socket). Si ya existe un
+//!   socket en esa ruta, de un arranque anterior, se sustituye
+//!
+//! Con `BIND_UNIX_SOCKET` y sin `BIND_ADDRESS`, el servidor escucha solo en el
+//! socket.
+//!
+//! Y dónde se sirve el frontend ([`ConfigFrontend`]).
+
+use std::env;
+use std::path::PathBuf;
+
+/// Dirección TCP por defecto
+pub const DIRECCION_POR_DEFECTO: &str = "0.0.0.0:8080";
+
+/// Direcciones en las que escucha el servidor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigServidor {
+    /// Direcciones TCP (`host:puerto`)
+    pub direcciones: Vec<String>,
+    /// Socket Unix
+    pub socket_unix: Option<PathBuf>,
+}
+
+impl ConfigServidor {
+    /// Lee `BIND_ADDRESS` y `BIND_UNIX_SOCKET`
+    ///
+    /// # Errores
+    /// Descripción de la primera dirección inválida de `BIND_ADDRESS`
+    pub fn from_env() -> Result<Self, String> {
+        let socket_unix = env::var("BIND_UNIX_SOCKET")
+            .ok()
+            .map(|ruta| ruta.trim().to_string())
+            .filter(|ruta| !ruta.is_empty())
+            .map(PathBuf::from);
+        let direcciones = match env::var("BIND_ADDRESS") {
+            Ok(valor) => parse_addresses(&valor)?,
+            Err(_) if socket_unix.is_some() => Vec::new(),
+            Err(_) => vec![DIRECCION_POR_DEFECTO.to_string()],
+        };
+        if direcciones.is_empty() && socket_unix.is_none() {
+            return Err("BIND_ADDRESS no tiene ninguna dirección".to_string());
+        }
+
+        Ok(ConfigServidor { direcciones, socket_unix })
+    }
+}
+
+/// Separa y valida una lista de direcciones TCP separadas por comas
+///
+/// Cada dirección es `host:puerto`, con las IPv6 entre corchetes. Las
+/// repetidas y las vacías se ignoran.
+///
+/// ```
+/// use pispas_reservation::config::parse_addresses;
+///
+/// assert_eq!(
+///     parse_addresses("0.0.0.0:8080, [::]:8080,localhost:9000,").unwrap(),
+///     vec!["0.0.0.0:8080", "[::]:8080", "localhost:9000"],
+/// );
+/// assert_eq!(parse_addresses("127.0.0.1:80,127.0.0.1:80").unwrap(), vec!["127.0.0.1:80"]);
+/// assert!(parse_addresses("0.0.0.0").is_err());
+/// assert!(parse_addresses("::1:8080").is_err());
+/// assert!(parse_addresses("[::1]:99999").is_err());
+/// ```
+pub fn parse_addresses(valor: &str) -> Result<Vec<String>, String> {
+    let mut direcciones: Vec<String> = Vec::new();
+    for direccion in valor.split(',').map(str::trim).filter(|direccion| !direccion.is_empty()) {
+        let valida = match direccion.rsplit_once(':') {
+            Some((host, puerto)) => {
+                let host_valido = match host.strip_prefix('[').and_then(|host| host.strip_suffix(']')) {
+                    Some(ipv6) => ipv6.parse::<std::net::Ipv6Addr>().is_ok(),
+                    None => !host.is_empty() && !host.contains(':'),
+                };
+                host_valido && puerto.parse::<u16>().is_ok()
+            }
+            None => false,
+        };
+        if !valida {
+            return Err(format!(
+                "Dirección inválida en BIND_ADDRESS: '{}' (formato host:puerto, IPv6 entre corchetes)",
+                direccion
+            ));
+        }
+        if !direcciones.iter().any(|existente| existente == direccion) {
+            direcciones.push(direccion.to_string());
+        }
+    }
+    Ok(direcciones)
+}
+
+/// Ruta por defecto del frontend
+pub const MONTAJE_POR_DEFECTO: &str = "/static";
+
+/// Dónde y desde dónde se sirve el frontend (ver [`crate::frontend`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigFrontend {
+    /// Ruta bajo la que se sirve (`/static`), sin barra final
+    pub montaje: String,
+    /// Directorio con el frontend compilado. Sin él, con la feature
+    /// `embed-frontend`, se sirve el incluido en el binario
+    pub directorio: Option<PathBuf>,
+}
+
+impl ConfigFrontend {
+    /// Lee `FRONTEND_MOUNT` (default: `/static`) y `FRONTEND_DIR` (default:
+    /// `./static`, o el frontend incluido en el binario con la feature
+    /// `embed-frontend`)
+    ///
+    /// Un `FRONTEND_MOUNT` inválido, incluida la raíz (que taparía la API),
+    /// se ignora con un aviso. Tampoco debe coincidir con el prefijo de
+    /// ninguna ruta de la API (`/public`, `/reservations`...).
+    pub fn from_env() -> Self {
+        let montaje = match env::var("FRONTEND_MOUNT") {
+            Ok(valor) => normalize_mount(&valor).unwrap_or_else(|| {
+                tracing::warn!("FRONTEND_MOUNT inválido ('{}'), se usa {}", valor, MONTAJE_POR_DEFECTO);
+                MONTAJE_POR_DEFECTO.to_string()
+            }),
+            Err(_) => MONTAJE_POR_DEFECTO.to_string(),
+        };
+        let directorio = env::var("FRONTEND_DIR").ok().map(PathBuf::from);
+        let directorio = if cfg!(feature = "embed-frontend") {
+            directorio
+        } else {
+            Some(directorio.unwrap_or_else(|| PathBuf::from("./static")))
+        };
+
+        ConfigFrontend { montaje, directorio }
+    }
+}
+
+/// Normaliza la ruta de montaje del frontend: empieza por `/`, sin barra
+/// final y sin segmentos vacíos ni `..`. La raíz no es válida
+///
+/// ```
+/// use pispas_reservation::config::normalize_mount;
+///
+/// assert_eq!(normalize_mount("/static").as_deref(), Some("/static"));
+/// assert_eq!(normalize_mount(" app/ ").as_deref(), Some("/app"));
+/// assert_eq!(normalize_mount("/panel/v2").as_deref(), Some("/panel/v2"));
+/// assert_eq!(normalize_mount("/"), None);
+/// assert_eq!(normalize_mount("/a//b"), None);
+/// assert_eq!(normalize_mount("/../etc"), None);
+/// ```
+pub fn normalize_mount(valor: &str) -> Option<String> {
+    let valor = valor.trim().trim_start_matches('/').trim_end_matches('/');
+    let valido = !valor.is_empty()
+        && valor.split('/').all(|segmento| {
+            !segmento.is_empty()
+                && segmento != ".."
+                && segmento.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        });
+    valido.then(|| format!("/{}", valor))
+}
@@ -0,0 +1,64 @@
+//! # Compatibilidad entre versiones
+//!
+//! Durante un despliegue gradual conviven instancias de la versión anterior
+//! y de la nueva contra la misma base de datos, así que cada versión tiene
+//! que leer lo que escribe la otra:
+//!
+//! - Los campos desconocidos se ignoran: ningún modelo usa
+//!   `deny_unknown_fields`
+//! - Todo campo nuevo lleva `#[serde(default)]` (o un `default = "..."`
+//!   explícito) o es `Option`, para leer los documentos que no lo tienen
+//! - Un valor desconocido en la configuración del restaurante no impide
+//!   cargarlo (ver [`configuracion`]): sin él fallarían el login y todas sus
+//!   rutas
+//! - Un valor nuevo de un estado ([`super::EstadoReserva`],
+//!   [`super::EstadoCuenta`], [`super::Rol`]...) se despliega en dos
+//!   versiones: primero la que sabe leerlo y, cuando ya no queda ninguna
+//!   instancia anterior, la que lo escribe
+//!
+//! `tests/schema_compat.rs` comprueba estas reglas con documentos de
+//! versiones anteriores y posteriores.
+
+use mongodb::bson::{from_bson, from_document, Bson, Document};
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer};
+use super::{CampoTicket, Configuracion, ContadorInsignia, MetodoVerificacion, PoliticaGruposGrandes, ReglaAlerta};
+
+/// Lee la configuración de un restaurante guardada por cualquier versión
+///
+/// Si no se puede leer tal cual, descarta los valores que esta versión no
+/// conoce: los métodos de verificación y políticas de grupos desconocidos
+/// vuelven a su valor por defecto, y las reglas de alerta, campos del
+/// ticket y contadores de la insignia desconocidos se omiten. Lo que se guarda con `PUT
+/// /restaurants/settings` sí se valida estrictamente.
+pub fn configuracion<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Configuracion, D::Error> {
+    let mut documento = Document::deserialize(deserializer)?;
+    if let Ok(configuracion) = from_document(documento.clone()) {
+        return Ok(configuracion);
+    }
+
+    remove_invalid::<MetodoVerificacion>(&mut documento, "verificacion_cliente");
+    remove_invalid::<PoliticaGruposGrandes>(&mut documento, "grupos_grandes");
+    retain_valid::<ReglaAlerta>(&mut documento, "alertas");
+    if let Ok(ticket) = documento.get_document_mut("ticket") {
+        retain_valid::<CampoTicket>(ticket, "campos");
+    }
+    if let Ok(insignia) = documento.get_document_mut("insignia") {
+        retain_valid::<ContadorInsignia>(insignia, "contadores");
+    }
+    from_document(documento).map_err(D::Error::custom)
+}
+
+/// Quita `campo` si su valor no es un `T` válido, para que tome el default
+fn remove_invalid<T: DeserializeOwned>(documento: &mut Document, campo: &str) {
+    if documento.get(campo).is_some_and(|valor| from_bson::<T>(valor.clone()).is_err()) {
+        documento.remove(campo);
+    }
+}
+
+/// Deja en la lista `campo` solo los elementos que son un `T` válido
+fn retain_valid<T: DeserializeOwned>(documento: &mut Document, campo: &str) {
+    if let Some(Bson::Array(elementos)) = documento.get_mut(campo) {
+        elementos.retain(|elemento| from_bson::<T>(elemento.clone()).is_ok());
+    }
+}
@@ -1,8 +1,14 @@
 // src/db/mod.rs
+pub mod compat;
 pub mod models;
 pub mod mongodb;
 
-pub use mongodb::{MongoRepo, Restaurant, Mesa, Reserva};
+pub use mongodb::{
+    MongoRepo, Restaurant, EstadoCuenta, BorradoPendiente, Configuracion, DuracionGrupo, ConfigDeposito, PoliticaCancelacion, ToleranciaRetraso, AccionRetraso, ConfigInsignia, ContadorInsignia, ReglasRiesgo, LimitesReserva, PoliticaGruposGrandes, PlantillaTicket, CampoTicket, ReglaAlerta, AlertaEnviada, Turno, MetodoVerificacion, Rol, Alcance, Empleado,
+    Mesa, Planta, Distribucion, Reserva, EstadoReserva, RetencionLegal, SeleccionMenu, Deposito, PartePago, Pago, EstadoPago, VerificacionCliente, ViolacionWidget,
+    OpcionMenu, ReglaBloqueo, Cliente, FusionClientes,
+    InformeAnonimizacion, RecuentoRestaurante, TrabajoReconstruccion, EstadoReconstruccion, ProgresoColeccion, Reconstruccion, EstadisticaDiaria, SnapshotPlano, MesasBorradas, ConfirmacionBorrado, SolicitudGrupo, EstadoSolicitudGrupo, RegistroTurno, NotaTraspaso, TokenRecuperacion, Reclamacion, IntentoLogin, ConsultasLocalizador, FallosLocalizador, Sesion, Suplantacion, ClaveApi, SuscripcionWebhook, PlantillaWebhook, MensajeReserva, AutorMensaje, WebhookRecibido, PeticionIdempotente, EstadoOAuth, Evento, EntradaAuditoria, EventoReserva, CambioCampo, EntradaDiario, ModoPersistencia, Checkpoint, EstadoPlataforma, localizador, normalize_name, is_duplicate_key, LONGITUD_LOCALIZADOR,
+};
 
 // Re-exports para compatibilidad
 pub use MongoRepo as Database;
\ No newline at end of file
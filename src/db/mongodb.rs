@@ -1,6 +1,9 @@
 use mongodb::{Client, Collection, Database};
 use serde::{Deserialize, Serialize};
 use std::env;
+use chrono::{DateTime, Duration, NaiveDateTime};
+use chrono_tz::Tz;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 use crate::api::AppError;
 
 pub type Result<T> = std::result::Result<T, AppError>;
@@ -11,12 +14,757 @@ pub struct Restaurant {
     pub id: Option<mongodb::bson::oid::ObjectId>,
     pub objid_pispas: String,
     pub nombre: String,
+    /// Nombre normalizado (ver [`normalize_name`]) usado para la unicidad
+    #[serde(default)]
+    pub nombre_normalizado: String,
     pub password: String,
     pub confirmar_automaticamente: bool,
+    pub created_at: i64, // timestamp unix
+    /// Configuración editable por el restaurante
+    #[serde(default, deserialize_with = "super::compat::configuracion")]
+    pub configuracion: Configuracion,
+    /// Grupo de locales del mismo propietario, entre los que se pueden
+    /// traspasar reservas
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_grupo: Option<mongodb::bson::oid::ObjectId>,
+    /// Email de la cuenta, al que se envían los enlaces para recuperar la
+    /// contraseña
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Identificador (`sub`) de la cuenta de Google vinculada para iniciar
+    /// sesión con Google (ver [`crate::api::oauth`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub google_sub: Option<String>,
+    /// Estado de la cuenta, que fija la administración (ver
+    /// [`crate::api::account_state`])
+    #[serde(default)]
+    pub estado: EstadoCuenta,
+    /// Motivo del último cambio de estado, mostrado en los errores
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub motivo_estado: Option<String>,
+    /// Token secreto del calendario de reservas, para suscribirse sin
+    /// cabecera `Authorization` (ver [`crate::api::calendar`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_calendario: Option<String>,
+    /// Borrado solicitado y aún sin purgar (ver
+    /// [`crate::api::account_deletion`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub borrado: Option<BorradoPendiente>,
+}
+
+/// Estado de la cuenta de un restaurante
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoCuenta {
+    /// Funcionamiento normal
+    #[default]
+    Activo,
+    /// Sin acceso: se rechaza cualquier petición de la cuenta y de su widget
+    Suspendido,
+    /// Solo consultas: se rechaza cualquier petición que modifique datos
+    SoloLectura,
+    /// Borrado solicitado: sin acceso, como `Suspendido`, hasta que se
+    /// restaura o se purga
+    PendienteBorrado,
+}
+
+impl EstadoCuenta {
+    /// Nombre con el que se guarda el estado
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EstadoCuenta::Activo => "activo",
+            EstadoCuenta::Suspendido => "suspendido",
+            EstadoCuenta::SoloLectura => "solo_lectura",
+            EstadoCuenta::PendienteBorrado => "pendiente_borrado",
+        }
+    }
+}
+
+/// Borrado de un restaurante durante el plazo en el que aún se puede restaurar
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BorradoPendiente {
+    pub solicitado_en: i64, // timestamp unix
+    /// Momento a partir del cual el trabajo de purga lo borra todo (ver
+    /// [`crate::jobs::account_purge`])
+    pub purgar_en: i64, // timestamp unix
+    /// Estado de la cuenta al que vuelve si se restaura
+    pub estado_anterior: EstadoCuenta,
+    /// Si lo pidió la administración en lugar del propietario
+    #[serde(default)]
+    pub por_administracion: bool,
+    /// Token del enlace enviado por email al propietario para restaurarlo
+    pub token_restauracion: String,
+}
+
+/// Configuración por restaurante
+///
+/// Se guarda como sub-documento de [`Restaurant`]; todos los campos tienen
+/// valor por defecto para que los documentos antiguos sigan siendo válidos.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Configuracion {
+    /// Turnos de servicio (comida, cena...) usados para agrupar reservas
+    #[serde(default)]
+    pub turnos: Vec<Turno>,
+    /// Verificación exigida al cliente en las reservas públicas
+    #[serde(default)]
+    pub verificacion_cliente: MetodoVerificacion,
+    /// Minutos que una reserva ocupa su mesa
+    #[serde(default = "default_duracion_reserva")]
+    pub duracion_reserva_minutos: u32,
+    /// Duración de las reservas según su número de comensales, de menor a
+    /// mayor `desde_personas`; los grupos más pequeños que la primera regla
+    /// usan `duracion_reserva_minutos`
+    #[serde(default)]
+    pub duraciones_grupo: Vec<DuracionGrupo>,
+    /// Minutos que la mesa sigue ocupada tras cada reserva para recogerla y
+    /// prepararla para la siguiente
+    #[serde(default)]
+    pub margen_limpieza_minutos: u32,
+    /// Registrar peticiones y respuestas completas (con datos personales
+    /// ocultos) para depurar integraciones
+    #[serde(default)]
+    pub registro_peticiones: bool,
+    /// Email al que se envían las alertas del restaurante
+    #[serde(default)]
+    pub email_alertas: Option<String>,
+    /// Reglas de alerta evaluadas por el monitor (ver [`crate::jobs::alerts`])
+    #[serde(default)]
+    pub alertas: Vec<ReglaAlerta>,
+    /// Webs (`https://latasca.es`) en las que puede usarse el widget; vacía,
+    /// en cualquiera (ver [`crate::api::widget_origin`])
+    #[serde(default)]
+    pub origenes_widget: Vec<String>,
+    /// IPs o rangos CIDR desde los que se admite la API de gestión; vacía,
+    /// desde cualquiera (ver [`crate::api::ip_allowlist`])
+    #[serde(default)]
+    pub ips_permitidas: Vec<String>,
+    /// Formato del ticket que se imprime para cada reserva (ver
+    /// [`crate::ticket`])
+    #[serde(default)]
+    pub ticket: PlantillaTicket,
+    /// Idioma en el que se escribe a los clientes cuando no se sabe el suyo
+    /// (ver [`crate::language`])
+    #[serde(default = "default_idioma")]
+    pub idioma: String,
+    /// Comensales máximos de una reserva desde el widget; sin él, sin límite
+    #[serde(default)]
+    pub max_personas_online: Option<i32>,
+    /// Qué hacer con las reservas del widget que superan `max_personas_online`
+    #[serde(default)]
+    pub grupos_grandes: PoliticaGruposGrandes,
+    /// Ciudad del restaurante, con la que se eligen los restaurantes de la
+    /// comparativa
+    #[serde(default)]
+    pub ciudad: Option<String>,
+    /// Dirección del restaurante, para los calendarios de los clientes
+    #[serde(default)]
+    pub direccion: Option<String>,
+    /// Zona horaria IANA del restaurante (`Europe/Madrid`), en la que se
+    /// interpretan la fecha y hora de las reservas (ver [`Configuracion::hora_local`])
+    /// y se escriben en los calendarios (ver [`crate::calendar`])
+    #[serde(default)]
+    pub zona_horaria: Option<String>,
+    /// Participar en la comparativa anónima con otros restaurantes de la
+    /// misma ciudad (ver [`crate::benchmark`])
+    #[serde(default)]
+    pub benchmark: bool,
+    /// Días a partir de hoy que lista `GET /reservations` cuando no se le
+    /// piden fechas
+    #[serde(default = "default_horizonte_listado")]
+    pub horizonte_listado_dias: u32,
+    /// Pago por adelantado exigido a los grupos grandes; sin él, ninguno
+    #[serde(default)]
+    pub deposito: Option<ConfigDeposito>,
+    /// Plazos para cancelar y modificar reservas; sin ella, sin plazos
+    #[serde(default)]
+    pub politica_cancelacion: Option<PoliticaCancelacion>,
+    /// Reglas de la puntuación de riesgo de no presentarse que acompaña a
+    /// las reservas en los listados
+    #[serde(default)]
+    pub riesgo: ReglasRiesgo,
+    /// Horas antes de la reserva a las que se envía al cliente un email de
+    /// recordatorio; sin ellas, no se envían (ver [`crate::jobs::reminders`])
+    #[serde(default)]
+    pub recordatorio_horas: Option<u32>,
+    /// Límites con los que se validan los datos de las reservas
+    #[serde(default)]
+    pub limites: LimitesReserva,
+    /// Qué hacer con las reservas confirmadas cuyo cliente no llega; sin
+    /// ella, nada (ver [`crate::jobs::late_arrivals`])
+    #[serde(default)]
+    pub tolerancia_retraso: Option<ToleranciaRetraso>,
+    /// Contadores que el restaurante publica en su insignia; sin ella,
+    /// ninguno (ver [`crate::api::badge`])
+    #[serde(default)]
+    pub insignia: Option<ConfigInsignia>,
+}
+
+fn default_duracion_reserva() -> u32 {
+    90
+}
+
+/// Duración de las reservas de los grupos a partir de un tamaño
+///
+/// ```json
+/// { "desde_personas": 3, "minutos": 90 }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DuracionGrupo {
+    /// Comensales a partir de los que se aplica
+    pub desde_personas: i32,
+    /// Minutos que la reserva ocupa su mesa
+    pub minutos: u32,
+}
+
+impl Configuracion {
+    /// Minutos que una reserva de `personas` comensales ocupa su mesa: los
+    /// de la regla de `duraciones_grupo` con el mayor `desde_personas` que
+    /// alcanza o, sin ninguna, `duracion_reserva_minutos`
+    ///
+    /// ```
+    /// use pispas_reservation::db::{Configuracion, DuracionGrupo};
+    ///
+    /// let configuracion = Configuracion {
+    ///     duracion_reserva_minutos: 60,
+    ///     duraciones_grupo: vec![
+    ///         DuracionGrupo { desde_personas: 3, minutos: 90 },
+    ///         DuracionGrupo { desde_personas: 7, minutos: 120 },
+    ///     ],
+    ///     ..Configuracion::default()
+    /// };
+    /// assert_eq!(configuracion.duracion_minutos(2), 60);
+    /// assert_eq!(configuracion.duracion_minutos(6), 90);
+    /// assert_eq!(configuracion.duracion_minutos(12), 120);
+    /// ```
+    pub fn duracion_minutos(&self, personas: i32) -> u32 {
+        self.duraciones_grupo.iter()
+            .filter(|regla| regla.desde_personas <= personas)
+            .max_by_key(|regla| regla.desde_personas)
+            .map_or(self.duracion_reserva_minutos, |regla| regla.minutos)
+    }
+
+    /// Minutos que una reserva de `personas` comensales bloquea su mesa
+    /// para otras: su duración más el margen de limpieza
+    ///
+    /// ```
+    /// use pispas_reservation::db::Configuracion;
+    ///
+    /// let configuracion = Configuracion { margen_limpieza_minutos: 15, ..Configuracion::default() };
+    /// assert_eq!(configuracion.ocupacion_minutos(2), 105);
+    /// assert_eq!(Configuracion::default().ocupacion_minutos(2), 90);
+    /// ```
+    pub fn ocupacion_minutos(&self, personas: i32) -> u32 {
+        self.duracion_minutos(personas) + self.margen_limpieza_minutos
+    }
+
+    /// Zona horaria del restaurante, si tiene una válida
+    pub fn zona(&self) -> Option<Tz> {
+        self.zona_horaria.as_deref().and_then(|zona| zona.parse().ok())
+    }
+
+    /// Fecha y hora del restaurante en el timestamp unix `momento`, para
+    /// compararla con la fecha y hora de sus reservas; sin zona horaria
+    /// válida se toma UTC
+    ///
+    /// ```
+    /// use pispas_reservation::db::Configuracion;
+    ///
+    /// let madrid = Configuracion { zona_horaria: Some("Europe/Madrid".to_string()), ..Configuracion::default() };
+    /// // 2030-06-01 12:00 UTC
+    /// assert_eq!(madrid.hora_local(1_906_545_600).to_string(), "2030-06-01 14:00:00");
+    /// assert_eq!(Configuracion::default().hora_local(1_906_545_600).to_string(), "2030-06-01 12:00:00");
+    /// ```
+    pub fn hora_local(&self, momento: i64) -> NaiveDateTime {
+        let instante = DateTime::from_timestamp(momento, 0).unwrap_or_default();
+        match self.zona() {
+            Some(zona) => instante.with_timezone(&zona).naive_local(),
+            None => instante.naive_utc(),
+        }
+    }
+}
+
+fn default_horizonte_listado() -> u32 {
+    30
+}
+
+fn default_idioma() -> String {
+    "es".to_string()
+}
+
+impl Default for Configuracion {
+    fn default() -> Self {
+        Configuracion {
+            turnos: Vec::new(),
+            verificacion_cliente: MetodoVerificacion::default(),
+            duracion_reserva_minutos: default_duracion_reserva(),
+            duraciones_grupo: Vec::new(),
+            margen_limpieza_minutos: 0,
+            registro_peticiones: false,
+            email_alertas: None,
+            alertas: Vec::new(),
+            origenes_widget: Vec::new(),
+            ips_permitidas: Vec::new(),
+            ticket: PlantillaTicket::default(),
+            idioma: default_idioma(),
+            max_personas_online: None,
+            grupos_grandes: PoliticaGruposGrandes::default(),
+            ciudad: None,
+            direccion: None,
+            zona_horaria: None,
+            benchmark: false,
+            horizonte_listado_dias: default_horizonte_listado(),
+            deposito: None,
+            politica_cancelacion: None,
+            riesgo: ReglasRiesgo::default(),
+            recordatorio_horas: None,
+            limites: LimitesReserva::default(),
+            tolerancia_retraso: None,
+            insignia: None,
+        }
+    }
+}
+
+/// Insignia pública del restaurante
+///
+/// ```json
+/// { "contadores": ["reservas", "comensales"] }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConfigInsignia {
+    /// Contadores que se publican, en el orden en que se muestran
+    pub contadores: Vec<ContadorInsignia>,
+}
+
+/// Contador agregado que se puede publicar en la insignia
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ContadorInsignia {
+    /// Reservas gestionadas: todas salvo las que el cliente no llegó a verificar
+    Reservas,
+    /// Comensales atendidos: los de las reservas sentadas o completadas
+    Comensales,
+}
+
+impl ContadorInsignia {
+    /// Nombre con el que se guarda el contador
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContadorInsignia::Reservas => "reservas",
+            ContadorInsignia::Comensales => "comensales",
+        }
+    }
+}
+
+/// Formato del ticket de una reserva
+///
+/// ```json
+/// { "ancho": 32, "cabecera": "La Tasca", "campos": ["hora", "nombre", "personas", "mesa"], "pie": "¡Buen provecho!", "cortar": true }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PlantillaTicket {
+    /// Caracteres por línea (32 en papel de 58 mm, 48 en papel de 80 mm)
+    #[serde(default = "default_ancho_ticket")]
+    pub ancho: u8,
+    /// Texto de la cabecera; sin él, el nombre del restaurante
+    #[serde(default)]
+    pub cabecera: Option<String>,
+    /// Datos de la reserva que se imprimen, en este orden
+    #[serde(default = "default_campos_ticket")]
+    pub campos: Vec<CampoTicket>,
+    /// Texto del pie
+    #[serde(default)]
+    pub pie: Option<String>,
+    /// Cortar el papel al terminar
+    #[serde(default = "default_cortar_ticket")]
+    pub cortar: bool,
+}
+
+fn default_ancho_ticket() -> u8 {
+    42
+}
+
+fn default_campos_ticket() -> Vec<CampoTicket> {
+    vec![
+        CampoTicket::Hora,
+        CampoTicket::Nombre,
+        CampoTicket::Personas,
+        CampoTicket::Mesa,
+        CampoTicket::Preseleccion,
+    ]
+}
+
+fn default_cortar_ticket() -> bool {
+    true
+}
+
+impl Default for PlantillaTicket {
+    fn default() -> Self {
+        PlantillaTicket {
+            ancho: default_ancho_ticket(),
+            cabecera: None,
+            campos: default_campos_ticket(),
+            pie: None,
+            cortar: default_cortar_ticket(),
+        }
+    }
+}
+
+/// Dato de la reserva que puede aparecer en su ticket
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CampoTicket {
+    Nombre,
+    Telefono,
+    Personas,
+    Fecha,
+    Hora,
+    Mesa,
+    Localizador,
+    /// Opciones de menú elegidas por adelantado, una por línea
+    Preseleccion,
+}
+
+/// Regla de alerta de un restaurante
+///
+/// ```json
+/// { "tipo": "cancelaciones", "max": 5, "ventana_minutos": 60 }
+/// { "tipo": "confirmacion_lenta", "max_mediana_minutos": 30, "ventana_horas": 24 }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "tipo", rename_all = "snake_case")]
+pub enum ReglaAlerta {
+    /// Más de `max` reservas canceladas en los últimos `ventana_minutos`
+    Cancelaciones { max: u32, ventana_minutos: u32 },
+    /// Mediana de la espera de las reservas confirmadas en las últimas
+    /// `ventana_horas` por encima de `max_mediana_minutos` (ver [`crate::sla`])
+    ConfirmacionLenta { max_mediana_minutos: u32, ventana_horas: u32 },
+}
+
+/// Método de verificación del cliente en reservas públicas
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MetodoVerificacion {
+    /// Sin verificación: la reserva pasa directamente a pendiente/confirmada
+    #[default]
+    Ninguna,
+    /// Enlace mágico enviado al email del cliente
+    Email,
+    /// Código enviado por SMS al teléfono del cliente
+    Telefono,
+}
+
+/// Pago por adelantado que el restaurante exige a los grupos grandes
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigDeposito {
+    /// Comensales a partir de los que se exige
+    pub min_personas: i32,
+    /// Importe por comensal, en céntimos
+    pub importe_por_persona_centimos: i64,
+}
+
+impl ConfigDeposito {
+    /// Pago pendiente que corresponde a una reserva, si tiene que pagar
+    ///
+    /// ```
+    /// use pispas_reservation::db::{ConfigDeposito, EstadoPago};
+    ///
+    /// let config = ConfigDeposito { min_personas: 6, importe_por_persona_centimos: 1000 };
+    /// assert!(config.pago(5).is_none());
+    /// let pago = config.pago(8).unwrap();
+    /// assert_eq!(pago.importe_centimos, 8000);
+    /// assert_eq!(pago.estado, EstadoPago::Pendiente);
+    /// ```
+    pub fn pago(&self, numero_personas: i32) -> Option<Pago> {
+        (numero_personas >= self.min_personas).then(|| Pago {
+            importe_centimos: i64::from(numero_personas) * self.importe_por_persona_centimos,
+            estado: EstadoPago::Pendiente,
+            proveedor: None,
+            referencia: None,
+            pagado_en: None,
+            reembolsado_en: None,
+        })
+    }
+}
+
+/// Plazos para cancelar y modificar una reserva antes de su hora
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PoliticaCancelacion {
+    /// Horas antes de la reserva a partir de las que cancelar es tardío
+    pub horas_cancelacion: u32,
+    /// Horas antes de la reserva a partir de las que ya no se cambian su
+    /// fecha, hora o comensales; sin él, hasta la hora de la reserva
+    #[serde(default)]
+    pub horas_modificacion: Option<u32>,
+    /// Aceptar las cancelaciones tardías marcándolas como tales, en lugar
+    /// de rechazarlas
+    #[serde(default)]
+    pub marcar_tardias: bool,
+}
+
+impl PoliticaCancelacion {
+    /// Indica si cancelar en `ahora` una reserva que empieza en `inicio` es
+    /// tardío
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use pispas_reservation::db::PoliticaCancelacion;
+    ///
+    /// let politica = PoliticaCancelacion { horas_cancelacion: 2, horas_modificacion: None, marcar_tardias: false };
+    /// let inicio = NaiveDate::from_ymd_opt(2030, 6, 15).unwrap().and_hms_opt(21, 0, 0).unwrap();
+    /// let dia = inicio.date();
+    /// assert!(!politica.cancelacion_tardia(inicio, dia.and_hms_opt(19, 0, 0).unwrap()));
+    /// assert!(politica.cancelacion_tardia(inicio, dia.and_hms_opt(19, 1, 0).unwrap()));
+    /// ```
+    pub fn cancelacion_tardia(&self, inicio: NaiveDateTime, ahora: NaiveDateTime) -> bool {
+        ahora > inicio - Duration::hours(i64::from(self.horas_cancelacion))
+    }
+
+    /// Indica si en `ahora` ya ha pasado el plazo para modificar una reserva
+    /// que empieza en `inicio`
+    pub fn modificacion_tardia(&self, inicio: NaiveDateTime, ahora: NaiveDateTime) -> bool {
+        ahora > inicio - Duration::hours(i64::from(self.horas_modificacion.unwrap_or(0)))
+    }
+}
+
+/// Reglas con las que se puntúa el riesgo de que una reserva no se presente
+///
+/// Cada factor suma sus puntos a la puntuación, que va de 0 a 100 (ver
+/// [`crate::risk`]); con 0 puntos el factor no cuenta.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct ReglasRiesgo {
+    /// Puntos por cada reserva anterior del cliente a la que no se presentó
+    pub puntos_por_no_show: u32,
+    /// Comensales a partir de los que un grupo es muy grande
+    pub personas_grupo_grande: i32,
+    pub puntos_grupo_grande: u32,
+    /// Horas de antelación por debajo de las que una reserva es de última hora
+    pub horas_ultimo_momento: u32,
+    pub puntos_ultimo_momento: u32,
+    /// Puntos si no hay un teléfono al que llamar al cliente
+    pub puntos_telefono_inalcanzable: u32,
+    /// No-shows previos a partir de los que las reservas del widget no se
+    /// confirman solas, para que el personal llame al cliente o le pida un
+    /// depósito; sin él, ninguna se retiene
+    pub no_shows_revision: Option<u32>,
+}
+
+impl Default for ReglasRiesgo {
+    fn default() -> Self {
+        ReglasRiesgo {
+            puntos_por_no_show: 30,
+            personas_grupo_grande: 8,
+            puntos_grupo_grande: 15,
+            horas_ultimo_momento: 2,
+            puntos_ultimo_momento: 15,
+            puntos_telefono_inalcanzable: 25,
+            no_shows_revision: None,
+        }
+    }
+}
+
+/// Límites de los datos de una reserva, del panel o del widget
+///
+/// Los valores por defecto son los que se aplicaban antes de que cada
+/// restaurante pudiera cambiarlos.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct LimitesReserva {
+    /// Comensales máximos de una reserva; sin él, los de la mesa
+    pub max_personas: Option<i32>,
+    /// Caracteres máximos de cada nota de una reserva
+    pub longitud_maxima_notas: u32,
+    /// Minutos de antelación mínima con los que se reserva; sin ella, se
+    /// admiten reservas en el pasado (para anotar las ya atendidas)
+    pub antelacion_minima_minutos: Option<u32>,
+    /// Días máximos de antelación con los que se reserva; sin ellos, sin límite
+    pub antelacion_maxima_dias: Option<u32>,
+    /// Exigir el teléfono del cliente
+    pub telefono_obligatorio: bool,
+}
+
+impl Default for LimitesReserva {
+    fn default() -> Self {
+        LimitesReserva {
+            max_personas: None,
+            longitud_maxima_notas: 500,
+            antelacion_minima_minutos: None,
+            antelacion_maxima_dias: None,
+            telefono_obligatorio: true,
+        }
+    }
+}
+
+impl LimitesReserva {
+    /// Indica si en `ahora` se puede reservar para `inicio`
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use pispas_reservation::db::LimitesReserva;
+    ///
+    /// let limites = LimitesReserva {
+    ///     antelacion_minima_minutos: Some(60),
+    ///     antelacion_maxima_dias: Some(30),
+    ///     ..LimitesReserva::default()
+    /// };
+    /// let ahora = NaiveDate::from_ymd_opt(2030, 6, 1).unwrap().and_hms_opt(12, 0, 0).unwrap();
+    /// let dia = ahora.date();
+    /// assert!(limites.dentro_de_plazo(dia.and_hms_opt(13, 0, 0).unwrap(), ahora));
+    /// assert!(!limites.dentro_de_plazo(dia.and_hms_opt(12, 59, 0).unwrap(), ahora));
+    /// assert!(limites.dentro_de_plazo(NaiveDate::from_ymd_opt(2030, 7, 1).unwrap().and_hms_opt(12, 0, 0).unwrap(), ahora));
+    /// assert!(!limites.dentro_de_plazo(NaiveDate::from_ymd_opt(2030, 7, 1).unwrap().and_hms_opt(12, 1, 0).unwrap(), ahora));
+    ///
+    /// // Sin plazos, cualquier momento, también en el pasado
+    /// assert!(LimitesReserva::default().dentro_de_plazo(dia.and_hms_opt(9, 0, 0).unwrap(), ahora));
+    /// ```
+    pub fn dentro_de_plazo(&self, inicio: NaiveDateTime, ahora: NaiveDateTime) -> bool {
+        let pronto = self.antelacion_minima_minutos
+            .is_some_and(|minutos| inicio < ahora + Duration::minutes(i64::from(minutos)));
+        let tarde = self.antelacion_maxima_dias
+            .is_some_and(|dias| inicio > ahora + Duration::days(i64::from(dias)));
+        !pronto && !tarde
+    }
+}
+
+/// Plazo de cortesía para los clientes que llegan tarde
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ToleranciaRetraso {
+    /// Minutos tras la hora de la reserva que se espera al cliente
+    pub minutos: u32,
+    /// Qué hacer con la reserva pasado el plazo
+    #[serde(default)]
+    pub accion: AccionRetraso,
+}
+
+impl ToleranciaRetraso {
+    /// Indica si en `ahora` ya ha pasado el plazo de una reserva que
+    /// empieza en `inicio`
+    ///
+    /// ```
+    /// use chrono::NaiveDate;
+    /// use pispas_reservation::db::{AccionRetraso, ToleranciaRetraso};
+    ///
+    /// let tolerancia = ToleranciaRetraso { minutos: 15, accion: AccionRetraso::NoShow };
+    /// let inicio = NaiveDate::from_ymd_opt(2030, 6, 15).unwrap().and_hms_opt(21, 0, 0).unwrap();
+    /// let dia = inicio.date();
+    /// assert!(!tolerancia.vencida(inicio, dia.and_hms_opt(21, 15, 0).unwrap()));
+    /// assert!(tolerancia.vencida(inicio, dia.and_hms_opt(21, 16, 0).unwrap()));
+    /// ```
+    pub fn vencida(&self, inicio: NaiveDateTime, ahora: NaiveDateTime) -> bool {
+        ahora > inicio + Duration::minutes(i64::from(self.minutos))
+    }
+}
+
+/// Qué se hace con una reserva cuyo cliente no ha llegado en el plazo
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AccionRetraso {
+    /// Marcarla como no presentada, con el no-show en la ficha del cliente
+    #[default]
+    NoShow,
+    /// Cancelarla para que su mesa vuelva a estar disponible, sin contarla
+    /// como no-show
+    Liberar,
+}
+
+/// Qué hace el widget con los grupos más grandes que el máximo online
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PoliticaGruposGrandes {
+    /// Rechazar la reserva explicando cómo repartir el grupo o contactar
+    #[default]
+    Rechazar,
+    /// Guardar una solicitud de grupo para que la gestione el personal
+    Solicitud,
+}
+
+/// Rol de una cuenta de personal del restaurante
+///
+/// Ordenados de menos a más permisos; el token del propio restaurante
+/// actúa como `Propietario` (ver [`crate::api::staff`]).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Rol {
+    /// Gestiona reservas: crear, listar, confirmar y cancelar
+    Camarero,
+    /// Además gestiona el plano, el menú, los clientes y los bloqueos
+    Encargado,
+    /// Además cambia la configuración y gestiona al personal
+    Propietario,
+}
+
+/// Alcance de una clave de API: a qué rutas puede llamar
+///
+/// Una clave sin alcances puede llamar a todo lo que permite su rol; con
+/// alcances, solo a las rutas que exigen uno de ellos (ver
+/// [`crate::api::auth`]).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Alcance {
+    #[serde(rename = "reservations:read")]
+    LeerReservas,
+    #[serde(rename = "reservations:write")]
+    EscribirReservas,
+    #[serde(rename = "tables:read")]
+    LeerMesas,
+    #[serde(rename = "tables:write")]
+    EscribirMesas,
+    #[serde(rename = "customers:read")]
+    LeerClientes,
+    #[serde(rename = "customers:write")]
+    EscribirClientes,
+    #[serde(rename = "menu:read")]
+    LeerMenu,
+    #[serde(rename = "menu:write")]
+    EscribirMenu,
+    #[serde(rename = "shifts:read")]
+    LeerTurnos,
+    #[serde(rename = "shifts:write")]
+    EscribirTurnos,
+    #[serde(rename = "settings:read")]
+    LeerAjustes,
+    #[serde(rename = "settings:write")]
+    EscribirAjustes,
+    #[serde(rename = "stats:read")]
+    LeerEstadisticas,
+}
+
+/// Cuenta de personal de un restaurante, con su propio token de acceso
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Empleado {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Nombre de usuario, único dentro del restaurante
+    pub usuario: String,
+    pub password: String,
+    pub rol: Rol,
     pub access_token: String,
     pub created_at: i64, // timestamp unix
 }
 
+/// Turno de servicio definido por el restaurante
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Turno {
+    /// Nombre del turno ("Comida", "Cena"...)
+    pub nombre: String,
+    /// Hora de inicio (formato HH:MM, incluida)
+    pub hora_inicio: String,
+    /// Hora de fin (formato HH:MM, excluida). Si es menor que el inicio,
+    /// el turno cruza la medianoche.
+    pub hora_fin: String,
+}
+
+impl Turno {
+    /// Indica si una hora (formato HH:MM) cae dentro del turno
+    pub fn contiene(&self, hora: &str) -> bool {
+        if self.hora_inicio <= self.hora_fin {
+            self.hora_inicio.as_str() <= hora && hora < self.hora_fin.as_str()
+        } else {
+            self.hora_inicio.as_str() <= hora || hora < self.hora_fin.as_str()
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Mesa {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -24,6 +772,9 @@ pub struct Mesa {
     pub id_restaurante: mongodb::bson::oid::ObjectId,
     pub tipo: String,
     pub nombre: String,
+    /// Nombre normalizado (ver [`normalize_name`]) usado para la unicidad
+    #[serde(default)]
+    pub nombre_normalizado: String,
     pub pos_x: f32,
     pub pos_y: f32,
     pub size_x: f32,
@@ -32,67 +783,1700 @@ pub struct Mesa {
     pub reservable: bool,
     pub min_personas: Option<i32>,
     pub max_personas: Option<i32>,
+    /// Planta en la que está la mesa; `None` para la planta principal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_planta: Option<mongodb::bson::oid::ObjectId>,
+    /// UUID generado por el cliente al crear la mesa sin conexión; único
+    /// por restaurante
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Planta (piso) del restaurante, con su propio lienzo en el plano
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Planta {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Nombre visible ("Planta baja", "Primera planta", "Terraza")
+    pub nombre: String,
+    /// Posición de la planta en el selector del plano
+    #[serde(default)]
+    pub orden: i32,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Distribución de mesas con nombre ("Terraza de fin de semana") y los días
+/// en que se usa
+///
+/// Fuera de sus días no se usa; los días sin distribución se reservan con
+/// todas las mesas reservables (ver [`crate::availability::active_layout`]).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Distribucion {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub nombre: String,
+    /// Nombres normalizados (ver [`normalize_name`]) de las mesas en
+    /// servicio; por nombre y no por ID porque el editor del plano vuelve a
+    /// crear las mesas al guardarlo
+    pub mesas: Vec<String>,
+    /// Días de la semana en que se usa (1 = lunes ... 7 = domingo)
+    #[serde(default)]
+    pub dias_semana: Vec<u32>,
+    /// Fechas concretas (YYYY-MM-DD) en que se usa, por encima de los días
+    /// de la semana
+    #[serde(default)]
+    pub fechas: Vec<String>,
+    pub created_at: i64, // timestamp unix
+}
+
+impl Distribucion {
+    /// Indica si una mesa está en servicio con esta distribución
+    pub fn incluye(&self, mesa: &Mesa) -> bool {
+        self.mesas.contains(&normalize_name(&mesa.nombre))
+    }
+}
+
+/// Estado de una reserva a lo largo de su vida
+///
+/// ```text
+/// sin_confirmar ─┬─> pendiente ─┬─> confirmada ─┬─> sentada ──> completada
+///                └──────────────│───────────────┘      ↑
+///                               ├──────────────────────┘
+///                               └─> no_show   (también desde confirmada)
+/// ```
+///
+/// Cualquier estado anterior a `sentada` se puede cancelar. `completada`,
+/// `no_show` y `cancelada` son finales (ver [`EstadoReserva::can_transition_to`]).
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoReserva {
+    /// Reserva del widget a la espera de que el cliente verifique su contacto
+    SinConfirmar,
+    /// A la espera de que el restaurante la confirme
+    Pendiente,
+    Confirmada,
+    /// El cliente ha llegado y ocupa la mesa
+    Sentada,
+    /// El cliente se ha ido y la mesa ha quedado libre
+    Completada,
+    /// El cliente no se presentó
+    NoShow,
+    Cancelada,
+}
+
+impl EstadoReserva {
+    /// Estados que ocupan la mesa
+    pub const ACTIVOS: [EstadoReserva; 4] = [
+        EstadoReserva::SinConfirmar,
+        EstadoReserva::Pendiente,
+        EstadoReserva::Confirmada,
+        EstadoReserva::Sentada,
+    ];
+
+    /// Nombre con el que se guarda el estado
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EstadoReserva::SinConfirmar => "sin_confirmar",
+            EstadoReserva::Pendiente => "pendiente",
+            EstadoReserva::Confirmada => "confirmada",
+            EstadoReserva::Sentada => "sentada",
+            EstadoReserva::Completada => "completada",
+            EstadoReserva::NoShow => "no_show",
+            EstadoReserva::Cancelada => "cancelada",
+        }
+    }
+
+    /// Estado a partir de su nombre
+    ///
+    /// ```
+    /// use pispas_reservation::db::EstadoReserva;
+    ///
+    /// assert_eq!(EstadoReserva::parse("no_show"), Some(EstadoReserva::NoShow));
+    /// assert_eq!(EstadoReserva::parse("borrada"), None);
+    /// ```
+    pub fn parse(estado: &str) -> Option<Self> {
+        [
+            EstadoReserva::SinConfirmar,
+            EstadoReserva::Pendiente,
+            EstadoReserva::Confirmada,
+            EstadoReserva::Sentada,
+            EstadoReserva::Completada,
+            EstadoReserva::NoShow,
+            EstadoReserva::Cancelada,
+        ]
+        .into_iter()
+        .find(|candidato| candidato.as_str() == estado)
+    }
+
+    /// Indica si la reserva ocupa su mesa
+    pub fn is_active(&self) -> bool {
+        EstadoReserva::ACTIVOS.contains(self)
+    }
+
+    /// Indica si el estado ya no puede cambiar
+    pub fn is_final(&self) -> bool {
+        matches!(self, EstadoReserva::Completada | EstadoReserva::NoShow | EstadoReserva::Cancelada)
+    }
+
+    /// Indica si una reserva puede pasar de este estado a `destino`
+    ///
+    /// ```
+    /// use pispas_reservation::db::EstadoReserva::*;
+    ///
+    /// assert!(Confirmada.can_transition_to(Sentada));
+    /// assert!(Sentada.can_transition_to(Completada));
+    /// assert!(Pendiente.can_transition_to(NoShow));
+    /// assert!(!Sentada.can_transition_to(Cancelada));
+    /// assert!(!Cancelada.can_transition_to(Confirmada));
+    /// assert!(!SinConfirmar.can_transition_to(Sentada));
+    /// ```
+    pub fn can_transition_to(&self, destino: EstadoReserva) -> bool {
+        use EstadoReserva::*;
+        matches!(
+            (self, destino),
+            (SinConfirmar, Pendiente | Confirmada | Cancelada)
+                | (Pendiente, Confirmada | Sentada | NoShow | Cancelada)
+                | (Confirmada, Sentada | NoShow | Cancelada)
+                | (Sentada, Completada)
+        )
+    }
+
+    /// Estados desde los que se puede llegar a `destino`
+    pub fn sources_of(destino: EstadoReserva) -> Vec<EstadoReserva> {
+        [
+            EstadoReserva::SinConfirmar,
+            EstadoReserva::Pendiente,
+            EstadoReserva::Confirmada,
+            EstadoReserva::Sentada,
+        ]
+        .into_iter()
+        .filter(|origen| origen.can_transition_to(destino))
+        .collect()
+    }
+}
+
+impl std::fmt::Display for EstadoReserva {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<EstadoReserva> for mongodb::bson::Bson {
+    fn from(estado: EstadoReserva) -> Self {
+        mongodb::bson::Bson::String(estado.as_str().to_string())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Reserva {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub id_mesa: mongodb::bson::oid::ObjectId,
+    pub nombre_cliente: String,
+    pub email_cliente: String,
+    pub telefono_cliente: String,
+    pub numero_personas: i32,
+    pub fecha: String,
+    pub hora: String,
+    pub estado: EstadoReserva,
+    pub created_at: i64, // timestamp unix
+    pub updated_at: i64, // timestamp unix
+    /// Origen de la reserva ("interno" para el panel, "publico" para el
+    /// widget, "sala" para los clientes sin reserva)
+    #[serde(default = "default_canal")]
+    pub canal: String,
+    /// Verificación pendiente del cliente (solo en estado "sin_confirmar")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verificacion: Option<VerificacionCliente>,
+    /// Identificador del dispositivo que hizo la reserva desde el widget
+    /// ("sesion:<id>" o "ip:<dirección>")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispositivo: Option<String>,
+    /// Timestamp unix en que se eliminaron los datos personales por
+    /// retención (ver [`crate::jobs::anonymization`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anonimizada_en: Option<i64>,
+    /// Cliente del CRM al que pertenece la reserva
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_cliente: Option<mongodb::bson::oid::ObjectId>,
+    /// Opciones de menú elegidas por adelantado
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preseleccion: Vec<SeleccionMenu>,
+    /// Depósito exigido a la reserva, dividido entre los comensales
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deposito: Option<Deposito>,
+    /// Pago por adelantado exigido por la configuración del restaurante
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pago: Option<Pago>,
+    /// Cancelada fuera del plazo de la política de cancelación
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cancelacion_tardia: bool,
+    /// Momento (timestamp unix) desde el que la reserva espera a que la
+    /// confirme el restaurante, si no es el de su creación (las del widget
+    /// que el cliente verificó después)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pendiente_desde: Option<i64>,
+    /// Momento (timestamp unix) en que el personal confirmó la reserva
+    /// pendiente (ver [`crate::sla`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmada_en: Option<i64>,
+    /// Momento (timestamp unix) en que llegó el cliente, registrado con
+    /// `POST /reservations/{id}/checkin` o al sentar a un cliente sin reserva
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub llegada_en: Option<i64>,
+    /// Momento (timestamp unix) en que se sentó al cliente
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sentada_en: Option<i64>,
+    /// Momento (timestamp unix) en que se completó la reserva
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completada_en: Option<i64>,
+    /// Local del que se traspasó la reserva, si vino de otro del grupo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transferida_desde: Option<mongodb::bson::oid::ObjectId>,
+    /// Código corto con el que el cliente consulta su reserva (ver [`localizador`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub localizador: Option<String>,
+    /// Token del enlace de gestión que se envía al cliente
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_gestion: Option<String>,
+    /// Retención legal por una disputa: mientras exista, la anonimización
+    /// no toca la reserva
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retencion_legal: Option<RetencionLegal>,
+    /// UUID generado por el cliente al crear la reserva sin conexión; único
+    /// por restaurante
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    /// Idioma en el que se escribe al cliente (ver [`crate::language`]); sin
+    /// él, el del restaurante
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idioma: Option<String>,
+    /// Peticiones del cliente ("cumpleaños, trona"); las ve el cliente
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notas_cliente: Option<String>,
+    /// Notas del personal; nunca se muestran al cliente
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notas_internas: Option<String>,
+    /// Momento (timestamp unix) en que se envió el recordatorio al cliente;
+    /// se quita al cambiar la fecha o la hora
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recordatorio_enviado_en: Option<i64>,
+    /// Momento (timestamp unix) en que el cliente confirmó que vendrá desde
+    /// su enlace; se quita al cambiar la fecha o la hora
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmada_por_cliente_en: Option<i64>,
+    /// ID de la petición que creó la reserva (ver [`crate::api::request_id`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Retención legal de una reserva
+///
+/// La marca el restaurante y solo la levanta la administración.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RetencionLegal {
+    /// Motivo de la retención (reclamación, disputa del pago...)
+    pub motivo: String,
+    /// Usuario que la marcó
+    pub usuario: String,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Depósito de una reserva, dividido en partes que se pagan por separado
+///
+/// Cada parte tiene su propio enlace de pago; la reserva se confirma cuando
+/// lo pagado alcanza el umbral.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Deposito {
+    /// Importe total en céntimos
+    pub importe_total_centimos: i64,
+    /// Importe pagado a partir del cual se confirma la reserva
+    pub umbral_centimos: i64,
+    pub partes: Vec<PartePago>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Parte de un depósito pagadera por un comensal
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PartePago {
+    /// Código del enlace de pago
+    pub codigo: String,
+    pub importe_centimos: i64,
+    /// Timestamp unix del pago, si ya se ha pagado
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pagada_en: Option<i64>,
+}
+
+impl Deposito {
+    /// Divide `importe_total_centimos` en `partes` lo más iguales posible
+    ///
+    /// Los céntimos sobrantes se reparten entre las primeras partes. El
+    /// umbral es el `umbral_porcentaje` del total, redondeado hacia arriba.
+    ///
+    /// ```
+    /// use pispas_reservation::db::Deposito;
+    ///
+    /// let deposito = Deposito::new(1000, 3, 50, |i| format!("p{}", i), 0);
+    /// let importes: Vec<i64> = deposito.partes.iter().map(|p| p.importe_centimos).collect();
+    /// assert_eq!(importes, vec![334, 333, 333]);
+    /// assert_eq!(deposito.umbral_centimos, 500);
+    /// ```
+    pub fn new(
+        importe_total_centimos: i64,
+        partes: u32,
+        umbral_porcentaje: u32,
+        codigo: impl Fn(u32) -> String,
+        current_time: i64,
+    ) -> Self {
+        let partes = partes.max(1);
+        let base = importe_total_centimos / i64::from(partes);
+        let resto = importe_total_centimos % i64::from(partes);
+
+        Deposito {
+            importe_total_centimos,
+            umbral_centimos: (importe_total_centimos * i64::from(umbral_porcentaje) + 99) / 100,
+            partes: (0..partes)
+                .map(|i| PartePago {
+                    codigo: codigo(i),
+                    importe_centimos: base + i64::from(i64::from(i) < resto),
+                    pagada_en: None,
+                })
+                .collect(),
+            created_at: current_time,
+        }
+    }
+
+    /// Importe pagado hasta ahora, en céntimos
+    pub fn pagado_centimos(&self) -> i64 {
+        self.partes
+            .iter()
+            .filter(|parte| parte.pagada_en.is_some())
+            .map(|parte| parte.importe_centimos)
+            .sum()
+    }
+
+    /// Indica si lo pagado alcanza el umbral de confirmación
+    pub fn umbral_alcanzado(&self) -> bool {
+        self.pagado_centimos() >= self.umbral_centimos
+    }
+
+    /// Estado del depósito: "pendiente", "parcial" o "completado"
+    pub fn estado(&self) -> &'static str {
+        match self.pagado_centimos() {
+            0 => "pendiente",
+            pagado if pagado >= self.importe_total_centimos => "completado",
+            _ => "parcial",
+        }
+    }
+}
+
+/// Pago por adelantado de una reserva (ver [`ConfigDeposito`])
+///
+/// A diferencia del [`Deposito`] dividido, es un único cobro que el
+/// personal marca como pagado o reembolsado (ver [`crate::payments`]).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Pago {
+    /// Importe en céntimos
+    pub importe_centimos: i64,
+    pub estado: EstadoPago,
+    /// Proveedor de pagos que ha gestionado el cobro
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proveedor: Option<String>,
+    /// Referencia del cobro en el proveedor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub referencia: Option<String>,
+    /// Timestamp unix del pago
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pagado_en: Option<i64>,
+    /// Timestamp unix del reembolso
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reembolsado_en: Option<i64>,
+}
+
+/// Estado del pago por adelantado de una reserva
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoPago {
+    Pendiente,
+    Pagado,
+    Reembolsado,
+}
+
+impl Reserva {
+    /// Indica si falta algún pago para poder confirmar la reserva: el
+    /// depósito dividido no alcanza el umbral o el pago por adelantado está
+    /// pendiente
+    pub fn pago_pendiente(&self) -> bool {
+        self.deposito.as_ref().is_some_and(|deposito| !deposito.umbral_alcanzado())
+            || self.pago.as_ref().is_some_and(|pago| pago.estado == EstadoPago::Pendiente)
+    }
+}
+
+/// Opción de menú elegida en una reserva
+///
+/// Guarda una copia del nombre para que la reserva siga siendo legible
+/// aunque la opción se modifique o elimine después.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SeleccionMenu {
+    pub id_opcion: mongodb::bson::oid::ObjectId,
+    pub nombre: String,
+    pub cantidad: u32,
+}
+
+fn default_canal() -> String {
+    "interno".to_string()
+}
+
+/// Verificación pendiente de un cliente en una reserva pública
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VerificacionCliente {
+    /// Método usado para enviar el código
+    pub metodo: MetodoVerificacion,
+    /// Código enviado (token del enlace mágico o código numérico del SMS)
+    pub codigo: String,
+    /// Timestamp unix a partir del cual el código deja de ser válido
+    pub expira_en: i64,
+    /// Intentos fallidos de verificación
+    #[serde(default)]
+    pub intentos: u32,
+}
+
+/// Intento de reserva bloqueado por las heurísticas anti-duplicados del widget
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ViolacionWidget {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Dispositivo que realizó el intento
+    pub dispositivo: String,
+    /// Motivo del bloqueo
+    pub motivo: String,
+    /// Email usado en el intento bloqueado
+    pub email_cliente: String,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Opción de menú que los clientes pueden elegir al reservar
+/// (menú degustación, maridaje, platos por encargo...)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OpcionMenu {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub nombre: String,
+    #[serde(default)]
+    pub descripcion: String,
+    /// Precio en céntimos, si se muestra al cliente
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precio_centimos: Option<i64>,
+    /// Las opciones inactivas no se ofrecen en nuevas reservas
+    pub activa: bool,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Franja horaria en la que el restaurante no acepta reservas públicas
+///
+/// Independiente de los turnos: sirve para huecos puntuales como un cambio
+/// de cocina ("sin reservas de 21:00 a 21:30 los sábados") o un evento.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReglaBloqueo {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Días de la semana en que aplica (1 = lunes ... 7 = domingo); vacío
+    /// para todos los días. Se ignora si hay `fecha`.
+    #[serde(default)]
+    pub dias_semana: Vec<u32>,
+    /// Fecha concreta (YYYY-MM-DD) si la regla no es semanal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fecha: Option<String>,
+    /// Hora de inicio (formato HH:MM, incluida)
+    pub hora_inicio: String,
+    /// Hora de fin (formato HH:MM, excluida)
+    pub hora_fin: String,
+    /// Motivo mostrado al cliente
+    #[serde(default)]
+    pub motivo: String,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Cliente del CRM de un restaurante
+///
+/// Se crea o actualiza automáticamente con cada reserva, identificando al
+/// cliente por email (o por teléfono si no hay email). Una misma persona
+/// puede acabar con varios registros si usa emails distintos; ver
+/// `POST /customers/merge`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Cliente {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Último nombre usado en una reserva
+    pub nombre: String,
+    /// Emails conocidos (normalizados a minúsculas)
+    #[serde(default)]
+    pub emails: Vec<String>,
+    /// Teléfonos conocidos
+    #[serde(default)]
+    pub telefonos: Vec<String>,
+    /// Reservas no canceladas del cliente
+    #[serde(default)]
+    pub visitas: u32,
+    /// Reservas a las que no se presentó
+    #[serde(default)]
+    pub no_shows: u32,
+    /// Mesa preferida, aprendida del historial o fijada por el personal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mesa_preferida: Option<mongodb::bson::oid::ObjectId>,
+    /// La preferencia la fijó el personal y el historial ya no la cambia
+    #[serde(default)]
+    pub preferencia_manual: bool,
+    pub created_at: i64, // timestamp unix
+    pub updated_at: i64, // timestamp unix
+}
+
+/// Registro de auditoría de una fusión de clientes
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FusionClientes {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Cliente que se conserva
+    pub id_destino: mongodb::bson::oid::ObjectId,
+    /// Copia del cliente eliminado tal como estaba antes de la fusión
+    pub origen: Cliente,
+    /// Reservas que pasaron del origen al destino
+    pub reservas_movidas: u64,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Datos derivados que se pueden reconstruir con `POST /admin/rebuild`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrabajoReconstruccion {
+    /// Nombres normalizados de restaurantes y mesas
+    Nombres,
+    /// Clientes del CRM y sus contadores de visitas y no-shows
+    Crm,
+    /// Estadísticas diarias precalculadas (ver [`EstadisticaDiaria`])
+    Counters,
+    /// Índices de MongoDB
+    Indexes,
+    /// Reservas, como proyección del diario de reservas
+    Reservas,
+}
+
+/// Estado de una reconstrucción de datos derivados
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoReconstruccion {
+    EnCurso,
+    Terminada,
+    Fallida,
+}
+
+/// Documentos revisados y actualizados de una colección en una reconstrucción
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProgresoColeccion {
+    pub coleccion: String,
+    pub revisados: u64,
+    pub actualizados: u64,
+}
+
+/// Ejecución de una reconstrucción de datos derivados
+///
+/// Se guarda al empezar y se actualiza con su progreso mientras avanza, para
+/// poder seguirla desde otra petición (`GET /admin/rebuild/{id}`).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Reconstruccion {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub trabajo: TrabajoReconstruccion,
+    pub estado: EstadoReconstruccion,
+    /// Progreso de cada colección, en el orden en que se recorren
+    #[serde(default)]
+    pub colecciones: Vec<ProgresoColeccion>,
+    /// Motivo por el que falló
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub iniciada_en: i64, // timestamp unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terminada_en: Option<i64>, // timestamp unix
+}
+
+/// Informe de una ejecución del trabajo de anonimización
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InformeAnonimizacion {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    /// Reservas con fecha anterior a esta (YYYY-MM-DD) se han anonimizado
+    pub fecha_limite: String,
+    /// Días de retención aplicados
+    pub retencion_dias: u32,
+    /// Reservas anonimizadas en total
+    pub reservas_anonimizadas: u64,
+    /// Reservas anonimizadas por restaurante
+    pub por_restaurante: Vec<RecuentoRestaurante>,
+    /// Reservas que tocaba anonimizar pero tienen retención legal
+    #[serde(default)]
+    pub reservas_retenidas: u64,
+    /// Intentos bloqueados del widget eliminados
+    pub violaciones_eliminadas: u64,
+    /// Mensajes de reservas eliminados
+    #[serde(default)]
+    pub mensajes_eliminados: u64,
     pub created_at: i64, // timestamp unix
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Reserva {
-    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
-    pub id: Option<mongodb::bson::oid::ObjectId>,
-    pub id_restaurante: mongodb::bson::oid::ObjectId,
-    pub id_mesa: mongodb::bson::oid::ObjectId,
-    pub nombre_cliente: String,
-    pub email_cliente: String,
-    pub telefono_cliente: String,
-    pub numero_personas: i32,
-    pub fecha: String,
-    pub hora: String,
-    pub estado: String,
-    pub created_at: i64, // timestamp unix
-    pub updated_at: i64, // timestamp unix
-}
+/// Número de documentos afectados de un restaurante
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecuentoRestaurante {
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub reservas: u64,
+}
+
+/// Estadísticas precalculadas de un restaurante en un día
+///
+/// Las escribe el trabajo de [`crate::jobs::rollups`] a partir de las
+/// reservas de ese día; la analítica las lee en lugar de recorrer las
+/// reservas.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EstadisticaDiaria {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Día de servicio (YYYY-MM-DD)
+    pub fecha: String,
+    /// Reservas del día, sin contar las que aún no ha verificado el cliente
+    pub reservas: u64,
+    /// Reservas por canal ("interno", "publico"...)
+    pub por_canal: std::collections::BTreeMap<String, u64>,
+    /// Comensales de las reservas que no se cancelaron ni faltaron
+    pub comensales: u64,
+    pub cancelaciones: u64,
+    pub no_presentadas: u64,
+    pub calculado_en: i64, // timestamp unix
+}
+
+impl EstadisticaDiaria {
+    /// Estadística vacía de un restaurante y día
+    pub fn new(id_restaurante: mongodb::bson::oid::ObjectId, fecha: &str, calculado_en: i64) -> Self {
+        EstadisticaDiaria {
+            id: None,
+            id_restaurante,
+            fecha: fecha.to_string(),
+            reservas: 0,
+            por_canal: Default::default(),
+            comensales: 0,
+            cancelaciones: 0,
+            no_presentadas: 0,
+            calculado_en,
+        }
+    }
+
+    /// Suma `reservas` reservas de un canal y estado con `personas` comensales en total
+    ///
+    /// ```
+    /// use mongodb::bson::oid::ObjectId;
+    /// use pispas_reservation::db::EstadisticaDiaria;
+    ///
+    /// let mut dia = EstadisticaDiaria::new(ObjectId::new(), "2030-06-15", 0);
+    /// dia.add("publico", "confirmada", 2, 7);
+    /// dia.add("interno", "cancelada", 1, 4);
+    /// dia.add("publico", "sin_confirmar", 1, 2);
+    /// dia.add("interno", "no_show", 1, 3);
+    ///
+    /// assert_eq!(dia.reservas, 4);
+    /// assert_eq!(dia.por_canal["publico"], 2);
+    /// assert_eq!(dia.comensales, 7);
+    /// assert_eq!(dia.cancelaciones, 1);
+    /// assert_eq!(dia.no_presentadas, 1);
+    /// ```
+    pub fn add(&mut self, canal: &str, estado: &str, reservas: u64, personas: u64) {
+        match estado {
+            "sin_confirmar" => return,
+            "cancelada" => self.cancelaciones += reservas,
+            "no_show" => self.no_presentadas += reservas,
+            _ => self.comensales += personas,
+        }
+        self.reservas += reservas;
+        *self.por_canal.entry(canal.to_string()).or_default() += reservas;
+    }
+}
+
+/// Copia del plano de mesas de un restaurante en un momento dado
+///
+/// Sirve para comparar distribuciones (ver [`crate::plan::diff`]).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotPlano {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub nombre: String,
+    pub mesas: Vec<Mesa>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Mesas borradas con `DELETE /tables/clear`, en la papelera
+///
+/// Se guardan completas (con su `_id`, al que apuntan las reservas) para
+/// poder restaurarlas hasta `expires_at` (ver [`crate::api::recycle_bin`]).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MesasBorradas {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub mesas: Vec<Mesa>,
+    /// Usuario que las borró
+    pub usuario: String,
+    pub expires_at: i64, // timestamp unix
+    pub created_at: i64, // timestamp unix
+}
+
+/// Confirmación pendiente de una operación destructiva
+///
+/// La primera llamada a la operación la crea y la devuelve; la segunda la
+/// consume (ver [`crate::api::recycle_bin`]).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfirmacionBorrado {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub token: String,
+    /// Operación confirmada ("vaciar_mesas", "purgar_reservas"...)
+    pub operacion: String,
+    /// Planta a la que se limita la operación, si se limita a una
+    pub id_planta: Option<mongodb::bson::oid::ObjectId>,
+    pub expires_at: i64, // timestamp unix
+    pub created_at: i64, // timestamp unix
+}
+
+/// Estado de una solicitud de grupo
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EstadoSolicitudGrupo {
+    /// Recibida, nadie la ha atendido todavía
+    #[default]
+    Nueva,
+    /// El personal está hablando con el cliente
+    EnConversacion,
+    /// Se ha convertido en una o varias reservas
+    Convertida,
+    Rechazada,
+}
+
+impl EstadoSolicitudGrupo {
+    /// Nombre con el que se guarda el estado
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EstadoSolicitudGrupo::Nueva => "nueva",
+            EstadoSolicitudGrupo::EnConversacion => "en_conversacion",
+            EstadoSolicitudGrupo::Convertida => "convertida",
+            EstadoSolicitudGrupo::Rechazada => "rechazada",
+        }
+    }
+
+    /// Indica si una solicitud puede pasar de este estado a `destino`;
+    /// `convertida` y `rechazada` son finales
+    ///
+    /// ```
+    /// use pispas_reservation::db::EstadoSolicitudGrupo::*;
+    ///
+    /// assert!(Nueva.can_transition_to(EnConversacion));
+    /// assert!(Nueva.can_transition_to(Rechazada));
+    /// assert!(EnConversacion.can_transition_to(Convertida));
+    /// assert!(!EnConversacion.can_transition_to(Nueva));
+    /// assert!(!Rechazada.can_transition_to(EnConversacion));
+    /// ```
+    pub fn can_transition_to(&self, destino: EstadoSolicitudGrupo) -> bool {
+        use EstadoSolicitudGrupo::*;
+        matches!(
+            (self, destino),
+            (Nueva, EnConversacion | Convertida | Rechazada) | (EnConversacion, Convertida | Rechazada)
+        )
+    }
+}
+
+impl From<EstadoSolicitudGrupo> for mongodb::bson::Bson {
+    fn from(estado: EstadoSolicitudGrupo) -> Self {
+        mongodb::bson::Bson::String(estado.as_str().to_string())
+    }
+}
+
+/// Petición de un grupo o evento que el personal gestiona a mano
+///
+/// No ocupa ninguna mesa (ver [`crate::api::group_request`]).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SolicitudGrupo {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub nombre_cliente: String,
+    pub email_cliente: String,
+    pub telefono_cliente: String,
+    pub numero_personas: i32,
+    /// Fecha deseada (YYYY-MM-DD)
+    pub fecha: String,
+    /// Hora deseada (HH:MM), si el cliente la indica
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hora: Option<String>,
+    /// Lo que cuenta el cliente del evento
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mensaje: Option<String>,
+    #[serde(default)]
+    pub estado: EstadoSolicitudGrupo,
+    /// Idioma en el que se escribe al cliente
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idioma: Option<String>,
+    /// Reserva en la que se convirtió
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_reserva: Option<mongodb::bson::oid::ObjectId>,
+    /// Notas del personal sobre la conversación con el cliente
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nota: Option<String>,
+    pub created_at: i64, // timestamp unix
+    pub updated_at: i64, // timestamp unix
+}
+
+/// Registro de un miembro del personal que trabaja un turno de un día
+///
+/// Lo crea cada persona al empezar su turno (ver [`crate::api::shift`]).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RegistroTurno {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub fecha: String,
+    /// Nombre del turno de la configuración del restaurante
+    pub turno: String,
+    /// Usuario del personal, o "propietario" con el token del restaurante
+    pub usuario: String,
+    pub inicio: i64, // timestamp unix
+}
+
+/// Nota de traspaso que un turno deja al siguiente
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotaTraspaso {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub fecha: String,
+    /// Turno en el que se escribió la nota
+    pub turno: String,
+    /// Usuario del personal que la escribió
+    pub autor: String,
+    pub texto: String,
+    /// Reservas del día a las que se refiere la nota
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub id_reservas: Vec<mongodb::bson::oid::ObjectId>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Alerta enviada por el monitor
+///
+/// Sirve de historial y para no repetir la misma alerta en cada pasada.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlertaEnviada {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    /// Restaurante afectado, o `None` para las alertas de operaciones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_restaurante: Option<mongodb::bson::oid::ObjectId>,
+    /// Identificador de la regla ("cancelaciones", "latencia_bd"...)
+    pub clave: String,
+    pub mensaje: String,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Token de un solo uso para restablecer la contraseña de un restaurante
+///
+/// Se envía por email desde `POST /restaurants/forgot-password` y se borra
+/// al usarlo o al pedir uno nuevo.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenRecuperacion {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub token: String,
+    pub expires_at: i64, // timestamp unix
+    pub created_at: i64, // timestamp unix
+}
+
+/// Reclamación de una cuenta de restaurante ya registrada
+///
+/// Creada por `POST /restaurants/claim` cuando alguien intenta registrar un
+/// `objid_pispas` existente; el código se envía al email de la cuenta.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Reclamacion {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Código numérico de 6 dígitos
+    pub codigo: String,
+    /// Intentos fallidos de verificación del código actual
+    #[serde(default)]
+    pub intentos: u32,
+    /// Intentos fallidos con cualquier código; se conservan al pedir uno nuevo
+    #[serde(default)]
+    pub fallos: u32,
+    /// Hasta cuándo no se puede reclamar la cuenta (0 si no está bloqueada)
+    #[serde(default)]
+    pub bloqueada_hasta: i64, // timestamp unix
+    pub expires_at: i64, // timestamp unix
+    pub created_at: i64, // timestamp unix
+}
+
+/// Sesión iniciada por el propietario de un restaurante
+///
+/// Cada login del propietario crea una sesión con su propio token, que puede
+/// revocarse sin afectar a las demás (ver [`crate::api::sessions`]).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Sesion {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub token: String,
+    /// IP desde la que se inició la sesión
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    /// `User-Agent` del dispositivo que inició la sesión
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    pub created_at: i64, // timestamp unix
+    /// Última petición hecha con el token (con una resolución de un minuto)
+    pub last_used_at: i64, // timestamp unix
+    /// Presente si la sesión la abrió el soporte para actuar como el propietario
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suplantacion: Option<Suplantacion>,
+}
+
+/// Datos de una sesión de suplantación abierta desde la administración
+///
+/// Ver [`crate::api::impersonation`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Suplantacion {
+    /// Quién del soporte la abrió
+    pub admin: String,
+    pub motivo: String,
+    /// Alcances a los que se limita; `None` si puede todo lo del propietario
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alcances: Option<Vec<Alcance>>,
+    pub expires_at: i64, // timestamp unix
+}
+
+/// Clave de API de un integrador
+///
+/// Actúa con el rol indicado sin pasar por el login. Las claves de pruebas
+/// (`sandbox`) se guardan y trabajan en la base de datos de pruebas (ver
+/// [`crate::api::sandbox`]).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClaveApi {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Nombre que le da el propietario ("TPV", "Integración web"...)
+    pub nombre: String,
+    pub token: String,
+    pub rol: Rol,
+    /// Rutas a las que puede llamar; `None` si no tiene más límite que el rol
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alcances: Option<Vec<Alcance>>,
+    #[serde(default)]
+    pub sandbox: bool,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Mensaje del hilo de una reserva entre el cliente y el restaurante
+///
+/// Ver [`crate::api::messages`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MensajeReserva {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub id_reserva: mongodb::bson::oid::ObjectId,
+    pub autor: AutorMensaje,
+    /// Usuario del personal que respondió (solo en los del restaurante)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usuario: Option<String>,
+    pub texto: String,
+    /// Cuándo lo leyó la otra parte
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leido_en: Option<i64>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Quién escribe un mensaje del hilo de una reserva
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AutorMensaje {
+    Cliente,
+    Restaurante,
+}
+
+impl AutorMensaje {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AutorMensaje::Cliente => "cliente",
+            AutorMensaje::Restaurante => "restaurante",
+        }
+    }
+}
+
+impl From<AutorMensaje> for mongodb::bson::Bson {
+    fn from(autor: AutorMensaje) -> Self {
+        mongodb::bson::Bson::String(autor.as_str().to_string())
+    }
+}
+
+/// Suscripción de un restaurante a sus eventos de dominio por webhook
+///
+/// Ver [`crate::api::webhooks`]; las entregas las hace
+/// [`crate::jobs::webhook_delivery`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SuscripcionWebhook {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// URL a la que se envía cada evento con `POST`
+    pub url: String,
+    /// Secreto con el que se firman las entregas
+    pub secreto: String,
+    /// Tipos de evento que se entregan (`reserva_creada`...); vacía, todos
+    #[serde(default)]
+    pub eventos: Vec<String>,
+    /// Forma del cuerpo de cada entrega (ver [`crate::webhooks`])
+    #[serde(default)]
+    pub plantilla: PlantillaWebhook,
+    /// Último evento ya entregado o descartado por el filtro
+    pub ultimo_id: mongodb::bson::oid::ObjectId,
+    /// Error de la última entrega fallida; se quita con la siguiente correcta
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ultimo_error: Option<String>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Forma del cuerpo que recibe una suscripción de webhook
+///
+/// ```json
+/// { "formato": "campos", "campos": { "booking.id": "id_entidad", "booking.date": "datos.fecha" } }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+#[serde(tag = "formato", rename_all = "snake_case")]
+pub enum PlantillaWebhook {
+    /// El evento completo, como en la exportación JSON Lines
+    #[default]
+    Completo,
+    /// Solo el tipo, la entidad y el momento del evento
+    Minimo,
+    /// Un objeto con los campos indicados: cada clave es la ruta en el
+    /// cuerpo enviado y su valor, la ruta en el evento completo
+    Campos { campos: std::collections::BTreeMap<String, String> },
+}
+
+/// Logins fallidos recientes de un nombre de restaurante o de una IP
+///
+/// Ver [`crate::api::login_lockout`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IntentoLogin {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    /// `nombre:<nombre normalizado>` o `ip:<dirección>`
+    pub clave: String,
+    /// Fallos desde `ventana_desde`
+    pub fallos: u32,
+    pub ventana_desde: i64, // timestamp unix
+    /// Hasta cuándo se rechazan los logins (0 si no está bloqueado)
+    #[serde(default)]
+    pub bloqueado_hasta: i64, // timestamp unix
+}
+
+/// Consultas por localizador recientes desde una IP
+///
+/// Ver `POST /public/reservations/lookup` en [`crate::api::public`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConsultasLocalizador {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub ip: String,
+    /// Consultas desde `ventana_desde`
+    pub consultas: u32,
+    pub ventana_desde: i64, // timestamp unix
+}
+
+/// Consultas fallidas recientes de un localizador, desde cualquier IP
+///
+/// Ver `POST /public/reservations/lookup` en [`crate::api::public`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FallosLocalizador {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    /// Localizador consultado, en mayúsculas
+    pub localizador: String,
+    /// Fallos desde `ventana_desde`
+    pub fallos: u32,
+    pub ventana_desde: i64, // timestamp unix
+    /// Hasta cuándo se rechazan las consultas (0 si no está bloqueado)
+    #[serde(default)]
+    pub bloqueado_hasta: i64, // timestamp unix
+}
+
+/// Notificación de webhook ya aceptada, para rechazar su repetición
+///
+/// Ver [`crate::api::webhook_auth`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WebhookRecibido {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    /// `<origen>:<firma>`
+    pub clave: String,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Petición de creación con `Idempotency-Key` y su respuesta
+///
+/// Ver [`crate::api::idempotency`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PeticionIdempotente {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Valor de la cabecera `Idempotency-Key`
+    pub clave: String,
+    /// SHA-256 del método, la ruta y el cuerpo de la petición
+    pub huella: String,
+    /// Código de la respuesta; `None` mientras la petición está en curso
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estado: Option<u16>,
+    /// Cuerpo de la respuesta
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub respuesta: Option<String>,
+    pub created_at: i64, // timestamp unix
+    /// Momento en que MongoDB borra la clave (índice TTL)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expira_en: Option<mongodb::bson::DateTime>,
+}
+
+/// Inicio de sesión OAuth2 en curso
+///
+/// Creado por `GET /auth/google/start` y consumido por el callback; el
+/// `state` viaja en la redirección para comprobar que la respuesta de Google
+/// corresponde a un inicio de sesión pedido desde aquí.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EstadoOAuth {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub state: String,
+    /// Restaurante al que vincular la cuenta de Google, si el inicio lo pidió
+    /// un propietario ya autenticado
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_restaurante: Option<mongodb::bson::oid::ObjectId>,
+    pub expires_at: i64, // timestamp unix
+    pub created_at: i64, // timestamp unix
+}
+
+/// Evento de dominio del registro de auditoría
+///
+/// Los eventos solo se añaden, nunca se modifican; ver [`crate::events`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Evento {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    /// Tipo de evento ("reserva_creada", "reserva_cancelada"...)
+    pub tipo: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_restaurante: Option<mongodb::bson::oid::ObjectId>,
+    /// Entidad afectada (reserva, restaurante...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_entidad: Option<mongodb::bson::oid::ObjectId>,
+    /// Datos propios del tipo de evento
+    #[serde(default)]
+    pub datos: mongodb::bson::Document,
+    /// ID de la petición que originó el evento (ver [`crate::api::request_id`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Entrada del registro de auditoría de un restaurante
+///
+/// Quién hizo una operación que modifica datos y qué cambió; ver
+/// [`crate::api::audit`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntradaAuditoria {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    /// Usuario del personal, o `propietario`
+    pub usuario: String,
+    pub rol: Rol,
+    /// Quién del soporte hizo la operación suplantando al propietario
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suplantada_por: Option<String>,
+    /// Operación ("crear_reserva", "cambiar_configuracion"...)
+    pub accion: String,
+    /// Tipo de entidad afectada ("reserva", "mesa"...)
+    pub entidad: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_entidad: Option<mongodb::bson::oid::ObjectId>,
+    /// Campos que cambian, con su valor antes y después
+    #[serde(default)]
+    pub cambios: Vec<CambioCampo>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Cambio en una reserva, para su historial
+///
+/// Lo guarda la colección `reservation_events`; ver
+/// [`crate::api::reservation_history`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventoReserva {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub id_reserva: mongodb::bson::oid::ObjectId,
+    /// Usuario del personal, `propietario`, `cliente` o `sistema`
+    pub autor: String,
+    /// Rol del autor, si es del restaurante
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rol: Option<Rol>,
+    /// Operación ("confirmar_reserva", "verificar_reserva"...)
+    pub accion: String,
+    /// Campos que cambian, con su valor antes y después
+    #[serde(default)]
+    pub cambios: Vec<CambioCampo>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Cambio de un campo en una [`EntradaAuditoria`] o un [`EventoReserva`]
+///
+/// Los campos anidados se nombran con puntos (`configuracion.duracion_reserva_minutos`);
+/// un valor `null` indica que el campo no existía (alta) o deja de existir (baja).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CambioCampo {
+    pub campo: String,
+    pub antes: mongodb::bson::Bson,
+    pub despues: mongodb::bson::Bson,
+}
+
+/// Posición hasta la que se ha exportado un flujo de datos
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Checkpoint {
+    /// Nombre del flujo ("envio_eventos")
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub ultimo_id: mongodb::bson::oid::ObjectId,
+    pub updated_at: i64, // timestamp unix
+}
+
+/// Aviso de mantenimiento o incidencia de la plataforma
+///
+/// Un único documento que la administración cambia con `PUT /admin/status` y
+/// que se publica en `GET /public/status` (ver [`crate::api::status`]).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EstadoPlataforma {
+    /// Siempre [`crate::api::status::ID_ESTADO_PLATAFORMA`]
+    #[serde(rename = "_id")]
+    pub id: String,
+    #[serde(default)]
+    pub mantenimiento: bool,
+    /// Descripción de la incidencia en curso
+    #[serde(default)]
+    pub incidencia: Option<String>,
+    #[serde(default)]
+    pub updated_at: i64, // timestamp unix
+}
+
+/// Cambio de una reserva en el diario de reservas
+///
+/// Lo guarda la colección `diario_reservas` en el modo de persistencia
+/// [`ModoPersistencia::Eventos`]; ver [`crate::journal`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntradaDiario {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<mongodb::bson::oid::ObjectId>,
+    pub id_restaurante: mongodb::bson::oid::ObjectId,
+    pub id_reserva: mongodb::bson::oid::ObjectId,
+    /// Posición del cambio entre los de su reserva, desde 1
+    pub secuencia: i64,
+    /// Operación que lo originó ("confirmar_reserva", "recordatorio"...)
+    pub motivo: String,
+    /// La reserva completa tras el cambio; `None` si se borró
+    pub reserva: Option<mongodb::bson::Document>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    pub created_at: i64, // timestamp unix
+}
+
+/// Cómo se guardan los cambios de las reservas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ModoPersistencia {
+    /// Solo el estado actual de cada reserva, en la colección `reservas`
+    #[default]
+    Crud,
+    /// Cada cambio, además, como entrada inmutable del diario de reservas,
+    /// del que `reservas` es una proyección (ver [`crate::journal`])
+    Eventos,
+}
+
+impl ModoPersistencia {
+    /// Lee `RESERVAS_PERSISTENCIA`: `crud` o `eventos` (default: crud)
+    pub fn from_env() -> Self {
+        match env::var("RESERVAS_PERSISTENCIA").as_deref().map(str::trim) {
+            Ok("eventos") => ModoPersistencia::Eventos,
+            Ok("crud") | Ok("") | Err(_) => ModoPersistencia::Crud,
+            Ok(otro) => {
+                tracing::warn!("RESERVAS_PERSISTENCIA desconocido ({}); se usa crud", otro);
+                ModoPersistencia::Crud
+            }
+        }
+    }
+}
+
+/// Sufijo del nombre de la base de datos de pruebas (ver [`MongoRepo::sandbox`])
+pub const SUFIJO_SANDBOX: &str = "_sandbox";
+
+#[derive(Debug, Clone)]
+pub struct MongoRepo {
+    pub client: Client,
+    pub database: Database,
+    /// Cómo se guardan los cambios de las reservas
+    pub persistencia: ModoPersistencia,
+}
+
+impl MongoRepo {
+    pub async fn init() -> Result<MongoRepo> {
+        let mongo_uri = env::var("MONGODB_URI")
+            .unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+
+        let database_name = env::var("MONGODB_DATABASE")
+            .unwrap_or_else(|_| "pispas_reservation".to_string());
+
+        let repo = Self::connect(&mongo_uri, &database_name).await?;
+        Ok(repo.with_persistencia(ModoPersistencia::from_env()))
+    }
+
+    /// Conecta con una URI y base de datos concretas (sin leer el entorno)
+    ///
+    /// Usado por [`MongoRepo::init`] y por los tests de integración, que
+    /// apuntan a un MongoDB efímero.
+    pub async fn connect(mongo_uri: &str, database_name: &str) -> Result<MongoRepo> {
+        let client = Client::with_uri_str(mongo_uri)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error conectando a MongoDB: {}", e)))?;
+
+        let database = client.database(database_name);
+
+        // Test connection
+        database
+            .run_command(mongodb::bson::doc! {"ping": 1})
+            .await
+            .map_err(|e| AppError::Internal(format!("Error validando conexión MongoDB: {}", e)))?;
+
+        tracing::info!("Conexión a MongoDB establecida exitosamente");
+
+        Ok(MongoRepo { client, database, persistencia: ModoPersistencia::default() })
+    }
+
+    /// El mismo repositorio con otro modo de persistencia de las reservas
+    pub fn with_persistencia(mut self, persistencia: ModoPersistencia) -> MongoRepo {
+        self.persistencia = persistencia;
+        self
+    }
+
+    /// Comprueba que la base de datos responde
+    pub async fn ping(&self) -> Result<()> {
+        self.database
+            .run_command(mongodb::bson::doc! {"ping": 1})
+            .await
+            .map_err(|e| AppError::database("ping", e))?;
+        Ok(())
+    }
+
+    /// Base de datos de pruebas, paralela a esta y en el mismo servidor
+    ///
+    /// Se llama como la base de datos con el sufijo [`SUFIJO_SANDBOX`]; ver
+    /// [`crate::api::sandbox`].
+    pub fn sandbox(&self) -> MongoRepo {
+        let nombre = format!("{}{}", self.database.name(), SUFIJO_SANDBOX);
+        MongoRepo {
+            client: self.client.clone(),
+            database: self.client.database(&nombre),
+            persistencia: self.persistencia,
+        }
+    }
+
+    /// Si este repositorio es el de la base de datos de pruebas
+    pub fn is_sandbox(&self) -> bool {
+        self.database.name().ends_with(SUFIJO_SANDBOX)
+    }
+
+    pub fn restaurants(&self) -> Collection<Restaurant> {
+        self.database.collection("restaurants")
+    }
+
+    pub fn mesas(&self) -> Collection<Mesa> {
+        self.database.collection("mesas")
+    }
+
+    pub fn empleados(&self) -> Collection<Empleado> {
+        self.database.collection("empleados")
+    }
+
+    pub fn plantas(&self) -> Collection<Planta> {
+        self.database.collection("plantas")
+    }
+
+    pub fn distribuciones(&self) -> Collection<Distribucion> {
+        self.database.collection("distribuciones")
+    }
+
+    pub fn reservas(&self) -> Collection<Reserva> {
+        self.database.collection("reservas")
+    }
+
+    pub fn widget_violaciones(&self) -> Collection<ViolacionWidget> {
+        self.database.collection("widget_violaciones")
+    }
+
+    pub fn opciones_menu(&self) -> Collection<OpcionMenu> {
+        self.database.collection("opciones_menu")
+    }
+
+    pub fn reglas_bloqueo(&self) -> Collection<ReglaBloqueo> {
+        self.database.collection("reglas_bloqueo")
+    }
+
+    pub fn clientes(&self) -> Collection<Cliente> {
+        self.database.collection("clientes")
+    }
+
+    pub fn fusiones_clientes(&self) -> Collection<FusionClientes> {
+        self.database.collection("fusiones_clientes")
+    }
+
+    pub fn informes_anonimizacion(&self) -> Collection<InformeAnonimizacion> {
+        self.database.collection("informes_anonimizacion")
+    }
+
+    pub fn reconstrucciones(&self) -> Collection<Reconstruccion> {
+        self.database.collection("reconstrucciones")
+    }
+
+    pub fn estadisticas_diarias(&self) -> Collection<EstadisticaDiaria> {
+        self.database.collection("stats_daily")
+    }
+
+    pub fn snapshots_plano(&self) -> Collection<SnapshotPlano> {
+        self.database.collection("snapshots_plano")
+    }
+
+    pub fn papelera_mesas(&self) -> Collection<MesasBorradas> {
+        self.database.collection("papelera_mesas")
+    }
+
+    pub fn confirmaciones_borrado(&self) -> Collection<ConfirmacionBorrado> {
+        self.database.collection("confirmaciones_borrado")
+    }
+
+    pub fn solicitudes_grupo(&self) -> Collection<SolicitudGrupo> {
+        self.database.collection("solicitudes_grupo")
+    }
+
+    pub fn registros_turno(&self) -> Collection<RegistroTurno> {
+        self.database.collection("registros_turno")
+    }
+
+    pub fn notas_traspaso(&self) -> Collection<NotaTraspaso> {
+        self.database.collection("notas_traspaso")
+    }
+
+    pub fn alertas(&self) -> Collection<AlertaEnviada> {
+        self.database.collection("alertas")
+    }
+
+    pub fn tokens_recuperacion(&self) -> Collection<TokenRecuperacion> {
+        self.database.collection("tokens_recuperacion")
+    }
 
-#[derive(Debug, Clone)]
-pub struct MongoRepo {
-    pub client: Client,
-    pub database: Database,
-}
+    pub fn reclamaciones(&self) -> Collection<Reclamacion> {
+        self.database.collection("reclamaciones")
+    }
 
-impl MongoRepo {
-    pub async fn init() -> Result<MongoRepo> {
-        let mongo_uri = env::var("MONGODB_URI")
-            .unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
+    pub fn estados_oauth(&self) -> Collection<EstadoOAuth> {
+        self.database.collection("estados_oauth")
+    }
 
-        let client = Client::with_uri_str(&mongo_uri)
-            .await
-            .map_err(|e| AppError::Internal(format!("Error conectando a MongoDB: {}", e)))?;
+    pub fn intentos_login(&self) -> Collection<IntentoLogin> {
+        self.database.collection("intentos_login")
+    }
 
-        let database_name = env::var("MONGODB_DATABASE")
-            .unwrap_or_else(|_| "pispas_reservation".to_string());
+    pub fn consultas_localizador(&self) -> Collection<ConsultasLocalizador> {
+        self.database.collection("consultas_localizador")
+    }
 
-        let database = client.database(&database_name);
+    pub fn fallos_localizador(&self) -> Collection<FallosLocalizador> {
+        self.database.collection("fallos_localizador")
+    }
 
-        // Test connection
-        database
-            .run_command(mongodb::bson::doc! {"ping": 1})
-            .await
-            .map_err(|e| AppError::Internal(format!("Error validando conexión MongoDB: {}", e)))?;
+    pub fn sesiones(&self) -> Collection<Sesion> {
+        self.database.collection("sesiones")
+    }
 
-        tracing::info!("Conexión a MongoDB establecida exitosamente");
+    pub fn webhooks_recibidos(&self) -> Collection<WebhookRecibido> {
+        self.database.collection("webhooks_recibidos")
+    }
 
-        Ok(MongoRepo { client, database })
+    pub fn peticiones_idempotentes(&self) -> Collection<PeticionIdempotente> {
+        self.database.collection("peticiones_idempotentes")
     }
 
-    pub fn restaurants(&self) -> Collection<Restaurant> {
-        self.database.collection("restaurants")
+    pub fn claves_api(&self) -> Collection<ClaveApi> {
+        self.database.collection("claves_api")
     }
 
-    pub fn mesas(&self) -> Collection<Mesa> {
-        self.database.collection("mesas")
+    pub fn mensajes_reserva(&self) -> Collection<MensajeReserva> {
+        self.database.collection("mensajes_reserva")
     }
 
-    pub fn reservas(&self) -> Collection<Reserva> {
-        self.database.collection("reservas")
+    pub fn suscripciones_webhook(&self) -> Collection<SuscripcionWebhook> {
+        self.database.collection("suscripciones_webhook")
+    }
+
+    pub fn eventos(&self) -> Collection<Evento> {
+        self.database.collection("eventos")
+    }
+
+    pub fn audit_log(&self) -> Collection<EntradaAuditoria> {
+        self.database.collection("audit_log")
+    }
+
+    pub fn diario_reservas(&self) -> Collection<EntradaDiario> {
+        self.database.collection("diario_reservas")
+    }
+
+    pub fn eventos_reserva(&self) -> Collection<EventoReserva> {
+        self.database.collection("reservation_events")
+    }
+
+    pub fn checkpoints(&self) -> Collection<Checkpoint> {
+        self.database.collection("checkpoints")
+    }
+
+    pub fn estado_plataforma(&self) -> Collection<EstadoPlataforma> {
+        self.database.collection("estado_plataforma")
+    }
+
+    /// Convierte los tokens guardados en los restaurantes (anteriores a las
+    /// sesiones) en una sesión cada uno, para no cerrar las sesiones abiertas
+    ///
+    /// No hace nada si ya no queda ningún restaurante con token.
+    pub async fn migrate_restaurant_tokens(&self) -> Result<()> {
+        use futures_util::TryStreamExt;
+        use mongodb::bson::{doc, Document};
+
+        let restaurants = self.database.collection::<Document>("restaurants");
+        let mut cursor = restaurants
+            .find(doc! { "access_token": { "$exists": true } })
+            .await
+            .map_err(|e| AppError::database("find_restaurant_tokens", e))?;
+
+        while let Some(restaurant) = cursor.try_next().await
+            .map_err(|e| AppError::database("find_restaurant_tokens", e))?
+        {
+            let (Ok(id), Ok(token)) = (restaurant.get_object_id("_id"), restaurant.get_str("access_token")) else {
+                continue;
+            };
+            let created_at = restaurant.get_i64("created_at").unwrap_or_default();
+            self.sesiones()
+                .update_one(
+                    doc! { "token": token },
+                    doc! { "$setOnInsert": {
+                        "id_restaurante": id,
+                        "token": token,
+                        "created_at": created_at,
+                        "last_used_at": created_at,
+                    } },
+                )
+                .upsert(true)
+                .await
+                .map_err(|e| AppError::database("migrate_restaurant_token", e))?;
+            restaurants
+                .update_one(doc! { "_id": id }, doc! { "$unset": { "access_token": "" } })
+                .await
+                .map_err(|e| AppError::database("migrate_restaurant_token", e))?;
+        }
+
+        // El índice único sobre el token ya no sirve: los restaurantes nuevos
+        // no tienen token y chocarían entre sí
+        if let Err(e) = restaurants.drop_index("access_token_1").await {
+            tracing::debug!("Índice access_token_1 no eliminado: {}", e);
+        }
+
+        Ok(())
+    }
+
+    /// Rellena `nombre_normalizado` en los restaurantes y mesas anteriores
+    /// al campo, antes de crear sus índices únicos
+    ///
+    /// Los documentos sin el campo, o con el valor vacío que les daba el
+    /// valor por defecto, reciben el nombre normalizado de su `nombre`. Si ya
+    /// lo tiene otro restaurante (o, en las mesas, otra mesa del mismo
+    /// restaurante), el campo se quita y se avisa en el log: el índice es
+    /// parcial y no incluye los documentos sin él, así que se crea igualmente
+    /// y el duplicado queda pendiente de renombrar.
+    pub async fn migrate_normalized_names(&self) -> Result<()> {
+        use futures_util::TryStreamExt;
+        use mongodb::bson::{doc, Document};
+
+        let pendientes = doc! { "$or": [
+            { "nombre_normalizado": { "$exists": false } },
+            { "nombre_normalizado": "" },
+        ] };
+
+        for (coleccion, ambito) in [("restaurants", None), ("mesas", Some("id_restaurante"))] {
+            let documentos = self.database.collection::<Document>(coleccion);
+            let mut cursor = documentos
+                .find(pendientes.clone())
+                .await
+                .map_err(|e| AppError::database("find_unnormalized_names", e))?;
+
+            while let Some(documento) = cursor.try_next().await
+                .map_err(|e| AppError::database("find_unnormalized_names", e))?
+            {
+                let (Ok(id), Ok(nombre)) = (documento.get_object_id("_id"), documento.get_str("nombre")) else {
+                    continue;
+                };
+                let normalizado = normalize_name(nombre);
+
+                let mut mismo_nombre = doc! { "_id": { "$ne": id }, "nombre_normalizado": &normalizado };
+                if let Some(ambito) = ambito {
+                    mismo_nombre.insert(ambito, documento.get(ambito).cloned().unwrap_or_default());
+                }
+                let duplicado = documentos
+                    .find_one(mismo_nombre)
+                    .await
+                    .map_err(|e| AppError::database("migrate_normalized_name", e))?
+                    .is_some();
+
+                let cambio = if duplicado {
+                    tracing::warn!(coleccion, %id, nombre, "Nombre duplicado al normalizarlo; queda sin nombre normalizado");
+                    doc! { "$unset": { "nombre_normalizado": "" } }
+                } else {
+                    doc! { "$set": { "nombre_normalizado": &normalizado } }
+                };
+                documentos
+                    .update_one(doc! { "_id": id }, cambio)
+                    .await
+                    .map_err(|e| AppError::database("migrate_normalized_name", e))?;
+            }
+        }
+
+        Ok(())
     }
 
     // Método para crear índices si es necesario
@@ -100,6 +2484,9 @@ impl MongoRepo {
         use mongodb::{options::IndexOptions, IndexModel};
         use mongodb::bson::doc;
 
+        self.migrate_restaurant_tokens().await?;
+        self.migrate_normalized_names().await?;
+
         // Índices para restaurants
         let restaurants = self.restaurants();
         let restaurant_indexes = vec![
@@ -112,8 +2499,29 @@ impl MongoRepo {
                 .options(IndexOptions::builder().unique(true).build())
                 .build(),
             IndexModel::builder()
-                .keys(doc! { "access_token": 1 })
-                .options(IndexOptions::builder().unique(true).build())
+                .keys(doc! { "id_grupo": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "nombre_normalizado": 1 })
+                .options(IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "nombre_normalizado": {"$exists": true} })
+                    .build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "google_sub": 1 })
+                .options(IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "google_sub": {"$exists": true} })
+                    .build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "token_calendario": 1 })
+                .options(IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "token_calendario": {"$exists": true} })
+                    .build())
                 .build(),
         ];
 
@@ -128,10 +2536,27 @@ impl MongoRepo {
             IndexModel::builder()
                 .keys(doc! { "id_restaurante": 1 })
                 .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "id_planta": 1 })
+                .build(),
             IndexModel::builder()
                 .keys(doc! { "id_restaurante": 1, "nombre": 1 })
                 .options(IndexOptions::builder().unique(true).build())
                 .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "nombre_normalizado": 1 })
+                .options(IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "nombre_normalizado": {"$exists": true} })
+                    .build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "uuid": 1 })
+                .options(IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "uuid": {"$exists": true} })
+                    .build())
+                .build(),
         ];
 
         mesas
@@ -141,6 +2566,14 @@ impl MongoRepo {
 
         // Índices para reservas
         let reservas = self.reservas();
+
+        // El índice único sobre mesa, fecha y hora impedía volver a reservar
+        // la hora de una reserva cancelada o terminada; los solapes ya los
+        // rechaza `validate_reservation` según el estado
+        if let Err(e) = reservas.drop_index("id_mesa_1_fecha_1_hora_1").await {
+            tracing::debug!("Índice id_mesa_1_fecha_1_hora_1 no eliminado: {}", e);
+        }
+
         let reservation_indexes = vec![
             IndexModel::builder()
                 .keys(doc! { "id_restaurante": 1 })
@@ -152,8 +2585,63 @@ impl MongoRepo {
                 .keys(doc! { "estado": 1 })
                 .build(),
             IndexModel::builder()
-                .keys(doc! { "id_mesa": 1, "fecha": 1, "hora": 1 })
-                .options(IndexOptions::builder().unique(true).build())
+                .keys(doc! { "id_restaurante": 1, "fecha": 1 })
+                .build(),
+            // Órdenes de `GET /reservations`
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "fecha": 1, "hora": 1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "created_at": 1 })
+                .build(),
+            // Historial de rotaciones de `GET /visual/eta`
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "estado": 1, "completada_en": -1 })
+                .build(),
+            // Tiempo de confirmación de `GET /stats/confirmations` y de las alertas
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "confirmada_en": -1 })
+                .build(),
+            // Orden por nombre y búsqueda por cliente (`q`) de `GET /reservations`
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "nombre_cliente": 1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "email_cliente": 1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "telefono_cliente": 1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "verificacion.codigo": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "dispositivo": 1, "created_at": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_cliente": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "deposito.partes.codigo": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "localizador": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "token_gestion": 1 })
+                .options(IndexOptions::builder().sparse(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "uuid": 1 })
+                .options(IndexOptions::builder()
+                    .unique(true)
+                    .partial_filter_expression(doc! { "uuid": {"$exists": true} })
+                    .build())
                 .build(),
         ];
 
@@ -162,12 +2650,437 @@ impl MongoRepo {
             .await
             .map_err(|e| AppError::Internal(format!("Error creando índices reservas: {}", e)))?;
 
+        // Índices para violaciones del widget
+        self.widget_violaciones()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "created_at": -1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices widget_violaciones: {}", e)))?;
+
+        // Índices para opciones de menú
+        self.opciones_menu()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices opciones_menu: {}", e)))?;
+
+        // Índices para reglas de bloqueo de franjas
+        self.reglas_bloqueo()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices reglas_bloqueo: {}", e)))?;
+
+        // Índices para clientes del CRM
+        let cliente_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "emails": 1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "telefonos": 1 })
+                .build(),
+        ];
+
+        self.clientes()
+            .create_indexes(cliente_indexes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices clientes: {}", e)))?;
+
+        // Índices para el personal
+        let empleado_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "access_token": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "usuario": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+        ];
+
+        self.empleados()
+            .create_indexes(empleado_indexes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices empleados: {}", e)))?;
+
+        // Índices para plantas
+        self.plantas()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "orden": 1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices plantas: {}", e)))?;
+
+        // Índices para distribuciones de mesas
+        self.distribuciones()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices distribuciones: {}", e)))?;
+
+        // Índices para la papelera de mesas
+        self.papelera_mesas()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "created_at": -1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices papelera_mesas: {}", e)))?;
+        self.confirmaciones_borrado()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "token": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices confirmaciones_borrado: {}", e)))?;
+
+        // Índices para solicitudes de grupo
+        self.solicitudes_grupo()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "estado": 1, "created_at": -1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices solicitudes_grupo: {}", e)))?;
+
+        // Índices para snapshots del plano
+        self.snapshots_plano()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "created_at": -1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices snapshots_plano: {}", e)))?;
+
+        // Índices para registros de turno (una entrada por persona y turno)
+        self.registros_turno()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "fecha": 1, "turno": 1, "usuario": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices registros_turno: {}", e)))?;
+
+        // Índices para notas de traspaso
+        self.notas_traspaso()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "fecha": 1, "created_at": 1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices notas_traspaso: {}", e)))?;
+
+        // Índices para alertas enviadas
+        self.alertas()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "clave": 1, "created_at": -1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices alertas: {}", e)))?;
+
+        // Índices para tokens de recuperación de contraseña
+        let token_recuperacion_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "token": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1 })
+                .build(),
+        ];
+        self.tokens_recuperacion()
+            .create_indexes(token_recuperacion_indexes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices tokens_recuperacion: {}", e)))?;
+
+        // Índices para reclamaciones de cuentas (una activa por restaurante)
+        self.reclamaciones()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices reclamaciones: {}", e)))?;
+
+        // Índices para inicios de sesión OAuth2
+        self.estados_oauth()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "state": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices estados_oauth: {}", e)))?;
+
+        // Índices para logins fallidos (uno por nombre o IP)
+        self.intentos_login()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "clave": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices intentos_login: {}", e)))?;
+
+        // Índices para consultas por localizador (una por IP)
+        self.consultas_localizador()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "ip": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices consultas_localizador: {}", e)))?;
+
+        // Índices para consultas fallidas por localizador (una por localizador)
+        self.fallos_localizador()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "localizador": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices fallos_localizador: {}", e)))?;
+
+        // Índices para sesiones del propietario
+        let sesion_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "token": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "last_used_at": -1 })
+                .build(),
+        ];
+        self.sesiones()
+            .create_indexes(sesion_indexes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices sesiones: {}", e)))?;
+
+        // Índices para webhooks recibidos (una entrada por firma)
+        let webhook_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "clave": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "created_at": 1 })
+                .build(),
+        ];
+        self.webhooks_recibidos()
+            .create_indexes(webhook_indexes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices webhooks_recibidos: {}", e)))?;
+
+        // Índices para peticiones con Idempotency-Key
+        let idempotencia_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "clave": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "expira_en": 1 })
+                .options(IndexOptions::builder().expire_after(std::time::Duration::ZERO).build())
+                .build(),
+        ];
+        self.peticiones_idempotentes()
+            .create_indexes(idempotencia_indexes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices peticiones_idempotentes: {}", e)))?;
+        // Las claves caducadas se borraban por `created_at` en cada petición
+        if let Err(e) = self.peticiones_idempotentes().drop_index("created_at_1").await {
+            tracing::debug!("Índice created_at_1 no eliminado: {}", e);
+        }
+
+        // Índices para claves de API
+        let clave_api_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "token": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1 })
+                .build(),
+        ];
+        self.claves_api()
+            .create_indexes(clave_api_indexes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices claves_api: {}", e)))?;
+
+        // Índices para mensajes de reservas: el hilo y los no leídos
+        let mensaje_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "id_reserva": 1, "created_at": 1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "autor": 1, "leido_en": 1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "created_at": 1 })
+                .build(),
+        ];
+        self.mensajes_reserva()
+            .create_indexes(mensaje_indexes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices mensajes_reserva: {}", e)))?;
+
+        // Índices para suscripciones de webhook
+        self.suscripciones_webhook()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices suscripciones_webhook: {}", e)))?;
+
+        // Índices para eventos de dominio (y sus entregas por webhook)
+        let evento_indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "created_at": 1, "_id": 1 })
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "id_restaurante": 1, "_id": 1 })
+                .build(),
+        ];
+        self.eventos()
+            .create_indexes(evento_indexes)
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices eventos: {}", e)))?;
+
+        // Índices para el registro de auditoría
+        self.audit_log()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "created_at": -1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices audit_log: {}", e)))?;
+
+        // Índices para el historial de cada reserva
+        self.eventos_reserva()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_reserva": 1, "created_at": 1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices reservation_events: {}", e)))?;
+
+        // Índices para el diario de reservas (una secuencia por reserva)
+        self.diario_reservas()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_reserva": 1, "secuencia": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices diario_reservas: {}", e)))?;
+
+        // Índices para estadísticas diarias (una por restaurante y día)
+        self.estadisticas_diarias()
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "id_restaurante": 1, "fecha": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            )
+            .await
+            .map_err(|e| AppError::Internal(format!("Error creando índices stats_daily: {}", e)))?;
+
         tracing::info!("Índices MongoDB creados exitosamente");
         Ok(())
     }
+}
 
-    // Función auxiliar para obtener timestamp actual
-    pub fn current_timestamp() -> i64 {
-        chrono::Utc::now().timestamp()
-    }
-}
\ No newline at end of file
+/// Caracteres del localizador (sin 0/O ni 1/I, que se confunden al dictarlo)
+const ALFABETO_LOCALIZADOR: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Longitud del localizador de una reserva
+pub const LONGITUD_LOCALIZADOR: usize = 8;
+
+/// Genera el localizador de una reserva a partir de bits aleatorios
+///
+/// # Ejemplo
+/// ```
+/// use pispas_reservation::db::localizador;
+///
+/// assert_eq!(localizador(0), "AAAAAAAA");
+/// assert_eq!(localizador(31), "9AAAAAAA");
+/// ```
+pub fn localizador(aleatorio: u128) -> String {
+    let base = ALFABETO_LOCALIZADOR.len() as u128;
+    (0..LONGITUD_LOCALIZADOR as u32)
+        .map(|i| ALFABETO_LOCALIZADOR[((aleatorio / base.pow(i)) % base) as usize] as char)
+        .collect()
+}
+
+/// Normaliza un nombre para comparaciones de unicidad
+///
+/// Elimina espacios al inicio y final, colapsa espacios intermedios,
+/// pasa a minúsculas y elimina diacríticos, de forma que "Mesa 1",
+/// " mesa  1 " y "Mésa 1" producen la misma clave.
+///
+/// # Ejemplo
+/// ```
+/// use pispas_reservation::db::normalize_name;
+///
+/// assert_eq!(normalize_name("  Mésa   1 "), "mesa 1");
+/// ```
+pub fn normalize_name(name: &str) -> String {
+    let sin_diacriticos: String = name
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect();
+
+    sin_diacriticos
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Código de MongoDB para una clave única duplicada
+const CLAVE_DUPLICADA: i32 = 11000;
+
+/// Comprueba si un error de escritura es por una clave única duplicada
+pub fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    use mongodb::error::{ErrorKind, WriteFailure};
+
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(e)) if e.code == CLAVE_DUPLICADA
+    )
+}
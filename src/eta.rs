@@ -0,0 +1,117 @@
+//! # Previsión de mesas libres
+//!
+//! Reglas puras (sin base de datos) de `GET /visual/eta`, que estima cuándo
+//! quedará libre cada mesa ocupada para dar esperas realistas a los clientes
+//! sin reserva.
+//!
+//! La duración de una mesa sale del historial de rotaciones del restaurante
+//! (de sentada a completada):
+//!
+//! - Se usa la mediana de los grupos de tamaño parecido (ver [`tramo`]) si
+//!   hay al menos [`MIN_MUESTRAS`]
+//! - Si no, la mediana de todas las rotaciones, con el mismo mínimo
+//! - Sin historial suficiente, la duración configurada de las reservas
+//!
+//! Una mesa que ya ha superado su duración estimada se da como libre "ya":
+//! la previsión nunca queda en el pasado.
+
+/// Rotaciones necesarias para fiarse de una mediana
+pub const MIN_MUESTRAS: usize = 5;
+
+/// Una mesa ya completada del historial
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotacion {
+    pub numero_personas: i32,
+    /// Minutos entre que se sentó el grupo y se completó la reserva
+    pub minutos: i64,
+}
+
+/// Duración estimada de una mesa ocupada
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Duracion {
+    pub minutos: u32,
+    /// Rotaciones en las que se basa; 0 si es la duración configurada
+    pub muestras: usize,
+}
+
+/// Tramo de tamaño de grupo con el que se comparan las rotaciones
+///
+/// Los grupos de 1-2, 3-4, 5-6 y 7 o más personas tardan distinto en comer,
+/// así que se comparan solo dentro de su tramo.
+///
+/// ```
+/// use pispas_reservation::eta::tramo;
+///
+/// assert_eq!(tramo(2), tramo(1));
+/// assert_ne!(tramo(2), tramo(3));
+/// assert_eq!(tramo(12), tramo(7));
+/// ```
+pub fn tramo(numero_personas: i32) -> i32 {
+    ((numero_personas.max(1) - 1) / 2).min(3)
+}
+
+/// Duración típica de una mesa con `numero_personas` según el historial
+///
+/// ```
+/// use pispas_reservation::eta::{duracion_tipica, Rotacion};
+///
+/// let pareja = |minutos| Rotacion { numero_personas: 2, minutos };
+/// let historial: Vec<Rotacion> = [50, 60, 70, 80, 90].into_iter().map(pareja).collect();
+///
+/// // Suficientes parejas: mediana de su tramo
+/// let duracion = duracion_tipica(&historial, 2, 120);
+/// assert_eq!((duracion.minutos, duracion.muestras), (70, 5));
+///
+/// // Sin grupos de 6 en el historial: mediana de todas las rotaciones
+/// assert_eq!(duracion_tipica(&historial, 6, 120).minutos, 70);
+///
+/// // Sin historial suficiente: duración configurada
+/// let duracion = duracion_tipica(&historial[..2], 2, 120);
+/// assert_eq!((duracion.minutos, duracion.muestras), (120, 0));
+/// ```
+pub fn duracion_tipica(historial: &[Rotacion], numero_personas: i32, por_defecto: u32) -> Duracion {
+    let validas = || historial.iter().filter(|rotacion| rotacion.minutos > 0);
+    let del_tramo: Vec<i64> = validas()
+        .filter(|rotacion| tramo(rotacion.numero_personas) == tramo(numero_personas))
+        .map(|rotacion| rotacion.minutos)
+        .collect();
+    let muestras = if del_tramo.len() >= MIN_MUESTRAS {
+        del_tramo
+    } else {
+        validas().map(|rotacion| rotacion.minutos).collect()
+    };
+    if muestras.len() < MIN_MUESTRAS {
+        return Duracion { minutos: por_defecto, muestras: 0 };
+    }
+    let usadas = muestras.len();
+    Duracion {
+        minutos: u32::try_from(mediana(muestras)).unwrap_or(u32::MAX),
+        muestras: usadas,
+    }
+}
+
+/// Mediana (redondeada hacia abajo) de una lista no vacía
+fn mediana(mut valores: Vec<i64>) -> i64 {
+    valores.sort_unstable();
+    let mitad = valores.len() / 2;
+    if valores.len().is_multiple_of(2) {
+        (valores[mitad - 1] + valores[mitad]) / 2
+    } else {
+        valores[mitad]
+    }
+}
+
+/// Momento (timestamp unix) en que se espera que quede libre una mesa
+///
+/// ```
+/// use pispas_reservation::eta::libre_en;
+///
+/// // Sentados a las 0 con 90 minutos de duración: libre a los 90 minutos
+/// assert_eq!(libre_en(0, 90, 1_800), 5_400);
+///
+/// // Ya pasada la duración estimada: libre ya
+/// assert_eq!(libre_en(0, 90, 6_000), 6_000);
+/// ```
+pub fn libre_en(sentada_en: i64, duracion_minutos: u32, ahora: i64) -> i64 {
+    (sentada_en + i64::from(duracion_minutos) * 60).max(ahora)
+}
@@ -0,0 +1,213 @@
+//! # Eventos de dominio
+//!
+//! Registro de solo-añadir (*append-only*) de lo que ocurre en el sistema:
+//! reservas creadas, confirmadas, sentadas, completadas, modificadas,
+//! canceladas o traspasadas, clientes que no se presentan, solicitudes de
+//! grupo, depósitos y pagos por adelantado, altas de restaurantes... Cada
+//! evento se guarda en la colección `eventos` y no se modifica después.
+//!
+//! El registro sirve de rastro de auditoría y de fuente para analítica
+//! fuera de línea: se exporta como JSON Lines desde
+//! `GET /admin/events/export` y, opcionalmente, se envía de forma continua
+//! a un bucket (ver [`crate::jobs::event_shipping`]).
+//!
+//! Registrar un evento nunca hace fallar la operación que lo origina: si
+//! MongoDB rechaza la escritura, el error queda en el log.
+//!
+//! Cada evento guarda el ID de la petición que lo originó (`request_id`, ver
+//! [`crate::api::request_id`]), que se exporta y viaja con sus webhooks.
+
+use mongodb::bson::{oid::ObjectId, Bson, Document};
+use serde::Serialize;
+use crate::api::request_id;
+use crate::db::{Evento, MongoRepo};
+
+/// Tipos de evento de dominio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TipoEvento {
+    RestauranteRegistrado,
+    ContrasenaRestablecida,
+    CuentaReclamada,
+    CuentaGoogleVinculada,
+    EstadoCuentaCambiado,
+    SesionRevocada,
+    SuplantacionIniciada,
+    ReservaCreada,
+    ReservaConfirmada,
+    ReservaCancelada,
+    ReservaModificada,
+    ReservaTraspasada,
+    ReservaSentada,
+    ReservaCompletada,
+    ReservaNoShow,
+    RetencionLegalMarcada,
+    RetencionLegalLevantada,
+    DepositoPagado,
+    PagoRegistrado,
+    PagoReembolsado,
+    SolicitudGrupoRecibida,
+    CuentaPurgada,
+}
+
+impl TipoEvento {
+    /// Todos los tipos de evento
+    pub const TODOS: [TipoEvento; 22] = [
+        TipoEvento::RestauranteRegistrado,
+        TipoEvento::ContrasenaRestablecida,
+        TipoEvento::CuentaReclamada,
+        TipoEvento::CuentaGoogleVinculada,
+        TipoEvento::EstadoCuentaCambiado,
+        TipoEvento::SesionRevocada,
+        TipoEvento::SuplantacionIniciada,
+        TipoEvento::ReservaCreada,
+        TipoEvento::ReservaConfirmada,
+        TipoEvento::ReservaCancelada,
+        TipoEvento::ReservaModificada,
+        TipoEvento::ReservaTraspasada,
+        TipoEvento::ReservaSentada,
+        TipoEvento::ReservaCompletada,
+        TipoEvento::ReservaNoShow,
+        TipoEvento::RetencionLegalMarcada,
+        TipoEvento::RetencionLegalLevantada,
+        TipoEvento::DepositoPagado,
+        TipoEvento::PagoRegistrado,
+        TipoEvento::PagoReembolsado,
+        TipoEvento::SolicitudGrupoRecibida,
+        TipoEvento::CuentaPurgada,
+    ];
+
+    /// Tipo de evento por su nombre
+    ///
+    /// ```
+    /// use pispas_reservation::events::TipoEvento;
+    ///
+    /// assert_eq!(TipoEvento::parse("reserva_creada"), Some(TipoEvento::ReservaCreada));
+    /// assert_eq!(TipoEvento::parse("reserva_borrada"), None);
+    /// ```
+    pub fn parse(nombre: &str) -> Option<TipoEvento> {
+        TipoEvento::TODOS.into_iter().find(|tipo| tipo.as_str() == nombre)
+    }
+
+    /// Nombre con el que se guarda el evento
+    ///
+    /// ```
+    /// use pispas_reservation::events::TipoEvento;
+    ///
+    /// assert_eq!(TipoEvento::ReservaCreada.as_str(), "reserva_creada");
+    /// ```
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TipoEvento::RestauranteRegistrado => "restaurante_registrado",
+            TipoEvento::ContrasenaRestablecida => "contrasena_restablecida",
+            TipoEvento::CuentaReclamada => "cuenta_reclamada",
+            TipoEvento::CuentaGoogleVinculada => "cuenta_google_vinculada",
+            TipoEvento::EstadoCuentaCambiado => "estado_cuenta_cambiado",
+            TipoEvento::SesionRevocada => "sesion_revocada",
+            TipoEvento::SuplantacionIniciada => "suplantacion_iniciada",
+            TipoEvento::ReservaCreada => "reserva_creada",
+            TipoEvento::ReservaConfirmada => "reserva_confirmada",
+            TipoEvento::ReservaCancelada => "reserva_cancelada",
+            TipoEvento::ReservaModificada => "reserva_modificada",
+            TipoEvento::ReservaTraspasada => "reserva_traspasada",
+            TipoEvento::ReservaSentada => "reserva_sentada",
+            TipoEvento::ReservaCompletada => "reserva_completada",
+            TipoEvento::ReservaNoShow => "reserva_no_show",
+            TipoEvento::RetencionLegalMarcada => "retencion_legal_marcada",
+            TipoEvento::RetencionLegalLevantada => "retencion_legal_levantada",
+            TipoEvento::DepositoPagado => "deposito_pagado",
+            TipoEvento::PagoRegistrado => "pago_registrado",
+            TipoEvento::PagoReembolsado => "pago_reembolsado",
+            TipoEvento::SolicitudGrupoRecibida => "solicitud_grupo_recibida",
+            TipoEvento::CuentaPurgada => "cuenta_purgada",
+        }
+    }
+}
+
+/// Registra un evento de dominio
+///
+/// # Parámetros
+/// - `id_restaurante`: Restaurante al que pertenece el evento
+/// - `id_entidad`: Entidad afectada (la reserva, el propio restaurante...)
+/// - `datos`: Datos propios del tipo de evento, sin datos personales
+/// - `now`: Timestamp del evento
+pub async fn record(
+    repo: &MongoRepo,
+    tipo: TipoEvento,
+    id_restaurante: Option<ObjectId>,
+    id_entidad: Option<ObjectId>,
+    datos: Document,
+    now: i64,
+) {
+    let evento = Evento {
+        id: None,
+        tipo: tipo.as_str().to_string(),
+        id_restaurante,
+        id_entidad,
+        datos,
+        request_id: request_id::current(),
+        created_at: now,
+    };
+
+    if let Err(e) = repo.eventos().insert_one(evento).await {
+        tracing::error!(tipo = tipo.as_str(), "Error registrando evento: {}", e);
+    }
+}
+
+/// Formato de un evento en la exportación JSON Lines
+#[derive(Serialize)]
+struct LineaEvento<'a> {
+    id: Option<String>,
+    tipo: &'a str,
+    id_restaurante: Option<String>,
+    id_entidad: Option<String>,
+    datos: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<&'a str>,
+    created_at: i64,
+}
+
+/// Línea JSON (terminada en `\n`) de un evento para la exportación
+///
+/// Los IDs se exportan como cadenas hexadecimales y `datos` en JSON
+/// extendido relajado.
+///
+/// ```
+/// use mongodb::bson::doc;
+/// use pispas_reservation::db::Evento;
+/// use pispas_reservation::events::json_line;
+///
+/// let evento = Evento {
+///     id: None,
+///     tipo: "reserva_creada".to_string(),
+///     id_restaurante: None,
+///     id_entidad: None,
+///     datos: doc! { "personas": 4 },
+///     request_id: None,
+///     created_at: 1717243200,
+/// };
+/// assert_eq!(
+///     json_line(&evento),
+///     "{\"id\":null,\"tipo\":\"reserva_creada\",\"id_restaurante\":null,\"id_entidad\":null,\"datos\":{\"personas\":4},\"created_at\":1717243200}\n"
+/// );
+/// ```
+pub fn json_line(evento: &Evento) -> String {
+    format!("{}\n", serde_json::to_string(&linea(evento)).unwrap_or_default())
+}
+
+/// Evento en JSON, con los mismos campos que en la exportación
+pub fn to_json(evento: &Evento) -> serde_json::Value {
+    serde_json::to_value(linea(evento)).unwrap_or_default()
+}
+
+fn linea(evento: &Evento) -> LineaEvento<'_> {
+    LineaEvento {
+        id: evento.id.map(|id| id.to_hex()),
+        tipo: &evento.tipo,
+        id_restaurante: evento.id_restaurante.map(|id| id.to_hex()),
+        id_entidad: evento.id_entidad.map(|id| id.to_hex()),
+        datos: Bson::Document(evento.datos.clone()).into_relaxed_extjson(),
+        request_id: evento.request_id.as_deref(),
+        created_at: evento.created_at,
+    }
+}
@@ -0,0 +1,161 @@
+//! # Frontend
+//!
+//! Sirve el frontend (una SPA) bajo la ruta de [`ConfigFrontend::montaje`]:
+//!
+//! - Los archivos que existen se sirven tal cual; la ruta de montaje sirve
+//!   `index.html`
+//! - Las rutas desconocidas que no parecen un archivo (sin extensión en el
+//!   último segmento, ver [`is_spa_route`]) sirven `index.html`, para que
+//!   el enrutado del navegador resuelva `/static/reservas/42`
+//! - Los archivos que faltan (`/static/app.v2.js`) responden 404
+//! - Nunca se listan los directorios
+//!
+//! El frontend se lee del directorio de [`ConfigFrontend::directorio`] o,
+//! compilado con la feature `embed-frontend` y sin `FRONTEND_DIR`, del
+//! directorio `static/` incluido en el binario. Incluido, cada archivo lleva
+//! como `ETag` su hash y el navegador lo revalida en cada visita
+//! (`Cache-Control: no-cache`), recibiendo `304` mientras no cambie el
+//! binario.
+//!
+//! ## Binario único
+//!
+//! Con la feature `single-binary` (que activa `embed-frontend`) el
+//! ejecutable no necesita ningún archivo a su lado: además del frontend,
+//! los textos de los emails y SMS ya están en el código, y los índices de
+//! MongoDB se crean al arrancar ([`crate::db::MongoRepo::create_indexes`]).
+//! El `.env` es opcional: basta con las variables de entorno.
+//!
+//! ```bash
+//! cargo build --release --features single-binary
+//! MONGODB_URI=mongodb://localhost:27017 ./target/release/pispas-reservation
+//! ```
+
+use actix_files::{Files, NamedFile};
+use actix_web::dev::{fn_service, ServiceRequest, ServiceResponse};
+use actix_web::{web, HttpResponse};
+use std::path::PathBuf;
+use crate::config::ConfigFrontend;
+
+/// Página de entrada de la SPA
+pub const INDEX: &str = "index.html";
+
+/// Comprueba si una ruta desconocida debe servir `index.html`: las que no
+/// parecen un archivo porque su último segmento no tiene extensión
+///
+/// ```
+/// use pispas_reservation::frontend::is_spa_route;
+///
+/// assert!(is_spa_route("/static/reservas/42"));
+/// assert!(is_spa_route("/static/"));
+/// assert!(is_spa_route("/static/v1.2/ajustes"));
+/// assert!(!is_spa_route("/static/app.v2.js"));
+/// assert!(!is_spa_route("/static/img/logo.png"));
+/// ```
+pub fn is_spa_route(ruta: &str) -> bool {
+    !ruta.rsplit('/').next().unwrap_or_default().contains('.')
+}
+
+/// Origen del frontend, para el log de arranque
+pub fn source(config: &ConfigFrontend) -> String {
+    match &config.directorio {
+        Some(directorio) => format!("el directorio {}", directorio.display()),
+        None if cfg!(feature = "embed-frontend") => "el binario".to_string(),
+        None => "ningún sitio".to_string(),
+    }
+}
+
+/// Registra el frontend y la redirección de la raíz a él
+pub fn configure(cfg: &mut web::ServiceConfig, config: &ConfigFrontend) {
+    let entrada = format!("{}/", config.montaje);
+    cfg.route("/", web::get().to(move || {
+        let entrada = entrada.clone();
+        async move {
+            HttpResponse::PermanentRedirect()
+                .append_header(("Location", entrada))
+                .finish()
+        }
+    }));
+
+    match &config.directorio {
+        Some(directorio) => serve_directory(cfg, &config.montaje, directorio.clone()),
+        None => serve_embedded(cfg, &config.montaje),
+    }
+}
+
+/// Sirve el frontend desde un directorio
+fn serve_directory(cfg: &mut web::ServiceConfig, montaje: &str, directorio: PathBuf) {
+    let index = directorio.join(INDEX);
+    cfg.service(
+        Files::new(montaje, directorio)
+            .index_file(INDEX)
+            .redirect_to_slash_directory()
+            .default_handler(fn_service(move |req: ServiceRequest| {
+                let index = index.clone();
+                async move {
+                    let (req, _) = req.into_parts();
+                    if !is_spa_route(req.path()) {
+                        return Ok(ServiceResponse::new(req, HttpResponse::NotFound().finish()));
+                    }
+                    let res = NamedFile::open_async(index).await?.into_response(&req);
+                    Ok(ServiceResponse::new(req, res))
+                }
+            })),
+    );
+}
+
+/// Frontend incluido en el binario
+#[cfg(feature = "embed-frontend")]
+#[derive(rust_embed::RustEmbed)]
+#[folder = "static/"]
+struct Embebido;
+
+/// Sirve el frontend incluido en el binario
+#[cfg(feature = "embed-frontend")]
+fn serve_embedded(cfg: &mut web::ServiceConfig, montaje: &str) {
+    use actix_web::http::header;
+
+    async fn serve(req: actix_web::HttpRequest, ruta: web::Path<String>) -> HttpResponse {
+        let ruta = ruta.into_inner();
+        let archivo = match Embebido::get(if ruta.is_empty() { INDEX } else { &ruta }) {
+            Some(archivo) => archivo,
+            None if is_spa_route(req.path()) => match Embebido::get(INDEX) {
+                Some(archivo) => archivo,
+                None => return HttpResponse::NotFound().finish(),
+            },
+            None => return HttpResponse::NotFound().finish(),
+        };
+
+        // El contenido solo cambia con el binario: el navegador revalida
+        // con el hash y recibe 304 si no ha cambiado
+        let etag = format!("\"{}\"", hex::encode(archivo.metadata.sha256_hash()));
+        let revalidado = req.headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|valor| valor.to_str().ok())
+            .is_some_and(|valor| valor.split(',').any(|etiqueta| etiqueta.trim() == etag));
+        let mut res = if revalidado { HttpResponse::NotModified() } else { HttpResponse::Ok() };
+        res.insert_header((header::ETAG, etag.as_str()))
+            .insert_header((header::CACHE_CONTROL, "no-cache"));
+        if revalidado {
+            return res.finish();
+        }
+        res.content_type(archivo.metadata.mimetype())
+            .body(archivo.data.into_owned())
+    }
+
+    let entrada = format!("{}/", montaje);
+    cfg.route(montaje, web::get().to(move || {
+        let entrada = entrada.clone();
+        async move {
+            HttpResponse::PermanentRedirect()
+                .append_header(("Location", entrada))
+                .finish()
+        }
+    }));
+    cfg.route(&format!("{}/{{ruta:.*}}", montaje), web::get().to(serve));
+}
+
+/// Sin la feature `embed-frontend` no hay frontend incluido que servir
+#[cfg(not(feature = "embed-frontend"))]
+fn serve_embedded(_cfg: &mut web::ServiceConfig, montaje: &str) {
+    tracing::warn!("Sin FRONTEND_DIR ni la feature embed-frontend: no se sirve el frontend en {}", montaje);
+}
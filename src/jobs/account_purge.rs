@@ -0,0 +1,144 @@
+//! # Purga de cuentas borradas
+//!
+//! Borra definitivamente los restaurantes pendientes de borrado cuyo plazo
+//! para restaurarlos ha terminado (ver [`crate::api::account_deletion`]),
+//! junto con todos sus datos: mesas, planos, reservas, clientes, personal,
+//! sesiones, claves de API, webhooks, el diario de reservas (ver
+//! [`crate::journal`])... Como en la anonimización, los
+//! eventos de dominio y el registro de auditoría se conservan; cada purga
+//! deja además un evento `cuenta_purgada`.
+//!
+//! ## Configuración
+//!
+//! - `PURGA_CUENTAS_INTERVALO_HORAS`: cada cuánto se ejecuta (default: 24)
+
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::Collection;
+use crate::api::{AppError, AppResult};
+use crate::clock::Clock;
+use crate::db::MongoRepo;
+use crate::events::{self, TipoEvento};
+
+/// Configuración del trabajo de purga
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigPurga {
+    /// Cada cuánto se ejecuta el trabajo
+    pub intervalo: Duration,
+}
+
+impl ConfigPurga {
+    /// Lee `PURGA_CUENTAS_INTERVALO_HORAS`
+    pub fn from_env() -> Self {
+        let horas: u64 = env::var("PURGA_CUENTAS_INTERVALO_HORAS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(24);
+
+        ConfigPurga { intervalo: Duration::from_secs(horas.max(1) * 3600) }
+    }
+}
+
+/// Colecciones con datos de un restaurante, por `id_restaurante`
+fn restaurant_collections(repo: &MongoRepo) -> [Collection<Document>; 28] {
+    [
+        repo.mesas().clone_with_type(),
+        repo.empleados().clone_with_type(),
+        repo.plantas().clone_with_type(),
+        repo.distribuciones().clone_with_type(),
+        repo.reservas().clone_with_type(),
+        repo.eventos_reserva().clone_with_type(),
+        repo.diario_reservas().clone_with_type(),
+        repo.mensajes_reserva().clone_with_type(),
+        repo.widget_violaciones().clone_with_type(),
+        repo.opciones_menu().clone_with_type(),
+        repo.reglas_bloqueo().clone_with_type(),
+        repo.clientes().clone_with_type(),
+        repo.fusiones_clientes().clone_with_type(),
+        repo.estadisticas_diarias().clone_with_type(),
+        repo.snapshots_plano().clone_with_type(),
+        repo.papelera_mesas().clone_with_type(),
+        repo.confirmaciones_borrado().clone_with_type(),
+        repo.solicitudes_grupo().clone_with_type(),
+        repo.registros_turno().clone_with_type(),
+        repo.notas_traspaso().clone_with_type(),
+        repo.alertas().clone_with_type(),
+        repo.tokens_recuperacion().clone_with_type(),
+        repo.reclamaciones().clone_with_type(),
+        repo.estados_oauth().clone_with_type(),
+        repo.sesiones().clone_with_type(),
+        repo.claves_api().clone_with_type(),
+        repo.suscripciones_webhook().clone_with_type(),
+        repo.peticiones_idempotentes().clone_with_type(),
+    ]
+}
+
+/// Borra un restaurante y todos sus datos
+///
+/// # Retorna
+/// El número de documentos borrados, sin contar el del restaurante
+pub async fn purge_restaurant(repo: &MongoRepo, id: ObjectId) -> AppResult<u64> {
+    let mut borrados = 0;
+    for coleccion in restaurant_collections(repo) {
+        let result = coleccion
+            .delete_many(doc! { "id_restaurante": id })
+            .await
+            .map_err(|e| AppError::database("purge_restaurant_data", e))?;
+        borrados += result.deleted_count;
+    }
+    repo.restaurants()
+        .delete_one(doc! { "_id": id })
+        .await
+        .map_err(|e| AppError::database("purge_restaurant", e))?;
+
+    Ok(borrados)
+}
+
+/// Purga los restaurantes cuyo plazo de restauración ha terminado
+///
+/// # Retorna
+/// El número de restaurantes purgados
+pub async fn run(repo: &MongoRepo, clock: &dyn Clock) -> AppResult<u64> {
+    let now = clock.timestamp();
+    let mut cursor = repo.restaurants()
+        .find(doc! { "borrado.purgar_en": { "$lte": now } })
+        .await
+        .map_err(|e| AppError::database("find_restaurants_to_purge", e))?;
+
+    let mut pendientes = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let restaurant = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando restaurant: {}", e)))?;
+        pendientes.extend(restaurant.id);
+    }
+
+    for &id in &pendientes {
+        let borrados = purge_restaurant(repo, id).await?;
+        events::record(
+            repo,
+            TipoEvento::CuentaPurgada,
+            Some(id),
+            Some(id),
+            doc! { "documentos": borrados as i64 },
+            now,
+        ).await;
+        tracing::info!(id_restaurante = %id, documentos = borrados, "Cuenta purgada");
+    }
+
+    Ok(pendientes.len() as u64)
+}
+
+/// Programa la purga de cuentas borradas
+pub fn spawn(repo: MongoRepo, clock: Arc<dyn Clock>, config: ConfigPurga) {
+    tracing::info!("Purga de cuentas borradas programada cada {} h", config.intervalo.as_secs() / 3600);
+
+    super::spawn_periodic("purga_cuentas", config.intervalo, move || {
+        let repo = repo.clone();
+        let clock = clock.clone();
+        async move {
+            run(&repo, clock.as_ref()).await.map(|_| ())
+        }
+    });
+}
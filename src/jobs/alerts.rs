@@ -0,0 +1,346 @@
+//! # Alertas de operación
+//!
+//! Monitor que evalúa periódicamente reglas de alerta y avisa por email:
+//!
+//! - Por restaurante, las reglas de su configuración (`alertas`), enviadas a
+//!   su `email_alertas` (ver `PUT /restaurants/settings`). Por ejemplo, más
+//!   de 5 cancelaciones en una hora, o que la mediana de lo que tardan en
+//!   confirmarse las reservas pendientes del último día pase de 30 minutos
+//!   (ver [`crate::sla`]).
+//! - De operaciones, configuradas por entorno y enviadas a `ALERTAS_EMAIL_OPS`:
+//!   latencia de MongoDB por encima de un umbral y webhook de pagos fallando
+//!   de forma continuada.
+//!
+//! Una misma alerta no se repite hasta pasado el periodo de silencio; todas
+//! quedan guardadas en la colección `alertas`. Sin destinatario configurado
+//! la alerta solo se registra en el log.
+
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use mongodb::bson::{doc, oid::ObjectId};
+use crate::api::{AppError, AppResult};
+use crate::clock::Clock;
+use crate::db::{AlertaEnviada, MongoRepo, ReglaAlerta, Reserva, Restaurant};
+use crate::notifications::{EmailMessage, Notifier};
+use crate::sla;
+
+/// Configuración del monitor de alertas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigMonitor {
+    /// Cada cuánto se evalúan las reglas
+    pub intervalo: Duration,
+    /// Tiempo durante el que no se repite una misma alerta
+    pub silencio_segundos: i64,
+    /// Destinatario de las alertas de operaciones
+    pub email_ops: Option<String>,
+    /// Latencia máxima de MongoDB antes de avisar
+    pub latencia_bd_ms: Option<u64>,
+    /// Minutos seguidos de fallos del webhook de pagos antes de avisar
+    pub webhook_minutos: Option<i64>,
+}
+
+impl ConfigMonitor {
+    /// Lee la configuración del entorno:
+    /// - `ALERTAS_INTERVALO_MINUTOS` (default: 5)
+    /// - `ALERTAS_SILENCIO_MINUTOS` (default: 60)
+    /// - `ALERTAS_EMAIL_OPS` (default: sin definir, solo log)
+    /// - `ALERTA_LATENCIA_BD_MS` (default: sin definir, regla desactivada)
+    /// - `ALERTA_WEBHOOK_MINUTOS` (default: sin definir, regla desactivada)
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+
+        ConfigMonitor {
+            intervalo: Duration::from_secs(var::<u64>("ALERTAS_INTERVALO_MINUTOS").unwrap_or(5).max(1) * 60),
+            silencio_segundos: var::<i64>("ALERTAS_SILENCIO_MINUTOS").unwrap_or(60) * 60,
+            email_ops: var::<String>("ALERTAS_EMAIL_OPS").filter(|email| !email.is_empty()),
+            latencia_bd_ms: var("ALERTA_LATENCIA_BD_MS"),
+            webhook_minutos: var("ALERTA_WEBHOOK_MINUTOS"),
+        }
+    }
+}
+
+/// Estado reciente del webhook de pagos
+///
+/// ```
+/// use pispas_reservation::jobs::alerts::SaludWebhook;
+///
+/// let mut salud = SaludWebhook::default();
+/// salud.registrar(false, 100);
+/// salud.registrar(false, 200);
+/// assert_eq!(salud.fallando_desde(), Some(100));
+/// salud.registrar(true, 300);
+/// assert_eq!(salud.fallando_desde(), None);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SaludWebhook {
+    primer_fallo: Option<i64>,
+}
+
+impl SaludWebhook {
+    /// Registra el resultado de una petición al webhook
+    pub fn registrar(&mut self, ok: bool, now: i64) {
+        if ok {
+            self.primer_fallo = None;
+        } else if self.primer_fallo.is_none() {
+            self.primer_fallo = Some(now);
+        }
+    }
+
+    /// Timestamp del primer fallo sin ningún éxito posterior
+    pub fn fallando_desde(&self) -> Option<i64> {
+        self.primer_fallo
+    }
+}
+
+fn salud_webhook() -> &'static Mutex<SaludWebhook> {
+    static SALUD: OnceLock<Mutex<SaludWebhook>> = OnceLock::new();
+    SALUD.get_or_init(|| Mutex::new(SaludWebhook::default()))
+}
+
+/// Registra el resultado de una petición al webhook de pagos
+pub fn record_webhook(ok: bool, now: i64) {
+    salud_webhook().lock().unwrap_or_else(|e| e.into_inner()).registrar(ok, now);
+}
+
+/// Timestamp desde el que falla el webhook de pagos sin ningún éxito
+pub fn webhook_failing_since() -> Option<i64> {
+    salud_webhook().lock().unwrap_or_else(|e| e.into_inner()).fallando_desde()
+}
+
+/// Alerta detectada en una pasada del monitor
+#[derive(Debug, Clone)]
+struct Alerta {
+    id_restaurante: Option<ObjectId>,
+    clave: String,
+    destinatario: Option<String>,
+    mensaje: String,
+}
+
+/// Mensaje de la regla de cancelaciones, si se supera el máximo
+///
+/// ```
+/// use pispas_reservation::jobs::alerts::cancellation_alert;
+///
+/// assert_eq!(cancellation_alert(5, 60, 5), None);
+/// assert!(cancellation_alert(5, 60, 6).unwrap().contains("6 cancelaciones"));
+/// ```
+pub fn cancellation_alert(max: u32, ventana_minutos: u32, canceladas: u64) -> Option<String> {
+    (canceladas > u64::from(max)).then(|| format!(
+        "{} cancelaciones en los últimos {} minutos (máximo {})",
+        canceladas, ventana_minutos, max
+    ))
+}
+
+/// Mensaje de la regla de confirmación lenta, si la mediana supera el máximo
+///
+/// ```
+/// use pispas_reservation::jobs::alerts::slow_confirmation_alert;
+///
+/// assert_eq!(slow_confirmation_alert(30, 24, None), None);
+/// assert_eq!(slow_confirmation_alert(30, 24, Some(30)), None);
+/// assert!(slow_confirmation_alert(30, 24, Some(45)).unwrap().contains("45 minutos"));
+/// ```
+pub fn slow_confirmation_alert(max_mediana_minutos: u32, ventana_horas: u32, mediana: Option<i64>) -> Option<String> {
+    mediana.filter(|mediana| *mediana > i64::from(max_mediana_minutos)).map(|mediana| format!(
+        "las reservas confirmadas en las últimas {} horas esperaron una mediana de {} minutos (máximo {})",
+        ventana_horas, mediana, max_mediana_minutos
+    ))
+}
+
+/// Mediana de la espera de las reservas confirmadas desde `desde`
+async fn confirmation_median(repo: &MongoRepo, restaurant: &Restaurant, desde: i64) -> AppResult<Option<i64>> {
+    let mut cursor = repo.reservas()
+        .find(doc! { "id_restaurante": restaurant.id, "confirmada_en": { "$gte": desde } })
+        .await
+        .map_err(|e| AppError::database("find_recent_confirmations", e))?;
+    let mut esperas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let reserva: Reserva = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?;
+        esperas.extend(sla::espera_minutos(&reserva));
+    }
+
+    Ok(sla::summarize(esperas).map(|percentiles| percentiles.p50_minutos))
+}
+
+/// Evalúa las reglas de un restaurante
+async fn restaurant_alerts(repo: &MongoRepo, restaurant: &Restaurant, now: i64) -> AppResult<Vec<Alerta>> {
+    let mut alertas = Vec::new();
+
+    for regla in &restaurant.configuracion.alertas {
+        match regla {
+            ReglaAlerta::Cancelaciones { max, ventana_minutos } => {
+                let canceladas = repo.reservas()
+                    .count_documents(doc! {
+                        "id_restaurante": restaurant.id,
+                        "estado": "cancelada",
+                        "updated_at": { "$gte": now - i64::from(*ventana_minutos) * 60 }
+                    })
+                    .await
+                    .map_err(|e| AppError::database("count_cancellations", e))?;
+
+                if let Some(mensaje) = cancellation_alert(*max, *ventana_minutos, canceladas) {
+                    alertas.push(Alerta {
+                        id_restaurante: restaurant.id,
+                        clave: "cancelaciones".to_string(),
+                        destinatario: restaurant.configuracion.email_alertas.clone(),
+                        mensaje: format!("{}: {}", restaurant.nombre, mensaje),
+                    });
+                }
+            }
+            ReglaAlerta::ConfirmacionLenta { max_mediana_minutos, ventana_horas } => {
+                let mediana = confirmation_median(repo, restaurant, now - i64::from(*ventana_horas) * 3600).await?;
+
+                if let Some(mensaje) = slow_confirmation_alert(*max_mediana_minutos, *ventana_horas, mediana) {
+                    alertas.push(Alerta {
+                        id_restaurante: restaurant.id,
+                        clave: "confirmacion_lenta".to_string(),
+                        destinatario: restaurant.configuracion.email_alertas.clone(),
+                        mensaje: format!("{}: {}", restaurant.nombre, mensaje),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(alertas)
+}
+
+/// Evalúa las reglas de operaciones
+async fn ops_alerts(repo: &MongoRepo, config: &ConfigMonitor, now: i64) -> Vec<Alerta> {
+    let mut alertas = Vec::new();
+    let alerta = |clave: &str, mensaje: String| Alerta {
+        id_restaurante: None,
+        clave: clave.to_string(),
+        destinatario: config.email_ops.clone(),
+        mensaje,
+    };
+
+    if let Some(max_ms) = config.latencia_bd_ms {
+        let inicio = Instant::now();
+        let resultado = repo.ping().await;
+        let ms = inicio.elapsed().as_millis() as u64;
+        match resultado {
+            Err(e) => alertas.push(alerta("latencia_bd", format!("MongoDB no responde: {}", e))),
+            Ok(()) if ms > max_ms => alertas.push(alerta(
+                "latencia_bd",
+                format!("Latencia de MongoDB de {} ms (máximo {} ms)", ms, max_ms),
+            )),
+            Ok(()) => {}
+        }
+    }
+
+    if let Some(minutos) = config.webhook_minutos {
+        if let Some(desde) = webhook_failing_since().filter(|desde| now - desde >= minutos * 60) {
+            alertas.push(alerta(
+                "webhook_pagos",
+                format!("El webhook de pagos falla desde hace {} minutos", (now - desde) / 60),
+            ));
+        }
+    }
+
+    alertas
+}
+
+/// Envía y guarda una alerta, salvo que ya se enviara durante el silencio
+///
+/// # Retorna
+/// `true` si la alerta es nueva
+async fn deliver(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    alerta: Alerta,
+    config: &ConfigMonitor,
+    now: i64,
+) -> AppResult<bool> {
+    let reciente = repo.alertas()
+        .find_one(doc! {
+            "id_restaurante": alerta.id_restaurante,
+            "clave": &alerta.clave,
+            "created_at": { "$gt": now - config.silencio_segundos }
+        })
+        .await
+        .map_err(|e| AppError::database("find_recent_alert", e))?;
+    if reciente.is_some() {
+        return Ok(false);
+    }
+
+    tracing::warn!(clave = %alerta.clave, "Alerta: {}", alerta.mensaje);
+    if let Some(destinatario) = &alerta.destinatario {
+        let envio = notifier.send_email(EmailMessage {
+            to: destinatario.clone(),
+            subject: format!("Alerta: {}", alerta.clave),
+            body: format!("{}\n", alerta.mensaje),
+            adjuntos: Vec::new(),
+            request_id: None,
+        }).await;
+        if let Err(e) = envio {
+            tracing::error!(clave = %alerta.clave, "Error enviando alerta: {}", e);
+        }
+    }
+
+    repo.alertas()
+        .insert_one(AlertaEnviada {
+            id: None,
+            id_restaurante: alerta.id_restaurante,
+            clave: alerta.clave,
+            mensaje: alerta.mensaje,
+            created_at: now,
+        })
+        .await
+        .map_err(|e| AppError::database("save_alert", e))?;
+
+    Ok(true)
+}
+
+/// Evalúa todas las reglas y envía las alertas nuevas
+///
+/// # Retorna
+/// Los mensajes de las alertas enviadas en esta pasada
+pub async fn run(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    clock: &dyn Clock,
+    config: &ConfigMonitor,
+) -> AppResult<Vec<String>> {
+    let now = clock.timestamp();
+    let mut alertas = ops_alerts(repo, config, now).await;
+
+    let mut cursor = repo.restaurants()
+        .find(doc! { "configuracion.alertas.0": { "$exists": true } })
+        .await
+        .map_err(|e| AppError::database("load_alert_rules", e))?;
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let restaurant = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando restaurant: {}", e)))?;
+        alertas.extend(restaurant_alerts(repo, &restaurant, now).await?);
+    }
+
+    let mut enviadas = Vec::new();
+    for alerta in alertas {
+        let mensaje = alerta.mensaje.clone();
+        if deliver(repo, notifier, alerta, config, now).await? {
+            enviadas.push(mensaje);
+        }
+    }
+
+    Ok(enviadas)
+}
+
+/// Programa el monitor de alertas
+pub fn spawn(repo: MongoRepo, notifier: Notifier, clock: Arc<dyn Clock>, config: ConfigMonitor) {
+    tracing::info!("Monitor de alertas programado cada {} min", config.intervalo.as_secs() / 60);
+
+    super::spawn_periodic("alertas", config.intervalo, move || {
+        let repo = repo.clone();
+        let notifier = notifier.clone();
+        let clock = clock.clone();
+        let config = config.clone();
+        async move {
+            run(&repo, &notifier, clock.as_ref(), &config).await.map(|_| ())
+        }
+    });
+}
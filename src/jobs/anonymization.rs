@@ -0,0 +1,226 @@
+//! # Anonimización de reservas antiguas
+//!
+//! Para cumplir la política de retención (RGPD), las reservas cuya fecha es
+//! anterior a `hoy - retención` pierden los datos personales del cliente:
+//!
+//! - `nombre_cliente` pasa a "Anónimo"
+//! - `email_cliente` y `telefono_cliente` quedan vacíos
+//! - se eliminan la verificación pendiente, el dispositivo del widget y las
+//!   notas, que son texto libre
+//!
+//! Se conservan mesa, fecha, hora, personas, estado y canal, de modo que las
+//! estadísticas siguen siendo válidas. Las reservas con retención legal
+//! ([`crate::db::RetencionLegal`]) se saltan hasta que la administración la
+//! levanta; los eventos de dominio nunca se borran, así que su rastro de
+//! auditoría se conserva igualmente. Con el diario de reservas activo (ver
+//! [`crate::journal`]), las entradas de las reservas anonimizadas pierden
+//! los mismos datos. Además se borran los intentos
+//! bloqueados del widget y los mensajes entre clientes y restaurantes (ver
+//! [`crate::api::messages`]) de la misma antigüedad, que solo contienen
+//! datos personales.
+//!
+//! Cada ejecución guarda un [`InformeAnonimizacion`] con los documentos
+//! afectados.
+
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{Duration as ChronoDuration, NaiveDate};
+use mongodb::bson::{doc, Document};
+use crate::api::{AppError, AppResult};
+use crate::availability::FORMATO_FECHA;
+use crate::clock::Clock;
+use crate::db::{InformeAnonimizacion, MongoRepo, RecuentoRestaurante};
+use crate::journal;
+
+/// Nombre que sustituye al del cliente
+pub const NOMBRE_ANONIMO: &str = "Anónimo";
+
+/// Política de retención de datos personales
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoliticaRetencion {
+    /// Días que se conservan los datos personales tras la fecha de la reserva
+    pub dias: u32,
+    /// Cada cuánto se ejecuta el trabajo
+    pub intervalo: Duration,
+}
+
+impl PoliticaRetencion {
+    /// Lee la política de `RETENCION_RESERVAS_DIAS` y
+    /// `ANONIMIZACION_INTERVALO_HORAS` (default: 24)
+    ///
+    /// Devuelve `None` si no hay retención configurada: el trabajo es
+    /// destructivo y solo se activa explícitamente.
+    pub fn from_env() -> Option<Self> {
+        let dias = env::var("RETENCION_RESERVAS_DIAS").ok()?.parse().ok()?;
+        let horas: u64 = env::var("ANONIMIZACION_INTERVALO_HORAS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(24);
+
+        Some(PoliticaRetencion {
+            dias,
+            intervalo: Duration::from_secs(horas.max(1) * 3600),
+        })
+    }
+}
+
+/// Filtro de las reservas con datos personales anteriores a `fecha_limite`
+///
+/// Con `retenidas`, las que tienen retención legal; si no, el resto.
+fn pending_filter(fecha_limite: &str, retenidas: bool) -> Document {
+    doc! {
+        "fecha": { "$lt": fecha_limite },
+        "anonimizada_en": { "$exists": false },
+        "retencion_legal": { "$exists": retenidas },
+    }
+}
+
+/// Recuento por restaurante de las reservas que se van a anonimizar
+async fn count_by_restaurant(repo: &MongoRepo, filtro: Document) -> AppResult<Vec<RecuentoRestaurante>> {
+    let pipeline = vec![
+        doc! { "$match": filtro },
+        doc! { "$group": { "_id": "$id_restaurante", "reservas": { "$sum": 1 } } },
+        doc! { "$sort": { "_id": 1 } },
+    ];
+
+    let mut cursor = repo.reservas()
+        .aggregate(pipeline)
+        .await
+        .map_err(|e| AppError::database("count_anonymizable", e))?;
+
+    let mut recuentos = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let grupo = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error leyendo recuento: {}", e)))?;
+
+        if let Ok(id_restaurante) = grupo.get_object_id("_id") {
+            let reservas = grupo.get_i32("reservas").map(i64::from)
+                .or_else(|_| grupo.get_i64("reservas"))
+                .unwrap_or(0);
+            recuentos.push(RecuentoRestaurante { id_restaurante, reservas: reservas as u64 });
+        }
+    }
+
+    Ok(recuentos)
+}
+
+/// Actualización que quita los datos personales de una reserva
+///
+/// `prefijo` es la ruta de la reserva dentro del documento: vacío para las
+/// reservas y `"reserva."` para las entradas del diario ([`crate::journal`]).
+///
+/// ```
+/// use pispas_reservation::jobs::anonymization::anonymize_update;
+///
+/// let update = anonymize_update("reserva.", 1_900_000_000);
+/// assert_eq!(update.get_document("$set").unwrap().get_str("reserva.nombre_cliente"), Ok("Anónimo"));
+/// assert!(update.get_document("$unset").unwrap().contains_key("reserva.notas_internas"));
+/// ```
+pub fn anonymize_update(prefijo: &str, current_time: i64) -> Document {
+    let campo = |nombre: &str| format!("{}{}", prefijo, nombre);
+    let mut set = Document::new();
+    set.insert(campo("nombre_cliente"), NOMBRE_ANONIMO);
+    set.insert(campo("email_cliente"), "");
+    set.insert(campo("telefono_cliente"), "");
+    set.insert(campo("anonimizada_en"), current_time);
+    let mut unset = Document::new();
+    for nombre in ["verificacion", "dispositivo", "notas_cliente", "notas_internas"] {
+        unset.insert(campo(nombre), "");
+    }
+
+    doc! { "$set": set, "$unset": unset }
+}
+
+/// Anonimiza las reservas con fecha anterior a `fecha_limite`
+///
+/// No guarda el informe; ver [`run`].
+pub async fn anonymize_before(
+    repo: &MongoRepo,
+    fecha_limite: NaiveDate,
+    retencion_dias: u32,
+    current_time: i64,
+) -> AppResult<InformeAnonimizacion> {
+    let fecha_limite = fecha_limite.format(FORMATO_FECHA).to_string();
+    let por_restaurante = count_by_restaurant(repo, pending_filter(&fecha_limite, false)).await?;
+    let retenidas = repo.reservas()
+        .count_documents(pending_filter(&fecha_limite, true))
+        .await
+        .map_err(|e| AppError::database("count_held_reservations", e))?;
+
+    let resultado = repo.reservas()
+        .update_many(pending_filter(&fecha_limite, false), anonymize_update("", current_time))
+        .await
+        .map_err(|e| AppError::database("anonymize_reservations", e))?;
+    journal::anonymize(repo, current_time).await?;
+
+    let limite_timestamp = NaiveDate::parse_from_str(&fecha_limite, FORMATO_FECHA)
+        .map(|fecha| fecha.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp())
+        .unwrap_or(0);
+
+    let violaciones = repo.widget_violaciones()
+        .delete_many(doc! { "created_at": { "$lt": limite_timestamp } })
+        .await
+        .map_err(|e| AppError::database("delete_old_widget_violations", e))?;
+
+    let mensajes = repo.mensajes_reserva()
+        .delete_many(doc! { "created_at": { "$lt": limite_timestamp } })
+        .await
+        .map_err(|e| AppError::database("delete_old_messages", e))?;
+
+    Ok(InformeAnonimizacion {
+        id: None,
+        fecha_limite,
+        retencion_dias,
+        reservas_anonimizadas: resultado.modified_count,
+        por_restaurante,
+        reservas_retenidas: retenidas,
+        violaciones_eliminadas: violaciones.deleted_count,
+        mensajes_eliminados: mensajes.deleted_count,
+        created_at: current_time,
+    })
+}
+
+/// Ejecuta el trabajo con `retencion_dias` respecto a la fecha actual y
+/// guarda el informe
+pub async fn run(
+    repo: &MongoRepo,
+    clock: &dyn Clock,
+    retencion_dias: u32,
+) -> AppResult<InformeAnonimizacion> {
+    let fecha_limite = clock.now().date_naive() - ChronoDuration::days(i64::from(retencion_dias));
+    let mut informe = anonymize_before(repo, fecha_limite, retencion_dias, clock.timestamp()).await?;
+
+    let insertado = repo.informes_anonimizacion()
+        .insert_one(&informe)
+        .await
+        .map_err(|e| AppError::database("save_anonymization_report", e))?;
+    informe.id = insertado.inserted_id.as_object_id();
+
+    tracing::info!(
+        fecha_limite = %informe.fecha_limite,
+        reservas = informe.reservas_anonimizadas,
+        retenidas = informe.reservas_retenidas,
+        violaciones = informe.violaciones_eliminadas,
+        "Anonimización de reservas completada"
+    );
+
+    Ok(informe)
+}
+
+/// Programa el trabajo según la política de retención, si está configurada
+pub fn spawn(repo: MongoRepo, clock: Arc<dyn Clock>, politica: PoliticaRetencion) {
+    tracing::info!(
+        retencion_dias = politica.dias,
+        "Anonimización de reservas programada cada {} h",
+        politica.intervalo.as_secs() / 3600
+    );
+
+    super::spawn_periodic("anonimizacion", politica.intervalo, move || {
+        let repo = repo.clone();
+        let clock = clock.clone();
+        async move {
+            run(&repo, clock.as_ref(), politica.dias).await.map(|_| ())
+        }
+    });
+}
@@ -0,0 +1,210 @@
+//! # Envío continuo de eventos
+//!
+//! Copia periódicamente los eventos de dominio nuevos (ver [`crate::events`])
+//! a un destino externo en lotes JSON Lines, un objeto por lote, con el
+//! mismo formato que `GET /admin/events/export`.
+//!
+//! El progreso se guarda en la colección `checkpoints` (último evento
+//! enviado), así que un reinicio continúa donde se quedó sin duplicar
+//! lotes. Los eventos del último minuto esperan a la siguiente pasada para
+//! no saltarse los que otra instancia aún esté escribiendo.
+//!
+//! ## Destinos
+//!
+//! El envío usa el trait [`DestinoEventos`]. El destino incluido escribe los
+//! lotes en un directorio, pensado para un bucket S3 (o compatible: MinIO,
+//! R2...) montado en el sistema de ficheros con `s3fs` o `rclone mount`.
+//!
+//! ## Configuración
+//!
+//! - `EVENTOS_ENVIO_DIR`: directorio de destino (sin definir, no hay envío)
+//! - `EVENTOS_ENVIO_INTERVALO_MINUTOS`: cada cuánto se envía (default: 5)
+
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::{FindOptions, UpdateOptions};
+use crate::api::{AppError, AppResult};
+use crate::clock::Clock;
+use crate::db::MongoRepo;
+use crate::events;
+
+/// Nombre del checkpoint del envío en la colección `checkpoints`
+const CHECKPOINT: &str = "envio_eventos";
+
+/// Máximo de eventos por lote
+const EVENTOS_POR_LOTE: i64 = 10_000;
+
+/// Segundos que espera un evento antes de enviarse
+const MARGEN_SEGUNDOS: i64 = 60;
+
+/// Destino de los lotes de eventos
+#[async_trait]
+pub trait DestinoEventos: Send + Sync {
+    /// Guarda un lote con el nombre dado; si ya existe, lo reemplaza
+    async fn put(&self, nombre: &str, contenido: Vec<u8>) -> AppResult<()>;
+}
+
+/// Destino que escribe cada lote como un fichero de un directorio
+#[derive(Debug, Clone)]
+pub struct DestinoDirectorio {
+    dir: PathBuf,
+}
+
+impl DestinoDirectorio {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        DestinoDirectorio { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl DestinoEventos for DestinoDirectorio {
+    async fn put(&self, nombre: &str, contenido: Vec<u8>) -> AppResult<()> {
+        let io_error = |e: std::io::Error| AppError::Internal(format!("Error escribiendo lote {}: {}", nombre, e));
+        let destino = self.dir.join(nombre);
+        let temporal = self.dir.join(format!(".{}.tmp", nombre));
+
+        // Escribir aparte y renombrar para no dejar nunca un lote a medias
+        tokio::fs::create_dir_all(&self.dir).await.map_err(io_error)?;
+        tokio::fs::write(&temporal, contenido).await.map_err(io_error)?;
+        tokio::fs::rename(&temporal, &destino).await.map_err(io_error)
+    }
+}
+
+/// Configuración del envío de eventos
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigEnvio {
+    pub dir: PathBuf,
+    pub intervalo: Duration,
+}
+
+impl ConfigEnvio {
+    /// Lee la configuración del entorno; `None` si el envío no está activado
+    pub fn from_env() -> Option<Self> {
+        let dir = env::var("EVENTOS_ENVIO_DIR").ok().filter(|dir| !dir.is_empty())?;
+        let minutos = env::var("EVENTOS_ENVIO_INTERVALO_MINUTOS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(5)
+            .max(1);
+
+        Some(ConfigEnvio {
+            dir: PathBuf::from(dir),
+            intervalo: Duration::from_secs(minutos * 60),
+        })
+    }
+}
+
+/// Nombre del objeto de un lote
+///
+/// Ordena cronológicamente por el último evento del lote.
+///
+/// ```
+/// use mongodb::bson::oid::ObjectId;
+/// use pispas_reservation::jobs::event_shipping::batch_name;
+///
+/// let ultimo = ObjectId::parse_str("665b1a000000000000000001").unwrap();
+/// assert_eq!(batch_name(ultimo), "eventos-665b1a000000000000000001.jsonl");
+/// ```
+pub fn batch_name(ultimo: ObjectId) -> String {
+    format!("eventos-{}.jsonl", ultimo.to_hex())
+}
+
+/// Envía un lote de eventos posteriores al checkpoint
+///
+/// # Retorna
+/// El número de eventos enviados (0 si no había nuevos)
+async fn ship_batch(repo: &MongoRepo, destino: &dyn DestinoEventos, now: i64) -> AppResult<u64> {
+    let cursor_error = |e: mongodb::error::Error| AppError::Internal(format!("Error iterando cursor: {}", e));
+
+    let checkpoint = repo.checkpoints()
+        .find_one(doc! { "_id": CHECKPOINT })
+        .await
+        .map_err(|e| AppError::database("load_event_checkpoint", e))?;
+
+    let limite = ObjectId::from_parts((now - MARGEN_SEGUNDOS).max(0) as u32, [0; 5], [0; 3]);
+    let mut rango = doc! { "$lt": limite };
+    if let Some(checkpoint) = &checkpoint {
+        rango.insert("$gt", checkpoint.ultimo_id);
+    }
+
+    let options = FindOptions::builder()
+        .sort(doc! { "_id": 1 })
+        .limit(EVENTOS_POR_LOTE)
+        .build();
+    let mut cursor = repo.eventos()
+        .find(doc! { "_id": rango })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("load_events_to_ship", e))?;
+
+    let mut contenido = String::new();
+    let mut ultimo = None;
+    let mut enviados = 0;
+    while cursor.advance().await.map_err(cursor_error)? {
+        let evento = cursor.deserialize_current().map_err(cursor_error)?;
+        contenido.push_str(&events::json_line(&evento));
+        ultimo = evento.id;
+        enviados += 1;
+    }
+
+    let Some(ultimo) = ultimo else {
+        return Ok(0);
+    };
+
+    destino.put(&batch_name(ultimo), contenido.into_bytes()).await?;
+    repo.checkpoints()
+        .update_one(
+            doc! { "_id": CHECKPOINT },
+            doc! { "$set": { "ultimo_id": ultimo, "updated_at": now } },
+        )
+        .with_options(UpdateOptions::builder().upsert(true).build())
+        .await
+        .map_err(|e| AppError::database("save_event_checkpoint", e))?;
+
+    Ok(enviados)
+}
+
+/// Envía todos los eventos pendientes, en lotes
+///
+/// # Retorna
+/// El número de eventos enviados
+pub async fn run(repo: &MongoRepo, destino: &dyn DestinoEventos, clock: &dyn Clock) -> AppResult<u64> {
+    let now = clock.timestamp();
+    let mut total = 0;
+
+    loop {
+        let enviados = ship_batch(repo, destino, now).await?;
+        total += enviados;
+        if enviados < EVENTOS_POR_LOTE as u64 {
+            break;
+        }
+    }
+
+    if total > 0 {
+        tracing::info!(eventos = total, "Eventos enviados");
+    }
+    Ok(total)
+}
+
+/// Programa el envío de eventos
+pub fn spawn(repo: MongoRepo, clock: Arc<dyn Clock>, config: ConfigEnvio) {
+    tracing::info!(
+        "Envío de eventos a {} programado cada {} min",
+        config.dir.display(),
+        config.intervalo.as_secs() / 60
+    );
+
+    let destino = Arc::new(DestinoDirectorio::new(config.dir));
+    super::spawn_periodic("envio_eventos", config.intervalo, move || {
+        let repo = repo.clone();
+        let clock = clock.clone();
+        let destino = destino.clone();
+        async move {
+            run(&repo, destino.as_ref(), clock.as_ref()).await.map(|_| ())
+        }
+    });
+}
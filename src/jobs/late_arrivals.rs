@@ -0,0 +1,242 @@
+//! # Clientes que no llegan
+//!
+//! Cada restaurante puede fijar en su configuración una `tolerancia_retraso`
+//! (ver `PUT /restaurants/settings`): los minutos que espera a un cliente
+//! tras la hora de su reserva. Pasado ese plazo, las reservas confirmadas
+//! sin llegada registrada (ver `POST /reservations/{id}/checkin`) se:
+//!
+//! - Marcan como no presentadas (`accion: "no_show"`, por defecto), con el
+//!   no-show en la ficha del cliente, como `POST /reservations/{id}/no-show`
+//! - O se cancelan para que su mesa vuelva a estar disponible
+//!   (`accion: "liberar"`), sin contar el no-show
+//!
+//! Cada cambio deja su evento (`reserva_no_show` o `reserva_cancelada`, con
+//! `automatica: true`), que llega también a los webhooks, y se avisa al
+//! personal por email a `email_alertas` o, si no hay, al email del
+//! restaurante. El cambio solo se aplica si la reserva sigue confirmada y a
+//! la misma hora, así que una llegada registrada a la vez gana.
+//!
+//! ## Configuración
+//!
+//! - `RETRASOS_INTERVALO_MINUTOS`: cada cuánto se buscan reservas vencidas
+//!   (default: 5)
+
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Duration as ChronoDuration;
+use mongodb::bson::doc;
+use mongodb::options::ReturnDocument;
+use crate::api::{AppError, AppResult};
+use crate::api::customer::{count_no_show, discount_visit, learn_preference};
+use crate::availability::{self, FORMATO_FECHA};
+use crate::clock::Clock;
+use crate::db::{AccionRetraso, EstadoReserva, MongoRepo, Reserva, Restaurant, ToleranciaRetraso};
+use crate::events::{self, TipoEvento};
+use crate::journal;
+use crate::notifications::{EmailMessage, Notifier};
+
+/// Configuración de la búsqueda de reservas vencidas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigRetrasos {
+    /// Cada cuánto se buscan reservas vencidas
+    pub intervalo: Duration,
+}
+
+impl ConfigRetrasos {
+    /// Lee `RETRASOS_INTERVALO_MINUTOS`
+    pub fn from_env() -> Self {
+        let minutos = env::var("RETRASOS_INTERVALO_MINUTOS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(5);
+
+        ConfigRetrasos { intervalo: Duration::from_secs(minutos.max(1) * 60) }
+    }
+}
+
+/// Email al personal sobre una reserva cuyo cliente no ha llegado
+fn staff_email(destinatario: &str, reserva: &Reserva, tolerancia: &ToleranciaRetraso) -> EmailMessage {
+    let resultado = match tolerancia.accion {
+        AccionRetraso::NoShow => "se ha marcado como no presentada",
+        AccionRetraso::Liberar => "se ha cancelado y su mesa vuelve a estar disponible",
+    };
+    EmailMessage {
+        to: destinatario.to_string(),
+        subject: format!("{} no ha llegado a su reserva de las {}", reserva.nombre_cliente, reserva.hora),
+        body: format!(
+            "La reserva de {} ({} personas) del {} a las {} {} tras {} minutos sin registrar su llegada.\n\nSi el cliente llega, puedes sentarlo sin reserva desde el panel.\n",
+            reserva.nombre_cliente,
+            reserva.numero_personas,
+            reserva.fecha,
+            reserva.hora,
+            resultado,
+            tolerancia.minutos,
+        ),
+        adjuntos: Vec::new(),
+        request_id: None,
+    }
+}
+
+/// Aplica la acción de la tolerancia a una reserva vencida
+///
+/// # Retorna
+/// `true` si se aplicó; `false` si la reserva cambió mientras tanto
+async fn release(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    restaurant: &Restaurant,
+    tolerancia: &ToleranciaRetraso,
+    reserva: &Reserva,
+    now: i64,
+) -> AppResult<bool> {
+    let destino = match tolerancia.accion {
+        AccionRetraso::NoShow => EstadoReserva::NoShow,
+        AccionRetraso::Liberar => EstadoReserva::Cancelada,
+    };
+    let actualizada = repo.reservas()
+        .find_one_and_update(
+            doc! {
+                "_id": reserva.id,
+                "estado": EstadoReserva::Confirmada,
+                "fecha": &reserva.fecha,
+                "hora": &reserva.hora,
+                "llegada_en": { "$exists": false },
+            },
+            doc! { "$set": { "estado": destino, "updated_at": now } },
+        )
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("release_late_reservation", e))?;
+    let Some(actualizada) = actualizada else {
+        return Ok(false);
+    };
+    if let Some(id_reserva) = actualizada.id {
+        let motivo = match tolerancia.accion {
+            AccionRetraso::NoShow => "marcar_no_show",
+            AccionRetraso::Liberar => "liberar_reserva",
+        };
+        journal::append(repo, id_reserva, motivo, now).await;
+    }
+
+    let (evento, datos) = match tolerancia.accion {
+        AccionRetraso::NoShow => (
+            TipoEvento::ReservaNoShow,
+            doc! { "estado_anterior": EstadoReserva::Confirmada, "automatica": true },
+        ),
+        AccionRetraso::Liberar => (
+            TipoEvento::ReservaCancelada,
+            doc! { "estado_anterior": EstadoReserva::Confirmada, "tardia": false, "automatica": true },
+        ),
+    };
+    events::record(repo, evento, restaurant.id, actualizada.id, datos, now).await;
+
+    if let Some(id_cliente) = actualizada.id_cliente {
+        discount_visit(repo, id_cliente).await?;
+        if tolerancia.accion == AccionRetraso::NoShow {
+            count_no_show(repo, id_cliente).await?;
+        }
+        learn_preference(repo, id_cliente).await;
+    }
+
+    if let Some(destinatario) = restaurant.configuracion.email_alertas.as_ref().or(restaurant.email.as_ref()) {
+        if let Err(e) = notifier.send_email(staff_email(destinatario, &actualizada, tolerancia)).await {
+            tracing::error!(reserva = ?actualizada.id, "Error avisando al personal de un cliente que no llegó: {}", e);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Aplica la tolerancia a las reservas vencidas de un restaurante
+async fn restaurant_late_arrivals(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    restaurant: &Restaurant,
+    tolerancia: &ToleranciaRetraso,
+    now: i64,
+) -> AppResult<u64> {
+    let ahora = restaurant.configuracion.hora_local(now);
+    // Las del día anterior, por las reservas de cerca de medianoche
+    let desde = ahora - ChronoDuration::minutes(i64::from(tolerancia.minutos)) - ChronoDuration::days(1);
+    let fechas: Vec<String> = desde.date()
+        .iter_days()
+        .take_while(|fecha| *fecha <= ahora.date())
+        .map(|fecha| fecha.format(FORMATO_FECHA).to_string())
+        .collect();
+
+    let mut cursor = repo.reservas()
+        .find(doc! {
+            "id_restaurante": restaurant.id,
+            "fecha": { "$in": fechas },
+            "estado": EstadoReserva::Confirmada,
+            "llegada_en": { "$exists": false },
+        })
+        .await
+        .map_err(|e| AppError::database("find_late_reservations", e))?;
+
+    let mut vencidas = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let reserva: Reserva = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?;
+        let vencida = availability::parse_inicio(&reserva.fecha, &reserva.hora)
+            .is_some_and(|inicio| tolerancia.vencida(inicio, ahora));
+        if vencida {
+            vencidas.push(reserva);
+        }
+    }
+
+    let mut aplicadas = 0;
+    for reserva in &vencidas {
+        if release(repo, notifier, restaurant, tolerancia, reserva, now).await? {
+            aplicadas += 1;
+        }
+    }
+
+    Ok(aplicadas)
+}
+
+/// Aplica la tolerancia de retraso en todos los restaurantes que la tienen
+///
+/// Se saltan los restaurantes pendientes de borrado.
+///
+/// # Retorna
+/// El número de reservas marcadas como no presentadas o liberadas
+pub async fn run(repo: &MongoRepo, notifier: &Notifier, clock: &dyn Clock) -> AppResult<u64> {
+    let now = clock.timestamp();
+
+    let mut cursor = repo.restaurants()
+        .find(doc! {
+            "configuracion.tolerancia_retraso": { "$type": "object" },
+            "borrado": { "$exists": false },
+        })
+        .await
+        .map_err(|e| AppError::database("load_late_arrival_settings", e))?;
+    let mut aplicadas = 0;
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let restaurant = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando restaurant: {}", e)))?;
+        if let Some(tolerancia) = restaurant.configuracion.tolerancia_retraso {
+            aplicadas += restaurant_late_arrivals(repo, notifier, &restaurant, &tolerancia, now).await?;
+        }
+    }
+
+    if aplicadas > 0 {
+        tracing::info!(reservas = aplicadas, "Reservas de clientes que no llegaron liberadas");
+    }
+    Ok(aplicadas)
+}
+
+/// Programa la búsqueda de reservas vencidas
+pub fn spawn(repo: MongoRepo, notifier: Notifier, clock: Arc<dyn Clock>, config: ConfigRetrasos) {
+    tracing::info!("Tolerancia de retraso comprobada cada {} min", config.intervalo.as_secs() / 60);
+
+    super::spawn_periodic("retrasos", config.intervalo, move || {
+        let repo = repo.clone();
+        let notifier = notifier.clone();
+        let clock = clock.clone();
+        async move {
+            run(&repo, &notifier, clock.as_ref()).await.map(|_| ())
+        }
+    });
+}
@@ -0,0 +1,79 @@
+//! # Trabajos programados
+//!
+//! Tareas de mantenimiento que se ejecutan periódicamente en segundo plano
+//! dentro del propio servidor. Cada trabajo expone también una función para
+//! ejecutarlo a demanda (por ejemplo desde la API de administración).
+//!
+//! - [`anonymization`] - Eliminación de datos personales de reservas antiguas
+//! - [`alerts`] - Monitor de alertas de operación
+//! - [`event_shipping`] - Envío continuo de los eventos de dominio
+//! - [`rollups`] - Estadísticas diarias precalculadas por restaurante
+//! - [`reminders`] - Recordatorios por email antes de cada reserva
+//! - [`late_arrivals`] - No-show o liberación de las reservas cuyo cliente no llega
+//! - [`webhook_delivery`] - Entrega de los eventos a las suscripciones de webhook
+//! - [`account_purge`] - Borrado definitivo de las cuentas borradas hace más de 30 días
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+pub mod account_purge;
+pub mod alerts;
+pub mod anonymization;
+pub mod event_shipping;
+pub mod late_arrivals;
+pub mod reminders;
+pub mod rollups;
+pub mod webhook_delivery;
+
+/// Resultado de la última ejecución de un trabajo programado
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UltimaEjecucion {
+    pub nombre: &'static str,
+    pub ok: bool,
+    /// Tiempo transcurrido desde que terminó
+    pub hace: Duration,
+}
+
+fn ultimas_ejecuciones() -> &'static Mutex<BTreeMap<&'static str, (bool, Instant)>> {
+    static EJECUCIONES: OnceLock<Mutex<BTreeMap<&'static str, (bool, Instant)>>> = OnceLock::new();
+    EJECUCIONES.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+/// Última ejecución de cada trabajo lanzado en este proceso, por nombre
+pub fn last_runs() -> Vec<UltimaEjecucion> {
+    ultimas_ejecuciones()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|(nombre, (ok, fin))| UltimaEjecucion { nombre, ok: *ok, hace: fin.elapsed() })
+        .collect()
+}
+
+/// Lanza `trabajo` en segundo plano cada `intervalo`
+///
+/// La primera ejecución es inmediata. Los errores se registran en el log y
+/// no detienen las ejecuciones siguientes; el resultado de la última queda
+/// en [`last_runs`].
+pub fn spawn_periodic<F, Fut>(nombre: &'static str, intervalo: Duration, trabajo: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = crate::api::AppResult<()>> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(intervalo);
+        loop {
+            ticker.tick().await;
+            tracing::debug!(trabajo = nombre, "Ejecutando trabajo programado");
+            let resultado = trabajo().await;
+            if let Err(e) = &resultado {
+                tracing::error!(trabajo = nombre, "Error en trabajo programado: {}", e);
+            }
+            ultimas_ejecuciones()
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(nombre, (resultado.is_ok(), Instant::now()));
+        }
+    });
+}
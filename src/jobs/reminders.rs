@@ -0,0 +1,226 @@
+//! # Recordatorios de reserva
+//!
+//! Envía a los clientes un email de recordatorio unas horas antes de su
+//! reserva. Cada restaurante elige cuántas con `recordatorio_horas` en su
+//! configuración (ver `PUT /restaurants/settings`); sin ellas no se envían.
+//!
+//! Solo se recuerdan las reservas pendientes o confirmadas con email. Cada
+//! reserva guarda cuándo se le envió el recordatorio
+//! (`recordatorio_enviado_en`), y se marca antes de enviarlo, así que ni un
+//! reinicio ni otra instancia del servidor lo repiten. Si el envío falla,
+//! la marca se quita para intentarlo en la siguiente pasada. Al cambiar la
+//! fecha o la hora de la reserva, el recordatorio se envía de nuevo.
+//!
+//! El recordatorio lleva los enlaces de gestión de la reserva, para que el
+//! cliente confirme que vendrá o la cancele (ver [`crate::api::public`]).
+//!
+//! Cada recordatorio se envía dentro del ID de la petición que creó la
+//! reserva (ver [`crate::api::request_id`]): el email y los logs del envío
+//! lo llevan.
+//!
+//! ## Configuración
+//!
+//! - `RECORDATORIOS_INTERVALO_MINUTOS`: cada cuánto se buscan reservas que
+//!   recordar (default: 15)
+
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{Duration as ChronoDuration, NaiveDateTime};
+use mongodb::bson::doc;
+use crate::api::{request_id, AppError, AppResult};
+use crate::api::public::{manage_links, manage_token};
+use crate::availability::{self, FORMATO_FECHA};
+use crate::clock::Clock;
+use crate::db::{EstadoReserva, MongoRepo, Reserva, Restaurant};
+use crate::journal;
+use crate::notifications::{EmailMessage, Notifier};
+
+/// Configuración del envío de recordatorios
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigRecordatorios {
+    /// Cada cuánto se buscan reservas que recordar
+    pub intervalo: Duration,
+}
+
+impl ConfigRecordatorios {
+    /// Lee `RECORDATORIOS_INTERVALO_MINUTOS`
+    pub fn from_env() -> Self {
+        let minutos = env::var("RECORDATORIOS_INTERVALO_MINUTOS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(15);
+
+        ConfigRecordatorios { intervalo: Duration::from_secs(minutos.max(1) * 60) }
+    }
+}
+
+/// Indica si toca recordar una reserva que empieza en `inicio`
+///
+/// Toca desde `horas` antes de la reserva hasta que empieza.
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use pispas_reservation::jobs::reminders::reminder_due;
+///
+/// let inicio = NaiveDate::from_ymd_opt(2030, 6, 2).unwrap().and_hms_opt(21, 0, 0).unwrap();
+/// let ahora = NaiveDate::from_ymd_opt(2030, 6, 1).unwrap().and_hms_opt(22, 0, 0).unwrap();
+/// assert!(reminder_due(inicio, ahora, 24));
+/// assert!(!reminder_due(inicio, ahora, 12));
+/// // Ya empezada, no
+/// assert!(!reminder_due(ahora, inicio, 24));
+/// ```
+pub fn reminder_due(inicio: NaiveDateTime, ahora: NaiveDateTime, horas: u32) -> bool {
+    ahora < inicio && inicio - ahora <= ChronoDuration::hours(i64::from(horas))
+}
+
+/// Email de recordatorio de una reserva con sus enlaces de gestión
+fn reminder_email(restaurant: &Restaurant, reserva: &Reserva, enlaces: &str) -> EmailMessage {
+    EmailMessage {
+        to: reserva.email_cliente.clone(),
+        subject: format!("Recordatorio de tu reserva en {}", restaurant.nombre),
+        body: format!(
+            "Hola {},\n\nTe recordamos tu reserva en {} el {} a las {} para {} personas.\n\n{}\nTe esperamos.\n",
+            reserva.nombre_cliente,
+            restaurant.nombre,
+            reserva.fecha,
+            reserva.hora,
+            reserva.numero_personas,
+            enlaces,
+        ),
+        adjuntos: Vec::new(),
+        request_id: None,
+    }
+}
+
+/// Marca una reserva como recordada y envía el recordatorio
+///
+/// # Retorna
+/// `true` si se envió; `false` si otra pasada ya la había marcado o el
+/// envío falló
+async fn remind(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    restaurant: &Restaurant,
+    reserva: &Reserva,
+    now: i64,
+) -> AppResult<bool> {
+    let enlaces = manage_links(&manage_token(repo, reserva, now).await?);
+    let marcada = repo.reservas()
+        .update_one(
+            doc! { "_id": reserva.id, "recordatorio_enviado_en": { "$exists": false } },
+            doc! { "$set": { "recordatorio_enviado_en": now } },
+        )
+        .await
+        .map_err(|e| AppError::database("mark_reminder", e))?;
+    if marcada.modified_count == 0 {
+        return Ok(false);
+    }
+    if let Some(id_reserva) = reserva.id {
+        journal::append(repo, id_reserva, "enviar_recordatorio", now).await;
+    }
+
+    if let Err(e) = notifier.send_email(reminder_email(restaurant, reserva, &enlaces)).await {
+        tracing::error!(reserva = ?reserva.id, "Error enviando recordatorio: {}", e);
+        repo.reservas()
+            .update_one(
+                doc! { "_id": reserva.id, "recordatorio_enviado_en": now },
+                doc! { "$unset": { "recordatorio_enviado_en": "" } },
+            )
+            .await
+            .map_err(|e| AppError::database("unmark_reminder", e))?;
+        if let Some(id_reserva) = reserva.id {
+            journal::append(repo, id_reserva, "fallar_recordatorio", now).await;
+        }
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Recuerda las reservas de un restaurante a las que les toca
+async fn restaurant_reminders(
+    repo: &MongoRepo,
+    notifier: &Notifier,
+    restaurant: &Restaurant,
+    horas: u32,
+    now: i64,
+) -> AppResult<u64> {
+    let ahora = restaurant.configuracion.hora_local(now);
+    let hasta = ahora + ChronoDuration::hours(i64::from(horas));
+    let fechas: Vec<String> = ahora.date()
+        .iter_days()
+        .take_while(|fecha| *fecha <= hasta.date())
+        .map(|fecha| fecha.format(FORMATO_FECHA).to_string())
+        .collect();
+
+    let mut cursor = repo.reservas()
+        .find(doc! {
+            "id_restaurante": restaurant.id,
+            "fecha": { "$in": fechas },
+            "estado": { "$in": [EstadoReserva::Pendiente, EstadoReserva::Confirmada] },
+            "email_cliente": { "$ne": "" },
+            "recordatorio_enviado_en": { "$exists": false },
+            "anonimizada_en": { "$exists": false },
+        })
+        .await
+        .map_err(|e| AppError::database("find_reminders", e))?;
+
+    let mut enviados = 0;
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let reserva: Reserva = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando reserva: {}", e)))?;
+        let toca = availability::parse_inicio(&reserva.fecha, &reserva.hora)
+            .is_some_and(|inicio| reminder_due(inicio, ahora, horas));
+        if toca && request_id::resume(reserva.request_id.clone(), remind(repo, notifier, restaurant, &reserva, now)).await? {
+            enviados += 1;
+        }
+    }
+
+    Ok(enviados)
+}
+
+/// Envía los recordatorios de todos los restaurantes que los tienen activos
+///
+/// Se saltan los restaurantes pendientes de borrado.
+///
+/// # Retorna
+/// El número de recordatorios enviados en esta pasada
+pub async fn run(repo: &MongoRepo, notifier: &Notifier, clock: &dyn Clock) -> AppResult<u64> {
+    let now = clock.timestamp();
+
+    let mut cursor = repo.restaurants()
+        .find(doc! {
+            "configuracion.recordatorio_horas": { "$gte": 1 },
+            "borrado": { "$exists": false },
+        })
+        .await
+        .map_err(|e| AppError::database("load_reminder_settings", e))?;
+    let mut enviados = 0;
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let restaurant = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando restaurant: {}", e)))?;
+        if let Some(horas) = restaurant.configuracion.recordatorio_horas {
+            enviados += restaurant_reminders(repo, notifier, &restaurant, horas, now).await?;
+        }
+    }
+
+    if enviados > 0 {
+        tracing::info!(recordatorios = enviados, "Recordatorios de reserva enviados");
+    }
+    Ok(enviados)
+}
+
+/// Programa el envío de recordatorios
+pub fn spawn(repo: MongoRepo, notifier: Notifier, clock: Arc<dyn Clock>, config: ConfigRecordatorios) {
+    tracing::info!("Recordatorios de reserva programados cada {} min", config.intervalo.as_secs() / 60);
+
+    super::spawn_periodic("recordatorios", config.intervalo, move || {
+        let repo = repo.clone();
+        let notifier = notifier.clone();
+        let clock = clock.clone();
+        async move {
+            run(&repo, &notifier, clock.as_ref()).await.map(|_| ())
+        }
+    });
+}
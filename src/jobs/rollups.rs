@@ -0,0 +1,191 @@
+//! # Estadísticas diarias precalculadas
+//!
+//! Recorrer las reservas para cada consulta de analítica es lento en los
+//! restaurantes con historial largo. Este trabajo agrega las reservas de cada
+//! restaurante y día en la colección `stats_daily` ([`EstadisticaDiaria`]):
+//! reservas por canal, comensales, cancelaciones y no presentados.
+//!
+//! Cada pasada recalcula los últimos días ya cerrados (hasta ayer), porque
+//! una reserva puede cancelarse o marcarse como no presentada después de su
+//! fecha. El día en curso no se agrega. Los días anteriores se pueden
+//! recalcular a demanda con `POST /admin/stats/rollup`.
+//!
+//! ## Configuración
+//!
+//! - `ESTADISTICAS_INTERVALO_HORAS`: cada cuánto se ejecuta (default: 24)
+//! - `ESTADISTICAS_DIAS_RECALCULO`: días cerrados que se recalculan en cada
+//!   pasada (default: 7)
+
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{Duration as ChronoDuration, NaiveDate};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use crate::api::{AppError, AppResult};
+use crate::availability::FORMATO_FECHA;
+use crate::clock::Clock;
+use crate::db::{EstadisticaDiaria, MongoRepo};
+
+/// Configuración del trabajo de estadísticas
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigRollup {
+    /// Cada cuánto se ejecuta el trabajo
+    pub intervalo: Duration,
+    /// Días cerrados que se recalculan en cada pasada
+    pub dias: u32,
+}
+
+impl ConfigRollup {
+    /// Lee `ESTADISTICAS_INTERVALO_HORAS` y `ESTADISTICAS_DIAS_RECALCULO`
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            env::var(name).ok().and_then(|v| v.parse().ok())
+        }
+
+        ConfigRollup {
+            intervalo: Duration::from_secs(var::<u64>("ESTADISTICAS_INTERVALO_HORAS").unwrap_or(24).max(1) * 3600),
+            dias: var::<u32>("ESTADISTICAS_DIAS_RECALCULO").unwrap_or(7).max(1),
+        }
+    }
+}
+
+/// Lee un recuento de `$sum`, que MongoDB devuelve como int32 o int64
+fn count(grupo: &Document, campo: &str) -> u64 {
+    grupo.get_i32(campo).map(i64::from)
+        .or_else(|_| grupo.get_i64(campo))
+        .unwrap_or(0)
+        .max(0) as u64
+}
+
+/// Recalcula las estadísticas de todos los restaurantes entre dos fechas (incluidas)
+///
+/// Sustituye las existentes y borra las de los días del rango que ya no
+/// tienen reservas (traspasadas, borradas...).
+///
+/// # Retorna
+/// El número de estadísticas escritas
+pub async fn rollup_range(
+    repo: &MongoRepo,
+    desde: NaiveDate,
+    hasta: NaiveDate,
+    current_time: i64,
+) -> AppResult<u64> {
+    rollup(repo, doc! {}, desde, hasta, current_time).await
+}
+
+/// Recalcula las estadísticas de un restaurante entre dos fechas (incluidas)
+///
+/// Como [`rollup_range`], con las reservas y estadísticas de ese restaurante.
+///
+/// # Retorna
+/// El número de estadísticas escritas
+pub async fn rollup_restaurant_range(
+    repo: &MongoRepo,
+    id_restaurante: ObjectId,
+    desde: NaiveDate,
+    hasta: NaiveDate,
+    current_time: i64,
+) -> AppResult<u64> {
+    rollup(repo, doc! { "id_restaurante": id_restaurante }, desde, hasta, current_time).await
+}
+
+/// Recalcula las estadísticas de las reservas de `filtro` entre dos fechas
+async fn rollup(
+    repo: &MongoRepo,
+    filtro: Document,
+    desde: NaiveDate,
+    hasta: NaiveDate,
+    current_time: i64,
+) -> AppResult<u64> {
+    let desde = desde.format(FORMATO_FECHA).to_string();
+    let hasta = hasta.format(FORMATO_FECHA).to_string();
+    let mut en_rango = filtro;
+    en_rango.insert("fecha", doc! { "$gte": &desde, "$lte": &hasta });
+
+    let pipeline = vec![
+        doc! { "$match": en_rango.clone() },
+        doc! { "$group": {
+            "_id": {
+                "id_restaurante": "$id_restaurante",
+                "fecha": "$fecha",
+                "canal": { "$ifNull": ["$canal", "interno"] },
+                "estado": "$estado",
+            },
+            "reservas": { "$sum": 1 },
+            "personas": { "$sum": "$numero_personas" },
+        } },
+    ];
+    let mut cursor = repo.reservas()
+        .aggregate(pipeline)
+        .await
+        .map_err(|e| AppError::database("rollup_daily_stats", e))?;
+
+    let mut estadisticas = BTreeMap::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let grupo = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error leyendo agregado: {}", e)))?;
+        let clave = grupo.get_document("_id")
+            .map_err(|e| AppError::Internal(format!("Error leyendo agregado: {}", e)))?;
+        let (Ok(id_restaurante), Ok(fecha)) = (clave.get_object_id("id_restaurante"), clave.get_str("fecha")) else {
+            continue;
+        };
+
+        estadisticas
+            .entry((id_restaurante, fecha.to_string()))
+            .or_insert_with(|| EstadisticaDiaria::new(id_restaurante, fecha, current_time))
+            .add(
+                clave.get_str("canal").unwrap_or("interno"),
+                clave.get_str("estado").unwrap_or_default(),
+                count(&grupo, "reservas"),
+                count(&grupo, "personas"),
+            );
+    }
+
+    let escritas = estadisticas.len() as u64;
+    for estadistica in estadisticas.into_values() {
+        repo.estadisticas_diarias()
+            .replace_one(
+                doc! { "id_restaurante": estadistica.id_restaurante, "fecha": &estadistica.fecha },
+                &estadistica,
+            )
+            .upsert(true)
+            .await
+            .map_err(|e| AppError::database("save_daily_stats", e))?;
+    }
+
+    en_rango.insert("calculado_en", doc! { "$lt": current_time });
+    repo.estadisticas_diarias()
+        .delete_many(en_rango)
+        .await
+        .map_err(|e| AppError::database("delete_stale_daily_stats", e))?;
+
+    Ok(escritas)
+}
+
+/// Recalcula los últimos `dias` días cerrados respecto a la fecha actual
+pub async fn run(repo: &MongoRepo, clock: &dyn Clock, dias: u32) -> AppResult<u64> {
+    let hasta = clock.now().date_naive() - ChronoDuration::days(1);
+    let desde = hasta - ChronoDuration::days(i64::from(dias.max(1)) - 1);
+    let escritas = rollup_range(repo, desde, hasta, clock.timestamp()).await?;
+
+    tracing::info!(%desde, %hasta, estadisticas = escritas, "Estadísticas diarias recalculadas");
+    Ok(escritas)
+}
+
+/// Programa el recálculo de las estadísticas diarias
+pub fn spawn(repo: MongoRepo, clock: Arc<dyn Clock>, config: ConfigRollup) {
+    tracing::info!(
+        dias = config.dias,
+        "Estadísticas diarias programadas cada {} h",
+        config.intervalo.as_secs() / 3600
+    );
+
+    super::spawn_periodic("estadisticas", config.intervalo, move || {
+        let repo = repo.clone();
+        let clock = clock.clone();
+        async move {
+            run(&repo, clock.as_ref(), config.dias).await.map(|_| ())
+        }
+    });
+}
@@ -0,0 +1,241 @@
+//! # Entrega de webhooks salientes
+//!
+//! Envía a cada suscripción de webhook (ver [`crate::api::webhooks`]) los
+//! eventos de dominio nuevos de su restaurante, en orden y uno por
+//! petición, filtrados y con la forma de su plantilla (ver
+//! [`crate::webhooks`]).
+//!
+//! Cada entrega es un `POST` con `Content-Type: application/json` y la
+//! cabecera `X-Webhook-Signature: t=<timestamp>,v1=<firma>`, firmada con el
+//! secreto de la suscripción igual que los webhooks entrantes (ver
+//! [`crate::api::webhook_auth`]), para que el receptor pueda verificarla.
+//! Si el evento viene de una petición, la entrega lleva también su
+//! `X-Request-Id`, y sus logs (también los de los reintentos) van dentro de
+//! ese ID (ver [`crate::api::request_id`]).
+//!
+//! El progreso se guarda en cada suscripción (último evento entregado). Si
+//! una entrega falla (error de red o respuesta que no es 2xx), la
+//! suscripción guarda el error y se detiene en ese evento hasta la
+//! siguiente pasada, así que el receptor los recibe todos y en orden, al
+//! menos una vez. Los eventos de los últimos segundos esperan a la
+//! siguiente pasada para no saltarse los que otra instancia aún esté
+//! escribiendo.
+//!
+//! ## Configuración
+//!
+//! - `WEBHOOKS_INTERVALO_MINUTOS`: cada cuánto se entregan los eventos
+//!   nuevos (default: 1)
+
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use async_trait::async_trait;
+use mongodb::bson::{doc, oid::ObjectId};
+use mongodb::options::FindOptions;
+use crate::api::{AppError, AppResult};
+use crate::api::request_id::{self, CABECERA as CABECERA_REQUEST_ID};
+use crate::api::webhook_auth::{sign, CABECERA_FIRMA};
+use crate::clock::Clock;
+use crate::db::{Evento, MongoRepo, SuscripcionWebhook};
+use crate::webhooks;
+
+/// Máximo de eventos por suscripción en cada pasada
+const EVENTOS_POR_PASADA: i64 = 1_000;
+
+/// Segundos que espera un evento antes de entregarse
+const MARGEN_SEGUNDOS: i64 = 10;
+
+/// Tiempo máximo de espera de cada entrega
+const TIMEOUT_ENTREGA: Duration = Duration::from_secs(10);
+
+/// Receptor de las entregas de webhook
+#[async_trait]
+pub trait EntregaWebhook: Send + Sync {
+    /// Envía un cuerpo JSON firmado a una URL
+    ///
+    /// `request_id` es el ID de la petición que originó el evento, si se
+    /// conoce.
+    async fn post(&self, url: &str, firma: &str, request_id: Option<&str>, cuerpo: Vec<u8>) -> AppResult<()>;
+}
+
+/// Entrega por HTTP
+#[derive(Debug, Clone)]
+pub struct EntregaHttp {
+    client: reqwest::Client,
+}
+
+impl EntregaHttp {
+    pub fn new() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(TIMEOUT_ENTREGA)
+            .build()
+            .unwrap_or_default();
+        EntregaHttp { client }
+    }
+}
+
+impl Default for EntregaHttp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl EntregaWebhook for EntregaHttp {
+    async fn post(&self, url: &str, firma: &str, request_id: Option<&str>, cuerpo: Vec<u8>) -> AppResult<()> {
+        let mut peticion = self.client.post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(CABECERA_FIRMA, firma);
+        if let Some(request_id) = request_id {
+            peticion = peticion.header(CABECERA_REQUEST_ID, request_id);
+        }
+        peticion
+            .body(cuerpo)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map(|_| ())
+            .map_err(|e| AppError::Internal(format!("Error entregando webhook: {}", e)))
+    }
+}
+
+/// Configuración de la entrega de webhooks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigWebhooks {
+    /// Cada cuánto se entregan los eventos nuevos
+    pub intervalo: Duration,
+}
+
+impl ConfigWebhooks {
+    /// Lee `WEBHOOKS_INTERVALO_MINUTOS`
+    pub fn from_env() -> Self {
+        let minutos = env::var("WEBHOOKS_INTERVALO_MINUTOS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(1);
+
+        ConfigWebhooks { intervalo: Duration::from_secs(minutos.max(1) * 60) }
+    }
+}
+
+/// Cabecera de firma de una entrega
+///
+/// ```
+/// use pispas_reservation::api::webhook_auth::sign;
+/// use pispas_reservation::jobs::webhook_delivery::signature_header;
+///
+/// assert_eq!(
+///     signature_header("whsec_1", 1718000000, b"{}"),
+///     format!("t=1718000000,v1={}", sign("whsec_1", 1718000000, b"{}"))
+/// );
+/// ```
+pub fn signature_header(secreto: &str, timestamp: i64, cuerpo: &[u8]) -> String {
+    format!("t={},v1={}", timestamp, sign(secreto, timestamp, cuerpo))
+}
+
+/// Entrega los eventos pendientes de una suscripción
+///
+/// # Retorna
+/// El número de eventos entregados
+async fn deliver_subscription(
+    repo: &MongoRepo,
+    entrega: &dyn EntregaWebhook,
+    suscripcion: &SuscripcionWebhook,
+    now: i64,
+) -> AppResult<u64> {
+    let cursor_error = |e: mongodb::error::Error| AppError::Internal(format!("Error iterando cursor: {}", e));
+
+    let limite = ObjectId::from_parts((now - MARGEN_SEGUNDOS).max(0) as u32, [0; 5], [0; 3]);
+    let options = FindOptions::builder()
+        .sort(doc! { "_id": 1 })
+        .limit(EVENTOS_POR_PASADA)
+        .build();
+    let mut cursor = repo.eventos()
+        .find(doc! {
+            "id_restaurante": suscripcion.id_restaurante,
+            "_id": { "$gt": suscripcion.ultimo_id, "$lt": limite },
+        })
+        .with_options(options)
+        .await
+        .map_err(|e| AppError::database("load_webhook_events", e))?;
+
+    let mut ultimo = suscripcion.ultimo_id;
+    let mut entregados = 0;
+    let mut error = None;
+    while cursor.advance().await.map_err(cursor_error)? {
+        let evento: Evento = cursor.deserialize_current().map_err(cursor_error)?;
+        let Some(id) = evento.id else { continue };
+
+        if webhooks::accepts(&suscripcion.eventos, &evento.tipo) {
+            let cuerpo = serde_json::to_vec(&webhooks::render(&suscripcion.plantilla, &evento))
+                .map_err(|e| AppError::Internal(format!("Error serializando webhook: {}", e)))?;
+            let firma = signature_header(&suscripcion.secreto, now, &cuerpo);
+            let envio = request_id::resume(evento.request_id.clone(), async {
+                let envio = entrega.post(&suscripcion.url, &firma, evento.request_id.as_deref(), cuerpo).await;
+                if let Err(e) = &envio {
+                    tracing::warn!(suscripcion = ?suscripcion.id, "Error entregando webhook: {}", e);
+                }
+                envio
+            }).await;
+            if let Err(e) = envio {
+                error = Some(e.to_string());
+                break;
+            }
+            entregados += 1;
+        }
+        ultimo = id;
+    }
+
+    if ultimo == suscripcion.ultimo_id && error == suscripcion.ultimo_error {
+        return Ok(entregados);
+    }
+    let cambios = match error {
+        Some(error) => doc! { "$set": { "ultimo_id": ultimo, "ultimo_error": error } },
+        None => doc! { "$set": { "ultimo_id": ultimo }, "$unset": { "ultimo_error": "" } },
+    };
+    repo.suscripciones_webhook()
+        .update_one(doc! { "_id": suscripcion.id }, cambios)
+        .await
+        .map_err(|e| AppError::database("save_webhook_progress", e))?;
+
+    Ok(entregados)
+}
+
+/// Entrega los eventos pendientes de todas las suscripciones
+///
+/// # Retorna
+/// El número de eventos entregados en esta pasada
+pub async fn run(repo: &MongoRepo, entrega: &dyn EntregaWebhook, clock: &dyn Clock) -> AppResult<u64> {
+    let now = clock.timestamp();
+
+    let mut cursor = repo.suscripciones_webhook()
+        .find(doc! {})
+        .await
+        .map_err(|e| AppError::database("load_webhook_subscriptions", e))?;
+    let mut entregados = 0;
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        let suscripcion = cursor.deserialize_current()
+            .map_err(|e| AppError::Internal(format!("Error deserializando suscripción: {}", e)))?;
+        entregados += deliver_subscription(repo, entrega, &suscripcion, now).await?;
+    }
+
+    if entregados > 0 {
+        tracing::info!(eventos = entregados, "Webhooks entregados");
+    }
+    Ok(entregados)
+}
+
+/// Programa la entrega de webhooks
+pub fn spawn(repo: MongoRepo, clock: Arc<dyn Clock>, config: ConfigWebhooks) {
+    tracing::info!("Entrega de webhooks programada cada {} min", config.intervalo.as_secs() / 60);
+
+    let entrega = Arc::new(EntregaHttp::new());
+    super::spawn_periodic("webhooks", config.intervalo, move || {
+        let repo = repo.clone();
+        let clock = clock.clone();
+        let entrega = entrega.clone();
+        async move {
+            run(&repo, entrega.as_ref(), clock.as_ref()).await.map(|_| ())
+        }
+    });
+}
@@ -0,0 +1,211 @@
+//! # Diario de reservas
+//!
+//! Modo de persistencia opcional para los clientes con más exigencias de
+//! auditoría (`RESERVAS_PERSISTENCIA=eventos`, ver [`ModoPersistencia`]):
+//! cada cambio de una reserva se guarda además como una [`EntradaDiario`]
+//! inmutable en la colección `diario_reservas`, con la reserva completa tal
+//! como queda, y la colección `reservas` pasa a ser una proyección del
+//! diario: la última entrada de cada reserva. `POST /admin/rebuild?what=reservas`
+//! la reconstruye a partir del diario (ver [`project`]). Por defecto (`crud`)
+//! solo se guarda el estado actual.
+//!
+//! Las entradas de una reserva se numeran con un contador que se incrementa
+//! en la propia reserva al leerla ([`CAMPO_VERSION`]), así que su orden es el
+//! de los cambios aunque dos peticiones la modifiquen a la vez. Cada
+//! operación anota su entrada después de escribir: las del panel, el
+//! cliente y la pasarela de pago junto con su historial (ver
+//! [`crate::api::reservation_history`]), y el resto (recordatorios,
+//! tolerancia de retraso, fusiones de clientes...) por su cuenta. Las
+//! reservas anteriores a activar el modo se anotan al arrancar ([`seed`]).
+//!
+//! Como los eventos de dominio, anotar una entrada nunca hace fallar la
+//! operación: si MongoDB rechaza la escritura, el error queda en el log y la
+//! siguiente entrada de la reserva la pone al día.
+//!
+//! Las entradas no se modifican, salvo para quitar los datos personales: al
+//! anonimizar una reserva (ver [`crate::jobs::anonymization`]) o al borrarla
+//! definitivamente, sus entradas anteriores los pierden igual que ella. La
+//! purga de una cuenta borra también su diario.
+
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use mongodb::options::ReturnDocument;
+use mongodb::Collection;
+use crate::api::{request_id, AppError, AppResult};
+use crate::db::{is_duplicate_key, EntradaDiario, ModoPersistencia, MongoRepo};
+use crate::jobs::anonymization::anonymize_update;
+
+/// Campo de la reserva con la secuencia de su última entrada en el diario
+pub const CAMPO_VERSION: &str = "version_diario";
+
+/// Las reservas como documentos, con los campos que no son de [`crate::db::Reserva`]
+fn reservas(repo: &MongoRepo) -> Collection<Document> {
+    repo.reservas().clone_with_type()
+}
+
+/// Si el diario de reservas está activo
+pub fn enabled(repo: &MongoRepo) -> bool {
+    repo.persistencia == ModoPersistencia::Eventos
+}
+
+/// Anota en el diario el estado actual de una reserva
+///
+/// No hace nada en modo `crud`. Si la reserva ya no existe, anota su borrado
+/// y quita los datos personales de sus entradas anteriores. Si MongoDB
+/// rechaza la escritura, el error queda en el log.
+///
+/// # Parámetros
+/// - `motivo`: Operación que cambió la reserva ("confirmar_reserva"...)
+/// - `now`: Timestamp del cambio
+pub async fn append(repo: &MongoRepo, id_reserva: ObjectId, motivo: &str, now: i64) {
+    if !enabled(repo) {
+        return;
+    }
+    if let Err(e) = write(repo, id_reserva, motivo, now).await {
+        tracing::error!(reserva = %id_reserva, motivo, "Error anotando la reserva en el diario: {}", e);
+    }
+}
+
+async fn write(repo: &MongoRepo, id_reserva: ObjectId, motivo: &str, now: i64) -> AppResult<()> {
+    let actual = reservas(repo)
+        .find_one_and_update(doc! { "_id": id_reserva }, doc! { "$inc": { CAMPO_VERSION: 1_i64 } })
+        .return_document(ReturnDocument::After)
+        .await
+        .map_err(|e| AppError::database("journal_reservation", e))?;
+
+    let entrada = match actual {
+        Some(reserva) => EntradaDiario {
+            id: None,
+            id_restaurante: reserva.get_object_id("id_restaurante")
+                .map_err(|e| AppError::Internal(format!("Reserva sin restaurante: {}", e)))?,
+            id_reserva,
+            secuencia: reserva.get_i64(CAMPO_VERSION)
+                .map_err(|e| AppError::Internal(format!("Versión de la reserva inválida: {}", e)))?,
+            motivo: motivo.to_string(),
+            reserva: Some(reserva),
+            request_id: request_id::current(),
+            created_at: now,
+        },
+        None => {
+            // Borrada: solo se anota si el diario aún no lo sabe
+            let ultima = repo.diario_reservas()
+                .find_one(doc! { "id_reserva": id_reserva })
+                .sort(doc! { "secuencia": -1 })
+                .await
+                .map_err(|e| AppError::database("journal_reservation", e))?;
+            let Some(ultima) = ultima.filter(|ultima| ultima.reserva.is_some()) else {
+                return Ok(());
+            };
+            scrub(repo, doc! { "id_reserva": id_reserva }, now).await?;
+            EntradaDiario {
+                id: None,
+                id_restaurante: ultima.id_restaurante,
+                id_reserva,
+                secuencia: ultima.secuencia + 1,
+                motivo: motivo.to_string(),
+                reserva: None,
+                request_id: request_id::current(),
+                created_at: now,
+            }
+        }
+    };
+
+    match repo.diario_reservas().insert_one(&entrada).await {
+        Ok(_) => Ok(()),
+        // Otra petición ha anotado ya el mismo borrado
+        Err(e) if entrada.reserva.is_none() && is_duplicate_key(&e) => Ok(()),
+        Err(e) => Err(AppError::database("journal_reservation", e)),
+    }
+}
+
+/// Quita los datos personales de las entradas del diario que cumplen `filtro`
+async fn scrub(repo: &MongoRepo, mut filtro: Document, now: i64) -> AppResult<u64> {
+    filtro.insert("reserva", doc! { "$type": "object" });
+    let resultado = repo.diario_reservas()
+        .update_many(filtro, anonymize_update("reserva.", now))
+        .await
+        .map_err(|e| AppError::database("scrub_journal", e))?;
+    Ok(resultado.modified_count)
+}
+
+/// Lleva al diario la anonimización de las reservas anonimizadas en `anonimizada_en`
+///
+/// Sus entradas anteriores pierden los datos personales y cada una anota
+/// su nuevo estado. No hace nada en modo `crud`.
+pub async fn anonymize(repo: &MongoRepo, anonimizada_en: i64) -> AppResult<()> {
+    if !enabled(repo) {
+        return Ok(());
+    }
+
+    for id_reserva in ids(repo, doc! { "anonimizada_en": anonimizada_en }).await? {
+        scrub(repo, doc! { "id_reserva": id_reserva }, anonimizada_en).await?;
+        append(repo, id_reserva, "anonimizar_reserva", anonimizada_en).await;
+    }
+    Ok(())
+}
+
+/// IDs de las reservas que cumplen `filtro`
+async fn ids(repo: &MongoRepo, filtro: Document) -> AppResult<Vec<ObjectId>> {
+    let mut cursor = reservas(repo)
+        .find(filtro)
+        .projection(doc! { "_id": 1 })
+        .await
+        .map_err(|e| AppError::database("find_reservation_ids", e))?;
+    let mut ids = Vec::new();
+    while cursor.advance().await.map_err(|e| AppError::Internal(format!("Error iterando cursor: {}", e)))? {
+        ids.extend(cursor.current().get_object_id("_id").ok());
+    }
+    Ok(ids)
+}
+
+/// Anota en el diario las reservas que aún no tienen ninguna entrada
+///
+/// Son las anteriores a activar el modo `eventos`, o las de una entrada que
+/// falló; se llama al arrancar. No hace nada en modo `crud`.
+///
+/// # Retorna
+/// El número de reservas anotadas
+pub async fn seed(repo: &MongoRepo, now: i64) -> AppResult<u64> {
+    if !enabled(repo) {
+        return Ok(0);
+    }
+
+    let pendientes = ids(repo, doc! { CAMPO_VERSION: { "$exists": false } }).await?;
+    for &id_reserva in &pendientes {
+        append(repo, id_reserva, "importar_reserva", now).await;
+    }
+    if !pendientes.is_empty() {
+        tracing::info!(reservas = pendientes.len(), "Reservas anotadas en el diario");
+    }
+    Ok(pendientes.len() as u64)
+}
+
+/// Deja una reserva como indica su última entrada del diario
+///
+/// La vuelve a escribir completa o, si la entrada es su borrado, la borra.
+///
+/// # Retorna
+/// `true` si la reserva no estaba ya así
+pub async fn project(repo: &MongoRepo, entrada: &EntradaDiario) -> AppResult<bool> {
+    let coleccion = reservas(repo);
+    let Some(reserva) = &entrada.reserva else {
+        let borrada = coleccion
+            .delete_one(doc! { "_id": entrada.id_reserva })
+            .await
+            .map_err(|e| AppError::database("project_reservation", e))?;
+        return Ok(borrada.deleted_count > 0);
+    };
+
+    let actual = coleccion
+        .find_one(doc! { "_id": entrada.id_reserva })
+        .await
+        .map_err(|e| AppError::database("project_reservation", e))?;
+    if actual.as_ref() == Some(reserva) {
+        return Ok(false);
+    }
+    coleccion
+        .replace_one(doc! { "_id": entrada.id_reserva }, reserva)
+        .upsert(true)
+        .await
+        .map_err(|e| AppError::database("project_reservation", e))?;
+    Ok(true)
+}
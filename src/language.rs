@@ -0,0 +1,139 @@
+//! # Idioma de comunicación
+//!
+//! Reglas puras (sin base de datos) para decidir en qué idioma se escribe a
+//! un cliente. Cada reserva guarda su idioma y cada restaurante tiene uno por
+//! defecto (`configuracion.idioma`).
+//!
+//! Cuando el widget no indica el idioma, se deduce ([`detect`]) por este
+//! orden:
+//!
+//! 1. El que pide el widget, si está entre los [`IDIOMAS`] soportados
+//! 2. La cabecera `Accept-Language` del navegador ([`from_accept_language`])
+//! 3. El prefijo internacional del teléfono ([`from_phone`])
+//! 4. El idioma por defecto del restaurante
+//!
+//! Los idiomas son códigos ISO 639-1 (`es`, `en`...): de `en-GB` solo se
+//! usa `en`.
+
+/// Idiomas en los que se puede escribir a los clientes
+pub const IDIOMAS: &[&str] = &["es", "en", "fr", "de", "it", "pt", "ca"];
+
+/// Prefijos internacionales cuyo idioma se puede suponer; los países con
+/// varios idiomas oficiales (Suiza, Bélgica...) no aparecen
+const PREFIJOS: &[(&str, &str)] = &[
+    ("351", "pt"),
+    ("353", "en"),
+    ("376", "ca"),
+    ("34", "es"),
+    ("44", "en"),
+    ("33", "fr"),
+    ("49", "de"),
+    ("43", "de"),
+    ("39", "it"),
+    ("55", "pt"),
+    ("52", "es"),
+    ("54", "es"),
+    ("56", "es"),
+    ("57", "es"),
+    ("61", "en"),
+    ("1", "en"),
+];
+
+/// Idioma soportado de una etiqueta como `en-GB` o `ES`
+///
+/// ```
+/// use pispas_reservation::language::normalize;
+///
+/// assert_eq!(normalize("en-GB"), Some("en"));
+/// assert_eq!(normalize(" ES "), Some("es"));
+/// assert_eq!(normalize("ja"), None);
+/// ```
+pub fn normalize(etiqueta: &str) -> Option<&'static str> {
+    let principal = etiqueta
+        .trim()
+        .split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    IDIOMAS.iter().copied().find(|idioma| *idioma == principal)
+}
+
+/// Idioma soportado preferido en una cabecera `Accept-Language`
+///
+/// Se respetan los pesos `q`; a igual peso gana el que aparece antes.
+///
+/// ```
+/// use pispas_reservation::language::from_accept_language;
+///
+/// assert_eq!(from_accept_language("ja-JP, fr;q=0.8, en;q=0.9"), Some("en"));
+/// assert_eq!(from_accept_language("de-AT,de;q=0.9"), Some("de"));
+/// assert_eq!(from_accept_language("*"), None);
+/// ```
+pub fn from_accept_language(cabecera: &str) -> Option<&'static str> {
+    let mut candidatos: Vec<(f32, &'static str)> = cabecera
+        .split(',')
+        .filter_map(|parte| {
+            let mut trozos = parte.split(';');
+            let idioma = normalize(trozos.next()?)?;
+            let peso = trozos
+                .find_map(|trozo| trozo.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (peso > 0.0).then_some((peso, idioma))
+        })
+        .collect();
+    // sort_by es estable: a igual peso se mantiene el orden de la cabecera
+    candidatos.sort_by(|a, b| b.0.total_cmp(&a.0));
+    candidatos.first().map(|(_, idioma)| *idioma)
+}
+
+/// Idioma probable según el prefijo internacional de un teléfono
+///
+/// Solo se usan los números con prefijo (`+44...` o `0044...`); los
+/// nacionales no dicen nada del cliente.
+///
+/// ```
+/// use pispas_reservation::language::from_phone;
+///
+/// assert_eq!(from_phone("+44 7700 900123"), Some("en"));
+/// assert_eq!(from_phone("0033 6 12 34 56 78"), Some("fr"));
+/// assert_eq!(from_phone("+351 912 345 678"), Some("pt"));
+/// assert_eq!(from_phone("600 000 000"), None);
+/// ```
+pub fn from_phone(telefono: &str) -> Option<&'static str> {
+    let digitos: String = telefono
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')'))
+        .collect();
+    let numero = digitos
+        .strip_prefix('+')
+        .or_else(|| digitos.strip_prefix("00"))?;
+    PREFIJOS
+        .iter()
+        .find(|(prefijo, _)| numero.starts_with(prefijo))
+        .map(|(_, idioma)| *idioma)
+}
+
+/// Idioma con el que se escribe al cliente de una reserva
+///
+/// ```
+/// use pispas_reservation::language::detect;
+///
+/// assert_eq!(detect(Some("fr"), Some("en"), "+34 600 000 000", "es"), "fr");
+/// assert_eq!(detect(None, Some("en-US,en;q=0.9"), "+34 600 000 000", "es"), "en");
+/// assert_eq!(detect(None, None, "+49 151 2345678", "es"), "de");
+/// assert_eq!(detect(None, Some("ja"), "600 000 000", "ca"), "ca");
+/// ```
+pub fn detect(
+    solicitado: Option<&str>,
+    accept_language: Option<&str>,
+    telefono: &str,
+    defecto: &str,
+) -> String {
+    solicitado
+        .and_then(normalize)
+        .or_else(|| accept_language.and_then(from_accept_language))
+        .or_else(|| from_phone(telefono))
+        .unwrap_or(defecto)
+        .to_string()
+}
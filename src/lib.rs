@@ -0,0 +1,82 @@
+//! # Pispas Reservation
+//!
+//! Librería del servidor de reservas: expone la API REST ([`api`]), la capa de
+//! acceso a MongoDB ([`db`]), el envío de mensajes a clientes
+//! ([`notifications`]), los pagos por adelantado ([`payments`]), el reloj de
+//! la aplicación ([`clock`]), las reglas de disponibilidad de mesas
+//! ([`availability`]), la previsión de mesas libres ([`eta`]), la
+//! comparación de planos ([`plan`]), los tickets de reserva ([`ticket`]) y
+//! sus calendarios iCalendar ([`calendar`]), el idioma de comunicación con
+//! los clientes ([`language`]), la comparativa entre restaurantes
+//! ([`benchmark`]), el riesgo de no presentarse ([`risk`]), el tiempo de
+//! confirmación de las reservas ([`sla`]), el registro de eventos de dominio
+//! ([`events`]) y su entrega por webhook ([`webhooks`]), el diario de
+//! reservas ([`journal`]), los trabajos programados ([`jobs`]), el frontend
+//! ([`frontend`]) y la configuración del servidor ([`config`]) para que el
+//! binario y los tests puedan montar la aplicación de la misma forma.
+
+use actix_web::web;
+use std::sync::Arc;
+
+pub mod api;
+pub mod availability;
+pub mod benchmark;
+pub mod calendar;
+pub mod clock;
+pub mod config;
+pub mod db;
+pub mod eta;
+pub mod events;
+pub mod frontend;
+pub mod jobs;
+pub mod journal;
+pub mod language;
+pub mod notifications;
+pub mod payments;
+pub mod plan;
+pub mod risk;
+pub mod sla;
+pub mod ticket;
+pub mod webhooks;
+
+/// Configura la aplicación completa sobre un `App` de Actix Web
+///
+/// Registra el estado compartido (repositorio, notificador, reloj y pagos), las rutas de la
+/// API, el frontend y la redirección de la ruta raíz (ver [`frontend`]). Los
+/// middlewares propios de la API van en [`api::init_routes`]; los globales
+/// (logging de accesos...) se añaden por fuera con `wrap`.
+///
+/// # Ejemplo
+///
+/// ```no_run
+/// use actix_web::{App, HttpServer};
+/// use std::sync::Arc;
+/// use pispas_reservation::{app_config, clock::SystemClock, db::MongoRepo, notifications::Notifier};
+///
+/// # async fn run() -> std::io::Result<()> {
+/// let repo = MongoRepo::init().await.unwrap();
+/// let notifier = Notifier::from_env();
+/// let clock = Arc::new(SystemClock);
+///
+/// HttpServer::new(move || {
+///     App::new().configure(app_config(repo.clone(), notifier.clone(), clock.clone()))
+/// })
+///     .bind("0.0.0.0:8080")?
+///     .run()
+///     .await
+/// # }
+/// ```
+pub fn app_config(
+    repo: db::MongoRepo,
+    notifier: notifications::Notifier,
+    clock: Arc<dyn clock::Clock>,
+) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        cfg.app_data(web::Data::new(repo))
+            .app_data(web::Data::new(notifier))
+            .app_data(web::Data::from(clock))
+            .app_data(web::Data::new(payments::Payments::from_env()))
+            .configure(|cfg| frontend::configure(cfg, &config::ConfigFrontend::from_env()))
+            .configure(api::init_routes);
+    }
+}
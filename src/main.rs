@@ -19,11 +19,53 @@
 //! MONGODB_URI=mongodb://localhost:27017
 //! MONGODB_DATABASE=pispas_reservation
 //!
+//! # Persistencia de las reservas: crud o eventos (diario de reservas)
+//! RESERVAS_PERSISTENCIA=crud
+//!
 //! # Servidor
-//! BIND_ADDRESS=0.0.0.0:8080
+//! BIND_ADDRESS=0.0.0.0:8080,[::]:8080
+//! # BIND_UNIX_SOCKET=/run/pispas/pispas.sock
+//! PUBLIC_BASE_URL=http://localhost:8080
+//!
+//! # Administración (si no se define, /admin/* responde 404)
+//! ADMIN_TOKEN=cambia-esto
+//!
+//! # Webhook de la pasarela de pago (si no se define, responde 404)
+//! PAYMENT_WEBHOOK_SECRET=cambia-esto
+//!
+//! # Pagos por adelantado de los grupos grandes
+//! PAYMENT_PROVIDER=manual
+//!
+//! # Inicio de sesión con Google (si no se define, /auth/google/* responde 404)
+//! GOOGLE_CLIENT_ID=...apps.googleusercontent.com
+//! GOOGLE_CLIENT_SECRET=cambia-esto
+//!
+//! # Notificaciones a clientes
+//! NOTIFICATION_PROVIDER=log
+//!
+//! # Retención de datos personales (si no se define, no se anonimiza)
+//! RETENCION_RESERVAS_DIAS=730
+//! ANONIMIZACION_INTERVALO_HORAS=24
+//!
+//! # Estadísticas diarias precalculadas
+//! ESTADISTICAS_INTERVALO_HORAS=24
+//! ESTADISTICAS_DIAS_RECALCULO=7
+//!
+//! # Recordatorios de reserva (las horas las elige cada restaurante)
+//! RECORDATORIOS_INTERVALO_MINUTOS=15
+//!
+//! # Clientes que no llegan (la tolerancia la elige cada restaurante)
+//! RETRASOS_INTERVALO_MINUTOS=5
+//!
+//! # Webhooks salientes (las suscripciones las crea cada restaurante)
+//! WEBHOOKS_INTERVALO_MINUTOS=1
+//!
+//! # Purga de cuentas borradas (pasado el plazo para restaurarlas)
+//! PURGA_CUENTAS_INTERVALO_HORAS=24
 //!
 //! # Logging
 //! RUST_LOG=debug,mongodb=info
+//! REQUEST_LOG_ALL=false
 //! ```
 //!
 //! ## Ejecución
@@ -53,12 +95,10 @@
 //! MongoDB Database
 //! ```
 
-use actix_files::Files;
-use actix_web::{web, App, HttpServer, middleware::Logger};
-use std::env;
+use actix_web::{App, HttpServer, middleware::Logger};
+use std::sync::Arc;
 
-mod api;
-mod db;
+use pispas_reservation::{app_config, clock, config, db, frontend, jobs, journal, notifications};
 
 /// Función principal que inicia el servidor web
 ///
@@ -68,18 +108,63 @@ mod db;
 /// 2. Configura el sistema de logging con tracing
 /// 3. Establece conexión con MongoDB
 /// 4. Crea índices en la base de datos
-/// 5. Configura el servidor HTTP con:
+/// 5. Programa los trabajos en segundo plano (anonimización, alertas, envío de eventos)
+/// 6. Configura el servidor HTTP con:
 ///    - Middleware de logging
 ///    - Rutas de la API
-///    - Servicio de archivos estáticos
+///    - Frontend (SPA)
 ///    - Redirección de la ruta raíz
-/// 6. Inicia el servidor en la dirección especificada
+/// 7. Inicia el servidor en las direcciones especificadas
 ///
 /// # Variables de entorno
 ///
 /// - `MONGODB_URI`: URI de conexión a MongoDB (default: mongodb://localhost:27017)
 /// - `MONGODB_DATABASE`: Nombre de la base de datos (default: pispas_reservation)
-/// - `BIND_ADDRESS`: Dirección y puerto del servidor (default: 0.0.0.0:8080)
+/// - `RESERVAS_PERSISTENCIA`: `crud` o `eventos`, que guarda además cada cambio de las
+///   reservas en un diario inmutable del que se pueden reconstruir (default: crud; ver
+///   `journal`)
+/// - `BIND_ADDRESS`: Direcciones y puertos del servidor, separadas por comas y con
+///   las IPv6 entre corchetes (default: 0.0.0.0:8080; ver `config`)
+/// - `BIND_UNIX_SOCKET`: Socket Unix en el que escuchar además, p. ej. para nginx
+///   (default: sin socket)
+/// - `FRONTEND_MOUNT`, `FRONTEND_DIR`: Ruta bajo la que se sirve el frontend y directorio
+///   del que se lee (default: /static y ./static; ver `frontend`). Compilado con la
+///   feature `embed-frontend`, sin `FRONTEND_DIR` se sirve el incluido en el binario
+/// - `PUBLIC_BASE_URL`: URL pública usada en los enlaces enviados a clientes (default: http://localhost:8080)
+/// - `ADMIN_TOKEN`: Token de la API de administración; sin él `/admin/*` responde 404
+/// - `PAYMENT_WEBHOOK_SECRET`: Secreto compartido con la pasarela de pago; sin él
+///   `/payments/webhook` responde 404
+/// - `PAYMENT_PROVIDER`: Proveedor de los pagos por adelantado: `manual` (default: manual;
+///   ver `payments`)
+/// - `GOOGLE_CLIENT_ID`, `GOOGLE_CLIENT_SECRET`: Cliente OAuth para iniciar sesión con
+///   Google; sin ellos `/auth/google/*` responde 404 (ver `api::oauth`)
+/// - `DEV_ROUTES`: Registrar las rutas de desarrollo `/dev/*` (default: false)
+/// - `NOTIFICATION_PROVIDER`: Proveedor de email/SMS: `log`, `console` o `memory` (default: log)
+/// - `WIDGET_VENTANA_SEGUNDOS`, `WIDGET_MAX_RESERVAS`, `WIDGET_MAX_EMAILS`: Límites
+///   anti-duplicados por dispositivo del widget (default: 3600, 5, 3)
+/// - `RATE_LIMIT_LOGIN`, `RATE_LIMIT_PUBLICO`, `RATE_LIMIT_TOKEN`: Peticiones por minuto
+///   en login/registro por IP, rutas públicas por IP y peticiones por token
+///   (default: 10, 60, 300; 0 desactiva)
+/// - `LOGIN_MAX_FALLOS`, `LOGIN_MAX_FALLOS_IP`: Logins fallidos por nombre de restaurante
+///   y por IP antes de bloquearlos (default: 5, 20; 0 desactiva)
+/// - `LOGIN_VENTANA_MINUTOS`, `LOGIN_BLOQUEO_MINUTOS`: Ventana en la que se cuentan los
+///   fallos y duración del bloqueo (default: 15, 15)
+/// - `RETENCION_RESERVAS_DIAS`: Días tras los que se anonimizan las reservas
+///   (default: sin definir, trabajo desactivado)
+/// - `ANONIMIZACION_INTERVALO_HORAS`: Frecuencia del trabajo de anonimización (default: 24)
+/// - `ALERTAS_INTERVALO_MINUTOS`, `ALERTAS_SILENCIO_MINUTOS`: Frecuencia del monitor de
+///   alertas y tiempo sin repetir una misma alerta (default: 5, 60)
+/// - `ALERTAS_EMAIL_OPS`: Destinatario de las alertas de operaciones (default: solo log)
+/// - `ALERTA_LATENCIA_BD_MS`, `ALERTA_WEBHOOK_MINUTOS`: Latencia máxima de MongoDB y
+///   minutos de fallos seguidos del webhook de pagos antes de avisar (default: desactivadas)
+/// - `EVENTOS_ENVIO_DIR`: Directorio (p. ej. un bucket S3 montado) al que se envían los
+///   eventos de dominio en lotes JSON Lines (default: sin definir, envío desactivado)
+/// - `EVENTOS_ENVIO_INTERVALO_MINUTOS`: Frecuencia del envío de eventos (default: 5)
+/// - `RETRASOS_INTERVALO_MINUTOS`: Frecuencia con la que se aplica la tolerancia de retraso
+///   de cada restaurante a las reservas cuyo cliente no llega (default: 5)
+/// - `PURGA_CUENTAS_INTERVALO_HORAS`: Frecuencia de la purga de cuentas borradas (default: 24)
+/// - `REQUEST_LOG_ALL`: Registrar todas las peticiones y respuestas con los datos
+///   personales ocultos (default: false, solo restaurantes con `registro_peticiones`)
 /// - `RUST_LOG`: Nivel de logging (default: debug para la app, info para MongoDB)
 ///
 /// # Errores
@@ -87,7 +172,8 @@ mod db;
 /// Retorna `std::io::Error` si:
 /// - No se puede conectar a MongoDB
 /// - Error al crear índices en la base de datos
-/// - No se puede bindear al puerto especificado
+/// - Alguna dirección de `BIND_ADDRESS` no es válida
+/// - No se puede bindear a alguna de las direcciones o al socket
 /// - Error general al inicializar el servidor
 ///
 /// # Ejemplos
@@ -99,8 +185,14 @@ mod db;
 /// # Ejecutar en puerto diferente
 /// BIND_ADDRESS=0.0.0.0:3000 cargo run
 ///
+/// # Escuchar en IPv4 e IPv6 y en un socket Unix para nginx
+/// BIND_ADDRESS=0.0.0.0:8080,[::]:8080 BIND_UNIX_SOCKET=/run/pispas/pispas.sock cargo run
+///
 /// # Ejecutar con MongoDB remoto
 /// MONGODB_URI=mongodb://remote:27017 cargo run
+///
+/// # Binario único, con el frontend incluido (ver `frontend`)
+/// cargo build --release --features single-binary
 /// ```
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -132,33 +224,86 @@ async fn main() -> std::io::Result<()> {
         }
         Err(e) => {
             tracing::error!("Error conectando a MongoDB: {}", e);
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
-                format!("Error de MongoDB: {}", e)
-            ));
+            return Err(std::io::Error::other(format!("Error de MongoDB: {}", e)));
         }
     };
 
-    // Obtener dirección de bind desde variables de entorno
-    let bind_address = env::var("BIND_ADDRESS")
-        .unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+    // Obtener direcciones de bind desde variables de entorno
+    let config_servidor = config::ConfigServidor::from_env()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+    let notifier = notifications::Notifier::from_env();
+    let clock: Arc<dyn clock::Clock> = Arc::new(clock::SystemClock);
+
+    // Con el diario de reservas, anotar las reservas anteriores a activarlo
+    if journal::enabled(&mongo_repo) {
+        tracing::info!("Diario de reservas activo (RESERVAS_PERSISTENCIA=eventos)");
+        if let Err(e) = journal::seed(&mongo_repo, clock.timestamp()).await {
+            tracing::warn!("Advertencia anotando reservas en el diario: {}", e);
+        }
+    }
+
+    // Trabajos programados
+    match jobs::anonymization::PoliticaRetencion::from_env() {
+        Some(politica) => jobs::anonymization::spawn(mongo_repo.clone(), clock.clone(), politica),
+        None => tracing::info!("Sin RETENCION_RESERVAS_DIAS: anonimización de reservas desactivada"),
+    }
+    jobs::alerts::spawn(
+        mongo_repo.clone(),
+        notifier.clone(),
+        clock.clone(),
+        jobs::alerts::ConfigMonitor::from_env(),
+    );
+    if let Some(config) = jobs::event_shipping::ConfigEnvio::from_env() {
+        jobs::event_shipping::spawn(mongo_repo.clone(), clock.clone(), config);
+    }
+    jobs::rollups::spawn(mongo_repo.clone(), clock.clone(), jobs::rollups::ConfigRollup::from_env());
+    jobs::reminders::spawn(
+        mongo_repo.clone(),
+        notifier.clone(),
+        clock.clone(),
+        jobs::reminders::ConfigRecordatorios::from_env(),
+    );
+    jobs::late_arrivals::spawn(
+        mongo_repo.clone(),
+        notifier.clone(),
+        clock.clone(),
+        jobs::late_arrivals::ConfigRetrasos::from_env(),
+    );
+    jobs::webhook_delivery::spawn(
+        mongo_repo.clone(),
+        clock.clone(),
+        jobs::webhook_delivery::ConfigWebhooks::from_env(),
+    );
+    jobs::account_purge::spawn(mongo_repo.clone(), clock.clone(), jobs::account_purge::ConfigPurga::from_env());
 
-    tracing::info!("Servidor iniciando en {}", bind_address);
     tracing::info!("prueba");
+    let config_frontend = config::ConfigFrontend::from_env();
+    tracing::info!("Frontend en {} desde {}", config_frontend.montaje, frontend::source(&config_frontend));
     // Crear y configurar el servidor HTTP
-    HttpServer::new(move || {
+    let mut server = HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(mongo_repo.clone()))
             .wrap(Logger::default())
-            .configure(api::init_routes)
-            .service(Files::new("/static", "./static").show_files_listing())
-            .route("/", web::get().to(|| async {
-                actix_web::HttpResponse::PermanentRedirect()
-                    .append_header(("Location", "/static/index.html"))
-                    .finish()
-            }))
-    })
-        .bind(&bind_address)?
-        .run()
-        .await
-}
\ No newline at end of file
+            .configure(app_config(mongo_repo.clone(), notifier.clone(), clock.clone()))
+    });
+    for direccion in &config_servidor.direcciones {
+        tracing::info!("Servidor iniciando en {}", direccion);
+        server = server.bind(direccion.as_str())?;
+    }
+    if let Some(ruta) = &config_servidor.socket_unix {
+        remove_stale_socket(ruta)?;
+        tracing::info!("Servidor iniciando en el socket {}", ruta.display());
+        server = server.bind_uds(ruta)?;
+    }
+    server.run().await
+}
+
+/// Borra el socket Unix que haya dejado un arranque anterior en `ruta`
+fn remove_stale_socket(ruta: &std::path::Path) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if std::fs::symlink_metadata(ruta).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(ruta)?;
+    }
+    Ok(())
+}
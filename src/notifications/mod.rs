@@ -0,0 +1,239 @@
+//! # Notificaciones
+//!
+//! Abstracción sobre los canales de envío de mensajes a clientes (email y SMS).
+//!
+//! Los handlers reciben un [`Notifier`] como `web::Data` y no conocen el
+//! proveedor concreto; así se puede sustituir el envío real por otro
+//! proveedor sin tocar la API.
+//!
+//! ## Proveedores disponibles
+//!
+//! - `log` (por defecto): registra el mensaje con `tracing` y no lo envía
+//! - `console`: imprime el mensaje completo por la salida estándar (demos)
+//! - `memory`: guarda los mensajes en memoria para que los tests y las demos
+//!   puedan consultarlos (ver `GET /dev/notifications`)
+
+use std::env;
+use std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use crate::api::{request_id, AppResult};
+
+/// Mensaje de email a enviar a un cliente
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EmailMessage {
+    /// Dirección de destino
+    pub to: String,
+    /// Asunto del email
+    pub subject: String,
+    /// Cuerpo en texto plano
+    pub body: String,
+    /// Ficheros adjuntos (la reserva en `.ics`...)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub adjuntos: Vec<Adjunto>,
+    /// Petición de la que sale el email; si no se indica, [`Notifier`] pone
+    /// la de la petición en curso
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Fichero de texto adjunto a un email
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Adjunto {
+    /// Nombre del fichero (`reserva.ics`)
+    pub nombre: String,
+    /// Tipo MIME (`text/calendar`)
+    pub tipo: String,
+    pub contenido: String,
+}
+
+/// Mensaje SMS a enviar a un cliente
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SmsMessage {
+    /// Teléfono de destino
+    pub to: String,
+    /// Texto del mensaje
+    pub body: String,
+    /// Petición de la que sale el SMS (ver [`EmailMessage::request_id`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+/// Proveedor capaz de enviar emails
+#[async_trait]
+pub trait EmailProvider: Send + Sync {
+    async fn send_email(&self, message: EmailMessage) -> AppResult<()>;
+}
+
+/// Proveedor capaz de enviar SMS
+#[async_trait]
+pub trait SmsProvider: Send + Sync {
+    async fn send_sms(&self, message: SmsMessage) -> AppResult<()>;
+}
+
+/// Proveedor que solo registra los mensajes en el log
+///
+/// Útil mientras no haya un proveedor real configurado: el flujo funciona
+/// de extremo a extremo y el contenido queda visible en los logs.
+#[derive(Debug, Default)]
+pub struct LogProvider;
+
+#[async_trait]
+impl EmailProvider for LogProvider {
+    async fn send_email(&self, message: EmailMessage) -> AppResult<()> {
+        tracing::info!(
+            to = %message.to,
+            subject = %message.subject,
+            adjuntos = message.adjuntos.len(),
+            request_id = message.request_id.as_deref(),
+            "Email (no enviado, proveedor log)"
+        );
+        tracing::debug!(body = %message.body, "Contenido del email");
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SmsProvider for LogProvider {
+    async fn send_sms(&self, message: SmsMessage) -> AppResult<()> {
+        tracing::info!(to = %message.to, request_id = message.request_id.as_deref(), "SMS (no enviado, proveedor log)");
+        tracing::debug!(body = %message.body, "Contenido del SMS");
+        Ok(())
+    }
+}
+
+/// Proveedor que imprime los mensajes completos por la salida estándar
+#[derive(Debug, Default)]
+pub struct ConsoleProvider;
+
+#[async_trait]
+impl EmailProvider for ConsoleProvider {
+    async fn send_email(&self, message: EmailMessage) -> AppResult<()> {
+        println!("=== EMAIL para {} ===\nAsunto: {}\n\n{}\n", message.to, message.subject, message.body);
+        for adjunto in &message.adjuntos {
+            println!("--- Adjunto {} ({}) ---\n{}\n", adjunto.nombre, adjunto.tipo, adjunto.contenido);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SmsProvider for ConsoleProvider {
+    async fn send_sms(&self, message: SmsMessage) -> AppResult<()> {
+        println!("=== SMS para {} ===\n{}\n", message.to, message.body);
+        Ok(())
+    }
+}
+
+/// Mensaje registrado por [`MemoryProvider`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "canal", rename_all = "snake_case")]
+pub enum SentMessage {
+    Email(EmailMessage),
+    Sms(SmsMessage),
+}
+
+/// Proveedor que guarda los mensajes en memoria en lugar de enviarlos
+///
+/// Los clones comparten la misma bandeja de salida.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryProvider {
+    sent: Arc<Mutex<Vec<SentMessage>>>,
+}
+
+impl MemoryProvider {
+    /// Mensajes enviados hasta ahora, del más antiguo al más reciente
+    pub fn sent(&self) -> Vec<SentMessage> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Vacía la bandeja de salida
+    pub fn clear(&self) {
+        self.sent.lock().unwrap().clear();
+    }
+
+    fn record(&self, message: SentMessage) {
+        self.sent.lock().unwrap().push(message);
+    }
+}
+
+#[async_trait]
+impl EmailProvider for MemoryProvider {
+    async fn send_email(&self, message: EmailMessage) -> AppResult<()> {
+        self.record(SentMessage::Email(message));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SmsProvider for MemoryProvider {
+    async fn send_sms(&self, message: SmsMessage) -> AppResult<()> {
+        self.record(SentMessage::Sms(message));
+        Ok(())
+    }
+}
+
+/// Punto de entrada para enviar notificaciones desde los handlers
+#[derive(Clone)]
+pub struct Notifier {
+    email: Arc<dyn EmailProvider>,
+    sms: Arc<dyn SmsProvider>,
+    /// Bandeja de salida si el proveedor es `memory`
+    memory: Option<MemoryProvider>,
+}
+
+impl Notifier {
+    /// Crea un notificador con proveedores concretos
+    pub fn new(email: Arc<dyn EmailProvider>, sms: Arc<dyn SmsProvider>) -> Self {
+        Notifier { email, sms, memory: None }
+    }
+
+    /// Crea un notificador que guarda los mensajes en memoria
+    pub fn memory() -> Self {
+        let memory = MemoryProvider::default();
+        Notifier {
+            email: Arc::new(memory.clone()),
+            sms: Arc::new(memory.clone()),
+            memory: Some(memory),
+        }
+    }
+
+    /// Crea el notificador según la variable de entorno `NOTIFICATION_PROVIDER`
+    ///
+    /// Valores soportados: `log` (por defecto), `console` y `memory`.
+    pub fn from_env() -> Self {
+        let provider = env::var("NOTIFICATION_PROVIDER")
+            .unwrap_or_else(|_| "log".to_string());
+
+        match provider.as_str() {
+            "console" => {
+                let console = Arc::new(ConsoleProvider);
+                Notifier::new(console.clone(), console)
+            }
+            "memory" => Notifier::memory(),
+            other => {
+                if other != "log" {
+                    tracing::warn!("Proveedor de notificaciones '{}' desconocido, usando 'log'", other);
+                }
+                let log = Arc::new(LogProvider);
+                Notifier::new(log.clone(), log)
+            }
+        }
+    }
+
+    /// Bandeja de salida en memoria, si el proveedor es `memory`
+    pub fn outbox(&self) -> Option<&MemoryProvider> {
+        self.memory.as_ref()
+    }
+
+    /// Envía un email
+    pub async fn send_email(&self, mut message: EmailMessage) -> AppResult<()> {
+        message.request_id = message.request_id.or_else(request_id::current);
+        self.email.send_email(message).await
+    }
+
+    /// Envía un SMS
+    pub async fn send_sms(&self, mut message: SmsMessage) -> AppResult<()> {
+        message.request_id = message.request_id.or_else(request_id::current);
+        self.sms.send_sms(message).await
+    }
+}
@@ -0,0 +1,87 @@
+//! # Pagos por adelantado
+//!
+//! Abstracción sobre el proveedor que cobra y reembolsa los pagos por
+//! adelantado de las reservas ([`crate::db::Pago`]).
+//!
+//! Los handlers reciben un [`Payments`] como `web::Data` y no conocen el
+//! proveedor concreto: integrar una pasarela es implementar
+//! [`PaymentProvider`] y elegirla en [`Payments::from_env`].
+//!
+//! ## Proveedores disponibles
+//!
+//! - `manual` (por defecto): el restaurante cobra y devuelve por su cuenta
+//!   (datáfono, transferencia...) y el personal lo anota en la reserva
+
+use std::env;
+use std::sync::Arc;
+use async_trait::async_trait;
+use crate::api::AppResult;
+use crate::db::Pago;
+
+/// Proveedor capaz de gestionar pagos por adelantado
+#[async_trait]
+pub trait PaymentProvider: Send + Sync {
+    /// Nombre con el que se guarda en cada pago
+    fn name(&self) -> &'static str;
+
+    /// Devuelve al cliente un pago ya cobrado
+    async fn refund(&self, pago: &Pago) -> AppResult<()>;
+}
+
+/// Proveedor para los pagos que el restaurante gestiona por su cuenta
+///
+/// El reembolso solo queda en el log: la devolución la hace el personal.
+#[derive(Debug, Default)]
+pub struct ManualProvider;
+
+#[async_trait]
+impl PaymentProvider for ManualProvider {
+    fn name(&self) -> &'static str {
+        "manual"
+    }
+
+    async fn refund(&self, pago: &Pago) -> AppResult<()> {
+        tracing::info!(
+            importe = pago.importe_centimos,
+            referencia = pago.referencia.as_deref().unwrap_or_default(),
+            "Reembolso (proveedor manual, lo hace el restaurante)"
+        );
+        Ok(())
+    }
+}
+
+/// Punto de entrada para gestionar pagos desde los handlers
+#[derive(Clone)]
+pub struct Payments {
+    provider: Arc<dyn PaymentProvider>,
+}
+
+impl Payments {
+    /// Crea la gestión de pagos con un proveedor concreto
+    pub fn new(provider: Arc<dyn PaymentProvider>) -> Self {
+        Payments { provider }
+    }
+
+    /// Crea la gestión de pagos según la variable de entorno `PAYMENT_PROVIDER`
+    ///
+    /// Valores soportados: `manual` (por defecto).
+    pub fn from_env() -> Self {
+        let provider = env::var("PAYMENT_PROVIDER")
+            .unwrap_or_else(|_| "manual".to_string());
+
+        if provider != "manual" {
+            tracing::warn!("Proveedor de pagos '{}' desconocido, usando 'manual'", provider);
+        }
+        Payments::new(Arc::new(ManualProvider))
+    }
+
+    /// Nombre del proveedor
+    pub fn provider_name(&self) -> &'static str {
+        self.provider.name()
+    }
+
+    /// Reembolsa un pago
+    pub async fn refund(&self, pago: &Pago) -> AppResult<()> {
+        self.provider.refund(pago).await
+    }
+}
@@ -0,0 +1,134 @@
+//! # Comparación de planos
+//!
+//! Reglas puras (sin base de datos) para comparar dos versiones del plano de
+//! mesas de un restaurante, por ejemplo un snapshot guardado y el plano
+//! actual. Las mesas se identifican por su nombre normalizado (ver
+//! [`normalize_name`]) y no por su ID, porque el editor guarda el plano
+//! borrando y volviendo a crear las mesas; una mesa renombrada cuenta como
+//! eliminada y añadida.
+//!
+//! Una mesa se considera movida si cambia su posición o su tamaño.
+
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::serde_helpers::serialize_object_id_as_hex_string;
+use serde::Serialize;
+use std::collections::HashMap;
+use crate::db::{normalize_name, Mesa};
+
+/// Posición y tamaño de una mesa en el plano
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Geometria {
+    pub pos_x: f32,
+    pub pos_y: f32,
+    pub size_x: f32,
+    pub size_y: f32,
+}
+
+impl From<&Mesa> for Geometria {
+    fn from(mesa: &Mesa) -> Self {
+        Geometria {
+            pos_x: mesa.pos_x,
+            pos_y: mesa.pos_y,
+            size_x: mesa.size_x,
+            size_y: mesa.size_y,
+        }
+    }
+}
+
+/// Mesa añadida o eliminada entre dos planos
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MesaPlano {
+    #[serde(serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    pub nombre: String,
+    pub geometria: Geometria,
+}
+
+impl From<&Mesa> for MesaPlano {
+    fn from(mesa: &Mesa) -> Self {
+        MesaPlano {
+            id: mesa.id.unwrap_or_default(),
+            nombre: mesa.nombre.clone(),
+            geometria: Geometria::from(mesa),
+        }
+    }
+}
+
+/// Mesa presente en los dos planos con otra posición o tamaño
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MesaMovida {
+    /// ID en el plano de destino
+    #[serde(serialize_with = "serialize_object_id_as_hex_string")]
+    pub id: ObjectId,
+    pub nombre: String,
+    pub antes: Geometria,
+    pub despues: Geometria,
+}
+
+/// Diferencias entre dos planos
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct DiffPlano {
+    pub anadidas: Vec<MesaPlano>,
+    pub eliminadas: Vec<MesaPlano>,
+    pub movidas: Vec<MesaMovida>,
+}
+
+impl DiffPlano {
+    /// Indica si los dos planos tienen la misma distribución
+    pub fn is_empty(&self) -> bool {
+        self.anadidas.is_empty() && self.eliminadas.is_empty() && self.movidas.is_empty()
+    }
+}
+
+/// Clave con la que se identifica una mesa entre dos planos
+fn clave(mesa: &Mesa) -> String {
+    if mesa.nombre_normalizado.is_empty() {
+        normalize_name(&mesa.nombre)
+    } else {
+        mesa.nombre_normalizado.clone()
+    }
+}
+
+/// Compara el plano `antes` con el plano `despues`
+///
+/// Las listas del resultado conservan el orden de las mesas en el plano del
+/// que salen (`eliminadas` el de `antes`; `anadidas` y `movidas` el de
+/// `despues`).
+///
+/// ```
+/// use pispas_reservation::plan::diff;
+///
+/// let resultado = diff(&[], &[]);
+/// assert!(resultado.is_empty());
+/// ```
+pub fn diff(antes: &[Mesa], despues: &[Mesa]) -> DiffPlano {
+    let previas: HashMap<String, &Mesa> = antes.iter().map(|mesa| (clave(mesa), mesa)).collect();
+    let actuales: HashMap<String, &Mesa> = despues.iter().map(|mesa| (clave(mesa), mesa)).collect();
+
+    let mut resultado = DiffPlano::default();
+
+    for mesa in antes {
+        if !actuales.contains_key(&clave(mesa)) {
+            resultado.eliminadas.push(MesaPlano::from(mesa));
+        }
+    }
+
+    for mesa in despues {
+        match previas.get(&clave(mesa)) {
+            None => resultado.anadidas.push(MesaPlano::from(mesa)),
+            Some(previa) => {
+                let (antes, despues) = (Geometria::from(*previa), Geometria::from(mesa));
+                if antes != despues {
+                    resultado.movidas.push(MesaMovida {
+                        id: mesa.id.unwrap_or_default(),
+                        nombre: mesa.nombre.clone(),
+                        antes,
+                        despues,
+                    });
+                }
+            }
+        }
+    }
+
+    resultado
+}
@@ -0,0 +1,136 @@
+//! # Riesgo de no presentarse
+//!
+//! Reglas puras (sin base de datos) de la puntuación que acompaña a las
+//! reservas próximas en los listados, para que el personal sepa a qué
+//! clientes llamar primero para confirmar.
+//!
+//! La puntuación va de 0 a 100 y suma los puntos de cada factor presente,
+//! según las reglas del restaurante ([`crate::db::ReglasRiesgo`]):
+//!
+//! - Cada reserva anterior del cliente a la que no se presentó
+//! - Un grupo muy grande
+//! - Una reserva hecha con poca antelación
+//! - Un teléfono vacío o que no parece un número (ver [`telefono_valido`])
+//!
+//! Además, los clientes con varios no-shows pueden quedar en revisión (ver
+//! [`requiere_revision`]): sus reservas del widget no se confirman solas.
+
+use serde::Serialize;
+use crate::db::ReglasRiesgo;
+
+/// Puntuación máxima
+pub const MAX_PUNTUACION: u32 = 100;
+
+/// Factor que ha sumado puntos a una puntuación
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MotivoRiesgo {
+    NoShowsPrevios,
+    GrupoGrande,
+    UltimoMomento,
+    TelefonoInalcanzable,
+}
+
+/// Datos de una reserva que se tienen en cuenta
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FactoresRiesgo<'a> {
+    /// Reservas anteriores del cliente a las que no se presentó
+    pub no_shows: u32,
+    pub numero_personas: i32,
+    /// Minutos entre que se hizo la reserva y su hora; `None` si no se sabe
+    pub antelacion_minutos: Option<i64>,
+    pub telefono: &'a str,
+}
+
+/// Puntuación de riesgo de una reserva
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct Riesgo {
+    /// De 0 (sin riesgo) a [`MAX_PUNTUACION`]
+    pub puntuacion: u32,
+    pub motivos: Vec<MotivoRiesgo>,
+}
+
+/// Indica si un teléfono parece un número al que se puede llamar
+///
+/// Basta con que tenga entre 9 y 15 dígitos; se ignoran espacios, guiones,
+/// paréntesis y el prefijo `+`.
+///
+/// ```
+/// use pispas_reservation::risk::telefono_valido;
+///
+/// assert!(telefono_valido("+34 600 123 456"));
+/// assert!(!telefono_valido(""));
+/// assert!(!telefono_valido("12345"));
+/// assert!(!telefono_valido("600-ABC-123"));
+/// ```
+pub fn telefono_valido(telefono: &str) -> bool {
+    let mut digitos = 0;
+    for caracter in telefono.trim().trim_start_matches('+').chars() {
+        match caracter {
+            '0'..='9' => digitos += 1,
+            ' ' | '-' | '(' | ')' | '.' => {}
+            _ => return false,
+        }
+    }
+    (9..=15).contains(&digitos)
+}
+
+/// Indica si las reservas de un cliente con `no_shows` no-shows previos
+/// deben esperar a que el personal las confirme
+///
+/// ```
+/// use pispas_reservation::db::ReglasRiesgo;
+/// use pispas_reservation::risk::requiere_revision;
+///
+/// let reglas = ReglasRiesgo { no_shows_revision: Some(2), ..ReglasRiesgo::default() };
+/// assert!(!requiere_revision(&reglas, 1));
+/// assert!(requiere_revision(&reglas, 2));
+/// assert!(!requiere_revision(&ReglasRiesgo::default(), 10));
+/// ```
+pub fn requiere_revision(reglas: &ReglasRiesgo, no_shows: u32) -> bool {
+    reglas.no_shows_revision.is_some_and(|minimo| no_shows >= minimo)
+}
+
+/// Puntúa una reserva con las reglas del restaurante
+///
+/// ```
+/// use pispas_reservation::db::ReglasRiesgo;
+/// use pispas_reservation::risk::{evaluar, FactoresRiesgo, MotivoRiesgo};
+///
+/// let reglas = ReglasRiesgo::default();
+/// let factores = FactoresRiesgo { no_shows: 0, numero_personas: 2, antelacion_minutos: Some(3 * 24 * 60), telefono: "600123456" };
+/// assert_eq!(evaluar(&reglas, &factores).puntuacion, 0);
+///
+/// // Un no-show previo y reservada media hora antes
+/// let factores = FactoresRiesgo { no_shows: 1, antelacion_minutos: Some(30), ..factores };
+/// let riesgo = evaluar(&reglas, &factores);
+/// assert_eq!(riesgo.puntuacion, 45);
+/// assert_eq!(riesgo.motivos, [MotivoRiesgo::NoShowsPrevios, MotivoRiesgo::UltimoMomento]);
+///
+/// // Nunca pasa de 100
+/// let factores = FactoresRiesgo { no_shows: 5, ..factores };
+/// assert_eq!(evaluar(&reglas, &factores).puntuacion, 100);
+/// ```
+pub fn evaluar(reglas: &ReglasRiesgo, factores: &FactoresRiesgo) -> Riesgo {
+    let mut puntuacion = 0u32;
+    let mut motivos = Vec::new();
+    let mut sumar = |puntos: u32, motivo| {
+        if puntos > 0 {
+            puntuacion = puntuacion.saturating_add(puntos);
+            motivos.push(motivo);
+        }
+    };
+
+    sumar(reglas.puntos_por_no_show.saturating_mul(factores.no_shows), MotivoRiesgo::NoShowsPrevios);
+    if factores.numero_personas >= reglas.personas_grupo_grande {
+        sumar(reglas.puntos_grupo_grande, MotivoRiesgo::GrupoGrande);
+    }
+    if factores.antelacion_minutos.is_some_and(|minutos| minutos < i64::from(reglas.horas_ultimo_momento) * 60) {
+        sumar(reglas.puntos_ultimo_momento, MotivoRiesgo::UltimoMomento);
+    }
+    if !telefono_valido(factores.telefono) {
+        sumar(reglas.puntos_telefono_inalcanzable, MotivoRiesgo::TelefonoInalcanzable);
+    }
+
+    Riesgo { puntuacion: puntuacion.min(MAX_PUNTUACION), motivos }
+}
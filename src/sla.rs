@@ -0,0 +1,70 @@
+//! # Tiempo de confirmación
+//!
+//! Reglas puras (sin base de datos) de `GET /stats/confirmations` y de la
+//! alerta `confirmacion_lenta` (ver [`crate::jobs::alerts`]), que miden
+//! cuánto tarda el restaurante en confirmar las reservas pendientes.
+//!
+//! La espera de una reserva va desde que queda pendiente (al crearla o,
+//! en las del widget con verificación, al verificarla el cliente) hasta que
+//! el personal la confirma: desde el panel, en bloque o desde la app sin
+//! conexión. Las que se confirman solas o al pagar el depósito no cuentan.
+
+use serde::Serialize;
+use crate::db::Reserva;
+
+/// Percentiles de las esperas de las reservas confirmadas, en minutos
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Percentiles {
+    /// Reservas confirmadas en las que se basan
+    pub confirmadas: usize,
+    pub p50_minutos: i64,
+    pub p90_minutos: i64,
+    pub p95_minutos: i64,
+    pub max_minutos: i64,
+}
+
+/// Minutos que esperó una reserva a que la confirmara el personal
+///
+/// `None` si no la confirmó el personal.
+pub fn espera_minutos(reserva: &Reserva) -> Option<i64> {
+    let confirmada_en = reserva.confirmada_en?;
+    let desde = reserva.pendiente_desde.unwrap_or(reserva.created_at);
+    Some((confirmada_en - desde).max(0) / 60)
+}
+
+/// Percentil `p` (0 a 100) de unas esperas ordenadas, por rango más cercano
+fn percentile(ordenadas: &[i64], p: usize) -> i64 {
+    let rango = (p * ordenadas.len()).div_ceil(100).max(1);
+    ordenadas[rango - 1]
+}
+
+/// Percentiles de unas esperas en minutos
+///
+/// # Retorna
+/// `None` si no hay ninguna espera
+///
+/// ```
+/// use pispas_reservation::sla::summarize;
+///
+/// let percentiles = summarize((1..=20).collect()).unwrap();
+/// assert_eq!(percentiles.confirmadas, 20);
+/// assert_eq!((percentiles.p50_minutos, percentiles.p90_minutos), (10, 18));
+/// assert_eq!((percentiles.p95_minutos, percentiles.max_minutos), (19, 20));
+///
+/// assert_eq!(summarize(vec![7]).unwrap().p50_minutos, 7);
+/// assert!(summarize(Vec::new()).is_none());
+/// ```
+pub fn summarize(mut esperas: Vec<i64>) -> Option<Percentiles> {
+    if esperas.is_empty() {
+        return None;
+    }
+    esperas.sort_unstable();
+
+    Some(Percentiles {
+        confirmadas: esperas.len(),
+        p50_minutos: percentile(&esperas, 50),
+        p90_minutos: percentile(&esperas, 90),
+        p95_minutos: percentile(&esperas, 95),
+        max_minutos: esperas[esperas.len() - 1],
+    })
+}
@@ -0,0 +1,279 @@
+//! # Tickets de reserva
+//!
+//! Reglas puras (sin base de datos) para componer el ticket que se imprime
+//! al llegar una reserva, según la plantilla de cada restaurante
+//! ([`PlantillaTicket`]):
+//!
+//! ```text
+//!                 LA TASCA
+//! ------------------------------------------
+//! Hora: 21:00
+//! Cliente: Juan Pérez
+//! Personas: 4
+//! Mesa: Terraza 3
+//! ------------------------------------------
+//!              ¡Buen provecho!
+//! ```
+//!
+//! Se genera en dos formatos: [`render_escpos`], los bytes ESC/POS que se
+//! envían tal cual a una impresora de tickets (o a un puente de impresión),
+//! y [`render_text`], el mismo ticket como texto plano para previsualizarlo
+//! o para impresoras que no hablan ESC/POS.
+//!
+//! En ESC/POS los acentos se quitan (`Pérez` → `Perez`), porque el juego de
+//! caracteres de cada impresora es distinto y el ASCII lo imprimen todas.
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use crate::db::{CampoTicket, PlantillaTicket, Reserva};
+
+/// Inicializa la impresora
+const ESC_INICIAR: &[u8] = &[0x1B, b'@'];
+/// Alinea a la izquierda
+const ESC_IZQUIERDA: &[u8] = &[0x1B, b'a', 0];
+/// Centra
+const ESC_CENTRAR: &[u8] = &[0x1B, b'a', 1];
+/// Letra de tamaño normal
+const GS_NORMAL: &[u8] = &[0x1D, b'!', 0x00];
+/// Letra de doble ancho y doble alto
+const GS_DOBLE: &[u8] = &[0x1D, b'!', 0x11];
+/// Avanza el papel y hace un corte parcial
+const GS_CORTAR: &[u8] = &[0x1D, b'V', 66, 0];
+
+/// Datos de una reserva que pueden aparecer en su ticket
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatosTicket {
+    pub restaurante: String,
+    pub nombre_cliente: String,
+    pub telefono_cliente: String,
+    pub numero_personas: i32,
+    pub fecha: String,
+    pub hora: String,
+    pub mesa: String,
+    pub localizador: Option<String>,
+    /// Opciones de menú elegidas, con sus unidades
+    pub preseleccion: Vec<(String, u32)>,
+}
+
+impl DatosTicket {
+    /// Datos del ticket de una reserva, con el nombre de su restaurante y de su mesa
+    pub fn new(restaurante: &str, reserva: &Reserva, mesa: &str) -> Self {
+        DatosTicket {
+            restaurante: restaurante.to_string(),
+            nombre_cliente: reserva.nombre_cliente.clone(),
+            telefono_cliente: reserva.telefono_cliente.clone(),
+            numero_personas: reserva.numero_personas,
+            fecha: reserva.fecha.clone(),
+            hora: reserva.hora.clone(),
+            mesa: mesa.to_string(),
+            localizador: reserva.localizador.clone(),
+            preseleccion: reserva.preseleccion
+                .iter()
+                .map(|seleccion| (seleccion.nombre.clone(), seleccion.cantidad))
+                .collect(),
+        }
+    }
+}
+
+/// Cómo se imprime una línea del ticket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Estilo {
+    /// Centrada y a doble tamaño
+    Titulo,
+    /// Centrada
+    Centrada,
+    /// Alineada a la izquierda
+    Normal,
+}
+
+/// Parte un texto en líneas de como mucho `ancho` caracteres, por palabras
+fn wrap(texto: &str, ancho: usize) -> Vec<String> {
+    let ancho = ancho.max(1);
+    let mut lineas = Vec::new();
+    let mut actual = String::new();
+
+    for palabra in texto.split_whitespace() {
+        let mut palabra = palabra.to_string();
+        loop {
+            let usados = actual.chars().count();
+            let libres = if usados == 0 { ancho } else { ancho.saturating_sub(usados + 1) };
+            if palabra.chars().count() <= libres {
+                if usados > 0 {
+                    actual.push(' ');
+                }
+                actual.push_str(&palabra);
+                break;
+            }
+            if usados > 0 {
+                lineas.push(std::mem::take(&mut actual));
+            } else {
+                // Una palabra más larga que la línea se corta
+                lineas.push(palabra.chars().take(ancho).collect());
+                palabra = palabra.chars().skip(ancho).collect();
+            }
+        }
+    }
+    if !actual.is_empty() {
+        lineas.push(actual);
+    }
+    lineas
+}
+
+/// Líneas del ticket, con su estilo
+fn lines(plantilla: &PlantillaTicket, datos: &DatosTicket) -> Vec<(Estilo, String)> {
+    let ancho = usize::from(plantilla.ancho);
+    let separador = "-".repeat(ancho);
+    let mut lineas = Vec::new();
+
+    let cabecera = plantilla.cabecera.as_deref().unwrap_or(&datos.restaurante);
+    for linea in wrap(&cabecera.to_uppercase(), ancho / 2) {
+        lineas.push((Estilo::Titulo, linea));
+    }
+    lineas.push((Estilo::Normal, separador.clone()));
+
+    for campo in &plantilla.campos {
+        let textos = match campo {
+            CampoTicket::Nombre => vec![format!("Cliente: {}", datos.nombre_cliente)],
+            CampoTicket::Telefono => vec![format!("Teléfono: {}", datos.telefono_cliente)],
+            CampoTicket::Personas => vec![format!("Personas: {}", datos.numero_personas)],
+            CampoTicket::Fecha => vec![format!("Fecha: {}", datos.fecha)],
+            CampoTicket::Hora => vec![format!("Hora: {}", datos.hora)],
+            CampoTicket::Mesa => vec![format!("Mesa: {}", datos.mesa)],
+            CampoTicket::Localizador => datos.localizador
+                .iter()
+                .map(|localizador| format!("Localizador: {}", localizador))
+                .collect(),
+            CampoTicket::Preseleccion if datos.preseleccion.is_empty() => Vec::new(),
+            CampoTicket::Preseleccion => std::iter::once("Menú:".to_string())
+                .chain(datos.preseleccion.iter().map(|(nombre, cantidad)| format!("{} x {}", cantidad, nombre)))
+                .collect(),
+        };
+        for texto in textos {
+            lineas.extend(wrap(&texto, ancho).into_iter().map(|linea| (Estilo::Normal, linea)));
+        }
+    }
+
+    lineas.push((Estilo::Normal, separador));
+    if let Some(pie) = &plantilla.pie {
+        for linea in wrap(pie, ancho) {
+            lineas.push((Estilo::Centrada, linea));
+        }
+    }
+    lineas
+}
+
+/// Centra un texto en una línea de `ancho` caracteres
+fn center(texto: &str, ancho: usize) -> String {
+    let margen = ancho.saturating_sub(texto.chars().count()) / 2;
+    format!("{}{}", " ".repeat(margen), texto)
+}
+
+/// Quita los acentos y cambia por `?` lo que no sea ASCII
+fn to_ascii(texto: &str) -> String {
+    texto.nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .map(|c| match c {
+            '¡' => '!',
+            c if c.is_ascii() => c,
+            _ => '?',
+        })
+        .collect()
+}
+
+/// Ticket como texto plano, una línea por cada línea impresa
+///
+/// ```
+/// use pispas_reservation::db::{CampoTicket, PlantillaTicket};
+/// use pispas_reservation::ticket::{render_text, DatosTicket};
+///
+/// let datos = DatosTicket {
+///     restaurante: "La Tasca".to_string(),
+///     nombre_cliente: "Juan Pérez".to_string(),
+///     telefono_cliente: "+34 600 000 000".to_string(),
+///     numero_personas: 4,
+///     fecha: "2030-06-15".to_string(),
+///     hora: "21:00".to_string(),
+///     mesa: "Terraza 3".to_string(),
+///     localizador: None,
+///     preseleccion: vec![("Menú degustación".to_string(), 2)],
+/// };
+/// let plantilla = PlantillaTicket {
+///     ancho: 24,
+///     campos: vec![CampoTicket::Hora, CampoTicket::Nombre, CampoTicket::Preseleccion],
+///     pie: Some("¡Buen provecho!".to_string()),
+///     ..PlantillaTicket::default()
+/// };
+///
+/// assert_eq!(render_text(&plantilla, &datos), [
+///     "        LA TASCA",
+///     "------------------------",
+///     "Hora: 21:00",
+///     "Cliente: Juan Pérez",
+///     "Menú:",
+///     "2 x Menú degustación",
+///     "------------------------",
+///     "    ¡Buen provecho!",
+/// ].join("\n"));
+/// ```
+pub fn render_text(plantilla: &PlantillaTicket, datos: &DatosTicket) -> String {
+    let ancho = usize::from(plantilla.ancho);
+    lines(plantilla, datos)
+        .into_iter()
+        .map(|(estilo, linea)| match estilo {
+            Estilo::Titulo | Estilo::Centrada => center(&linea, ancho),
+            Estilo::Normal => linea,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Ticket como bytes ESC/POS
+///
+/// Empieza inicializando la impresora y, si la plantilla lo pide, termina
+/// cortando el papel.
+///
+/// ```
+/// use pispas_reservation::db::PlantillaTicket;
+/// use pispas_reservation::ticket::{render_escpos, DatosTicket};
+///
+/// let datos = DatosTicket {
+///     restaurante: "La Tasca".to_string(),
+///     nombre_cliente: "Juan Pérez".to_string(),
+///     telefono_cliente: "+34 600 000 000".to_string(),
+///     numero_personas: 4,
+///     fecha: "2030-06-15".to_string(),
+///     hora: "21:00".to_string(),
+///     mesa: "Terraza 3".to_string(),
+///     localizador: None,
+///     preseleccion: Vec::new(),
+/// };
+/// let ticket = render_escpos(&PlantillaTicket::default(), &datos);
+///
+/// assert!(ticket.starts_with(&[0x1B, b'@']));
+/// assert!(ticket.ends_with(&[0x1D, b'V', 66, 0]));
+/// assert!(ticket.windows(18).any(|w| w == b"Cliente: Juan Pere"));
+/// assert!(ticket.is_ascii());
+/// ```
+pub fn render_escpos(plantilla: &PlantillaTicket, datos: &DatosTicket) -> Vec<u8> {
+    let mut bytes = ESC_INICIAR.to_vec();
+    let mut anterior = None;
+
+    for (estilo, linea) in lines(plantilla, datos) {
+        if anterior != Some(estilo) {
+            let (alineacion, tamano) = match estilo {
+                Estilo::Titulo => (ESC_CENTRAR, GS_DOBLE),
+                Estilo::Centrada => (ESC_CENTRAR, GS_NORMAL),
+                Estilo::Normal => (ESC_IZQUIERDA, GS_NORMAL),
+            };
+            bytes.extend_from_slice(alineacion);
+            bytes.extend_from_slice(tamano);
+            anterior = Some(estilo);
+        }
+        bytes.extend_from_slice(to_ascii(&linea).as_bytes());
+        bytes.push(b'\n');
+    }
+
+    if plantilla.cortar {
+        bytes.extend_from_slice(GS_CORTAR);
+    }
+    bytes
+}
@@ -0,0 +1,131 @@
+//! # Cuerpos de los webhooks salientes
+//!
+//! Cada suscripción de webhook de un restaurante (ver
+//! [`crate::api::webhooks`]) elige qué eventos de dominio recibe y con qué
+//! forma. Este módulo decide si un evento se entrega a una suscripción y
+//! construye el cuerpo a partir de su plantilla:
+//!
+//! - `completo`: el evento tal cual se exporta (ver [`crate::events::to_json`])
+//! - `minimo`: solo `tipo`, `id_entidad` y `created_at`
+//! - `campos`: un objeto a medida, para receptores que esperan una forma
+//!   concreta. Cada campo se copia de una ruta con puntos del evento
+//!   completo (`datos.fecha`) a otra del cuerpo (`booking.date`); los que
+//!   no existen en el evento se envían como `null`
+
+use serde_json::{Map, Value};
+use crate::db::{Evento, PlantillaWebhook};
+use crate::events;
+
+/// Indica si una suscripción a `eventos` recibe los eventos de `tipo`
+///
+/// Una lista vacía recibe todos.
+///
+/// ```
+/// use pispas_reservation::webhooks::accepts;
+///
+/// assert!(accepts(&[], "reserva_creada"));
+/// assert!(accepts(&["reserva_creada".to_string()], "reserva_creada"));
+/// assert!(!accepts(&["reserva_cancelada".to_string()], "reserva_creada"));
+/// ```
+pub fn accepts(eventos: &[String], tipo: &str) -> bool {
+    eventos.is_empty() || eventos.iter().any(|evento| evento == tipo)
+}
+
+/// Indica si una ruta de campo es válida: segmentos no vacíos separados por `.`
+///
+/// ```
+/// use pispas_reservation::webhooks::valid_path;
+///
+/// assert!(valid_path("datos.fecha"));
+/// assert!(valid_path("tipo"));
+/// assert!(!valid_path(""));
+/// assert!(!valid_path("datos..fecha"));
+/// ```
+pub fn valid_path(ruta: &str) -> bool {
+    ruta.split('.').all(|segmento| !segmento.trim().is_empty())
+}
+
+/// Valor de una ruta con puntos dentro de un JSON
+fn lookup<'a>(valor: &'a Value, ruta: &str) -> Option<&'a Value> {
+    ruta.split('.').try_fold(valor, |actual, segmento| actual.get(segmento))
+}
+
+/// Escribe un valor en una ruta con puntos, creando los objetos intermedios
+///
+/// Si un segmento intermedio ya tiene un valor que no es un objeto, se
+/// reemplaza.
+fn insert(destino: &mut Map<String, Value>, ruta: &str, valor: Value) {
+    match ruta.split_once('.') {
+        None => {
+            destino.insert(ruta.to_string(), valor);
+        }
+        Some((primero, resto)) => {
+            let hijo = destino.entry(primero).or_insert_with(|| Value::Object(Map::new()));
+            if !hijo.is_object() {
+                *hijo = Value::Object(Map::new());
+            }
+            if let Value::Object(hijo) = hijo {
+                insert(hijo, resto, valor);
+            }
+        }
+    }
+}
+
+/// Cuerpo de la entrega de un evento con una plantilla
+///
+/// ```
+/// use std::collections::BTreeMap;
+/// use mongodb::bson::doc;
+/// use serde_json::json;
+/// use pispas_reservation::db::{Evento, PlantillaWebhook};
+/// use pispas_reservation::webhooks::render;
+///
+/// let evento = Evento {
+///     id: None,
+///     tipo: "reserva_creada".to_string(),
+///     id_restaurante: None,
+///     id_entidad: None,
+///     datos: doc! { "fecha": "2030-06-15", "personas": 4 },
+///     request_id: None,
+///     created_at: 1717243200,
+/// };
+///
+/// assert_eq!(
+///     render(&PlantillaWebhook::Minimo, &evento),
+///     json!({ "tipo": "reserva_creada", "id_entidad": null, "created_at": 1717243200 })
+/// );
+///
+/// let campos = BTreeMap::from([
+///     ("booking.date".to_string(), "datos.fecha".to_string()),
+///     ("booking.covers".to_string(), "datos.personas".to_string()),
+///     ("event".to_string(), "tipo".to_string()),
+///     ("notes".to_string(), "datos.notas".to_string()),
+/// ]);
+/// assert_eq!(
+///     render(&PlantillaWebhook::Campos { campos }, &evento),
+///     json!({
+///         "booking": { "date": "2030-06-15", "covers": 4 },
+///         "event": "reserva_creada",
+///         "notes": null
+///     })
+/// );
+/// ```
+pub fn render(plantilla: &PlantillaWebhook, evento: &Evento) -> Value {
+    let completo = events::to_json(evento);
+    match plantilla {
+        PlantillaWebhook::Completo => completo,
+        PlantillaWebhook::Minimo => serde_json::json!({
+            "tipo": completo["tipo"],
+            "id_entidad": completo["id_entidad"],
+            "created_at": completo["created_at"],
+        }),
+        PlantillaWebhook::Campos { campos } => {
+            let mut cuerpo = Map::new();
+            for (destino, origen) in campos {
+                let valor = lookup(&completo, origen).cloned().unwrap_or(Value::Null);
+                insert(&mut cuerpo, destino, valor);
+            }
+            Value::Object(cuerpo)
+        }
+    }
+}
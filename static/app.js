@@ -27,7 +27,9 @@ function showMessage(message, isSuccess = true) {
 // Función para manejar errores de API
 async function handleApiError(response) {
     if (!response.ok) {
-        const errorData = await response.json().catch(() => ({ message: 'Error desconocido' }));
+        // Los errores llegan en `error` dentro del sobre de la respuesta
+        const cuerpo = await response.json().catch(() => ({ error: { message: 'Error desconocido' } }));
+        const errorData = cuerpo.error || {};
         const message = errorData.message || errorData.error || 'Error en la operación';
         showMessage(message, false);
         throw new Error(message);
@@ -115,7 +117,7 @@ async function cargarPlano() {
         });
 
         await handleApiError(response);
-        const data = await response.json();
+        const { data } = await response.json();
 
         mesaCounter = 1;
 
@@ -134,7 +136,8 @@ async function cargarPlano() {
                 mesa.size_y,
                 mesa.min_personas,
                 mesa.max_personas,
-                mesa.forma
+                mesa.forma,
+                mesa.id_planta
             );
 
             const tr = document.createElement('tr');
@@ -174,7 +177,7 @@ async function login() {
         });
 
         await handleApiError(response);
-        const data = await response.json();
+        const { data } = await response.json();
 
         accessToken = data.access_token;
         restauranteId = data.id_restaurante;
@@ -192,7 +195,7 @@ async function login() {
 let mesaCounter = 1;
 
 // Función para crear una nueva mesa visual
-function crearMesa(nombre = null, posX = 100, posY = 100, sizeX = 80, sizeY = 80, minPersonas = 2, maxPersonas = 4, forma = "cuadrado") {
+function crearMesa(nombre = null, posX = 100, posY = 100, sizeX = 80, sizeY = 80, minPersonas = 2, maxPersonas = 4, forma = "cuadrado", planta = null) {
     const plano = document.getElementById('plano');
 
     const mesa = document.createElement('div');
@@ -205,6 +208,9 @@ function crearMesa(nombre = null, posX = 100, posY = 100, sizeX = 80, sizeY = 80
     mesa.dataset.max = maxPersonas;
     mesa.dataset.forma = forma;
     mesa.dataset.numero = mesaCounter;
+    if (planta) {
+        mesa.dataset.planta = planta;
+    }
 
     if (forma === "circulo") {
         mesa.style.borderRadius = "50%";
@@ -337,8 +343,16 @@ async function guardarPlano() {
     }
 
     try {
-        // Primero limpiar las mesas existentes
-        await fetch(`/tables/clear?id_restaurante=${restauranteId}`, {
+        // Primero limpiar las mesas existentes (pide un token de confirmación)
+        const aviso = await fetch(`/tables/clear?id_restaurante=${restauranteId}`, {
+            method: 'DELETE',
+            headers: {
+                'Authorization': `Bearer ${accessToken}`
+            }
+        });
+        // Responde 428 con el token: va en `error` dentro del sobre
+        const { confirmacion } = (await aviso.json()).error;
+        await fetch(`/tables/clear?id_restaurante=${restauranteId}&confirmacion=${confirmacion}`, {
             method: 'DELETE',
             headers: {
                 'Authorization': `Bearer ${accessToken}`
@@ -366,7 +380,9 @@ async function guardarPlano() {
                 forma: mesa.dataset.forma || "cuadrado",
                 reservable: true,
                 min_personas: parseInt(mesa.dataset.min) || null,
-                max_personas: parseInt(mesa.dataset.max) || null
+                max_personas: parseInt(mesa.dataset.max) || null,
+                // Conservar la planta al volver a crear la mesa
+                id_planta: mesa.dataset.planta || null
             };
 
             const response = await fetch('/tables', {
@@ -0,0 +1,118 @@
+//! Borrado de cuentas con plazo para restaurarlas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::Duration;
+use common::{bearer, create_table, send, test_clock, TestDb, TestRestaurant};
+use mongodb::bson::doc;
+use pispas_reservation::clock::Clock;
+use pispas_reservation::jobs::account_purge;
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+const ADMIN_TOKEN: &str = "admin-test-token";
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn deleted_accounts_can_be_restored_until_they_are_purged() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri("/restaurants/register")
+        .set_json(json!({
+            "objid_pispas": "objid-la-tasca",
+            "name": "La Tasca",
+            "password": "secreto123",
+            "confirmar_automaticamente": false,
+            "email": "dueno@latasca.es"
+        }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let restaurant = TestRestaurant {
+        id: body["id"].as_str().unwrap().to_string(),
+        token: body["access_token"].as_str().unwrap().to_string(),
+    };
+    create_table(&app, &restaurant, "Mesa 1").await;
+    let login = || TestRequest::post()
+        .uri("/restaurants/login")
+        .set_json(json!({ "name": "La Tasca", "password": "secreto123" }));
+
+    let borrar = |password: &str| bearer(TestRequest::delete(), &restaurant.token)
+        .uri("/restaurants/account")
+        .set_json(json!({ "password": password }));
+    let (status, _) = send(&app, borrar("otra")).await;
+    assert_eq!(status, 401);
+    let (status, body) = send(&app, borrar("secreto123")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "pendiente_borrado");
+    assert_eq!(body["purgar_en"], clock.timestamp() + 30 * 86_400);
+
+    // Sin acceso mientras está pendiente de borrado, ni siquiera desde el widget
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/tables")).await;
+    assert_eq!(status, 401);
+    let (status, body) = send(&app, login()).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["codigo"], "cuenta_pendiente_borrado");
+    let (status, _) = send(&app, TestRequest::get()
+        .uri(&format!("/public/restaurants/{}/menu-options", restaurant.id))).await;
+    assert_eq!(status, 403);
+    let (status, _) = send(&app, bearer(TestRequest::put(), ADMIN_TOKEN)
+        .uri(&format!("/admin/restaurants/{}/state", restaurant.id))
+        .set_json(json!({ "estado": "activo" }))).await;
+    assert_eq!(status, 409);
+
+    // El propietario la restaura con el enlace del email, una sola vez
+    let sent = notifier.outbox().unwrap().sent();
+    let SentMessage::Email(email) = sent.last().unwrap() else {
+        panic!("se esperaba un email: {:?}", sent);
+    };
+    assert_eq!(email.to, "dueno@latasca.es");
+    let path = email.body
+        .split_whitespace()
+        .find_map(|word| word.find("/restaurants/restore/").map(|i| word[i..].to_string()))
+        .expect("el email debe incluir el enlace de restauración");
+    let (status, body) = send(&app, TestRequest::get().uri(&path)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "activo");
+    let (status, _) = send(&app, TestRequest::get().uri(&path)).await;
+    assert_eq!(status, 404);
+
+    let (status, body) = send(&app, login()).await;
+    assert_eq!(status, 200, "{}", body);
+    let token = body["access_token"].as_str().unwrap().to_string();
+    let (_, mesas) = send(&app, bearer(TestRequest::get(), &token).uri("/tables")).await;
+    assert_eq!(mesas.as_array().unwrap().len(), 1, "{}", mesas);
+
+    // La administración también puede borrarla y restaurarla
+    let admin = |req: TestRequest, ruta: &str| bearer(req, ADMIN_TOKEN)
+        .uri(&format!("/admin/restaurants/{}{}", restaurant.id, ruta));
+    let (status, _) = send(&app, admin(TestRequest::post(), "/restore")).await;
+    assert_eq!(status, 409);
+    let (status, body) = send(&app, admin(TestRequest::delete(), "")).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, _) = send(&app, admin(TestRequest::delete(), "")).await;
+    assert_eq!(status, 409);
+    let (status, body) = send(&app, admin(TestRequest::post(), "/restore")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "activo");
+
+    // Pasado el plazo se purga con todos sus datos
+    let (status, _) = send(&app, admin(TestRequest::delete(), "")).await;
+    assert_eq!(status, 200);
+    clock.advance(Duration::days(29));
+    assert_eq!(account_purge::run(&db.repo, clock.as_ref()).await.unwrap(), 0);
+    clock.advance(Duration::days(2));
+    assert_eq!(account_purge::run(&db.repo, clock.as_ref()).await.unwrap(), 1);
+
+    assert_eq!(db.repo.restaurants().count_documents(doc! {}).await.unwrap(), 0);
+    assert_eq!(db.repo.mesas().count_documents(doc! {}).await.unwrap(), 0);
+    assert_eq!(db.repo.eventos().count_documents(doc! { "tipo": "cuenta_purgada" }).await.unwrap(), 1);
+    let (status, _) = send(&app, admin(TestRequest::post(), "/restore")).await;
+    assert_eq!(status, 404);
+}
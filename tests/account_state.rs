@@ -0,0 +1,90 @@
+//! Suspensión y modo solo lectura de cuentas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, send, TestDb};
+use serde_json::json;
+
+const ADMIN_TOKEN: &str = "admin-test-token";
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn admin_suspends_and_restores_an_account() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    create_table(&app, &restaurant, "Mesa 1").await;
+    let cambiar_estado = |estado: &str| bearer(TestRequest::put(), ADMIN_TOKEN)
+        .uri(&format!("/admin/restaurants/{}/state", restaurant.id))
+        .set_json(json!({ "estado": estado, "motivo": "Impagos" }));
+    let nueva_mesa = || bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .set_json(json!({
+            "id_restaurante": restaurant.id,
+            "tipo": "mesa",
+            "nombre": "Mesa 2",
+            "pos_x": 0.0,
+            "pos_y": 0.0,
+            "size_x": 80.0,
+            "size_y": 80.0,
+            "forma": "cuadrado",
+            "reservable": true,
+            "min_personas": 2,
+            "max_personas": 4
+        }));
+    let listar_mesas = || bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables?id_restaurante={}", restaurant.id));
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/admin/restaurants/{}/state", restaurant.id))
+        .set_json(json!({ "estado": "activo" }))).await;
+    assert_eq!(status, 401, "el token de un restaurante no es de administración");
+
+    // Solo lectura: se puede consultar pero no modificar
+    let (status, body) = send(&app, cambiar_estado("solo_lectura")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado_anterior"], "activo");
+
+    let (status, _) = send(&app, listar_mesas()).await;
+    assert_eq!(status, 200);
+    let (status, body) = send(&app, nueva_mesa()).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["codigo"], "cuenta_solo_lectura");
+
+    // Suspendida: ni consultas, ni login, ni widget
+    let (status, _) = send(&app, cambiar_estado("suspendido")).await;
+    assert_eq!(status, 200);
+
+    let (status, body) = send(&app, listar_mesas()).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["codigo"], "cuenta_suspendida");
+    assert!(body["message"].as_str().unwrap().contains("Impagos"), "{}", body);
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri("/restaurants/login")
+        .set_json(json!({ "name": "La Tasca", "password": "secreto123" }))).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["codigo"], "cuenta_suspendida");
+
+    let (status, body) = send(&app, TestRequest::get()
+        .uri(&format!("/public/restaurants/{}/menu-options", restaurant.id))).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["codigo"], "cuenta_suspendida");
+
+    // Reactivada: todo vuelve a funcionar en la siguiente petición
+    let (status, _) = send(&app, cambiar_estado("activo")).await;
+    assert_eq!(status, 200);
+    let (status, body) = send(&app, nueva_mesa()).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let eventos = db.repo.eventos()
+        .count_documents(mongodb::bson::doc! { "tipo": "estado_cuenta_cambiado" })
+        .await
+        .unwrap();
+    assert_eq!(eventos, 3);
+}
@@ -0,0 +1,364 @@
+//! API de administración contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::{TimeZone, Utc};
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use mongodb::bson::doc;
+use pispas_reservation::notifications::Notifier;
+
+const ADMIN_TOKEN: &str = "admin-test-token";
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn rebuild_backfills_normalized_names() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "Café Olé").await;
+    create_table(&app, &restaurant, "Terraza 1").await;
+
+    // Simula documentos anteriores a la migración, sin nombre normalizado
+    db.repo.restaurants()
+        .update_many(doc! {}, doc! { "$set": { "nombre_normalizado": "" } })
+        .await
+        .unwrap();
+    db.repo.mesas()
+        .update_many(doc! {}, doc! { "$set": { "nombre_normalizado": "" } })
+        .await
+        .unwrap();
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/admin/rebuild?what=nombres")).await;
+    assert_eq!(status, 401, "el token de un restaurante no es de administración");
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/rebuild?what=nombres")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["colecciones"][0]["actualizados"], 1);
+    assert_eq!(body["colecciones"][1]["actualizados"], 1);
+
+    let stored = db.repo.restaurants().find_one(doc! {}).await.unwrap().unwrap();
+    assert_eq!(stored.nombre_normalizado, "cafe ole");
+
+    // Repetirlo no vuelve a escribir nada
+    let (_, body) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/rebuild?what=nombres")).await;
+    assert_eq!(body["colecciones"][0]["actualizados"], 0);
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/rebuild?what=indexes")).await;
+    assert_eq!(status, 200);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn rebuild_counters_recomputes_the_daily_stats_and_stores_its_progress() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let clock = common::test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    for fecha in ["2030-06-15", "2030-08-20"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&id_mesa, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    clock.set(Utc.with_ymd_and_hms(2030, 9, 1, 3, 0, 0).unwrap());
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), "admin-test-tokeN")
+        .uri("/admin/rebuild?what=counters")).await;
+    assert_eq!(status, 401);
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/rebuild?what=counters")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "terminada");
+    assert_eq!(body["colecciones"][0]["coleccion"], "stats_daily");
+    // Del 15 de junio al 31 de agosto
+    assert_eq!(body["colecciones"][0]["revisados"], 78);
+    assert_eq!(body["colecciones"][0]["actualizados"], 2);
+
+    let dia = db.repo.estadisticas_diarias()
+        .find_one(doc! { "fecha": "2030-08-20" })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(dia.reservas, 1);
+
+    let id = body["id"].as_str().unwrap();
+    let (status, guardada) = send(&app, bearer(TestRequest::get(), ADMIN_TOKEN)
+        .uri(&format!("/admin/rebuild/{}", id))).await;
+    assert_eq!(status, 200, "{}", guardada);
+    assert_eq!(guardada["trabajo"], "counters");
+    assert_eq!(guardada["estado"], "terminada");
+    assert_eq!(guardada["colecciones"], body["colecciones"]);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn rebuild_counters_stops_at_yesterday_in_the_restaurant_time_zone() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let clock = common::test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(serde_json::json!({ "zona_horaria": "America/New_York" }))).await;
+    assert_eq!(status, 200);
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    for fecha in ["2030-08-30", "2030-08-31"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&id_mesa, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    // 1 de septiembre en UTC, aún 31 de agosto en Nueva York
+    clock.set(Utc.with_ymd_and_hms(2030, 9, 1, 3, 0, 0).unwrap());
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/rebuild?what=counters")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["colecciones"][0]["revisados"], 1);
+    assert_eq!(body["colecciones"][0]["actualizados"], 1);
+
+    let dias = db.repo.estadisticas_diarias()
+        .count_documents(doc! { "fecha": "2030-08-31" })
+        .await
+        .unwrap();
+    assert_eq!(dias, 0, "el día en curso del restaurante no se agrega");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn anonymize_strips_personal_data_of_old_reservations() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let clock = common::test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    for fecha in ["2030-06-15", "2030-06-20"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&id_mesa, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    clock.set(Utc.with_ymd_and_hms(2030, 6, 18, 3, 0, 0).unwrap());
+
+    let (status, informe) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/anonymize?dias=0")).await;
+    assert_eq!(status, 200, "{}", informe);
+    assert_eq!(informe["fecha_limite"], "2030-06-18");
+    assert_eq!(informe["reservas_anonimizadas"], 1);
+    assert_eq!(informe["por_restaurante"][0]["reservas"], 1);
+
+    let antigua = db.repo.reservas().find_one(doc! { "fecha": "2030-06-15" }).await.unwrap().unwrap();
+    assert_eq!(antigua.nombre_cliente, "Anónimo");
+    assert_eq!(antigua.email_cliente, "");
+    assert_eq!(antigua.telefono_cliente, "");
+    assert_eq!(antigua.numero_personas, 2);
+    assert!(antigua.anonimizada_en.is_some());
+
+    let reciente = db.repo.reservas().find_one(doc! { "fecha": "2030-06-20" }).await.unwrap().unwrap();
+    assert_eq!(reciente.nombre_cliente, "Juan Pérez");
+
+    // Una segunda ejecución no vuelve a contar las ya anonimizadas
+    let (_, informe) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/anonymize?dias=0")).await;
+    assert_eq!(informe["reservas_anonimizadas"], 0);
+
+    let (status, informes) = send(&app, bearer(TestRequest::get(), ADMIN_TOKEN)
+        .uri("/admin/anonymize/reports")).await;
+    assert_eq!(status, 200);
+    assert_eq!(informes.as_array().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn legal_hold_skips_anonymization_until_lifted() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let clock = common::test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+    let id_reserva = body["id"].as_str().unwrap().to_string();
+
+    let marcar = || bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/legal-hold", id_reserva))
+        .set_json(serde_json::json!({ "motivo": "Reclamación del cliente" }));
+    let (status, body) = send(&app, marcar()).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["retencion_legal"]["usuario"], "propietario");
+    let (status, _) = send(&app, marcar()).await;
+    assert_eq!(status, 409, "ya tiene retención legal");
+
+    // El restaurante no puede levantarla
+    let levantar = |token: &str| bearer(TestRequest::delete(), token)
+        .uri(&format!("/admin/reservations/{}/legal-hold", id_reserva));
+    let (status, _) = send(&app, levantar(&restaurant.token)).await;
+    assert_eq!(status, 401);
+
+    clock.set(Utc.with_ymd_and_hms(2030, 6, 18, 3, 0, 0).unwrap());
+    let (status, informe) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/anonymize?dias=0")).await;
+    assert_eq!(status, 200, "{}", informe);
+    assert_eq!(informe["reservas_anonimizadas"], 0);
+    assert_eq!(informe["reservas_retenidas"], 1);
+    let retenida = db.repo.reservas().find_one(doc! {}).await.unwrap().unwrap();
+    assert_eq!(retenida.nombre_cliente, "Juan Pérez");
+
+    let (status, body) = send(&app, levantar(ADMIN_TOKEN)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["motivo"], "Reclamación del cliente");
+
+    let (_, informe) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/anonymize?dias=0")).await;
+    assert_eq!(informe["reservas_anonimizadas"], 1);
+    assert_eq!(informe["reservas_retenidas"], 0);
+
+    let eventos = db.repo.eventos()
+        .count_documents(doc! { "tipo": { "$in": ["retencion_legal_marcada", "retencion_legal_levantada"] } })
+        .await
+        .unwrap();
+    assert_eq!(eventos, 2);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn restaurant_diagnostics_never_expose_passwords() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, TestRequest::get().uri("/restaurants/all/debug")).await;
+    assert_eq!(status, 404);
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/admin/restaurants")).await;
+    assert_eq!(status, 401, "el token de un restaurante no es de administración");
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), ADMIN_TOKEN)
+        .uri("/admin/restaurants")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body[0]["nombre"], "La Tasca");
+    assert_eq!(body[0]["mesas"], 1);
+    assert!(body[0].get("password").is_none());
+    assert!(body[0].get("access_token").is_none());
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn explain_reports_the_index_used_by_each_canonical_query() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    std::env::set_var("DEV_ROUTES", "true");
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let (status, _) = send(&app, TestRequest::get().uri("/admin/explain")).await;
+    assert_eq!(status, 401);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), ADMIN_TOKEN).uri("/admin/explain")).await;
+    assert_eq!(status, 200, "{}", body);
+    let sesion = body["consultas"].as_array().unwrap()
+        .iter()
+        .find(|consulta| consulta["nombre"] == "token_sesion")
+        .unwrap();
+    assert_eq!(sesion["indices"][0], "token_1");
+    assert_eq!(sesion["ok"], true);
+
+    // Sin el índice la consulta recorre la colección entera
+    db.repo.sesiones().drop_index("token_1").await.unwrap();
+    let (_, body) = send(&app, bearer(TestRequest::get(), ADMIN_TOKEN).uri("/admin/explain")).await;
+    assert_eq!(body["ok"], false);
+    assert_eq!(body["consultas"][0]["etapas"][0], "COLLSCAN");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn public_status_reflects_maintenance_and_declared_incidents() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let (status, body) = send(&app, TestRequest::get().uri("/public/status")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "operativo");
+    assert_eq!(body["incidencia"], false);
+    assert_eq!(body["componentes"]["base_datos"]["estado"], "operativo");
+    assert!(body["uptime_segundos"].is_u64());
+
+    let (status, _) = send(&app, TestRequest::put()
+        .uri("/admin/status")
+        .set_json(serde_json::json!({ "mantenimiento": true }))).await;
+    assert_eq!(status, 401);
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), ADMIN_TOKEN)
+        .uri("/admin/status")
+        .set_json(serde_json::json!({ "mantenimiento": true, "incidencia": " Actualización de la base de datos " }))).await;
+    assert_eq!(status, 200);
+
+    let (_, body) = send(&app, TestRequest::get().uri("/public/status")).await;
+    assert_eq!(body["estado"], "mantenimiento");
+    assert_eq!(body["incidencia"], true);
+    assert_eq!(body["mensaje"], "Actualización de la base de datos");
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), ADMIN_TOKEN)
+        .uri("/admin/status")
+        .set_json(serde_json::json!({ "mantenimiento": false }))).await;
+    assert_eq!(status, 200);
+
+    let (_, body) = send(&app, TestRequest::get().uri("/public/status")).await;
+    assert_eq!(body["estado"], "operativo");
+    assert!(body["mensaje"].is_null());
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn deprecated_routes_announce_their_sunset() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let (status, _) = send(&app, TestRequest::get().uri("/restaurants/all")).await;
+    assert_eq!(status, 401, "el listado completo es solo de administración");
+
+    let resp = actix_web::test::call_service(&app, bearer(TestRequest::get(), ADMIN_TOKEN)
+        .uri("/restaurants/all")
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("deprecation").unwrap(), "@1792108800");
+    assert_eq!(resp.headers().get("sunset").unwrap(), "Fri, 16 Apr 2027 00:00:00 GMT");
+    assert_eq!(resp.headers().get("link").unwrap(), "</admin/restaurants>; rel=\"successor-version\"");
+
+    let resp = actix_web::test::call_service(&app, TestRequest::get().uri("/public/status").to_request()).await;
+    assert!(resp.headers().get("deprecation").is_none());
+
+    let (status, body) = send(&app, TestRequest::get().uri("/public/deprecations")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["paths"]["/restaurants/all"]["get"]["deprecated"], true);
+    assert_eq!(body["paths"]["/restaurants/all"]["get"]["x-successor"], "/admin/restaurants");
+}
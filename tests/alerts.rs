@@ -0,0 +1,76 @@
+//! Monitor de alertas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use std::time::Duration;
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::jobs::alerts::{self, ConfigMonitor, SaludWebhook};
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+fn config() -> ConfigMonitor {
+    ConfigMonitor {
+        intervalo: Duration::from_secs(300),
+        silencio_segundos: 3600,
+        email_ops: None,
+        latencia_bd_ms: None,
+        webhook_minutos: None,
+    }
+}
+
+#[test]
+fn webhook_failures_count_from_the_first_one_after_a_success() {
+    let mut salud = SaludWebhook::default();
+    salud.registrar(true, 50);
+    assert_eq!(salud.fallando_desde(), None);
+    salud.registrar(false, 100);
+    salud.registrar(false, 1900);
+    assert_eq!(salud.fallando_desde(), Some(100));
+    salud.registrar(true, 2000);
+    salud.registrar(false, 2100);
+    assert_eq!(salud.fallando_desde(), Some(2100));
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn too_many_cancellations_alert_the_owner_once() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({
+            "email_alertas": "encargado@latasca.es",
+            "alertas": [{ "tipo": "cancelaciones", "max": 1, "ventana_minutos": 60 }]
+        }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    for hora in ["13:00", "21:00"] {
+        let (_, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, "2030-06-15", hora))).await;
+        let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri(&format!("/reservations/{}/cancel", reserva["id"].as_str().unwrap()))).await;
+        assert_eq!(status, 200);
+    }
+
+    let enviadas = alerts::run(&db.repo, &notifier, clock.as_ref(), &config()).await.unwrap();
+    assert_eq!(enviadas.len(), 1, "{:?}", enviadas);
+    match &notifier.outbox().unwrap().sent()[..] {
+        [SentMessage::Email(email)] => {
+            assert_eq!(email.to, "encargado@latasca.es");
+            assert!(email.body.contains("2 cancelaciones"), "{}", email.body);
+        }
+        otros => panic!("se esperaba un email: {:?}", otros),
+    }
+
+    let enviadas = alerts::run(&db.repo, &notifier, clock.as_ref(), &config()).await.unwrap();
+    assert!(enviadas.is_empty(), "la alerta no se repite durante el silencio");
+}
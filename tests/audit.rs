@@ -0,0 +1,116 @@
+//! Registro de auditoría contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn owner_sees_who_changed_what() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let otro = register_restaurant(&app, "La Bodega").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/staff")
+        .set_json(json!({ "usuario": "ana", "password": "secreto123", "rol": "camarero" }))).await;
+    let (_, login) = send(&app, TestRequest::post()
+        .uri("/staff/login")
+        .set_json(json!({ "id_restaurante": restaurant.id, "usuario": "ana", "password": "secreto123" }))).await;
+    let camarero = login["access_token"].as_str().unwrap().to_string();
+
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &camarero)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let id_reserva = reserva["id"].as_str().unwrap().to_string();
+    let (status, _) = send(&app, bearer(TestRequest::post(), &camarero)
+        .uri(&format!("/reservations/{}/confirm", id_reserva))).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "duracion_reserva_minutos": 90 }))).await;
+    assert_eq!(status, 200);
+
+    let (status, entradas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/audit")).await;
+    assert_eq!(status, 200, "{}", entradas);
+    let acciones: Vec<_> = entradas.as_array().unwrap().iter()
+        .map(|entrada| entrada["accion"].as_str().unwrap())
+        .collect();
+    assert_eq!(acciones, ["cambiar_configuracion", "confirmar_reserva", "crear_reserva", "crear_empleado", "crear_mesa"]);
+
+    let confirmacion = &entradas[1];
+    assert_eq!(confirmacion["usuario"], "ana");
+    assert_eq!(confirmacion["rol"], "camarero");
+    assert_eq!(confirmacion["id_entidad"], id_reserva.as_str());
+    assert_eq!(confirmacion["cambios"], json!([{ "campo": "estado", "antes": "pendiente", "despues": "confirmada" }]));
+
+    // Ni secretos ni datos de contacto de los clientes
+    let texto = entradas.to_string();
+    assert!(!texto.contains("secreto123"));
+    assert!(!texto.contains("juan@email.com"));
+
+    let (status, filtradas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/audit?entidad=reserva&id_entidad={}", id_reserva))).await;
+    assert_eq!(status, 200);
+    assert_eq!(filtradas.as_array().unwrap().len(), 2);
+
+    // Solo el propietario, y solo su restaurante
+    let (status, _) = send(&app, bearer(TestRequest::get(), &camarero).uri("/audit")).await;
+    assert_eq!(status, 401);
+    let (status, ajenas) = send(&app, bearer(TestRequest::get(), &otro.token).uri("/audit")).await;
+    assert_eq!(status, 200);
+    assert_eq!(ajenas, json!([]));
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_history_traces_customer_and_staff_changes() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let otro = register_restaurant(&app, "La Bodega").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, reserva) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let id = reserva["id"].as_str().unwrap().to_string();
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", id))
+        .set_json(json!({ "hora": "21:30" }))).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/confirm", id))).await;
+    assert_eq!(status, 200);
+
+    let (status, historial) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/reservations/{}/history", id))).await;
+    assert_eq!(status, 200, "{}", historial);
+    let eventos: Vec<_> = historial.as_array().unwrap().iter()
+        .map(|evento| (evento["accion"].as_str().unwrap(), evento["autor"].as_str().unwrap()))
+        .collect();
+    assert_eq!(eventos, [
+        ("crear_reserva", "cliente"),
+        ("modificar_reserva", "propietario"),
+        ("confirmar_reserva", "propietario"),
+    ]);
+    assert!(historial[0]["rol"].is_null());
+    assert_eq!(historial[1]["rol"], "propietario");
+    assert_eq!(historial[1]["cambios"], json!([{ "campo": "hora", "antes": "21:00", "despues": "21:30" }]));
+    assert!(!historial.to_string().contains("juan@email.com"));
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &otro.token)
+        .uri(&format!("/reservations/{}/history", id))).await;
+    assert_eq!(status, 404);
+}
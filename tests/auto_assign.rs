@@ -0,0 +1,72 @@
+//! Asignación automática de mesa contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn auto_assign_picks_the_best_fitting_free_table() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, terraza) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/floors")
+        .set_json(json!({ "nombre": "Terraza", "orden": 1 }))).await;
+    assert_eq!(status, 200, "{}", terraza);
+    let id_terraza = terraza["id"].as_str().unwrap().to_string();
+    for (nombre, max_personas, planta) in [("Terraza 1", 6, Some(&id_terraza)), ("Grande", 8, None)] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/tables")
+            .set_json(json!({
+                "id_restaurante": restaurant.id,
+                "tipo": "mesa",
+                "nombre": nombre,
+                "pos_x": 100.0,
+                "pos_y": 100.0,
+                "size_x": 80.0,
+                "size_y": 80.0,
+                "forma": "cuadrado",
+                "reservable": true,
+                "min_personas": 1,
+                "max_personas": max_personas,
+                "id_planta": planta
+            }))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    let asignar = |cuerpo: Value| bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations/auto-assign")
+        .set_json(cuerpo);
+
+    // La que deja menos sillas vacías, salvo que se prefiera otra planta
+    let (status, body) = send(&app, asignar(json!({ "fecha": "2030-06-15", "hora": "21:00", "numero_personas": 2 }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["id"], mesa.as_str());
+    let (_, body) = send(&app, asignar(json!({
+        "fecha": "2030-06-15", "hora": "21:00", "numero_personas": 2, "plantas": [id_terraza]
+    }))).await;
+    assert_eq!(body["nombre"], "Terraza 1");
+
+    // Las mesas ocupadas no cuentan
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200);
+    let (_, body) = send(&app, asignar(json!({ "fecha": "2030-06-15", "hora": "21:30", "numero_personas": 2 }))).await;
+    assert_eq!(body["nombre"], "Terraza 1");
+    let (_, body) = send(&app, asignar(json!({ "fecha": "2030-06-15", "hora": "21:00", "numero_personas": 7 }))).await;
+    assert_eq!(body["nombre"], "Grande");
+
+    let (status, body) = send(&app, asignar(json!({ "fecha": "2030-06-15", "hora": "21:00", "numero_personas": 10 }))).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["codigo"], "sin_mesa_libre");
+    let (status, _) = send(&app, asignar(json!({ "fecha": "2030-06-15", "hora": "25:00", "numero_personas": 2 }))).await;
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,69 @@
+//! Disponibilidad de varios días para el widget contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::notifications::Notifier;
+use serde_json::{json, Value};
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn widget_gets_free_hours_for_many_days_at_once() {
+    let db = TestDb::start().await;
+    let app = common::init_app_with(&db, Notifier::memory(), test_clock()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({
+            "turnos": [{ "nombre": "Cena", "hora_inicio": "20:00", "hora_fin": "22:00" }],
+            "max_personas_online": 6
+        }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let consultar = |cuerpo: Value| TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/availability/batch", restaurant.id))
+        .set_json(cuerpo);
+    let (status, body) = send(&app, consultar(json!({ "personas": 2, "desde": "2030-06-14", "hasta": "2030-06-16" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["dias"], json!([
+        { "fecha": "2030-06-14", "horas": ["20:00", "20:30", "21:00", "21:30"] },
+        // La reserva de 21:00 a 22:30 choca con todas las horas de la cena
+        { "fecha": "2030-06-15", "horas": [] },
+        { "fecha": "2030-06-16", "horas": ["20:00", "20:30", "21:00", "21:30"] }
+    ]));
+
+    // Una lista de fechas, sin repetir y en orden
+    let (status, body) = send(&app, consultar(json!({ "personas": 2, "fechas": ["2030-06-16", "2030-06-14", "2030-06-16"] }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let fechas: Vec<&str> = body["dias"].as_array().unwrap().iter().map(|dia| dia["fecha"].as_str().unwrap()).collect();
+    assert_eq!(fechas, ["2030-06-14", "2030-06-16"]);
+
+    // Un grupo demasiado grande para el widget no tiene horas
+    let (status, body) = send(&app, consultar(json!({ "personas": 8, "fechas": ["2030-06-14"] }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["dias"][0]["horas"], json!([]));
+
+    for cuerpo in [
+        json!({ "personas": 2 }),
+        json!({ "personas": 0, "fechas": ["2030-06-14"] }),
+        json!({ "personas": 2, "desde": "2030-06-14", "hasta": "2030-06-01" }),
+        json!({ "personas": 2, "desde": "2030-06-01", "hasta": "2030-12-31" }),
+        json!({ "personas": 2, "fechas": ["14/06/2030"] }),
+    ] {
+        let (status, _) = send(&app, consultar(cuerpo.clone())).await;
+        assert_eq!(status, 400, "{}", cuerpo);
+    }
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/public/restaurants/507f1f77bcf86cd799439011/availability/batch")
+        .set_json(json!({ "personas": 2, "fechas": ["2030-06-14"] }))).await;
+    assert_eq!(status, 404);
+}
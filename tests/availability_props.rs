@@ -0,0 +1,221 @@
+//! Tests de propiedades del motor de disponibilidad
+//!
+//! Generan conjuntos aleatorios de mesas, duraciones y reservas y comprueban
+//! los invariantes de [`pispas_reservation::availability`]:
+//!
+//! - Aceptar reservas solo cuando no hay conflicto nunca deja dos reservas
+//!   activas solapadas en la misma mesa.
+//! - La búsqueda de disponibilidad nunca ofrece una mesa ocupada ni una mesa
+//!   que no admite el número de personas, y no omite ninguna mesa libre.
+//! - El prefiltro por fechas de la consulta a MongoDB nunca descarta una
+//!   reserva que se solapa con el intervalo buscado.
+
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use mongodb::bson::oid::ObjectId;
+use pispas_reservation::availability::{
+    available_tables, candidate_dates, has_conflict, overlaps, parse_inicio, CapacidadMesa,
+    Ocupacion, FORMATO_FECHA, FORMATO_HORA,
+};
+use pispas_reservation::db::{EstadoReserva, Reserva};
+use proptest::prelude::*;
+
+/// ID de mesa determinista a partir de un índice
+fn mesa_id(index: usize) -> ObjectId {
+    let mut bytes = [0u8; 12];
+    bytes[4..].copy_from_slice(&(index as u64).to_be_bytes());
+    ObjectId::from_bytes(bytes)
+}
+
+fn base() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2030, 6, 15).unwrap().and_hms_opt(0, 0, 0).unwrap()
+}
+
+/// Instantes dentro de tres días, en pasos de 5 minutos como en el selector de horas
+fn instante() -> impl Strategy<Value = NaiveDateTime> {
+    (0i64..(3 * 24 * 12)).prop_map(|paso| base() + Duration::minutes(paso * 5))
+}
+
+fn duracion() -> impl Strategy<Value = u32> {
+    15u32..=240
+}
+
+fn mesas() -> impl Strategy<Value = Vec<CapacidadMesa>> {
+    prop::collection::vec(
+        (any::<bool>(), prop::option::of(1i32..=4), prop::option::of(2i32..=12)),
+        1..8,
+    )
+    .prop_map(|specs| {
+        specs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (reservable, min, max))| CapacidadMesa {
+                id_mesa: mesa_id(i),
+                reservable,
+                min_personas: min,
+                max_personas: max,
+            })
+            .collect()
+    })
+}
+
+/// Peticiones de reserva: (índice de mesa, inicio, personas)
+fn peticiones() -> impl Strategy<Value = Vec<(usize, NaiveDateTime, i32)>> {
+    prop::collection::vec((0usize..8, instante(), 1i32..=10), 0..40)
+}
+
+fn reserva(id_mesa: ObjectId, inicio: NaiveDateTime, estado: EstadoReserva) -> Reserva {
+    Reserva {
+        id: None,
+        id_restaurante: ObjectId::from_bytes([1; 12]),
+        id_mesa,
+        nombre_cliente: "Cliente".to_string(),
+        email_cliente: "cliente@email.com".to_string(),
+        telefono_cliente: "600000000".to_string(),
+        numero_personas: 2,
+        fecha: inicio.format(FORMATO_FECHA).to_string(),
+        hora: inicio.format(FORMATO_HORA).to_string(),
+        estado,
+        created_at: 0,
+        updated_at: 0,
+        canal: "interno".to_string(),
+        verificacion: None,
+        dispositivo: None,
+        anonimizada_en: None,
+        id_cliente: None,
+        preseleccion: Vec::new(),
+        deposito: None,
+        pago: None,
+        cancelacion_tardia: false,
+        pendiente_desde: None,
+        confirmada_en: None,
+        llegada_en: None,
+        sentada_en: None,
+        completada_en: None,
+        transferida_desde: None,
+        localizador: None,
+        token_gestion: None,
+        retencion_legal: None,
+        uuid: None,
+        idioma: None,
+        notas_cliente: None,
+        notas_internas: None,
+        recordatorio_enviado_en: None,
+        confirmada_por_cliente_en: None,
+        request_id: None,
+    }
+}
+
+proptest! {
+    #[test]
+    fn accepted_bookings_never_overlap_on_a_table(
+        duracion in duracion(),
+        peticiones in peticiones(),
+    ) {
+        let mut aceptadas: Vec<Ocupacion> = Vec::new();
+
+        for (mesa, inicio, _) in peticiones {
+            let candidata = Ocupacion::new(mesa_id(mesa), inicio, duracion);
+            if !has_conflict(&candidata, &aceptadas) {
+                aceptadas.push(candidata);
+            }
+        }
+
+        for (i, a) in aceptadas.iter().enumerate() {
+            for b in &aceptadas[i + 1..] {
+                prop_assert!(!a.conflicts_with(b), "{:?} se solapa con {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn availability_never_offers_an_occupied_or_unfit_table(
+        mesas in mesas(),
+        duracion in duracion(),
+        peticiones in peticiones(),
+        inicio in instante(),
+        personas in 1i32..=10,
+    ) {
+        let ocupaciones: Vec<Ocupacion> = peticiones
+            .iter()
+            .filter(|(mesa, _, _)| *mesa < mesas.len())
+            .map(|(mesa, inicio, _)| Ocupacion::new(mesa_id(*mesa), *inicio, duracion))
+            .collect();
+
+        let libres = available_tables(&mesas, &ocupaciones, inicio, duracion, personas);
+
+        for mesa in &mesas {
+            let candidata = Ocupacion::new(mesa.id_mesa, inicio, duracion);
+            let ocupada = has_conflict(&candidata, &ocupaciones);
+            let ofrecida = libres.contains(&mesa.id_mesa);
+
+            prop_assert_eq!(ofrecida, mesa.fits(personas) && !ocupada, "mesa {:?}", mesa);
+        }
+    }
+
+    #[test]
+    fn booking_an_offered_table_never_creates_a_conflict(
+        mesas in mesas(),
+        duracion in duracion(),
+        peticiones in peticiones(),
+    ) {
+        let mut aceptadas: Vec<Ocupacion> = Vec::new();
+
+        for (_, inicio, personas) in peticiones {
+            let libres = available_tables(&mesas, &aceptadas, inicio, duracion, personas);
+            if let Some(id_mesa) = libres.first() {
+                let nueva = Ocupacion::new(*id_mesa, inicio, duracion);
+                prop_assert!(!has_conflict(&nueva, &aceptadas));
+                aceptadas.push(nueva);
+            }
+        }
+    }
+
+    #[test]
+    fn overlap_is_symmetric_and_back_to_back_is_free(
+        a in instante(),
+        b in instante(),
+        duracion_a in duracion(),
+        duracion_b in duracion(),
+    ) {
+        let a_fin = a + Duration::minutes(i64::from(duracion_a));
+        let b_fin = b + Duration::minutes(i64::from(duracion_b));
+
+        prop_assert_eq!(overlaps(a, a_fin, b, b_fin), overlaps(b, b_fin, a, a_fin));
+        prop_assert!(!overlaps(a, a_fin, a_fin, a_fin + Duration::minutes(i64::from(duracion_b))));
+        prop_assert!(overlaps(a, a_fin, a, a_fin));
+    }
+
+    #[test]
+    fn finished_reservations_do_not_occupy_tables(
+        inicio in instante(),
+        duracion in duracion(),
+    ) {
+        for estado in [EstadoReserva::Cancelada, EstadoReserva::Completada, EstadoReserva::NoShow] {
+            let terminada = reserva(mesa_id(0), inicio, estado);
+            prop_assert!(Ocupacion::from_reserva(&terminada, duracion).is_none());
+        }
+
+        for estado in EstadoReserva::ACTIVOS {
+            let activa = reserva(mesa_id(0), inicio, estado);
+            let ocupacion = Ocupacion::from_reserva(&activa, duracion).unwrap();
+            prop_assert_eq!(ocupacion.inicio, inicio);
+            prop_assert_eq!(parse_inicio(&activa.fecha, &activa.hora), Some(inicio));
+        }
+    }
+
+    #[test]
+    fn date_prefilter_keeps_every_overlapping_reservation(
+        existente in instante(),
+        buscada in instante(),
+        duracion in duracion(),
+    ) {
+        let ocupacion = Ocupacion::new(mesa_id(0), existente, duracion);
+        let intervalo = Ocupacion::new(mesa_id(0), buscada, duracion);
+
+        if ocupacion.conflicts_with(&intervalo) {
+            let fechas = candidate_dates(intervalo.inicio, intervalo.fin);
+            let fecha = existente.format(FORMATO_FECHA).to_string();
+            prop_assert!(fechas.contains(&fecha), "{} no está en {:?}", fecha, fechas);
+        }
+    }
+}
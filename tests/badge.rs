@@ -0,0 +1,90 @@
+//! Insignia pública con los contadores de un restaurante contra un MongoDB
+//! efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::http::header;
+use actix_web::test::{call_service, read_body, TestRequest};
+use common::{bearer, register_restaurant, send, unwrap_envelope, TestDb};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use serde_json::json;
+
+/// Guarda `cuantas` reservas de dos comensales con `estado`
+async fn insert_reservations(db: &TestDb, id_restaurante: &str, estado: &str, cuantas: usize) {
+    let id_restaurante = ObjectId::parse_str(id_restaurante).unwrap();
+    let reservas: Vec<Document> = (0..cuantas)
+        .map(|_| doc! { "id_restaurante": id_restaurante, "estado": estado, "numero_personas": 2 })
+        .collect();
+    db.repo.reservas().clone_with_type::<Document>().insert_many(reservas).await.unwrap();
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn opted_in_counters_are_published_rounded_and_cached() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let insignia = format!("/public/restaurants/{}/badge", restaurant.id);
+    let (status, _) = send(&app, TestRequest::get().uri(&insignia)).await;
+    assert_eq!(status, 404);
+
+    let ajustes = |contadores: serde_json::Value| bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "insignia": { "contadores": contadores } }));
+    let (status, _) = send(&app, ajustes(json!([]))).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, ajustes(json!(["reservas", "reservas"]))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, ajustes(json!(["reservas", "comensales"]))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Las no verificadas no cuentan y las pocas sentadas no llegan al mínimo
+    insert_reservations(&db, &restaurant.id, "confirmada", 127).await;
+    insert_reservations(&db, &restaurant.id, "sin_confirmar", 20).await;
+    insert_reservations(&db, &restaurant.id, "completada", 4).await;
+
+    let resp = call_service(&app, TestRequest::get().uri(&insignia).to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get(header::CACHE_CONTROL).unwrap(), "public, max-age=900");
+    let etag = resp.headers().get(header::ETAG).unwrap().clone();
+    let body = unwrap_envelope(serde_json::from_slice(&read_body(resp).await).unwrap());
+    assert_eq!(body["contadores"][0], json!({
+        "contador": "reservas",
+        "valor": 130,
+        "texto": "130 reservas gestionadas"
+    }));
+    assert_eq!(body["contadores"][1]["contador"], "comensales");
+    assert!(body["contadores"][1]["valor"].is_null(), "{}", body);
+
+    // Mientras dure la caché, los contadores no cambian
+    insert_reservations(&db, &restaurant.id, "confirmada", 50).await;
+    let resp = call_service(&app, TestRequest::get()
+        .uri(&insignia)
+        .insert_header((header::IF_NONE_MATCH, etag))
+        .to_request()).await;
+    assert_eq!(resp.status(), 304);
+
+    let resp = call_service(&app, TestRequest::get().uri(&format!("{}.svg", insignia)).to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).unwrap(), "image/svg+xml");
+    let svg = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert!(svg.contains(">130 reservas gestionadas</text>"), "{}", svg);
+    assert!(!svg.contains("La Tasca"));
+    let resp = call_service(&app, TestRequest::get().uri(&format!("{}.svg?contador=comensales", insignia)).to_request()).await;
+    let svg = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert!(svg.contains(">comensales atendidos</text>"), "{}", svg);
+    let (status, _) = send(&app, TestRequest::get().uri(&format!("{}.svg?contador=visitas", insignia))).await;
+    assert_eq!(status, 400);
+
+    // Cambiar la configuración se aplica en la siguiente petición
+    let (status, _) = send(&app, ajustes(json!(["reservas"]))).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, TestRequest::get().uri(&format!("{}.svg?contador=comensales", insignia))).await;
+    assert_eq!(status, 404);
+    let (_, body) = send(&app, TestRequest::get().uri(&insignia)).await;
+    assert_eq!(body["contadores"].as_array().unwrap().len(), 1, "{}", body);
+    assert_eq!(body["contadores"][0]["valor"], 180);
+}
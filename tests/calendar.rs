@@ -0,0 +1,126 @@
+//! Reservas en formato iCalendar contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::{call_service, read_body, TestRequest};
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservations_are_published_as_icalendar() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let app = common::init_app_with(&db, notifier.clone(), test_clock()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "zona_horaria": "Marte/Olympus" }))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "zona_horaria": "Europe/Madrid", "direccion": "Calle Mayor 1, Madrid" }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // El email de confirmación lleva la reserva adjunta, en UTC
+    let (status, reserva) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let email = match notifier.outbox().unwrap().sent().last() {
+        Some(SentMessage::Email(email)) => email.clone(),
+        otro => panic!("se esperaba un email: {:?}", otro),
+    };
+    let [adjunto] = &email.adjuntos[..] else {
+        panic!("se esperaba un adjunto: {:?}", email.adjuntos);
+    };
+    assert_eq!(adjunto.nombre, "reserva.ics");
+    let contenido = adjunto.contenido.replace("\r\n ", "");
+    assert!(contenido.contains("\r\nDTSTART:20300615T190000Z\r\n"), "{}", contenido);
+    assert!(contenido.contains("SUMMARY:Reserva en La Tasca"));
+    assert!(contenido.contains("LOCATION:La Tasca\\, Calle Mayor 1\\, Madrid"));
+    assert!(contenido.contains("/cancel\r\n"));
+
+    // El enlace de gestión la descarga igual
+    let linea = email.body.lines().find(|l| l.ends_with("/ics")).expect("el email debe enlazar el .ics");
+    let resp = call_service(&app, TestRequest::get().uri(&linea[linea.find("/public/").unwrap()..]).to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(String::from_utf8(read_body(resp).await.to_vec()).unwrap(), adjunto.contenido);
+    let (status, _) = send(&app, TestRequest::get().uri("/public/reservations/no-existe/ics")).await;
+    assert_eq!(status, 404);
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-16", "14:00"))).await;
+    assert_eq!(status, 200);
+
+    // El panel lo descarga con su token
+    let resp = call_service(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations/calendar.ics")
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "text/calendar; charset=utf-8");
+    let ics = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2, "{}", ics);
+    assert!(ics.contains("\r\nDTSTART:20300616T120000Z\r\n"));
+
+    // Un cliente de calendario, con el token de la URL
+    let (status, _) = send(&app, TestRequest::get().uri("/reservations/calendar.ics")).await;
+    assert_eq!(status, 401);
+    let (status, token) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/calendar-token")).await;
+    assert_eq!(status, 200, "{}", token);
+    let url = token["url"].as_str().unwrap();
+    let ruta = &url[url.find("/reservations/").unwrap()..];
+    let resp = call_service(&app, TestRequest::get().uri(ruta).to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let ics_token = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert_eq!(ics_token.matches("BEGIN:VEVENT").count(), 2);
+
+    // Rotar el token invalida la URL anterior; revocarlo, la nueva
+    let (_, nuevo) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/calendar-token")).await;
+    let (status, _) = send(&app, TestRequest::get().uri(ruta)).await;
+    assert_eq!(status, 401);
+    let nueva_ruta = format!("/reservations/calendar.ics?token={}", nuevo["token"].as_str().unwrap());
+    let (status, _) = send(&app, TestRequest::get().uri(&nueva_ruta)).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri("/restaurants/calendar-token")).await;
+    assert_eq!(status, 204);
+    let (status, _) = send(&app, TestRequest::get().uri(&nueva_ruta)).await;
+    assert_eq!(status, 401);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn the_calendar_feed_starts_today_in_the_restaurant_time_zone() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    for fecha in ["2030-06-01", "2030-06-02"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    // Las 12:00 UTC del 1 de junio son las 02:00 del 2 en Kiritimati
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "zona_horaria": "Pacific/Kiritimati" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let resp = call_service(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations/calendar.ics")
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let ics = String::from_utf8(read_body(resp).await.to_vec()).unwrap();
+    assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1, "{}", ics);
+}
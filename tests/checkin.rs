@@ -0,0 +1,89 @@
+//! Llegada de los clientes (`POST /reservations/{id}/checkin`) contra un
+//! MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::clock::Clock;
+use pispas_reservation::notifications::Notifier;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn check_in_records_the_arrival_and_occupies_the_table() {
+    let db = TestDb::start().await;
+    let clock = test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let mut ids = Vec::new();
+    for hora in ["12:30", "14:30"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, "2030-06-01", hora))).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+    let check_in = |id: &str| bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/checkin", id));
+
+    // Llegan diez minutos tarde
+    clock.advance(Duration::minutes(40));
+    let (status, body) = send(&app, check_in(&ids[0])).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "sentada");
+    assert_eq!(body["llegada_en"], clock.timestamp());
+    assert_eq!(body["retraso_minutos"], 10);
+    let (status, _) = send(&app, check_in(&ids[0])).await;
+    assert_eq!(status, 409);
+
+    // La mesa aparece ocupada en el plano en vivo
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/visual/eta")).await;
+    assert_eq!(body["mesas"].as_array().unwrap().len(), 1, "{}", body);
+    assert_eq!(body["mesas"][0]["id_reserva"], ids[0].as_str());
+    assert_eq!(body["mesas"][0]["sentada_en"], clock.timestamp());
+
+    // El siguiente grupo llega antes de que se vaya el primero
+    clock.advance(Duration::minutes(20));
+    let (status, body) = send(&app, check_in(&ids[1])).await;
+    assert_eq!(status, 409, "{}", body);
+    assert_eq!(body["codigo"], "mesa_ocupada");
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/complete", ids[0]))).await;
+    assert_eq!(status, 200);
+    let (status, body) = send(&app, check_in(&ids[1])).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["retraso_minutos"], -90);
+
+    let (status, _) = send(&app, check_in("507f1f77bcf86cd799439011")).await;
+    assert_eq!(status, 404);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn check_in_delay_uses_the_restaurant_time_zone() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(serde_json::json!({ "zona_horaria": "Europe/Madrid" }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Las 12:00 UTC del reloj de los tests son las 14:00 en Madrid
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-01", "14:15"))).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/checkin", body["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["retraso_minutos"], -15);
+}
@@ -0,0 +1,137 @@
+//! Reclamación de cuentas duplicadas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{send, test_clock, TestDb};
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn owner_recovers_account_with_emailed_code() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let app = common::init_app_with(&db, notifier.clone(), test_clock()).await;
+
+    let registro = json!({
+        "objid_pispas": "PISPAS-001",
+        "name": "La Tasca",
+        "password": "secreto123",
+        "confirmar_automaticamente": false,
+        "email": "dueno@latasca.es"
+    });
+    let (status, _) = send(&app, TestRequest::post().uri("/restaurants/register").set_json(&registro)).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, TestRequest::post().uri("/restaurants/register").set_json(&registro)).await;
+    assert_eq!(status, 409);
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri("/restaurants/claim")
+        .set_json(json!({ "objid_pispas": "PISPAS-001" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["email"], "d***@latasca.es");
+
+    let codigo = match &notifier.outbox().unwrap().sent()[..] {
+        [SentMessage::Email(email)] => {
+            assert_eq!(email.to, "dueno@latasca.es");
+            email.body.lines()
+                .find(|linea| linea.len() == 6 && linea.chars().all(|c| c.is_ascii_digit()))
+                .expect("código en el email")
+                .to_string()
+        }
+        otros => panic!("se esperaba un email: {:?}", otros),
+    };
+    let incorrecto = if codigo == "000000" { "111111" } else { "000000" };
+
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/restaurants/claim/verify")
+        .set_json(json!({ "objid_pispas": "PISPAS-001", "codigo": incorrecto, "password": "nueva-clave" }))).await;
+    assert_eq!(status, 400);
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri("/restaurants/claim/verify")
+        .set_json(json!({ "objid_pispas": "PISPAS-001", "codigo": codigo, "password": "nueva-clave" }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/restaurants/login")
+        .set_json(json!({ "name": "La Tasca", "password": "nueva-clave" }))).await;
+    assert_eq!(status, 200);
+
+    // El código solo vale una vez
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/restaurants/claim/verify")
+        .set_json(json!({ "objid_pispas": "PISPAS-001", "codigo": codigo, "password": "otra-clave" }))).await;
+    assert_eq!(status, 401);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn failed_codes_add_up_across_reissues_until_the_claim_is_locked() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+
+    let (status, _) = send(&app, TestRequest::post().uri("/restaurants/register").set_json(json!({
+        "objid_pispas": "PISPAS-001",
+        "name": "La Tasca",
+        "password": "secreto123",
+        "confirmar_automaticamente": false,
+        "email": "dueno@latasca.es"
+    }))).await;
+    assert_eq!(status, 200);
+
+    let pedir = || TestRequest::post()
+        .uri("/restaurants/claim")
+        .set_json(json!({ "objid_pispas": "PISPAS-001" }));
+    let verificar = |codigo: &str| TestRequest::post()
+        .uri("/restaurants/claim/verify")
+        .set_json(json!({ "objid_pispas": "PISPAS-001", "codigo": codigo, "password": "nueva-clave" }));
+    let ultimo_codigo = || match notifier.outbox().unwrap().sent().last() {
+        Some(SentMessage::Email(email)) => email.body.lines()
+            .find(|linea| linea.len() == 6 && linea.chars().all(|c| c.is_ascii_digit()))
+            .expect("código en el email")
+            .to_string(),
+        otro => panic!("se esperaba un email: {:?}", otro),
+    };
+
+    let (status, body) = send(&app, pedir()).await;
+    assert_eq!(status, 200, "{}", body);
+    // Un código nuevo solo cada minuto
+    let (status, _) = send(&app, pedir()).await;
+    assert_eq!(status, 429);
+
+    // Cinco fallos por código, sin que pedir otro ponga la cuenta a cero
+    let fallar_cinco = || async {
+        let codigo = ultimo_codigo();
+        let incorrecto = if codigo == "000000" { "111111" } else { "000000" };
+        for _ in 0..5 {
+            let (status, _) = send(&app, verificar(incorrecto)).await;
+            assert_eq!(status, 400);
+        }
+    };
+    fallar_cinco().await;
+    let (status, _) = send(&app, verificar(&ultimo_codigo())).await;
+    assert_eq!(status, 401, "agotados los intentos del código");
+    clock.advance(chrono::Duration::minutes(1));
+    let (status, body) = send(&app, pedir()).await;
+    assert_eq!(status, 200, "{}", body);
+    fallar_cinco().await;
+
+    // Tras diez fallos, ni pedir otro código ni el correcto sirven
+    clock.advance(chrono::Duration::minutes(1));
+    let (status, body) = send(&app, pedir()).await;
+    assert_eq!(status, 423, "{}", body);
+    assert_eq!(body["codigo"], "reclamacion_bloqueada");
+    let (status, _) = send(&app, verificar(&ultimo_codigo())).await;
+    assert_eq!(status, 423);
+
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/restaurants/login")
+        .set_json(json!({ "name": "La Tasca", "password": "secreto123" }))).await;
+    assert_eq!(status, 200);
+}
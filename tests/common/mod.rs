@@ -0,0 +1,192 @@
+//! # Utilidades para los tests de integración
+//!
+//! Arranca un MongoDB efímero en un contenedor (testcontainers) y monta la
+//! aplicación con [`pispas_reservation::app_config`], igual que el binario.
+//!
+//! Si la variable `TEST_MONGODB_URI` está definida se usa ese servidor en
+//! lugar de levantar un contenedor. Cada [`TestApp`] usa una base de datos
+//! con nombre aleatorio para que los tests no se pisen entre sí.
+//!
+//! Los tests que lo usan están marcados con `#[ignore]` porque necesitan
+//! Docker (o un MongoDB accesible); se ejecutan con:
+//!
+//! ```bash
+//! cargo test -- --ignored
+//! ```
+
+#![allow(dead_code)]
+
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::{test, App};
+use chrono::{TimeZone, Utc};
+use pispas_reservation::{app_config, clock::FixedClock, db::MongoRepo, notifications::Notifier};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use testcontainers_modules::mongo::Mongo;
+use testcontainers_modules::testcontainers::runners::AsyncRunner;
+use testcontainers_modules::testcontainers::ContainerAsync;
+
+/// MongoDB efímero para un test
+pub struct TestDb {
+    pub repo: MongoRepo,
+    /// Contenedor en ejecución; se detiene al soltar el `TestDb`
+    _container: Option<ContainerAsync<Mongo>>,
+}
+
+impl TestDb {
+    /// Levanta MongoDB (o usa `TEST_MONGODB_URI`) y crea los índices
+    pub async fn start() -> TestDb {
+        let (uri, container) = match std::env::var("TEST_MONGODB_URI") {
+            Ok(uri) => (uri, None),
+            Err(_) => {
+                let container = Mongo::default()
+                    .start()
+                    .await
+                    .expect("No se pudo arrancar el contenedor de MongoDB (¿Docker disponible?)");
+                let port = container
+                    .get_host_port_ipv4(27017)
+                    .await
+                    .expect("Puerto de MongoDB no disponible");
+                (format!("mongodb://127.0.0.1:{}", port), Some(container))
+            }
+        };
+
+        let database = format!("pispas_test_{}", uuid::Uuid::new_v4().simple());
+        let repo = MongoRepo::connect(&uri, &database)
+            .await
+            .expect("No se pudo conectar a MongoDB");
+        repo.create_indexes().await.expect("No se pudieron crear los índices");
+
+        TestDb { repo, _container: container }
+    }
+}
+
+/// Reloj congelado en 2030-06-01 12:00 UTC, anterior a las fechas de reserva de los tests
+pub fn test_clock() -> Arc<FixedClock> {
+    Arc::new(FixedClock::new(Utc.with_ymd_and_hms(2030, 6, 1, 12, 0, 0).unwrap()))
+}
+
+/// Inicializa el servicio HTTP de la aplicación sobre un `TestDb`
+pub async fn init_app(
+    db: &TestDb,
+) -> impl Service<Request, Response = ServiceResponse, Error = actix_web::Error> {
+    init_app_with(db, Notifier::memory(), test_clock()).await
+}
+
+/// Igual que [`init_app`] pero con un notificador y un reloj concretos
+pub async fn init_app_with(
+    db: &TestDb,
+    notifier: Notifier,
+    clock: Arc<FixedClock>,
+) -> impl Service<Request, Response = ServiceResponse, Error = actix_web::Error> {
+    test::init_service(
+        App::new().configure(app_config(db.repo.clone(), notifier, clock))
+    ).await
+}
+
+/// Envía una petición y devuelve el status y el cuerpo JSON (o `Null`)
+///
+/// El cuerpo se saca del sobre (ver [`unwrap_envelope`]); para ver el sobre
+/// entero, [`send_raw`].
+pub async fn send<S>(app: &S, req: test::TestRequest) -> (u16, Value)
+where
+    S: Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let (status, json) = send_raw(app, req).await;
+    (status, unwrap_envelope(json))
+}
+
+/// Envía una petición y devuelve el status y el cuerpo JSON tal cual (o `Null`)
+pub async fn send_raw<S>(app: &S, req: test::TestRequest) -> (u16, Value)
+where
+    S: Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status().as_u16();
+    let body = test::read_body(resp).await;
+    let json = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    (status, json)
+}
+
+/// Cuerpo de una respuesta dentro del sobre: `error` en los errores y
+/// `data` en las demás; lo que no es un sobre se devuelve tal cual
+pub fn unwrap_envelope(json: Value) -> Value {
+    let es_sobre = json.get("meta").is_some() && json.get("warnings").is_some();
+    match json {
+        Value::Object(mut sobre) if es_sobre => sobre.remove("error")
+            .or_else(|| sobre.remove("data"))
+            .unwrap_or(Value::Null),
+        json => json,
+    }
+}
+
+/// Añade el header Authorization con un token Bearer
+pub fn bearer(req: test::TestRequest, token: &str) -> test::TestRequest {
+    req.insert_header(("Authorization", format!("Bearer {}", token)))
+}
+
+/// Restaurante registrado durante un test
+pub struct TestRestaurant {
+    pub id: String,
+    pub token: String,
+}
+
+/// Registra un restaurante y devuelve su ID y token
+pub async fn register_restaurant<S>(app: &S, name: &str) -> TestRestaurant
+where
+    S: Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let (status, body) = send(app, test::TestRequest::post()
+        .uri("/restaurants/register")
+        .set_json(json!({
+            "objid_pispas": format!("objid-{}", name),
+            "name": name,
+            "password": "secreto123",
+            "confirmar_automaticamente": false
+        }))).await;
+    assert_eq!(status, 200, "registro fallido: {}", body);
+
+    TestRestaurant {
+        id: body["id"].as_str().unwrap().to_string(),
+        token: body["access_token"].as_str().unwrap().to_string(),
+    }
+}
+
+/// Crea una mesa de 2 a 4 personas y devuelve su ID
+pub async fn create_table<S>(app: &S, restaurant: &TestRestaurant, nombre: &str) -> String
+where
+    S: Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+{
+    let (status, body) = send(app, bearer(test::TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .set_json(json!({
+            "id_restaurante": restaurant.id,
+            "tipo": "mesa",
+            "nombre": nombre,
+            "pos_x": 100.0,
+            "pos_y": 100.0,
+            "size_x": 80.0,
+            "size_y": 80.0,
+            "forma": "cuadrado",
+            "reservable": true,
+            "min_personas": 2,
+            "max_personas": 4
+        }))).await;
+    assert_eq!(status, 200, "creación de mesa fallida: {}", body);
+
+    body["id"].as_str().unwrap().to_string()
+}
+
+/// Datos de reserva válidos para una mesa
+pub fn reservation_body(id_mesa: &str, fecha: &str, hora: &str) -> Value {
+    json!({
+        "id_mesa": id_mesa,
+        "nombre_cliente": "Juan Pérez",
+        "email_cliente": "juan@email.com",
+        "telefono_cliente": "+34 600 000 000",
+        "numero_personas": 2,
+        "fecha": fecha,
+        "hora": hora
+    })
+}
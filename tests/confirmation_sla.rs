@@ -0,0 +1,91 @@
+//! Tiempo de confirmación de las reservas pendientes contra un MongoDB
+//! efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::jobs::alerts::{self, ConfigMonitor};
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn confirmation_latency_is_reported_and_alerts_when_slow() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mut ids = Vec::new();
+    for nombre in ["Mesa 1", "Mesa 2", "Mesa 3", "Mesa 4"] {
+        let mesa = create_table(&app, &restaurant, nombre).await;
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, "2030-06-02", "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+
+    // Esperan 10, 40 y 100 minutos; la última sigue pendiente
+    for (id, minutos) in ids.iter().zip([10, 30, 60]) {
+        clock.advance(Duration::minutes(minutos));
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri(&format!("/reservations/{}/confirm", id))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/stats/confirmations?desde=2030-06-01&hasta=2030-06-01")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["totales"]["confirmadas"], 3, "{}", body);
+    assert_eq!(body["totales"]["p50_minutos"], 40);
+    assert_eq!(body["totales"]["p90_minutos"], 100);
+    assert_eq!(body["totales"]["max_minutos"], 100);
+    assert_eq!(body["dias"][0]["fecha"], "2030-06-01");
+
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/stats/confirmations?desde=2030-05-01&hasta=2030-05-31")).await;
+    assert!(body["totales"].is_null(), "{}", body);
+    assert_eq!(body["dias"], json!([]));
+
+    // Alerta si la mediana del último día pasa de 30 minutos
+    let ajustes = |max: u32| bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({
+            "email_alertas": "encargado@latasca.es",
+            "alertas": [{ "tipo": "confirmacion_lenta", "max_mediana_minutos": max, "ventana_horas": 24 }]
+        }));
+    let (status, _) = send(&app, ajustes(0)).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, ajustes(30)).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let config = ConfigMonitor {
+        intervalo: std::time::Duration::from_secs(300),
+        silencio_segundos: 3600,
+        email_ops: None,
+        latencia_bd_ms: None,
+        webhook_minutos: None,
+    };
+    let outbox = notifier.outbox().unwrap();
+    outbox.clear();
+    let enviadas = alerts::run(&db.repo, &notifier, clock.as_ref(), &config).await.unwrap();
+    assert_eq!(enviadas.len(), 1, "{:?}", enviadas);
+    match &outbox.sent()[..] {
+        [SentMessage::Email(email)] => {
+            assert_eq!(email.to, "encargado@latasca.es");
+            assert!(email.body.contains("mediana de 40 minutos"), "{}", email.body);
+        }
+        otros => panic!("se esperaba un email: {:?}", otros),
+    }
+
+    // Pasada la ventana ya no hay confirmaciones recientes
+    clock.advance(Duration::hours(25));
+    let enviadas = alerts::run(&db.repo, &notifier, clock.as_ref(), &config).await.unwrap();
+    assert!(enviadas.is_empty(), "{:?}", enviadas);
+}
@@ -0,0 +1,128 @@
+//! CRM de clientes contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservations_feed_customers_and_duplicates_can_be_merged() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let reservas = [
+        ("2030-06-15", "juan@email.com"),
+        ("2030-06-16", "JUAN@email.com "),
+        ("2030-06-17", "juanp@trabajo.com"),
+    ];
+    let mut ids = Vec::new();
+    for (fecha, email) in reservas {
+        let mut body = reservation_body(&id_mesa, fecha, "21:00");
+        body["email_cliente"] = json!(email);
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(body)).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+
+    // Cancelar una reserva descuenta la visita
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", ids[1]))).await;
+    assert_eq!(status, 200);
+
+    let (status, clientes) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/customers")).await;
+    assert_eq!(status, 200);
+    let clientes = clientes.as_array().unwrap().clone();
+    assert_eq!(clientes.len(), 2, "el email se normaliza: {:?}", clientes);
+
+    let destino = clientes.iter().find(|c| c["emails"][0] == "juan@email.com").unwrap();
+    let origen = clientes.iter().find(|c| c["emails"][0] == "juanp@trabajo.com").unwrap();
+    assert_eq!(destino["visitas"], 1);
+    assert_eq!(origen["visitas"], 1);
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/customers/merge")
+        .set_json(json!({ "id_destino": destino["id"], "id_origen": origen["id"] }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["reservas_movidas"], 1);
+    assert_eq!(body["cliente"]["visitas"], 2);
+    assert_eq!(body["cliente"]["emails"], json!(["juan@email.com", "juanp@trabajo.com"]));
+
+    let (_, clientes) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/customers")).await;
+    assert_eq!(clientes.as_array().unwrap().len(), 1);
+
+    let (status, fusiones) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/customers/merges")).await;
+    assert_eq!(status, 200);
+    assert_eq!(fusiones[0]["origen"]["id"], origen["id"]);
+    assert_eq!(fusiones[0]["reservas_movidas"], 1);
+
+    // Un cliente no se puede fusionar consigo mismo
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/customers/merge")
+        .set_json(json!({ "id_destino": destino["id"], "id_origen": destino["id"] }))).await;
+    assert_eq!(status, 400);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn favorite_table_is_learned_and_can_be_pinned_by_staff() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa_1 = create_table(&app, &restaurant, "Mesa 1").await;
+    let mesa_2 = create_table(&app, &restaurant, "Mesa 2").await;
+
+    for fecha in ["2030-06-10", "2030-06-11"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa_2, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    let (_, clientes) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/customers")).await;
+    let cliente = clientes[0].clone();
+    assert_eq!(cliente["mesa_preferida"], json!(mesa_2));
+    assert_eq!(cliente["preferencia_manual"], false);
+
+    let email = cliente["emails"][0].as_str().unwrap();
+    let (_, mesas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables/available?fecha=2030-06-15&hora=21:00&personas=2&email={}", email))).await;
+    assert_eq!(mesas[0]["id"], json!(mesa_2), "la preferida libre va la primera");
+
+    let (status, cliente) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/customers/{}/preference", cliente["id"].as_str().unwrap()))
+        .set_json(json!({ "id_mesa": mesa_1 }))).await;
+    assert_eq!(status, 200, "{}", cliente);
+    assert_eq!(cliente["mesa_preferida"], json!(mesa_1));
+    assert_eq!(cliente["preferencia_manual"], true);
+
+    // El historial ya no cambia una preferencia fijada a mano
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa_2, "2030-06-12", "21:00"))).await;
+    assert_eq!(status, 200);
+
+    let (_, mesas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables/available?fecha=2030-06-15&hora=21:00&personas=2&email={}", email))).await;
+    assert_eq!(mesas[0]["id"], json!(mesa_1));
+
+    // Borrarla vuelve a aprenderla del historial
+    let (_, cliente) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/customers/{}/preference", cliente["id"].as_str().unwrap()))
+        .set_json(json!({ "id_mesa": null }))).await;
+    assert_eq!(cliente["mesa_preferida"], json!(mesa_2));
+    assert_eq!(cliente["preferencia_manual"], false);
+}
@@ -0,0 +1,189 @@
+//! Depósitos divididos: reparto puro y flujo de pago contra MongoDB
+//!
+//! Los tests HTTP necesitan MongoDB; ver `tests/common/mod.rs`.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::api::webhook_auth::sign;
+use pispas_reservation::clock::Clock;
+use pispas_reservation::db::Deposito;
+use serde_json::json;
+
+fn deposito(total: i64, partes: u32, umbral: u32) -> Deposito {
+    Deposito::new(total, partes, umbral, |i| format!("parte-{}", i), 0)
+}
+
+#[test]
+fn split_spreads_the_remainder_and_always_adds_up() {
+    for (total, partes) in [(1000, 3), (1000, 7), (999, 1), (5, 5), (12001, 8)] {
+        let deposito = deposito(total, partes, 100);
+        let importes: Vec<i64> = deposito.partes.iter().map(|p| p.importe_centimos).collect();
+
+        assert_eq!(importes.len(), partes as usize);
+        assert_eq!(importes.iter().sum::<i64>(), total);
+        assert!(importes.windows(2).all(|par| par[0] - par[1] <= 1 && par[0] >= par[1]), "{:?}", importes);
+    }
+}
+
+#[test]
+fn state_and_threshold_follow_paid_parts() {
+    let mut deposito = deposito(1000, 4, 50);
+    assert_eq!(deposito.umbral_centimos, 500);
+    assert_eq!(deposito.estado(), "pendiente");
+
+    deposito.partes[0].pagada_en = Some(1);
+    assert_eq!(deposito.estado(), "parcial");
+    assert!(!deposito.umbral_alcanzado());
+
+    deposito.partes[1].pagada_en = Some(2);
+    assert_eq!(deposito.pagado_centimos(), 500);
+    assert!(deposito.umbral_alcanzado());
+
+    deposito.partes[2].pagada_en = Some(3);
+    deposito.partes[3].pagada_en = Some(4);
+    assert_eq!(deposito.estado(), "completado");
+
+    // El umbral se redondea hacia arriba
+    assert_eq!(self::deposito(999, 3, 50).umbral_centimos, 500);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_is_confirmed_once_paid_parts_reach_the_threshold() {
+    std::env::set_var("PAYMENT_WEBHOOK_SECRET", "secreto-pasarela");
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let id = reserva["id"].as_str().unwrap().to_string();
+
+    let (status, deposito) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/deposit", id))
+        .set_json(json!({ "importe_total_centimos": 3000, "partes": 3, "umbral_porcentaje": 60 }))).await;
+    assert_eq!(status, 200, "{}", deposito);
+    assert_eq!(deposito["umbral_centimos"], 1800);
+    let codigos: Vec<String> = deposito["partes"].as_array().unwrap()
+        .iter()
+        .map(|parte| parte["codigo"].as_str().unwrap().to_string())
+        .collect();
+    assert!(deposito["partes"][0]["enlace"].as_str().unwrap().ends_with(&codigos[0]));
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/confirm", id))).await;
+    assert_eq!(status, 409, "sin pagos no se puede confirmar");
+
+    let (status, parte) = send(&app, TestRequest::get()
+        .uri(&format!("/public/payments/{}", codigos[0]))).await;
+    assert_eq!(status, 200, "{}", parte);
+    assert_eq!(parte["importe_centimos"], 1000);
+    assert_eq!(parte["pagada"], false);
+
+    let now = test_clock().timestamp();
+    let firmado = |secreto: &str, t: i64, codigo: &str| {
+        let cuerpo = json!({ "codigo": codigo }).to_string();
+        TestRequest::post()
+            .uri("/payments/webhook")
+            .insert_header(("X-Webhook-Signature", format!("t={},v1={}", t, sign(secreto, t, cuerpo.as_bytes()))))
+            .insert_header(("Content-Type", "application/json"))
+            .set_payload(cuerpo)
+    };
+    let pagar = |t: i64, codigo: &str| firmado("secreto-pasarela", t, codigo);
+
+    let (status, _) = send(&app, firmado("otro", now, &codigos[0])).await;
+    assert_eq!(status, 401, "secreto incorrecto");
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/payments/webhook")
+        .set_json(json!({ "codigo": codigos[0] }))).await;
+    assert_eq!(status, 401, "sin firma");
+    let (status, _) = send(&app, pagar(now - 3600, &codigos[0])).await;
+    assert_eq!(status, 401, "firma caducada");
+
+    let (status, pago) = send(&app, pagar(now, &codigos[0])).await;
+    assert_eq!(status, 200, "{}", pago);
+    assert_eq!(pago["estado_deposito"], "parcial");
+    assert_eq!(pago["estado_reserva"], "pendiente");
+
+    // La misma notificación capturada no se puede reenviar
+    let (status, _) = send(&app, pagar(now, &codigos[0])).await;
+    assert_eq!(status, 401, "repetición");
+
+    // Un reintento firmado de nuevo no cuenta dos veces
+    let (status, pago) = send(&app, pagar(now + 1, &codigos[0])).await;
+    assert_eq!(status, 200, "{}", pago);
+    assert_eq!(pago["pagado_centimos"], 1000);
+
+    let (_, pago) = send(&app, pagar(now, &codigos[1])).await;
+    assert_eq!(pago["pagado_centimos"], 2000);
+    assert_eq!(pago["estado_reserva"], "confirmada");
+
+    // Con pagos hechos el depósito ya no se puede rehacer
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/deposit", id))
+        .set_json(json!({ "importe_total_centimos": 6000 }))).await;
+    assert_eq!(status, 409);
+
+    let (status, deposito) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/reservations/{}/deposit", id))).await;
+    assert_eq!(status, 200);
+    assert_eq!(deposito["estado"], "parcial");
+    assert_eq!(deposito["partes"][1]["pagada"], true);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn large_parties_pay_in_advance_before_confirming() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "deposito": { "min_personas": 2, "importe_por_persona_centimos": 1500 } }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let id = reserva["id"].as_str().unwrap().to_string();
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/confirm", id))).await;
+    assert_eq!(status, 409, "sin pagar no se puede confirmar");
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/payment/refund", id))).await;
+    assert_eq!(status, 409, "no hay nada cobrado que reembolsar");
+
+    let (status, pago) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/payment/paid", id))
+        .set_json(json!({ "referencia": "TPV-42" }))).await;
+    assert_eq!(status, 200, "{}", pago);
+    assert_eq!(pago["estado_reserva"], "confirmada");
+    assert_eq!(pago["pago"]["importe_centimos"], 3000);
+    assert_eq!(pago["pago"]["estado"], "pagado");
+    assert_eq!(pago["pago"]["proveedor"], "manual");
+    assert_eq!(pago["pago"]["referencia"], "TPV-42");
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/payment/paid", id))).await;
+    assert_eq!(status, 409, "ya está pagado");
+
+    let (status, pago) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/payment/refund", id))).await;
+    assert_eq!(status, 200, "{}", pago);
+    assert_eq!(pago["pago"]["estado"], "reembolsado");
+    assert_eq!(pago["estado_reserva"], "confirmada");
+
+    let (_, reservas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-15")).await;
+    assert_eq!(reservas[0]["pago"]["estado"], "reembolsado");
+}
@@ -0,0 +1,74 @@
+//! Sobre de las respuestas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, send_raw, TestDb};
+use pispas_reservation::api::envelope::{TIPO_SIN_SOBRE, TIPO_SOBRE};
+
+const ADMIN_TOKEN: &str = "admin-test-token";
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn responses_are_wrapped_unless_a_legacy_client_opts_out() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    for fecha in ["2030-06-15", "2030-06-16", "2030-06-17"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+    let listar = || bearer(TestRequest::get(), &restaurant.token).uri("/reservations?page=2&limit=2");
+
+    // Sin pedir nada, el sobre
+    let resp = actix_web::test::call_service(&app, listar().to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("x-total-count").unwrap(), "3");
+    assert!(resp.headers().get("deprecation").is_none());
+    let request_id = resp.headers().get("x-request-id").unwrap().to_str().unwrap().to_string();
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 1, "{}", body);
+    assert_eq!(body["meta"]["request_id"], request_id.as_str());
+    assert_eq!(body["meta"]["paginacion"]["total"], 3);
+    assert_eq!(body["meta"]["paginacion"]["pagina"], 2);
+    assert_eq!(body["meta"]["paginacion"]["limite"], 2);
+    assert_eq!(body["warnings"].as_array().unwrap().len(), 0);
+
+    // Pedirlo explícitamente da lo mismo
+    let (status, body) = send_raw(&app, listar().insert_header(("Accept", TIPO_SOBRE))).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1, "{}", body);
+
+    // Los clientes antiguos reciben la lista tal cual, avisados de la retirada
+    let resp = actix_web::test::call_service(&app, listar()
+        .insert_header(("Accept", TIPO_SIN_SOBRE))
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("deprecation").unwrap(), "@1792108800");
+    assert_eq!(resp.headers().get("sunset").unwrap(), "Fri, 16 Apr 2027 00:00:00 GMT");
+    let body: serde_json::Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(body.as_array().unwrap().len(), 1, "{}", body);
+
+    // Los errores conservan su cuerpo en `error`
+    let (status, body) = send_raw(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?page=0")).await;
+    assert_eq!(status, 400);
+    assert!(body["data"].is_null(), "{}", body);
+    assert!(body["error"]["error"].is_string(), "{}", body);
+    assert!(body["meta"].get("paginacion").is_none());
+
+    // Las rutas obsoletas lo avisan en `warnings`
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let (status, body) = send_raw(&app, bearer(TestRequest::get(), ADMIN_TOKEN)
+        .uri("/restaurants/all")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert!(body["data"].is_array(), "{}", body);
+    let aviso = body["warnings"][0].as_str().unwrap();
+    assert!(aviso.contains("obsoleta") && aviso.contains("/admin/restaurants"), "{}", aviso);
+}
@@ -0,0 +1,28 @@
+//! Código HTTP de cada [`AppError`]
+
+use actix_web::ResponseError;
+use pispas_reservation::api::AppError;
+
+#[test]
+fn every_error_variant_maps_to_its_status() {
+    let casos = [
+        (AppError::Validation("fecha".to_string()), 400),
+        (AppError::validation_field("fecha", "inválida"), 400),
+        (AppError::Unauthorized("token".to_string()), 401),
+        (AppError::unauthorized_operation("login", "clave"), 401),
+        (AppError::forbidden("cuenta_suspendida", "suspendida"), 403),
+        (AppError::NotFound("reserva".to_string()), 404),
+        (AppError::not_found_id("Reserva", "1"), 404),
+        (AppError::Conflict("mesa ocupada".to_string()), 409),
+        (AppError::conflict_with_code("cancelacion_tardia", "tarde"), 409),
+        (AppError::locked("cuenta_bloqueada", "bloqueada", 60), 423),
+        (AppError::too_many_requests("despacio", 5), 429),
+        (AppError::Internal("fallo".to_string()), 500),
+        (AppError::internal_trace("fallo", Some("traza".to_string())), 500),
+    ];
+
+    for (error, status) in casos {
+        assert_eq!(error.error_response().status().as_u16(), status, "{}", error);
+        assert_eq!(error.status_code().as_u16(), status, "{}", error);
+    }
+}
@@ -0,0 +1,84 @@
+//! Previsión de mesas libres (`GET /visual/eta`) contra MongoDB
+//!
+//! Los tests HTTP necesitan MongoDB; ver `tests/common/mod.rs`.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, send, test_clock, TestDb};
+use pispas_reservation::clock::Clock;
+use pispas_reservation::notifications::Notifier;
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn eta_learns_turn_times_from_completed_tables() {
+    let db = TestDb::start().await;
+    let clock = test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+    let restaurant = register_restaurant(&app, "Casa Lola").await;
+    let mesa_1 = create_table(&app, &restaurant, "Mesa 1").await;
+    let mesa_2 = create_table(&app, &restaurant, "Mesa 2").await;
+
+    // Cinco parejas que tardan una hora en comer
+    for vuelta in 0..5 {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations/walk-in")
+            .set_json(json!({ "id_mesa": mesa_1, "numero_personas": 2 }))).await;
+        assert_eq!(status, 200, "{}", body);
+        let id = body["id"].as_str().unwrap().to_string();
+
+        if vuelta == 0 {
+            // Sin historial: la duración configurada
+            let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+                .uri("/visual/eta")).await;
+            assert_eq!(status, 200, "{}", body);
+            assert_eq!(body["mesas"][0]["nombre_mesa"], "Mesa 1");
+            assert_eq!(body["mesas"][0]["duracion_estimada_minutos"], 90);
+            assert_eq!(body["mesas"][0]["muestras"], 0);
+            assert_eq!(body["mesas"][0]["minutos_restantes"], 90);
+        }
+
+        clock.advance(Duration::minutes(60));
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri(&format!("/reservations/{}/complete", id))).await;
+        assert_eq!(status, 200, "{}", body);
+        clock.advance(Duration::minutes(40));
+    }
+
+    // Un grupo de 4 en la mesa 2 y, diez minutos después, una pareja en la 1
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations/walk-in")
+        .set_json(json!({ "id_mesa": mesa_2, "numero_personas": 4 }))).await;
+    assert_eq!(status, 200, "{}", body);
+    clock.advance(Duration::minutes(10));
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations/walk-in")
+        .set_json(json!({ "id_mesa": mesa_1, "numero_personas": 2 }))).await;
+    assert_eq!(status, 200, "{}", body);
+    clock.advance(Duration::minutes(15));
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/visual/eta")).await;
+    assert_eq!(status, 200, "{}", body);
+    let mesas = body["mesas"].as_array().unwrap();
+    assert_eq!(mesas.len(), 2);
+    // Sin grupos de 4 en el historial, cuenta toda la rotación
+    assert_eq!(mesas[0]["id_mesa"], mesa_2);
+    assert_eq!(mesas[0]["duracion_estimada_minutos"], 60);
+    assert_eq!(mesas[0]["minutos_restantes"], 35);
+    assert_eq!(mesas[1]["id_mesa"], mesa_1);
+    assert_eq!(mesas[1]["muestras"], 5);
+    assert_eq!(mesas[1]["minutos_restantes"], 45);
+    assert_eq!(mesas[1]["libre_en"], clock.timestamp() + 45 * 60);
+    assert_eq!(mesas[1]["retrasada"], false);
+
+    // Pasada la duración estimada, las mesas se dan como libres ya
+    clock.advance(Duration::minutes(60));
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/visual/eta")).await;
+    assert_eq!(body["mesas"][0]["minutos_restantes"], 0);
+    assert_eq!(body["mesas"][1]["retrasada"], true);
+    assert_eq!(body["mesas"][1]["libre_en"], clock.timestamp());
+}
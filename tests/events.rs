@@ -0,0 +1,60 @@
+//! Eventos de dominio: exportación JSON Lines y envío continuo
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::{call_service, read_body, TestRequest};
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::jobs::event_shipping::{self, DestinoDirectorio};
+use serde_json::Value;
+
+const ADMIN_TOKEN: &str = "admin-test-token";
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_lifecycle_is_exported_and_shipped_once() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (_, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    let id = reserva["id"].as_str().unwrap();
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", id))).await;
+    assert_eq!(status, 200);
+
+    let resp = call_service(&app, bearer(TestRequest::get(), ADMIN_TOKEN)
+        .uri("/admin/events/export?desde=0")
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/x-ndjson");
+    let body = read_body(resp).await;
+    let eventos: Vec<Value> = std::str::from_utf8(&body).unwrap()
+        .lines()
+        .map(|linea| serde_json::from_str(linea).unwrap())
+        .collect();
+    let tipos: Vec<&str> = eventos.iter().map(|e| e["tipo"].as_str().unwrap()).collect();
+    assert_eq!(tipos, ["restaurante_registrado", "reserva_creada", "reserva_cancelada"]);
+    assert_eq!(eventos[2]["id_entidad"], id);
+    assert_eq!(eventos[2]["datos"]["estado_anterior"], "pendiente");
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/admin/events/export")).await;
+    assert_eq!(status, 401, "el token de un restaurante no es de administración");
+
+    let dir = std::env::temp_dir().join(format!("eventos-{}", uuid::Uuid::new_v4()));
+    let destino = DestinoDirectorio::new(&dir);
+    let clock = test_clock();
+    assert_eq!(event_shipping::run(&db.repo, &destino, clock.as_ref()).await.unwrap(), 3);
+    assert_eq!(event_shipping::run(&db.repo, &destino, clock.as_ref()).await.unwrap(), 0);
+
+    let lotes: Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().path()).collect();
+    assert_eq!(lotes.len(), 1);
+    assert_eq!(std::fs::read_to_string(&lotes[0]).unwrap(), std::str::from_utf8(&body).unwrap());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,90 @@
+//! Plantas del plano contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn tables_can_be_placed_and_filtered_by_floor() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, planta) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/floors")
+        .set_json(json!({ "nombre": "Primera planta", "orden": 1 }))).await;
+    assert_eq!(status, 200, "{}", planta);
+    let id_planta = planta["id"].as_str().unwrap().to_string();
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .set_json(json!({
+            "id_restaurante": restaurant.id,
+            "tipo": "mesa",
+            "nombre": "Salón 1",
+            "pos_x": 100.0,
+            "pos_y": 100.0,
+            "size_x": 80.0,
+            "size_y": 80.0,
+            "forma": "cuadrado",
+            "reservable": true,
+            "min_personas": 1,
+            "max_personas": 4,
+            "id_planta": id_planta
+        }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (_, mesas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables?id_restaurante={}&planta={}", restaurant.id, id_planta))).await;
+    assert_eq!(mesas.as_array().unwrap().len(), 1);
+    assert_eq!(mesas[0]["nombre"], "Salón 1");
+    assert_eq!(mesas[0]["id_planta"], json!(id_planta));
+
+    let (_, mesas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables?id_restaurante={}", restaurant.id))).await;
+    assert_eq!(mesas.as_array().unwrap().len(), 2, "sin planta se listan todas");
+
+    let (_, libres) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables/available?fecha=2030-06-15&hora=21:00&personas=2&planta={}", id_planta))).await;
+    assert_eq!(libres.as_array().unwrap().len(), 1);
+
+    // Una planta con mesas no se puede eliminar
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/floors/{}", id_planta))).await;
+    assert_eq!(status, 409);
+
+    let (status, aviso) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/tables/clear?id_restaurante={}&planta={}", restaurant.id, id_planta))).await;
+    assert_eq!(status, 428);
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!(
+            "/tables/clear?id_restaurante={}&planta={}&confirmacion={}",
+            restaurant.id, id_planta, aviso["confirmacion"].as_str().unwrap(),
+        ))).await;
+    assert_eq!(status, 200);
+
+    let (_, mesas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables?id_restaurante={}", restaurant.id))).await;
+    assert_eq!(mesas.as_array().unwrap().len(), 1, "vaciar una planta no toca las demás");
+
+    let (status, planta) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/floors/{}", id_planta))
+        .set_json(json!({ "nombre": "Altillo", "orden": 2 }))).await;
+    assert_eq!(status, 200);
+    assert_eq!(planta["nombre"], "Altillo");
+
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/floors/{}", id_planta))).await;
+    assert_eq!(status, 204);
+
+    let (_, plantas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/floors")).await;
+    assert_eq!(plantas, json!([]));
+}
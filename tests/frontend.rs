@@ -0,0 +1,79 @@
+//! Servicio del frontend como SPA
+//!
+//! No necesitan MongoDB: montan solo [`pispas_reservation::frontend`] sobre
+//! el directorio `static/` del repositorio.
+
+use actix_web::test::{self, TestRequest};
+use actix_web::App;
+use pispas_reservation::config::ConfigFrontend;
+use pispas_reservation::frontend;
+use std::path::PathBuf;
+
+fn config(montaje: &str) -> ConfigFrontend {
+    ConfigFrontend {
+        montaje: montaje.to_string(),
+        directorio: Some(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/static"))),
+    }
+}
+
+#[actix_web::test]
+async fn unknown_routes_fall_back_to_the_index() {
+    let config = config("/app");
+    let app = test::init_service(App::new().configure(|cfg| frontend::configure(cfg, &config))).await;
+    let index = std::fs::read(config.directorio.as_ref().unwrap().join("index.html")).unwrap();
+
+    let resp = test::call_service(&app, TestRequest::get().uri("/").to_request()).await;
+    assert_eq!(resp.status(), 308);
+    assert_eq!(resp.headers().get("location").unwrap(), "/app/");
+
+    for ruta in ["/app/", "/app/reservas/42", "/app/index.html"] {
+        let resp = test::call_service(&app, TestRequest::get().uri(ruta).to_request()).await;
+        assert_eq!(resp.status(), 200, "{}", ruta);
+        assert_eq!(test::read_body(resp).await.as_ref(), index.as_slice(), "{}", ruta);
+    }
+
+    let resp = test::call_service(&app, TestRequest::get().uri("/app/app.js").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().get("content-type").unwrap().to_str().unwrap().contains("javascript"));
+
+    let resp = test::call_service(&app, TestRequest::get().uri("/app/app.v2.js").to_request()).await;
+    assert_eq!(resp.status(), 404, "un archivo que falta no es una ruta de la SPA");
+}
+
+#[actix_web::test]
+async fn directories_are_never_listed() {
+    let dir = std::env::temp_dir().join(format!("pispas-frontend-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("assets")).unwrap();
+    std::fs::write(dir.join("index.html"), "<p>SPA</p>").unwrap();
+    std::fs::write(dir.join("assets/secreto.txt"), "no listar").unwrap();
+    let config = ConfigFrontend { montaje: "/static".to_string(), directorio: Some(dir.clone()) };
+    let app = test::init_service(App::new().configure(|cfg| frontend::configure(cfg, &config))).await;
+
+    let resp = test::call_service(&app, TestRequest::get().uri("/static/assets/").to_request()).await;
+    let body = test::read_body(resp).await;
+    assert!(!String::from_utf8_lossy(&body).contains("secreto.txt"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(feature = "embed-frontend")]
+#[actix_web::test]
+async fn embedded_files_are_revalidated_with_their_hash() {
+    let config = ConfigFrontend { montaje: "/static".to_string(), directorio: None };
+    let app = test::init_service(App::new().configure(|cfg| frontend::configure(cfg, &config))).await;
+
+    let resp = test::call_service(&app, TestRequest::get().uri("/static/reservas/42").to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let etag = resp.headers().get("etag").unwrap().clone();
+    let body = test::read_body(resp).await;
+    assert_eq!(body.as_ref(), include_bytes!("../static/index.html"));
+
+    let resp = test::call_service(&app, TestRequest::get()
+        .uri("/static/")
+        .insert_header(("If-None-Match", etag))
+        .to_request()).await;
+    assert_eq!(resp.status(), 304);
+
+    let resp = test::call_service(&app, TestRequest::get().uri("/static/app.v2.js").to_request()).await;
+    assert_eq!(resp.status(), 404);
+}
@@ -0,0 +1,142 @@
+//! Grupos más grandes que el máximo online y solicitudes de grupo
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use mongodb::bson::doc;
+use pispas_reservation::db::EstadoSolicitudGrupo;
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn large_public_parties_are_rejected_or_stored_as_group_requests() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "max_personas_online": 8 }))).await;
+    assert_eq!(status, 200);
+
+    let mut grupo = reservation_body(&id_mesa, "2030-06-15", "21:00");
+    grupo["numero_personas"] = json!(14);
+
+    // Por defecto se rechaza proponiendo repartir el grupo
+    let (status, body) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .insert_header(("X-Widget-Session", "grupo-1"))
+        .set_json(&grupo)).await;
+    assert_eq!(status, 400, "{}", body);
+    assert!(body["message"].as_str().unwrap().contains("7 + 7"), "{}", body);
+
+    // El panel no tiene límite, solo la capacidad de la mesa
+    db.repo.mesas()
+        .update_one(
+            doc! { "_id": mongodb::bson::oid::ObjectId::parse_str(&id_mesa).unwrap() },
+            doc! { "$set": { "max_personas": 14 } },
+        )
+        .await
+        .unwrap();
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(&grupo)).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "max_personas_online": 8, "grupos_grandes": "solicitud" }))).await;
+    assert_eq!(status, 200);
+
+    grupo["hora"] = json!("13:00");
+    let (status, body) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .insert_header(("X-Widget-Session", "grupo-2"))
+        .set_json(&grupo)).await;
+    assert_eq!(status, 202, "{}", body);
+    assert_eq!(body["tipo"], "solicitud_grupo");
+    assert_eq!(body["estado"], "nueva");
+
+    let solicitud = db.repo.solicitudes_grupo().find_one(doc! {}).await.unwrap().unwrap();
+    assert_eq!(solicitud.numero_personas, 14);
+    assert_eq!(solicitud.telefono_cliente, "+34 600 000 000");
+    assert_eq!(solicitud.estado, EstadoSolicitudGrupo::Nueva);
+    assert_eq!(db.repo.reservas().count_documents(doc! { "hora": "13:00" }).await.unwrap(), 0);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn staff_track_group_requests_until_converted() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "Casa Pepe").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let solicitud = json!({
+        "nombre_cliente": "Ana Ruiz",
+        "email_cliente": "ana@email.com",
+        "telefono_cliente": "+44 7700 900123",
+        "numero_personas": 30,
+        "fecha": "2030-06-20",
+        "mensaje": "Comida de empresa"
+    });
+    let (status, body) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/group-requests", restaurant.id))
+        .set_json(&solicitud)).await;
+    assert_eq!(status, 202, "{}", body);
+    let id = body["id"].as_str().unwrap().to_string();
+
+    // Una segunda solicitud abierta para el mismo día es un duplicado
+    let (status, _) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/group-requests", restaurant.id))
+        .set_json(&solicitud)).await;
+    assert_eq!(status, 409);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/group-requests?estado=nueva")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["idioma"], "en");
+    assert_eq!(body[0]["mensaje"], "Comida de empresa");
+
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/group-requests/{}", id))
+        .set_json(json!({ "estado": "en_conversacion", "nota": "Enviado el menú de grupo" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "en_conversacion");
+
+    // Para convertirla hace falta la reserva del grupo
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/group-requests/{}", id))
+        .set_json(json!({ "estado": "convertida" }))).await;
+    assert_eq!(status, 400);
+
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-20", "14:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/group-requests/{}", id))
+        .set_json(json!({ "estado": "convertida", "id_reserva": reserva["id"] }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["id_reserva"], reserva["id"]);
+    assert_eq!(body["nota"], "Enviado el menú de grupo");
+
+    // Convertida es final
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/group-requests/{}", id))
+        .set_json(json!({ "estado": "rechazada" }))).await;
+    assert_eq!(status, 409);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/group-requests?estado=nueva")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body.as_array().unwrap().len(), 0);
+}
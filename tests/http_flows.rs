@@ -0,0 +1,964 @@
+//! Flujos HTTP completos contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, unwrap_envelope, TestDb};
+use mongodb::bson::{doc, oid::ObjectId, Document};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn register_login_table_book_and_confirm() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri("/restaurants/login")
+        .set_json(json!({ "name": "La Tasca", "password": "secreto123" }))).await;
+    assert_eq!(status, 200);
+    assert_ne!(body["access_token"], restaurant.token.as_str(), "cada login abre una sesión nueva");
+    assert_eq!(body["id_restaurante"], restaurant.id.as_str());
+
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "pendiente");
+    let id_reserva = body["id"].as_str().unwrap().to_string();
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/confirm", id_reserva))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "confirmada");
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-15")).await;
+    assert_eq!(status, 200);
+    let reservas = body.as_array().unwrap();
+    assert_eq!(reservas.len(), 1);
+    assert_eq!(reservas[0]["id"], id_reserva.as_str());
+    assert_eq!(reservas[0]["estado"], "confirmada");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn duplicate_table_names_are_rejected_after_normalization() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "Casa Pepe").await;
+    create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .set_json(json!({
+            "id_restaurante": restaurant.id,
+            "tipo": "mesa",
+            "nombre": "  MÉSA   1 ",
+            "pos_x": 0.0,
+            "pos_y": 0.0,
+            "size_x": 80.0,
+            "size_y": 80.0,
+            "forma": "circulo",
+            "reservable": true,
+            "min_personas": null,
+            "max_personas": null
+        }))).await;
+    assert_eq!(status, 409);
+
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables?id_restaurante={}", restaurant.id))).await;
+    assert_eq!(body.as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn normalized_names_are_backfilled_before_the_unique_indexes() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "Café Olé").await;
+    let otro = register_restaurant(&app, "Casa Pepe").await;
+    create_table(&app, &restaurant, "Mesa 1").await;
+
+    // Documentos anteriores al campo: sin él, o con el valor vacío por defecto,
+    // y un restaurante cuyo nombre coincide con otro una vez normalizado
+    let restaurants = db.repo.database.collection::<Document>("restaurants");
+    restaurants
+        .update_many(doc! {}, doc! { "$unset": { "nombre_normalizado": "" } })
+        .await
+        .unwrap();
+    restaurants
+        .update_one(doc! { "nombre": "Casa Pepe" }, doc! { "$set": { "nombre": "CAFE OLE" } })
+        .await
+        .unwrap();
+    db.repo.mesas()
+        .update_many(doc! {}, doc! { "$set": { "nombre_normalizado": "" } })
+        .await
+        .unwrap();
+
+    db.repo.create_indexes().await.unwrap();
+
+    let original = db.repo.restaurants().find_one(doc! { "nombre": "Café Olé" }).await.unwrap().unwrap();
+    assert_eq!(original.nombre_normalizado, "cafe ole");
+    let duplicado = restaurants
+        .find_one(doc! { "_id": ObjectId::parse_str(&otro.id).unwrap() })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!duplicado.contains_key("nombre_normalizado"), "{:?}", duplicado);
+    let mesa = db.repo.mesas().find_one(doc! {}).await.unwrap().unwrap();
+    assert_eq!(mesa.nombre_normalizado, "mesa 1");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn requests_without_token_are_rejected() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let (status, _) = send(&app, TestRequest::get().uri("/reservations")).await;
+    assert_eq!(status, 401);
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), "token-falso")
+        .uri("/reservations")).await;
+    assert_eq!(status, 401);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn shifts_group_reservations_of_the_day() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "El Rincón").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({
+            "turnos": [
+                { "nombre": "Comida", "hora_inicio": "13:00", "hora_fin": "16:30" },
+                { "nombre": "Cena", "hora_inicio": "20:00", "hora_fin": "23:59" }
+            ]
+        }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    for hora in ["14:00", "21:00", "18:00"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&id_mesa, "2030-06-15", hora))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations/by-shift?fecha=2030-06-15")).await;
+    assert_eq!(status, 200);
+
+    let turnos = body["turnos"].as_array().unwrap();
+    let nombres: Vec<&str> = turnos.iter().map(|t| t["nombre"].as_str().unwrap()).collect();
+    assert_eq!(nombres, ["Comida", "Cena", "sin_turno"]);
+    for turno in turnos {
+        assert_eq!(turno["total_reservas"], 1);
+        assert_eq!(turno["total_personas"], 2);
+    }
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn listings_return_only_the_requested_fields() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200);
+
+    let (status, reservas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-15&fields=nombre_cliente,hora,no_existe")).await;
+    assert_eq!(status, 200, "{}", reservas);
+    assert_eq!(reservas, json!([{ "nombre_cliente": "Juan Pérez", "hora": "21:00" }]));
+
+    let (_, mesas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables?id_restaurante={}&fields=", restaurant.id))).await;
+    assert_eq!(mesas[0]["nombre"], "Mesa 1");
+    assert!(mesas[0].get("max_personas").is_some(), "sin campos, respuesta completa");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn client_uuids_make_offline_creations_repeatable() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let uuid = "3F2B8C1E-6D4A-4F7B-9A0E-2C5D8E1F4A6B";
+    let mesa = json!({
+        "id_restaurante": restaurant.id,
+        "tipo": "mesa",
+        "nombre": "Terraza 1",
+        "pos_x": 0.0,
+        "pos_y": 0.0,
+        "size_x": 80.0,
+        "size_y": 80.0,
+        "forma": "circulo",
+        "reservable": true,
+        "min_personas": 1,
+        "max_personas": 4,
+        "uuid": uuid
+    });
+
+    let (status, creada) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .set_json(&mesa)).await;
+    assert_eq!(status, 200, "{}", creada);
+    assert_eq!(creada["uuid"], uuid.to_lowercase());
+
+    // La sincronización repetida devuelve la misma mesa
+    let (status, repetida) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .set_json(&mesa)).await;
+    assert_eq!(status, 200, "{}", repetida);
+    assert_eq!(repetida["message"], "La mesa ya existía");
+    assert_eq!(repetida["id"], creada["id"]);
+
+    let id_mesa = creada["id"].as_str().unwrap();
+    let mut reserva = reservation_body(id_mesa, "2030-06-15", "21:00");
+    reserva["uuid"] = json!(uuid);
+    let (status, creada) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(&reserva)).await;
+    assert_eq!(status, 200, "{}", creada);
+
+    // Sin UUID, la misma reserva chocaría con la primera
+    let (status, repetida) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(&reserva)).await;
+    assert_eq!(status, 200, "{}", repetida);
+    assert_eq!(repetida["message"], "La reserva ya existía");
+    assert_eq!(repetida["id"], creada["id"]);
+    assert_eq!(repetida["localizador"], creada["localizador"]);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-15")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["uuid"], uuid.to_lowercase());
+
+    reserva["uuid"] = json!("no-es-un-uuid");
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(&reserva)).await;
+    assert_eq!(status, 400);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservations_can_be_edited_with_creation_checks() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa_1 = create_table(&app, &restaurant, "Mesa 1").await;
+    let mesa_2 = create_table(&app, &restaurant, "Mesa 2").await;
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa_1, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+    let id = body["id"].as_str().unwrap().to_string();
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa_2, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200);
+
+    let editar = |cambios: serde_json::Value| bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", id))
+        .set_json(cambios);
+
+    // La propia reserva no cuenta como conflicto
+    let (status, body) = send(&app, editar(json!({ "hora": "21:30", "numero_personas": 4 }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["reserva"]["hora"], "21:30");
+    assert_eq!(body["reserva"]["numero_personas"], 4);
+    assert_eq!(body["reserva"]["nombre_cliente"], "Juan Pérez");
+    assert_eq!(body["reserva"]["estado"], "pendiente");
+
+    // Las mismas validaciones que al crear
+    let (status, _) = send(&app, editar(json!({ "numero_personas": 9 }))).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, editar(json!({ "id_mesa": mesa_2 }))).await;
+    assert_eq!(status, 409);
+
+    let (status, body) = send(&app, editar(json!({ "nombre_cliente": "Ana López", "email_cliente": "ana@email.com" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["reserva"]["email_cliente"], "ana@email.com");
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", id))).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, editar(json!({ "hora": "22:00" }))).await;
+    assert_eq!(status, 409);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_listing_is_paginated_with_a_total_count() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    for fecha in ["2030-06-15", "2030-06-16", "2030-06-17"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&id_mesa, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    let resp = actix_web::test::call_service(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?page=2&limit=2")
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("x-total-count").unwrap(), "3");
+    let body = unwrap_envelope(actix_web::test::read_body_json(resp).await);
+    let reservas = body.as_array().unwrap();
+    assert_eq!(reservas.len(), 1);
+    assert_eq!(reservas[0]["fecha"], "2030-06-15", "las más recientes van primero");
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?limit=501")).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?page=0")).await;
+    assert_eq!(status, 400);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_listing_combines_range_state_and_table_filters() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa_1 = create_table(&app, &restaurant, "Mesa 1").await;
+    let mesa_2 = create_table(&app, &restaurant, "Mesa 2").await;
+    let mut ids = Vec::new();
+    for (id_mesa, fecha) in [(&mesa_1, "2030-06-14"), (&mesa_1, "2030-06-15"), (&mesa_1, "2030-06-16"), (&mesa_2, "2030-06-15")] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(id_mesa, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/confirm", ids[1]))).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", ids[2]))).await;
+    assert_eq!(status, 200);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!(
+            "/reservations?fecha_desde=2030-06-15&fecha_hasta=2030-06-16&estado=pendiente,confirmada&id_mesa={}",
+            mesa_1,
+        ))).await;
+    assert_eq!(status, 200, "{}", body);
+    let reservas = body.as_array().unwrap();
+    assert_eq!(reservas.len(), 1);
+    assert_eq!(reservas[0]["id"], ids[1].as_str());
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha_desde=2030-06-15&estado=pendiente")).await;
+    assert_eq!(status, 200);
+    let reservas = body.as_array().unwrap();
+    assert_eq!(reservas.len(), 1);
+    assert_eq!(reservas[0]["id"], ids[3].as_str());
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha_hasta=15-06-2030")).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?id_mesa=no-es-un-id")).await;
+    assert_eq!(status, 400);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_listing_can_be_sorted() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    for (nombre, fecha, hora) in [("Carla", "2030-06-15", "21:00"), ("Ana", "2030-06-16", "13:00"), ("Berta", "2030-06-15", "13:00")] {
+        let mut body = reservation_body(&id_mesa, fecha, hora);
+        body["nombre_cliente"] = json!(nombre);
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(body)).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    let nombres = |body: serde_json::Value| -> Vec<String> {
+        body.as_array().unwrap().iter().map(|r| r["nombre_cliente"].as_str().unwrap().to_string()).collect()
+    };
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations")).await;
+    assert_eq!(status, 200);
+    assert_eq!(nombres(body), ["Ana", "Carla", "Berta"]);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?sort=fecha")).await;
+    assert_eq!(status, 200);
+    assert_eq!(nombres(body), ["Berta", "Carla", "Ana"]);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?sort=nombre_cliente")).await;
+    assert_eq!(status, 200);
+    assert_eq!(nombres(body), ["Ana", "Berta", "Carla"]);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?sort=-created_at")).await;
+    assert_eq!(status, 200);
+    assert_eq!(nombres(body).len(), 3);
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?sort=telefono_cliente")).await;
+    assert_eq!(status, 400);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_listing_searches_by_customer() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let clientes = [
+        ("Ana Martínez", "ana@correo.es", "+34 611 111 111", "2030-06-15"),
+        ("Berta Ruiz", "berta.r@email.com", "+34 622 222 222", "2030-06-16"),
+        ("Carlos Ana", "carlos@email.com", "+34 633 333 333", "2031-01-10"),
+    ];
+    for (nombre, email, telefono, fecha) in clientes {
+        let mut body = reservation_body(&id_mesa, fecha, "21:00");
+        body["nombre_cliente"] = json!(nombre);
+        body["email_cliente"] = json!(email);
+        body["telefono_cliente"] = json!(telefono);
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(body)).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    let nombres = |body: serde_json::Value| -> Vec<String> {
+        body.as_array().unwrap().iter().map(|r| r["nombre_cliente"].as_str().unwrap().to_string()).collect()
+    };
+
+    // Sin distinguir mayúsculas y fuera del horizonte por defecto
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?q=ANA&sort=nombre_cliente")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(nombres(body), ["Ana Martínez", "Carlos Ana"]);
+
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?q=berta.r%40")).await;
+    assert_eq!(nombres(body), ["Berta Ruiz"]);
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?q=633")).await;
+    assert_eq!(nombres(body), ["Carlos Ana"]);
+
+    // Se combina con las fechas; los caracteres especiales no son regex
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?q=ana&fecha_hasta=2030-12-31")).await;
+    assert_eq!(nombres(body), ["Ana Martínez"]);
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?q=.*")).await;
+    assert_eq!(nombres(body).len(), 0);
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?q=a")).await;
+    assert_eq!(status, 400);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_listing_defaults_to_upcoming_reservations() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    for fecha in ["2030-06-15", "2030-09-01"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&id_mesa, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    let fechas = |body: serde_json::Value| -> Vec<String> {
+        body.as_array().unwrap().iter().map(|r| r["fecha"].as_str().unwrap().to_string()).collect()
+    };
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations")).await;
+    assert_eq!(status, 200);
+    assert_eq!(fechas(body), ["2030-06-15"], "sin fechas, solo los próximos 30 días");
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha_desde=2030-01-01")).await;
+    assert_eq!(status, 200);
+    assert_eq!(fechas(body), ["2030-09-01", "2030-06-15"]);
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "horizonte_listado_dias": 0 }))).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "horizonte_listado_dias": 120 }))).await;
+    assert_eq!(status, 200);
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations")).await;
+    assert_eq!(fechas(body).len(), 2);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=15-06-2030")).await;
+    assert_eq!(status, 400);
+    assert!(body["message"].as_str().unwrap().contains("'fecha'"), "{}", body);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_listing_starts_today_in_the_restaurant_time_zone() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    for fecha in ["2030-06-01", "2030-06-02"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&id_mesa, fecha, "21:00"))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    // Las 12:00 UTC del 1 de junio son las 02:00 del 2 en Kiritimati
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "zona_horaria": "Pacific/Kiritimati" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations")).await;
+    assert_eq!(status, 200);
+    let fechas: Vec<&str> = body.as_array().unwrap().iter().map(|r| r["fecha"].as_str().unwrap()).collect();
+    assert_eq!(fechas, ["2030-06-02"]);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservations_follow_the_lifecycle_state_machine() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "Casa Lola").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let mut ids = Vec::new();
+    for hora in ["13:00", "21:00"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&id_mesa, "2030-06-15", hora))).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+
+    // Solo se completa una reserva con el cliente sentado
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/complete", ids[0]))).await;
+    assert_eq!(status, 409);
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/seat", ids[0]))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "sentada");
+
+    // Sentada no se cancela ni se marca como no presentada
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", ids[0]))).await;
+    assert_eq!(status, 404);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/no-show", ids[0]))).await;
+    assert_eq!(status, 409);
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/complete", ids[0]))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "completada");
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/no-show", ids[1]))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "no_show");
+
+    // Los estados finales ya no cambian
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/seat", ids[1]))).await;
+    assert_eq!(status, 409);
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", ids[0]))
+        .set_json(json!({ "numero_personas": 3 }))).await;
+    assert_eq!(status, 409);
+
+    // La mesa queda libre a la hora de las reservas terminadas
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?estado=completada,no_show")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body.as_array().unwrap().len(), 2);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?estado=no_presentada")).await;
+    assert_eq!(status, 400, "{}", body);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn cancelled_reservations_free_their_slot() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let reservar = || bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"));
+
+    let (status, reserva) = send(&app, reservar()).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let (status, body) = send(&app, reservar()).await;
+    assert_eq!(status, 409, "{}", body);
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", reserva["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 200);
+    let (status, body) = send(&app, reservar()).await;
+    assert_eq!(status, 200, "{}", body);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn walk_ins_are_seated_without_customer_details() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "Casa Lola").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations/walk-in")
+        .set_json(json!({ "id_mesa": id_mesa, "numero_personas": 2, "email_cliente": "no-es-un-email" }))).await;
+    assert_eq!(status, 400);
+
+    // Sin fecha ni hora, las del reloj (2030-06-01 12:00)
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations/walk-in")
+        .set_json(json!({ "id_mesa": id_mesa, "numero_personas": 2 }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "sentada");
+    assert_eq!(body["fecha"], "2030-06-01");
+    assert_eq!(body["hora"], "12:00");
+    assert_eq!(body["ocupada_hasta"], "13:30");
+    let id = body["id"].as_str().unwrap().to_string();
+
+    // La mesa está ocupada durante la duración de la reserva
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations/walk-in")
+        .set_json(json!({ "id_mesa": id_mesa, "numero_personas": 2, "hora": "13:00" }))).await;
+    assert_eq!(status, 409);
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-01", "14:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?estado=sentada")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body[0]["nombre_cliente"], "Cliente sin reserva");
+    assert_eq!(body[0]["canal"], "sala");
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/complete", id))).await;
+    assert_eq!(status, 200, "{}", body);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn bulk_operations_report_each_reservation() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let otro = register_restaurant(&app, "La Bodega").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let mut ids = Vec::new();
+    for hora in ["13:00", "16:00", "20:00"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, "2030-06-15", hora))).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", ids[2]))).await;
+    assert_eq!(status, 200);
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations/bulk")
+        .set_json(json!({ "ids": [ids[0], ids[1], ids[0], ids[2], "no-es-un-id"], "accion": "confirmar" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["procesadas"], 2);
+    assert_eq!(body["fallidas"], 2);
+    let resultados = body["resultados"].as_array().unwrap();
+    assert_eq!(resultados.len(), 4, "los IDs repetidos se tratan una vez");
+    assert_eq!(resultados[0], json!({ "id": ids[0], "ok": true, "estado": "confirmada" }));
+    assert_eq!(resultados[2]["estado"], "cancelada");
+    assert_eq!(resultados[2]["ok"], false);
+    assert_eq!(resultados[3]["error"], "ID de reserva inválido");
+
+    // Las reservas de otro restaurante no se tocan
+    let (_, body) = send(&app, bearer(TestRequest::post(), &otro.token)
+        .uri("/reservations/bulk")
+        .set_json(json!({ "ids": [ids[0]], "accion": "cancelar" }))).await;
+    assert_eq!(body["resultados"][0]["error"], "Reserva no encontrada");
+
+    let (_, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations/bulk")
+        .set_json(json!({ "ids": ids, "accion": "cancelar" }))).await;
+    assert_eq!(body["procesadas"], 2);
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations/bulk")
+        .set_json(json!({ "ids": vec![ids[0].clone(); 101], "accion": "cancelar" }))).await;
+    assert_eq!(status, 400);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn late_cancellations_follow_the_cancellation_policy() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let politica = |marcar_tardias: bool| json!({
+        "politica_cancelacion": { "horas_cancelacion": 2, "horas_modificacion": 4, "marcar_tardias": marcar_tardias }
+    });
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(politica(false))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // El reloj de los tests está en 2030-06-01 12:00
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-01", "13:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let id = reserva["id"].as_str().unwrap().to_string();
+
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", id))
+        .set_json(json!({ "hora": "13:30" }))).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["codigo"], "modificacion_tardia");
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", id))
+        .set_json(json!({ "notas_internas": "Llega tarde" }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", id))).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["codigo"], "cancelacion_tardia");
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(politica(true))).await;
+    assert_eq!(status, 200);
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", id))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["cancelacion_tardia"], true);
+
+    let (_, reservas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-01")).await;
+    assert_eq!(reservas[0]["cancelacion_tardia"], true);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn cancellation_deadlines_use_the_restaurant_time_zone() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({
+            "zona_horaria": "Europe/Madrid",
+            "politica_cancelacion": { "horas_cancelacion": 2, "horas_modificacion": 2, "marcar_tardias": false }
+        }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Las 12:00 UTC del reloj de los tests son las 14:00 en Madrid: a una
+    // reserva de las 15:30 le queda hora y media
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-01", "15:30"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let id = reserva["id"].as_str().unwrap().to_string();
+
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", id))
+        .set_json(json!({ "hora": "16:00" }))).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["codigo"], "modificacion_tardia");
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", id))).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["codigo"], "cancelacion_tardia");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_listings_score_no_show_risk() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let mut ids = Vec::new();
+    for (fecha, hora) in [("2030-06-10", "20:00"), ("2030-06-20", "20:00"), ("2030-06-01", "13:00")] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, fecha, hora))).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+
+    // Sin historial ni otros factores, riesgo 0
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-20")).await;
+    assert_eq!(body[0]["riesgo"], json!({ "puntuacion": 0, "motivos": [] }));
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/no-show", ids[0]))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-20")).await;
+    assert_eq!(body[0]["riesgo"]["puntuacion"], 30);
+    assert_eq!(body[0]["riesgo"]["motivos"], json!(["no_shows_previos"]));
+
+    // Hecha a las 12:00 para las 13:00: además, de última hora
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations/by-shift?fecha=2030-06-01")).await;
+    let reserva = &body["turnos"][0]["reservas"][0];
+    assert_eq!(reserva["riesgo"]["puntuacion"], 45);
+    assert_eq!(reserva["riesgo"]["motivos"], json!(["no_shows_previos", "ultimo_momento"]));
+
+    // La reserva no presentada ya no lleva riesgo
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-10")).await;
+    assert!(body[0].get("riesgo").is_none());
+
+    // Las reglas son del restaurante
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "riesgo": { "puntos_por_no_show": 150 } }))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "riesgo": { "puntos_por_no_show": 0, "puntos_telefono_inalcanzable": 40 } }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-20")).await;
+    assert_eq!(body[0]["riesgo"]["puntuacion"], 0);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservations_follow_the_restaurant_validation_limits() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let reservar = |cuerpo: serde_json::Value| bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(cuerpo);
+
+    // Sin límites configurados, los de siempre
+    let mut cuerpo = reservation_body(&id_mesa, "2030-05-20", "21:00");
+    cuerpo["notas_cliente"] = json!("x".repeat(500));
+    let (status, body) = send(&app, reservar(cuerpo)).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "verificacion_cliente": "telefono", "limites": { "telefono_obligatorio": false } }))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "limites": {
+            "max_personas": 3,
+            "longitud_maxima_notas": 20,
+            "antelacion_minima_minutos": 60,
+            "antelacion_maxima_dias": 30,
+            "telefono_obligatorio": false
+        } }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // El reloj de los tests está en 2030-06-01 12:00
+    for (fecha, hora) in [("2030-05-20", "21:00"), ("2030-06-01", "12:30"), ("2030-07-02", "21:00")] {
+        let (status, body) = send(&app, reservar(reservation_body(&id_mesa, fecha, hora))).await;
+        assert_eq!(status, 400, "{} {}", fecha, hora);
+        assert!(body["message"].as_str().unwrap().contains("'fecha'"), "{}", body);
+    }
+    let mut cuerpo = reservation_body(&id_mesa, "2030-06-15", "21:00");
+    cuerpo["numero_personas"] = json!(4);
+    let (status, body) = send(&app, reservar(cuerpo)).await;
+    assert_eq!(status, 400);
+    assert!(body["message"].as_str().unwrap().contains("'numero_personas'"), "{}", body);
+    let mut cuerpo = reservation_body(&id_mesa, "2030-06-15", "21:00");
+    cuerpo["notas_cliente"] = json!("x".repeat(21));
+    let (status, _) = send(&app, reservar(cuerpo)).await;
+    assert_eq!(status, 400);
+
+    let mut cuerpo = reservation_body(&id_mesa, "2030-06-15", "21:00");
+    cuerpo["telefono_cliente"] = json!("");
+    let (status, body) = send(&app, reservar(cuerpo)).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Al editar, el plazo solo cuenta si cambian la fecha o la hora
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", body["id"].as_str().unwrap()))
+        .set_json(json!({ "fecha": "2030-08-01" }))).await;
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,132 @@
+//! Claves de idempotencia contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, unwrap_envelope, TestDb};
+use mongodb::bson::{doc, oid::ObjectId};
+use pispas_reservation::api::idempotency::fingerprint;
+use pispas_reservation::clock::Clock;
+use pispas_reservation::db::PeticionIdempotente;
+use pispas_reservation::notifications::Notifier;
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn retried_creations_return_the_original_response() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let reserva = reservation_body(&id_mesa, "2030-06-15", "21:00");
+
+    let crear = |clave: &str, cuerpo: &serde_json::Value| bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .insert_header(("Idempotency-Key", clave))
+        .set_json(cuerpo);
+
+    let (status, original) = send(&app, crear("reintento-1", &reserva)).await;
+    assert_eq!(status, 200, "{}", original);
+
+    // El reintento no choca con la reserva ya creada: recibe la misma respuesta
+    let resp = test::call_service(&app, crear("reintento-1", &reserva).to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("idempotent-replayed").unwrap(), "true");
+    let repetida = unwrap_envelope(test::read_body_json(resp).await);
+    assert_eq!(repetida, original);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-15")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+
+    // La misma clave con otro cuerpo se rechaza
+    let otra = reservation_body(&id_mesa, "2030-06-15", "23:00");
+    let (status, _) = send(&app, crear("reintento-1", &otra)).await;
+    assert_eq!(status, 400);
+
+    // También se repiten las respuestas de error del cliente
+    let (status, _) = send(&app, crear("reintento-2", &reserva)).await;
+    assert_eq!(status, 409);
+    let resp = test::call_service(&app, crear("reintento-2", &reserva).to_request()).await;
+    assert_eq!(resp.status(), 409);
+    assert_eq!(resp.headers().get("idempotent-replayed").unwrap(), "true");
+
+    // Las claves son de cada ruta
+    let mesa = json!({
+        "id_restaurante": restaurant.id,
+        "tipo": "mesa",
+        "nombre": "Mesa 2",
+        "pos_x": 0.0,
+        "pos_y": 0.0,
+        "size_x": 80.0,
+        "size_y": 80.0,
+        "forma": "cuadrado",
+        "reservable": true,
+        "min_personas": 2,
+        "max_personas": 4
+    });
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .insert_header(("Idempotency-Key", "reintento-1"))
+        .set_json(&mesa)).await;
+    assert_eq!(status, 400);
+
+    let (status, primera) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .insert_header(("Idempotency-Key", "mesa-2"))
+        .set_json(&mesa)).await;
+    assert_eq!(status, 200, "{}", primera);
+    let (status, segunda) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .insert_header(("Idempotency-Key", "mesa-2"))
+        .set_json(&mesa)).await;
+    assert_eq!(status, 200, "sin la clave sería un nombre repetido: {}", segunda);
+    assert_eq!(segunda["id"], primera["id"]);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn keys_of_requests_that_never_finished_are_released() {
+    let db = TestDb::start().await;
+    let clock = test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let reserva = reservation_body(&id_mesa, "2030-06-15", "21:00");
+    let crear = || bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .insert_header(("Idempotency-Key", "colgada"))
+        .set_json(&reserva);
+
+    // Una petición que se quedó a medias (el servidor se reinició)
+    db.repo.peticiones_idempotentes()
+        .insert_one(PeticionIdempotente {
+            id: None,
+            id_restaurante: ObjectId::parse_str(&restaurant.id).unwrap(),
+            clave: "colgada".to_string(),
+            huella: fingerprint("POST", "/reservations", &serde_json::to_vec(&reserva).unwrap()),
+            estado: None,
+            respuesta: None,
+            created_at: clock.timestamp(),
+            expira_en: None,
+        })
+        .await
+        .unwrap();
+    let (status, _) = send(&app, crear()).await;
+    assert_eq!(status, 409);
+
+    clock.advance(Duration::seconds(61));
+    let (status, body) = send(&app, crear()).await;
+    assert_eq!(status, 200, "{}", body);
+    let guardada = db.repo.peticiones_idempotentes()
+        .find_one(doc! { "clave": "colgada" })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(guardada.estado, Some(200));
+    assert!(guardada.expira_en.is_some());
+}
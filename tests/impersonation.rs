@@ -0,0 +1,99 @@
+//! Suplantación del propietario por el soporte contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::{call_service, TestRequest};
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::api::impersonation::CABECERA;
+use pispas_reservation::notifications::Notifier;
+use serde_json::json;
+
+const ADMIN_TOKEN: &str = "admin-test-token";
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn support_impersonates_an_owner_with_audit_and_banner() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let clock = test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let suplantar = |cuerpo: serde_json::Value| bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri(&format!("/admin/impersonate/{}", restaurant.id))
+        .set_json(cuerpo);
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/admin/impersonate/{}", restaurant.id))
+        .set_json(json!({ "admin": "lucia", "motivo": "Incidencia 4821" }))).await;
+    assert_eq!(status, 401);
+    let (status, _) = send(&app, suplantar(json!({ "admin": "lucia", "motivo": " " }))).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, suplantar(json!({ "admin": "lucia", "motivo": "Incidencia 4821", "minutos": 600 }))).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/impersonate/507f1f77bcf86cd799439011")
+        .set_json(json!({ "admin": "lucia", "motivo": "Incidencia 4821" }))).await;
+    assert_eq!(status, 404);
+
+    let (status, body) = send(&app, suplantar(json!({ "admin": "lucia", "motivo": "Incidencia 4821" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let token = body["access_token"].as_str().unwrap().to_string();
+
+    // Actúa como el propietario, con el aviso en cada respuesta
+    let (status, yo) = send(&app, bearer(TestRequest::get(), &token).uri("/staff/me")).await;
+    assert_eq!(status, 200);
+    assert_eq!(yo["rol"], "propietario");
+    assert_eq!(yo["suplantada_por"], "lucia");
+    let resp = call_service(&app, bearer(TestRequest::post(), &token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get(CABECERA).unwrap(), "lucia");
+    let resp = call_service(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-16", "21:00"))
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().get(CABECERA).is_none());
+
+    // El registro de auditoría distingue sus operaciones
+    let (status, entradas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/audit?accion=crear_reserva")).await;
+    assert_eq!(status, 200, "{}", entradas);
+    let suplantadas: Vec<_> = entradas.as_array().unwrap().iter()
+        .map(|entrada| entrada["suplantada_por"].as_str())
+        .collect();
+    assert_eq!(suplantadas, [None, Some("lucia")]);
+
+    // El propietario la ve entre sus sesiones
+    let (_, sesiones) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/restaurants/sessions")).await;
+    assert!(sesiones.as_array().unwrap().iter().any(|sesion| sesion["suplantada_por"] == "lucia"));
+
+    // Con alcances solo llega a esas rutas
+    let (status, body) = send(&app, suplantar(json!({
+        "admin": "lucia", "motivo": "Incidencia 4821", "minutos": 60, "alcances": ["reservations:read"]
+    }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let lectura = body["access_token"].as_str().unwrap().to_string();
+    let (status, _) = send(&app, bearer(TestRequest::get(), &lectura).uri("/reservations")).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &lectura)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-17", "21:00"))).await;
+    assert_eq!(status, 403);
+
+    // Y caduca
+    clock.advance(Duration::minutes(31));
+    let (status, _) = send(&app, bearer(TestRequest::get(), &token).uri("/reservations")).await;
+    assert_eq!(status, 401);
+    let (status, _) = send(&app, bearer(TestRequest::get(), &lectura).uri("/reservations")).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/reservations")).await;
+    assert_eq!(status, 200);
+}
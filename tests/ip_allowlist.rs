@@ -0,0 +1,82 @@
+//! IPs autorizadas de la API de gestión contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use std::net::SocketAddr;
+use actix_web::test::TestRequest;
+use common::{bearer, register_restaurant, send, TestDb};
+use serde_json::json;
+
+/// Dirección de la conexión de una petición desde `ip`
+fn peer(ip: &str) -> SocketAddr {
+    SocketAddr::new(ip.parse().unwrap(), 40000)
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn management_api_only_answers_authorized_ips() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesas = format!("/tables?id_restaurante={}", restaurant.id);
+
+    // Sin lista, cualquier IP
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&mesas)
+        .peer_addr(peer("192.0.2.1"))).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "ips_permitidas": ["oficina"] }))).await;
+    assert_eq!(status, 400);
+
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "ips_permitidas": ["203.0.113.25/24", "203.0.113.0/24", "2001:db8::1"] }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["configuracion"]["ips_permitidas"], json!(["203.0.113.0/24", "2001:db8::1"]));
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&mesas)
+        .peer_addr(peer("192.0.2.1"))).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["codigo"], "ip_no_autorizada");
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri(&mesas)).await;
+    assert_eq!(status, 403, "sin dirección remota conocida no se admite");
+    assert_eq!(body["codigo"], "ip_no_autorizada");
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&mesas)
+        .peer_addr(peer("203.0.113.7"))).await;
+    assert_eq!(status, 200);
+
+    // Sin un proxy de confianza delante, X-Forwarded-For lo pone el cliente
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&mesas)
+        .peer_addr(peer("192.0.2.1"))
+        .insert_header(("X-Forwarded-For", "203.0.113.7"))).await;
+    assert_eq!(status, 403, "X-Forwarded-For falsificado");
+    assert_eq!(body["codigo"], "ip_no_autorizada");
+
+    // Las rutas públicas no se limitan
+    let (status, _) = send(&app, TestRequest::get()
+        .uri(&format!("/public/restaurants/{}/menu-options", restaurant.id))
+        .peer_addr(peer("192.0.2.1"))).await;
+    assert_eq!(status, 200);
+
+    // Desde una IP autorizada se puede quitar la lista
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .peer_addr(peer("2001:db8::1"))
+        .set_json(json!({ "ips_permitidas": [] }))).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&mesas)
+        .peer_addr(peer("192.0.2.1"))).await;
+    assert_eq!(status, 200);
+}
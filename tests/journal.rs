@@ -0,0 +1,108 @@
+//! Diario de reservas (`RESERVAS_PERSISTENCIA=eventos`) contra un MongoDB
+//! efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use futures_util::TryStreamExt;
+use mongodb::bson::{doc, oid::ObjectId};
+use pispas_reservation::db::{EntradaDiario, ModoPersistencia};
+use pispas_reservation::journal;
+
+const ADMIN_TOKEN: &str = "admin-test-token";
+
+async fn entries(db: &TestDb, id: ObjectId) -> Vec<EntradaDiario> {
+    db.repo.diario_reservas()
+        .find(doc! { "id_reserva": id })
+        .sort(doc! { "secuencia": 1 })
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservations_are_rebuilt_from_the_journal() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let mut db = TestDb::start().await;
+    db.repo = db.repo.clone().with_persistencia(ModoPersistencia::Eventos);
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let mut ids = Vec::new();
+    for hora in ["13:00", "21:00"] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, "2030-06-15", hora))).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(ObjectId::parse_str(body["id"].as_str().unwrap()).unwrap());
+    }
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/confirm", ids[0]))).await;
+    assert_eq!(status, 200);
+
+    let diario = entries(&db, ids[0]).await;
+    let motivos: Vec<_> = diario.iter().map(|entrada| (entrada.secuencia, entrada.motivo.as_str())).collect();
+    assert_eq!(motivos, [(1, "crear_reserva"), (2, "confirmar_reserva")]);
+    assert_eq!(diario[1].reserva.as_ref().unwrap().get_str("estado"), Ok("confirmada"));
+
+    // Cambios por fuera del diario, que la reconstrucción deshace
+    db.repo.reservas()
+        .update_one(doc! { "_id": ids[0] }, doc! { "$set": { "estado": "cancelada" } })
+        .await
+        .unwrap();
+    db.repo.reservas().delete_one(doc! { "_id": ids[1] }).await.unwrap();
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/rebuild?what=reservas")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["colecciones"][0]["revisados"], 2, "{}", body);
+    assert_eq!(body["colecciones"][0]["actualizados"], 2, "{}", body);
+    let reserva = db.repo.reservas().find_one(doc! { "_id": ids[0] }).await.unwrap().unwrap();
+    assert_eq!(reserva.estado.to_string(), "confirmada");
+    assert!(db.repo.reservas().find_one(doc! { "_id": ids[1] }).await.unwrap().is_some());
+
+    let (_, body) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/rebuild?what=reservas")).await;
+    assert_eq!(body["colecciones"][0]["actualizados"], 0, "{}", body);
+
+    // Un borrado queda anotado y las entradas anteriores pierden los datos personales
+    db.repo.reservas().delete_one(doc! { "_id": ids[1] }).await.unwrap();
+    journal::append(&db.repo, ids[1], "borrar_reserva", 1_900_000_000).await;
+    let diario = entries(&db, ids[1]).await;
+    assert_eq!(diario.len(), 2);
+    assert!(diario[1].reserva.is_none());
+    assert_eq!(diario[0].reserva.as_ref().unwrap().get_str("nombre_cliente"), Ok("Anónimo"));
+    assert_eq!(diario[0].reserva.as_ref().unwrap().get_str("email_cliente"), Ok(""));
+
+    let (_, body) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/rebuild?what=reservas")).await;
+    assert_eq!(body["colecciones"][0]["actualizados"], 0, "{}", body);
+    assert!(db.repo.reservas().find_one(doc! { "_id": ids[1] }).await.unwrap().is_none());
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn crud_mode_keeps_no_journal() {
+    std::env::set_var("ADMIN_TOKEN", ADMIN_TOKEN);
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "13:00"))).await;
+    assert_eq!(status, 200);
+    assert_eq!(db.repo.diario_reservas().count_documents(doc! {}).await.unwrap(), 0);
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), ADMIN_TOKEN)
+        .uri("/admin/rebuild?what=reservas")).await;
+    assert_eq!(status, 400);
+}
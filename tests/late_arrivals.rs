@@ -0,0 +1,130 @@
+//! Tolerancia de retraso de los clientes contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use mongodb::bson::doc;
+use pispas_reservation::jobs::late_arrivals;
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn confirmed_reservations_without_arrival_are_released_after_the_grace_period() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+    let outbox = notifier.outbox().unwrap();
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let ajustes = |tolerancia: serde_json::Value| bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "email_alertas": "encargado@latasca.es", "tolerancia_retraso": tolerancia }));
+    let (status, _) = send(&app, ajustes(json!({ "minutos": 0 }))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, ajustes(json!({ "minutos": 15 }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let mut ids = Vec::new();
+    let mut mesas = Vec::new();
+    for (nombre, hora) in [("Mesa 1", "12:30"), ("Mesa 2", "12:30"), ("Mesa 3", "12:30"), ("Mesa 4", "13:00")] {
+        let mesa = create_table(&app, &restaurant, nombre).await;
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, "2030-06-01", hora))).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+        mesas.push(mesa);
+    }
+    // La de la mesa 3 se queda pendiente
+    for id in [&ids[0], &ids[1], &ids[3]] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri(&format!("/reservations/{}/confirm", id))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+    let estado = |id: &str| {
+        let id = mongodb::bson::oid::ObjectId::parse_str(id).unwrap();
+        let repo = db.repo.clone();
+        async move { repo.reservas().find_one(doc! { "_id": id }).await.unwrap().unwrap().estado.to_string() }
+    };
+
+    // El de la mesa 2 llega; a las 12:40 aún no ha pasado la tolerancia
+    clock.advance(Duration::minutes(40));
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/checkin", ids[1]))).await;
+    assert_eq!(status, 200);
+    outbox.clear();
+    assert_eq!(late_arrivals::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 0);
+
+    clock.advance(Duration::minutes(10));
+    assert_eq!(late_arrivals::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 1);
+    assert_eq!(late_arrivals::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 0);
+    assert_eq!(estado(&ids[0]).await, "no_show");
+    assert_eq!(estado(&ids[1]).await, "sentada");
+    assert_eq!(estado(&ids[2]).await, "pendiente");
+    assert_eq!(estado(&ids[3]).await, "confirmada");
+    match &outbox.sent()[..] {
+        [SentMessage::Email(email)] => {
+            assert_eq!(email.to, "encargado@latasca.es");
+            assert!(email.body.contains("no presentada"), "{}", email.body);
+        }
+        otros => panic!("se esperaba un email: {:?}", otros),
+    }
+    let (_, cliente) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/customers")).await;
+    assert_eq!(cliente[0]["no_shows"], 1, "{}", cliente);
+
+    // Liberando, la reserva se cancela sin contar el no-show
+    let (status, _) = send(&app, ajustes(json!({ "minutos": 15, "accion": "liberar" }))).await;
+    assert_eq!(status, 200);
+    clock.advance(Duration::minutes(30));
+    assert_eq!(late_arrivals::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 1);
+    assert_eq!(estado(&ids[3]).await, "cancelada");
+    let automaticas = db.repo.eventos()
+        .count_documents(doc! { "datos.automatica": true })
+        .await
+        .unwrap();
+    assert_eq!(automaticas, 2);
+    let (_, cliente) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/customers")).await;
+    assert_eq!(cliente[0]["no_shows"], 1, "{}", cliente);
+
+    // La mesa liberada se puede volver a reservar a esa hora
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesas[3], "2030-06-01", "13:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn the_grace_period_uses_the_restaurant_time_zone() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "zona_horaria": "America/New_York", "tolerancia_retraso": { "minutos": 15 } }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Las 12:00 UTC del reloj de los tests son las 08:00 en Nueva York: la
+    // reserva de las 11:30 aún no ha llegado, aunque en UTC ya habría vencido
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-01", "11:30"))).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/confirm", body["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(late_arrivals::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 0);
+
+    clock.advance(Duration::hours(4));
+    assert_eq!(late_arrivals::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 1);
+}
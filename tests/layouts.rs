@@ -0,0 +1,76 @@
+//! Distribuciones de mesas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservations_follow_the_layout_of_their_day() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let salon = create_table(&app, &restaurant, "Salón 1").await;
+    let terraza = create_table(&app, &restaurant, "Terraza 1").await;
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/layouts")
+        .set_json(json!({ "nombre": "Fin de semana", "mesas": ["Salón 1", "terraza 1"], "dias_semana": [5, 6, 7] }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["mesas"], json!(["salon 1", "terraza 1"]));
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/layouts")
+        .set_json(json!({ "nombre": "Entre semana", "mesas": ["Salón 1"], "dias_semana": [1, 2, 3, 4] }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let entre_semana = body["id"].as_str().unwrap().to_string();
+
+    // Un día no puede tener dos distribuciones, ni una mesa inexistente
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/layouts")
+        .set_json(json!({ "nombre": "Solo domingo", "mesas": ["Salón 1"], "dias_semana": [7] }))).await;
+    assert_eq!(status, 409);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/layouts")
+        .set_json(json!({ "nombre": "Jardín", "mesas": ["Jardín 1"], "fechas": ["2030-08-15"] }))).await;
+    assert_eq!(status, 400);
+
+    // El lunes 2030-06-17 la terraza está cerrada
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/layouts/active?fecha=2030-06-17")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body["distribucion"]["nombre"], "Entre semana");
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/tables/available?fecha=2030-06-17&hora=21:00&personas=2")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body.as_array().unwrap().len(), 1);
+    assert_eq!(body[0]["id"], salon.as_str());
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&terraza, "2030-06-17", "21:00"))).await;
+    assert_eq!(status, 409);
+
+    // El viernes 2030-06-14 sí
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&terraza, "2030-06-14", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Sin distribución ese día, se reserva con todas las mesas
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/layouts/{}", entre_semana))).await;
+    assert_eq!(status, 204);
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/layouts/active?fecha=2030-06-17")).await;
+    assert_eq!(status, 200);
+    assert!(body["distribucion"].is_null());
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&terraza, "2030-06-17", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+}
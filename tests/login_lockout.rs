@@ -0,0 +1,74 @@
+//! Bloqueo tras logins fallidos contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::Duration;
+use common::{register_restaurant, send, test_clock, TestDb};
+use pispas_reservation::notifications::Notifier;
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn repeated_failures_lock_the_login_for_a_while() {
+    std::env::set_var("LOGIN_MAX_FALLOS", "3");
+    std::env::set_var("LOGIN_BLOQUEO_MINUTOS", "10");
+    let db = TestDb::start().await;
+    let clock = test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+    register_restaurant(&app, "La Tasca").await;
+
+    let login = |password: &str| TestRequest::post()
+        .uri("/restaurants/login")
+        .set_json(json!({ "name": "La Tasca", "password": password }));
+
+    for _ in 0..3 {
+        let (status, _) = send(&app, login("incorrecta")).await;
+        assert_eq!(status, 401);
+    }
+
+    // Bloqueado incluso con la contraseña correcta
+    let (status, body) = send(&app, login("secreto123")).await;
+    assert_eq!(status, 423, "{}", body);
+    assert_eq!(body["codigo"], "login_bloqueado");
+    assert_eq!(body["retry_after"], 600);
+
+    clock.advance(Duration::minutes(11));
+    let (status, body) = send(&app, login("secreto123")).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // El login correcto pone los fallos a cero
+    for _ in 0..2 {
+        send(&app, login("incorrecta")).await;
+    }
+    let (status, _) = send(&app, login("secreto123")).await;
+    assert_eq!(status, 200);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn the_ip_lock_ignores_spoofed_forwarded_addresses() {
+    std::env::set_var("LOGIN_MAX_FALLOS_IP", "4");
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let login = |i: u32, peer: &str, forwarded: &str| TestRequest::post()
+        .uri("/restaurants/login")
+        .peer_addr(peer.parse().unwrap())
+        .insert_header(("X-Forwarded-For", forwarded.to_string()))
+        .set_json(json!({ "name": format!("Nadie {}", i), "password": "incorrecta" }));
+
+    // Cambiar X-Forwarded-For en cada intento no esquiva el bloqueo de la IP
+    for i in 0..4 {
+        let (status, _) = send(&app, login(i, "192.0.2.40:40000", &format!("198.51.100.{}", i))).await;
+        assert_eq!(status, 401);
+    }
+    let (status, body) = send(&app, login(4, "192.0.2.40:40000", "198.51.100.4")).await;
+    assert_eq!(status, 429, "{}", body);
+
+    // Ni bloquea a otro cliente que dice venir de la IP bloqueada
+    let (status, _) = send(&app, login(5, "192.0.2.41:40000", "192.0.2.40")).await;
+    assert_eq!(status, 401);
+}
@@ -0,0 +1,175 @@
+//! Consulta pública de reservas por localizador contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::db::{localizador, LONGITUD_LOCALIZADOR};
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+#[test]
+fn localizers_avoid_ambiguous_characters() {
+    for aleatorio in [0, 1, u128::MAX, 0xdead_beef_cafe, 1 << 100] {
+        let codigo = localizador(aleatorio);
+        assert_eq!(codigo.len(), LONGITUD_LOCALIZADOR);
+        assert!(!codigo.contains(['0', 'O', '1', 'I']), "{}", codigo);
+    }
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn lookup_returns_status_and_emails_the_manage_link() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let app = common::init_app_with(&db, notifier.clone(), test_clock()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let codigo = reserva["localizador"].as_str().unwrap().to_string();
+
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/public/reservations/lookup")
+        .set_json(json!({ "localizador": codigo, "email": "otro@email.com" }))).await;
+    assert_eq!(status, 404);
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri("/public/reservations/lookup")
+        .set_json(json!({ "localizador": codigo.to_lowercase(), "email": "Juan@Email.com" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "pendiente");
+    assert_eq!(body["restaurante"], "La Tasca");
+    assert_eq!(body["enlace_enviado"], true);
+    assert!(!body.to_string().contains("/manage/"), "el enlace no va en la respuesta");
+
+    let enviados = notifier.outbox().unwrap().sent();
+    let [SentMessage::Email(email)] = &enviados[..] else {
+        panic!("se esperaba un email: {:?}", enviados);
+    };
+    assert_eq!(email.to, "juan@email.com");
+    let enlace = email.body.lines().find(|l| l.contains("/manage/")).unwrap();
+    let ruta = &enlace[enlace.find("/public/").unwrap()..];
+
+    let (status, body) = send(&app, TestRequest::get().uri(ruta)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["localizador"], json!(codigo));
+
+    // Agotadas las consultas de la IP, responde 429 aunque cambie la sesión
+    // (con un localizador distinto cada vez, para no bloquear ninguno)
+    let consultar = |ip: &str, sesion: &str, codigo: &str| TestRequest::post()
+        .uri("/public/reservations/lookup")
+        .peer_addr(format!("{}:40000", ip).parse().unwrap())
+        .insert_header(("X-Widget-Session", sesion.to_string()))
+        .set_json(json!({ "localizador": codigo, "email": "juan@email.com" }));
+    for (i, letra) in ('A'..='J').enumerate() {
+        let codigo = letra.to_string().repeat(LONGITUD_LOCALIZADOR);
+        let (status, _) = send(&app, consultar("10.0.0.1", &format!("sesion-{}", i), &codigo)).await;
+        assert_eq!(status, 404);
+    }
+    let (status, _) = send(&app, consultar("10.0.0.1", "otra", "KKKKKKKK")).await;
+    assert_eq!(status, 429);
+    let (status, _) = send(&app, consultar("10.0.0.2", "otra", "KKKKKKKK")).await;
+    assert_eq!(status, 404);
+    let consultas = db.repo.consultas_localizador()
+        .find_one(mongodb::bson::doc! { "ip": "10.0.0.1" })
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(consultas.consultas, 11);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn a_locator_is_locked_after_failed_lookups_from_any_ip() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let codigo = reserva["localizador"].as_str().unwrap().to_string();
+
+    let consultar = |ip: String, email: &str| TestRequest::post()
+        .uri("/public/reservations/lookup")
+        .peer_addr(format!("{}:40000", ip).parse().unwrap())
+        .set_json(json!({ "localizador": codigo, "email": email }));
+
+    // Cada intento desde una IP distinta: el límite por IP no llega a saltar
+    for i in 0..5 {
+        let (status, _) = send(&app, consultar(format!("10.0.1.{}", i), &format!("prueba{}@email.com", i))).await;
+        assert_eq!(status, 404);
+    }
+    let (status, body) = send(&app, consultar("10.0.1.100".to_string(), "prueba5@email.com")).await;
+    assert_eq!(status, 429, "{}", body);
+    // Bloqueado, ni siquiera el email correcto lo consulta
+    let (status, _) = send(&app, consultar("10.0.1.101".to_string(), "juan@email.com")).await;
+    assert_eq!(status, 429);
+
+    let fallos = db.repo.fallos_localizador()
+        .find_one(mongodb::bson::doc! { "localizador": &codigo })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(fallos.bloqueado_hasta > 0);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn customers_see_their_notes_but_never_internal_ones() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    // Desde el widget, las notas internas se ignoran
+    let mut body = reservation_body(&mesa, "2030-06-15", "21:00");
+    body["notas_cliente"] = json!("  Cumpleaños, trona ");
+    body["notas_internas"] = json!("Inventada por el cliente");
+    let (status, reserva) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(&body)).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let id = reserva["id"].as_str().unwrap().to_string();
+    let codigo = reserva["localizador"].as_str().unwrap().to_string();
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", id))
+        .set_json(json!({ "notas_internas": "Cliente habitual, mesa junto a la ventana" }))).await;
+    assert_eq!(status, 200);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-15")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body[0]["notas_cliente"], "Cumpleaños, trona");
+    assert_eq!(body[0]["notas_internas"], "Cliente habitual, mesa junto a la ventana");
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri("/public/reservations/lookup")
+        .set_json(json!({ "localizador": codigo, "email": "juan@email.com" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["notas_cliente"], "Cumpleaños, trona");
+    assert!(!body.to_string().contains("habitual"), "{}", body);
+
+    // Una nota vacía se quita; una demasiado larga se rechaza
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", id))
+        .set_json(json!({ "notas_cliente": "" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert!(body["reserva"]["notas_cliente"].is_null());
+    assert_eq!(body["reserva"]["notas_internas"], "Cliente habitual, mesa junto a la ventana");
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", id))
+        .set_json(json!({ "notas_internas": "x".repeat(501) }))).await;
+    assert_eq!(status, 400);
+}
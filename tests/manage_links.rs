@@ -0,0 +1,80 @@
+//! Confirmación y cancelación de reservas desde los enlaces de gestión
+//! contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::clock::Clock;
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+/// Ruta (desde `/public/`) del enlace del último email que acaba en `sufijo`
+fn link(notifier: &Notifier, sufijo: &str) -> String {
+    let enviados = notifier.outbox().unwrap().sent();
+    let Some(SentMessage::Email(email)) = enviados.last() else {
+        panic!("se esperaba un email: {:?}", enviados);
+    };
+    let linea = email.body.lines().find(|l| l.ends_with(sufijo)).expect("el email debe incluir el enlace");
+    linea[linea.find("/public/").unwrap()..].to_string()
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn customers_confirm_and_cancel_from_their_links() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "politica_cancelacion": { "horas_cancelacion": 2 } }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Sin verificación, el email de confirmación llega al reservar
+    let (status, reserva) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let confirmar = link(&notifier, "/confirm");
+    let cancelar = link(&notifier, "/cancel");
+
+    // Abrir el enlace solo muestra la reserva
+    let (status, body) = send(&app, TestRequest::get().uri(&cancelar)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "pendiente");
+    assert_eq!(body["localizador"], reserva["localizador"]);
+
+    let (status, body) = send(&app, TestRequest::post().uri(&confirmar)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "pendiente");
+    assert_eq!(body["confirmada_por_cliente_en"], clock.timestamp());
+    let (_, reservas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-15")).await;
+    assert_eq!(reservas[0]["confirmada_por_cliente_en"], clock.timestamp());
+
+    let (status, body) = send(&app, TestRequest::post().uri(&cancelar)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "cancelada");
+    let (status, _) = send(&app, TestRequest::post().uri(&cancelar)).await;
+    assert_eq!(status, 409);
+    let (status, _) = send(&app, TestRequest::post().uri(&confirmar)).await;
+    assert_eq!(status, 409);
+
+    let (status, _) = send(&app, TestRequest::post().uri("/public/reservations/no-existe/cancel")).await;
+    assert_eq!(status, 404);
+
+    // La política de cancelación también se aplica al cliente
+    let (status, body) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(reservation_body(&mesa, "2030-06-01", "13:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = send(&app, TestRequest::post().uri(&link(&notifier, "/cancel"))).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["codigo"], "cancelacion_tardia");
+}
@@ -0,0 +1,72 @@
+//! Opciones de menú y preselección contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn preselection_is_stored_and_summarized_for_the_kitchen() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa_1 = create_table(&app, &restaurant, "Mesa 1").await;
+    let mesa_2 = create_table(&app, &restaurant, "Mesa 2").await;
+
+    let (status, degustacion) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/menu-options")
+        .set_json(json!({ "nombre": "Menú degustación", "precio_centimos": 8500 }))).await;
+    assert_eq!(status, 200, "{}", degustacion);
+    let (_, maridaje) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/menu-options")
+        .set_json(json!({ "nombre": "Maridaje" }))).await;
+
+    let (_, publicas) = send(&app, TestRequest::get()
+        .uri(&format!("/public/restaurants/{}/menu-options", restaurant.id))).await;
+    assert_eq!(publicas.as_array().unwrap().len(), 2);
+
+    let mut body = reservation_body(&mesa_1, "2030-06-15", "21:00");
+    body["preseleccion"] = json!([
+        { "id_opcion": degustacion["id"], "cantidad": 1 },
+        { "id_opcion": degustacion["id"], "cantidad": 1 },
+        { "id_opcion": maridaje["id"], "cantidad": 1 }
+    ]);
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(body)).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let mut body = reservation_body(&mesa_2, "2030-06-15", "20:00");
+    body["preseleccion"] = json!([{ "id_opcion": degustacion["id"], "cantidad": 2 }]);
+    let (status, body) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(body)).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, cocina) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/kitchen/preorders?fecha=2030-06-15")).await;
+    assert_eq!(status, 200, "{}", cocina);
+    assert_eq!(cocina["reservas"][0]["hora"], "20:00");
+    assert_eq!(cocina["reservas"][1]["preseleccion"][0]["cantidad"], 2, "las repetidas se suman");
+    assert_eq!(cocina["totales"], json!([
+        { "id_opcion": maridaje["id"], "nombre": "Maridaje", "cantidad": 1 },
+        { "id_opcion": degustacion["id"], "nombre": "Menú degustación", "cantidad": 4 }
+    ]));
+
+    // Una opción desactivada ya no se puede elegir
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/menu-options/{}", maridaje["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 204);
+
+    let mut body = reservation_body(&mesa_1, "2030-06-16", "21:00");
+    body["preseleccion"] = json!([{ "id_opcion": maridaje["id"], "cantidad": 1 }]);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(body)).await;
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,112 @@
+//! Mensajes entre clientes y restaurantes contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::api::messages::MAX_MENSAJES_SIN_LEER;
+use pispas_reservation::notifications::{EmailMessage, Notifier, SentMessage};
+use serde_json::json;
+
+/// Último email enviado
+fn last_email(notifier: &Notifier) -> EmailMessage {
+    match notifier.outbox().unwrap().sent().last() {
+        Some(SentMessage::Email(email)) => email.clone(),
+        otro => panic!("se esperaba un email: {:?}", otro),
+    }
+}
+
+/// Ruta (desde `/public/`) del enlace de un email que acaba en `sufijo`
+fn link(email: &EmailMessage, sufijo: &str) -> String {
+    let linea = email.body.lines().find(|l| l.ends_with(sufijo)).expect("el email debe incluir el enlace");
+    linea[linea.find("/public/").unwrap()..].to_string()
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn customers_and_staff_exchange_messages() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let app = common::init_app_with(&db, notifier.clone(), test_clock()).await;
+    let outbox = notifier.outbox().unwrap();
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "email_alertas": "sala@latasca.es" }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, reserva) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let hilo = link(&last_email(&notifier), "/messages");
+    let (_, reservas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-15")).await;
+    let id_reserva = reservas[0]["id"].as_str().unwrap().to_string();
+    outbox.clear();
+
+    // El cliente escribe; el restaurante recibe un aviso por conversación
+    let (status, _) = send(&app, TestRequest::post().uri(&hilo).set_json(json!({ "texto": "  " }))).await;
+    assert_eq!(status, 400);
+    for texto in ["¿Tenéis trona?", "Somos dos adultos y un bebé"] {
+        let (status, body) = send(&app, TestRequest::post().uri(&hilo).set_json(json!({ "texto": texto }))).await;
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(body["autor"], "cliente");
+    }
+    let enviados = outbox.sent();
+    assert_eq!(enviados.len(), 1, "{:?}", enviados);
+    let aviso = last_email(&notifier);
+    assert_eq!(aviso.to, "sala@latasca.es");
+    assert!(aviso.body.contains("¿Tenéis trona?"), "{}", aviso.body);
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations/messages/unread")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["total"], 2);
+    assert_eq!(body["reservas"][0]["id_reserva"], id_reserva.as_str());
+
+    // Abrir el hilo en el panel los marca como leídos
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/reservations/{}/messages", id_reserva))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations/messages/unread")).await;
+    assert_eq!(body["total"], 0);
+
+    // La respuesta llega al cliente por email con el enlace al hilo
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/messages", id_reserva))
+        .set_json(json!({ "texto": "Sí, os la dejamos preparada" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["autor"], "restaurante");
+    let respuesta = last_email(&notifier);
+    assert_eq!(respuesta.to, "juan@email.com");
+    assert_eq!(link(&respuesta, "/messages"), hilo);
+
+    let (status, body) = send(&app, TestRequest::get().uri(&hilo)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["no_leidos"], 1);
+    assert_eq!(body["localizador"], reserva["localizador"]);
+    let mensajes = body["mensajes"].as_array().unwrap();
+    assert_eq!(mensajes.len(), 3);
+    assert!(mensajes[2].get("usuario").is_none());
+    let (_, body) = send(&app, TestRequest::get().uri(&hilo)).await;
+    assert_eq!(body["no_leidos"], 0);
+
+    // Sin que el restaurante lea, el cliente no puede escribir sin fin
+    for _ in 0..MAX_MENSAJES_SIN_LEER {
+        let (status, _) = send(&app, TestRequest::post().uri(&hilo).set_json(json!({ "texto": "¿Hola?" }))).await;
+        assert_eq!(status, 200);
+    }
+    let (status, body) = send(&app, TestRequest::post().uri(&hilo).set_json(json!({ "texto": "¿Hola?" }))).await;
+    assert_eq!(status, 409);
+    assert_eq!(body["codigo"], "demasiados_mensajes");
+
+    let (status, _) = send(&app, TestRequest::get().uri("/public/reservations/no-existe/messages")).await;
+    assert_eq!(status, 404);
+}
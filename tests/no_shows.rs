@@ -0,0 +1,74 @@
+//! No-shows de cada cliente contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, reservation_body, send, TestDb, TestRestaurant};
+use serde_json::{json, Value};
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn repeat_no_shows_are_flagged_and_held_for_review() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri("/restaurants/register")
+        .set_json(json!({
+            "objid_pispas": "objid-la-tasca",
+            "name": "La Tasca",
+            "password": "secreto123",
+            "confirmar_automaticamente": true
+        }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let restaurant = TestRestaurant {
+        id: body["id"].as_str().unwrap().to_string(),
+        token: body["access_token"].as_str().unwrap().to_string(),
+    };
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "riesgo": { "no_shows_revision": 0 } }))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "riesgo": { "no_shows_revision": 1 } }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let reservar_widget = |cuerpo: Value| TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(cuerpo);
+    let (status, reserva) = send(&app, reservar_widget(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    assert_eq!(reserva["estado"], "confirmada");
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/no-show", reserva["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 200);
+
+    let (_, clientes) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/customers")).await;
+    assert_eq!(clientes[0]["no_shows"], 1, "{}", clientes);
+    assert_eq!(clientes[0]["visitas"], 0);
+
+    // El panel lo ve al crear la siguiente reserva del mismo cliente
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-16", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["no_shows_previos"], 1);
+
+    // Desde el widget queda pendiente de revisión; otro cliente, no
+    let (status, body) = send(&app, reservar_widget(reservation_body(&mesa, "2030-06-17", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "pendiente");
+    let mut cuerpo = reservation_body(&mesa, "2030-06-18", "21:00");
+    cuerpo["email_cliente"] = json!("ana@email.com");
+    cuerpo["telefono_cliente"] = json!("+34 611 111 111");
+    let (status, body) = send(&app, reservar_widget(cuerpo)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "confirmada");
+    // El widget nunca enseña los no-shows al cliente
+    assert!(body.get("no_shows_previos").is_none());
+}
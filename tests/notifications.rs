@@ -0,0 +1,184 @@
+//! Contenido de las notificaciones enviadas a clientes
+//!
+//! Usa el proveedor `memory` para inspeccionar los mensajes. Ver
+//! `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use pispas_reservation::notifications::{LogProvider, Notifier, SentMessage};
+use serde_json::json;
+use std::sync::Arc;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn email_verification_link_is_sent_and_verifies_public_booking() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let app = common::init_app_with(&db, notifier.clone(), common::test_clock()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "verificacion_cliente": "email" }))).await;
+    assert_eq!(status, 200);
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "sin_confirmar");
+    assert_eq!(body["verificacion"], "email");
+
+    let sent = notifier.outbox().unwrap().sent();
+    assert_eq!(sent.len(), 1);
+    let SentMessage::Email(email) = &sent[0] else {
+        panic!("se esperaba un email: {:?}", sent[0]);
+    };
+    assert_eq!(email.to, "juan@email.com");
+    assert!(email.subject.contains("La Tasca"));
+
+    let path = email.body
+        .split_whitespace()
+        .find_map(|word| word.find("/public/reservations/verify/").map(|i| word[i..].to_string()))
+        .expect("el email debe incluir el enlace de verificación");
+
+    let (status, body) = send(&app, TestRequest::get().uri(&path)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["estado"], "pendiente");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn dev_endpoint_exposes_memory_outbox() {
+    std::env::set_var("DEV_ROUTES", "true");
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let app = common::init_app_with(&db, notifier.clone(), common::test_clock()).await;
+
+    let restaurant = register_restaurant(&app, "Casa Pepe").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "verificacion_cliente": "telefono" }))).await;
+    assert_eq!(status, 200);
+
+    let (status, _) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200);
+
+    let (status, body) = send(&app, TestRequest::get().uri("/dev/notifications")).await;
+    assert_eq!(status, 200);
+    assert_eq!(body[0]["canal"], "sms");
+    assert_eq!(body[0]["to"], "+34 600 000 000");
+
+    let (status, _) = send(&app, TestRequest::delete().uri("/dev/notifications")).await;
+    assert_eq!(status, 204);
+    assert!(notifier.outbox().unwrap().sent().is_empty());
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn dev_endpoint_is_hidden_without_memory_provider() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::new(Arc::new(LogProvider), Arc::new(LogProvider));
+    let app = common::init_app_with(&db, notifier, common::test_clock()).await;
+
+    let (status, _) = send(&app, TestRequest::get().uri("/dev/notifications")).await;
+    assert_eq!(status, 404);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn sms_code_expires_when_clock_advances() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = common::test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "El Rincón").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "verificacion_cliente": "telefono" }))).await;
+
+    let (_, body) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    let id_reserva = body["id"].as_str().unwrap().to_string();
+
+    let SentMessage::Sms(sms) = &notifier.outbox().unwrap().sent()[0] else {
+        panic!("se esperaba un SMS");
+    };
+    let codigo = sms.body.rsplit(' ').next().unwrap().to_string();
+
+    clock.advance(chrono::Duration::minutes(16));
+
+    let (status, _) = send(&app, TestRequest::post()
+        .uri(&format!("/public/reservations/{}/verify", id_reserva))
+        .set_json(json!({ "codigo": codigo }))).await;
+    assert_eq!(status, 400);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn public_bookings_detect_the_customer_language() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "idioma": "ca" }))).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "idioma": "klingon" }))).await;
+    assert_eq!(status, 400);
+
+    let casos = [
+        ("2030-06-14", None, "+34 600 000 000", "en"),
+        ("2030-06-15", None, "+33 6 12 34 56 78", "fr"),
+        ("2030-06-16", None, "600 000 000", "ca"),
+        ("2030-06-17", Some("de-AT"), "+33 6 12 34 56 78", "de"),
+    ];
+    for (i, (fecha, idioma, telefono, _)) in casos.iter().enumerate() {
+        let mut body = reservation_body(&id_mesa, fecha, "21:00");
+        body["telefono_cliente"] = json!(telefono);
+        if let Some(idioma) = idioma {
+            body["idioma"] = json!(idioma);
+        }
+        let mut req = TestRequest::post()
+            .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+            .insert_header(("X-Widget-Session", format!("sesion-{}", i)))
+            .set_json(body);
+        if i == 0 {
+            req = req.insert_header(("Accept-Language", "en-GB,en;q=0.9,es;q=0.5"));
+        }
+        let (status, body) = send(&app, req).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations")).await;
+    assert_eq!(status, 200);
+    for (fecha, _, _, esperado) in casos {
+        let reserva = body.as_array().unwrap().iter().find(|r| r["fecha"] == fecha).unwrap();
+        assert_eq!(reserva["idioma"], esperado, "{}", fecha);
+    }
+
+    let mut body = reservation_body(&id_mesa, "2030-06-18", "21:00");
+    body["idioma"] = json!("ja");
+    let (status, _) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(body)).await;
+    assert_eq!(status, 400);
+}
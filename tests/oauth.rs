@@ -0,0 +1,138 @@
+//! Inicio de sesión con Google contra un MongoDB efímero
+//!
+//! Google se sustituye por un servidor local: el endpoint de tokens devuelve
+//! el `code` recibido como token de acceso y `userinfo` responde con la
+//! cuenta `sub-<code>` y el email `<code>`, así cada test elige la cuenta de
+//! Google con el código que envía al callback.
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use actix_web::test::{self, TestRequest};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use common::{bearer, register_restaurant, send, TestDb};
+use serde_json::json;
+
+/// Arranca (una vez por proceso) el Google falso y configura el cliente OAuth
+fn fake_google() {
+    static URL: OnceLock<String> = OnceLock::new();
+    let url = URL.get_or_init(|| {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                let server = HttpServer::new(|| {
+                    App::new()
+                        .route("/token", web::post().to(|form: web::Form<HashMap<String, String>>| async move {
+                            HttpResponse::Ok().json(json!({ "access_token": form["code"], "token_type": "Bearer" }))
+                        }))
+                        .route("/userinfo", web::get().to(|req: HttpRequest| async move {
+                            let code = req.headers().get("Authorization").unwrap().to_str().unwrap()["Bearer ".len()..].to_string();
+                            HttpResponse::Ok().json(json!({ "sub": format!("sub-{}", code), "email": code, "email_verified": true }))
+                        }))
+                })
+                    .workers(1)
+                    .bind("127.0.0.1:0")
+                    .unwrap();
+                tx.send(format!("http://{}", server.addrs()[0])).unwrap();
+                server.run().await.unwrap();
+            });
+        });
+        rx.recv().unwrap()
+    });
+
+    std::env::set_var("GOOGLE_CLIENT_ID", "test-client");
+    std::env::set_var("GOOGLE_CLIENT_SECRET", "test-secret");
+    std::env::set_var("GOOGLE_TOKEN_URL", format!("{}/token", url));
+    std::env::set_var("GOOGLE_USERINFO_URL", format!("{}/userinfo", url));
+}
+
+/// Llama a `/auth/google/start` y devuelve el `state` de la redirección
+async fn start<S>(app: &S, req: TestRequest) -> String
+where
+    S: actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>,
+{
+    let resp = test::call_service(app, req.uri("/auth/google/start").to_request()).await;
+    assert_eq!(resp.status().as_u16(), 302);
+    let location = resp.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("https://accounts.google.com/"), "{}", location);
+
+    location.split('&')
+        .find_map(|param| param.strip_prefix("state="))
+        .expect("la redirección no lleva state")
+        .to_string()
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn owner_signs_in_with_google_account_matching_email() {
+    fake_google();
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let (status, registro) = send(&app, TestRequest::post()
+        .uri("/restaurants/register")
+        .set_json(json!({
+            "objid_pispas": "PISPAS-001",
+            "name": "La Tasca",
+            "password": "secreto123",
+            "confirmar_automaticamente": false,
+            "email": "dueno@latasca.es"
+        }))).await;
+    assert_eq!(status, 200, "{}", registro);
+
+    // Una cuenta de Google sin restaurante no entra
+    let state = start(&app, TestRequest::get()).await;
+    let (status, _) = send(&app, TestRequest::get()
+        .uri(&format!("/auth/google/callback?code=otro@gmail.com&state={}", state))).await;
+    assert_eq!(status, 404);
+
+    let state = start(&app, TestRequest::get()).await;
+    let callback = format!("/auth/google/callback?code=dueno@latasca.es&state={}", state);
+    let (status, body) = send(&app, TestRequest::get().uri(&callback)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_ne!(body["access_token"], registro["access_token"], "cada login abre una sesión nueva");
+    assert_eq!(body["id_restaurante"], registro["id"]);
+
+    // El state solo vale una vez
+    let (status, _) = send(&app, TestRequest::get().uri(&callback)).await;
+    assert_eq!(status, 401);
+
+    let stored = db.repo.restaurants().find_one(mongodb::bson::doc! {}).await.unwrap().unwrap();
+    assert_eq!(stored.google_sub.as_deref(), Some("sub-dueno@latasca.es"));
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn logged_in_owner_links_any_google_account() {
+    fake_google();
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let tasca = register_restaurant(&app, "La Tasca").await;
+    let bodega = register_restaurant(&app, "La Bodega").await;
+
+    let state = start(&app, bearer(TestRequest::get(), &tasca.token)).await;
+    let (status, body) = send(&app, TestRequest::get()
+        .uri(&format!("/auth/google/callback?code=personal@gmail.com&state={}", state))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["id_restaurante"], tasca.id.as_str());
+
+    // Ya vinculada: entra sin token
+    let state = start(&app, TestRequest::get()).await;
+    let (status, body) = send(&app, TestRequest::get()
+        .uri(&format!("/auth/google/callback?code=personal@gmail.com&state={}", state))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["id_restaurante"], tasca.id.as_str());
+
+    // No se puede vincular la misma cuenta a otro restaurante
+    let state = start(&app, bearer(TestRequest::get(), &bodega.token)).await;
+    let (status, _) = send(&app, TestRequest::get()
+        .uri(&format!("/auth/google/callback?code=personal@gmail.com&state={}", state))).await;
+    assert_eq!(status, 409);
+
+    let (status, _) = send(&app, TestRequest::get()
+        .uri("/auth/google/callback?error=access_denied&state=x")).await;
+    assert_eq!(status, 401);
+}
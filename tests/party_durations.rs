@@ -0,0 +1,65 @@
+//! Duración de las reservas según el tamaño del grupo contra un MongoDB
+//! efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn larger_parties_keep_their_table_longer() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let ajustes = |duraciones: serde_json::Value| bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "duracion_reserva_minutos": 60, "duraciones_grupo": duraciones }));
+    let (status, _) = send(&app, ajustes(json!([
+        { "desde_personas": 3, "minutos": 90 },
+        { "desde_personas": 3, "minutos": 120 }
+    ]))).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, ajustes(json!([{ "desde_personas": 3, "minutos": 5 }]))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, ajustes(json!([{ "desde_personas": 3, "minutos": 120 }]))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let reservar = |hora: &str, personas: i32| {
+        let mut cuerpo = reservation_body(&mesa, "2030-06-15", hora);
+        cuerpo["numero_personas"] = json!(personas);
+        bearer(TestRequest::post(), &restaurant.token).uri("/reservations").set_json(cuerpo)
+    };
+    let libres = |hora: &str, personas: i32| bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables/available?fecha=2030-06-15&hora={}&personas={}", hora, personas));
+
+    // La pareja de las 13:00 deja la mesa a las 14:00
+    let (status, body) = send(&app, reservar("13:00", 2)).await;
+    assert_eq!(status, 200, "{}", body);
+    let (_, mesas) = send(&app, libres("14:00", 4)).await;
+    assert_eq!(mesas.as_array().unwrap().len(), 1, "{}", mesas);
+
+    // El grupo de cuatro no cabe a las 11:30: ocuparía la mesa hasta las 13:30
+    let (_, mesas) = send(&app, libres("11:30", 4)).await;
+    assert_eq!(mesas.as_array().unwrap().len(), 0, "{}", mesas);
+    let (_, mesas) = send(&app, libres("11:30", 2)).await;
+    assert_eq!(mesas.as_array().unwrap().len(), 1, "{}", mesas);
+    let (status, _) = send(&app, reservar("11:30", 4)).await;
+    assert_eq!(status, 409);
+    let (status, body) = send(&app, reservar("11:00", 4)).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Y el grupo de las 20:00 choca con una pareja a las 21:30
+    let (status, body) = send(&app, reservar("20:00", 4)).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, _) = send(&app, reservar("21:30", 2)).await;
+    assert_eq!(status, 409);
+    let (status, body) = send(&app, reservar("22:00", 2)).await;
+    assert_eq!(status, 200, "{}", body);
+}
@@ -0,0 +1,75 @@
+//! Recuperación de contraseña contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, send, test_clock, TestDb};
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn emailed_token_resets_the_password_once() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let app = common::init_app_with(&db, notifier.clone(), test_clock()).await;
+
+    let (status, registro) = send(&app, TestRequest::post()
+        .uri("/restaurants/register")
+        .set_json(json!({
+            "objid_pispas": "objid-tasca",
+            "name": "La Tasca",
+            "password": "secreto123",
+            "confirmar_automaticamente": false,
+            "email": "dueno@latasca.es"
+        }))).await;
+    assert_eq!(status, 200, "{}", registro);
+
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/restaurants/forgot-password")
+        .set_json(json!({ "name": "la tasca" }))).await;
+    assert_eq!(status, 200);
+
+    let token = match &notifier.outbox().unwrap().sent()[..] {
+        [SentMessage::Email(email)] => {
+            assert_eq!(email.to, "dueno@latasca.es");
+            email.body.lines()
+                .find(|linea| linea.len() == 36 && linea.matches('-').count() == 4)
+                .expect("token en el email")
+                .to_string()
+        }
+        otros => panic!("se esperaba un email: {:?}", otros),
+    };
+
+    // Un nombre desconocido responde igual y no envía nada
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/restaurants/forgot-password")
+        .set_json(json!({ "name": "No existe" }))).await;
+    assert_eq!(status, 200);
+    assert_eq!(notifier.outbox().unwrap().sent().len(), 1);
+
+    let (status, body) = send(&app, TestRequest::post()
+        .uri("/restaurants/reset-password")
+        .set_json(json!({ "token": token, "password": "nueva-clave" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let nuevo_token = body["access_token"].as_str().unwrap();
+
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/restaurants/login")
+        .set_json(json!({ "name": "La Tasca", "password": "nueva-clave" }))).await;
+    assert_eq!(status, 200);
+
+    // El token de acceso anterior deja de valer y el de recuperación no se reutiliza
+    let (status, _) = send(&app, bearer(TestRequest::get(), registro["access_token"].as_str().unwrap())
+        .uri("/restaurants/settings")).await;
+    assert_eq!(status, 401);
+    let (status, _) = send(&app, bearer(TestRequest::get(), nuevo_token)
+        .uri("/restaurants/settings")).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, TestRequest::post()
+        .uri("/restaurants/reset-password")
+        .set_json(json!({ "token": token, "password": "otra-clave" }))).await;
+    assert_eq!(status, 401);
+}
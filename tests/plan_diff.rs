@@ -0,0 +1,95 @@
+//! Comparación de planos: reglas puras y snapshots contra MongoDB
+//!
+//! Los tests HTTP necesitan MongoDB; ver `tests/common/mod.rs`.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, send, TestDb};
+use mongodb::bson::oid::ObjectId;
+use pispas_reservation::db::{normalize_name, Mesa};
+use pispas_reservation::plan::diff;
+use serde_json::json;
+
+fn mesa(nombre: &str, pos_x: f32, pos_y: f32) -> Mesa {
+    Mesa {
+        id: Some(ObjectId::new()),
+        id_restaurante: ObjectId::new(),
+        tipo: "mesa".to_string(),
+        nombre: nombre.to_string(),
+        nombre_normalizado: normalize_name(nombre),
+        pos_x,
+        pos_y,
+        size_x: 80.0,
+        size_y: 80.0,
+        forma: "cuadrado".to_string(),
+        reservable: true,
+        min_personas: None,
+        max_personas: Some(4),
+        id_planta: None,
+        uuid: None,
+        created_at: 0,
+    }
+}
+
+#[test]
+fn tables_are_matched_by_name_across_recreations() {
+    let antes = vec![mesa("Mesa 1", 0.0, 0.0), mesa("Mesa 2", 100.0, 0.0), mesa("Barra", 0.0, 200.0)];
+    // El editor vuelve a crear las mesas con IDs nuevos
+    let despues = vec![mesa("mesa 1", 0.0, 0.0), mesa("Mesa 2", 150.0, 0.0), mesa("Terraza 1", 0.0, 400.0)];
+
+    let resultado = diff(&antes, &despues);
+
+    assert_eq!(resultado.eliminadas.iter().map(|m| m.nombre.as_str()).collect::<Vec<_>>(), ["Barra"]);
+    assert_eq!(resultado.anadidas.iter().map(|m| m.nombre.as_str()).collect::<Vec<_>>(), ["Terraza 1"]);
+    assert_eq!(resultado.movidas.len(), 1);
+    assert_eq!(resultado.movidas[0].nombre, "Mesa 2");
+    assert_eq!(resultado.movidas[0].antes.pos_x, 100.0);
+    assert_eq!(resultado.movidas[0].despues.pos_x, 150.0);
+}
+
+#[test]
+fn identical_plans_and_resizes() {
+    let plano = vec![mesa("Mesa 1", 0.0, 0.0), mesa("Mesa 2", 100.0, 0.0)];
+    assert!(diff(&plano, &plano).is_empty());
+
+    let mut redimensionado = plano.clone();
+    redimensionado[1].size_x = 160.0;
+    let resultado = diff(&plano, &redimensionado);
+    assert_eq!(resultado.movidas.len(), 1, "cambiar el tamaño cuenta como mover");
+    assert!(resultado.anadidas.is_empty() && resultado.eliminadas.is_empty());
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn snapshots_can_be_compared_with_the_current_plan() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, snapshot) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables/snapshots")
+        .set_json(json!({ "nombre": "Invierno" }))).await;
+    assert_eq!(status, 200, "{}", snapshot);
+    assert_eq!(snapshot["mesas"], 1);
+
+    create_table(&app, &restaurant, "Terraza 1").await;
+
+    let (status, cambios) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables/diff?from={}", snapshot["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 200, "{}", cambios);
+    assert_eq!(cambios["to"], "current");
+    assert_eq!(cambios["anadidas"][0]["nombre"], "Terraza 1");
+    assert_eq!(cambios["eliminadas"], json!([]));
+    assert_eq!(cambios["movidas"], json!([]));
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/tables/diff?from=ayer")).await;
+    assert_eq!(status, 400);
+
+    let (_, snapshots) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/tables/snapshots")).await;
+    assert_eq!(snapshots[0]["nombre"], "Invierno");
+}
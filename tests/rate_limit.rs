@@ -0,0 +1,112 @@
+//! Límite de peticiones: cubos de tokens y respuesta 429 del middleware
+
+use std::time::{Duration, Instant};
+use actix_web::middleware::from_fn;
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{web, App, HttpResponse};
+use pispas_reservation::api::rate_limit::{limit_requests, Limitador};
+
+#[test]
+fn buckets_refill_over_time_and_are_independent_per_key() {
+    let mut limitador = Limitador::new(60);
+    let ahora = Instant::now();
+
+    for _ in 0..60 {
+        assert!(limitador.check("ip:10.0.0.1", ahora).is_ok());
+    }
+    assert_eq!(limitador.check("ip:10.0.0.1", ahora), Err(1));
+    assert!(limitador.check("ip:10.0.0.2", ahora).is_ok(), "otra IP tiene su propio cubo");
+
+    let despues = ahora + Duration::from_secs(5);
+    for _ in 0..5 {
+        assert!(limitador.check("ip:10.0.0.1", despues).is_ok());
+    }
+    assert!(limitador.check("ip:10.0.0.1", despues).is_err());
+}
+
+#[test]
+fn a_flood_of_new_keys_does_not_reset_a_limited_client() {
+    let mut limitador = Limitador::new(1);
+    let ahora = Instant::now();
+    assert!(limitador.check("ip:10.0.0.1", ahora).is_ok());
+
+    for i in 0..25_000u64 {
+        let momento = ahora + Duration::from_millis(i);
+        assert!(limitador.check(&format!("ip:10.1.{}.{}", i / 256, i % 256), momento).is_ok());
+        if i % 100 == 0 {
+            assert!(limitador.check("ip:10.0.0.1", momento).is_err(), "cubo reiniciado tras {} claves", i);
+        }
+    }
+}
+
+#[actix_web::test]
+async fn login_attempts_beyond_the_limit_get_429_with_retry_after() {
+    let app = init_service(
+        App::new().service(
+            web::scope("")
+                .wrap(from_fn(limit_requests))
+                .route("/restaurants/login", web::post().to(HttpResponse::Ok)),
+        ),
+    ).await;
+    let login = || TestRequest::post()
+        .uri("/restaurants/login")
+        .peer_addr("192.0.2.10:40000".parse().unwrap())
+        .to_request();
+
+    for _ in 0..10 {
+        assert_eq!(call_service(&app, login()).await.status(), 200);
+    }
+
+    let resp = call_service(&app, login()).await;
+    assert_eq!(resp.status(), 429);
+    let retry_after: u64 = resp.headers().get("Retry-After").unwrap().to_str().unwrap().parse().unwrap();
+    assert!((1..=6).contains(&retry_after), "{}", retry_after);
+}
+
+#[actix_web::test]
+async fn rotating_x_forwarded_for_does_not_get_a_new_bucket() {
+    let app = init_service(
+        App::new().service(
+            web::scope("")
+                .wrap(from_fn(limit_requests))
+                .route("/restaurants/login", web::post().to(HttpResponse::Ok)),
+        ),
+    ).await;
+
+    for i in 0..10 {
+        let req = TestRequest::post()
+            .uri("/restaurants/login")
+            .peer_addr("192.0.2.20:40000".parse().unwrap())
+            .insert_header(("X-Forwarded-For", format!("198.51.100.{}", i)))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), 200);
+    }
+
+    let req = TestRequest::post()
+        .uri("/restaurants/login")
+        .peer_addr("192.0.2.20:40000".parse().unwrap())
+        .insert_header(("X-Forwarded-For", "198.51.100.99"))
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 429);
+}
+
+#[actix_web::test]
+async fn unknown_bearer_tokens_are_limited_per_ip() {
+    let app = init_service(
+        App::new().service(
+            web::scope("")
+                .wrap(from_fn(limit_requests))
+                .route("/reservations", web::get().to(HttpResponse::Ok)),
+        ),
+    ).await;
+    let guess = |i: u32| TestRequest::get()
+        .uri("/reservations")
+        .peer_addr("192.0.2.30:40000".parse().unwrap())
+        .insert_header(("Authorization", format!("Bearer token-{}", i)))
+        .to_request();
+
+    for i in 0..10 {
+        assert_eq!(call_service(&app, guess(i)).await.status(), 200);
+    }
+    assert_eq!(call_service(&app, guess(10)).await.status(), 429, "cada token nuevo no tiene su propio cubo");
+}
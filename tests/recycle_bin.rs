@@ -0,0 +1,98 @@
+//! Confirmación y papelera del vaciado de mesas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn clearing_tables_needs_confirmation_and_can_be_undone() {
+    let db = TestDb::start().await;
+    let clock = common::test_clock();
+    let app = common::init_app_with(&db, pispas_reservation::notifications::Notifier::memory(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    create_table(&app, &restaurant, "Mesa 2").await;
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+
+    let clear = |confirmacion: &str| format!(
+        "/tables/clear?id_restaurante={}&confirmacion={}", restaurant.id, confirmacion,
+    );
+    let mesas = || bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables?id_restaurante={}", restaurant.id));
+
+    // Sin confirmación no se borra nada
+    let (status, aviso) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/tables/clear?id_restaurante={}", restaurant.id))).await;
+    assert_eq!(status, 428);
+    assert_eq!(aviso["mesas"], 2);
+    let token = aviso["confirmacion"].as_str().unwrap().to_string();
+    let (_, body) = send(&app, mesas()).await;
+    assert_eq!(body.as_array().unwrap().len(), 2);
+
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token).uri(&clear("inventado"))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, bearer(TestRequest::delete(), &restaurant.token).uri(&clear(&token))).await;
+    assert_eq!(status, 200, "{}", body);
+    let (_, body) = send(&app, mesas()).await;
+    assert!(body.as_array().unwrap().is_empty());
+
+    // El token solo sirve una vez
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token).uri(&clear(&token))).await;
+    assert_eq!(status, 400);
+
+    // Deshacer devuelve las mesas con sus IDs
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token).uri("/tables/undo-last")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["mesas"], 2);
+    let (_, body) = send(&app, mesas()).await;
+    assert!(body.as_array().unwrap().iter().any(|mesa| mesa["id"] == id_mesa.as_str()));
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token).uri("/tables/undo-last")).await;
+    assert_eq!(status, 404);
+
+    let (status, audit) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/audit?accion=restaurar_mesa")).await;
+    assert_eq!(status, 200);
+    assert_eq!(audit.as_array().unwrap().len(), 2);
+
+    // Pasada la ventana ya no se puede deshacer
+    let (_, aviso) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/tables/clear?id_restaurante={}", restaurant.id))).await;
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&clear(aviso["confirmacion"].as_str().unwrap()))).await;
+    assert_eq!(status, 200);
+    clock.advance(Duration::minutes(16));
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token).uri("/tables/undo-last")).await;
+    assert_eq!(status, 404);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn undo_refuses_to_duplicate_table_names() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (_, aviso) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/tables/clear?id_restaurante={}", restaurant.id))).await;
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!(
+            "/tables/clear?id_restaurante={}&confirmacion={}",
+            restaurant.id, aviso["confirmacion"].as_str().unwrap(),
+        ))).await;
+    assert_eq!(status, 200);
+
+    create_table(&app, &restaurant, "mesa 1").await;
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token).uri("/tables/undo-last")).await;
+    assert_eq!(status, 409);
+}
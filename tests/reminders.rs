@@ -0,0 +1,103 @@
+//! Recordatorios de reserva contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::clock::Clock;
+use pispas_reservation::jobs::reminders;
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reminders_are_sent_once_before_each_reservation() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+    let outbox = notifier.outbox().unwrap();
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "recordatorio_horas": 0 }))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "recordatorio_horas": 24 }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let mut ids = Vec::new();
+    for (fecha, hora) in [("2030-06-02", "10:00"), ("2030-06-05", "20:00"), ("2030-06-01", "21:00")] {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, fecha, hora))).await;
+        assert_eq!(status, 200, "{}", body);
+        ids.push(body["id"].as_str().unwrap().to_string());
+    }
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", ids[2]))).await;
+    assert_eq!(status, 200);
+    outbox.clear();
+
+    // Solo la de mañana a las 10:00 está a menos de 24 horas
+    assert_eq!(reminders::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 1);
+    match &outbox.sent()[..] {
+        [SentMessage::Email(email)] => {
+            assert_eq!(email.to, "juan@email.com");
+            assert!(email.body.contains("2030-06-02 a las 10:00"), "{}", email.body);
+        }
+        otros => panic!("se esperaba un email: {:?}", otros),
+    }
+    let (_, body) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations?fecha=2030-06-02")).await;
+    assert_eq!(body[0]["recordatorio_enviado_en"], clock.timestamp());
+
+    // Una pasada posterior (o tras un reinicio) no lo repite
+    assert_eq!(reminders::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 0);
+
+    // Con otra hora se recuerda de nuevo
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/reservations/{}", ids[0]))
+        .set_json(json!({ "hora": "11:00" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(reminders::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 1);
+
+    clock.advance(Duration::days(4));
+    assert_eq!(reminders::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 1);
+    assert_eq!(outbox.sent().len(), 3);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reminders_use_the_restaurant_time_zone() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+    let outbox = notifier.outbox().unwrap();
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "recordatorio_horas": 2, "zona_horaria": "America/New_York" }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Las 12:00 UTC del reloj de los tests son las 08:00 en Nueva York: la
+    // reserva de las 09:30 está a hora y media
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-01", "09:30"))).await;
+    assert_eq!(status, 200, "{}", body);
+    outbox.clear();
+
+    assert_eq!(reminders::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 1);
+    assert_eq!(outbox.sent().len(), 1);
+}
@@ -0,0 +1,113 @@
+//! ID de petición contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use std::sync::Mutex;
+use actix_web::test::{self, TestRequest};
+use async_trait::async_trait;
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, unwrap_envelope, TestDb};
+use pispas_reservation::api::AppResult;
+use pispas_reservation::jobs::{reminders, webhook_delivery::{self, EntregaWebhook}};
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+/// Receptor de webhooks que guarda el ID de petición de cada entrega
+#[derive(Default)]
+struct Receptor {
+    request_ids: Mutex<Vec<Option<String>>>,
+}
+
+#[async_trait]
+impl EntregaWebhook for Receptor {
+    async fn post(&self, _url: &str, _firma: &str, request_id: Option<&str>, _cuerpo: Vec<u8>) -> AppResult<()> {
+        self.request_ids.lock().unwrap().push(request_id.map(str::to_string));
+        Ok(())
+    }
+}
+
+fn email_request_ids(notifier: &Notifier) -> Vec<Option<String>> {
+    notifier.outbox().unwrap().sent().into_iter()
+        .map(|mensaje| match mensaje {
+            SentMessage::Email(email) => email.request_id,
+            SentMessage::Sms(sms) => sms.request_id,
+        })
+        .collect()
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn errors_carry_the_request_id() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+
+    // Se respeta el ID que llega del balanceador
+    let resp = test::call_service(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .insert_header(("X-Request-Id", "lb-42"))
+        .set_json(json!({ "ips_permitidas": ["oficina"] }))
+        .to_request()).await;
+    assert_eq!(resp.status(), 400);
+    assert_eq!(resp.headers().get("x-request-id").unwrap(), "lb-42");
+    let body = unwrap_envelope(test::read_body_json(resp).await);
+    assert_eq!(body["request_id"], "lb-42");
+
+    // Uno inválido se sustituye por uno generado
+    let resp = test::call_service(&app, TestRequest::get()
+        .uri("/tables")
+        .insert_header(("X-Request-Id", "con espacios"))
+        .to_request()).await;
+    assert_eq!(resp.status(), 401);
+    let generado = resp.headers().get("x-request-id").unwrap().to_str().unwrap().to_string();
+    assert!(uuid::Uuid::parse_str(&generado).is_ok());
+    let body = unwrap_envelope(test::read_body_json(resp).await);
+    assert_eq!(body["request_id"], generado.as_str());
+
+    // Las respuestas correctas también llevan la cabecera
+    let resp = test::call_service(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("/tables?id_restaurante={}", restaurant.id))
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert!(resp.headers().contains_key("x-request-id"));
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn background_jobs_keep_the_originating_request_id() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let clock = test_clock();
+    let app = common::init_app_with(&db, notifier.clone(), clock.clone()).await;
+    let receptor = Receptor::default();
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "recordatorio_horas": 24 }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/webhooks")
+        .set_json(json!({ "url": "https://tpv.example.com/hooks", "eventos": ["reserva_creada"] }))).await;
+    assert_eq!(status, 200, "{}", body);
+    notifier.outbox().unwrap().clear();
+
+    // El email de confirmación sale dentro de la petición
+    let (status, reserva) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .insert_header(("X-Request-Id", "lb-reserva-7"))
+        .set_json(reservation_body(&mesa, "2030-06-02", "10:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    assert_eq!(email_request_ids(&notifier), [Some("lb-reserva-7".to_string())]);
+    notifier.outbox().unwrap().clear();
+
+    // El recordatorio y el webhook, horas después, llevan el mismo
+    clock.advance(Duration::hours(1));
+    assert_eq!(reminders::run(&db.repo, &notifier, clock.as_ref()).await.unwrap(), 1);
+    assert_eq!(email_request_ids(&notifier), [Some("lb-reserva-7".to_string())]);
+    assert_eq!(webhook_delivery::run(&db.repo, &receptor, clock.as_ref()).await.unwrap(), 1);
+    assert_eq!(*receptor.request_ids.lock().unwrap(), [Some("lb-reserva-7".to_string())]);
+}
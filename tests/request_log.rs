@@ -0,0 +1,53 @@
+//! Ocultación de datos personales en el registro de peticiones
+
+use pispas_reservation::api::request_log::{redact_body, redact_query, REDACTED};
+use serde_json::{json, Value};
+
+#[test]
+fn json_bodies_hide_secrets_emails_and_phones() {
+    let body = json!({
+        "name": "La Tasca",
+        "password": "secreto123",
+        "access_token": "abc-123",
+        "email_cliente": "ana.garcia@example.com",
+        "telefono_cliente": "+34 600 123 456",
+        "numero_personas": 4
+    });
+
+    let redacted: Value = serde_json::from_str(&redact_body(body.to_string().as_bytes())).unwrap();
+
+    assert_eq!(redacted["name"], "La Tasca");
+    assert_eq!(redacted["password"], REDACTED);
+    assert_eq!(redacted["access_token"], REDACTED);
+    assert_eq!(redacted["email_cliente"], "a***@example.com");
+    assert_eq!(redacted["telefono_cliente"], "***456");
+    assert_eq!(redacted["numero_personas"], 4);
+}
+
+#[test]
+fn nested_values_and_loose_emails_are_hidden() {
+    let body = json!([
+        { "reserva": { "codigo": 123456, "notas": "avisar a ana@example.com" } },
+        { "contacto": "ana@example.com" }
+    ]);
+
+    let redacted = redact_body(body.to_string().as_bytes());
+
+    assert!(!redacted.contains("123456"));
+    assert!(!redacted.contains("ana@example.com"));
+    assert!(redacted.contains("a***@example.com"));
+}
+
+#[test]
+fn non_json_bodies_are_not_logged() {
+    assert_eq!(redact_body(b"password=secreto123"), "<19 bytes no JSON, no registrados>");
+    assert_eq!(redact_body(b""), "");
+}
+
+#[test]
+fn query_strings_hide_sensitive_parameters() {
+    assert_eq!(
+        redact_query("fecha=2030-06-01&token=abc&email=ana@example.com"),
+        format!("fecha=2030-06-01&token={}&email=a***@example.com", REDACTED)
+    );
+}
@@ -0,0 +1,80 @@
+//! Borrado definitivo de reservas canceladas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use mongodb::bson::doc;
+use pispas_reservation::notifications::Notifier;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn only_old_cancelled_reservations_are_deleted_after_confirming() {
+    let db = TestDb::start().await;
+    let clock = test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let reservar = |fecha: &str| bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, fecha, "21:00"));
+    let cancelar = |id: &str| bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", id));
+    let borrar = |id: &str, confirmacion: &str| bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/reservations/{}?confirmacion={}", id, confirmacion));
+
+    let (_, activa) = send(&app, reservar("2030-06-15")).await;
+    let activa = activa["id"].as_str().unwrap().to_string();
+    let (_, cancelada) = send(&app, reservar("2030-06-16")).await;
+    let cancelada = cancelada["id"].as_str().unwrap().to_string();
+    let (status, _) = send(&app, cancelar(&cancelada)).await;
+    assert_eq!(status, 200);
+
+    // Recién cancelada todavía no se puede borrar; una activa, nunca
+    let (status, body) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/reservations/{}", cancelada))).await;
+    assert_eq!(status, 409, "{}", body);
+    assert_eq!(body["codigo"], "reserva_no_borrable");
+    clock.advance(Duration::days(31));
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/reservations/{}", activa))).await;
+    assert_eq!(status, 409);
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri("/reservations/507f1f77bcf86cd799439011")).await;
+    assert_eq!(status, 404);
+
+    let (status, body) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/reservations/{}", cancelada))).await;
+    assert_eq!(status, 428, "{}", body);
+    let confirmacion = body["confirmacion"].as_str().unwrap().to_string();
+    let (status, _) = send(&app, borrar(&cancelada, "otra")).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, borrar(&cancelada, &confirmacion)).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, _) = send(&app, borrar(&cancelada, &confirmacion)).await;
+    assert_eq!(status, 404);
+
+    // El borrado masivo solo se lleva las canceladas antiguas
+    let (_, otra) = send(&app, reservar("2030-07-20")).await;
+    let otra = otra["id"].as_str().unwrap().to_string();
+    send(&app, cancelar(&otra)).await;
+    let (_, reciente) = send(&app, reservar("2030-07-21")).await;
+    clock.advance(Duration::days(31));
+    send(&app, cancelar(reciente["id"].as_str().unwrap())).await;
+
+    let (status, body) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri("/reservations/purge")).await;
+    assert_eq!(status, 428, "{}", body);
+    assert_eq!(body["reservas"], 1);
+    let (status, body) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/reservations/purge?confirmacion={}", body["confirmacion"].as_str().unwrap()))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["reservas"], 1);
+
+    assert_eq!(db.repo.reservas().count_documents(doc! {}).await.unwrap(), 2);
+    assert_eq!(db.repo.audit_log().count_documents(doc! { "accion": "borrar_reserva" }).await.unwrap(), 2);
+}
@@ -0,0 +1,130 @@
+//! Claves de API y modo de pruebas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use mongodb::bson::doc;
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn sandbox_keys_never_touch_real_reservations() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/api-keys")
+        .set_json(json!({ "nombre": "TPV", "rol": "propietario" }))).await;
+    assert_eq!(status, 400, "una clave no actúa como propietario");
+
+    let (status, real) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/api-keys")
+        .set_json(json!({ "nombre": "TPV" }))).await;
+    assert_eq!(status, 200, "{}", real);
+    let (status, pruebas) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/api-keys")
+        .set_json(json!({ "nombre": "Web", "sandbox": true }))).await;
+    assert_eq!(status, 200, "{}", pruebas);
+    let token_pruebas = pruebas["token"].as_str().unwrap().to_string();
+    assert!(token_pruebas.starts_with("sbx_"));
+
+    // La misma API con la mesa copiada, pero en la base de datos de pruebas
+    let resp = test::call_service(&app, bearer(TestRequest::post(), &token_pruebas)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("x-sandbox").unwrap(), "true");
+
+    let (status, reservas) = send(&app, bearer(TestRequest::get(), &token_pruebas)
+        .uri("/reservations")).await;
+    assert_eq!(status, 200);
+    assert_eq!(reservas.as_array().unwrap().len(), 1);
+    let (status, reservas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations")).await;
+    assert_eq!(status, 200);
+    assert_eq!(reservas, json!([]));
+    let copia = db.repo.sandbox().restaurants()
+        .find_one(doc! { "nombre": "La Tasca" })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(copia.password.is_empty());
+
+    // La clave real trabaja con los datos reales
+    let (status, _) = send(&app, bearer(TestRequest::post(), real["token"].as_str().unwrap())
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200);
+
+    let (status, claves) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/restaurants/api-keys")).await;
+    assert_eq!(status, 200);
+    assert_eq!(claves.as_array().unwrap().len(), 2);
+    assert!(claves[0].get("token").is_none());
+    assert_eq!(claves[1]["sandbox"], true);
+
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/restaurants/api-keys/{}", pruebas["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 204);
+    let (status, _) = send(&app, bearer(TestRequest::get(), &token_pruebas)
+        .uri("/reservations")).await;
+    assert_eq!(status, 401);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn scoped_keys_only_reach_routes_with_their_scopes() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/api-keys")
+        .set_json(json!({ "nombre": "Mostrador", "alcances": [] }))).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/api-keys")
+        .set_json(json!({ "nombre": "Mostrador", "alcances": ["reservations:delete"] }))).await;
+    assert_eq!(status, 400, "alcance desconocido");
+
+    let (status, clave) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/api-keys")
+        .set_json(json!({
+            "nombre": "Mostrador",
+            "rol": "camarero",
+            "alcances": ["reservations:read", "reservations:write", "tables:write"]
+        }))).await;
+    assert_eq!(status, 200, "{}", clave);
+    assert_eq!(clave["alcances"], json!(["reservations:read", "reservations:write", "tables:write"]));
+    let token = clave["token"].as_str().unwrap().to_string();
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200);
+    let (status, reservas) = send(&app, bearer(TestRequest::get(), &token)
+        .uri("/reservations")).await;
+    assert_eq!(status, 200);
+    assert_eq!(reservas.as_array().unwrap().len(), 1);
+
+    // Sin el alcance de la ruta
+    let (status, _) = send(&app, bearer(TestRequest::get(), &token).uri("/tables")).await;
+    assert_eq!(status, 403);
+    let (status, _) = send(&app, bearer(TestRequest::get(), &token).uri("/shifts")).await;
+    assert_eq!(status, 403);
+
+    // El alcance no amplía el rol de camarero
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &token).uri("/tables/clear")).await;
+    assert_eq!(status, 401);
+
+    let (status, yo) = send(&app, bearer(TestRequest::get(), &token).uri("/staff/me")).await;
+    assert_eq!(status, 200);
+    assert_eq!(yo["alcances"][0], "reservations:read");
+}
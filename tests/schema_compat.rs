@@ -0,0 +1,135 @@
+//! Tests de compatibilidad del esquema entre versiones
+//!
+//! Durante un despliegue gradual, la versión anterior y la nueva comparten
+//! la base de datos (ver [`pispas_reservation::db::compat`]). Estos tests
+//! leen documentos como los que escribiría cada una:
+//!
+//! - Documentos de la primera versión, sin ninguno de los campos añadidos
+//!   después, se leen con sus valores por defecto.
+//! - Documentos de una versión posterior, con campos que esta no conoce, se
+//!   leen ignorándolos.
+//! - Valores desconocidos en la configuración del restaurante no impiden
+//!   cargarlo.
+
+use mongodb::bson::{doc, from_document, oid::ObjectId, to_document, Document};
+use pispas_reservation::db::{
+    CampoTicket, ContadorInsignia, EstadoCuenta, EstadoPlataforma, EstadoReserva, Mesa,
+    MetodoVerificacion, PoliticaGruposGrandes, ReglaAlerta, Reserva, Restaurant,
+};
+
+/// Restaurante tal y como lo guardaba la primera versión
+fn restaurante_original() -> Document {
+    doc! {
+        "_id": ObjectId::new(),
+        "objid_pispas": "pispas-1",
+        "nombre": "La Tasca",
+        "password": "$2b$12$hash",
+        "confirmar_automaticamente": true,
+        "created_at": 1_700_000_000_i64,
+    }
+}
+
+/// Reserva tal y como la guardaba la primera versión
+fn reserva_original() -> Document {
+    doc! {
+        "_id": ObjectId::new(),
+        "id_restaurante": ObjectId::new(),
+        "id_mesa": ObjectId::new(),
+        "nombre_cliente": "Juan",
+        "email_cliente": "juan@email.com",
+        "telefono_cliente": "600000000",
+        "numero_personas": 4,
+        "fecha": "2030-06-15",
+        "hora": "21:00",
+        "estado": "confirmada",
+        "created_at": 1_700_000_000_i64,
+        "updated_at": 1_700_000_000_i64,
+    }
+}
+
+#[test]
+fn documents_from_the_first_version_get_their_defaults() {
+    let restaurante: Restaurant = from_document(restaurante_original()).unwrap();
+    assert_eq!(restaurante.estado, EstadoCuenta::default());
+    assert_eq!(restaurante.configuracion.verificacion_cliente, MetodoVerificacion::Ninguna);
+    assert!(restaurante.configuracion.alertas.is_empty());
+    assert!(restaurante.email.is_none());
+
+    let mesa: Mesa = from_document(doc! {
+        "_id": ObjectId::new(),
+        "id_restaurante": ObjectId::new(),
+        "tipo": "mesa",
+        "nombre": "Mesa 1",
+        "pos_x": 10.0,
+        "pos_y": 20.0,
+        "size_x": 50.0,
+        "size_y": 50.0,
+        "forma": "cuadrada",
+        "reservable": true,
+        "created_at": 1_700_000_000_i64,
+    }).unwrap();
+    assert!(mesa.min_personas.is_none() && mesa.id_planta.is_none());
+
+    let reserva: Reserva = from_document(reserva_original()).unwrap();
+    assert_eq!(reserva.estado, EstadoReserva::Confirmada);
+    assert_eq!(reserva.canal, "interno");
+    assert!(reserva.localizador.is_none() && reserva.notas_internas.is_none());
+
+    let plataforma: EstadoPlataforma = from_document(doc! { "_id": "plataforma" }).unwrap();
+    assert!(!plataforma.mantenimiento && plataforma.incidencia.is_none());
+}
+
+#[test]
+fn fields_written_by_a_newer_version_are_ignored() {
+    let mut restaurante = restaurante_original();
+    restaurante.insert("campo_futuro", "valor");
+    restaurante.insert("configuracion", doc! { "idioma": "en", "opcion_futura": { "activa": true } });
+    let restaurante: Restaurant = from_document(restaurante).unwrap();
+    assert_eq!(restaurante.configuracion.idioma, "en");
+
+    let mut reserva = reserva_original();
+    reserva.insert("campo_futuro", 42);
+    reserva.insert("deposito_futuro", doc! { "importe": 10 });
+    let reserva: Reserva = from_document(reserva).unwrap();
+    assert_eq!(reserva.nombre_cliente, "Juan");
+
+    // Lo que escribe esta versión se vuelve a leer igual
+    let guardado = to_document(&reserva).unwrap();
+    let releida: Reserva = from_document(guardado).unwrap();
+    assert_eq!(releida.fecha, reserva.fecha);
+}
+
+#[test]
+fn unknown_settings_values_do_not_lock_the_restaurant_out() {
+    let mut restaurante = restaurante_original();
+    restaurante.insert("configuracion", doc! {
+        "idioma": "es",
+        "verificacion_cliente": "whatsapp",
+        "grupos_grandes": "lista_espera",
+        "alertas": [
+            { "tipo": "cancelaciones", "max": 3, "ventana_minutos": 60 },
+            { "tipo": "no_presentadas", "max": 2 },
+        ],
+        "ticket": { "ancho": 48, "campos": ["hora", "alergias", "mesa"] },
+        "insignia": { "contadores": ["resenas", "reservas"] },
+    });
+
+    let restaurante: Restaurant = from_document(restaurante).unwrap();
+    let configuracion = restaurante.configuracion;
+    assert_eq!(configuracion.verificacion_cliente, MetodoVerificacion::Ninguna);
+    assert_eq!(configuracion.grupos_grandes, PoliticaGruposGrandes::Rechazar);
+    assert_eq!(configuracion.alertas, vec![ReglaAlerta::Cancelaciones { max: 3, ventana_minutos: 60 }]);
+    assert_eq!(configuracion.ticket.ancho, 48);
+    assert_eq!(configuracion.ticket.campos, vec![CampoTicket::Hora, CampoTicket::Mesa]);
+    assert_eq!(configuracion.insignia.unwrap().contadores, vec![ContadorInsignia::Reservas]);
+    assert_eq!(configuracion.idioma, "es");
+}
+
+#[test]
+fn unknown_states_are_not_silently_reinterpreted() {
+    // Un estado desconocido no se convierte en otro: se despliega primero
+    // la versión que lo sabe leer
+    let mut reserva = reserva_original();
+    reserva.insert("estado", "en_espera");
+    assert!(from_document::<Reserva>(reserva).is_err());
+}
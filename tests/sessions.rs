@@ -0,0 +1,91 @@
+//! Sesiones del propietario contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, register_restaurant, send, TestDb};
+use mongodb::bson::{doc, Document};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn owner_lists_and_revokes_sessions() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let otro = register_restaurant(&app, "La Bodega").await;
+
+    let (status, login) = send(&app, TestRequest::post()
+        .uri("/restaurants/login")
+        .insert_header(("User-Agent", "Tablet de sala"))
+        .set_json(json!({ "name": "La Tasca", "password": "secreto123" }))).await;
+    assert_eq!(status, 200, "{}", login);
+    let tablet = login["access_token"].as_str().unwrap().to_string();
+
+    let (status, sesiones) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/restaurants/sessions")).await;
+    assert_eq!(status, 200, "{}", sesiones);
+    let sesiones = sesiones.as_array().unwrap();
+    assert_eq!(sesiones.len(), 2, "la del registro y la del login");
+    let actual = sesiones.iter().find(|s| s["actual"] == true).unwrap();
+    let id_tablet = sesiones.iter()
+        .find(|s| s["user_agent"] == "Tablet de sala")
+        .expect("sesión de la tablet")["id"].as_str().unwrap().to_string();
+    assert_ne!(actual["id"], id_tablet.as_str());
+
+    // Otro restaurante no puede revocar la sesión
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &otro.token)
+        .uri(&format!("/restaurants/sessions/{}", id_tablet))).await;
+    assert_eq!(status, 404);
+
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/restaurants/sessions/{}", id_tablet))).await;
+    assert_eq!(status, 204);
+
+    // El token revocado deja de valer; el resto sigue funcionando
+    let (status, _) = send(&app, bearer(TestRequest::get(), &tablet)
+        .uri("/restaurants/settings")).await;
+    assert_eq!(status, 401);
+    let (status, sesiones) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/restaurants/sessions")).await;
+    assert_eq!(status, 200);
+    assert_eq!(sesiones.as_array().unwrap().len(), 1);
+
+    let eventos = db.repo.eventos()
+        .count_documents(doc! { "tipo": "sesion_revocada" })
+        .await
+        .unwrap();
+    assert_eq!(eventos, 1);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn restaurant_tokens_are_migrated_to_sessions() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+
+    // Token guardado en el restaurante, como antes de las sesiones
+    let id = mongodb::bson::oid::ObjectId::parse_str(&restaurant.id).unwrap();
+    db.repo.sesiones().delete_many(doc! {}).await.unwrap();
+    db.repo.database.collection::<Document>("restaurants")
+        .update_one(doc! { "_id": id }, doc! { "$set": { "access_token": "token-antiguo" } })
+        .await
+        .unwrap();
+
+    db.repo.create_indexes().await.unwrap();
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), "token-antiguo")
+        .uri("/restaurants/sessions")).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body[0]["actual"], true);
+
+    let restante = db.repo.database.collection::<Document>("restaurants")
+        .count_documents(doc! { "access_token": { "$exists": true } })
+        .await
+        .unwrap();
+    assert_eq!(restante, 0);
+}
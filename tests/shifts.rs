@@ -0,0 +1,84 @@
+//! Turnos del personal y notas de traspaso contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn evening_team_sees_the_notes_left_by_the_lunch_team() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 4").await;
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({
+            "turnos": [
+                { "nombre": "Comida", "hora_inicio": "13:00", "hora_fin": "16:30" },
+                { "nombre": "Cena", "hora_inicio": "20:00", "hora_fin": "23:59" }
+            ]
+        }))).await;
+    assert_eq!(status, 200);
+
+    let (_, cuenta) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/staff")
+        .set_json(json!({ "usuario": "marta", "password": "secreto123", "rol": "camarero" }))).await;
+    let (_, login) = send(&app, TestRequest::post()
+        .uri("/staff/login")
+        .set_json(json!({ "id_restaurante": restaurant.id, "usuario": cuenta["usuario"], "password": "secreto123" }))).await;
+    let marta = login["access_token"].as_str().unwrap().to_string();
+
+    let (_, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+
+    for _ in 0..2 {
+        let (status, body) = send(&app, bearer(TestRequest::post(), &marta)
+            .uri("/shifts/start")
+            .set_json(json!({ "fecha": "2030-06-15", "turno": "Comida" }))).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+    let (status, _) = send(&app, bearer(TestRequest::post(), &marta)
+        .uri("/shifts/start")
+        .set_json(json!({ "fecha": "2030-06-15", "turno": "Desayuno" }))).await;
+    assert_eq!(status, 400, "el turno tiene que existir");
+
+    let (status, nota) = send(&app, bearer(TestRequest::post(), &marta)
+        .uri("/shifts/notes")
+        .set_json(json!({
+            "fecha": "2030-06-15",
+            "turno": "Comida",
+            "texto": "Piden trona para la cena",
+            "id_reservas": [reserva["id"]]
+        }))).await;
+    assert_eq!(status, 200, "{}", nota);
+    assert_eq!(nota["autor"], "marta");
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &marta)
+        .uri("/shifts/notes")
+        .set_json(json!({
+            "fecha": "2030-06-16",
+            "turno": "Comida",
+            "texto": "Reserva de otro día",
+            "id_reservas": [reserva["id"]]
+        }))).await;
+    assert_eq!(status, 400);
+
+    let (status, dia) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations/by-shift?fecha=2030-06-15&turno=Cena")).await;
+    assert_eq!(status, 200, "{}", dia);
+    assert_eq!(dia["turnos"].as_array().unwrap().len(), 1);
+    assert_eq!(dia["turnos"][0]["total_reservas"], 1);
+    assert_eq!(dia["notas"][0]["texto"], "Piden trona para la cena");
+    assert_eq!(dia["notas"][0]["id_reservas"], json!([reserva["id"]]));
+
+    let (_, dia) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/reservations/by-shift?fecha=2030-06-15")).await;
+    assert_eq!(dia["turnos"][0]["personal"], json!(["marta"]));
+}
@@ -0,0 +1,111 @@
+//! Franjas bloqueadas: reglas puras y su efecto en la API
+//!
+//! Los tests HTTP necesitan MongoDB; ver `tests/common/mod.rs`.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::NaiveDate;
+use common::{bearer, create_table, register_restaurant, send, TestDb};
+use mongodb::bson::oid::ObjectId;
+use pispas_reservation::availability::{blocking_rule, rule_blocks};
+use pispas_reservation::db::ReglaBloqueo;
+use serde_json::json;
+
+fn regla(dias_semana: Vec<u32>, fecha: Option<&str>, desde: &str, hasta: &str) -> ReglaBloqueo {
+    ReglaBloqueo {
+        id: None,
+        id_restaurante: ObjectId::new(),
+        dias_semana,
+        fecha: fecha.map(str::to_string),
+        hora_inicio: desde.to_string(),
+        hora_fin: hasta.to_string(),
+        motivo: "Cambio de turno".to_string(),
+        created_at: 0,
+    }
+}
+
+fn instante(fecha: &str, hora: &str) -> chrono::NaiveDateTime {
+    pispas_reservation::availability::parse_inicio(fecha, hora).unwrap()
+}
+
+#[test]
+fn weekly_rules_block_start_times_inside_the_window() {
+    // 2030-06-15 es sábado
+    assert_eq!(NaiveDate::from_ymd_opt(2030, 6, 15).unwrap().format("%A").to_string(), "Saturday");
+    let sabados = regla(vec![6], None, "21:00", "21:30");
+
+    assert!(rule_blocks(&sabados, instante("2030-06-15", "21:00")));
+    assert!(rule_blocks(&sabados, instante("2030-06-15", "21:29")));
+    assert!(!rule_blocks(&sabados, instante("2030-06-15", "21:30")), "el fin es excluido");
+    assert!(!rule_blocks(&sabados, instante("2030-06-15", "20:59")));
+    assert!(!rule_blocks(&sabados, instante("2030-06-14", "21:15")), "viernes");
+}
+
+#[test]
+fn rules_without_weekdays_apply_every_day_and_dated_rules_only_that_day() {
+    let diaria = regla(Vec::new(), None, "16:00", "17:00");
+    assert!(rule_blocks(&diaria, instante("2030-06-17", "16:30")));
+
+    let puntual = regla(vec![1], Some("2030-06-20"), "13:00", "15:00");
+    assert!(rule_blocks(&puntual, instante("2030-06-20", "14:00")));
+    assert!(!rule_blocks(&puntual, instante("2030-06-17", "14:00")), "la fecha manda sobre los días");
+
+    let reglas = [diaria, puntual];
+    assert!(blocking_rule(&reglas, instante("2030-06-20", "14:00")).is_some());
+    assert!(blocking_rule(&reglas, instante("2030-06-20", "21:00")).is_none());
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn blocked_slots_reject_public_bookings_and_hide_availability() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/slot-rules")
+        .set_json(json!({ "dias_semana": [8], "hora_inicio": "21:00", "hora_fin": "21:30" }))).await;
+    assert_eq!(status, 400, "día de la semana fuera de rango");
+
+    let (status, regla) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/slot-rules")
+        .set_json(json!({
+            "dias_semana": [6],
+            "hora_inicio": "21:00",
+            "hora_fin": "21:30",
+            "motivo": "Cambio de turno en cocina"
+        }))).await;
+    assert_eq!(status, 200, "{}", regla);
+
+    let (_, mesas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/tables/available?fecha=2030-06-15&hora=21:00&personas=2")).await;
+    assert_eq!(mesas, json!([]));
+
+    let (_, mesas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/tables/available?fecha=2030-06-15&hora=22:00&personas=2")).await;
+    assert_eq!(mesas.as_array().unwrap().len(), 1);
+
+    let mut body = common::reservation_body(&id_mesa, "2030-06-15", "21:15");
+    let (status, respuesta) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(body.clone())).await;
+    assert_eq!(status, 409);
+    assert!(respuesta.to_string().contains("Cambio de turno en cocina"), "{}", respuesta);
+
+    body["hora"] = json!("21:30");
+    let (status, respuesta) = send(&app, TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .set_json(body)).await;
+    assert_eq!(status, 200, "{}", respuesta);
+
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/slot-rules/{}", regla["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 204);
+
+    let (_, reglas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/slot-rules")).await;
+    assert_eq!(reglas, json!([]));
+}
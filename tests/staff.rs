@@ -0,0 +1,75 @@
+//! Cuentas de personal y permisos por rol contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use pispas_reservation::api::staff::Permiso;
+use pispas_reservation::db::Rol;
+use serde_json::json;
+
+#[test]
+fn each_role_includes_the_permissions_of_the_ones_below() {
+    assert!(Permiso::Reservas.permite(Rol::Camarero));
+    assert!(!Permiso::Gestion.permite(Rol::Camarero));
+    assert!(Permiso::Gestion.permite(Rol::Encargado));
+    assert!(!Permiso::Configuracion.permite(Rol::Encargado));
+    assert!(Permiso::Configuracion.permite(Rol::Propietario));
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn waiters_can_confirm_reservations_but_not_manage_the_restaurant() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, cuenta) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/staff")
+        .set_json(json!({ "usuario": "ana", "password": "secreto123", "rol": "camarero" }))).await;
+    assert_eq!(status, 200, "{}", cuenta);
+    assert_eq!(cuenta["rol"], "camarero");
+
+    let (status, login) = send(&app, TestRequest::post()
+        .uri("/staff/login")
+        .set_json(json!({ "id_restaurante": restaurant.id, "usuario": "ana", "password": "secreto123" }))).await;
+    assert_eq!(status, 200, "{}", login);
+    let camarero = login["access_token"].as_str().unwrap().to_string();
+
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &camarero)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &camarero)
+        .uri(&format!("/reservations/{}/confirm", reserva["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &camarero)
+        .uri("/tables/clear")).await;
+    assert_eq!(status, 401);
+    let (status, _) = send(&app, bearer(TestRequest::put(), &camarero)
+        .uri("/restaurants/settings")
+        .set_json(json!({}))).await;
+    assert_eq!(status, 401);
+    let (status, _) = send(&app, bearer(TestRequest::get(), &camarero)
+        .uri("/staff")).await;
+    assert_eq!(status, 401);
+
+    let (status, mesas) = send(&app, bearer(TestRequest::get(), &camarero)
+        .uri("/tables")).await;
+    assert_eq!(status, 200);
+    assert_eq!(mesas.as_array().unwrap().len(), 1, "la mesa sigue en el plano");
+
+    // Al dar de baja la cuenta, su token deja de funcionar
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/staff/{}", cuenta["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 204);
+    let (status, _) = send(&app, bearer(TestRequest::get(), &camarero)
+        .uri("/reservations")).await;
+    assert_eq!(status, 401);
+}
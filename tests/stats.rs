@@ -0,0 +1,130 @@
+//! Estadísticas diarias precalculadas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use chrono::{Duration, NaiveDate};
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb, TestRestaurant};
+use mongodb::bson::oid::ObjectId;
+use pispas_reservation::clock::Clock;
+use pispas_reservation::db::EstadisticaDiaria;
+use pispas_reservation::jobs::rollups;
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn rollup_aggregates_each_day_and_drops_emptied_days() {
+    let db = TestDb::start().await;
+    let clock = test_clock();
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let mut ids = Vec::new();
+    for (fecha, hora) in [("2030-06-15", "13:00"), ("2030-06-15", "21:00"), ("2030-06-16", "21:00")] {
+        let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(reservation_body(&mesa, fecha, hora))).await;
+        assert_eq!(status, 200, "{}", reserva);
+        ids.push(reserva["id"].as_str().unwrap().to_string());
+    }
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", ids[1]))).await;
+    assert_eq!(status, 200);
+
+    let dia = |fecha: &str| NaiveDate::parse_from_str(fecha, "%Y-%m-%d").unwrap();
+    let escritas = rollups::rollup_range(&db.repo, dia("2030-06-15"), dia("2030-06-16"), clock.timestamp()).await.unwrap();
+    assert_eq!(escritas, 2);
+
+    let (status, stats) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/stats/daily?desde=2030-06-15&hasta=2030-06-30")).await;
+    assert_eq!(status, 200, "{}", stats);
+    assert_eq!(stats["dias"][0], json!({
+        "fecha": "2030-06-15",
+        "reservas": 2,
+        "por_canal": { "interno": 2 },
+        "comensales": 2,
+        "cancelaciones": 1,
+        "no_presentadas": 0
+    }));
+    assert_eq!(stats["totales"]["reservas"], 3);
+    assert_eq!(stats["totales"]["comensales"], 4);
+
+    // Un día que se queda sin reservas desaparece al recalcular
+    db.repo.reservas().delete_many(mongodb::bson::doc! { "fecha": "2030-06-16" }).await.unwrap();
+    clock.advance(Duration::seconds(1));
+    rollups::rollup_range(&db.repo, dia("2030-06-15"), dia("2030-06-16"), clock.timestamp()).await.unwrap();
+    let (_, stats) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/stats/daily?desde=2030-06-15&hasta=2030-06-30")).await;
+    assert_eq!(stats["dias"].as_array().unwrap().len(), 1);
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/stats/daily?desde=2030-06-30&hasta=2030-06-15")).await;
+    assert_eq!(status, 400);
+}
+
+/// Apunta al restaurante en la comparativa de una ciudad y le guarda un día
+/// con `reservas` reservas y `comensales` comensales
+async fn join_benchmark<S>(app: &S, db: &TestDb, restaurant: &TestRestaurant, ciudad: &str, reservas: u64, comensales: u64)
+where
+    S: actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse, Error = actix_web::Error>,
+{
+    let (status, body) = send(app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "ciudad": ciudad, "benchmark": true }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let mut dia = EstadisticaDiaria::new(ObjectId::parse_str(&restaurant.id).unwrap(), "2030-05-05", 0);
+    dia.reservas = reservas;
+    dia.comensales = comensales;
+    db.repo.estadisticas_diarias().insert_one(&dia).await.unwrap();
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn benchmark_needs_consent_and_enough_peers_in_the_same_city() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let rango = "/stats/benchmark?desde=2030-05-01&hasta=2030-05-10";
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri(rango)).await;
+    assert_eq!(status, 403);
+
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "benchmark": true }))).await;
+    assert_eq!(status, 400, "{}", body);
+
+    // 9 comensales por día frente a 3, 6, 12 y 15
+    join_benchmark(&app, &db, &restaurant, "Madrid", 30, 90).await;
+    for (i, comensales) in [30, 60, 120, 150].into_iter().enumerate() {
+        let otro = register_restaurant(&app, &format!("Madrid {}", i)).await;
+        join_benchmark(&app, &db, &otro, "madrid", 30, comensales).await;
+    }
+    // Ni otra ciudad ni un restaurante con pocas reservas cuentan
+    let lejos = register_restaurant(&app, "Sevilla").await;
+    join_benchmark(&app, &db, &lejos, "Sevilla", 30, 90).await;
+    let pocas = register_restaurant(&app, "Pequeño").await;
+    join_benchmark(&app, &db, &pocas, "Madrid", 5, 90).await;
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri(rango)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["suficientes_datos"], false);
+    assert!(body["participantes"].is_null());
+    assert!(body["metricas"]["comensales_por_dia"].is_null());
+
+    let quinto = register_restaurant(&app, "Madrid 4").await;
+    join_benchmark(&app, &db, &quinto, "MÁDRID ", 30, 90).await;
+
+    let (status, body) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri(rango)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["suficientes_datos"], true);
+    assert_eq!(body["participantes"], 5);
+    assert_eq!(body["metricas"]["comensales_por_dia"], json!({
+        "propio": 9.0, "p25": 6.0, "mediana": 9.0, "p75": 12.0, "percentil": 50
+    }));
+    assert!(body["metricas"]["ocupacion"].is_null());
+}
@@ -0,0 +1,149 @@
+//! Sincronización de cambios sin conexión contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use actix_web::ResponseError;
+use chrono::Duration;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::api::sync::apply_batch;
+use pispas_reservation::api::AppError;
+use pispas_reservation::notifications::Notifier;
+use serde_json::json;
+
+#[actix_web::test]
+async fn a_rejected_mutation_does_not_stop_the_batch() {
+    let mut aplicadas = Vec::new();
+    let resultados = apply_batch(vec!["crear", "ocupada", "actualizar"], |indice, mutacion| {
+        if mutacion != "ocupada" {
+            aplicadas.push(indice);
+        }
+        async move {
+            match mutacion {
+                "ocupada" => Err(AppError::Conflict("Mesa ocupada".to_string())),
+                _ => Ok(mutacion),
+            }
+        }
+    }).await.unwrap();
+
+    assert_eq!(aplicadas, [0, 2]);
+    assert_eq!(resultados[0].as_ref().unwrap(), &"crear");
+    assert_eq!(resultados[1].as_ref().unwrap_err().status_code(), 409);
+    assert_eq!(resultados[2].as_ref().unwrap(), &"actualizar");
+}
+
+#[actix_web::test]
+async fn a_server_error_stops_the_batch() {
+    let mut aplicadas = Vec::new();
+    let resultado = apply_batch(vec!["crear", "caida", "actualizar"], |indice, mutacion| {
+        aplicadas.push(indice);
+        async move {
+            match mutacion {
+                "caida" => Err(AppError::Internal("MongoDB no responde".to_string())),
+                _ => Ok(mutacion),
+            }
+        }
+    }).await;
+
+    assert!(resultado.is_err());
+    assert_eq!(aplicadas, [0, 1]);
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn offline_batches_apply_with_per_item_outcomes() {
+    let db = TestDb::start().await;
+    let clock = test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let uuid = "3f2b8c1e-6d4a-4f7b-9a0e-2c5d8e1f4a6b";
+    let push = |mutaciones: serde_json::Value| bearer(TestRequest::post(), &restaurant.token)
+        .uri("/sync/push")
+        .set_json(json!({ "mutaciones": mutaciones }));
+
+    let mut crear = reservation_body(&id_mesa, "2030-06-15", "21:00");
+    crear["tipo"] = json!("crear_reserva");
+    crear["uuid"] = json!(uuid);
+    let mut ocupada = reservation_body(&id_mesa, "2030-06-15", "21:30");
+    ocupada["tipo"] = json!("crear_reserva");
+    ocupada["uuid"] = json!("9b7e4d2a-1c3f-4e5b-8a6d-0f2e4c6a8b1d");
+
+    let (status, body) = send(&app, push(json!([crear, ocupada, { "tipo": "borrar_todo" }]))).await;
+    assert_eq!(status, 200, "{}", body);
+    let resultados = body["resultados"].as_array().unwrap();
+    assert_eq!(resultados[0]["resultado"], "creada");
+    assert_eq!(resultados[1]["resultado"], "rechazada");
+    assert_eq!(resultados[1]["codigo_http"], 409);
+    assert_eq!(resultados[2]["resultado"], "rechazada");
+    assert_eq!(resultados[2]["codigo_http"], 400);
+    let id = resultados[0]["id"].clone();
+    let version = resultados[0]["version"].as_i64().unwrap();
+
+    // Reintentar el lote no duplica la reserva
+    let (_, body) = send(&app, push(json!([crear]))).await;
+    assert_eq!(body["resultados"][0]["resultado"], "existente");
+    assert_eq!(body["resultados"][0]["id"], id);
+
+    // Sin cambios en el servidor, el cambio de la app se aplica
+    clock.advance(Duration::minutes(5));
+    let (_, body) = send(&app, push(json!([{
+        "tipo": "actualizar_reserva",
+        "uuid": uuid,
+        "version": version,
+        "modificado_en": version + 60,
+        "telefono_cliente": "+34 611 111 111"
+    }]))).await;
+    assert_eq!(body["resultados"][0]["resultado"], "aplicada", "{}", body);
+    let version_nueva = body["resultados"][0]["version"].as_i64().unwrap();
+    assert!(version_nueva > version);
+
+    // Un cambio anterior hecho sobre la versión vieja pierde frente al servidor
+    clock.advance(Duration::minutes(5));
+    let (_, body) = send(&app, push(json!([{
+        "tipo": "actualizar_reserva",
+        "uuid": uuid,
+        "version": version,
+        "modificado_en": version + 30,
+        "estado": "confirmada"
+    }]))).await;
+    assert_eq!(body["resultados"][0]["resultado"], "conflicto", "{}", body);
+    assert_eq!(body["resultados"][0]["reserva"]["telefono_cliente"], "+34 611 111 111");
+    assert_eq!(body["resultados"][0]["reserva"]["estado"], "pendiente");
+
+    // Uno posterior gana
+    let (_, body) = send(&app, push(json!([{
+        "tipo": "actualizar_reserva",
+        "uuid": uuid,
+        "version": version,
+        "modificado_en": version_nueva + 1,
+        "estado": "confirmada"
+    }]))).await;
+    assert_eq!(body["resultados"][0]["resultado"], "aplicada", "{}", body);
+
+    // La cancelación siempre se aplica, y después nada la reactiva
+    let cancelar = json!({
+        "tipo": "actualizar_reserva",
+        "id": id,
+        "version": version,
+        "modificado_en": version,
+        "estado": "cancelada"
+    });
+    let (_, body) = send(&app, push(json!([cancelar]))).await;
+    assert_eq!(body["resultados"][0]["resultado"], "aplicada", "{}", body);
+    let (_, body) = send(&app, push(json!([cancelar, {
+        "tipo": "actualizar_reserva",
+        "uuid": uuid,
+        "version": version_nueva,
+        "modificado_en": version_nueva + 3600,
+        "nombre_cliente": "Juan P."
+    }]))).await;
+    assert_eq!(body["resultados"][0]["resultado"], "aplicada");
+    assert_eq!(body["resultados"][1]["resultado"], "conflicto");
+    assert_eq!(body["resultados"][1]["reserva"]["estado"], "cancelada");
+
+    let (status, _) = send(&app, push(json!(vec![json!({ "tipo": "crear_reserva" }); 101]))).await;
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,80 @@
+//! Cambio de mesa de una reserva contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservations_move_to_free_tables_that_fit() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa_1 = create_table(&app, &restaurant, "Mesa 1").await;
+    let mesa_2 = create_table(&app, &restaurant, "Mesa 2").await;
+    let mesa_3 = create_table(&app, &restaurant, "Mesa 3").await;
+
+    let mut reservas = Vec::new();
+    for (mesa, hora, personas) in [(&mesa_1, "21:00", 2), (&mesa_2, "21:30", 2), (&mesa_1, "14:00", 4)] {
+        let mut cuerpo = reservation_body(mesa, "2030-06-15", hora);
+        cuerpo["numero_personas"] = json!(personas);
+        let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+            .uri("/reservations")
+            .set_json(cuerpo)).await;
+        assert_eq!(status, 200, "{}", body);
+        reservas.push(body["id"].as_str().unwrap().to_string());
+    }
+    let mover = |reserva: &str, mesa: &str| bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/move", reserva))
+        .set_json(json!({ "id_mesa": mesa }));
+
+    // Ocupada a esa hora, o la misma mesa
+    let (status, _) = send(&app, mover(&reservas[0], &mesa_2)).await;
+    assert_eq!(status, 409);
+    let (status, _) = send(&app, mover(&reservas[0], &mesa_1)).await;
+    assert_eq!(status, 400);
+
+    let (status, body) = send(&app, mover(&reservas[0], &mesa_3)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["reserva"]["id_mesa"], mesa_3.as_str());
+    assert_eq!(body["reserva"]["hora"], "21:00");
+
+    // La mesa que deja queda libre
+    let (status, body) = send(&app, mover(&reservas[1], &mesa_1)).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // Sin sitio para el grupo
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/tables")
+        .set_json(json!({
+            "id_restaurante": restaurant.id,
+            "tipo": "mesa",
+            "nombre": "Barra",
+            "pos_x": 0.0,
+            "pos_y": 0.0,
+            "size_x": 40.0,
+            "size_y": 40.0,
+            "forma": "cuadrado",
+            "reservable": true,
+            "min_personas": 1,
+            "max_personas": 2
+        }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let barra = body["id"].as_str().unwrap().to_string();
+    let (status, _) = send(&app, mover(&reservas[2], &barra)).await;
+    assert_eq!(status, 400);
+
+    let (status, _) = send(&app, mover(&reservas[2], "no-es-un-id")).await;
+    assert_eq!(status, 400);
+    let (status, _) = send(&app, mover(&reservas[2], "507f1f77bcf86cd799439011")).await;
+    assert_eq!(status, 404);
+
+    let (_, entradas) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/audit?accion=mover_reserva")).await;
+    assert_eq!(entradas.as_array().unwrap().len(), 2);
+}
@@ -0,0 +1,57 @@
+//! Tickets de reserva contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservation_tickets_follow_the_restaurant_layout() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let id_mesa = create_table(&app, &restaurant, "Terraza 3").await;
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&id_mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", body);
+    let ticket = format!("/reservations/{}/ticket", body["id"].as_str().unwrap());
+
+    let resp = test::call_service(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&ticket)
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("Content-Type").unwrap(), "application/octet-stream");
+    let bytes = test::read_body(resp).await;
+    assert!(bytes.starts_with(&[0x1B, b'@']));
+    assert!(bytes.ends_with(&[0x1D, b'V', 66, 0]));
+    assert!(bytes.windows(15).any(|w| w == b"Mesa: Terraza 3"));
+
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "ticket": { "ancho": 32, "campos": ["nombre", "localizador"], "pie": "Gracias" } }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let resp = test::call_service(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("{}?formato=texto", ticket))
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    let texto = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(texto.contains("Cliente: Juan Pérez"));
+    assert!(texto.contains("Localizador: "));
+    assert!(!texto.contains("Mesa:"));
+    assert!(texto.ends_with("Gracias"));
+
+    let (status, _) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri(&format!("{}?formato=pdf", ticket))).await;
+    assert_eq!(status, 400);
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "ticket": { "ancho": 8 } }))).await;
+    assert_eq!(status, 400);
+}
@@ -0,0 +1,77 @@
+//! Grupos de locales y traspaso de reservas contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::notifications::{Notifier, SentMessage};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn reservations_move_between_locations_of_the_same_group() {
+    let db = TestDb::start().await;
+    let notifier = Notifier::memory();
+    let app = common::init_app_with(&db, notifier.clone(), test_clock()).await;
+
+    let centro = register_restaurant(&app, "La Tasca Centro").await;
+    let playa = register_restaurant(&app, "La Tasca Playa").await;
+    let ajeno = register_restaurant(&app, "El Rincón").await;
+    let mesa_centro = create_table(&app, &centro, "Mesa 1").await;
+    let mesa_playa = create_table(&app, &playa, "Terraza 1").await;
+    let mesa_ajena = create_table(&app, &ajeno, "Mesa 1").await;
+
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &centro.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa_centro, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let id = reserva["id"].as_str().unwrap().to_string();
+
+    // Sin grupo no se puede traspasar
+    let (status, _) = send(&app, bearer(TestRequest::post(), &centro.token)
+        .uri(&format!("/reservations/{}/transfer", id))
+        .set_json(json!({ "id_restaurante": ajeno.id, "id_mesa": mesa_ajena }))).await;
+    assert_eq!(status, 401);
+
+    let (status, grupo) = send(&app, bearer(TestRequest::post(), &centro.token)
+        .uri("/restaurants/group")
+        .set_json(json!({ "token_local": playa.token }))).await;
+    assert_eq!(status, 200, "{}", grupo);
+
+    let (_, grupo) = send(&app, bearer(TestRequest::get(), &playa.token)
+        .uri("/restaurants/group")).await;
+    assert_eq!(grupo["locales"].as_array().unwrap().len(), 2);
+
+    // La mesa de destino tiene que estar libre
+    let (status, _) = send(&app, bearer(TestRequest::post(), &playa.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa_playa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &centro.token)
+        .uri(&format!("/reservations/{}/transfer", id))
+        .set_json(json!({ "id_restaurante": playa.id, "id_mesa": mesa_playa }))).await;
+    assert_eq!(status, 409);
+
+    notifier.outbox().unwrap().clear();
+    let (status, body) = send(&app, bearer(TestRequest::post(), &centro.token)
+        .uri(&format!("/reservations/{}/transfer", id))
+        .set_json(json!({ "id_restaurante": playa.id, "id_mesa": mesa_playa, "hora": "23:00" }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["reserva"]["id_restaurante"], json!(playa.id));
+    assert_eq!(body["reserva"]["hora"], "23:00");
+
+    let enviados = notifier.outbox().unwrap().sent();
+    match &enviados[..] {
+        [SentMessage::Email(email)] => assert!(email.subject.contains("La Tasca Playa"), "{:?}", email),
+        otros => panic!("se esperaba un email: {:?}", otros),
+    }
+
+    let (_, reservas) = send(&app, bearer(TestRequest::get(), &playa.token)
+        .uri("/reservations?fecha=2030-06-15")).await;
+    assert_eq!(reservas.as_array().unwrap().len(), 2);
+    let (_, reservas) = send(&app, bearer(TestRequest::get(), &centro.token)
+        .uri("/reservations?fecha=2030-06-15")).await;
+    assert_eq!(reservas, json!([]));
+}
@@ -0,0 +1,54 @@
+//! Margen de limpieza entre reservas de una mesa contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn cleanup_buffer_keeps_tables_busy_after_each_reservation() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "margen_limpieza_minutos": 600 }))).await;
+    assert_eq!(status, 400);
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "duracion_reserva_minutos": 90, "margen_limpieza_minutos": 15 }))).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let reservar = |fecha: &str, hora: &str| bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, fecha, hora));
+    let (status, body) = send(&app, reservar("2030-06-15", "21:00")).await;
+    assert_eq!(status, 200, "{}", body);
+
+    // La mesa se libera a las 22:30, pero no se puede volver a ocupar hasta las 22:45
+    let (status, _) = send(&app, reservar("2030-06-15", "22:30")).await;
+    assert_eq!(status, 409);
+    let (status, _) = send(&app, reservar("2030-06-15", "19:20")).await;
+    assert_eq!(status, 409);
+    let (_, libres) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/tables/available?fecha=2030-06-15&hora=22:40&personas=2")).await;
+    assert_eq!(libres.as_array().unwrap().len(), 0, "{}", libres);
+    let (_, libres) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/tables/available?fecha=2030-06-15&hora=22:45&personas=2")).await;
+    assert_eq!(libres.as_array().unwrap().len(), 1, "{}", libres);
+    let (status, body) = send(&app, reservar("2030-06-15", "22:45")).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, body) = send(&app, reservar("2030-06-15", "19:15")).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let (_, configuracion) = send(&app, bearer(TestRequest::get(), &restaurant.token)
+        .uri("/restaurants/settings")).await;
+    assert_eq!(configuracion["margen_limpieza_minutos"], 15);
+}
@@ -0,0 +1,139 @@
+//! Suscripciones y entrega de webhooks salientes contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use std::sync::Mutex;
+use actix_web::test::TestRequest;
+use async_trait::async_trait;
+use common::{bearer, create_table, register_restaurant, reservation_body, send, test_clock, TestDb};
+use pispas_reservation::api::webhook_auth::sign;
+use pispas_reservation::api::{AppError, AppResult};
+use pispas_reservation::clock::Clock;
+use pispas_reservation::jobs::webhook_delivery::{self, EntregaWebhook};
+use pispas_reservation::notifications::Notifier;
+use serde_json::{json, Value};
+
+/// Receptor en memoria; falla mientras `caido` sea `true`
+#[derive(Default)]
+struct Receptor {
+    recibidos: Mutex<Vec<(String, String, Value)>>,
+    caido: Mutex<bool>,
+}
+
+impl Receptor {
+    fn take(&self) -> Vec<(String, String, Value)> {
+        std::mem::take(&mut self.recibidos.lock().unwrap())
+    }
+}
+
+#[async_trait]
+impl EntregaWebhook for Receptor {
+    async fn post(&self, url: &str, firma: &str, _request_id: Option<&str>, cuerpo: Vec<u8>) -> AppResult<()> {
+        if *self.caido.lock().unwrap() {
+            return Err(AppError::Internal("503 Service Unavailable".to_string()));
+        }
+        let cuerpo = serde_json::from_slice(&cuerpo).unwrap();
+        self.recibidos.lock().unwrap().push((url.to_string(), firma.to_string(), cuerpo));
+        Ok(())
+    }
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn subscriptions_receive_filtered_and_templated_events() {
+    let db = TestDb::start().await;
+    let clock = test_clock();
+    let app = common::init_app_with(&db, Notifier::memory(), clock.clone()).await;
+    let receptor = Receptor::default();
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+
+    let (status, body) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/webhooks")
+        .set_json(json!({ "url": "https://tpv.example.com/hooks", "eventos": ["reserva_borrada"] }))).await;
+    assert_eq!(status, 400, "{}", body);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/webhooks")
+        .set_json(json!({ "url": "ftp://tpv.example.com" }))).await;
+    assert_eq!(status, 400);
+
+    let (status, legado) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/webhooks")
+        .set_json(json!({
+            "url": "https://legado.example.com/reservas",
+            "eventos": ["reserva_cancelada"],
+            "plantilla": {
+                "formato": "campos",
+                "campos": { "booking.id": "id_entidad", "action": "tipo", "booking.notes": "datos.notas" }
+            }
+        }))).await;
+    assert_eq!(status, 200, "{}", legado);
+    let secreto = legado["secreto"].as_str().unwrap().to_string();
+    let (status, minimo) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/restaurants/webhooks")
+        .set_json(json!({ "url": "https://tpv.example.com/hooks", "plantilla": { "formato": "minimo" } }))).await;
+    assert_eq!(status, 200, "{}", minimo);
+
+    let (_, lista) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/restaurants/webhooks")).await;
+    assert_eq!(lista.as_array().unwrap().len(), 2);
+    assert!(lista[0].get("secreto").is_none());
+
+    let (status, reserva) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-15", "21:00"))).await;
+    assert_eq!(status, 200, "{}", reserva);
+    let id_reserva = reserva["id"].as_str().unwrap();
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri(&format!("/reservations/{}/cancel", id_reserva))).await;
+    assert_eq!(status, 200);
+
+    // Si el receptor falla, nada avanza y el error queda en la suscripción
+    *receptor.caido.lock().unwrap() = true;
+    assert_eq!(webhook_delivery::run(&db.repo, &receptor, clock.as_ref()).await.unwrap(), 0);
+    let (_, lista) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/restaurants/webhooks")).await;
+    assert!(lista[0]["ultimo_error"].as_str().unwrap().contains("503"), "{}", lista);
+
+    *receptor.caido.lock().unwrap() = false;
+    assert_eq!(webhook_delivery::run(&db.repo, &receptor, clock.as_ref()).await.unwrap(), 3);
+    let recibidos = receptor.take();
+    let legado_recibidos: Vec<_> = recibidos.iter()
+        .filter(|(url, _, _)| url.starts_with("https://legado"))
+        .collect();
+    let [(_, firma, cuerpo)] = &legado_recibidos[..] else {
+        panic!("se esperaba una entrega al receptor legado: {:?}", recibidos);
+    };
+    assert_eq!(cuerpo, &json!({ "booking": { "id": id_reserva, "notes": null }, "action": "reserva_cancelada" }));
+    let t = clock.timestamp();
+    assert_eq!(firma, &format!("t={},v1={}", t, sign(&secreto, t, &serde_json::to_vec(cuerpo).unwrap())));
+
+    let tipos: Vec<&Value> = recibidos.iter()
+        .filter(|(url, _, _)| url.starts_with("https://tpv"))
+        .map(|(_, _, cuerpo)| &cuerpo["tipo"])
+        .collect();
+    assert_eq!(tipos, [&json!("reserva_creada"), &json!("reserva_cancelada")]);
+    let (_, lista) = send(&app, bearer(TestRequest::get(), &restaurant.token).uri("/restaurants/webhooks")).await;
+    assert!(lista[0]["ultimo_error"].is_null());
+
+    // Cada evento se entrega una sola vez
+    assert_eq!(webhook_delivery::run(&db.repo, &receptor, clock.as_ref()).await.unwrap(), 0);
+
+    // Cambiar la plantilla afecta a las entregas siguientes
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri(&format!("/restaurants/webhooks/{}", minimo["id"].as_str().unwrap()))
+        .set_json(json!({ "eventos": ["reserva_creada"], "plantilla": { "formato": "completo" } }))).await;
+    assert_eq!(status, 200, "{}", body);
+    let (status, _) = send(&app, bearer(TestRequest::post(), &restaurant.token)
+        .uri("/reservations")
+        .set_json(reservation_body(&mesa, "2030-06-16", "21:00"))).await;
+    assert_eq!(status, 200);
+    assert_eq!(webhook_delivery::run(&db.repo, &receptor, clock.as_ref()).await.unwrap(), 1);
+    let recibidos = receptor.take();
+    assert_eq!(recibidos[0].2["datos"]["fecha"], "2030-06-16", "{:?}", recibidos);
+
+    let (status, _) = send(&app, bearer(TestRequest::delete(), &restaurant.token)
+        .uri(&format!("/restaurants/webhooks/{}", minimo["id"].as_str().unwrap()))).await;
+    assert_eq!(status, 204);
+}
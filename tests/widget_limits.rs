@@ -0,0 +1,64 @@
+//! Límites de reservas del widget por dispositivo contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{create_table, register_restaurant, reservation_body, send, TestDb};
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn changing_the_widget_session_does_not_escape_the_ip_limit() {
+    std::env::set_var("WIDGET_MAX_RESERVAS", "2");
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let reservar = |ip: &str, sesion: &str, hora: &str| TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .peer_addr(format!("{}:40000", ip).parse().unwrap())
+        .insert_header(("X-Widget-Session", sesion.to_string()))
+        .set_json(reservation_body(&mesa, "2030-06-15", hora));
+
+    for (sesion, hora) in [("sesion-1", "13:00"), ("sesion-2", "15:00")] {
+        let (status, body) = send(&app, reservar("10.0.0.1", sesion, hora)).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+    let (status, body) = send(&app, reservar("10.0.0.1", "sesion-3", "17:00")).await;
+    assert_eq!(status, 429, "{}", body);
+
+    let (status, body) = send(&app, reservar("10.0.0.10", "sesion-3", "17:00")).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let violacion = db.repo.widget_violaciones().find_one(mongodb::bson::doc! {}).await.unwrap().unwrap();
+    assert_eq!(violacion.dispositivo, "ip:10.0.0.1|sesion:sesion-3");
+}
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn a_spoofed_forwarded_address_does_not_escape_the_ip_limit() {
+    std::env::set_var("WIDGET_MAX_RESERVAS", "2");
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let mesa = create_table(&app, &restaurant, "Mesa 1").await;
+    let reservar = |forwarded: &str, hora: &str| TestRequest::post()
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .peer_addr("10.0.0.2:40000".parse().unwrap())
+        .insert_header(("X-Forwarded-For", forwarded.to_string()))
+        .set_json(reservation_body(&mesa, "2030-06-15", hora));
+
+    for (forwarded, hora) in [("198.51.100.1", "13:00"), ("198.51.100.2", "15:00")] {
+        let (status, body) = send(&app, reservar(forwarded, hora)).await;
+        assert_eq!(status, 200, "{}", body);
+    }
+    // Sin un proxy de confianza delante, X-Forwarded-For lo pone el cliente
+    let (status, body) = send(&app, reservar("198.51.100.3", "17:00")).await;
+    assert_eq!(status, 429, "{}", body);
+
+    let violacion = db.repo.widget_violaciones().find_one(mongodb::bson::doc! {}).await.unwrap().unwrap();
+    assert_eq!(violacion.dispositivo, "ip:10.0.0.2");
+}
@@ -0,0 +1,65 @@
+//! Orígenes autorizados del widget contra un MongoDB efímero
+//!
+//! Ver `tests/common/mod.rs` para los requisitos de ejecución.
+
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use common::{bearer, register_restaurant, send, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+#[ignore = "requiere Docker o TEST_MONGODB_URI"]
+async fn widget_only_answers_authorized_origins() {
+    let db = TestDb::start().await;
+    let app = common::init_app(&db).await;
+    let restaurant = register_restaurant(&app, "La Tasca").await;
+    let menu = format!("/public/restaurants/{}/menu-options", restaurant.id);
+
+    // Sin lista, cualquier web
+    let resp = test::call_service(&app, TestRequest::get()
+        .uri(&menu)
+        .insert_header(("Origin", "https://cualquiera.com"))
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("access-control-allow-origin").unwrap(), "https://cualquiera.com");
+
+    let (status, body) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "origenes_widget": ["https://LaTasca.es/", "https://latasca.es"] }))).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(body["configuracion"]["origenes_widget"], json!(["https://latasca.es"]));
+
+    let (status, _) = send(&app, bearer(TestRequest::put(), &restaurant.token)
+        .uri("/restaurants/settings")
+        .set_json(json!({ "origenes_widget": ["https://latasca.es/reservas"] }))).await;
+    assert_eq!(status, 400, "un origen no lleva ruta");
+
+    let (status, body) = send(&app, TestRequest::get()
+        .uri(&menu)
+        .insert_header(("Origin", "https://cualquiera.com"))).await;
+    assert_eq!(status, 403);
+    assert_eq!(body["codigo"], "origen_no_autorizado");
+
+    let resp = test::call_service(&app, TestRequest::get()
+        .uri(&menu)
+        .insert_header(("Origin", "https://latasca.es"))
+        .to_request()).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers().get("access-control-allow-origin").unwrap(), "https://latasca.es");
+    assert_eq!(resp.headers().get("content-security-policy").unwrap(), "frame-ancestors https://latasca.es");
+
+    // Preflight del navegador antes de crear una reserva
+    let resp = test::call_service(&app, TestRequest::default()
+        .method(actix_web::http::Method::OPTIONS)
+        .uri(&format!("/public/restaurants/{}/reservations", restaurant.id))
+        .insert_header(("Origin", "https://latasca.es"))
+        .insert_header(("Access-Control-Request-Method", "POST"))
+        .to_request()).await;
+    assert_eq!(resp.status(), 204);
+    assert!(resp.headers().get("access-control-allow-headers").is_some());
+
+    // Sin Origin (servidor a servidor) no se bloquea
+    let (status, _) = send(&app, TestRequest::get().uri(&menu)).await;
+    assert_eq!(status, 200);
+}